@@ -0,0 +1,125 @@
+//! Per-sample hot path: Kalman filtering, MPU6500 processing and payload
+//! encoding.  Run with `cargo bench`; at 1 kHz a sample has 1 ms to get
+//! through all three.
+
+use std::hint::black_box;
+
+use anyhow::Result;
+use criterion::{Criterion, criterion_group, criterion_main};
+
+use sensors_to_mqtt::config::{ConnectionConfig, I2cConnectionConfig, SensorConfig};
+use sensors_to_mqtt::filters::kalman_1d::KalmanFilter1D;
+use sensors_to_mqtt::payload::{Json, PayloadSerializer, Reading};
+use sensors_to_mqtt::sensors::Sensor;
+use sensors_to_mqtt::sensors::i2c::mpu6500::{MPU6500, Variant};
+use sensors_to_mqtt::service::{PayloadKeys, channel_payloads};
+use sensors_to_mqtt::transport::I2cBus;
+
+/// MPU6500 that answers `WHO_AM_I` and otherwise returns a slowly
+/// wobbling level frame, so the filters have something to track.
+struct FakeImu {
+    step: i16,
+}
+
+impl I2cBus for FakeImu {
+    fn write(&mut self, _addr: u8, _data: &[u8]) -> Result<()> {
+        Ok(())
+    }
+
+    fn read(&mut self, _addr: u8, buf: &mut [u8]) -> Result<()> {
+        buf.fill(0);
+        Ok(())
+    }
+
+    fn write_read(&mut self, _addr: u8, write: &[u8], read: &mut [u8]) -> Result<()> {
+        if write == [0x75] {
+            read[0] = 0x70;
+            return Ok(());
+        }
+        self.step = (self.step + 1) % 64;
+        let wobble = (self.step - 32) * 8;
+        // accel x/y/z, temperature, gyro x/y/z — big-endian
+        let words = [wobble, -wobble, 16384 + wobble, 0, wobble, 0, -wobble];
+        for (chunk, w) in read.chunks_mut(2).zip(words) {
+            chunk.copy_from_slice(&w.to_be_bytes()[..chunk.len()]);
+        }
+        Ok(())
+    }
+}
+
+fn imu() -> MPU6500 {
+    let cfg = SensorConfig {
+        name: "Bench IMU".to_string(),
+        enabled: true,
+        required: true,
+        init_retries: 0,
+        priority: 0,
+        group: None,
+        topic_group: None,
+        tags: Default::default(),
+        channel_map: Default::default(),
+        driver: "mpu6500".to_string(),
+        connection: ConnectionConfig::I2c(I2cConnectionConfig {
+            device: "/dev/null".to_string(),
+            address: 0x68,
+            bus: None,
+        }),
+        settings: None,
+    };
+    MPU6500::with_bus(&cfg, Variant::Mpu6500, Box::new(FakeImu { step: 0 }), 0x68)
+        .expect("fake IMU initialises")
+}
+
+fn kalman(c: &mut Criterion) {
+    let mut filter = KalmanFilter1D::new(0.1, 0.1);
+    let mut x = 0.0;
+    c.bench_function("kalman_update", |b| {
+        b.iter(|| {
+            x = if x > 1.0 { 0.0 } else { x + 0.01 };
+            black_box(filter.update(black_box(x)))
+        })
+    });
+}
+
+fn mpu6500_read(c: &mut Criterion) {
+    let mut sensor = imu();
+    c.bench_function("mpu6500_read", |b| {
+        b.iter(|| black_box(sensor.read().unwrap()))
+    });
+}
+
+fn payloads(c: &mut Criterion) {
+    let data = imu().read().unwrap();
+    let ts = data.timestamp.to_rfc3339();
+    let keys = PayloadKeys::default();
+    c.bench_function("channel_payloads", |b| {
+        b.iter(|| black_box(channel_payloads(&data, &ts, &keys, None)))
+    });
+    c.bench_function("channel_payloads_precision_3", |b| {
+        b.iter(|| black_box(channel_payloads(&data, &ts, &keys, Some(3))))
+    });
+
+    // What the sensor task does: encode into a buffer it keeps
+    let fields: Vec<&str> = data.fields.keys().map(|k| &**k).collect();
+    let reading = Reading {
+        sensor: "Bench IMU",
+        tags: &Default::default(),
+        timestamp: &ts,
+        schema_version: Some(1),
+        data: &data,
+        keys: &fields,
+        values: None,
+        precision: Some(3),
+    };
+    let mut buf = Vec::new();
+    c.bench_function("json_encode_into_reused", |b| {
+        b.iter(|| {
+            buf.clear();
+            Json.encode_into(&reading, &mut buf);
+            black_box(buf.len())
+        })
+    });
+}
+
+criterion_group!(benches, kalman, mpu6500_read, payloads);
+criterion_main!(benches);
@@ -0,0 +1,117 @@
+//! Adaptive sample rate: slow down while the vehicle is standing still.
+//!
+//! One IMU's acceleration (gravity removed) and rotation rate magnitudes are
+//! compared with `[adaptive_rate]` thresholds.  When both stay below them
+//! for `idle_after_secs` — parked, or waiting in traffic — every sensor task
+//! drops to `idle_interval_ms`.  The first reading above either threshold
+//! restores the full rate, so the start of a manoeuvre is never missed by
+//! more than one idle interval.
+
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+
+use crate::config::AdaptiveRateConfig;
+use crate::sensors::SensorData;
+
+pub struct ActivityMonitor {
+    cfg: AdaptiveRateConfig,
+    idle: bool,
+    /// Start of the current run of readings below both thresholds
+    quiet_since: Option<DateTime<Utc>>,
+}
+
+impl ActivityMonitor {
+    pub fn new(cfg: AdaptiveRateConfig) -> Self {
+        Self {
+            cfg,
+            idle: false,
+            quiet_since: None,
+        }
+    }
+
+    pub fn idle_interval(&self) -> Duration {
+        Duration::from_millis(self.cfg.idle_interval_ms)
+    }
+
+    /// Feed one reading; returns `Some(idle)` when the rate should change.
+    /// A reading missing either field counts as motion.
+    pub fn update(&mut self, data: &SensorData) -> Option<bool> {
+        let below =
+            |key: &str, threshold: f64| data.fields.get(key).is_some_and(|v| v.abs() < threshold);
+        let quiet = below(&self.cfg.g_field, self.cfg.g_threshold)
+            && below(&self.cfg.gyro_field, self.cfg.gyro_threshold);
+
+        if !quiet {
+            self.quiet_since = None;
+            return std::mem::take(&mut self.idle).then_some(false);
+        }
+        if self.idle {
+            return None;
+        }
+        let since = *self.quiet_since.get_or_insert(data.timestamp);
+        let held = (data.timestamp - since).num_milliseconds() as f64 / 1000.0;
+        if held < self.cfg.idle_after_secs {
+            return None;
+        }
+        self.idle = true;
+        Some(true)
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Unit tests
+// ---------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn motion(t0: DateTime<Utc>, secs: i64, g: f64, gyro: f64) -> SensorData {
+        let mut d = SensorData::new(
+            [
+                ("combined_g".into(), g),
+                ("angular_velocity".into(), gyro),
+            ]
+            .into_iter()
+            .collect(),
+        );
+        d.timestamp = t0 + chrono::Duration::seconds(secs);
+        d
+    }
+
+    #[test]
+    fn test_idles_after_quiet_period_and_wakes_on_motion() {
+        let mut m = ActivityMonitor::new(AdaptiveRateConfig {
+            enabled: true,
+            ..Default::default()
+        });
+        let t0 = Utc::now();
+        assert_eq!(m.update(&motion(t0, 0, 0.01, 0.5)), None);
+        assert_eq!(m.update(&motion(t0, 20, 0.02, 1.0)), None);
+        assert_eq!(m.update(&motion(t0, 30, 0.01, 0.5)), Some(true));
+        assert_eq!(m.update(&motion(t0, 40, 0.01, 0.5)), None);
+
+        // Turning the wheel while stopped is motion too
+        assert_eq!(m.update(&motion(t0, 41, 0.01, 8.0)), Some(false));
+        assert_eq!(m.update(&motion(t0, 42, 0.3, 0.5)), None);
+
+        // The quiet period restarts after every movement
+        assert_eq!(m.update(&motion(t0, 50, 0.01, 0.5)), None);
+        assert_eq!(m.update(&motion(t0, 70, 0.01, 0.5)), None);
+        assert_eq!(m.update(&motion(t0, 80, 0.01, 0.5)), Some(true));
+    }
+
+    #[test]
+    fn test_missing_fields_count_as_motion() {
+        let mut m = ActivityMonitor::new(AdaptiveRateConfig {
+            enabled: true,
+            idle_after_secs: 0.0,
+            ..Default::default()
+        });
+        let mut d = SensorData::new(Default::default());
+        d.timestamp = Utc::now();
+        assert_eq!(m.update(&d), None);
+        assert_eq!(m.update(&motion(d.timestamp, 1, 0.0, 0.0)), Some(true));
+    }
+}
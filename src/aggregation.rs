@@ -0,0 +1,235 @@
+//! Time-window rollups of published channels (`[mqtt.aggregation]`).
+//!
+//! Long-term storage rarely wants every reading.  For each configured
+//! window length this keeps the count, minimum, maximum, mean and standard
+//! deviation of every aggregated channel, and hands back a [`Rollup`] when
+//! a window ends, which the service publishes on `AGG/<n>S` next to the raw
+//! `FILTERED` / `DERIVED` stream.
+//!
+//! Windows are aligned to the clock (a 10 s window covers :00–:10, :10–:20
+//! …) by reading timestamp, so rollups of different sensors line up.  A
+//! window ends with the first reading of a later one; windows without
+//! readings are skipped, and the window in progress when the sensor stops
+//! is dropped.  Readings flagged `invalid` and non-finite values are left
+//! out.
+
+use chrono::{DateTime, Utc};
+use indexmap::IndexMap;
+use serde::Serialize;
+
+use crate::config::{AggregationConfig, ChannelFilter};
+use crate::sensors::{Quality, SensorData};
+
+/// One channel over one window.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct ChannelStats {
+    pub samples: u64,
+    pub min: f64,
+    pub max: f64,
+    pub mean: f64,
+    /// Population standard deviation
+    pub stddev: f64,
+}
+
+/// The channels of one completed window.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Rollup {
+    pub window_secs: u64,
+    /// Start of the window
+    pub start: DateTime<Utc>,
+    pub channels: IndexMap<String, ChannelStats>,
+}
+
+/// Running statistics (Welford), so a long window needs no buffer.
+#[derive(Debug, Clone, Copy)]
+struct Running {
+    n: u64,
+    min: f64,
+    max: f64,
+    mean: f64,
+    m2: f64,
+}
+
+impl Running {
+    fn new(v: f64) -> Self {
+        Self {
+            n: 1,
+            min: v,
+            max: v,
+            mean: v,
+            m2: 0.0,
+        }
+    }
+
+    fn push(&mut self, v: f64) {
+        self.n += 1;
+        self.min = self.min.min(v);
+        self.max = self.max.max(v);
+        let delta = v - self.mean;
+        self.mean += delta / self.n as f64;
+        self.m2 += delta * (v - self.mean);
+    }
+
+    fn stats(&self) -> ChannelStats {
+        ChannelStats {
+            samples: self.n,
+            min: self.min,
+            max: self.max,
+            mean: self.mean,
+            stddev: (self.m2 / self.n as f64).sqrt(),
+        }
+    }
+}
+
+struct Window {
+    secs: u64,
+    /// Index of the window in progress since the epoch
+    index: Option<i64>,
+    channels: IndexMap<String, Running>,
+}
+
+impl Window {
+    fn finish(&mut self) -> Option<Rollup> {
+        let index = self.index?;
+        if self.channels.is_empty() {
+            return None;
+        }
+        let start = DateTime::from_timestamp(index * self.secs as i64, 0)?;
+        Some(Rollup {
+            window_secs: self.secs,
+            start,
+            channels: self
+                .channels
+                .drain(..)
+                .map(|(k, r)| (k, r.stats()))
+                .collect(),
+        })
+    }
+}
+
+/// The rollups of one sensor.
+pub struct Aggregator {
+    windows: Vec<Window>,
+    channels: ChannelFilter,
+}
+
+impl Aggregator {
+    pub fn new(cfg: &AggregationConfig) -> Self {
+        Self {
+            windows: cfg
+                .windows_secs
+                .iter()
+                .filter(|&&secs| secs > 0)
+                .map(|&secs| Window {
+                    secs,
+                    index: None,
+                    channels: IndexMap::new(),
+                })
+                .collect(),
+            channels: cfg.channels.clone(),
+        }
+    }
+
+    /// Add `keys` of `data`, a reading as published, and return the windows
+    /// it ended.
+    pub fn process(&mut self, data: &SensorData, keys: &[&str]) -> Vec<Rollup> {
+        let ms = data.timestamp.timestamp_millis();
+        let mut done = Vec::new();
+        for w in &mut self.windows {
+            let index = ms.div_euclid(w.secs as i64 * 1000);
+            if w.index != Some(index) {
+                done.extend(w.finish());
+                w.index = Some(index);
+            }
+            for &key in keys {
+                if !self.channels.allows(key) || data.quality(key) == Quality::Invalid {
+                    continue;
+                }
+                let Some(&v) = data.fields.get(key).filter(|v| v.is_finite()) else {
+                    continue;
+                };
+                match w.channels.get_mut(key) {
+                    Some(r) => r.push(v),
+                    None => {
+                        w.channels.insert(key.to_string(), Running::new(v));
+                    }
+                }
+            }
+        }
+        done
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Unit tests
+// ---------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn reading(ms: i64, x: f64) -> SensorData {
+        let mut data = SensorData::new([("x".into(), x)].into_iter().collect());
+        data.timestamp = DateTime::from_timestamp_millis(ms).unwrap();
+        data
+    }
+
+    #[test]
+    fn test_rollups_per_window() {
+        let cfg = AggregationConfig {
+            enabled: true,
+            windows_secs: vec![1, 10],
+            ..Default::default()
+        };
+        let mut agg = Aggregator::new(&cfg);
+        let base = 1_700_000_000_000;
+
+        for (i, x) in [2.0, 4.0, 4.0, 4.0, 5.0, 5.0, 7.0, 9.0]
+            .into_iter()
+            .enumerate()
+        {
+            assert!(
+                agg.process(&reading(base + i as i64 * 100, x), &["x"])
+                    .is_empty()
+            );
+        }
+        let mut invalid = reading(base + 900, 1000.0);
+        invalid.set_quality("x", Quality::Invalid);
+        assert!(agg.process(&invalid, &["x"]).is_empty());
+
+        // The next second ends the 1 s window but not the 10 s one
+        let done = agg.process(&reading(base + 1000, 0.0), &["x"]);
+        assert_eq!(done.len(), 1);
+        assert_eq!(done[0].window_secs, 1);
+        assert_eq!(done[0].start.timestamp_millis(), base);
+        let x = done[0].channels["x"];
+        assert_eq!((x.samples, x.min, x.max), (8, 2.0, 9.0));
+        assert!((x.mean - 5.0).abs() < 1e-12);
+        assert!((x.stddev - 2.0).abs() < 1e-12);
+
+        // After a gap both end; the empty windows between are skipped
+        let done = agg.process(&reading(base + 25_000, 0.0), &["x"]);
+        assert_eq!(done.len(), 2);
+        assert_eq!(done[0].channels["x"].samples, 1);
+        assert_eq!(done[1].window_secs, 10);
+        assert_eq!(done[1].channels["x"].samples, 9);
+    }
+
+    #[test]
+    fn test_channel_filter() {
+        let cfg = AggregationConfig {
+            enabled: true,
+            windows_secs: vec![1],
+            channels: ChannelFilter {
+                include: vec![],
+                exclude: vec!["y".into()],
+            },
+        };
+        let mut agg = Aggregator::new(&cfg);
+        let mut data = reading(0, 1.0);
+        data.fields.insert("y".into(), 2.0);
+        agg.process(&data, &["x", "y", "missing"]);
+        let done = agg.process(&reading(1000, 1.0), &["x"]);
+        assert_eq!(done[0].channels.keys().collect::<Vec<_>>(), ["x"]);
+    }
+}
@@ -0,0 +1,171 @@
+//! Threshold alerts (`[[alerts]]`).
+//!
+//! Each entry watches one of a sensor's channels and fires when it goes
+//! above or below a limit, such as coolant temperature or oil pressure.
+//! A fired alert is kept in `AppState::alerts`; the TUI flashes it in the
+//! status bar (and on the sensor's tab) until it is acknowledged with `a`,
+//! even if the channel has come back within its limits by then, so a short
+//! excursion isn't missed while watching the charts.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+
+use crate::config::AlertConfig;
+use crate::sensors::SensorData;
+
+/// Ids of fired alerts, unique across sensor tasks.
+static NEXT_ID: AtomicU64 = AtomicU64::new(1);
+
+/// An alert that has fired, as shown in the TUI.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Alert {
+    pub id: u64,
+    pub sensor: String,
+    /// What crossed which limit, e.g. `Coolant hot: 112.4 above 110`
+    pub text: String,
+    pub since: DateTime<Local>,
+    /// Still outside its limits
+    pub active: bool,
+    pub acknowledged: bool,
+}
+
+impl Alert {
+    /// A newly fired alert with the next free id.
+    pub fn new(sensor: &str, text: String) -> Self {
+        Self {
+            id: NEXT_ID.fetch_add(1, Ordering::Relaxed),
+            sensor: sensor.to_string(),
+            text,
+            since: Local::now(),
+            active: true,
+            acknowledged: false,
+        }
+    }
+}
+
+/// A change in one alert's state.
+#[derive(Debug, Clone, PartialEq)]
+pub enum AlertChange {
+    Fired(Alert),
+    Cleared { id: u64 },
+}
+
+/// The `[[alerts]]` of one sensor.
+pub struct AlertMonitor {
+    rules: Vec<Rule>,
+}
+
+struct Rule {
+    cfg: AlertConfig,
+    /// Id of the alert while it is firing
+    firing: Option<u64>,
+}
+
+impl AlertMonitor {
+    pub fn new(cfgs: Vec<AlertConfig>) -> Self {
+        Self {
+            rules: cfgs
+                .into_iter()
+                .map(|cfg| Rule { cfg, firing: None })
+                .collect(),
+        }
+    }
+
+    /// Compare a reading with every limit.  Readings without the channel
+    /// leave its alert as it was.
+    pub fn process(&mut self, data: &SensorData) -> Vec<AlertChange> {
+        let mut changes = Vec::new();
+        for rule in &mut self.rules {
+            let c = &rule.cfg;
+            let Some(&v) = data.fields.get(c.field.as_str()) else {
+                continue;
+            };
+            match rule.firing {
+                None => {
+                    let crossed = match (c.above, c.below) {
+                        (Some(hi), _) if v > hi => Some(("above", hi)),
+                        (_, Some(lo)) if v < lo => Some(("below", lo)),
+                        _ => None,
+                    };
+                    if let Some((side, limit)) = crossed {
+                        let alert = Alert::new(
+                            &c.sensor,
+                            format!("{}: {:.2} {} {}", label(c), v, side, limit),
+                        );
+                        rule.firing = Some(alert.id);
+                        changes.push(AlertChange::Fired(alert));
+                    }
+                }
+                Some(id) => {
+                    let h = c.hysteresis;
+                    let back = c.above.is_none_or(|hi| v <= hi - h)
+                        && c.below.is_none_or(|lo| v >= lo + h);
+                    if back {
+                        rule.firing = None;
+                        changes.push(AlertChange::Cleared { id });
+                    }
+                }
+            }
+        }
+        changes
+    }
+}
+
+fn label(c: &AlertConfig) -> String {
+    if c.message.is_empty() {
+        format!("{} {}", c.sensor, c.field)
+    } else {
+        c.message.clone()
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Unit tests
+// ---------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn reading(v: f64) -> SensorData {
+        SensorData::new([("coolant".into(), v)].into_iter().collect())
+    }
+
+    #[test]
+    fn test_fires_once_and_clears_with_hysteresis() {
+        let mut m = AlertMonitor::new(vec![AlertConfig {
+            sensor: "engine".into(),
+            field: "coolant".into(),
+            above: Some(110.0),
+            below: Some(40.0),
+            hysteresis: 5.0,
+            message: "Coolant".into(),
+        }]);
+        assert!(m.process(&reading(90.0)).is_empty());
+
+        let fired = m.process(&reading(112.5));
+        let [AlertChange::Fired(alert)] = fired.as_slice() else {
+            panic!("expected one alert, got {:?}", fired);
+        };
+        assert_eq!(alert.text, "Coolant: 112.50 above 110");
+        assert!(alert.active && !alert.acknowledged);
+
+        // Still firing, and back under the limit but within the hysteresis
+        assert!(m.process(&reading(115.0)).is_empty());
+        assert!(m.process(&reading(107.0)).is_empty());
+        assert!(m.process(&SensorData::new(Default::default())).is_empty());
+        assert_eq!(
+            m.process(&reading(104.0)),
+            [AlertChange::Cleared { id: alert.id }]
+        );
+
+        let fired = m.process(&reading(30.0));
+        let [AlertChange::Fired(low)] = fired.as_slice() else {
+            panic!("expected one alert, got {:?}", fired);
+        };
+        assert_ne!(low.id, alert.id);
+        assert!(low.text.ends_with("below 40"), "{}", low.text);
+    }
+}
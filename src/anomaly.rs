@@ -0,0 +1,272 @@
+//! Anomaly scores (`[[anomalies]]`).
+//!
+//! Each entry keeps an exponentially weighted baseline of one of a sensor's
+//! channels, its mean and spread, and publishes how far each reading is
+//! from it:
+//!
+//! ```text
+//! score = |value − mean| / max(spread, min_spread)
+//! ```
+//!
+//! The weights halve every `half_life_secs`, timed by the readings'
+//! timestamps.  A score above `threshold` raises an alert, as `[[alerts]]`
+//! do, which clears once the score is back under half of it.  A channel
+//! that stops changing for `stuck_secs` raises one too: a dying sensor
+//! tends to either spike or freeze.
+
+use chrono::{DateTime, Utc};
+
+use crate::alerts::{Alert, AlertChange};
+use crate::config::AnomalyConfig;
+use crate::sensors::{FieldDescriptor, Quality, SensorData, VizType};
+
+/// Scores are capped here, so a channel with no spread yet doesn't publish
+/// infinity.
+const MAX_SCORE: f64 = 1000.0;
+
+/// Descriptors for the channels `cfgs` add.  Keys and labels come from the
+/// config, so they are leaked once per call (at sensor start).
+pub fn field_descriptors(cfgs: &[AnomalyConfig]) -> Vec<FieldDescriptor> {
+    let leak = |s: String| -> &'static str { Box::leak(s.into_boxed_str()) };
+    cfgs.iter()
+        .enumerate()
+        .map(|(i, a)| FieldDescriptor {
+            key: leak(a.name.clone()),
+            label: leak(format!("{} anomaly", a.field)),
+            viz: VizType::Numeric { unit: "σ" },
+            range: None,
+            group: (i == 0).then_some("ANOMALY"),
+        })
+        .collect()
+}
+
+/// The `[[anomalies]]` of one sensor.
+pub struct AnomalyMonitor {
+    channels: Vec<Channel>,
+}
+
+struct Channel {
+    cfg: AnomalyConfig,
+    baseline: Option<Baseline>,
+    /// Value the channel has held and since when, for `stuck_secs`
+    unchanged: Option<(f64, DateTime<Utc>)>,
+    /// Ids of the alerts while they are firing
+    spike: Option<u64>,
+    stuck: Option<u64>,
+}
+
+struct Baseline {
+    mean: f64,
+    var: f64,
+    started: DateTime<Utc>,
+    last: DateTime<Utc>,
+}
+
+impl AnomalyMonitor {
+    pub fn new(cfgs: Vec<AnomalyConfig>) -> Self {
+        Self {
+            channels: cfgs
+                .into_iter()
+                .map(|cfg| Channel {
+                    cfg,
+                    baseline: None,
+                    unchanged: None,
+                    spike: None,
+                    stuck: None,
+                })
+                .collect(),
+        }
+    }
+
+    /// Append each configured score to `data` and return the alerts raised
+    /// or cleared.  Readings without the source channel get no score; for
+    /// the first half-life the baseline is still forming, so the score is
+    /// 0, flagged invalid, and raises nothing.
+    pub fn process(&mut self, data: &mut SensorData) -> Vec<AlertChange> {
+        let mut changes = Vec::new();
+        for ch in &mut self.channels {
+            let Some(&value) = data.fields.get(ch.cfg.field.as_str()) else {
+                continue;
+            };
+            let score = ch.score(value, data.timestamp);
+            ch.check_spike(score, value, &mut changes);
+            ch.check_stuck(value, data.timestamp, &mut changes);
+
+            let key = ch.cfg.name.as_str();
+            data.fields.insert(key.to_owned().into(), score.unwrap_or(0.0));
+            if score.is_none() {
+                data.set_quality(key, Quality::Invalid);
+            }
+        }
+        changes
+    }
+}
+
+impl Channel {
+    /// Score `value` against the baseline so far, then fold it in.
+    fn score(&mut self, value: f64, now: DateTime<Utc>) -> Option<f64> {
+        // A clock step backwards leaves nothing to weigh against
+        let b = match &mut self.baseline {
+            Some(b) if b.last < now => b,
+            _ => {
+                self.baseline = Some(Baseline {
+                    mean: value,
+                    var: 0.0,
+                    started: now,
+                    last: now,
+                });
+                return None;
+            }
+        };
+        let spread = b.var.sqrt().max(self.cfg.min_spread);
+        let diff = value - b.mean;
+        let warm = secs(b.started, now) >= self.cfg.half_life_secs;
+        let score = match diff.abs() {
+            0.0 => 0.0,
+            d if spread > 0.0 => (d / spread).min(MAX_SCORE),
+            _ => MAX_SCORE,
+        };
+
+        let alpha = 1.0 - 0.5f64.powf(secs(b.last, now) / self.cfg.half_life_secs);
+        let step = alpha * diff;
+        b.mean += step;
+        b.var = (1.0 - alpha) * (b.var + diff * step);
+        b.last = now;
+        warm.then_some(score)
+    }
+
+    fn check_spike(&mut self, score: Option<f64>, value: f64, changes: &mut Vec<AlertChange>) {
+        let threshold = self.cfg.threshold;
+        match (self.spike, score) {
+            (None, Some(s)) if threshold > 0.0 && s > threshold => {
+                let alert = Alert::new(
+                    &self.cfg.sensor,
+                    format!(
+                        "{} {} anomaly: {:.2} is {:.1}σ off its baseline",
+                        self.cfg.sensor, self.cfg.field, value, s
+                    ),
+                );
+                self.spike = Some(alert.id);
+                changes.push(AlertChange::Fired(alert));
+            }
+            (Some(id), s) if s.is_none_or(|s| s < threshold / 2.0) => {
+                self.spike = None;
+                changes.push(AlertChange::Cleared { id });
+            }
+            _ => {}
+        }
+    }
+
+    fn check_stuck(&mut self, value: f64, now: DateTime<Utc>, changes: &mut Vec<AlertChange>) {
+        let since = match self.unchanged {
+            Some((v, since)) if v == value && since <= now => since,
+            _ => {
+                self.unchanged = Some((value, now));
+                if let Some(id) = self.stuck.take() {
+                    changes.push(AlertChange::Cleared { id });
+                }
+                return;
+            }
+        };
+        let stuck_secs = self.cfg.stuck_secs;
+        if self.stuck.is_none() && stuck_secs > 0.0 && secs(since, now) >= stuck_secs {
+            let alert = Alert::new(
+                &self.cfg.sensor,
+                format!(
+                    "{} {} stuck at {:.2} for {:.0} s",
+                    self.cfg.sensor, self.cfg.field, value, stuck_secs
+                ),
+            );
+            self.stuck = Some(alert.id);
+            changes.push(AlertChange::Fired(alert));
+        }
+    }
+}
+
+fn secs(from: DateTime<Utc>, to: DateTime<Utc>) -> f64 {
+    (to - from).num_microseconds().unwrap_or(i64::MAX) as f64 / 1e6
+}
+
+// ---------------------------------------------------------------------------
+// Unit tests
+// ---------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn monitor(toml: &str) -> AnomalyMonitor {
+        let cfg = format!(
+            "sensor = \"cabin\"\nfield = \"temp\"\nname = \"temp_anomaly\"\n{}",
+            toml
+        );
+        AnomalyMonitor::new(vec![toml::from_str(&cfg).unwrap()])
+    }
+
+    fn reading(secs: f64, v: f64) -> SensorData {
+        let t0 = DateTime::from_timestamp(1_700_000_000, 0).unwrap();
+        let mut data = SensorData::new([("temp".into(), v)].into_iter().collect());
+        data.timestamp = t0 + chrono::Duration::microseconds((secs * 1e6) as i64);
+        data
+    }
+
+    #[test]
+    fn test_spike_scores_and_alerts_after_warmup() {
+        let mut m = monitor("half_life_secs = 5.0\nthreshold = 6.0");
+        // 20 ± 0.5, alternating, once a second
+        let noise = |i: usize| if i.is_multiple_of(2) { 20.5 } else { 19.5 };
+        for i in 0..5 {
+            let mut d = reading(i as f64, noise(i));
+            assert!(m.process(&mut d).is_empty());
+            assert_eq!(d.quality("temp_anomaly"), Quality::Invalid, "warming up");
+        }
+        for i in 5..30 {
+            let mut d = reading(i as f64, noise(i));
+            assert!(m.process(&mut d).is_empty());
+            assert!(
+                d.fields["temp_anomaly"] < 3.0,
+                "{}",
+                d.fields["temp_anomaly"]
+            );
+        }
+
+        let mut d = reading(30.0, 45.0);
+        let changes = m.process(&mut d);
+        let [AlertChange::Fired(alert)] = changes.as_slice() else {
+            panic!("expected one alert, got {:?}", changes);
+        };
+        assert!(d.fields["temp_anomaly"] > 6.0);
+        assert!(
+            alert.text.starts_with("cabin temp anomaly: 45.00 is"),
+            "{}",
+            alert.text
+        );
+
+        // Back to normal clears it
+        let mut d = reading(31.0, 20.0);
+        assert_eq!(m.process(&mut d), [AlertChange::Cleared { id: alert.id }]);
+    }
+
+    #[test]
+    fn test_stuck_channel_raises_an_alert() {
+        let mut m = monitor("stuck_secs = 10.0\nthreshold = 0.0");
+        for i in 0..10 {
+            assert!(m.process(&mut reading(i as f64, 21.0)).is_empty());
+        }
+        let changes = m.process(&mut reading(10.0, 21.0));
+        let [AlertChange::Fired(alert)] = changes.as_slice() else {
+            panic!("expected one alert, got {:?}", changes);
+        };
+        assert_eq!(alert.text, "cabin temp stuck at 21.00 for 10 s");
+        assert!(m.process(&mut reading(11.0, 21.0)).is_empty());
+        assert_eq!(
+            m.process(&mut reading(12.0, 21.1)),
+            [AlertChange::Cleared { id: alert.id }]
+        );
+
+        // Readings without the channel are left alone
+        let mut d = SensorData::default();
+        assert!(m.process(&mut d).is_empty());
+        assert!(!d.fields.contains_key("temp_anomaly"));
+    }
+}
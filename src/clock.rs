@@ -0,0 +1,183 @@
+//! Timestamps and wall-clock sync health.
+//!
+//! Readings carry two times: `SensorData::timestamp` from the wall clock,
+//! which is what consumers line up across devices, and
+//! `SensorData::monotonic`, seconds since the daemon started, which never
+//! jumps.  A Pi without an RTC boots with a stale wall clock and steps it
+//! once NTP catches up, so readings from before that are mis-dated by
+//! minutes or years; comparing the two times recovers the true spacing.
+//!
+//! A background task asks the time daemon whether the wall clock is
+//! synchronised (`[clock] sync_check`) and keeps `AppState::clock_synced`
+//! current; readings taken while it isn't are flagged `clock_unsynced`.
+//! Each time daemon is a [`SyncProbe`], so another one (gpsd, PTP) only
+//! needs a new implementation.
+
+use std::process::Command;
+use std::sync::OnceLock;
+use std::time::Instant;
+
+use anyhow::{Context, Result, bail};
+use tokio_util::sync::CancellationToken;
+use tracing::{info, warn};
+
+use crate::config::{ClockConfig, SyncCheck};
+use crate::models::SharedState;
+
+/// Seconds on the monotonic clock since the first call in this process.
+pub fn monotonic_secs() -> f64 {
+    static START: OnceLock<Instant> = OnceLock::new();
+    START.get_or_init(Instant::now).elapsed().as_secs_f64()
+}
+
+/// Asks a time daemon whether the system clock is synchronised.
+pub trait SyncProbe: Send + Sync {
+    fn name(&self) -> &'static str;
+    fn synced(&self) -> Result<bool>;
+}
+
+/// `chronyc -c tracking`: synchronised unless the leap status says not.
+pub struct Chrony;
+
+impl SyncProbe for Chrony {
+    fn name(&self) -> &'static str {
+        "chrony"
+    }
+
+    fn synced(&self) -> Result<bool> {
+        let out = run("chronyc", &["-c", "tracking"])?;
+        parse_chrony_tracking(&out).context("unexpected chronyc output")
+    }
+}
+
+/// `timedatectl show`: systemd's `NTPSynchronized` property, set by
+/// whichever NTP client is running.
+pub struct Timedatectl;
+
+impl SyncProbe for Timedatectl {
+    fn name(&self) -> &'static str {
+        "timedatectl"
+    }
+
+    fn synced(&self) -> Result<bool> {
+        let out = run("timedatectl", &["show", "-p", "NTPSynchronized", "--value"])?;
+        match out.trim() {
+            "yes" => Ok(true),
+            "no" => Ok(false),
+            other => bail!("unexpected timedatectl output {:?}", other),
+        }
+    }
+}
+
+fn run(program: &str, args: &[&str]) -> Result<String> {
+    let out = Command::new(program)
+        .args(args)
+        .output()
+        .with_context(|| format!("failed to run {}", program))?;
+    if !out.status.success() {
+        bail!("{} exited with {}", program, out.status);
+    }
+    Ok(String::from_utf8_lossy(&out.stdout).into_owned())
+}
+
+/// Leap status is the 14th field of the CSV tracking report.
+fn parse_chrony_tracking(out: &str) -> Option<bool> {
+    let leap = out.trim().split(',').nth(13)?;
+    Some(leap != "Not synchronised")
+}
+
+/// The probe `check` selects; `auto` takes the first one that answers.
+pub fn probe(check: SyncCheck) -> Option<Box<dyn SyncProbe>> {
+    match check {
+        SyncCheck::Off => None,
+        SyncCheck::Chrony => Some(Box::new(Chrony)),
+        SyncCheck::Timedatectl => Some(Box::new(Timedatectl)),
+        SyncCheck::Auto => {
+            let probes: [Box<dyn SyncProbe>; 2] = [Box::new(Chrony), Box::new(Timedatectl)];
+            probes.into_iter().find(|p| p.synced().is_ok())
+        }
+    }
+}
+
+/// Poll the sync status every `check_interval_secs` until `cancel` fires,
+/// posting to the status bar when it changes.
+pub fn spawn_sync_check(cfg: &ClockConfig, state: SharedState, cancel: CancellationToken) {
+    let check = cfg.sync_check;
+    let interval = std::time::Duration::from_secs(cfg.check_interval_secs.max(1));
+    tokio::spawn(async move {
+        let Ok(Some(probe)) = tokio::task::spawn_blocking(move || probe(check)).await else {
+            if check != SyncCheck::Off {
+                warn!("No clock sync status available (tried chrony and timedatectl)");
+            }
+            return;
+        };
+        let probe: std::sync::Arc<dyn SyncProbe> = probe.into();
+        info!("Checking clock sync with {}", probe.name());
+        loop {
+            let result = tokio::task::spawn_blocking({
+                let probe = probe.clone();
+                move || probe.synced()
+            })
+            .await;
+            let synced = match result {
+                Ok(Ok(synced)) => Some(synced),
+                Ok(Err(e)) => {
+                    warn!("Clock sync check with {} failed: {:#}", probe.name(), e);
+                    None
+                }
+                Err(_) => None,
+            };
+            set_synced(&state, synced).await;
+            tokio::select! {
+                _ = cancel.cancelled() => return,
+                _ = tokio::time::sleep(interval) => {}
+            }
+        }
+    });
+}
+
+async fn set_synced(state: &SharedState, synced: Option<bool>) {
+    let mut s = state.write().await;
+    if s.clock_synced == synced {
+        return;
+    }
+    match synced {
+        Some(false) => {
+            warn!("System clock is not synchronised; readings are flagged");
+            s.status.warn("System clock not synchronised");
+        }
+        Some(true) if s.clock_synced == Some(false) => {
+            info!("System clock synchronised");
+            s.status.info("System clock synchronised");
+        }
+        _ => {}
+    }
+    s.clock_synced = synced;
+}
+
+// ---------------------------------------------------------------------------
+// Unit tests
+// ---------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_chrony_tracking() {
+        let synced = "A9FEA97B,169.254.169.123,4,1718000000.123,0.000001,0.000002,\
+                      0.000010,-12.345,0.001,0.020,0.000300,0.000100,64.5,Normal\n";
+        assert_eq!(parse_chrony_tracking(synced), Some(true));
+        let unsynced = "00000000,,0,0.000,0.000000,0.000000,0.000000,0.000,0.000,\
+                        0.000,1.000000,1.000000,0.0,Not synchronised\n";
+        assert_eq!(parse_chrony_tracking(unsynced), Some(false));
+        assert_eq!(parse_chrony_tracking("506 Cannot talk to daemon"), None);
+    }
+
+    #[test]
+    fn test_monotonic_secs_never_goes_back() {
+        let a = monotonic_secs();
+        let b = monotonic_secs();
+        assert!(b >= a && a >= 0.0);
+    }
+}
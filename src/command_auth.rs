@@ -0,0 +1,190 @@
+//! Access control for the MQTT command topics (`[command_auth]`).
+//!
+//! Anyone who can publish to the broker can send commands, so on a shared
+//! broker `allow` limits which command topics are followed, and a `secret`
+//! makes them require a signature.  A signed payload is the command with
+//! the time it was signed and an HMAC-SHA256 in front:
+//!
+//! ```text
+//! <unix time in ms> <hex HMAC> <command>
+//! ```
+//!
+//! The HMAC is of `<topic>\n<time>\n<command>`, so a signed command can't
+//! be replayed on another topic, and each must be newer than the last one
+//! accepted (and, with `max_age_secs`, recent) so it can't be replayed
+//! later.  `sensors-to-mqtt sign` prints a signed payload.
+
+use std::sync::Mutex;
+
+use chrono::Utc;
+use hmac_sha256::HMAC;
+
+use crate::config::CommandAuthConfig;
+
+/// Command topics `allow` can name.
+pub const COMMANDS: &[&str] = &["groups", "config", "publish", "extremes"];
+
+pub struct CommandAuth {
+    allow: Vec<String>,
+    secret: Vec<u8>,
+    max_age_ms: i64,
+    /// Time of the newest signed command accepted, in ms
+    last: Mutex<i64>,
+}
+
+impl CommandAuth {
+    pub fn new(cfg: &CommandAuthConfig) -> Self {
+        Self {
+            allow: cfg.allow.clone(),
+            secret: cfg.secret.as_bytes().to_vec(),
+            max_age_ms: (cfg.max_age_secs as i64).saturating_mul(1000),
+            last: Mutex::new(i64::MIN),
+        }
+    }
+
+    /// The command to act on in `payload`, received on `topic` of the
+    /// `command` kind (one of [`COMMANDS`]), or why it is rejected.
+    pub fn check(&self, command: &str, topic: &str, payload: &[u8]) -> Result<Vec<u8>, String> {
+        self.check_at(command, topic, payload, Utc::now().timestamp_millis())
+    }
+
+    fn check_at(
+        &self,
+        command: &str,
+        topic: &str,
+        payload: &[u8],
+        now_ms: i64,
+    ) -> Result<Vec<u8>, String> {
+        if !self.allow.iter().any(|c| c == command) {
+            return Err(format!("'{}' commands are not allowed", command));
+        }
+        if self.secret.is_empty() {
+            return Ok(payload.to_vec());
+        }
+
+        let mut parts = payload.splitn(3, |&b| b == b' ');
+        let (Some(time), Some(mac), Some(body)) = (parts.next(), parts.next(), parts.next()) else {
+            return Err("not signed".into());
+        };
+        let time: i64 = std::str::from_utf8(time)
+            .ok()
+            .and_then(|t| t.parse().ok())
+            .ok_or("not signed")?;
+        let mac = parse_hex(mac).ok_or("malformed signature")?;
+        if !HMAC::verify(signed_input(topic, time, body), &self.secret, &mac) {
+            return Err("bad signature".into());
+        }
+        if self.max_age_ms > 0 && (now_ms - time).abs() > self.max_age_ms {
+            return Err(format!(
+                "signed {:.1}s from this machine's time",
+                (now_ms - time) as f64 / 1000.0
+            ));
+        }
+        let mut last = self.last.lock().unwrap();
+        if time <= *last {
+            return Err("replayed: not newer than the last command".into());
+        }
+        *last = time;
+        Ok(body.to_vec())
+    }
+}
+
+/// `command` signed with `secret` for publishing to `topic` at `time_ms`.
+pub fn sign(secret: &str, topic: &str, command: &str, time_ms: i64) -> String {
+    let mac = HMAC::mac(signed_input(topic, time_ms, command.as_bytes()), secret);
+    let hex: String = mac.iter().map(|b| format!("{:02x}", b)).collect();
+    format!("{} {} {}", time_ms, hex, command)
+}
+
+fn signed_input(topic: &str, time_ms: i64, body: &[u8]) -> Vec<u8> {
+    let mut input = format!("{}\n{}\n", topic, time_ms).into_bytes();
+    input.extend_from_slice(body);
+    input
+}
+
+fn parse_hex(text: &[u8]) -> Option<[u8; 32]> {
+    if text.len() != 64 {
+        return None;
+    }
+    let mut out = [0u8; 32];
+    for (byte, pair) in out.iter_mut().zip(text.chunks(2)) {
+        *byte = u8::from_str_radix(std::str::from_utf8(pair).ok()?, 16).ok()?;
+    }
+    Some(out)
+}
+
+// ---------------------------------------------------------------------------
+// Unit tests
+// ---------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TOPIC: &str = "/S/GROUPS/engine/SET";
+
+    fn auth(allow: &[&str], secret: &str) -> CommandAuth {
+        CommandAuth::new(&CommandAuthConfig {
+            allow: allow.iter().map(|s| s.to_string()).collect(),
+            secret: secret.into(),
+            max_age_secs: 60,
+        })
+    }
+
+    #[test]
+    fn test_allow_list() {
+        let a = auth(&["groups"], "");
+        assert_eq!(a.check("groups", TOPIC, b"off"), Ok(b"off".to_vec()));
+        let err = a.check("config", "/S/CONFIG/SET", b"{}").unwrap_err();
+        assert!(err.contains("not allowed"), "{}", err);
+    }
+
+    #[test]
+    fn test_signed_commands() {
+        let a = auth(&["groups"], "s3cret");
+        let now = 1_700_000_000_000;
+        let signed = sign("s3cret", TOPIC, "off", now);
+        assert_eq!(
+            a.check_at("groups", TOPIC, signed.as_bytes(), now + 500),
+            Ok(b"off".to_vec())
+        );
+
+        // The same payload again, or on another topic
+        let err = a
+            .check_at("groups", TOPIC, signed.as_bytes(), now + 600)
+            .unwrap_err();
+        assert!(err.starts_with("replayed"), "{}", err);
+        let other = "/S/GROUPS/chassis/SET";
+        let err = a
+            .check_at("groups", other, signed.as_bytes(), now + 600)
+            .unwrap_err();
+        assert_eq!(err, "bad signature");
+
+        for (payload, expected) in [
+            ("off".into(), "not signed"),
+            (sign("guess", TOPIC, "off", now + 1), "bad signature"),
+            (
+                sign("s3cret", TOPIC, "off", now + 1).replace(" off", " on"),
+                "bad signature",
+            ),
+            (format!("{} abcd off", now + 1), "malformed signature"),
+        ] {
+            let err = a
+                .check_at("groups", TOPIC, payload.as_bytes(), now + 1000)
+                .unwrap_err();
+            assert_eq!(err, expected, "{}", payload);
+        }
+
+        let stale = sign("s3cret", TOPIC, "on", now + 1);
+        let err = a
+            .check_at("groups", TOPIC, stale.as_bytes(), now + 120_000)
+            .unwrap_err();
+        assert!(err.contains("from this machine's time"), "{}", err);
+
+        let later = sign("s3cret", TOPIC, "on", now + 2000);
+        assert_eq!(
+            a.check_at("groups", TOPIC, later.as_bytes(), now + 2500),
+            Ok(b"on".to_vec())
+        );
+    }
+}
@@ -6,7 +6,10 @@
 //!   3. Default search paths: ./config.toml, /etc/sensors-to-mqtt/config.toml
 //!   4. Built-in defaults
 
-use anyhow::{Context, Result};
+use anyhow::{Context, Result, bail};
+use indexmap::IndexMap;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
 // Use absolute path to avoid ambiguity with the local `config` module name.
 use ::config::{Config, Environment, File};
 use serde::Deserialize;
@@ -18,60 +21,1627 @@ use serde::Deserialize;
 #[derive(Debug, Deserialize, Clone)]
 #[serde(default)]
 pub struct AppConfig {
+    /// Identifies this installation when several share a broker; fills
+    /// `${device_id}` in the MQTT client id and base topic.  Empty uses the
+    /// hostname.
+    pub device_id: String,
     pub log_level: String,
     pub log_json: bool,
     pub tui_refresh_rate_ms: u64,
+    pub service: ServiceConfig,
+    pub ui: UiConfig,
     pub mqtt: MqttConfig,
+    pub sampling: SamplingConfig,
+    pub dynamics: DynamicsConfig,
+    pub slip: SlipConfig,
+    pub laps: LapsConfig,
+    pub drift: DriftConfig,
+    pub vibration_metrics: VibrationMetricsConfig,
+    pub transforms: Vec<TransformConfig>,
+    pub derivatives: Vec<DerivativeConfig>,
+    pub integrals: Vec<IntegralConfig>,
+    pub differences: Vec<DifferenceConfig>,
+    pub extremes: ExtremesConfig,
+    pub alerts: Vec<AlertConfig>,
+    pub anomalies: Vec<AnomalyConfig>,
+    pub fused_sensors: Vec<FusedSensorConfig>,
+    pub ignition: IgnitionConfig,
+    pub adaptive_rate: AdaptiveRateConfig,
+    pub error_budget: ErrorBudgetConfig,
+    pub stuck_values: StuckValuesConfig,
+    pub clock: ClockConfig,
+    pub self_check: SelfCheckConfig,
+    pub dashboard: DashboardConfig,
+    pub remote_config: RemoteConfig,
+    pub command_auth: CommandAuthConfig,
+    pub i2c_buses: Vec<I2cBusConfig>,
+    pub groups: Vec<SensorGroupConfig>,
     pub sensors: Vec<SensorConfig>,
 }
 
 impl Default for AppConfig {
     fn default() -> Self {
         Self {
+            device_id: String::new(),
             log_level: "info".to_string(),
             log_json: false,
             tui_refresh_rate_ms: 100,
+            service: ServiceConfig::default(),
+            ui: UiConfig::default(),
             mqtt: MqttConfig::default(),
+            sampling: SamplingConfig::default(),
+            dynamics: DynamicsConfig::default(),
+            slip: SlipConfig::default(),
+            laps: LapsConfig::default(),
+            drift: DriftConfig::default(),
+            vibration_metrics: VibrationMetricsConfig::default(),
+            transforms: Vec::new(),
+            derivatives: Vec::new(),
+            integrals: Vec::new(),
+            differences: Vec::new(),
+            extremes: ExtremesConfig::default(),
+            alerts: Vec::new(),
+            anomalies: Vec::new(),
+            fused_sensors: Vec::new(),
+            ignition: IgnitionConfig::default(),
+            adaptive_rate: AdaptiveRateConfig::default(),
+            error_budget: ErrorBudgetConfig::default(),
+            stuck_values: StuckValuesConfig::default(),
+            clock: ClockConfig::default(),
+            self_check: SelfCheckConfig::default(),
+            dashboard: DashboardConfig::default(),
+            remote_config: RemoteConfig::default(),
+            command_auth: CommandAuthConfig::default(),
+            i2c_buses: Vec::new(),
+            groups: Vec::new(),
             sensors: Vec::new(),
         }
     }
 }
 
+// ---------------------------------------------------------------------------
+// Service
+// ---------------------------------------------------------------------------
+
+#[derive(Debug, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct ServiceConfig {
+    /// Interface when `--mode` isn't given.
+    pub run_mode: RunMode,
+}
+
+/// Interface to start (`service.run_mode`, overridden by `--mode`).
+///
+/// `auto` picks the TUI when stdout is a terminal and the daemon otherwise.
+/// Without a terminal the TUI can't start, so `tui` then falls back to the
+/// daemon with a warning.
+#[derive(Debug, Deserialize, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum RunMode {
+    #[default]
+    Auto,
+    Tui,
+    Plain,
+    Daemon,
+}
+
+// ---------------------------------------------------------------------------
+// UI
+// ---------------------------------------------------------------------------
+
+#[derive(Debug, Deserialize, Clone)]
+#[serde(default)]
+pub struct UiConfig {
+    pub theme: ThemeName,
+    /// Directory for CSV history exports (`e` key) and session recordings
+    /// (`R` key); created on demand.
+    pub export_dir: String,
+    /// Delete the oldest recordings and exports once together they pass
+    /// this many megabytes; 0 keeps everything.
+    pub export_max_mb: u64,
+    /// Delete recordings and exports older than this; 0 keeps them.
+    pub export_max_age_days: u64,
+    /// Summary interval for `--mode plain`.
+    pub plain_refresh_secs: u64,
+    /// Unix socket the daemon serves its UI on for `sensors-to-mqtt attach`;
+    /// empty disables it.
+    pub attach_socket: String,
+    /// Selected tab and chart series, restored when the TUI starts and
+    /// saved when it quits; empty disables it.
+    pub view_state_file: String,
+    /// Full scale of the G-meter bars in G; 0 uses the channel's range.
+    pub g_meter_full_scale: f64,
+    /// Ring the terminal bell when an `[[alerts]]` entry fires.
+    pub alert_bell: bool,
+    /// Memory the chart histories of all sensors may use together, in
+    /// megabytes, split evenly between them; 0 is unlimited.
+    pub history_max_mb: u64,
+}
+
+impl Default for UiConfig {
+    fn default() -> Self {
+        Self {
+            theme: ThemeName::default(),
+            export_dir: ".".to_string(),
+            export_max_mb: 0,
+            export_max_age_days: 0,
+            plain_refresh_secs: 2,
+            attach_socket: "/tmp/sensors-to-mqtt.sock".to_string(),
+            view_state_file: "~/.local/state/sensors-to-mqtt/view.toml".to_string(),
+            g_meter_full_scale: 0.0,
+            alert_bell: false,
+            history_max_mb: 64,
+        }
+    }
+}
+
+/// Colour palette used by the TUI.
+///
+/// `mono` renders without any colour and relies on bold / reversed text
+/// for emphasis.  It is also selected automatically when the `NO_COLOR`
+/// environment variable is set.
+#[derive(Debug, Deserialize, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ThemeName {
+    #[default]
+    Dark,
+    Light,
+    HighContrast,
+    Mono,
+}
+
 // ---------------------------------------------------------------------------
 // MQTT
 // ---------------------------------------------------------------------------
 
 #[derive(Debug, Deserialize, Clone)]
 #[serde(default)]
-pub struct MqttConfig {
+pub struct MqttConfig {
+    pub enabled: bool,
+    pub host: String,
+    pub port: u16,
+    pub base_topic: String,
+    pub client_id: String,
+    pub keep_alive_secs: u64,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    /// Reconnect after the broker is lost; otherwise the client gives up
+    /// and stays in the error state
+    pub auto_reconnect: bool,
+    /// First wait before reconnecting, doubled after each failed attempt
+    pub reconnect_min_secs: u64,
+    /// Longest wait between attempts
+    pub reconnect_max_secs: u64,
+    /// Publish INFO with every reading, as older versions did.  By default
+    /// it is retained and only re-sent when a channel's quality changes.
+    pub info_every_reading: bool,
+    /// Round floats in FILTERED / DERIVED payloads to this many decimal
+    /// places.  `None` publishes full precision.
+    pub payload_precision: Option<u32>,
+    /// Encoding of FILTERED / DERIVED; every other topic stays JSON
+    pub payload_format: PayloadFormat,
+    /// Channels kept in FILTERED payloads
+    pub filtered_channels: ChannelFilter,
+    /// Channels kept in DERIVED payloads
+    pub derived_channels: ChannelFilter,
+    /// Also publish each sensor's latest reading, retained, to LATEST this
+    /// often, so a new subscriber gets current values at once; 0 never does
+    pub snapshot_interval_secs: u64,
+    /// Payload layout to publish, named in each payload as
+    /// `schema_version`; 0 publishes the layout from before versioning,
+    /// without the field
+    pub schema_version: u32,
+    /// Per-channel statistics over fixed windows, on `AGG/<n>S`
+    pub aggregation: AggregationConfig,
+    /// Second broker that receives a subset of what is published here
+    pub bridge: MqttBridgeConfig,
+}
+
+impl Default for MqttConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            host: "localhost".to_string(),
+            port: 1883,
+            base_topic: "/SENSORS".to_string(),
+            client_id: "sensors-to-mqtt".to_string(),
+            keep_alive_secs: 20,
+            username: None,
+            password: None,
+            auto_reconnect: true,
+            reconnect_min_secs: 1,
+            reconnect_max_secs: 60,
+            info_every_reading: false,
+            payload_precision: None,
+            payload_format: PayloadFormat::Json,
+            filtered_channels: ChannelFilter::default(),
+            derived_channels: ChannelFilter::default(),
+            snapshot_interval_secs: 0,
+            schema_version: crate::payload::SCHEMA_VERSION,
+            aggregation: AggregationConfig::default(),
+            bridge: MqttBridgeConfig::default(),
+        }
+    }
+}
+
+/// `protobuf` publishes `Reading` messages from
+/// `proto/sensors_to_mqtt.proto` (`sensors-to-mqtt proto` prints it); see
+/// [`crate::payload`] for the others.
+#[derive(Debug, Deserialize, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum PayloadFormat {
+    #[default]
+    Json,
+    Protobuf,
+    Cbor,
+    InfluxLine,
+}
+
+/// Which channels a payload carries (`[mqtt.filtered_channels]`,
+/// `[mqtt.derived_channels]`): those in `include`, or all when it is empty,
+/// minus those in `exclude`.
+#[derive(Debug, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct ChannelFilter {
+    pub include: Vec<String>,
+    pub exclude: Vec<String>,
+}
+
+impl ChannelFilter {
+    pub fn allows(&self, key: &str) -> bool {
+        (self.include.is_empty() || self.include.iter().any(|k| k == key))
+            && !self.exclude.iter().any(|k| k == key)
+    }
+}
+
+impl AppConfig {
+    /// `tui_refresh_rate_ms`, with a floor so a typo can't spin the UI.
+    pub fn tui_refresh(&self) -> std::time::Duration {
+        std::time::Duration::from_millis(self.tui_refresh_rate_ms.max(20))
+    }
+}
+
+impl MqttConfig {
+    pub fn address(&self) -> String {
+        format!("{}:{}", self.host, self.port)
+    }
+}
+
+/// Forward part of what is published to a second broker (`[mqtt.bridge]`),
+/// typically from the in-car broker to a cloud one over a metered link; see
+/// [`crate::mqtt_handler::Bridge`].
+#[derive(Debug, Deserialize, Clone)]
+#[serde(default)]
+pub struct MqttBridgeConfig {
+    pub enabled: bool,
+    pub host: String,
+    pub port: u16,
+    pub client_id: String,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    /// Replaces `mqtt.base_topic` on the remote broker; empty keeps it
+    pub base_topic: String,
+    /// Last topic segments forwarded (`DERIVED`, `AVAILABILITY` …); empty
+    /// forwards everything
+    pub topics: Vec<String>,
+    /// Keys kept in forwarded FILTERED / DERIVED payloads besides
+    /// `timestamp`; empty keeps them all
+    pub channels: Vec<String>,
+    /// Most messages per second forwarded on each topic; retained messages
+    /// always go through.  0 forwards every message.
+    pub max_rate_hz: f64,
+}
+
+impl Default for MqttBridgeConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            host: String::new(),
+            port: 1883,
+            client_id: "sensors-to-mqtt-bridge".to_string(),
+            username: None,
+            password: None,
+            base_topic: String::new(),
+            topics: ["DERIVED", "AVAILABILITY", "ERRORS", "IGNITION", "LAPS"]
+                .map(String::from)
+                .to_vec(),
+            channels: Vec::new(),
+            max_rate_hz: 1.0,
+        }
+    }
+}
+
+/// Rollups of each sensor's channels (`[mqtt.aggregation]`); see
+/// [`crate::aggregation`].
+#[derive(Debug, Deserialize, Clone)]
+#[serde(default)]
+pub struct AggregationConfig {
+    pub enabled: bool,
+    /// Window lengths; each is published on its own `AGG/<n>S` topic
+    pub windows_secs: Vec<u64>,
+    /// Published channels aggregated; all by default
+    pub channels: ChannelFilter,
+}
+
+impl Default for AggregationConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            windows_secs: vec![1, 10, 60],
+            channels: ChannelFilter::default(),
+        }
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Sampling
+// ---------------------------------------------------------------------------
+
+/// When sensor tasks read their drivers.
+#[derive(Debug, Deserialize, Clone)]
+#[serde(default)]
+pub struct SamplingConfig {
+    /// Time between readings; a driver's own `sample_rate` may limit it
+    /// further
+    pub interval_ms: u64,
+    /// Read on wall-clock multiples of `interval_ms` and stamp readings
+    /// with that instant, instead of free-running from process start
+    pub align_to_clock: bool,
+    /// How reads that fall due together wait for each other; see
+    /// [`crate::read_order`]
+    pub read_strategy: ReadStrategy,
+    /// Add `dt_ms` and `missed_samples` to every sensor's readings; see
+    /// [`crate::processing::timing`]
+    pub timing_channels: bool,
+    /// Reads each sensor task makes before it ends; set by `--once` and
+    /// `--samples`, never from the file
+    #[serde(skip)]
+    pub max_samples: Option<u64>,
+}
+
+impl Default for SamplingConfig {
+    fn default() -> Self {
+        Self {
+            interval_ms: 20,
+            align_to_clock: false,
+            read_strategy: ReadStrategy::Parallel,
+            timing_channels: false,
+            max_samples: None,
+        }
+    }
+}
+
+/// `parallel` reads sensors on different buses at the same time, taking
+/// turns by `priority` on a shared one.  `priority` reads one sensor at a
+/// time across all buses, highest `priority` first.  `sequential` reads one
+/// at a time in the order they fall due.
+#[derive(Debug, Deserialize, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ReadStrategy {
+    Sequential,
+    Priority,
+    #[default]
+    Parallel,
+}
+
+// ---------------------------------------------------------------------------
+// Vehicle dynamics
+// ---------------------------------------------------------------------------
+
+/// Derived speed / corner radius / braking distance for one IMU; see
+/// [`crate::dynamics`].
+#[derive(Debug, Deserialize, Clone)]
+#[serde(default)]
+pub struct DynamicsConfig {
+    pub enabled: bool,
+    /// IMU whose readings get the derived channels
+    pub sensor: String,
+    /// Forward-positive acceleration channel in G
+    pub longitudinal_field: String,
+    /// Lateral acceleration channel in G
+    pub lateral_field: String,
+    /// Sensor reporting GPS speed in km/h; empty integrates the IMU alone
+    pub gps_sensor: String,
+    pub gps_speed_field: String,
+    /// Share of the gap to GPS speed closed on each reading (0–1)
+    pub gps_weight: f64,
+    /// Deceleration (G) that starts a braking event
+    pub brake_threshold_g: f64,
+    /// Lateral G below which the vehicle counts as driving straight
+    pub min_lateral_g: f64,
+    /// Yaw rate in °/s, positive turning right; integrated into `heading`
+    pub yaw_rate_field: String,
+    /// GPS course over ground in degrees clockwise from north
+    pub gps_course_field: String,
+    /// GPS speed (km/h) below which the course is too noisy to use
+    pub min_course_speed_kmh: f64,
+    /// Share of the gap to the GPS course closed on each reading (0–1)
+    pub course_weight: f64,
+}
+
+impl Default for DynamicsConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            sensor: String::new(),
+            longitudinal_field: "g_force_x".to_string(),
+            lateral_field: "g_force_y".to_string(),
+            gps_sensor: String::new(),
+            gps_speed_field: "speed".to_string(),
+            gps_weight: 0.1,
+            brake_threshold_g: 0.3,
+            min_lateral_g: 0.05,
+            yaw_rate_field: "yaw_rate".to_string(),
+            gps_course_field: "course".to_string(),
+            min_course_speed_kmh: 10.0,
+            course_weight: 0.05,
+        }
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Wheel slip
+// ---------------------------------------------------------------------------
+
+/// Lockup and wheelspin from a wheel speed sensor; see [`crate::slip`].
+#[derive(Debug, Deserialize, Clone)]
+#[serde(default)]
+pub struct SlipConfig {
+    pub enabled: bool,
+    /// Sensor whose readings carry the vehicle speed and get the slip
+    /// channels, normally the `[dynamics]` IMU
+    pub sensor: String,
+    /// Vehicle speed channel on `sensor`, in km/h
+    pub vehicle_speed_field: String,
+    /// Sensor reporting the speed of one wheel
+    pub wheel_sensor: String,
+    /// Wheel speed channel on `wheel_sensor`, in km/h
+    pub wheel_speed_field: String,
+    /// Slip below minus this many percent is a lockup
+    pub lockup_pct: f64,
+    /// Slip above this many percent is wheelspin
+    pub wheelspin_pct: f64,
+    /// Vehicle speed below which slip isn't judged
+    pub min_speed_kmh: f64,
+}
+
+impl Default for SlipConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            sensor: String::new(),
+            vehicle_speed_field: "speed".to_string(),
+            wheel_sensor: String::new(),
+            wheel_speed_field: "wheel_speed".to_string(),
+            lockup_pct: 20.0,
+            wheelspin_pct: 20.0,
+            min_speed_kmh: 10.0,
+        }
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Lap timing
+// ---------------------------------------------------------------------------
+
+/// GPS lap timer; see [`crate::laps`].
+#[derive(Debug, Deserialize, Clone)]
+#[serde(default)]
+pub struct LapsConfig {
+    pub enabled: bool,
+    /// Sensor reporting position in decimal degrees
+    pub gps_sensor: String,
+    pub latitude_field: String,
+    pub longitude_field: String,
+    /// Start/finish line endpoints as `[latitude, longitude]`
+    pub start_finish: [[f64; 2]; 2],
+    /// Crossings sooner than this after the lap started are ignored
+    pub min_lap_secs: f64,
+}
+
+impl Default for LapsConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            gps_sensor: String::new(),
+            latitude_field: "latitude".to_string(),
+            longitude_field: "longitude".to_string(),
+            start_finish: [[0.0; 2]; 2],
+            min_lap_secs: 10.0,
+        }
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Drift report
+// ---------------------------------------------------------------------------
+
+/// Gyro vs accelerometer roll/pitch drift for one IMU; see
+/// [`crate::drift`].
+#[derive(Debug, Deserialize, Clone)]
+#[serde(default)]
+pub struct DriftConfig {
+    pub enabled: bool,
+    /// IMU whose readings get the drift channels
+    pub sensor: String,
+    /// X / Y / Z acceleration including gravity, in G
+    pub accel_fields: [String; 3],
+    /// Roll (about X) and pitch (about Y) rates in °/s
+    pub gyro_fields: [String; 2],
+    /// Length of each drift fit
+    pub window_secs: f64,
+}
+
+impl Default for DriftConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            sensor: String::new(),
+            accel_fields: ["accel_raw_x", "accel_raw_y", "accel_raw_z"].map(String::from),
+            gyro_fields: ["gyro_x", "gyro_y"].map(String::from),
+            window_secs: 10.0,
+        }
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Vibration metrics
+// ---------------------------------------------------------------------------
+
+/// Windowed RMS, peak-to-peak and crest factor for one IMU; see
+/// [`crate::vibration`].
+#[derive(Debug, Deserialize, Clone)]
+#[serde(default)]
+pub struct VibrationMetricsConfig {
+    pub enabled: bool,
+    /// IMU whose readings get the vibration metrics
+    pub sensor: String,
+    /// X / Y / Z acceleration in G, ideally high-passed
+    pub accel_fields: [String; 3],
+    /// Length of each window; one report is published per window
+    pub window_secs: f64,
+}
+
+impl Default for VibrationMetricsConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            sensor: String::new(),
+            accel_fields: ["vibration_x", "vibration_y", "vibration_z"].map(String::from),
+            window_secs: 1.0,
+        }
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Transforms
+// ---------------------------------------------------------------------------
+
+/// Scaling and trimming of one of a sensor's channels (`[[transforms]]`),
+/// applied as it is read; see [`crate::transforms`].
+#[derive(Debug, Deserialize, Clone)]
+pub struct TransformConfig {
+    /// Sensor whose readings are transformed
+    pub sensor: String,
+    /// Channel to rewrite, e.g. `ch0_voltage`
+    pub field: String,
+    #[serde(default = "default_scale")]
+    pub gain: f64,
+    /// Added after `gain`
+    #[serde(default)]
+    pub offset: f64,
+    /// Results closer to 0 than this read as 0
+    #[serde(default)]
+    pub deadband: f64,
+    #[serde(default)]
+    pub min: Option<f64>,
+    #[serde(default)]
+    pub max: Option<f64>,
+    /// Replaces the driver's unit in the TUI and schemas, e.g. `PSI`
+    #[serde(default)]
+    pub unit: Option<String>,
+}
+
+// ---------------------------------------------------------------------------
+// Derivatives
+// ---------------------------------------------------------------------------
+
+/// A rate-of-change channel (`[[derivatives]]`) computed from another of
+/// one sensor's channels; see [`crate::derivatives`].
+#[derive(Debug, Deserialize, Clone)]
+pub struct DerivativeConfig {
+    /// Sensor whose readings get the channel
+    pub sensor: String,
+    /// Channel to differentiate, e.g. `accel_x`
+    pub field: String,
+    /// Key of the new channel, e.g. `jerk_x`
+    pub name: String,
+    /// Readings the slope is fitted over; 0 uses the last two only
+    #[serde(default = "default_derivative_window")]
+    pub window_secs: f64,
+    /// Multiplier for the per-second rate, e.g. 60 for per minute
+    #[serde(default = "default_scale")]
+    pub scale: f64,
+    /// Unit shown in the TUI and schemas
+    #[serde(default)]
+    pub unit: String,
+}
+
+fn default_derivative_window() -> f64 {
+    0.1
+}
+
+fn default_scale() -> f64 {
+    1.0
+}
+
+// ---------------------------------------------------------------------------
+// Integrals
+// ---------------------------------------------------------------------------
+
+/// A running-integral channel (`[[integrals]]`) computed from another of
+/// one sensor's channels; see [`crate::integrals`].
+#[derive(Debug, Deserialize, Clone)]
+pub struct IntegralConfig {
+    /// Sensor whose readings get the channel
+    pub sensor: String,
+    /// Channel to integrate, e.g. `yaw_rate`
+    pub field: String,
+    /// Key of the new channel, e.g. `yaw_angle`
+    pub name: String,
+    /// Multiplier for the integrand, e.g. 9.80665 to turn G into m/s
+    #[serde(default = "default_scale")]
+    pub scale: f64,
+    /// Time constant the total decays towards 0 with, to bleed off drift
+    /// from a biased input; 0 never decays
+    #[serde(default)]
+    pub leak_secs: f64,
+    /// Keep the total in `[0, wrap)`, e.g. 360 for a heading; 0 never wraps
+    #[serde(default)]
+    pub wrap: f64,
+    /// Start again from 0 this often; 0 never resets
+    #[serde(default)]
+    pub reset_secs: f64,
+    /// Unit shown in the TUI and schemas
+    #[serde(default)]
+    pub unit: String,
+}
+
+// ---------------------------------------------------------------------------
+// Differences
+// ---------------------------------------------------------------------------
+
+/// A channel (`[[differences]]`) holding one channel minus another, which
+/// may belong to a different sensor; see [`crate::differences`].  Both are
+/// written `<sensor>.<channel>`.
+#[derive(Debug, Deserialize, Clone)]
+pub struct DifferenceConfig {
+    /// Key of the new channel, e.g. `brake_temp_delta`
+    pub name: String,
+    /// Channel subtracted from; the difference is published with this
+    /// sensor's readings
+    pub from: String,
+    /// Channel subtracted
+    pub minus: String,
+    /// A `minus` reading this much older than the `from` one is left out
+    #[serde(default = "default_difference_max_age")]
+    pub max_age_ms: u64,
+    /// Unit shown in the TUI and schemas
+    #[serde(default)]
+    pub unit: String,
+}
+
+impl DifferenceConfig {
+    /// The sensor `from` names, whose readings get the channel.
+    pub fn sensor(&self) -> &str {
+        channel_ref(&self.from).map_or("", |(sensor, _)| sensor)
+    }
+}
+
+/// `<sensor>.<channel>` split at the last dot, as sensor names may have
+/// dots in them.
+pub fn channel_ref(s: &str) -> Option<(&str, &str)> {
+    s.rsplit_once('.')
+        .filter(|(sensor, channel)| !sensor.is_empty() && !channel.is_empty())
+}
+
+fn default_difference_max_age() -> u64 {
+    1000
+}
+
+// ---------------------------------------------------------------------------
+// All-time extremes
+// ---------------------------------------------------------------------------
+
+/// Lowest and highest values ever seen on chosen channels, kept across
+/// runs; see [`crate::extremes`].
+#[derive(Debug, Deserialize, Clone)]
+#[serde(default)]
+pub struct ExtremesConfig {
+    pub enabled: bool,
+    /// `<sensor>.<channel>` entries tracked
+    pub channels: Vec<String>,
+    /// Where the extremes are kept between runs; a leading `~/` is expanded
+    pub file: String,
+    /// How often changed extremes are written to `file` and published
+    pub save_interval_secs: u64,
+}
+
+impl Default for ExtremesConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            channels: Vec::new(),
+            file: "~/.local/state/sensors-to-mqtt/extremes.toml".to_string(),
+            save_interval_secs: 10,
+        }
+    }
+}
+
+/// `configured` with a leading `~/` expanded; `None` when it is empty or
+/// `~` can't be resolved.
+pub fn resolve_path(configured: &str) -> Option<PathBuf> {
+    match configured.strip_prefix("~/") {
+        _ if configured.is_empty() => None,
+        Some(rest) => std::env::var_os("HOME").map(|home| Path::new(&home).join(rest)),
+        None => Some(PathBuf::from(configured)),
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Anomaly scores
+// ---------------------------------------------------------------------------
+
+/// A score (`[[anomalies]]`) of how far one of a sensor's channels is from
+/// its recent baseline; see [`crate::anomaly`].
+#[derive(Debug, Deserialize, Clone)]
+pub struct AnomalyConfig {
+    /// Sensor whose readings get the score
+    pub sensor: String,
+    /// Channel to watch, e.g. `coolant_temp`
+    pub field: String,
+    /// Key of the score channel, e.g. `coolant_anomaly`
+    pub name: String,
+    /// How quickly the baseline follows the channel
+    #[serde(default = "default_anomaly_half_life")]
+    pub half_life_secs: f64,
+    /// Score that raises an alert; 0 publishes the score only
+    #[serde(default = "default_anomaly_threshold")]
+    pub threshold: f64,
+    /// Smallest spread a deviation is measured against, in the channel's
+    /// unit, so a channel that barely moves doesn't alert on its next step
+    #[serde(default)]
+    pub min_spread: f64,
+    /// Raise an alert when the channel hasn't changed for this long; 0
+    /// never does
+    #[serde(default)]
+    pub stuck_secs: f64,
+}
+
+fn default_anomaly_half_life() -> f64 {
+    60.0
+}
+
+fn default_anomaly_threshold() -> f64 {
+    6.0
+}
+
+// ---------------------------------------------------------------------------
+// Fused sensors
+// ---------------------------------------------------------------------------
+
+/// A virtual sensor (`[[fused_sensors]]`) averaging the readings of rigidly
+/// mounted sensors of the same kind; see [`crate::fusion`].
+#[derive(Debug, Deserialize, Clone)]
+pub struct FusedSensorConfig {
+    /// Name it is published and shown under, like a sensor's
+    pub name: String,
+    /// Sensors whose readings are averaged; at least two
+    pub sources: Vec<String>,
+    /// Channels averaged; with `include` empty, the linear acceleration and
+    /// rotation rate axes ([`crate::fusion::DEFAULT_CHANNELS`])
+    #[serde(default)]
+    pub channels: ChannelFilter,
+    /// Rate of the fused readings; 0 follows `sampling.interval_ms`
+    #[serde(default)]
+    pub interval_ms: u64,
+    /// Source readings older than this are left out of the average
+    #[serde(default = "default_fusion_max_age")]
+    pub max_age_ms: u64,
+    /// Largest disagreement between sources, in the channels' unit, before
+    /// an alert fires; 0 publishes `divergence` only
+    #[serde(default)]
+    pub max_divergence: f64,
+    /// Fused channels that `divergence` compares
+    #[serde(default)]
+    pub divergence_channels: ChannelFilter,
+}
+
+fn default_fusion_max_age() -> u64 {
+    500
+}
+
+// ---------------------------------------------------------------------------
+// Alerts
+// ---------------------------------------------------------------------------
+
+/// A threshold on one of a sensor's channels (`[[alerts]]`), shown in the
+/// TUI until acknowledged; see [`crate::alerts`].
+#[derive(Debug, Deserialize, Clone)]
+pub struct AlertConfig {
+    /// Sensor whose readings are watched
+    pub sensor: String,
+    /// Channel to compare, e.g. `coolant_temp`
+    pub field: String,
+    /// Fires when the channel goes above this
+    #[serde(default)]
+    pub above: Option<f64>,
+    /// Fires when the channel goes below this
+    #[serde(default)]
+    pub below: Option<f64>,
+    /// How far back inside the limit the channel must come before the alert
+    /// clears, so a value hovering at the limit doesn't keep re-firing
+    #[serde(default)]
+    pub hysteresis: f64,
+    /// Shown in the status bar; empty uses `<sensor> <field>`
+    #[serde(default)]
+    pub message: String,
+}
+
+// ---------------------------------------------------------------------------
+// Ignition detection
+// ---------------------------------------------------------------------------
+
+/// Pause sampling and publishing while the car is off; see
+/// [`crate::ignition`].
+#[derive(Debug, Deserialize, Clone)]
+#[serde(default)]
+pub struct IgnitionConfig {
+    pub enabled: bool,
+    /// Sensor that tells whether the engine is on, e.g. an INA219 on the
+    /// supply or a GPIO input wired to the ignition line
+    pub sensor: String,
+    pub field: String,
+    /// The ignition counts as on while `field` is at or above this
+    pub on_above: f64,
+    /// How long the signal must stay off before sampling pauses
+    pub off_delay_secs: f64,
+    /// How long the signal must stay on before sampling resumes
+    pub on_delay_secs: f64,
+    /// Keep sampling while paused, into the history only: nothing is
+    /// published or recorded, but the TUI charts the readings greyed and
+    /// `e` exports them, in case the pause came mid-run
+    pub buffer_while_paused: bool,
+}
+
+impl Default for IgnitionConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            sensor: String::new(),
+            field: "bus_voltage_v".to_string(),
+            // Above a resting battery, below a running alternator
+            on_above: 13.2,
+            off_delay_secs: 60.0,
+            on_delay_secs: 2.0,
+            buffer_while_paused: false,
+        }
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Adaptive sample rate
+// ---------------------------------------------------------------------------
+
+/// Drop to an idle rate while an IMU sees no motion; see
+/// [`crate::activity`].
+#[derive(Debug, Deserialize, Clone)]
+#[serde(default)]
+pub struct AdaptiveRateConfig {
+    pub enabled: bool,
+    /// IMU whose readings decide whether the vehicle is moving
+    pub sensor: String,
+    /// Acceleration magnitude without gravity, in G
+    pub g_field: String,
+    /// Rotation rate magnitude in °/s
+    pub gyro_field: String,
+    pub g_threshold: f64,
+    pub gyro_threshold: f64,
+    /// How long both must stay below their thresholds before idling
+    pub idle_after_secs: f64,
+    /// Time between readings while idle
+    pub idle_interval_ms: u64,
+}
+
+impl Default for AdaptiveRateConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            sensor: String::new(),
+            g_field: "combined_g".to_string(),
+            gyro_field: "angular_velocity".to_string(),
+            g_threshold: 0.05,
+            gyro_threshold: 3.0,
+            idle_after_secs: 30.0,
+            idle_interval_ms: 1000,
+        }
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Error budget
+// ---------------------------------------------------------------------------
+
+/// Disable sensors whose reads keep failing; see [`crate::error_budget`].
+/// Applies to every sensor when enabled.
+#[derive(Debug, Deserialize, Clone)]
+#[serde(default)]
+pub struct ErrorBudgetConfig {
+    pub enabled: bool,
+    /// Share of failed reads over the window that disables the sensor
+    pub max_failure_pct: f64,
+    pub window_secs: u64,
+    /// Reads needed in the window before it is judged
+    pub min_reads: u64,
+}
+
+impl Default for ErrorBudgetConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            max_failure_pct: 20.0,
+            window_secs: 300,
+            min_reads: 100,
+        }
+    }
+}
+
+/// Warn about channels that stop changing; see
+/// [`crate::processing::stuck`].  Applies to every sensor when enabled.
+#[derive(Debug, Deserialize, Clone)]
+#[serde(default)]
+pub struct StuckValuesConfig {
+    pub enabled: bool,
+    /// Identical readings in a row that make a channel stuck
+    pub samples: u32,
+    /// Driver channels checked; all by default
+    pub channels: ChannelFilter,
+}
+
+impl Default for StuckValuesConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            samples: 100,
+            channels: ChannelFilter::default(),
+        }
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Clock
+// ---------------------------------------------------------------------------
+
+/// Wall-clock sync checking; see [`crate::clock`].
+#[derive(Debug, Deserialize, Clone)]
+#[serde(default)]
+pub struct ClockConfig {
+    pub sync_check: SyncCheck,
+    pub check_interval_secs: u64,
+}
+
+impl Default for ClockConfig {
+    fn default() -> Self {
+        Self {
+            sync_check: SyncCheck::default(),
+            check_interval_secs: 60,
+        }
+    }
+}
+
+/// How to find out whether the system clock is synchronised.  `auto` uses
+/// chrony if it answers and `timedatectl` otherwise.
+#[derive(Debug, Deserialize, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum SyncCheck {
+    #[default]
+    Auto,
+    Chrony,
+    Timedatectl,
+    Off,
+}
+
+// ---------------------------------------------------------------------------
+// Startup self-check
+// ---------------------------------------------------------------------------
+
+/// Report on the buses, sensors, broker and clock after start; see
+/// [`crate::self_check`].
+#[derive(Debug, Deserialize, Clone)]
+#[serde(default)]
+pub struct SelfCheckConfig {
     pub enabled: bool,
-    pub host: String,
-    pub port: u16,
-    pub base_topic: String,
-    pub client_id: String,
-    pub keep_alive_secs: u64,
-    pub username: Option<String>,
-    pub password: Option<String>,
+    /// Longest wait for the sensors and broker to come up before checking
+    pub timeout_secs: u64,
+    /// In daemon mode, exit with an error when a critical check fails
+    pub exit_on_failure: bool,
 }
 
-impl Default for MqttConfig {
+impl Default for SelfCheckConfig {
     fn default() -> Self {
         Self {
             enabled: true,
-            host: "localhost".to_string(),
-            port: 1883,
-            base_topic: "/SENSORS".to_string(),
-            client_id: "sensors-to-mqtt".to_string(),
-            keep_alive_secs: 20,
-            username: None,
-            password: None,
+            timeout_secs: 15,
+            exit_on_failure: true,
         }
     }
 }
 
-impl MqttConfig {
-    pub fn address(&self) -> String {
-        format!("{}:{}", self.host, self.port)
+// ---------------------------------------------------------------------------
+// Dashboard document
+// ---------------------------------------------------------------------------
+
+/// Retained `<base_topic>/DASHBOARD` with every sensor's latest values; see
+/// [`crate::dashboard`].
+#[derive(Debug, Deserialize, Clone)]
+#[serde(default)]
+pub struct DashboardConfig {
+    pub enabled: bool,
+    /// Time between updates; nothing is sent while no values change
+    pub interval_ms: u64,
+}
+
+impl Default for DashboardConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            interval_ms: 1000,
+        }
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Remote settings changes
+// ---------------------------------------------------------------------------
+
+/// Settings documents published to `<base_topic>/CONFIG/SET`; see
+/// [`crate::remote_config`].
+#[derive(Debug, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct RemoteConfig {
+    pub enabled: bool,
+    /// Changes applied over MQTT are kept in this file and reapplied at
+    /// startup; empty keeps them until the next restart only.
+    pub persist_file: String,
+}
+
+// ---------------------------------------------------------------------------
+// MQTT command access
+// ---------------------------------------------------------------------------
+
+/// Which MQTT commands are accepted, and whether they must be signed; see
+/// [`crate::command_auth`].
+#[derive(Debug, Deserialize, Clone)]
+#[serde(default)]
+pub struct CommandAuthConfig {
+    /// Command topics followed: `groups` (`GROUPS/<name>/SET`) and
+    /// `config` (`CONFIG/SET`)
+    pub allow: Vec<String>,
+    /// HMAC-SHA256 key commands must be signed with; empty accepts them
+    /// unsigned.
+    pub secret: String,
+    /// Oldest signed command accepted, against this machine's clock; 0
+    /// only requires each to be newer than the last.
+    pub max_age_secs: u64,
+}
+
+impl Default for CommandAuthConfig {
+    fn default() -> Self {
+        Self {
+            allow: vec![
+                "groups".to_string(),
+                "config".to_string(),
+                "publish".to_string(),
+                "extremes".to_string(),
+            ],
+            secret: String::new(),
+            max_age_secs: 60,
+        }
+    }
+}
+
+// ---------------------------------------------------------------------------
+// I2C buses
+// ---------------------------------------------------------------------------
+
+/// A named I2C bus (`[[i2c_buses]]`).  Sensors refer to it with
+/// `bus = "<name>"` in their connection instead of repeating the device.
+#[derive(Debug, Deserialize, Clone)]
+pub struct I2cBusConfig {
+    pub name: String,
+    pub device: String,
+    /// Clock the bus is wired for, in Hz (e.g. 100000 or 400000).  Checked
+    /// against the kernel's setting at startup; it can't be set from here.
+    #[serde(default)]
+    pub speed_hz: Option<u32>,
+}
+
+// ---------------------------------------------------------------------------
+// Sensor groups
+// ---------------------------------------------------------------------------
+
+/// A named set of sensors (`[[groups]]`), switched on and off together from
+/// the TUI or over MQTT; see [`crate::groups`].  Sensors join it with
+/// `group = "<name>"`.
+#[derive(Debug, Deserialize, Clone)]
+pub struct SensorGroupConfig {
+    pub name: String,
+    /// Whether its sensors read at startup
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+    /// Publish its sensors under `<base_topic>/<name>/IMU/...`
+    #[serde(default)]
+    pub in_topic: bool,
+}
+
+impl AppConfig {
+    /// Check group names and every sensor's `group`, and note the groups
+    /// that go in the topic path.
+    pub fn resolve_groups(&mut self) -> Result<()> {
+        for (i, group) in self.groups.iter().enumerate() {
+            if let Some(problem) = topic_level_problem(&group.name) {
+                bail!("Group name '{}' {}", group.name.escape_debug(), problem);
+            }
+            if self.groups[..i].iter().any(|g| g.name == group.name) {
+                bail!("Group '{}' is defined more than once", group.name);
+            }
+        }
+        for sensor in &mut self.sensors {
+            let Some(name) = &sensor.group else {
+                continue;
+            };
+            let Some(group) = self.groups.iter().find(|g| &g.name == name) else {
+                bail!(
+                    "Sensor '{}' is in group '{}', which has no [[groups]] entry",
+                    sensor.name,
+                    name
+                );
+            };
+            sensor.topic_group = group.in_topic.then(|| group.name.clone());
+        }
+        Ok(())
+    }
+
+    /// Check the names that end up in MQTT topics: every sensor needs a name
+    /// of its own that is a single topic level, and the base topics can't
+    /// contain wildcards.  Otherwise a sensor could publish into another's
+    /// topics, or under one that subscribers can't match exactly.
+    pub fn check_topic_names(&self) -> Result<()> {
+        for (i, sensor) in self.sensors.iter().enumerate() {
+            if let Some(problem) = topic_level_problem(&sensor.name) {
+                bail!(
+                    "Sensor name '{}' {}; it is used as an MQTT topic level",
+                    sensor.name.escape_debug(),
+                    problem
+                );
+            }
+            if self.sensors[..i].iter().any(|s| s.name == sensor.name) {
+                bail!("Sensor '{}' is defined more than once", sensor.name);
+            }
+        }
+        for (key, topic) in [
+            ("base_topic", &self.mqtt.base_topic),
+            ("bridge.base_topic", &self.mqtt.bridge.base_topic),
+        ] {
+            if topic.contains(['+', '#']) || topic.chars().any(char::is_control) {
+                bail!(
+                    "mqtt.{} '{}' can't contain '+', '#' or control characters",
+                    key,
+                    topic.escape_debug()
+                );
+            }
+        }
+        Ok(())
+    }
+
+    /// Point every sensor connection with a `bus` name at that bus's device.
+    pub fn resolve_i2c_buses(&mut self) -> Result<()> {
+        for (i, bus) in self.i2c_buses.iter().enumerate() {
+            if self.i2c_buses[..i].iter().any(|b| b.name == bus.name) {
+                bail!("I2C bus '{}' is defined more than once", bus.name);
+            }
+        }
+        for sensor in &mut self.sensors {
+            let ConnectionConfig::I2c(c) = &mut sensor.connection else {
+                continue;
+            };
+            let Some(name) = &c.bus else {
+                continue;
+            };
+            let Some(bus) = self.i2c_buses.iter().find(|b| &b.name == name) else {
+                bail!(
+                    "Sensor '{}' uses I2C bus '{}', which has no [[i2c_buses]] entry",
+                    sensor.name,
+                    name
+                );
+            };
+            c.device = bus.device.clone();
+        }
+        Ok(())
+    }
+
+    /// Fill `${hostname}` and `${device_id}` in the MQTT client ids and base
+    /// topics, so one config file can be deployed to several vehicles, and
+    /// `${schema_version}`, so a new payload layout can go under topics of
+    /// its own.
+    pub fn expand_placeholders(&mut self) -> Result<()> {
+        if self.mqtt.schema_version > crate::payload::SCHEMA_VERSION {
+            bail!(
+                "mqtt.schema_version {} is newer than this version publishes ({})",
+                self.mqtt.schema_version,
+                crate::payload::SCHEMA_VERSION
+            );
+        }
+        let host = hostname();
+        let device_id = match self.device_id.trim() {
+            "" => host.clone(),
+            id if id.contains(['/', '+', '#']) => {
+                bail!("device_id '{}' can't contain '/', '+' or '#'", id)
+            }
+            id => Some(id.to_string()),
+        };
+        let vars = [
+            ("hostname", host),
+            ("device_id", device_id),
+            ("schema_version", Some(self.mqtt.schema_version.to_string())),
+        ];
+        for (key, value) in [
+            ("client_id", &mut self.mqtt.client_id),
+            ("base_topic", &mut self.mqtt.base_topic),
+            ("bridge.client_id", &mut self.mqtt.bridge.client_id),
+            ("bridge.base_topic", &mut self.mqtt.bridge.base_topic),
+        ] {
+            for (name, sub) in &vars {
+                let placeholder = format!("${{{}}}", name);
+                if !value.contains(&placeholder) {
+                    continue;
+                }
+                let Some(sub) = sub else {
+                    bail!(
+                        "mqtt.{} uses {} but the hostname couldn't be read; set device_id and use ${{device_id}}",
+                        key,
+                        placeholder
+                    );
+                };
+                *value = value.replace(&placeholder, sub);
+            }
+            if let Some(start) = value.find("${") {
+                bail!(
+                    "mqtt.{} has an unknown placeholder at '{}'; use ${{hostname}}, ${{device_id}} or ${{schema_version}}",
+                    key,
+                    &value[start..]
+                );
+            }
+        }
+        Ok(())
+    }
+
+    /// Reject two enabled sensors on the same I2C device and address.
+    pub fn check_i2c_addresses(&self) -> Result<()> {
+        let mut seen: HashMap<(&str, u16), &str> = HashMap::new();
+        for sensor in self.sensors.iter().filter(|s| s.enabled) {
+            let ConnectionConfig::I2c(c) = &sensor.connection else {
+                continue;
+            };
+            if let Some(other) = seen.insert((&c.device, c.address), &sensor.name) {
+                bail!(
+                    "Sensors '{}' and '{}' both use I2C address {:#04x} on {} — \
+                     change one's address (most boards have an address pin) \
+                     or move it to another bus",
+                    other,
+                    sensor.name,
+                    c.address,
+                    c.device
+                );
+            }
+        }
+        Ok(())
+    }
+
+    /// Check every `[[transforms]]` entry names a sensor and usable
+    /// numbers.
+    pub fn check_transforms(&self) -> Result<()> {
+        for t in &self.transforms {
+            let what = format!("Transform of '{}' on '{}'", t.field, t.sensor);
+            if !self.sensors.iter().any(|s| s.name == t.sensor) {
+                bail!("{}: sensor '{}' isn't configured", what, t.sensor);
+            }
+            if !(t.gain.is_finite() && t.offset.is_finite()) {
+                bail!("{}: gain and offset must be numbers", what);
+            }
+            if !(t.deadband >= 0.0 && t.deadband.is_finite()) {
+                bail!("{}: deadband must be 0 or more", what);
+            }
+            if let (Some(min), Some(max)) = (t.min, t.max)
+                && min > max
+            {
+                bail!("{}: min must not be above max", what);
+            }
+        }
+        Ok(())
+    }
+
+    /// Check every `[[derivatives]]` entry names a sensor and a usable
+    /// window.
+    pub fn check_derivatives(&self) -> Result<()> {
+        for d in &self.derivatives {
+            if !self.sensors.iter().any(|s| s.name == d.sensor) {
+                bail!(
+                    "Derivative '{}' is for sensor '{}', which isn't configured",
+                    d.name,
+                    d.sensor
+                );
+            }
+            if d.name.is_empty() || d.name == d.field {
+                bail!(
+                    "Derivative of '{}' on '{}' needs a name of its own",
+                    d.field,
+                    d.sensor
+                );
+            }
+            if !(d.window_secs >= 0.0 && d.window_secs.is_finite()) {
+                bail!("Derivative '{}': window_secs must be 0 or more", d.name);
+            }
+        }
+        Ok(())
+    }
+
+    /// Check every `[[integrals]]` entry names a sensor and usable options.
+    pub fn check_integrals(&self) -> Result<()> {
+        for i in &self.integrals {
+            if !self.sensors.iter().any(|s| s.name == i.sensor) {
+                bail!(
+                    "Integral '{}' is for sensor '{}', which isn't configured",
+                    i.name,
+                    i.sensor
+                );
+            }
+            if i.name.is_empty() || i.name == i.field {
+                bail!(
+                    "Integral of '{}' on '{}' needs a name of its own",
+                    i.field,
+                    i.sensor
+                );
+            }
+            for (option, v) in [
+                ("leak_secs", i.leak_secs),
+                ("wrap", i.wrap),
+                ("reset_secs", i.reset_secs),
+            ] {
+                if !(v >= 0.0 && v.is_finite()) {
+                    bail!("Integral '{}': {} must be 0 or more", i.name, option);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Check every `[[differences]]` entry names two channels of configured
+    /// sensors and a key of its own.
+    pub fn check_differences(&self) -> Result<()> {
+        for d in &self.differences {
+            for operand in [&d.from, &d.minus] {
+                let Some((sensor, _)) = channel_ref(operand) else {
+                    bail!(
+                        "Difference '{}': '{}' should be <sensor>.<channel>",
+                        d.name,
+                        operand
+                    );
+                };
+                if !self.sensors.iter().any(|s| s.name == sensor) {
+                    bail!(
+                        "Difference '{}' uses sensor '{}', which isn't configured",
+                        d.name,
+                        sensor
+                    );
+                }
+            }
+            if d.name.is_empty() || channel_ref(&d.from).is_some_and(|(_, c)| c == d.name) {
+                bail!(
+                    "Difference '{} - {}' needs a name of its own",
+                    d.from,
+                    d.minus
+                );
+            }
+        }
+        Ok(())
+    }
+
+    /// Aggregation windows last at least a second, each a different length.
+    pub fn check_aggregation(&self) -> Result<()> {
+        let agg = &self.mqtt.aggregation;
+        if !agg.enabled {
+            return Ok(());
+        }
+        if agg.windows_secs.is_empty() {
+            bail!("mqtt.aggregation is enabled but windows_secs is empty");
+        }
+        for (i, w) in agg.windows_secs.iter().enumerate() {
+            if *w == 0 {
+                bail!("mqtt.aggregation.windows_secs can't include 0");
+            }
+            if agg.windows_secs[..i].contains(w) {
+                bail!("mqtt.aggregation.windows_secs lists {} twice", w);
+            }
+        }
+        Ok(())
+    }
+
+    /// `bridge.channels` trims JSON objects, so it needs JSON payloads.
+    pub fn check_bridge(&self) -> Result<()> {
+        let bridge = &self.mqtt.bridge;
+        if bridge.enabled
+            && !bridge.channels.is_empty()
+            && self.mqtt.payload_format != PayloadFormat::Json
+        {
+            bail!(
+                "mqtt.bridge.channels only works with payload_format = \"json\"; \
+                 remove it to forward whole payloads"
+            );
+        }
+        Ok(())
+    }
+
+    /// Tracked extremes name a configured or fused sensor's channel.
+    pub fn check_extremes(&self) -> Result<()> {
+        if !self.extremes.enabled {
+            return Ok(());
+        }
+        for entry in &self.extremes.channels {
+            let Some((sensor, _)) = channel_ref(entry) else {
+                bail!("Extremes: '{}' should be <sensor>.<channel>", entry);
+            };
+            let known = self.sensors.iter().any(|s| s.name == sensor)
+                || self.fused_sensors.iter().any(|f| f.name == sensor);
+            if !known {
+                bail!(
+                    "Extremes: '{}' names sensor '{}', which isn't configured",
+                    entry,
+                    sensor
+                );
+            }
+        }
+        Ok(())
+    }
+
+    /// Fused sensors need a topic-safe name of their own and at least two
+    /// distinct configured sources.
+    pub fn check_fused_sensors(&self) -> Result<()> {
+        for (i, f) in self.fused_sensors.iter().enumerate() {
+            if let Some(problem) = topic_level_problem(&f.name) {
+                bail!(
+                    "Fused sensor name '{}' {}; it is used as an MQTT topic level",
+                    f.name.escape_debug(),
+                    problem
+                );
+            }
+            if self.sensors.iter().any(|s| s.name == f.name)
+                || self.fused_sensors[..i].iter().any(|o| o.name == f.name)
+            {
+                bail!("Sensor '{}' is defined more than once", f.name);
+            }
+            if f.sources.len() < 2 {
+                bail!("Fused sensor '{}' needs at least two sources", f.name);
+            }
+            for (j, source) in f.sources.iter().enumerate() {
+                if !self.sensors.iter().any(|s| &s.name == source) {
+                    bail!(
+                        "Fused sensor '{}': source '{}' isn't a configured sensor",
+                        f.name,
+                        source
+                    );
+                }
+                if f.sources[..j].contains(source) {
+                    bail!("Fused sensor '{}' lists '{}' twice", f.name, source);
+                }
+            }
+            if !(f.max_divergence >= 0.0 && f.max_divergence.is_finite()) {
+                bail!(
+                    "Fused sensor '{}': max_divergence must be 0 or more",
+                    f.name
+                );
+            }
+        }
+        Ok(())
+    }
+
+    /// Check every `[[anomalies]]` entry names a sensor and usable options.
+    pub fn check_anomalies(&self) -> Result<()> {
+        for a in &self.anomalies {
+            if !self.sensors.iter().any(|s| s.name == a.sensor) {
+                bail!(
+                    "Anomaly score '{}' is for sensor '{}', which isn't configured",
+                    a.name,
+                    a.sensor
+                );
+            }
+            if a.name.is_empty() || a.name == a.field {
+                bail!(
+                    "Anomaly score of '{}' on '{}' needs a name of its own",
+                    a.field,
+                    a.sensor
+                );
+            }
+            if !(a.half_life_secs > 0.0 && a.half_life_secs.is_finite()) {
+                bail!("Anomaly score '{}': half_life_secs must be above 0", a.name);
+            }
+            for (option, v) in [
+                ("threshold", a.threshold),
+                ("min_spread", a.min_spread),
+                ("stuck_secs", a.stuck_secs),
+            ] {
+                if !(v >= 0.0 && v.is_finite()) {
+                    bail!("Anomaly score '{}': {} must be 0 or more", a.name, option);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Reapply the settings changes kept in `remote_config.persist_file`.
+    pub fn apply_persisted_settings(&mut self) -> Result<()> {
+        let path = self.remote_config.persist_file.clone();
+        if path.is_empty() || !std::path::Path::new(&path).exists() {
+            return Ok(());
+        }
+        let text =
+            std::fs::read_to_string(&path).with_context(|| format!("Failed to read {}", path))?;
+        let doc: toml::Table =
+            toml::from_str(&text).with_context(|| format!("Failed to parse {}", path))?;
+        crate::remote_config::apply(&mut self.sensors, &doc)
+            .with_context(|| format!("Failed to apply the settings kept in {}", path))
+    }
+
+    /// Check `command_auth.allow` names known commands.
+    pub fn check_command_auth(&self) -> Result<()> {
+        for name in &self.command_auth.allow {
+            if !crate::command_auth::COMMANDS.contains(&name.as_str()) {
+                bail!(
+                    "Unknown command '{}' in command_auth.allow (expected {})",
+                    name,
+                    crate::command_auth::COMMANDS.join(", ")
+                );
+            }
+        }
+        Ok(())
+    }
+
+    /// Check every `[[alerts]]` entry names a sensor and has a limit.
+    pub fn check_alerts(&self) -> Result<()> {
+        for a in &self.alerts {
+            if !self.sensors.iter().any(|s| s.name == a.sensor) {
+                bail!(
+                    "Alert on '{}' is for sensor '{}', which isn't configured",
+                    a.field,
+                    a.sensor
+                );
+            }
+            if a.above.is_none() && a.below.is_none() {
+                bail!(
+                    "Alert on '{}' of '{}' needs `above`, `below` or both",
+                    a.field,
+                    a.sensor
+                );
+            }
+            if !(a.hysteresis >= 0.0 && a.hysteresis.is_finite()) {
+                bail!(
+                    "Alert on '{}' of '{}': hysteresis must be 0 or more",
+                    a.field,
+                    a.sensor
+                );
+            }
+        }
+        Ok(())
     }
 }
 
@@ -84,6 +1654,32 @@ pub struct SensorConfig {
     pub name: String,
     #[serde(default = "default_true")]
     pub enabled: bool,
+    /// Stop the service if this sensor still fails after its init retries;
+    /// `false` marks it offline and carries on without it.
+    #[serde(default = "default_true")]
+    pub required: bool,
+    /// Extra init attempts (with backoff) before giving up
+    #[serde(default = "default_init_retries")]
+    pub init_retries: u32,
+    /// Reads first among sensors waiting for the same turn, highest first
+    /// (`sampling.read_strategy`)
+    #[serde(default)]
+    pub priority: i32,
+    /// Name of a `[[groups]]` entry
+    #[serde(default)]
+    pub group: Option<String>,
+    /// `group` when that group goes in the topic path; set by
+    /// [`AppConfig::resolve_groups`]
+    #[serde(skip)]
+    pub topic_group: Option<String>,
+    /// Fixed key/value attributes (`location = "front-left"`) published
+    /// under `tags` on INFO, for consumers to group and filter by
+    #[serde(default)]
+    pub tags: IndexMap<String, String>,
+    /// Names to publish driver channels under (`g_force_y =
+    /// "longitudinal_g"`); `""` leaves a channel out
+    #[serde(default)]
+    pub channel_map: IndexMap<String, String>,
     pub driver: String,
     pub connection: ConnectionConfig,
     /// Driver-specific settings stored as raw TOML so each driver can
@@ -91,10 +1687,30 @@ pub struct SensorConfig {
     pub settings: Option<toml::Value>,
 }
 
+/// Why `name` can't be one level of an MQTT topic, if it can't.  Spaces
+/// are allowed inside a name, as MQTT allows them.
+fn topic_level_problem(name: &str) -> Option<&'static str> {
+    if name.trim().is_empty() {
+        Some("is empty")
+    } else if name.contains(['/', '+', '#']) {
+        Some("can't contain '/', '+' or '#'")
+    } else if name.chars().any(char::is_control) {
+        Some("can't contain control characters")
+    } else if name != name.trim() {
+        Some("can't start or end with a space")
+    } else {
+        None
+    }
+}
+
 fn default_true() -> bool {
     true
 }
 
+fn default_init_retries() -> u32 {
+    2
+}
+
 /// Connection type — discriminated by the `type` field in TOML.
 ///
 /// Example:
@@ -118,6 +1734,9 @@ pub struct I2cConnectionConfig {
     #[serde(default = "default_i2c_device")]
     pub device: String,
     pub address: u16,
+    /// Name of an `[[i2c_buses]]` entry; overrides `device`
+    #[serde(default)]
+    pub bus: Option<String>,
 }
 
 fn default_i2c_device() -> String {
@@ -195,6 +1814,40 @@ impl ConnectionConfig {
 /// Environment variable format: `SENSORS_TO_MQTT__MQTT__HOST=broker.local`
 /// (double-underscore maps to each level of TOML nesting).
 pub fn load_configuration(path: Option<&str>) -> Result<AppConfig> {
+    let mut cfg: AppConfig = sources(path)?
+        .try_deserialize()
+        .context("Failed to deserialize configuration")?;
+    cfg.apply_persisted_settings()?;
+    cfg.expand_placeholders()?;
+    cfg.resolve_i2c_buses()?;
+    cfg.resolve_groups()?;
+    cfg.check_topic_names()?;
+    cfg.check_i2c_addresses()?;
+    cfg.check_transforms()?;
+    cfg.check_derivatives()?;
+    cfg.check_integrals()?;
+    cfg.check_differences()?;
+    cfg.check_aggregation()?;
+    cfg.check_bridge()?;
+    cfg.check_extremes()?;
+    cfg.check_anomalies()?;
+    cfg.check_fused_sensors()?;
+    cfg.check_alerts()?;
+    cfg.check_command_auth()?;
+
+    Ok(cfg)
+}
+
+/// The merged settings as written, before defaults and placeholders are
+/// applied — what diagnostic bundles include.
+pub fn load_raw_configuration(path: Option<&str>) -> Result<toml::Table> {
+    sources(path)?
+        .try_deserialize()
+        .context("Failed to read configuration as TOML")
+}
+
+/// The config files and environment overrides, in priority order.
+fn sources(path: Option<&str>) -> Result<Config> {
     let mut builder = Config::builder();
 
     if let Some(p) = path {
@@ -212,13 +1865,22 @@ pub fn load_configuration(path: Option<&str>) -> Result<AppConfig> {
             .try_parsing(true),
     );
 
-    let cfg: AppConfig = builder
-        .build()
-        .context("Failed to build configuration")?
-        .try_deserialize()
-        .context("Failed to deserialize configuration")?;
+    builder.build().context("Failed to build configuration")
+}
 
-    Ok(cfg)
+/// This machine's name, from the kernel on Linux or the environment
+/// elsewhere.
+fn hostname() -> Option<String> {
+    ["/proc/sys/kernel/hostname", "/etc/hostname"]
+        .iter()
+        .filter_map(|p| std::fs::read_to_string(p).ok())
+        .chain(
+            ["HOSTNAME", "COMPUTERNAME"]
+                .iter()
+                .filter_map(|v| std::env::var(v).ok()),
+        )
+        .map(|h| h.trim().to_string())
+        .find(|h| !h.is_empty())
 }
 
 // ---------------------------------------------------------------------------
@@ -238,6 +1900,8 @@ mod tests {
         assert_eq!(cfg.host, "localhost");
         assert!(cfg.enabled);
         assert_eq!(cfg.keep_alive_secs, 20);
+        assert!(cfg.auto_reconnect);
+        assert_eq!((cfg.reconnect_min_secs, cfg.reconnect_max_secs), (1, 60));
     }
 
     #[test]
@@ -259,13 +1923,194 @@ mod tests {
         assert_eq!(cfg.address(), "broker.local:8883");
     }
 
+    // --- I2C address conflicts ---
+
+    #[test]
+    fn test_i2c_address_conflict_rejected() {
+        let sensor = |name: &str, device: &str, enabled: bool| SensorConfig {
+            name: name.into(),
+            enabled,
+            required: true,
+            init_retries: 0,
+            priority: 0,
+            group: None,
+            topic_group: None,
+            tags: Default::default(),
+            channel_map: Default::default(),
+            driver: "bme280".into(),
+            connection: ConnectionConfig::I2c(I2cConnectionConfig {
+                device: device.into(),
+                address: 0x76,
+                bus: None,
+            }),
+            settings: None,
+        };
+        let mut cfg = AppConfig {
+            sensors: vec![
+                sensor("a", "/dev/i2c-1", true),
+                sensor("b", "/dev/i2c-0", true),
+                sensor("spare", "/dev/i2c-1", false),
+            ],
+            ..Default::default()
+        };
+        assert!(cfg.check_i2c_addresses().is_ok());
+
+        cfg.sensors.push(sensor("c", "/dev/i2c-1", true));
+        let msg = cfg.check_i2c_addresses().unwrap_err().to_string();
+        assert!(msg.contains("'a' and 'c'"), "{}", msg);
+        assert!(msg.contains("0x76"), "{}", msg);
+    }
+
+    #[test]
+    fn test_topic_names_checked() {
+        let sensor = |name: &str| -> SensorConfig {
+            toml::from_str(&format!(
+                "name = '{}'\ndriver = 'synthetic'\n[connection]\ntype = 'gpio'\npin = 17",
+                name
+            ))
+            .unwrap()
+        };
+        let mut cfg = AppConfig {
+            sensors: vec![sensor("Front IMU"), sensor("cabin")],
+            ..Default::default()
+        };
+        assert!(cfg.check_topic_names().is_ok());
+
+        for bad in ["imu/1", "imu+", "#", "", " cabin", "imu\n"] {
+            cfg.sensors[1].name = bad.into();
+            let msg = cfg.check_topic_names().unwrap_err().to_string();
+            assert!(msg.starts_with("Sensor name '"), "{}", msg);
+        }
+        cfg.sensors[1].name = "Front IMU".into();
+        let msg = cfg.check_topic_names().unwrap_err().to_string();
+        assert!(msg.contains("more than once"), "{}", msg);
+
+        cfg.sensors[1].name = "cabin".into();
+        cfg.mqtt.base_topic = "/fleet/+/SENSORS".into();
+        let msg = cfg.check_topic_names().unwrap_err().to_string();
+        assert!(msg.contains("mqtt.base_topic"), "{}", msg);
+    }
+
+    #[test]
+    fn test_bridge_channels_need_json() {
+        let mut cfg = AppConfig::default();
+        cfg.mqtt.bridge.enabled = true;
+        cfg.mqtt.bridge.channels = vec!["g_force_x".into()];
+        cfg.mqtt.payload_format = PayloadFormat::Protobuf;
+        let msg = cfg.check_bridge().unwrap_err().to_string();
+        assert!(msg.contains("mqtt.bridge.channels"), "{}", msg);
+
+        cfg.mqtt.payload_format = PayloadFormat::Json;
+        assert!(cfg.check_bridge().is_ok());
+        cfg.mqtt.payload_format = PayloadFormat::Cbor;
+        cfg.mqtt.bridge.channels.clear();
+        assert!(cfg.check_bridge().is_ok());
+    }
+
+    #[test]
+    fn test_fused_sensors_checked() {
+        let mut cfg: AppConfig = toml::from_str(
+            r#"
+[[sensors]]
+name = "front"
+driver = "synthetic"
+connection = { type = "gpio", pin = 17 }
+
+[[sensors]]
+name = "rear"
+driver = "synthetic"
+connection = { type = "gpio", pin = 18 }
+
+[[fused_sensors]]
+name = "chassis"
+sources = ["front", "rear"]
+"#,
+        )
+        .unwrap();
+        assert!(cfg.check_fused_sensors().is_ok());
+        assert_eq!(cfg.fused_sensors[0].max_age_ms, 500);
+
+        cfg.fused_sensors[0].sources = vec!["front".into(), "front".into()];
+        let msg = cfg.check_fused_sensors().unwrap_err().to_string();
+        assert!(msg.contains("twice"), "{}", msg);
+
+        cfg.fused_sensors[0].sources = vec!["front".into(), "cabin".into()];
+        let msg = cfg.check_fused_sensors().unwrap_err().to_string();
+        assert!(msg.contains("'cabin' isn't a configured sensor"), "{}", msg);
+
+        cfg.fused_sensors[0].sources = vec!["front".into(), "rear".into()];
+        cfg.fused_sensors[0].name = "rear".into();
+        let msg = cfg.check_fused_sensors().unwrap_err().to_string();
+        assert!(msg.contains("more than once"), "{}", msg);
+    }
+
+    #[test]
+    fn test_differences_checked() {
+        let mut cfg = AppConfig {
+            sensors: toml::from_str::<AppConfig>(
+                "[[sensors]]\nname = \"FL v2.1\"\ndriver = \"synthetic\"\n\
+                 connection = { type = \"gpio\", pin = 17 }",
+            )
+            .unwrap()
+            .sensors,
+            differences: vec![DifferenceConfig {
+                name: "delta".into(),
+                from: "FL v2.1.temp".into(),
+                minus: "FL v2.1.ambient".into(),
+                max_age_ms: 1000,
+                unit: String::new(),
+            }],
+            ..Default::default()
+        };
+        assert!(cfg.check_differences().is_ok());
+        assert_eq!(cfg.differences[0].sensor(), "FL v2.1");
+
+        cfg.differences[0].minus = "FR.temp".into();
+        let msg = cfg.check_differences().unwrap_err().to_string();
+        assert!(msg.contains("sensor 'FR'"), "{}", msg);
+
+        cfg.differences[0].minus = "temp".into();
+        let msg = cfg.check_differences().unwrap_err().to_string();
+        assert!(msg.contains("<sensor>.<channel>"), "{}", msg);
+    }
+
     // --- ConnectionConfig to_display ---
 
+    #[test]
+    fn test_placeholders_expand_in_client_id_and_base_topic() {
+        let mut cfg = AppConfig {
+            device_id: "van-2".into(),
+            ..Default::default()
+        };
+        cfg.mqtt.client_id = "sensors-${device_id}".into();
+        cfg.mqtt.base_topic = "/fleet/${device_id}/SENSORS/v${schema_version}".into();
+        cfg.mqtt.bridge.base_topic = "cars/${device_id}".into();
+        cfg.expand_placeholders().unwrap();
+        assert_eq!(cfg.mqtt.client_id, "sensors-van-2");
+        assert_eq!(cfg.mqtt.base_topic, "/fleet/van-2/SENSORS/v1");
+        assert_eq!(cfg.mqtt.bridge.base_topic, "cars/van-2");
+
+        // Without placeholders nothing changes
+        let mut cfg = AppConfig::default();
+        cfg.expand_placeholders().unwrap();
+        assert_eq!(cfg.mqtt.client_id, "sensors-to-mqtt");
+
+        cfg.mqtt.base_topic = "/fleet/${vin}".into();
+        let err = cfg.expand_placeholders().unwrap_err().to_string();
+        assert!(err.contains("${vin}"), "{}", err);
+        assert!(err.contains("${schema_version}"), "{}", err);
+
+        cfg.mqtt.schema_version = 99;
+        let err = cfg.expand_placeholders().unwrap_err().to_string();
+        assert!(err.contains("schema_version 99"), "{}", err);
+    }
+
     #[test]
     fn test_i2c_display() {
         let c = ConnectionConfig::I2c(I2cConnectionConfig {
             device: "/dev/i2c-1".into(),
             address: 0x68,
+            bus: None,
         });
         assert!(c.to_display().contains("0x68"));
         assert!(c.to_display().contains("i2c-1"));
@@ -379,6 +2224,33 @@ active_low = false
         }
     }
 
+    #[test]
+    fn test_default_theme_is_dark() {
+        assert_eq!(AppConfig::default().ui.theme, ThemeName::Dark);
+        assert_eq!(AppConfig::default().service.run_mode, RunMode::Auto);
+        assert_eq!(AppConfig::default().ui.export_dir, ".");
+    }
+
+    #[test]
+    fn test_load_ui_theme_toml() {
+        use std::io::Write;
+        let mut f = tempfile::Builder::new().suffix(".toml").tempfile().unwrap();
+        write!(
+            f,
+            r#"
+[ui]
+theme = "high_contrast"
+
+[service]
+run_mode = "daemon"
+"#
+        )
+        .unwrap();
+        let cfg = load_configuration(Some(f.path().to_str().unwrap())).unwrap();
+        assert_eq!(cfg.ui.theme, ThemeName::HighContrast);
+        assert_eq!(cfg.service.run_mode, RunMode::Daemon);
+    }
+
     #[test]
     fn test_load_nonexistent_required_file_fails() {
         let result = load_configuration(Some("/nonexistent/path/config.toml"));
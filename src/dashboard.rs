@@ -0,0 +1,132 @@
+//! Retained `<base_topic>/DASHBOARD` document (`[dashboard]`).
+//!
+//! One JSON object with the latest value of every channel of every sensor,
+//! the same data as the TUI overview.  A web page can subscribe to this
+//! single topic instead of reassembling state from a dozen per-sensor
+//! topics, and since it is retained the page fills in as soon as it
+//! connects.  It is rebuilt every `interval_ms` and only published when
+//! something in it changed.
+
+use std::time::Duration;
+
+use serde_json::{Map, Value, json};
+use tokio_util::sync::CancellationToken;
+
+use crate::config::DashboardConfig;
+use crate::models::{AppState, SharedState};
+use crate::mqtt_handler::MqttHandle;
+use crate::sensors::ChannelValue;
+use crate::service::round_to;
+
+/// The document for the current state, sensors in tab order.
+pub fn dashboard_payload(s: &AppState, precision: Option<u32>) -> Value {
+    let mut sensors = Map::new();
+    for name in s.sensor_names() {
+        let Some(st) = s.sensor_statuses.get(&name) else {
+            continue;
+        };
+        let mut entry = json!({
+            "driver": st.driver,
+            "online": st.connected,
+        });
+        if let Some(data) = s.sensor_data.get(&name) {
+            let mut channels = Map::new();
+            for (key, v) in &data.fields {
+                channels.insert(key.to_string(), json!(round_to(*v, precision)));
+            }
+            for (key, v) in &data.values {
+                let v = match v {
+                    ChannelValue::Float(f) => json!(round_to(*f, precision)),
+                    other => json!(other),
+                };
+                channels.insert(key.clone(), v);
+            }
+            entry["timestamp"] = json!(data.timestamp.to_rfc3339());
+            entry["channels"] = Value::Object(channels);
+            if !data.quality.is_empty() {
+                entry["quality"] = json!(data.quality);
+            }
+        }
+        sensors.insert(name, entry);
+    }
+    json!({ "paused": s.paused, "sensors": sensors })
+}
+
+/// Publish the document every `interval_ms` until `cancel` fires.
+pub fn spawn_dashboard(
+    cfg: &DashboardConfig,
+    state: SharedState,
+    mqtt: MqttHandle,
+    base_topic: &str,
+    cancel: CancellationToken,
+) {
+    let topic: std::sync::Arc<str> = format!("{}/DASHBOARD", base_topic).into();
+    let mut tick = tokio::time::interval(Duration::from_millis(cfg.interval_ms.max(100)));
+    tick.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+    tokio::spawn(async move {
+        let mut last = String::new();
+        loop {
+            tokio::select! {
+                _ = cancel.cancelled() => return,
+                _ = tick.tick() => {}
+            }
+            let payload =
+                dashboard_payload(&*state.read().await, mqtt.payload_precision).to_string();
+            if payload != last {
+                mqtt.publish_retained(std::sync::Arc::clone(&topic), payload.as_str())
+                    .await;
+                last = payload;
+            }
+        }
+    });
+}
+
+// ---------------------------------------------------------------------------
+// Unit tests
+// ---------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{CalibrationState, SensorStatus};
+    use crate::sensors::{Quality, SensorData};
+
+    #[test]
+    fn test_payload_lists_latest_values() {
+        let mut s = AppState::new(String::new(), true, 10);
+        for (name, driver) in [("imu", "mpu6500"), ("gps", "nmea")] {
+            s.sensor_statuses.insert(
+                name.into(),
+                SensorStatus {
+                    name: name.into(),
+                    driver: driver.into(),
+                    connection_display: String::new(),
+                    group: None,
+                    enabled: true,
+                    connected: name == "imu",
+                    last_error: None,
+                    stuck: Vec::new(),
+                    calibration: CalibrationState::Idle,
+                    fields: Vec::new(),
+                },
+            );
+        }
+        let mut data = SensorData::new([("g_force_x".into(), 0.123456)].into_iter().collect());
+        data.set_value("fix", ChannelValue::Int(3));
+        data.set_quality("fix", Quality::Stale);
+        s.sensor_data.insert("imu".into(), data);
+
+        let doc = dashboard_payload(&s, Some(3));
+        let sensors = doc["sensors"].as_object().unwrap();
+        assert_eq!(sensors.keys().collect::<Vec<_>>(), ["gps", "imu"]);
+        assert_eq!(
+            doc["sensors"]["gps"],
+            json!({"driver": "nmea", "online": false})
+        );
+        let imu = &doc["sensors"]["imu"];
+        assert_eq!(imu["channels"], json!({"g_force_x": 0.123, "fix": 3}));
+        assert_eq!(imu["quality"], json!({"fix": "stale"}));
+        assert!(imu["timestamp"].is_string());
+        assert_eq!(doc["paused"], false);
+    }
+}
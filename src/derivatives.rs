@@ -0,0 +1,181 @@
+//! Rate-of-change channels (`[[derivatives]]`).
+//!
+//! Each entry adds the time derivative of one of a sensor's channels, such
+//! as jerk from acceleration or how fast a temperature is rising.  The rate
+//! is the least-squares slope of the readings in the last `window_secs`,
+//! with time taken from the readings' timestamps, so an uneven or slowed
+//! sample rate doesn't skew it and the window smooths sample noise that a
+//! plain difference would amplify.  The result is published like any other
+//! channel, on `DERIVED`.
+
+use std::collections::VecDeque;
+
+use chrono::{DateTime, Utc};
+
+use crate::config::DerivativeConfig;
+use crate::sensors::{FieldDescriptor, Quality, SensorData, VizType};
+
+/// Descriptors for the channels `cfgs` add.  Keys and labels come from the
+/// config, so they are leaked once per call (at sensor start).
+pub fn field_descriptors(cfgs: &[DerivativeConfig]) -> Vec<FieldDescriptor> {
+    let leak = |s: String| -> &'static str { Box::leak(s.into_boxed_str()) };
+    cfgs.iter()
+        .enumerate()
+        .map(|(i, d)| FieldDescriptor {
+            key: leak(d.name.clone()),
+            label: leak(format!("d({})/dt", d.field)),
+            viz: VizType::Numeric {
+                unit: leak(d.unit.clone()),
+            },
+            range: None,
+            group: (i == 0).then_some("RATE OF CHANGE"),
+        })
+        .collect()
+}
+
+/// The `[[derivatives]]` of one sensor.
+pub struct Differentiator {
+    channels: Vec<Channel>,
+}
+
+struct Channel {
+    cfg: DerivativeConfig,
+    /// `(timestamp, value)` of the readings in the window, oldest first
+    samples: VecDeque<(DateTime<Utc>, f64)>,
+}
+
+impl Differentiator {
+    pub fn new(cfgs: Vec<DerivativeConfig>) -> Self {
+        Self {
+            channels: cfgs
+                .into_iter()
+                .map(|cfg| Channel {
+                    cfg,
+                    samples: VecDeque::new(),
+                })
+                .collect(),
+        }
+    }
+
+    /// Append each configured derivative to `data`.  Readings without the
+    /// source channel get none; until the window holds two readings the
+    /// channel is 0 and flagged invalid.
+    pub fn process(&mut self, data: &mut SensorData) {
+        for ch in &mut self.channels {
+            let Some(&value) = data.fields.get(ch.cfg.field.as_str()) else {
+                continue;
+            };
+            // A clock step backwards leaves nothing to compare against
+            if ch.samples.back().is_some_and(|&(t, _)| t >= data.timestamp) {
+                ch.samples.clear();
+            }
+            ch.samples.push_back((data.timestamp, value));
+            while ch.samples.len() > 2
+                && secs(ch.samples[1].0, data.timestamp) >= ch.cfg.window_secs
+            {
+                ch.samples.pop_front();
+            }
+
+            let key = ch.cfg.name.as_str();
+            match slope(&ch.samples) {
+                Some(rate) => {
+                    data.fields.insert(key.to_owned().into(), rate * ch.cfg.scale);
+                }
+                None => {
+                    data.fields.insert(key.to_owned().into(), 0.0);
+                    data.set_quality(key, Quality::Invalid);
+                }
+            }
+        }
+    }
+}
+
+fn secs(from: DateTime<Utc>, to: DateTime<Utc>) -> f64 {
+    (to - from).num_microseconds().unwrap_or(i64::MAX) as f64 / 1e6
+}
+
+/// Least-squares slope in units per second, times relative to the oldest
+/// sample to keep the sums small.
+fn slope(samples: &VecDeque<(DateTime<Utc>, f64)>) -> Option<f64> {
+    let &(t0, _) = samples.front()?;
+    let n = samples.len() as f64;
+    let (mut st, mut sy, mut stt, mut sty) = (0.0, 0.0, 0.0, 0.0);
+    for &(t, y) in samples {
+        let t = secs(t0, t);
+        st += t;
+        sy += y;
+        stt += t * t;
+        sty += t * y;
+    }
+    let denom = n * stt - st * st;
+    (n >= 2.0 && denom > f64::EPSILON).then(|| (n * sty - st * sy) / denom)
+}
+
+// ---------------------------------------------------------------------------
+// Unit tests
+// ---------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+
+    fn config(window_secs: f64) -> DerivativeConfig {
+        DerivativeConfig {
+            sensor: "imu".into(),
+            field: "accel_x".into(),
+            name: "jerk_x".into(),
+            window_secs,
+            scale: 1.0,
+            unit: "G/s".into(),
+        }
+    }
+
+    fn reading(t: DateTime<Utc>, accel_x: f64) -> SensorData {
+        let mut d = SensorData::new([("accel_x".into(), accel_x)].into_iter().collect());
+        d.timestamp = t;
+        d
+    }
+
+    #[test]
+    fn test_rate_uses_timestamps() {
+        let mut diff = Differentiator::new(vec![config(0.0)]);
+        let t0 = Utc::now();
+
+        let mut first = reading(t0, 1.0);
+        diff.process(&mut first);
+        assert_eq!(first.quality.get("jerk_x"), Some(&Quality::Invalid));
+
+        // 0.5 G in 250 ms, however long the gap between readings is
+        let mut d = reading(t0 + Duration::milliseconds(250), 1.5);
+        diff.process(&mut d);
+        assert!((d.fields["jerk_x"] - 2.0).abs() < 1e-9);
+        assert!(d.quality.is_empty());
+
+        let mut d = SensorData::new(Default::default());
+        diff.process(&mut d);
+        assert!(!d.fields.contains_key("jerk_x"), "no source, no derivative");
+    }
+
+    #[test]
+    fn test_window_smooths_noise() {
+        let t0 = Utc::now();
+        let mut raw = Differentiator::new(vec![config(0.0)]);
+        let mut smooth = Differentiator::new(vec![config(0.2)]);
+        let (mut worst_raw, mut worst_smooth) = (0.0f64, 0.0f64);
+        for i in 0..100 {
+            // A 3 units/s ramp with ±0.01 alternating noise at 100 Hz
+            let t = t0 + Duration::milliseconds(i * 10);
+            let v = 0.03 * i as f64 + if i % 2 == 0 { 0.01 } else { -0.01 };
+            let (mut a, mut b) = (reading(t, v), reading(t, v));
+            raw.process(&mut a);
+            smooth.process(&mut b);
+            if i >= 25 {
+                worst_raw = worst_raw.max((a.fields["jerk_x"] - 3.0).abs());
+                worst_smooth = worst_smooth.max((b.fields["jerk_x"] - 3.0).abs());
+            }
+        }
+        assert!(worst_raw > 1.5, "two-point difference: {}", worst_raw);
+        assert!(worst_smooth < 0.1, "20-reading fit: {}", worst_smooth);
+    }
+}
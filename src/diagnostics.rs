@@ -0,0 +1,320 @@
+//! Diagnostic bundles for bug reports (TUI `D` key).
+//!
+//! One `.tar` in `ui.export_dir` with what's needed to look into a problem
+//! on an install nobody can get to: the settings with credentials redacted,
+//! every sensor's status, latest reading and history, the recent log, and
+//! the version and features the binary was built with.  It's a plain ustar
+//! archive written here rather than with an archive crate; `tar xf` opens
+//! it anywhere.
+
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+use anyhow::{Context, Result, ensure};
+use chrono::Local;
+
+use crate::export::{file_stem, write_history_csv};
+use crate::tui::snapshot::StateSnapshot;
+
+/// The settings bundles include, already redacted; recorded at startup.
+static CONFIG: OnceLock<String> = OnceLock::new();
+
+/// Keys whose values are replaced, wherever they appear in the settings.
+const SECRET_KEYS: &[&str] = &["password", "username", "token", "secret"];
+
+/// Cargo features this binary was built with.
+const FEATURES: &[(&str, bool)] = &[
+    ("tui", cfg!(feature = "tui")),
+    ("mqtt", cfg!(feature = "mqtt")),
+    ("f32", cfg!(feature = "f32")),
+    ("mpu6500", cfg!(feature = "mpu6500")),
+    ("bmp280", cfg!(feature = "bmp280")),
+    ("bme280", cfg!(feature = "bme280")),
+    ("sht31", cfg!(feature = "sht31")),
+    ("bh1750", cfg!(feature = "bh1750")),
+    ("ina219", cfg!(feature = "ina219")),
+    ("ads1115", cfg!(feature = "ads1115")),
+    ("gpio-button", cfg!(feature = "gpio-button")),
+    ("sds011", cfg!(feature = "sds011")),
+];
+
+/// Keep the settings (see [`crate::config::load_raw_configuration`]) for
+/// later bundles, with credentials redacted.
+pub fn record_config(mut settings: toml::Table) {
+    redact(&mut settings);
+    let text = toml::to_string_pretty(&settings)
+        .unwrap_or_else(|e| format!("# settings could not be written: {}\n", e));
+    let _ = CONFIG.set(text);
+}
+
+fn redact(table: &mut toml::Table) {
+    for (key, value) in table.iter_mut() {
+        let key = key.to_ascii_lowercase();
+        if SECRET_KEYS.iter().any(|s| key.contains(s)) {
+            *value = toml::Value::String("<redacted>".into());
+        } else {
+            redact_value(value);
+        }
+    }
+}
+
+fn redact_value(value: &mut toml::Value) {
+    match value {
+        toml::Value::Table(t) => redact(t),
+        toml::Value::Array(a) => a.iter_mut().for_each(redact_value),
+        _ => {}
+    }
+}
+
+/// Write a bundle of `snap` to `<dir>/diagnostics_<YYYYmmdd_HHMMSS>.tar`,
+/// creating `dir` if needed.  Returns the path written.
+pub fn write_bundle(dir: &Path, snap: StateSnapshot) -> Result<PathBuf> {
+    std::fs::create_dir_all(dir)
+        .with_context(|| format!("Failed to create export directory {}", dir.display()))?;
+    let root = format!("diagnostics_{}", Local::now().format("%Y%m%d_%H%M%S"));
+    let path = dir.join(format!("{}.tar", root));
+    let file = std::fs::File::create(&path)
+        .with_context(|| format!("Failed to create {}", path.display()))?;
+    let mut out = std::io::BufWriter::new(file);
+    write_archive(&mut out, &root, snap)?;
+    out.flush()?;
+    Ok(path)
+}
+
+/// The bundle's files, under the directory `root`.
+fn write_archive(out: impl Write, root: &str, mut snap: StateSnapshot) -> Result<()> {
+    let mut tar = Tar::new(out);
+
+    let features: Vec<&str> = FEATURES
+        .iter()
+        .filter(|(_, on)| *on)
+        .map(|(name, _)| *name)
+        .collect();
+    let version = format!(
+        "sensors-to-mqtt {}\ntarget: {} {}\nfeatures: {}\nwritten: {}\n",
+        env!("CARGO_PKG_VERSION"),
+        std::env::consts::OS,
+        std::env::consts::ARCH,
+        features.join(" "),
+        Local::now().to_rfc3339(),
+    );
+    tar.append(&format!("{}/version.txt", root), version.as_bytes())?;
+
+    let config = CONFIG
+        .get()
+        .map_or("# settings were not recorded\n", String::as_str);
+    tar.append(&format!("{}/config.toml", root), config.as_bytes())?;
+
+    let mut log = snap.logs.join("\n");
+    log.push('\n');
+    tar.append(&format!("{}/log.txt", root), log.as_bytes())?;
+
+    // Histories as CSV, the rest of the state as one JSON document
+    let histories = std::mem::take(&mut snap.sensor_history);
+    for (name, history) in snap.sensor_names.iter().zip(histories) {
+        let Some(history) = history else {
+            continue;
+        };
+        let mut csv = Vec::new();
+        write_history_csv(&history, &mut csv)?;
+        tar.append(&format!("{}/history/{}.csv", root, file_stem(name)), &csv)?;
+    }
+    snap.logs.clear();
+    let state = serde_json::to_vec_pretty(&snap)?;
+    tar.append(&format!("{}/state.json", root), &state)?;
+
+    tar.finish()
+}
+
+// ---------------------------------------------------------------------------
+// ustar writer
+// ---------------------------------------------------------------------------
+
+const BLOCK: usize = 512;
+
+/// Regular files only, every one dated now and owned by root.
+struct Tar<W: Write> {
+    out: W,
+    mtime: u64,
+}
+
+impl<W: Write> Tar<W> {
+    fn new(out: W) -> Self {
+        Self {
+            out,
+            mtime: Local::now().timestamp().max(0) as u64,
+        }
+    }
+
+    fn append(&mut self, name: &str, data: &[u8]) -> Result<()> {
+        self.out
+            .write_all(&header(name, data.len() as u64, self.mtime)?)?;
+        self.out.write_all(data)?;
+        let pad = (BLOCK - data.len() % BLOCK) % BLOCK;
+        self.out.write_all(&[0; BLOCK][..pad])?;
+        Ok(())
+    }
+
+    /// Two zero blocks mark the end of the archive.
+    fn finish(mut self) -> Result<()> {
+        self.out.write_all(&[0; 2 * BLOCK])?;
+        Ok(())
+    }
+}
+
+fn header(name: &str, size: u64, mtime: u64) -> Result<[u8; BLOCK]> {
+    ensure!(name.len() <= 100, "archive path too long: {}", name);
+    ensure!(size < 1 << 33, "{} is too large for the archive", name);
+    let mut h = [0u8; BLOCK];
+    h[..name.len()].copy_from_slice(name.as_bytes());
+    octal(&mut h[100..108], 0o644);
+    octal(&mut h[108..116], 0);
+    octal(&mut h[116..124], 0);
+    octal(&mut h[124..136], size);
+    octal(&mut h[136..148], mtime);
+    h[156] = b'0';
+    h[257..263].copy_from_slice(b"ustar\0");
+    h[263..265].copy_from_slice(b"00");
+    // The checksum is summed with its own field as spaces, and written as
+    // six digits, a NUL and one of those spaces.
+    h[148..156].fill(b' ');
+    let sum: u64 = h.iter().map(|&b| b as u64).sum();
+    octal(&mut h[148..155], sum);
+    Ok(h)
+}
+
+/// Zero-padded octal digits and a NUL, filling `field`.
+fn octal(field: &mut [u8], value: u64) {
+    let text = format!("{:0width$o}\0", value, width = field.len() - 1);
+    field.copy_from_slice(text.as_bytes());
+}
+
+// ---------------------------------------------------------------------------
+// Unit tests
+// ---------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{AppState, SensorHistory, SensorStatus};
+    use crate::sensors::SensorData;
+    use std::collections::{HashMap, VecDeque};
+    use std::sync::{Arc, Mutex};
+
+    /// `(name, contents)` of each file, checking every header's checksum.
+    fn untar(mut bytes: &[u8]) -> Vec<(String, Vec<u8>)> {
+        let mut files = Vec::new();
+        while bytes[..BLOCK].iter().any(|&b| b != 0) {
+            let (h, rest) = bytes.split_at(BLOCK);
+            let field = |r: std::ops::Range<usize>| {
+                let text = std::str::from_utf8(&h[r]).unwrap();
+                u64::from_str_radix(text.trim_matches(['\0', ' ']), 8).unwrap()
+            };
+            let sum: u64 = h
+                .iter()
+                .enumerate()
+                .map(|(i, &b)| {
+                    if (148..156).contains(&i) {
+                        32
+                    } else {
+                        b as u64
+                    }
+                })
+                .sum();
+            assert_eq!(field(148..156), sum, "checksum");
+            assert_eq!(&h[257..263], b"ustar\0");
+
+            let name = std::str::from_utf8(&h[..100]).unwrap();
+            let size = field(124..136) as usize;
+            files.push((
+                name.trim_end_matches('\0').to_string(),
+                rest[..size].to_vec(),
+            ));
+            bytes = &rest[size.div_ceil(BLOCK) * BLOCK..];
+        }
+        files
+    }
+
+    #[test]
+    fn test_bundle_is_a_tar_of_state_history_and_log() {
+        let mut app = AppState::new("disabled".into(), false, 10);
+        app.sensor_statuses.insert(
+            "engine bay".into(),
+            SensorStatus {
+                name: "engine bay".into(),
+                driver: "synthetic".into(),
+                connection_display: String::new(),
+                group: None,
+                enabled: true,
+                connected: true,
+                last_error: None,
+                stuck: Vec::new(),
+                calibration: Default::default(),
+                fields: Vec::new(),
+            },
+        );
+        let mut history = SensorHistory::new(10);
+        history.push(&SensorData::new(
+            [("temp".into(), 21.5)].into_iter().collect(),
+        ));
+        app.sensor_history.insert("engine bay".into(), history);
+        let logs = Arc::new(Mutex::new(VecDeque::from(["INFO started".to_string()])));
+        let snap = StateSnapshot::from(&app, &logs);
+
+        let mut bytes = Vec::new();
+        write_archive(&mut bytes, "diag", snap).unwrap();
+        assert_eq!(bytes.len() % BLOCK, 0);
+        let files: HashMap<_, _> = untar(&bytes).into_iter().collect();
+
+        let names: Vec<&str> = {
+            let mut n: Vec<&str> = files.keys().map(String::as_str).collect();
+            n.sort();
+            n
+        };
+        assert_eq!(
+            names,
+            [
+                "diag/config.toml",
+                "diag/history/engine_bay.csv",
+                "diag/log.txt",
+                "diag/state.json",
+                "diag/version.txt",
+            ]
+        );
+        assert_eq!(files["diag/log.txt"], b"INFO started\n");
+        let csv = String::from_utf8(files["diag/history/engine_bay.csv"].clone()).unwrap();
+        assert!(
+            csv.starts_with("timestamp,temp\n") && csv.ends_with(",21.5\n"),
+            "{}",
+            csv
+        );
+        let state: serde_json::Value = serde_json::from_slice(&files["diag/state.json"]).unwrap();
+        assert_eq!(state["sensor_names"][0], "engine bay");
+        assert_eq!(state["sensor_history"], serde_json::json!([]));
+    }
+
+    #[test]
+    fn test_credentials_are_redacted() {
+        let mut settings: toml::Table = toml::from_str(
+            r#"
+            [mqtt]
+            host = "broker.local"
+            username = "car"
+            password = "hunter2"
+            [mqtt.bridge]
+            password = "also secret"
+            [[sensors]]
+            name = "imu"
+            api_token = "abc"
+            "#,
+        )
+        .unwrap();
+        redact(&mut settings);
+        let text = toml::to_string(&settings).unwrap();
+        assert!(text.contains("broker.local"));
+        for secret in ["car\"", "hunter2", "also secret", "abc"] {
+            assert!(!text.contains(secret), "{} leaked:\n{}", secret, text);
+        }
+    }
+}
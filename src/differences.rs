@@ -0,0 +1,135 @@
+//! Channels across sensors (`[[differences]]`).
+//!
+//! Each entry adds one channel minus another, such as the temperature
+//! difference between the left and right brakes, each measured by its own
+//! sensor:
+//!
+//! ```text
+//! brake_temp_delta = FL.object_temp_c − FR.object_temp_c
+//! ```
+//!
+//! Each sensor reads in its own task, so there is no cycle both operands
+//! belong to.  The difference is worked out with every reading of the
+//! `from` sensor and published with it; a `minus` channel of another sensor
+//! comes from that sensor's latest reading, which can be up to one of its
+//! sample intervals older.  A `minus` reading more than `max_age_ms` older
+//! than the `from` one is dropped, as though there were none: the
+//! difference is then 0 and flagged invalid, rather than mixing values read
+//! far apart.
+
+use std::collections::HashMap;
+
+use crate::config::{DifferenceConfig, channel_ref};
+use crate::fusion::is_fresh;
+use crate::sensors::{FieldDescriptor, Quality, SensorData, VizType};
+
+/// Descriptors for the channels `cfgs` add.  Keys and labels come from the
+/// config, so they are leaked once per call (at sensor start).
+pub fn field_descriptors(cfgs: &[DifferenceConfig]) -> Vec<FieldDescriptor> {
+    let leak = |s: String| -> &'static str { Box::leak(s.into_boxed_str()) };
+    cfgs.iter()
+        .enumerate()
+        .map(|(i, d)| FieldDescriptor {
+            key: leak(d.name.clone()),
+            label: leak(format!("{} − {}", d.from, d.minus)),
+            viz: VizType::Numeric {
+                unit: leak(d.unit.clone()),
+            },
+            range: None,
+            group: (i == 0).then_some("DIFFERENCES"),
+        })
+        .collect()
+}
+
+/// Append each of `cfgs`, all with `from` on sensor `name`, to `data`, that
+/// sensor's reading.  `latest` holds every sensor's latest reading.
+/// Readings without the `from` channel get no difference, and `minus`
+/// readings more than `max_age_ms` older are left out.
+pub fn apply(
+    cfgs: &[DifferenceConfig],
+    name: &str,
+    data: &mut SensorData,
+    latest: &HashMap<String, SensorData>,
+) {
+    for d in cfgs {
+        let Some(&from) = channel_ref(&d.from).and_then(|(_, c)| data.fields.get(c)) else {
+            continue;
+        };
+        let from_quality = channel_ref(&d.from).map_or(Quality::Good, |(_, c)| data.quality(c));
+        let minus = channel_ref(&d.minus).and_then(|(sensor, channel)| {
+            let other = if sensor == name {
+                &*data
+            } else {
+                latest
+                    .get(sensor)
+                    .filter(|o| is_fresh(o, data.timestamp, d.max_age_ms))?
+            };
+            Some((*other.fields.get(channel)?, other.quality(channel)))
+        });
+
+        let (value, quality) = match minus {
+            Some((minus, q)) if from_quality == Quality::Good => (from - minus, q),
+            Some((minus, _)) => (from - minus, from_quality),
+            None => (0.0, Quality::Invalid),
+        };
+        data.fields.insert(d.name.clone().into(), value);
+        data.set_quality(&d.name, quality);
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Unit tests
+// ---------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn reading(ms: i64, temp: f64) -> SensorData {
+        let mut data = SensorData::new([("temp".into(), temp)].into_iter().collect());
+        data.timestamp = chrono::DateTime::from_timestamp(1_700_000_000, 0).unwrap()
+            + chrono::Duration::milliseconds(ms);
+        data
+    }
+
+    #[test]
+    fn test_difference_across_sensors() {
+        let cfgs: Vec<DifferenceConfig> = vec![
+            toml::from_str("name = \"delta\"\nfrom = \"FL.temp\"\nminus = \"FR.temp\"").unwrap(),
+        ];
+        let mut latest = HashMap::new();
+
+        // No reading from FR yet
+        let mut data = reading(0, 80.0);
+        apply(&cfgs, "FL", &mut data, &latest);
+        assert_eq!(data.fields["delta"], 0.0);
+        assert_eq!(data.quality("delta"), Quality::Invalid);
+
+        latest.insert("FR".to_string(), reading(0, 65.5));
+        let mut data = reading(200, 80.0);
+        apply(&cfgs, "FL", &mut data, &latest);
+        assert_eq!(data.fields["delta"], 14.5);
+        assert_eq!(data.quality("delta"), Quality::Good);
+
+        // A stale FR reading is passed on with its quality
+        latest
+            .get_mut("FR")
+            .unwrap()
+            .set_quality("temp", Quality::Stale);
+        let mut data = reading(1000, 81.0);
+        apply(&cfgs, "FL", &mut data, &latest);
+        assert_eq!(data.fields["delta"], 15.5);
+        assert_eq!(data.quality("delta"), Quality::Stale);
+
+        // FR stopped reading a while ago, so its reading is dropped
+        let mut data = reading(1500, 81.0);
+        apply(&cfgs, "FL", &mut data, &latest);
+        assert_eq!(data.fields["delta"], 0.0);
+        assert_eq!(data.quality("delta"), Quality::Invalid);
+
+        // A reading without the channel is left alone
+        let mut data = SensorData::default();
+        apply(&cfgs, "FL", &mut data, &latest);
+        assert!(!data.fields.contains_key("delta"));
+    }
+}
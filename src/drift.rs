@@ -0,0 +1,217 @@
+//! Roll/pitch dead-reckoning drift report.
+//!
+//! Integrates the gyro roll and pitch rates and compares the result with
+//! the roll and pitch implied by the accelerometer's gravity vector.  Over
+//! each window the gap between the two is fitted with a straight line; its
+//! slope is the drift rate, published as `roll_drift` / `pitch_drift` in
+//! °/min.  The gyro angles are re-synced at the start of every window, so a
+//! report reflects recent filter settings only.
+//!
+//! Accelerometer angles are only trustworthy while the sensor isn't being
+//! accelerated, so read the report from a still or steadily moving sensor.
+
+use chrono::{DateTime, Utc};
+
+use crate::config::DriftConfig;
+use crate::sensors::{FieldDescriptor, Quality, SensorData, VizType};
+
+/// Readings further apart than this restart the window.
+const MAX_STEP_SECS: f64 = 0.5;
+
+const FIELDS: &[FieldDescriptor] = &[
+    FieldDescriptor {
+        key: "roll_drift",
+        label: "Roll Drift",
+        viz: VizType::Numeric { unit: "°/min" },
+        range: Some((-60.0, 60.0)),
+        group: Some("DRIFT"),
+    },
+    FieldDescriptor {
+        key: "pitch_drift",
+        label: "Pitch Drift",
+        viz: VizType::Numeric { unit: "°/min" },
+        range: Some((-60.0, 60.0)),
+        group: None,
+    },
+];
+
+/// Running least-squares fit of `y = a + b·t`.
+#[derive(Debug, Default, Clone, Copy)]
+struct LineFit {
+    n: f64,
+    t: f64,
+    y: f64,
+    tt: f64,
+    ty: f64,
+}
+
+impl LineFit {
+    fn push(&mut self, t: f64, y: f64) {
+        self.n += 1.0;
+        self.t += t;
+        self.y += y;
+        self.tt += t * t;
+        self.ty += t * y;
+    }
+
+    fn slope(&self) -> Option<f64> {
+        let denom = self.n * self.tt - self.t * self.t;
+        (self.n >= 2.0 && denom.abs() > f64::EPSILON)
+            .then(|| (self.n * self.ty - self.t * self.y) / denom)
+    }
+}
+
+pub struct DriftMonitor {
+    cfg: DriftConfig,
+    window_start: Option<DateTime<Utc>>,
+    last: Option<DateTime<Utc>>,
+    /// Gyro-integrated `[roll, pitch]` in degrees
+    gyro: [f64; 2],
+    fits: [LineFit; 2],
+    /// `[roll, pitch]` drift in °/min from the last complete window
+    drift: Option<[f64; 2]>,
+}
+
+impl DriftMonitor {
+    pub fn new(cfg: DriftConfig) -> Self {
+        Self {
+            cfg,
+            window_start: None,
+            last: None,
+            gyro: [0.0; 2],
+            fits: [LineFit::default(); 2],
+            drift: None,
+        }
+    }
+
+    /// Descriptors for the channels [`process`](Self::process) adds.
+    pub fn field_descriptors() -> &'static [FieldDescriptor] {
+        FIELDS
+    }
+
+    /// Update from one IMU reading and append the drift channels to it.
+    /// Readings without the configured accel / gyro channels are left alone.
+    pub fn process(&mut self, data: &mut SensorData) {
+        let field = |k: &String| data.fields.get(k.as_str()).copied();
+        let (Some(ax), Some(ay), Some(az), Some(roll_rate), Some(pitch_rate)) = (
+            field(&self.cfg.accel_fields[0]),
+            field(&self.cfg.accel_fields[1]),
+            field(&self.cfg.accel_fields[2]),
+            field(&self.cfg.gyro_fields[0]),
+            field(&self.cfg.gyro_fields[1]),
+        ) else {
+            return;
+        };
+        let accel = [
+            ay.atan2(az).to_degrees(),
+            (-ax).atan2((ay * ay + az * az).sqrt()).to_degrees(),
+        ];
+
+        let step = self
+            .last
+            .map(|t| (data.timestamp - t).num_microseconds().unwrap_or(0) as f64 / 1e6);
+        self.last = Some(data.timestamp);
+
+        match (self.window_start, step) {
+            (Some(start), Some(dt)) if dt > 0.0 && dt <= MAX_STEP_SECS => {
+                self.gyro[0] += roll_rate * dt;
+                self.gyro[1] += pitch_rate * dt;
+                let t = (data.timestamp - start).num_milliseconds() as f64 / 1000.0;
+                for ((fit, gyro), accel) in self.fits.iter_mut().zip(self.gyro).zip(accel) {
+                    fit.push(t, gyro - accel);
+                }
+                if t >= self.cfg.window_secs {
+                    if let (Some(roll), Some(pitch)) = (self.fits[0].slope(), self.fits[1].slope())
+                    {
+                        self.drift = Some([roll * 60.0, pitch * 60.0]);
+                    }
+                    self.restart(data.timestamp, accel);
+                }
+            }
+            _ => self.restart(data.timestamp, accel),
+        }
+
+        for (key, i) in [("roll_drift", 0), ("pitch_drift", 1)] {
+            match self.drift {
+                Some(d) => {
+                    data.fields.insert(key.into(), d[i]);
+                }
+                None => {
+                    data.fields.insert(key.into(), 0.0);
+                    data.set_quality(key, Quality::Invalid);
+                }
+            }
+        }
+    }
+
+    fn restart(&mut self, at: DateTime<Utc>, accel: [f64; 2]) {
+        self.window_start = Some(at);
+        self.gyro = accel;
+        // The gap is zero by construction at the start of a window
+        self.fits = [LineFit::default(); 2];
+        for fit in &mut self.fits {
+            fit.push(0.0, 0.0);
+        }
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Unit tests
+// ---------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+
+    /// Roll the sensor at `rate` °/s for `secs` while the gyro reports
+    /// `rate + bias`.  Returns the last reading.
+    fn roll(monitor: &mut DriftMonitor, secs: f64, rate: f64, bias: f64) -> SensorData {
+        let t0 = Utc::now();
+        let mut last = SensorData::default();
+        for i in 0..(secs * 50.0) as i64 {
+            let t = t0 + Duration::milliseconds(i * 20);
+            let angle = (rate * i as f64 * 0.02).to_radians();
+            let mut d = SensorData::new(
+                [
+                    ("accel_raw_x", 0.0),
+                    ("accel_raw_y", angle.sin()),
+                    ("accel_raw_z", angle.cos()),
+                    ("gyro_x", rate + bias),
+                    ("gyro_y", 0.0),
+                ]
+                .into_iter()
+                .map(|(k, v)| (k.into(), v))
+                .collect(),
+            );
+            d.timestamp = t;
+            monitor.process(&mut d);
+            last = d;
+        }
+        last
+    }
+
+    #[test]
+    fn test_no_report_until_first_window() {
+        let mut monitor = DriftMonitor::new(DriftConfig::default());
+        let d = roll(&mut monitor, 1.0, 5.0, 0.0);
+        assert_eq!(d.quality("roll_drift"), Quality::Invalid);
+    }
+
+    #[test]
+    fn test_gyro_bias_reported_as_drift() {
+        let mut monitor = DriftMonitor::new(DriftConfig {
+            window_secs: 5.0,
+            ..Default::default()
+        });
+        // 0.1 °/s bias = 6 °/min, while rolling at 3 °/s
+        let d = roll(&mut monitor, 6.0, 3.0, 0.1);
+        assert_eq!(d.quality("roll_drift"), Quality::Good);
+        assert!(
+            (d.fields["roll_drift"] - 6.0).abs() < 0.2,
+            "{}",
+            d.fields["roll_drift"]
+        );
+        assert!(d.fields["pitch_drift"].abs() < 0.01);
+    }
+}
@@ -0,0 +1,366 @@
+//! Vehicle dynamics — speed, corner radius, braking distance and heading
+//! derived from an IMU's G-force and yaw rate channels.
+//!
+//! Enabled with a `[dynamics]` section naming the IMU.  Speed is integrated
+//! from longitudinal G; that drifts, so when a GPS sensor is configured each
+//! fresh GPS speed pulls the estimate back towards it.  Heading is likewise
+//! integrated from yaw rate and pulled towards GPS course over ground while
+//! moving fast enough for the course to mean something.  The derived channels
+//! are appended to the IMU's readings, so they show up in the TUI, the
+//! DERIVED topic, recordings and exports like any driver field.
+
+use chrono::{DateTime, Utc};
+
+use crate::config::DynamicsConfig;
+use crate::sensors::{FieldDescriptor, Quality, SensorData, VizType};
+
+/// m/s² per G.
+pub const STANDARD_GRAVITY: f64 = 9.80665;
+
+/// Readings further apart than this are treated as a gap, not integrated.
+const MAX_STEP_SECS: f64 = 0.5;
+
+/// Below this speed (m/s) the vehicle is treated as stopped.
+const STOPPED_MPS: f64 = 0.5;
+
+const FIELDS: &[FieldDescriptor] = &[
+    FieldDescriptor {
+        key: "speed",
+        label: "Speed",
+        viz: VizType::Numeric { unit: "km/h" },
+        range: Some((0.0, 300.0)),
+        group: Some("DYNAMICS"),
+    },
+    FieldDescriptor {
+        key: "corner_radius",
+        label: "Corner Radius",
+        viz: VizType::Numeric { unit: "m" },
+        range: Some((0.0, 1000.0)),
+        group: None,
+    },
+    FieldDescriptor {
+        key: "braking_distance",
+        label: "Braking Dist",
+        viz: VizType::Numeric { unit: "m" },
+        range: Some((0.0, 200.0)),
+        group: None,
+    },
+    FieldDescriptor {
+        key: "heading",
+        label: "Heading",
+        viz: VizType::Numeric { unit: "°" },
+        range: Some((0.0, 360.0)),
+        group: None,
+    },
+];
+
+/// The latest trustworthy GPS reading, for the fusion.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct GpsFix {
+    pub speed_kmh: Option<f64>,
+    /// Course over ground in degrees clockwise from north
+    pub course: Option<f64>,
+}
+
+pub struct VehicleDynamics {
+    cfg: DynamicsConfig,
+    /// Estimated speed in m/s
+    speed: f64,
+    last: Option<DateTime<Utc>>,
+    braking: bool,
+    /// Distance covered in the current braking event
+    brake_distance: f64,
+    /// Distance of the last completed braking event
+    last_brake_distance: f64,
+    /// Degrees clockwise from north, 0..360
+    heading: f64,
+    /// Whether `heading` has been aligned with a GPS course yet; until then
+    /// it is relative to the direction faced at start
+    heading_aligned: bool,
+}
+
+impl VehicleDynamics {
+    pub fn new(cfg: DynamicsConfig) -> Self {
+        Self {
+            cfg,
+            speed: 0.0,
+            last: None,
+            braking: false,
+            brake_distance: 0.0,
+            last_brake_distance: 0.0,
+            heading: 0.0,
+            heading_aligned: false,
+        }
+    }
+
+    /// Descriptors for the channels [`process`](Self::process) adds.
+    pub fn field_descriptors() -> &'static [FieldDescriptor] {
+        FIELDS
+    }
+
+    /// Estimated speed in km/h.
+    pub fn speed_kmh(&self) -> f64 {
+        self.speed * 3.6
+    }
+
+    /// Update from one IMU reading and append the derived channels to it.
+    /// `gps` holds the latest GPS speed and course, if recent enough to
+    /// trust.  Readings without the configured G channels are left alone.
+    pub fn process(&mut self, data: &mut SensorData, gps: GpsFix) {
+        let gps_speed_kmh = gps.speed_kmh;
+        let (Some(&long_g), Some(&lat_g)) = (
+            data.fields.get(self.cfg.longitudinal_field.as_str()),
+            data.fields.get(self.cfg.lateral_field.as_str()),
+        ) else {
+            return;
+        };
+
+        let dt = self
+            .last
+            .map(|t| (data.timestamp - t).num_microseconds().unwrap_or(0) as f64 / 1e6)
+            .filter(|dt| *dt > 0.0 && *dt <= MAX_STEP_SECS)
+            .unwrap_or(0.0);
+        self.last = Some(data.timestamp);
+
+        self.speed += long_g * STANDARD_GRAVITY * dt;
+        if let Some(gps) = gps_speed_kmh {
+            let weight = self.cfg.gps_weight.clamp(0.0, 1.0);
+            self.speed += weight * (gps / 3.6 - self.speed);
+        }
+        self.speed = self.speed.max(0.0);
+
+        self.update_braking(-long_g, dt);
+
+        data.fields.insert("speed".into(), self.speed_kmh());
+        if gps_speed_kmh.is_none() {
+            data.set_quality("speed", Quality::Estimated);
+        }
+
+        // r = v² / a — meaningless when driving straight or barely moving
+        let lateral = lat_g.abs() * STANDARD_GRAVITY;
+        if lat_g.abs() >= self.cfg.min_lateral_g && self.speed > STOPPED_MPS {
+            data.fields
+                .insert("corner_radius".into(), self.speed.powi(2) / lateral);
+        } else {
+            data.fields.insert("corner_radius".into(), 0.0);
+            data.set_quality("corner_radius", Quality::Invalid);
+        }
+
+        let braking_distance = if self.braking {
+            self.brake_distance
+        } else {
+            self.last_brake_distance
+        };
+        data.fields
+            .insert("braking_distance".into(), braking_distance);
+
+        if let Some(&yaw_rate) = data.fields.get(self.cfg.yaw_rate_field.as_str()) {
+            let fused = self.update_heading(yaw_rate, dt, gps);
+            data.fields.insert("heading".into(), self.heading);
+            if !fused {
+                data.set_quality("heading", Quality::Estimated);
+            }
+        }
+    }
+
+    /// Integrate yaw rate, then close part of the gap to the GPS course if
+    /// the GPS is moving fast enough; the first usable course is taken
+    /// outright.  Returns whether a course was fused in.
+    fn update_heading(&mut self, yaw_rate: f64, dt: f64, gps: GpsFix) -> bool {
+        self.heading = (self.heading + yaw_rate * dt).rem_euclid(360.0);
+        let course = gps.course.filter(|_| {
+            gps.speed_kmh
+                .is_some_and(|v| v >= self.cfg.min_course_speed_kmh)
+        });
+        let Some(course) = course else {
+            return false;
+        };
+        if self.heading_aligned {
+            let weight = self.cfg.course_weight.clamp(0.0, 1.0);
+            self.heading += weight * angle_diff(course, self.heading);
+        } else {
+            self.heading = course;
+            self.heading_aligned = true;
+        }
+        self.heading = self.heading.rem_euclid(360.0);
+        true
+    }
+
+    /// A braking event starts when deceleration reaches the threshold and
+    /// ends once it falls below half of it (hysteresis) or the vehicle stops.
+    fn update_braking(&mut self, decel_g: f64, dt: f64) {
+        let threshold = self.cfg.brake_threshold_g;
+        if !self.braking && decel_g >= threshold && self.speed > STOPPED_MPS {
+            self.braking = true;
+            self.brake_distance = 0.0;
+        }
+        if !self.braking {
+            return;
+        }
+        self.brake_distance += self.speed * dt;
+        if decel_g < threshold / 2.0 || self.speed <= STOPPED_MPS {
+            self.braking = false;
+            self.last_brake_distance = self.brake_distance;
+        }
+    }
+}
+
+/// `a - b` in degrees, the short way round: -180..180.
+fn angle_diff(a: f64, b: f64) -> f64 {
+    (a - b + 180.0).rem_euclid(360.0) - 180.0
+}
+
+// ---------------------------------------------------------------------------
+// Unit tests
+// ---------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+
+    fn reading(t: DateTime<Utc>, long_g: f64, lat_g: f64) -> SensorData {
+        let mut d = SensorData::new(
+            [
+                ("g_force_x".into(), long_g),
+                ("g_force_y".into(), lat_g),
+            ]
+            .into_iter()
+            .collect(),
+        );
+        d.timestamp = t;
+        d
+    }
+
+    /// Feed `secs` of constant G at 50 Hz; returns the last reading.
+    fn drive(
+        dynamics: &mut VehicleDynamics,
+        t: &mut DateTime<Utc>,
+        secs: f64,
+        long_g: f64,
+        lat_g: f64,
+    ) -> SensorData {
+        let mut last = SensorData::default();
+        for _ in 0..(secs * 50.0) as usize {
+            *t += Duration::milliseconds(20);
+            last = reading(*t, long_g, lat_g);
+            dynamics.process(&mut last, GpsFix::default());
+        }
+        last
+    }
+
+    #[test]
+    fn test_speed_corner_radius_and_braking() {
+        let mut dynamics = VehicleDynamics::new(DynamicsConfig::default());
+        let mut t = Utc::now();
+
+        // 0.5 G for 2 s ≈ 9.8 m/s ≈ 35 km/h
+        let d = drive(&mut dynamics, &mut t, 2.0, 0.5, 0.0);
+        assert!(
+            (d.fields["speed"] - 35.3).abs() < 1.0,
+            "{}",
+            d.fields["speed"]
+        );
+        assert_eq!(d.quality("speed"), Quality::Estimated);
+        assert_eq!(d.quality("corner_radius"), Quality::Invalid);
+
+        // 1 G lateral at ~9.8 m/s → r = v² / a ≈ 9.8 m
+        let d = drive(&mut dynamics, &mut t, 0.1, 0.0, 1.0);
+        assert!((d.fields["corner_radius"] - 9.8).abs() < 0.5);
+        assert_eq!(d.quality("corner_radius"), Quality::Good);
+
+        // Brake at 1 G to a stop: v² / 2a ≈ 4.9 m
+        let d = drive(&mut dynamics, &mut t, 2.0, -1.0, 0.0);
+        assert_eq!(d.fields["speed"], 0.0);
+        assert!(
+            (d.fields["braking_distance"] - 4.9).abs() < 0.5,
+            "{}",
+            d.fields["braking_distance"]
+        );
+    }
+
+    #[test]
+    fn test_gps_speed_corrects_drift() {
+        let mut dynamics = VehicleDynamics::new(DynamicsConfig::default());
+        let mut t = Utc::now();
+        let mut d = SensorData::default();
+        // Small accelerometer bias, but GPS says 50 km/h
+        for _ in 0..200 {
+            t += Duration::milliseconds(20);
+            d = reading(t, 0.02, 0.0);
+            dynamics.process(
+                &mut d,
+                GpsFix {
+                    speed_kmh: Some(50.0),
+                    course: None,
+                },
+            );
+        }
+        assert!(
+            (d.fields["speed"] - 50.0).abs() < 2.0,
+            "{}",
+            d.fields["speed"]
+        );
+        assert_eq!(d.quality("speed"), Quality::Good);
+    }
+
+    #[test]
+    fn test_heading_follows_yaw_rate_and_gps_course() {
+        let mut dynamics = VehicleDynamics::new(DynamicsConfig::default());
+        let mut t = Utc::now();
+        let mut step = |yaw_rate: f64, gps: GpsFix| {
+            t += Duration::milliseconds(20);
+            let mut d = reading(t, 0.0, 0.0);
+            d.fields.insert("yaw_rate".into(), yaw_rate);
+            dynamics.process(&mut d, gps);
+            d
+        };
+
+        // 1 s at -30 °/s from the start direction wraps below zero
+        let mut d = SensorData::default();
+        for _ in 0..50 {
+            d = step(-30.0, GpsFix::default());
+        }
+        assert!(
+            (d.fields["heading"] - 330.0).abs() < 1.0,
+            "{}",
+            d.fields["heading"]
+        );
+        assert_eq!(d.quality("heading"), Quality::Estimated);
+
+        // Too slow for the course to count
+        let crawling = GpsFix {
+            speed_kmh: Some(3.0),
+            course: Some(90.0),
+        };
+        d = step(0.0, crawling);
+        assert_eq!(d.quality("heading"), Quality::Estimated);
+
+        // The first usable course is taken outright
+        let moving = GpsFix {
+            speed_kmh: Some(40.0),
+            course: Some(10.0),
+        };
+        d = step(0.0, moving);
+        assert_eq!(d.fields["heading"], 10.0);
+        assert_eq!(d.quality("heading"), Quality::Good);
+
+        // A gyro bias is held in check, across north, by the course
+        let north = GpsFix {
+            speed_kmh: Some(40.0),
+            course: Some(359.0),
+        };
+        for _ in 0..500 {
+            d = step(2.0, north);
+        }
+        let off = angle_diff(d.fields["heading"], 359.0);
+        assert!(off.abs() < 2.0, "{}", d.fields["heading"]);
+    }
+
+    #[test]
+    fn test_angle_diff_takes_the_short_way() {
+        assert_eq!(angle_diff(10.0, 350.0), 20.0);
+        assert_eq!(angle_diff(350.0, 10.0), -20.0);
+        assert_eq!(angle_diff(90.0, 0.0), 90.0);
+    }
+}
@@ -0,0 +1,120 @@
+//! Error budget: stop reading a sensor whose reads keep failing.
+//!
+//! A loose connector rarely fails outright — it fails one read in five and
+//! floods the log and status bar while the sensor looks connected.  Each
+//! sensor task counts its reads and failures over the last `window_secs`
+//! in one-second buckets; once at least `min_reads` have been made and more
+//! than `max_failure_pct` of them failed, the sensor is disabled until the
+//! service restarts.
+
+use std::collections::VecDeque;
+
+use chrono::{DateTime, Utc};
+
+use crate::config::ErrorBudgetConfig;
+
+struct Bucket {
+    second: i64,
+    reads: u32,
+    failures: u32,
+}
+
+pub struct ErrorBudget {
+    cfg: ErrorBudgetConfig,
+    buckets: VecDeque<Bucket>,
+}
+
+impl ErrorBudget {
+    pub fn new(cfg: ErrorBudgetConfig) -> Self {
+        Self {
+            cfg,
+            buckets: VecDeque::new(),
+        }
+    }
+
+    /// Count one read.  Returns the failure percentage over the window once
+    /// it exceeds the budget.
+    pub fn record(&mut self, at: DateTime<Utc>, ok: bool) -> Option<f64> {
+        let second = at.timestamp();
+        match self.buckets.back_mut() {
+            Some(b) if b.second == second => {
+                b.reads += 1;
+                b.failures += u32::from(!ok);
+            }
+            _ => self.buckets.push_back(Bucket {
+                second,
+                reads: 1,
+                failures: u32::from(!ok),
+            }),
+        }
+        let oldest = second - self.cfg.window_secs.max(1) as i64;
+        while self.buckets.front().is_some_and(|b| b.second <= oldest) {
+            self.buckets.pop_front();
+        }
+
+        let (reads, failures) = self.buckets.iter().fold((0u64, 0u64), |(r, f), b| {
+            (r + b.reads as u64, f + b.failures as u64)
+        });
+        let pct = 100.0 * failures as f64 / reads as f64;
+        (reads >= self.cfg.min_reads && pct > self.cfg.max_failure_pct).then_some(pct)
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Unit tests
+// ---------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn budget() -> ErrorBudget {
+        ErrorBudget::new(ErrorBudgetConfig {
+            enabled: true,
+            max_failure_pct: 10.0,
+            window_secs: 60,
+            min_reads: 50,
+        })
+    }
+
+    /// `reads` reads per second for `secs` seconds from `t0 + start`, every
+    /// `fail_every`-th one failing.  Returns the first verdict.
+    fn run(
+        b: &mut ErrorBudget,
+        t0: DateTime<Utc>,
+        start: i64,
+        secs: i64,
+        fail_every: u32,
+    ) -> Option<f64> {
+        let mut verdict = None;
+        for s in start..start + secs {
+            for i in 0..10 {
+                let at =
+                    t0 + chrono::Duration::seconds(s) + chrono::Duration::milliseconds(i * 100);
+                let ok = fail_every == 0 || !(i as u32 + 1).is_multiple_of(fail_every);
+                verdict = verdict.or(b.record(at, ok));
+            }
+        }
+        verdict
+    }
+
+    #[test]
+    fn test_flapping_sensor_exceeds_budget() {
+        let mut b = budget();
+        let t0 = DateTime::from_timestamp(1_700_000_000, 0).unwrap();
+        // 1 in 10 failing is exactly the budget, not over it
+        assert_eq!(run(&mut b, t0, 0, 120, 10), None);
+        let pct = run(&mut b, t0, 120, 30, 5).unwrap();
+        assert!(pct > 10.0 && pct < 20.0, "{}", pct);
+    }
+
+    #[test]
+    fn test_old_failures_age_out() {
+        let mut b = budget();
+        let t0 = DateTime::from_timestamp(1_700_000_000, 0).unwrap();
+        // A burst too short to reach min_reads...
+        assert_eq!(run(&mut b, t0, 0, 4, 1), None);
+        // ...is gone from the window by the time enough reads are counted
+        assert_eq!(run(&mut b, t0, 60, 60, 0), None);
+    }
+}
@@ -0,0 +1,126 @@
+//! CSV export of a sensor's rolling history (TUI `e` key).
+
+use anyhow::{Context, Result};
+use chrono::Local;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use crate::models::SensorHistory;
+
+/// Write `history` as CSV: a `timestamp` column followed by one column per
+/// field in alphabetical order.
+///
+/// Field buffers are aligned from the newest sample backwards; a field that
+/// appeared later than others leaves its older cells empty.
+pub fn write_history_csv(history: &SensorHistory, mut out: impl Write) -> Result<()> {
+    let mut keys: Vec<&String> = history.fields.keys().collect();
+    keys.sort();
+
+    write!(out, "timestamp")?;
+    for k in &keys {
+        write!(out, ",{}", k)?;
+    }
+    writeln!(out)?;
+
+    let rows = history.timestamps.len();
+    for (row, ts) in history.timestamps.iter().enumerate() {
+        let from_end = rows - row;
+        write!(out, "{}", ts.to_rfc3339())?;
+        for k in &keys {
+            let buf = &history.fields[*k];
+            match buf.len().checked_sub(from_end).and_then(|i| buf.get(i)) {
+                Some(v) => write!(out, ",{}", v)?,
+                None => write!(out, ",")?,
+            }
+        }
+        writeln!(out)?;
+    }
+    Ok(())
+}
+
+/// Export `history` to `<dir>/<sensor>_<YYYYmmdd_HHMMSS>.csv`, creating `dir`
+/// if needed.  Returns the path written.
+pub fn export_history_csv(dir: &Path, sensor: &str, history: &SensorHistory) -> Result<PathBuf> {
+    std::fs::create_dir_all(dir)
+        .with_context(|| format!("Failed to create export directory {}", dir.display()))?;
+    let path = dir.join(format!(
+        "{}_{}.csv",
+        file_stem(sensor),
+        Local::now().format("%Y%m%d_%H%M%S")
+    ));
+    let file = std::fs::File::create(&path)
+        .with_context(|| format!("Failed to create {}", path.display()))?;
+    let mut out = std::io::BufWriter::new(file);
+    write_history_csv(history, &mut out)?;
+    out.flush()?;
+    Ok(path)
+}
+
+/// Sensor names are free text; keep file names portable.
+pub(crate) fn file_stem(name: &str) -> String {
+    name.chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '-' || c == '_' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+// ---------------------------------------------------------------------------
+// Unit tests
+// ---------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sensors::SensorData;
+
+    fn push(h: &mut SensorHistory, pairs: &[(&str, f64)]) {
+        h.push(&SensorData::new(
+            pairs.iter().map(|(k, v)| (k.to_string().into(), *v)).collect(),
+        ));
+    }
+
+    #[test]
+    fn test_csv_header_and_rows() {
+        let mut h = SensorHistory::new(10);
+        push(&mut h, &[("b", 2.0), ("a", 1.0)]);
+        push(&mut h, &[("b", 4.0), ("a", 3.0)]);
+        let mut out = Vec::new();
+        write_history_csv(&h, &mut out).unwrap();
+        let text = String::from_utf8(out).unwrap();
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(lines[0], "timestamp,a,b");
+        assert_eq!(lines.len(), 3);
+        assert!(lines[1].ends_with(",1,2"), "{}", lines[1]);
+        assert!(lines[2].ends_with(",3,4"), "{}", lines[2]);
+    }
+
+    #[test]
+    fn test_csv_late_field_leaves_blank_cells() {
+        let mut h = SensorHistory::new(10);
+        push(&mut h, &[("a", 1.0)]);
+        push(&mut h, &[("a", 2.0), ("b", 9.0)]);
+        let mut out = Vec::new();
+        write_history_csv(&h, &mut out).unwrap();
+        let text = String::from_utf8(out).unwrap();
+        let lines: Vec<&str> = text.lines().collect();
+        assert!(lines[1].ends_with(",1,"), "{}", lines[1]);
+        assert!(lines[2].ends_with(",2,9"), "{}", lines[2]);
+    }
+
+    #[test]
+    fn test_export_writes_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut h = SensorHistory::new(10);
+        push(&mut h, &[("x", 0.5)]);
+        let path = export_history_csv(&dir.path().join("sub"), "Front IMU", &h).unwrap();
+        assert!(path.exists());
+        let name = path.file_name().unwrap().to_str().unwrap();
+        assert!(name.starts_with("Front_IMU_"), "{}", name);
+        assert!(name.ends_with(".csv"));
+    }
+}
@@ -0,0 +1,314 @@
+//! All-time extremes (`[extremes]`): the lowest and highest value ever
+//! seen on chosen channels, such as peak lateral G, lean angle or exhaust
+//! temperature.
+//!
+//! Every reading passes through [`Extremes::update`] as it is stored.  The
+//! values are kept in `extremes.file`, read at startup and written every
+//! `save_interval_secs` while they change and again at shutdown, so they
+//! survive restarts.  Whenever they change they are also published,
+//! retained, on `<base_topic>/EXTREMES`.
+//!
+//! `X` in the TUI resets them all.  Over MQTT, publishing `all` (or an
+//! empty payload) to `<base_topic>/EXTREMES/RESET` does the same, and
+//! publishing a `<sensor>.<channel>` resets that channel alone.
+
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use indexmap::IndexMap;
+use serde::{Deserialize, Serialize};
+use tokio_util::sync::CancellationToken;
+use tracing::{error, info, warn};
+
+use crate::command_auth::CommandAuth;
+use crate::config::{ExtremesConfig, channel_ref};
+use crate::models::SharedState;
+use crate::mqtt_handler::MqttHandle;
+use crate::sensors::{Quality, SensorData};
+
+/// A value and when it was read.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Extreme {
+    pub value: f64,
+    pub at: DateTime<Utc>,
+}
+
+/// One channel's extremes; `None` until it has been read.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ChannelExtremes {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min: Option<Extreme>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max: Option<Extreme>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Extremes {
+    /// Keyed by `<sensor>.<channel>`, in config order
+    #[serde(default)]
+    pub channels: IndexMap<String, ChannelExtremes>,
+    /// Bumped on every change, so the store task can tell when to save
+    #[serde(skip)]
+    pub revision: u64,
+}
+
+impl Extremes {
+    /// Tracking `channels`, starting from what `saved` has for them.
+    pub fn new(channels: &[String], saved: Option<Extremes>) -> Self {
+        let mut saved = saved.unwrap_or_default().channels;
+        Self {
+            channels: channels
+                .iter()
+                .map(|key| (key.clone(), saved.shift_remove(key).unwrap_or_default()))
+                .collect(),
+            revision: 0,
+        }
+    }
+
+    /// Take in a reading of `sensor`.  Values flagged `invalid` don't count.
+    pub fn update(&mut self, sensor: &str, data: &SensorData) {
+        for (key, e) in &mut self.channels {
+            let Some((_, channel)) = channel_ref(key).filter(|(s, _)| *s == sensor) else {
+                continue;
+            };
+            let Some(&value) = data.fields.get(channel).filter(|v| v.is_finite()) else {
+                continue;
+            };
+            if data.quality(channel) == Quality::Invalid {
+                continue;
+            }
+            let new = Extreme {
+                value,
+                at: data.timestamp,
+            };
+            if e.min.is_none_or(|m| value < m.value) {
+                e.min = Some(new);
+                self.revision += 1;
+            }
+            if e.max.is_none_or(|m| value > m.value) {
+                e.max = Some(new);
+                self.revision += 1;
+            }
+        }
+    }
+
+    /// Forget the extremes of `key`, or of every channel when `None`.
+    /// `false` if `key` isn't tracked.
+    pub fn reset(&mut self, key: Option<&str>) -> bool {
+        match key {
+            None => self
+                .channels
+                .values_mut()
+                .for_each(|e| *e = Default::default()),
+            Some(key) => match self.channels.get_mut(key) {
+                Some(e) => *e = Default::default(),
+                None => return false,
+            },
+        }
+        self.revision += 1;
+        true
+    }
+
+    /// `None` when the file doesn't exist yet.  A file that can't be parsed
+    /// is reported and ignored rather than keeping the service from
+    /// starting; it is overwritten at the next save.
+    pub fn load(path: &Path) -> Option<Self> {
+        let text = std::fs::read_to_string(path).ok()?;
+        match toml::from_str(&text) {
+            Ok(v) => Some(v),
+            Err(e) => {
+                warn!("Ignoring extremes file {}: {}", path.display(), e);
+                None
+            }
+        }
+    }
+
+    /// Write to `<path>.tmp` next to `path` and rename it over `path`, so a
+    /// crash or power loss mid-save leaves the previous file intact.
+    pub fn save(&self, path: &Path) -> Result<()> {
+        if let Some(dir) = path.parent().filter(|d| !d.as_os_str().is_empty()) {
+            std::fs::create_dir_all(dir)
+                .with_context(|| format!("Failed to create {}", dir.display()))?;
+        }
+        let text = toml::to_string(self)?;
+        let mut tmp = path.as_os_str().to_owned();
+        tmp.push(".tmp");
+        let tmp = PathBuf::from(tmp);
+        std::fs::write(&tmp, text).with_context(|| format!("Failed to write {}", tmp.display()))?;
+        std::fs::rename(&tmp, path).with_context(|| format!("Failed to replace {}", path.display()))
+    }
+}
+
+/// The tracker for `cfg`, starting from its file, and the file's path.
+pub fn load(cfg: &ExtremesConfig) -> (Extremes, Option<PathBuf>) {
+    let path = crate::config::resolve_path(&cfg.file);
+    let saved = path.as_deref().and_then(Extremes::load);
+    (Extremes::new(&cfg.channels, saved), path)
+}
+
+/// Write the current extremes to `path`, if they are tracked.
+pub async fn save(state: &SharedState, path: &Path) {
+    let extremes = state.read().await.extremes.clone();
+    if let Some(extremes) = extremes {
+        write(extremes, path.to_path_buf()).await;
+    }
+}
+
+/// Save `extremes` to `path` off the runtime, so a slow disk holds up
+/// neither the lock nor other tasks.
+async fn write(extremes: Extremes, path: PathBuf) {
+    let result = tokio::task::spawn_blocking(move || extremes.save(&path)).await;
+    match result {
+        Ok(Ok(())) => {}
+        Ok(Err(e)) => error!("Saving extremes failed: {:#}", e),
+        Err(e) => error!("Saving extremes failed: {}", e),
+    }
+}
+
+/// Save changed extremes to `path` and publish them on `mqtt` every
+/// `interval`, and follow `RESET` commands that `auth` accepts, until
+/// `cancel` fires.
+pub fn spawn_extremes_store(
+    state: SharedState,
+    mqtt: Option<MqttHandle>,
+    base_topic: &str,
+    path: Option<PathBuf>,
+    interval: Duration,
+    auth: Arc<CommandAuth>,
+    cancel: CancellationToken,
+) {
+    let topic: Arc<str> = format!("{}/EXTREMES", base_topic).into();
+    let mut commands = mqtt
+        .as_ref()
+        .map(|h| h.subscribe(format!("{}/RESET", topic)));
+    tokio::spawn(async move {
+        let mut stored = None;
+        let mut tick = tokio::time::interval(interval);
+        loop {
+            let command = async {
+                match commands.as_mut() {
+                    Some(c) => c.recv().await,
+                    None => std::future::pending().await,
+                }
+            };
+            tokio::select! {
+                _ = cancel.cancelled() => return,
+                Some(msg) = command => {
+                    match auth.check("extremes", &msg.topic, &msg.payload) {
+                        Ok(payload) => reset(&state, &payload).await,
+                        Err(e) => warn!("Rejected MQTT extremes command: {}", e),
+                    }
+                }
+                _ = tick.tick() => {}
+            }
+            let extremes = {
+                let s = state.read().await;
+                let Some(extremes) = s.extremes.as_ref() else {
+                    return;
+                };
+                if stored == Some(extremes.revision) {
+                    continue;
+                }
+                stored = Some(extremes.revision);
+                extremes.clone()
+            };
+            let channels = serde_json::json!(extremes.channels);
+            if let Some(path) = &path {
+                write(extremes, path.clone()).await;
+            }
+            if let Some(h) = &mqtt {
+                let mut payload = serde_json::json!({
+                    "timestamp": Utc::now().to_rfc3339(),
+                    "channels": channels,
+                });
+                if let Some(v) = h.schema_version {
+                    payload["schema_version"] = serde_json::json!(v);
+                }
+                h.publish_retained(Arc::clone(&topic), payload.to_string())
+                    .await;
+            }
+        }
+    });
+}
+
+/// Reset what a `RESET` payload names: `all` or nothing for every channel,
+/// otherwise one `<sensor>.<channel>`.
+async fn reset(state: &SharedState, payload: &[u8]) {
+    let text = String::from_utf8_lossy(payload);
+    let key = Some(text.trim()).filter(|k| !k.is_empty() && !k.eq_ignore_ascii_case("all"));
+    let mut s = state.write().await;
+    let Some(extremes) = s.extremes.as_mut() else {
+        return;
+    };
+    if extremes.reset(key) {
+        info!(
+            "Extremes of {} reset over MQTT",
+            key.unwrap_or("every channel")
+        );
+    } else {
+        warn!("Extremes reset: '{}' isn't tracked", text.trim());
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Unit tests
+// ---------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn reading(secs: i64, key: &str, value: f64) -> SensorData {
+        let mut data = SensorData::new([(key.to_string().into(), value)].into_iter().collect());
+        data.timestamp = DateTime::from_timestamp(1_700_000_000 + secs, 0).unwrap();
+        data
+    }
+
+    #[test]
+    fn test_extremes_tracked_and_kept_across_runs() {
+        let channels = vec!["Front IMU.g_force_y".to_string(), "EGT.temp_c".to_string()];
+        let mut e = Extremes::new(&channels, None);
+
+        e.update("Front IMU", &reading(0, "g_force_y", 0.4));
+        e.update("Front IMU", &reading(1, "g_force_y", -1.1));
+        e.update("Front IMU", &reading(2, "g_force_y", 1.3));
+        e.update("Front IMU", &reading(3, "g_force_y", 0.9));
+        // Another sensor's channel of the same name isn't tracked
+        e.update("Rear IMU", &reading(4, "g_force_y", 5.0));
+        let mut invalid = reading(5, "temp_c", 2000.0);
+        invalid.set_quality("temp_c", Quality::Invalid);
+        e.update("EGT", &invalid);
+
+        let g = &e.channels["Front IMU.g_force_y"];
+        assert_eq!(g.min.unwrap().value, -1.1);
+        assert_eq!(g.max.unwrap().value, 1.3);
+        assert_eq!(g.max.unwrap().at.timestamp(), 1_700_000_002);
+        assert_eq!(e.channels["EGT.temp_c"], ChannelExtremes::default());
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("state/extremes.toml");
+        e.save(&path).unwrap();
+        // Saved again over the first file, through a temporary one
+        e.save(&path).unwrap();
+        assert!(!dir.path().join("state/extremes.toml.tmp").exists());
+
+        // Channels no longer configured are dropped, new ones start empty
+        let channels = vec![
+            "Front IMU.g_force_y".to_string(),
+            "Front IMU.lean".to_string(),
+        ];
+        let mut e = Extremes::new(&channels, Extremes::load(&path));
+        assert_eq!(e.channels["Front IMU.g_force_y"].max.unwrap().value, 1.3);
+        assert_eq!(e.channels.len(), 2);
+
+        assert!(!e.reset(Some("EGT.temp_c")));
+        assert!(e.reset(Some("Front IMU.g_force_y")));
+        assert_eq!(
+            e.channels["Front IMU.g_force_y"],
+            ChannelExtremes::default()
+        );
+    }
+}
@@ -0,0 +1,213 @@
+//! Gravity-vector complementary filter for linear acceleration.
+//!
+//! The per-axis path removes gravity by assuming the measured acceleration
+//! points along it, which stops holding as soon as the car brakes or
+//! corners: during combined braking and cornering the measured vector
+//! tilts, and part of the real acceleration is subtracted as "gravity".
+//! This filter tracks the gravity direction on all three axes together
+//! instead.  The gyro rotates the estimate between readings, and the
+//! accelerometer pulls it back to stop drift, but only while the measured
+//! magnitude is close to 1 g, when it can only be gravity.  Linear
+//! acceleration is then the measurement minus that estimate.
+
+use serde::{Deserialize, Serialize};
+
+use super::{Real, to_f64};
+
+type Vec3 = [Real; 3];
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GravityFilter {
+    /// Fraction of the way toward the measured direction per reading
+    correction: Real,
+    /// Deviation from 1 g (in g) beyond which the accelerometer is ignored
+    tolerance: Real,
+    /// Gravity direction in the sensor frame, unit length
+    gravity: Option<Vec3>,
+}
+
+impl GravityFilter {
+    pub fn new(correction: f64, tolerance: f64) -> Self {
+        Self {
+            correction: correction.clamp(0.0, 1.0) as Real,
+            tolerance: tolerance.max(0.0) as Real,
+            gravity: None,
+        }
+    }
+
+    /// Feed one reading: acceleration in g, rotation rate in °/s and the
+    /// seconds since the previous reading.  Returns the linear acceleration
+    /// in g, gravity removed on every axis.
+    pub fn update(&mut self, accel: [f64; 3], gyro_dps: [f64; 3], dt: f64) -> [f64; 3] {
+        let a = accel.map(|v| v as Real);
+        let norm = length(a);
+        let g = match self.gravity {
+            None => normalize(a).unwrap_or([0.0, 0.0, 1.0]),
+            Some(g) => {
+                // A world-fixed vector seen from the sensor turns by -ω × g
+                let w = gyro_dps.map(|v| v.to_radians() as Real);
+                let turned = sub(g, scale(cross(w, g), dt as Real));
+                let trust = if self.tolerance > 0.0 {
+                    (1.0 - (norm - 1.0).abs() / self.tolerance).max(0.0)
+                } else {
+                    0.0
+                };
+                let measured = normalize(a).unwrap_or(turned);
+                let pulled = add(
+                    turned,
+                    scale(sub(measured, turned), self.correction * trust),
+                );
+                normalize(pulled).unwrap_or(turned)
+            }
+        };
+        self.gravity = Some(g);
+        sub(a, g).map(to_f64)
+    }
+
+    /// Current gravity direction (unit vector, sensor frame).
+    pub fn gravity(&self) -> Option<[f64; 3]> {
+        self.gravity.map(|g| g.map(to_f64))
+    }
+
+    /// Tilt as a unit quaternion `[w, x, y, z]` (Hamilton) rotating the
+    /// sensor frame onto a level one with Z up.  Gravity fixes roll and
+    /// pitch only, so the rotation has no component about Z: heading is
+    /// not tracked.
+    pub fn orientation(&self) -> Option<[f64; 4]> {
+        let [gx, gy, gz] = self.gravity?.map(to_f64);
+        // Shortest rotation taking g onto +Z: axis g × Z, half-angle form
+        let q = [1.0 + gz, gy, -gx, 0.0];
+        let len = q.iter().map(|v| v * v).sum::<f64>().sqrt();
+        Some(if len < 1e-9 {
+            // Upside down: half a turn about X
+            [0.0, 1.0, 0.0, 0.0]
+        } else {
+            q.map(|v| v / len)
+        })
+    }
+
+    /// Forget the estimate; the next reading starts it again.
+    pub fn reset(&mut self) {
+        self.gravity = None;
+    }
+}
+
+fn add(a: Vec3, b: Vec3) -> Vec3 {
+    [a[0] + b[0], a[1] + b[1], a[2] + b[2]]
+}
+
+fn sub(a: Vec3, b: Vec3) -> Vec3 {
+    [a[0] - b[0], a[1] - b[1], a[2] - b[2]]
+}
+
+fn scale(a: Vec3, s: Real) -> Vec3 {
+    a.map(|v| v * s)
+}
+
+fn cross(a: Vec3, b: Vec3) -> Vec3 {
+    [
+        a[1] * b[2] - a[2] * b[1],
+        a[2] * b[0] - a[0] * b[2],
+        a[0] * b[1] - a[1] * b[0],
+    ]
+}
+
+fn length(a: Vec3) -> Real {
+    (a[0] * a[0] + a[1] * a[1] + a[2] * a[2]).sqrt()
+}
+
+fn normalize(a: Vec3) -> Option<Vec3> {
+    let len = length(a);
+    (len > 1e-6).then(|| scale(a, 1.0 / len))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn close(a: [f64; 3], b: [f64; 3]) -> bool {
+        a.iter().zip(b).all(|(x, y)| (x - y).abs() < 1e-3)
+    }
+
+    #[test]
+    fn test_combined_braking_and_cornering() {
+        let mut f = GravityFilter::new(0.02, 0.1);
+        assert!(close(f.update([0.0, 0.0, 1.0], [0.0; 3], 0.02), [0.0; 3]));
+        // 0.5 g lateral and 0.4 g braking on level ground, held for 2 s
+        let mut linear = [0.0; 3];
+        for _ in 0..100 {
+            linear = f.update([0.5, -0.4, 1.0], [0.0; 3], 0.02);
+        }
+        assert!(close(linear, [0.5, -0.4, 0.0]), "{:?}", linear);
+    }
+
+    #[test]
+    fn test_gyro_tracks_tilt() {
+        let mut f = GravityFilter::new(0.0, 0.1);
+        f.update([0.0, 0.0, 1.0], [0.0; 3], 0.02);
+        // Roll 30° about X at 30 °/s while standing still
+        let mut linear = [0.0; 3];
+        for i in 1..=50 {
+            let angle = (i as f64 * 0.6).to_radians();
+            let accel = [0.0, angle.sin(), angle.cos()];
+            linear = f.update(accel, [30.0, 0.0, 0.0], 0.02);
+        }
+        assert!(close(linear, [0.0; 3]), "{:?}", linear);
+    }
+
+    /// `v` rotated by the unit quaternion `q` (`q v q*`).
+    fn rotate(q: [f64; 4], v: [f64; 3]) -> [f64; 3] {
+        let [w, x, y, z] = q;
+        let u = [x, y, z];
+        let cross = |a: [f64; 3], b: [f64; 3]| {
+            [
+                a[1] * b[2] - a[2] * b[1],
+                a[2] * b[0] - a[0] * b[2],
+                a[0] * b[1] - a[1] * b[0],
+            ]
+        };
+        let t = cross(u, v).map(|c| 2.0 * c);
+        let ut = cross(u, t);
+        [0, 1, 2].map(|i| v[i] + w * t[i] + ut[i])
+    }
+
+    #[test]
+    fn test_orientation_levels_gravity() {
+        let mut f = GravityFilter::new(0.02, 0.1);
+        assert_eq!(f.orientation(), None);
+        f.update([0.0, 0.0, 1.0], [0.0; 3], 0.02);
+        assert!(close_q(f.orientation().unwrap(), [1.0, 0.0, 0.0, 0.0]));
+
+        // Mounted rolled 30° and pitched 10°
+        let mut f = GravityFilter::new(0.02, 0.1);
+        let (r, p) = (30f64.to_radians(), 10f64.to_radians());
+        let g = [-p.sin(), r.sin() * p.cos(), r.cos() * p.cos()];
+        f.update(g, [0.0; 3], 0.02);
+        let q = f.orientation().unwrap();
+        assert!(close(rotate(q, g), [0.0, 0.0, 1.0]), "{:?}", q);
+        assert_eq!(q[3], 0.0, "no heading");
+    }
+
+    fn close_q(a: [f64; 4], b: [f64; 4]) -> bool {
+        a.iter().zip(b).all(|(x, y)| (x - y).abs() < 1e-6)
+    }
+
+    #[test]
+    fn test_accelerometer_corrects_drift() {
+        let mut f = GravityFilter::new(0.1, 0.1);
+        f.update([0.0, 0.0, 1.0], [0.0; 3], 0.02);
+        // A gyro bias turns the estimate away from the still sensor's 1 g...
+        for _ in 0..50 {
+            f.update([0.0, 0.0, 1.0], [2.0, 0.0, 0.0], 0.02);
+        }
+        let g = f.gravity().unwrap();
+        assert!(g[1].abs() < 0.01, "{:?}", g);
+        // ...and without correction it drifts away
+        let mut uncorrected = GravityFilter::new(0.0, 0.1);
+        uncorrected.update([0.0, 0.0, 1.0], [0.0; 3], 0.02);
+        for _ in 0..50 {
+            uncorrected.update([0.0, 0.0, 1.0], [2.0, 0.0, 0.0], 0.02);
+        }
+        assert!(uncorrected.gravity().unwrap()[1].abs() > 0.03);
+    }
+}
@@ -0,0 +1,87 @@
+//! First-order high-pass filter.
+//!
+//! Removes the slowly changing part of a signal — gravity, a sustained turn
+//! or braking — and keeps what changes faster than the cutoff, such as the
+//! vibration of an unbalanced wheel.  The time step comes from each call,
+//! so readings don't have to arrive at an exact rate.
+
+use serde::{Deserialize, Serialize};
+
+use super::{Real, to_f64};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HighPassFilter {
+    /// Time constant `1 / (2π · cutoff)` in seconds
+    rc: Real,
+    last_input: Option<Real>,
+    output: Real,
+}
+
+impl HighPassFilter {
+    pub fn new(cutoff_hz: f64) -> Self {
+        Self {
+            rc: (1.0 / (2.0 * std::f64::consts::PI * cutoff_hz.max(1e-3))) as Real,
+            last_input: None,
+            output: 0.0,
+        }
+    }
+
+    /// Feed one sample taken `dt` seconds after the previous one.  The
+    /// first sample after [`reset`](Self::reset) only sets the baseline and
+    /// returns 0.
+    pub fn update(&mut self, input: f64, dt: f64) -> f64 {
+        let input = input as Real;
+        self.output = match self.last_input {
+            Some(last) => {
+                let alpha = self.rc / (self.rc + dt.max(0.0) as Real);
+                alpha * (self.output + input - last)
+            }
+            None => 0.0,
+        };
+        self.last_input = Some(input);
+        to_f64(self.output)
+    }
+
+    pub fn reset(&mut self) {
+        self.last_input = None;
+        self.output = 0.0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Peak output over the last second of a 1 g sine at `hz` on top of a
+    /// 1 g offset, sampled at 200 Hz.
+    fn peak(filter: &mut HighPassFilter, hz: f64) -> f64 {
+        let dt = 1.0 / 200.0;
+        (0..1000)
+            .map(|i| {
+                let t = i as f64 * dt;
+                filter.update(1.0 + (2.0 * std::f64::consts::PI * hz * t).sin(), dt)
+            })
+            .skip(800)
+            .fold(0.0, |m, v: f64| m.max(v.abs()))
+    }
+
+    #[test]
+    fn test_removes_offset_and_keeps_vibration() {
+        let mut filter = HighPassFilter::new(5.0);
+        // Wheel vibration well above the cutoff passes almost unchanged
+        assert!(peak(&mut filter, 30.0) > 0.9);
+        // A slow swing is mostly removed
+        filter.reset();
+        assert!(peak(&mut filter, 0.5) < 0.15);
+    }
+
+    #[test]
+    fn test_constant_input_decays_to_zero() {
+        let mut filter = HighPassFilter::new(1.0);
+        assert_eq!(filter.update(1.0, 0.01), 0.0);
+        let step = filter.update(2.0, 0.01);
+        assert!(step > 0.9);
+        let settled = (0..500).map(|_| filter.update(2.0, 0.01)).last().unwrap();
+        assert!(settled.abs() < 0.01, "{}", settled);
+    }
+}
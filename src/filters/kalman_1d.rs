@@ -1,23 +1,81 @@
 //! 1-D Kalman filter with adaptive gain and optional dead-zone.
+//!
+//! The dead zone holds the output until the estimate moves by more than
+//! `dead_zone`, which hides sensor noise on a still vehicle.  On its own it
+//! turns a slow ramp into stair steps and can hold a stale value forever
+//! when the true value settles just inside the zone.  Two settings soften
+//! that: with a release ratio below 1 the output, once moving, keeps
+//! following until the change per reading drops below
+//! `dead_zone * release` (hysteresis); and a non-zero decay pulls a held
+//! output that fraction of the way toward the estimate on every reading.
 
 use serde::{Deserialize, Serialize};
 
+use super::{Real, to_f64};
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct KalmanFilter1D {
-    q: f64,
-    r: f64,
-    p: f64,
-    x: f64,
-    k: f64,
+    q: Real,
+    r: Real,
+    p: Real,
+    x: Real,
+    k: Real,
     initialized: bool,
-    dead_zone: f64,
-    last_output: f64,
+    dead_zone: Real,
+    /// Fraction of `dead_zone` the change must stay above to keep tracking
+    dead_zone_release: Real,
+    /// Fraction of the gap a held output closes per reading
+    dead_zone_decay: Real,
+    /// Output followed the estimate at the last update
+    tracking: bool,
+    last_output: Real,
     /// Clamp output to 0.0 when |output| is below this threshold
-    absolute_zero: f64,
+    absolute_zero: Real,
+    /// Innovation size (in the channel's units) above which the gain is
+    /// scaled by `fast_gain` instead of `slow_gain`; `None` applies the
+    /// plain Kalman gain
+    adaptive: Option<AdaptiveGain>,
+    /// Measurement minus the prior estimate at the last update
+    innovation: Real,
+    /// Whether the dead zone held the last output
+    held: bool,
+}
+
+/// Gain scaling by innovation size: follow large changes faster and smooth
+/// small ones harder.  The threshold is in the channel's own units, so it
+/// needs setting per channel (1 g and 1 °/s are very different steps).
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct AdaptiveGain {
+    pub threshold: Real,
+    pub fast_gain: Real,
+    pub slow_gain: Real,
+}
+
+impl Default for AdaptiveGain {
+    fn default() -> Self {
+        Self {
+            threshold: 1.0,
+            fast_gain: 1.5,
+            slow_gain: 0.8,
+        }
+    }
+}
+
+/// Filter internals after the last update, for tuning.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct KalmanDiagnostics {
+    /// Estimate variance
+    pub p: f64,
+    /// Kalman gain
+    pub k: f64,
+    pub innovation: f64,
+    /// The dead zone kept the previous output
+    pub held: bool,
 }
 
 impl KalmanFilter1D {
     pub fn new(q: f64, r: f64) -> Self {
+        let (q, r) = (q as Real, r as Real);
         Self {
             q,
             r,
@@ -26,28 +84,54 @@ impl KalmanFilter1D {
             k: 0.0,
             initialized: false,
             dead_zone: 0.01,
+            dead_zone_release: 1.0,
+            dead_zone_decay: 0.0,
+            tracking: false,
             last_output: 0.0,
             absolute_zero: 0.0,
+            adaptive: Some(AdaptiveGain::default()),
+            innovation: 0.0,
+            held: false,
         }
     }
 
     // New: Allow configuring dead zone threshold
     pub fn with_dead_zone(mut self, threshold: f64) -> Self {
-        self.dead_zone = threshold;
+        self.dead_zone = threshold as Real;
+        self
+    }
+
+    /// Hysteresis for the dead zone; `1.0` (the default) has none.
+    pub fn with_dead_zone_release(mut self, ratio: f64) -> Self {
+        self.dead_zone_release = ratio.clamp(0.0, 1.0) as Real;
+        self
+    }
+
+    /// Decay of a held output toward the estimate; `0.0` (the default)
+    /// holds it indefinitely.
+    pub fn with_dead_zone_decay(mut self, rate: f64) -> Self {
+        self.dead_zone_decay = rate.clamp(0.0, 1.0) as Real;
         self
     }
 
     pub fn with_absolute_zero(mut self, threshold: f64) -> Self {
-        self.absolute_zero = threshold;
+        self.absolute_zero = threshold as Real;
+        self
+    }
+
+    /// Replace the gain scaling, or turn it off with `None`.
+    pub fn with_adaptive_gain(mut self, adaptive: Option<AdaptiveGain>) -> Self {
+        self.adaptive = adaptive;
         self
     }
 
     pub fn update(&mut self, measurement: f64) -> f64 {
+        let measurement = measurement as Real;
         if !self.initialized {
             self.x = measurement;
             self.last_output = measurement;
             self.initialized = true;
-            return measurement;
+            return to_f64(measurement);
         }
 
         // Kalman filter prediction and update
@@ -55,11 +139,11 @@ impl KalmanFilter1D {
         self.k = self.p / (self.p + self.r);
 
         // Adaptive smoothing based on measurement delta
-        let delta = (measurement - self.x).abs();
-        let alpha = if delta > 1.0 {
-            self.k * 1.5 // Faster response to large changes
-        } else {
-            self.k * 0.8 // More smoothing for small changes
+        self.innovation = measurement - self.x;
+        let alpha = match self.adaptive {
+            Some(a) if self.innovation.abs() > a.threshold => self.k * a.fast_gain,
+            Some(a) => self.k * a.slow_gain,
+            None => self.k,
         };
 
         // Update state estimate
@@ -69,8 +153,16 @@ impl KalmanFilter1D {
 
         // Dead zone filter
         let change = (self.x - self.last_output).abs();
-        let output = if change < self.dead_zone {
-            self.last_output // No change if within dead zone
+        let threshold = if self.tracking {
+            self.dead_zone * self.dead_zone_release
+        } else {
+            self.dead_zone
+        };
+        self.tracking = change >= threshold;
+        self.held = !self.tracking;
+        let output = if self.held {
+            // Within the dead zone: hold, or creep toward the estimate
+            self.last_output + self.dead_zone_decay * (self.x - self.last_output)
         } else {
             self.x // Use new value if change is significant
         };
@@ -79,7 +171,7 @@ impl KalmanFilter1D {
         if self.absolute_zero > 0.0 && output.abs() < self.absolute_zero {
             return 0.0;
         }
-        output
+        to_f64(output)
     }
 
     /// Reset the filter to initial state
@@ -89,11 +181,23 @@ impl KalmanFilter1D {
         self.k = 0.0;
         self.initialized = false;
         self.last_output = 0.0;
+        self.tracking = false;
+        self.innovation = 0.0;
+        self.held = false;
     }
 
     /// Get the current state estimate
     pub fn get_estimate(&self) -> f64 {
-        self.x
+        to_f64(self.x)
+    }
+
+    pub fn diagnostics(&self) -> KalmanDiagnostics {
+        KalmanDiagnostics {
+            p: to_f64(self.p),
+            k: to_f64(self.k),
+            innovation: to_f64(self.innovation),
+            held: self.held,
+        }
     }
 }
 
@@ -140,4 +244,91 @@ mod tests {
         let result = filter.update(1.2);
         assert!(result > 1.0); // Should change
     }
+
+    /// Outputs for a ramp from 0 rising `step` per reading.
+    fn ramp(filter: KalmanFilter1D, step: f64, n: usize) -> Vec<f64> {
+        let mut filter = filter.with_adaptive_gain(None);
+        (0..n).map(|i| filter.update(i as f64 * step)).collect()
+    }
+
+    #[test]
+    fn test_dead_zone_release_follows_ramp_smoothly() {
+        let filter = || KalmanFilter1D::new(1.0, 0.01).with_dead_zone(0.1);
+        let changes = |out: Vec<f64>| out.windows(2).filter(|w| w[0] != w[1]).count();
+        // 0.06 per reading: a plain dead zone only moves every other reading
+        let stairs = changes(ramp(filter(), 0.06, 40));
+        let smooth = changes(ramp(filter().with_dead_zone_release(0.5), 0.06, 40));
+        assert!(stairs < 25, "{}", stairs);
+        assert!(smooth > 35, "{}", smooth);
+    }
+
+    #[test]
+    fn test_dead_zone_decay_releases_stale_value() {
+        let settle = |filter: KalmanFilter1D| {
+            let mut filter = filter.with_adaptive_gain(None);
+            filter.update(1.0);
+            // Settles 0.05 away, inside the 0.1 dead zone
+            (0..200).map(|_| filter.update(1.05)).last().unwrap()
+        };
+        let filter = || KalmanFilter1D::new(0.1, 0.1).with_dead_zone(0.1);
+        assert_eq!(settle(filter()), 1.0);
+        assert!((settle(filter().with_dead_zone_decay(0.05)) - 1.05).abs() < 1e-3);
+    }
+
+    /// Output after a step from 0 to `step` and `n` more samples of it.
+    fn step_response(filter: KalmanFilter1D, step: f64, n: usize) -> f64 {
+        let mut filter = filter.with_dead_zone(0.0);
+        filter.update(0.0);
+        (0..n).map(|_| filter.update(step)).last().unwrap()
+    }
+
+    #[test]
+    fn test_plain_gain_is_scale_invariant() {
+        // Without adaptation the gain doesn't depend on the values, so a
+        // channel in °/s responds like one in g
+        let plain = || KalmanFilter1D::new(0.01, 0.1).with_adaptive_gain(None);
+        let small = step_response(plain(), 0.5, 5);
+        let large = step_response(plain(), 50.0, 5);
+        assert!(
+            (large / small - 100.0).abs() < 1e-4,
+            "{} vs {}",
+            small,
+            large
+        );
+    }
+
+    #[test]
+    fn test_adaptive_gain_depends_on_step_size() {
+        let adaptive = || KalmanFilter1D::new(0.01, 0.1);
+        let plain = || KalmanFilter1D::new(0.01, 0.1).with_adaptive_gain(None);
+        // Below the threshold: smoothed harder than plain Kalman
+        assert!(step_response(adaptive(), 0.5, 1) < step_response(plain(), 0.5, 1));
+        // Above it: follows faster
+        assert!(step_response(adaptive(), 50.0, 1) > step_response(plain(), 50.0, 1));
+
+        // A threshold in the channel's units restores the small-step behaviour
+        let scaled = KalmanFilter1D::new(0.01, 0.1).with_adaptive_gain(Some(AdaptiveGain {
+            threshold: 100.0,
+            ..Default::default()
+        }));
+        let ratio = step_response(scaled, 50.0, 1) / step_response(adaptive(), 0.5, 1);
+        assert!((ratio - 100.0).abs() < 1e-4, "{}", ratio);
+    }
+
+    #[test]
+    fn test_diagnostics_report_dead_zone_hold() {
+        let mut filter = KalmanFilter1D::new(0.1, 0.1).with_dead_zone(0.1);
+        filter.update(1.0);
+        let d = filter.diagnostics();
+        assert_eq!((d.k, d.innovation, d.held), (0.0, 0.0, false));
+
+        filter.update(1.05);
+        let d = filter.diagnostics();
+        assert!(d.held);
+        assert!((d.innovation - 0.05).abs() < 1e-6);
+        assert!(d.k > 0.0 && d.k < 1.0);
+
+        filter.update(1.5);
+        assert!(!filter.diagnostics().held);
+    }
 }
@@ -1 +1,19 @@
+pub mod gravity;
+pub mod high_pass;
 pub mod kalman_1d;
+
+/// Scalar the filters compute in: `f32` with the `f32` feature, else `f64`.
+/// Readings stay `f64` everywhere else and are converted at the filter.
+#[cfg(feature = "f32")]
+pub type Real = f32;
+#[cfg(not(feature = "f32"))]
+pub type Real = f64;
+
+#[cfg(feature = "f32")]
+fn to_f64(v: Real) -> f64 {
+    v as f64
+}
+#[cfg(not(feature = "f32"))]
+fn to_f64(v: Real) -> f64 {
+    v
+}
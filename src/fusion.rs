@@ -0,0 +1,462 @@
+//! Fused sensors (`[[fused_sensors]]`).
+//!
+//! Sensors bolted to the same rigid body, such as a front and a rear IMU,
+//! see the same motion with independent noise.  A fused sensor averages
+//! their latest readings, channel by channel, into a virtual sensor with
+//! its own tab and topics; with two sources the noise drops by about √2.
+//!
+//! Only channels that average meaningfully are fused: by default the
+//! linear acceleration and rotation rate axes ([`DEFAULT_CHANNELS`]).  A
+//! rolling peak such as `peak_g`, or an angle that wraps such as `heading`,
+//! would come out wrong, so they are left out unless `channels` lists them.
+//!
+//! Averaging also cross-checks the sources.  Each fused reading carries two
+//! extra channels that say how far apart they were:
+//!
+//! | Key           | Description                                               |
+//! |---------------|-----------------------------------------------------------|
+//! | divergence    | largest spread between sources over `divergence_channels` |
+//! | fused_sources | sources that contributed to this reading                  |
+//!
+//! A `divergence` above `max_divergence` raises an alert, as `[[alerts]]`
+//! do, which clears once it is back under half of it.  A source whose
+//! reading is older than `max_age_ms`, and a channel not flagged good, is
+//! left out rather than dragging the average to a stale value.  The fused
+//! readings are published as they are: no `[[transforms]]` or other
+//! per-sensor processing applies to them.
+
+use chrono::{DateTime, Utc};
+use indexmap::IndexMap;
+use tokio::task::JoinHandle;
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, info, warn};
+
+use crate::aggregation::Aggregator;
+use crate::alerts::{Alert, AlertChange};
+use crate::config::FusedSensorConfig;
+use crate::models::{
+    AppState, CalibrationState, PublishMode, SensorHistory, SensorStatus, SharedState,
+};
+use crate::mqtt_handler::MqttHandle;
+use crate::sensors::{ChannelKey, FieldDescriptor, FieldMeta, Quality, SensorData, VizType};
+use crate::service::{
+    DERIVED_KEYS, PayloadKeys, PublishState, SensorTopics, publish_availability, publish_rollups,
+    publish_sensor_data, push_data,
+};
+
+const FIELDS: &[FieldDescriptor] = &[
+    FieldDescriptor {
+        key: "divergence",
+        label: "Divergence",
+        viz: VizType::Value,
+        range: None,
+        group: Some("FUSION"),
+    },
+    FieldDescriptor {
+        key: "fused_sources",
+        label: "Sources",
+        viz: VizType::Value,
+        range: None,
+        group: None,
+    },
+];
+
+/// Channels fused when `channels.include` is empty: linear acceleration
+/// and rotation rate, which average like any noisy measurement.
+pub const DEFAULT_CHANNELS: &[&str] = &[
+    "g_force_x",
+    "g_force_y",
+    "g_force_z",
+    "accel_x",
+    "accel_y",
+    "accel_z",
+    "accel_raw_x",
+    "accel_raw_y",
+    "accel_raw_z",
+    "gyro_x",
+    "gyro_y",
+    "gyro_z",
+    "roll_rate",
+    "pitch_rate",
+    "yaw_rate",
+];
+
+/// Readings kept for a fused sensor's chart.
+const HISTORY_SIZE: usize = 600;
+
+/// Add the fused sensors to the state, next to the real ones.
+pub fn register(state: &mut AppState, cfgs: &[FusedSensorConfig]) {
+    for f in cfgs {
+        state.sensor_statuses.insert(
+            f.name.clone(),
+            SensorStatus {
+                name: f.name.clone(),
+                driver: "fusion".to_string(),
+                connection_display: f.sources.join(" + "),
+                group: None,
+                enabled: true,
+                connected: false,
+                last_error: None,
+                stuck: Vec::new(),
+                calibration: CalibrationState::Idle,
+                fields: Vec::new(),
+            },
+        );
+        state
+            .sensor_history
+            .insert(f.name.clone(), SensorHistory::new(HISTORY_SIZE));
+    }
+}
+
+pub struct Fusion {
+    cfg: FusedSensorConfig,
+    /// Timestamp of each source's reading last fused
+    fused: Vec<Option<DateTime<Utc>>>,
+    /// Id of the divergence alert while it is firing
+    alert: Option<u64>,
+}
+
+impl Fusion {
+    pub fn new(mut cfg: FusedSensorConfig) -> Self {
+        if cfg.channels.include.is_empty() {
+            cfg.channels.include = DEFAULT_CHANNELS.iter().map(|k| k.to_string()).collect();
+        }
+        Self {
+            fused: vec![None; cfg.sources.len()],
+            cfg,
+            alert: None,
+        }
+    }
+
+    /// Whether `data` is recent enough to fuse at `now`.
+    pub fn is_fresh(&self, data: &SensorData, now: DateTime<Utc>) -> bool {
+        is_fresh(data, now, self.cfg.max_age_ms)
+    }
+
+    /// Average the sources' latest `readings` (in `sources` order) and
+    /// return the fused reading with the alerts raised or cleared.  `None`
+    /// when no fresh source has a reading newer than the last one fused.
+    pub fn fuse(
+        &mut self,
+        readings: &[Option<SensorData>],
+        now: DateTime<Utc>,
+    ) -> Option<(SensorData, Vec<AlertChange>)> {
+        let mut fresh = Vec::with_capacity(readings.len());
+        let mut new = false;
+        for (last, data) in self.fused.iter_mut().zip(readings) {
+            let Some(data) = data
+                .as_ref()
+                .filter(|d| is_fresh(d, now, self.cfg.max_age_ms))
+            else {
+                continue;
+            };
+            new |= last.is_none_or(|t| data.timestamp > t);
+            *last = Some(data.timestamp);
+            fresh.push(data);
+        }
+        if !new {
+            return None;
+        }
+
+        // Every good value of each channel, in the order the sources list them
+        let mut values: IndexMap<&ChannelKey, Vec<f64>> = IndexMap::new();
+        for data in &fresh {
+            for (key, &v) in &data.fields {
+                if self.cfg.channels.allows(key) && data.quality(key) == Quality::Good {
+                    values.entry(key).or_default().push(v);
+                }
+            }
+        }
+
+        let mut out = SensorData {
+            timestamp: fresh.iter().map(|d| d.timestamp).max()?,
+            monotonic: fresh.iter().filter_map(|d| d.monotonic).reduce(f64::max),
+            clock_unsynced: fresh.iter().any(|d| d.clock_unsynced),
+            ..SensorData::default()
+        };
+        let mut divergence: Option<(&str, f64)> = None;
+        for (key, vs) in &values {
+            let mean = vs.iter().sum::<f64>() / vs.len() as f64;
+            out.fields.insert((*key).clone(), mean);
+            if vs.len() < 2 || !self.cfg.divergence_channels.allows(key) {
+                continue;
+            }
+            let (lo, hi) = vs
+                .iter()
+                .fold((f64::INFINITY, f64::NEG_INFINITY), |(lo, hi), &v| {
+                    (lo.min(v), hi.max(v))
+                });
+            if divergence.is_none_or(|(_, d)| hi - lo > d) {
+                divergence = Some((key, hi - lo));
+            }
+        }
+        out.fields
+            .insert("divergence".into(), divergence.map_or(0.0, |(_, d)| d));
+        if divergence.is_none() {
+            out.set_quality("divergence", Quality::Invalid);
+        }
+        out.fields
+            .insert("fused_sources".into(), fresh.len() as f64);
+
+        let changes = self.check_divergence(divergence);
+        Some((out, changes))
+    }
+
+    fn check_divergence(&mut self, divergence: Option<(&str, f64)>) -> Vec<AlertChange> {
+        let limit = self.cfg.max_divergence;
+        match (self.alert, divergence) {
+            (None, Some((key, d))) if limit > 0.0 && d > limit => {
+                let alert = Alert::new(
+                    &self.cfg.name,
+                    format!(
+                        "{} sources disagree: {} {:.3} apart (limit {})",
+                        self.cfg.name, key, d, limit
+                    ),
+                );
+                self.alert = Some(alert.id);
+                vec![AlertChange::Fired(alert)]
+            }
+            (Some(id), d) if d.is_none_or(|(_, d)| d < limit / 2.0) => {
+                self.alert = None;
+                vec![AlertChange::Cleared { id }]
+            }
+            _ => Vec::new(),
+        }
+    }
+}
+
+/// Whether `data` was read no more than `max_age_ms` before `now`.
+pub(crate) fn is_fresh(data: &SensorData, now: DateTime<Utc>, max_age_ms: u64) -> bool {
+    (now - data.timestamp).num_milliseconds() <= max_age_ms as i64
+}
+
+/// Fuse and publish `cfg`'s sources every `interval` until `cancel` fires.
+pub fn spawn_fused_sensor(
+    cfg: FusedSensorConfig,
+    state: SharedState,
+    mqtt: Option<MqttHandle>,
+    base_topic: String,
+    interval: std::time::Duration,
+    cancel: CancellationToken,
+) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let name = cfg.name.clone();
+        let topics = SensorTopics::new(&base_topic, None, &name);
+        let derived: Vec<&str> = DERIVED_KEYS
+            .iter()
+            .chain(FIELDS.iter().map(|f| &f.key))
+            .copied()
+            .collect();
+        let keys = match &mqtt {
+            Some(h) => PayloadKeys::new(&derived, &h.filtered_channels, &h.derived_channels),
+            None => PayloadKeys::default(),
+        };
+        let tags = IndexMap::new();
+        let mut publish_state = PublishState::default();
+        let mut aggregator = mqtt
+            .as_ref()
+            .and_then(|h| h.aggregation.as_ref())
+            .map(Aggregator::new);
+        let mut online = false;
+        let mut fusion = Fusion::new(cfg);
+        let mut tick = tokio::time::interval(interval);
+        info!("Fusing {} as '{}'", fusion.cfg.sources.join(" + "), name);
+
+        loop {
+            tokio::select! {
+                _ = cancel.cancelled() => break,
+                _ = tick.tick() => {}
+            }
+            let (readings, publish_mode) = {
+                let s = state.read().await;
+                let readings: Vec<_> = fusion
+                    .cfg
+                    .sources
+                    .iter()
+                    .map(|n| s.sensor_data.get(n).cloned())
+                    .collect();
+                (readings, s.publish_mode)
+            };
+            let now = Utc::now();
+            let Some((data, changes)) = fusion.fuse(&readings, now) else {
+                let stale = !readings.iter().flatten().any(|d| fusion.is_fresh(d, now));
+                if online && stale {
+                    online = false;
+                    set_online(&state, &fusion, &name, false).await;
+                    publish_availability(mqtt.as_ref(), &topics, false).await;
+                }
+                continue;
+            };
+
+            if !online {
+                online = true;
+                set_online(&state, &fusion, &name, true).await;
+                publish_availability(mqtt.as_ref(), &topics, true).await;
+            }
+            if !changes.is_empty() {
+                let mut s = state.write().await;
+                for change in changes {
+                    if let AlertChange::Fired(a) = &change {
+                        warn!("Alert: {}", a.text);
+                    }
+                    s.update_alert(change);
+                }
+            }
+            match (publish_mode, &mqtt) {
+                (PublishMode::Mqtt, Some(h)) => {
+                    publish_sensor_data(h, &topics, &name, &tags, &data, &keys, &mut publish_state)
+                        .await;
+                    if let Some(a) = aggregator.as_mut() {
+                        publish_rollups(h, &topics, a, &data, &keys).await;
+                    }
+                }
+                (PublishMode::Log, _) => debug!("'{}' reading: {:?}", name, data.fields),
+                _ => {}
+            }
+            push_data(&state, &name, data).await;
+        }
+        publish_availability(mqtt.as_ref(), &topics, false).await;
+    })
+}
+
+/// Mark the fused sensor online or offline, and give it the sources'
+/// channel descriptions once they are known.
+async fn set_online(state: &SharedState, fusion: &Fusion, name: &str, online: bool) {
+    let mut s = state.write().await;
+    let fields: Vec<FieldMeta> = fusion
+        .cfg
+        .sources
+        .iter()
+        .filter_map(|n| s.sensor_statuses.get(n))
+        .find(|st| !st.fields.is_empty())
+        .map(|st| {
+            st.fields
+                .iter()
+                .filter(|f| fusion.cfg.channels.allows(&f.key))
+                .cloned()
+                .chain(FIELDS.iter().map(FieldMeta::from))
+                .collect()
+        })
+        .unwrap_or_default();
+    let Some(st) = s.sensor_statuses.get_mut(name) else {
+        return;
+    };
+    st.connected = online;
+    st.last_error = (!online).then(|| "no recent readings from its sources".to_string());
+    if st.fields.is_empty() {
+        st.fields = fields;
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Unit tests
+// ---------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fusion(toml: &str) -> Fusion {
+        let cfg = format!(
+            "name = \"chassis\"\nsources = [\"front\", \"rear\"]\n{}",
+            toml
+        );
+        Fusion::new(toml::from_str(&cfg).unwrap())
+    }
+
+    fn reading(ms: i64, g: f64, yaw: f64) -> Option<SensorData> {
+        let mut data = SensorData::new(
+            [("g_force_x".into(), g), ("yaw_rate".into(), yaw)]
+                .into_iter()
+                .collect(),
+        );
+        data.timestamp = DateTime::from_timestamp(1_700_000_000, 0).unwrap()
+            + chrono::Duration::milliseconds(ms);
+        Some(data)
+    }
+
+    fn at(ms: i64) -> DateTime<Utc> {
+        DateTime::from_timestamp(1_700_000_000, 0).unwrap() + chrono::Duration::milliseconds(ms)
+    }
+
+    #[test]
+    fn test_averages_fresh_sources() {
+        let mut f = fusion("divergence_channels = { include = [\"g_force_x\"] }");
+        let (data, changes) = f
+            .fuse(&[reading(0, 0.30, 10.0), reading(5, 0.20, 14.0)], at(10))
+            .unwrap();
+        assert!(changes.is_empty());
+        assert!((data.fields["g_force_x"] - 0.25).abs() < 1e-9);
+        assert_eq!(data.fields["yaw_rate"], 12.0);
+        // yaw_rate is 4 apart but not compared
+        assert!((data.fields["divergence"] - 0.1).abs() < 1e-9);
+        assert_eq!(data.fields["fused_sources"], 2.0);
+        assert_eq!(data.timestamp, at(5));
+
+        // Nothing new since, so nothing to publish
+        assert!(
+            f.fuse(&[reading(0, 0.30, 10.0), reading(5, 0.20, 14.0)], at(20))
+                .is_none()
+        );
+
+        // A stale source and an invalid channel are left out
+        let mut rear = reading(600, 9.0, 9.0);
+        rear.as_mut()
+            .unwrap()
+            .set_quality("g_force_x", Quality::Invalid);
+        let (data, _) = f.fuse(&[reading(0, 0.30, 10.0), rear], at(650)).unwrap();
+        assert!(!data.fields.contains_key("g_force_x"));
+        assert_eq!(data.fields["yaw_rate"], 9.0);
+        assert_eq!(data.fields["fused_sources"], 1.0);
+        assert_eq!(data.quality("divergence"), Quality::Invalid);
+    }
+
+    #[test]
+    fn test_peaks_and_wrapping_angles_not_fused_by_default() {
+        let with = |ms, peak, heading| {
+            let mut data = reading(ms, 0.2, 0.0).unwrap();
+            data.fields.insert("peak_g".into(), peak);
+            data.fields.insert("heading".into(), heading);
+            Some(data)
+        };
+        let (data, _) = fusion("")
+            .fuse(&[with(0, 1.5, 359.0), with(0, 0.9, 1.0)], at(0))
+            .unwrap();
+        assert_eq!(data.fields["g_force_x"], 0.2);
+        assert!(!data.fields.contains_key("peak_g"));
+        assert!(!data.fields.contains_key("heading"));
+
+        // Listed, they are averaged like any other channel
+        let (data, _) = fusion("channels = { include = [\"peak_g\"] }")
+            .fuse(&[with(0, 1.5, 359.0), with(0, 0.9, 1.0)], at(0))
+            .unwrap();
+        assert!((data.fields["peak_g"] - 1.2).abs() < 1e-9);
+        assert!(!data.fields.contains_key("g_force_x"));
+    }
+
+    #[test]
+    fn test_divergence_alert() {
+        let mut f = fusion("max_divergence = 0.2\nchannels = { include = [\"g_force_x\"] }");
+        let (data, changes) = f
+            .fuse(&[reading(0, 0.1, 0.0), reading(0, 0.5, 0.0)], at(0))
+            .unwrap();
+        assert!(!data.fields.contains_key("yaw_rate"));
+        let [AlertChange::Fired(alert)] = changes.as_slice() else {
+            panic!("expected one alert, got {:?}", changes);
+        };
+        assert_eq!(
+            alert.text,
+            "chassis sources disagree: g_force_x 0.400 apart (limit 0.2)"
+        );
+
+        // Still over half the limit: stays up
+        let (_, changes) = f
+            .fuse(&[reading(10, 0.1, 0.0), reading(10, 0.25, 0.0)], at(10))
+            .unwrap();
+        assert!(changes.is_empty());
+        let (_, changes) = f
+            .fuse(&[reading(20, 0.1, 0.0), reading(20, 0.15, 0.0)], at(20))
+            .unwrap();
+        assert_eq!(changes, [AlertChange::Cleared { id: alert.id }]);
+    }
+}
@@ -0,0 +1,172 @@
+//! Switching `[[groups]]` of sensors on and off at runtime.
+//!
+//! `AppState::groups` holds whether each group is on; sensor tasks in a
+//! group that is off stop reading and report themselves offline.  The TUI
+//! toggles the selected sensor's group, and over MQTT a group is switched
+//! by publishing `on` or `off` to `<base_topic>/GROUPS/<name>/SET`.  The
+//! current state of every group is kept retained on
+//! `<base_topic>/GROUPS/<name>`, whichever way it was changed.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use indexmap::IndexMap;
+use tokio_util::sync::CancellationToken;
+use tracing::{info, warn};
+
+use crate::command_auth::CommandAuth;
+use crate::config::SensorGroupConfig;
+use crate::models::SharedState;
+use crate::mqtt_handler::MqttHandle;
+
+/// How often the retained group states are compared with `AppState`.
+const STATE_POLL: Duration = Duration::from_millis(500);
+
+/// Load the configured groups into `groups`, with their startup state.
+pub fn register_groups(groups: &mut IndexMap<String, bool>, cfg: &[SensorGroupConfig]) {
+    groups.extend(cfg.iter().map(|g| (g.name.clone(), g.enabled)));
+}
+
+/// `on` / `off` (also `true` / `false`, `1` / `0`), any case.
+pub fn parse_switch(payload: &[u8]) -> Option<bool> {
+    let text = std::str::from_utf8(payload).ok()?.trim();
+    match text.to_ascii_lowercase().as_str() {
+        "on" | "true" | "1" => Some(true),
+        "off" | "false" | "0" => Some(false),
+        _ => None,
+    }
+}
+
+/// Follow `SET` commands that `auth` accepts and publish every group's
+/// state until `cancel` fires.
+pub fn spawn_group_control(
+    state: SharedState,
+    mqtt: MqttHandle,
+    base_topic: &str,
+    auth: Arc<CommandAuth>,
+    cancel: CancellationToken,
+) {
+    let prefix = format!("{}/GROUPS/", base_topic);
+    let mut commands = mqtt.subscribe(format!("{}+/SET", prefix));
+    tokio::spawn(async move {
+        let mut published: IndexMap<String, bool> = IndexMap::new();
+        let mut tick = tokio::time::interval(STATE_POLL);
+        loop {
+            tokio::select! {
+                _ = cancel.cancelled() => return,
+                Some(msg) = commands.recv() => {
+                    let Some(group) = msg
+                        .topic
+                        .strip_prefix(prefix.as_str())
+                        .and_then(|t| t.strip_suffix("/SET"))
+                    else {
+                        continue;
+                    };
+                    let payload = match auth.check("groups", &msg.topic, &msg.payload) {
+                        Ok(p) => p,
+                        Err(e) => {
+                            warn!("Rejected MQTT command for group '{}': {}", group, e);
+                            continue;
+                        }
+                    };
+                    let mut s = state.write().await;
+                    match parse_switch(&payload) {
+                        Some(on) if s.set_group_enabled(group, on) => {
+                            info!("Group '{}' switched {} over MQTT", group, on_off(on));
+                        }
+                        Some(_) => {
+                            warn!("MQTT command for unknown group '{}'", group);
+                            s.status.warn(format!("No group '{}'", group));
+                        }
+                        None => warn!(
+                            "Group '{}': expected 'on' or 'off', got {:?}",
+                            group,
+                            String::from_utf8_lossy(&payload)
+                        ),
+                    }
+                }
+                _ = tick.tick() => {}
+            }
+            let groups = state.read().await.groups.clone();
+            for (name, &on) in &groups {
+                if published.get(name) != Some(&on) {
+                    let topic: Arc<str> = format!("{}{}", prefix, name).into();
+                    mqtt.publish_retained(topic, on_off(on)).await;
+                }
+            }
+            published = groups;
+        }
+    });
+}
+
+fn on_off(on: bool) -> &'static str {
+    if on { "on" } else { "off" }
+}
+
+// ---------------------------------------------------------------------------
+// Unit tests
+// ---------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::MqttConfig;
+    use crate::models::AppState;
+
+    #[test]
+    fn test_parse_switch() {
+        assert_eq!(parse_switch(b"on"), Some(true));
+        assert_eq!(parse_switch(b" OFF\n"), Some(false));
+        assert_eq!(parse_switch(b"1"), Some(true));
+        assert_eq!(parse_switch(b"toggle"), None);
+    }
+
+    #[test]
+    fn test_groups_switch_their_sensors() {
+        let mut s = AppState::new(String::new(), false, 10);
+        let cfg: Vec<SensorGroupConfig> = toml::from_str::<toml::Table>(
+            "[[groups]]\nname = \"chassis\"\n[[groups]]\nname = \"engine\"\nenabled = false",
+        )
+        .unwrap()["groups"]
+            .clone()
+            .try_into()
+            .unwrap();
+        register_groups(&mut s.groups, &cfg);
+
+        assert!(s.group_enabled(None), "ungrouped sensors always read");
+        assert!(s.group_enabled(Some("chassis")));
+        assert!(!s.group_enabled(Some("engine")));
+        assert!(s.set_group_enabled("engine", true));
+        assert!(s.group_enabled(Some("engine")));
+        assert!(!s.set_group_enabled("lights", false));
+    }
+
+    #[tokio::test]
+    async fn test_group_state_published_retained() {
+        let mut s = AppState::new(String::new(), true, 10);
+        s.groups.insert("chassis".into(), true);
+        let state = Arc::new(tokio::sync::RwLock::new(s));
+        let mqtt = MqttHandle::offline(&MqttConfig::default());
+        let cancel = CancellationToken::new();
+        let auth = Arc::new(CommandAuth::new(&Default::default()));
+        spawn_group_control(state.clone(), mqtt.clone(), "/S", auth, cancel.clone());
+
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        state.write().await.set_group_enabled("chassis", false);
+        tokio::time::sleep(STATE_POLL + Duration::from_millis(100)).await;
+        cancel.cancel();
+
+        let sent: Vec<_> = mqtt
+            .take_queued()
+            .into_iter()
+            .map(|m| (m.topic.to_string(), m.payload, m.retain))
+            .collect();
+        assert_eq!(
+            sent,
+            [
+                ("/S/GROUPS/chassis".to_string(), b"on".to_vec(), true),
+                ("/S/GROUPS/chassis".to_string(), b"off".to_vec(), true),
+            ]
+        );
+    }
+}
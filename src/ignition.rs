@@ -0,0 +1,132 @@
+//! Ignition detection: pause sampling and publishing while the car is off.
+//!
+//! One sensor tells whether the engine is running — usually an INA219 on the
+//! supply, where a charging alternator lifts the voltage above a resting
+//! battery, or a GPIO input wired to the ignition line.  Its `field` is
+//! compared with `on_above` on every reading, and the state only switches
+//! once the signal has held for the configured grace period, so a stall, a
+//! cranking dip or a bouncing contact doesn't pause the daemon.
+//!
+//! While the ignition is off, `AppState::paused` is set: every other sensor
+//! stops reading and publishing, and the ignition sensor is polled slowly so
+//! it can notice the engine starting again.
+
+use chrono::{DateTime, Utc};
+
+use crate::config::IgnitionConfig;
+use crate::sensors::{Quality, SensorData};
+
+pub struct IgnitionMonitor {
+    cfg: IgnitionConfig,
+    on: bool,
+    /// When the signal started disagreeing with `on`
+    pending: Option<DateTime<Utc>>,
+}
+
+impl IgnitionMonitor {
+    /// Starts on, since the daemon normally boots with the ignition; if the
+    /// car is actually off, sampling pauses after `off_delay_secs`.
+    pub fn new(cfg: IgnitionConfig) -> Self {
+        Self {
+            cfg,
+            on: true,
+            pending: None,
+        }
+    }
+
+    pub fn is_on(&self) -> bool {
+        self.on
+    }
+
+    /// Feed one reading; returns the new state when it switches.  Readings
+    /// without a good value for the field are ignored.
+    pub fn update(&mut self, data: &SensorData) -> Option<bool> {
+        if data.quality(&self.cfg.field) != Quality::Good {
+            return None;
+        }
+        let signal = *data.fields.get(self.cfg.field.as_str())? >= self.cfg.on_above;
+        if signal == self.on {
+            self.pending = None;
+            return None;
+        }
+
+        let since = *self.pending.get_or_insert(data.timestamp);
+        let held = (data.timestamp - since).num_milliseconds() as f64 / 1000.0;
+        let delay = if signal {
+            self.cfg.on_delay_secs
+        } else {
+            self.cfg.off_delay_secs
+        };
+        if held < delay {
+            return None;
+        }
+        self.on = signal;
+        self.pending = None;
+        Some(signal)
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Unit tests
+// ---------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn monitor() -> IgnitionMonitor {
+        IgnitionMonitor::new(IgnitionConfig {
+            enabled: true,
+            sensor: "Battery".into(),
+            ..Default::default()
+        })
+    }
+
+    fn volts(t0: DateTime<Utc>, secs: i64, v: f64) -> SensorData {
+        let mut d = SensorData::new([("bus_voltage_v".into(), v)].into_iter().collect());
+        d.timestamp = t0 + chrono::Duration::seconds(secs);
+        d
+    }
+
+    #[test]
+    fn test_switches_only_after_grace_period() {
+        let mut m = monitor();
+        let t0 = Utc::now();
+        assert_eq!(m.update(&volts(t0, 0, 14.1)), None);
+
+        // Engine off: the battery rests at 12.6 V
+        assert_eq!(m.update(&volts(t0, 1, 12.6)), None);
+        assert_eq!(m.update(&volts(t0, 30, 12.6)), None);
+        assert!(m.is_on());
+        assert_eq!(m.update(&volts(t0, 61, 12.6)), Some(false));
+        assert_eq!(m.update(&volts(t0, 62, 12.6)), None);
+
+        // Cranking, then the alternator comes up
+        assert_eq!(m.update(&volts(t0, 100, 14.0)), None);
+        assert_eq!(m.update(&volts(t0, 102, 14.0)), Some(true));
+        assert!(m.is_on());
+    }
+
+    #[test]
+    fn test_brief_dip_does_not_pause() {
+        let mut m = monitor();
+        let t0 = Utc::now();
+        assert_eq!(m.update(&volts(t0, 0, 12.0)), None);
+        assert_eq!(m.update(&volts(t0, 40, 14.0)), None);
+        // The grace period restarts after the signal comes back
+        assert_eq!(m.update(&volts(t0, 50, 12.0)), None);
+        assert_eq!(m.update(&volts(t0, 100, 12.0)), None);
+        assert!(m.is_on());
+    }
+
+    #[test]
+    fn test_ignores_bad_readings() {
+        let mut m = monitor();
+        let t0 = Utc::now();
+        let mut d = volts(t0, 0, 0.0);
+        d.set_quality("bus_voltage_v", Quality::Invalid);
+        assert_eq!(m.update(&d), None);
+        assert_eq!(m.update(&volts(t0, 100, 0.0)), None);
+        assert!(m.is_on(), "grace period starts at the first good reading");
+    }
+}
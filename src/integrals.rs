@@ -0,0 +1,179 @@
+//! Running-integral channels (`[[integrals]]`).
+//!
+//! Each entry adds the running total of one of a sensor's channels over
+//! time, such as a yaw angle from `yaw_rate` or the speed change from
+//! longitudinal G.  Steps use the trapezoid rule with time taken from the
+//! readings' timestamps.  A gap longer than [`MAX_STEP_SECS`] isn't
+//! integrated across: the total holds and continues from the next reading.
+//!
+//! An integral of a real sensor drifts, since any bias in the input adds
+//! up.  `leak_secs` lets the total decay towards 0 with that time constant,
+//! which suits quantities that should settle (a speed change during a
+//! manoeuvre); `reset_secs` starts it again from 0 periodically; `wrap`
+//! keeps an angle within one turn.
+
+use chrono::{DateTime, Utc};
+
+use crate::config::IntegralConfig;
+use crate::sensors::{FieldDescriptor, SensorData, VizType};
+
+/// Readings further apart than this aren't integrated across.
+const MAX_STEP_SECS: f64 = 0.5;
+
+/// Descriptors for the channels `cfgs` add.  Keys and labels come from the
+/// config, so they are leaked once per call (at sensor start).
+pub fn field_descriptors(cfgs: &[IntegralConfig]) -> Vec<FieldDescriptor> {
+    let leak = |s: String| -> &'static str { Box::leak(s.into_boxed_str()) };
+    cfgs.iter()
+        .enumerate()
+        .map(|(i, c)| FieldDescriptor {
+            key: leak(c.name.clone()),
+            label: leak(format!("∫{} dt", c.field)),
+            viz: VizType::Numeric {
+                unit: leak(c.unit.clone()),
+            },
+            range: (c.wrap > 0.0).then_some((0.0, c.wrap)),
+            group: (i == 0).then_some("INTEGRALS"),
+        })
+        .collect()
+}
+
+/// The `[[integrals]]` of one sensor.
+pub struct Integrator {
+    channels: Vec<Channel>,
+}
+
+struct Channel {
+    cfg: IntegralConfig,
+    total: f64,
+    /// Timestamp and scaled input of the previous reading
+    last: Option<(DateTime<Utc>, f64)>,
+    /// When the current `reset_secs` period began
+    period_start: Option<DateTime<Utc>>,
+}
+
+impl Integrator {
+    pub fn new(cfgs: Vec<IntegralConfig>) -> Self {
+        Self {
+            channels: cfgs
+                .into_iter()
+                .map(|cfg| Channel {
+                    cfg,
+                    total: 0.0,
+                    last: None,
+                    period_start: None,
+                })
+                .collect(),
+        }
+    }
+
+    /// Add each reading's step to the totals and append them to `data`.
+    /// Readings without the source channel get none.
+    pub fn process(&mut self, data: &mut SensorData) {
+        let now = data.timestamp;
+        for ch in &mut self.channels {
+            let Some(&raw) = data.fields.get(ch.cfg.field.as_str()) else {
+                continue;
+            };
+            let value = raw * ch.cfg.scale;
+
+            let start = *ch.period_start.get_or_insert(now);
+            if ch.cfg.reset_secs > 0.0 && secs(start, now) >= ch.cfg.reset_secs {
+                ch.total = 0.0;
+                ch.period_start = Some(now);
+            } else if let Some((t, prev)) = ch.last {
+                let dt = secs(t, now);
+                if dt > 0.0 && dt <= MAX_STEP_SECS {
+                    ch.total += (prev + value) / 2.0 * dt;
+                    if ch.cfg.leak_secs > 0.0 {
+                        ch.total *= (-dt / ch.cfg.leak_secs).exp();
+                    }
+                }
+            }
+            if ch.cfg.wrap > 0.0 {
+                ch.total = ch.total.rem_euclid(ch.cfg.wrap);
+            }
+            ch.last = Some((now, value));
+
+            data.fields.insert(ch.cfg.name.clone().into(), ch.total);
+        }
+    }
+}
+
+fn secs(from: DateTime<Utc>, to: DateTime<Utc>) -> f64 {
+    (to - from).num_microseconds().unwrap_or(i64::MAX) as f64 / 1e6
+}
+
+// ---------------------------------------------------------------------------
+// Unit tests
+// ---------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+
+    fn config() -> IntegralConfig {
+        IntegralConfig {
+            sensor: "imu".into(),
+            field: "yaw_rate".into(),
+            name: "yaw_angle".into(),
+            scale: 1.0,
+            leak_secs: 0.0,
+            wrap: 0.0,
+            reset_secs: 0.0,
+            unit: "°".into(),
+        }
+    }
+
+    /// Feed `rate` at the given millisecond offsets; returns the last total.
+    fn run(integrator: &mut Integrator, rate: f64, offsets_ms: &[i64]) -> f64 {
+        let t0 = DateTime::from_timestamp(1_700_000_000, 0).unwrap();
+        let mut total = 0.0;
+        for &ms in offsets_ms {
+            let mut d = SensorData::new([("yaw_rate".into(), rate)].into_iter().collect());
+            d.timestamp = t0 + Duration::milliseconds(ms);
+            integrator.process(&mut d);
+            total = d.fields["yaw_angle"];
+        }
+        total
+    }
+
+    #[test]
+    fn test_integrates_over_uneven_steps_but_not_gaps() {
+        let mut i = Integrator::new(vec![config()]);
+        // 90 °/s for 0.1 + 0.3 + 0.2 s
+        assert!((run(&mut i, 90.0, &[0, 100, 400, 600]) - 54.0).abs() < 1e-9);
+
+        let mut i = Integrator::new(vec![config()]);
+        // The 2 s gap isn't integrated across
+        assert!((run(&mut i, 90.0, &[0, 100, 2100, 2200]) - 18.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_wrap_leak_and_reset() {
+        let steps: Vec<i64> = (0..=100).map(|n| n * 50).collect();
+
+        let mut i = Integrator::new(vec![IntegralConfig {
+            wrap: 360.0,
+            ..config()
+        }]);
+        // 100 °/s for 5 s is 500°, one and a bit turns
+        assert!((run(&mut i, 100.0, &steps) - 140.0).abs() < 1e-6);
+
+        let mut i = Integrator::new(vec![IntegralConfig {
+            leak_secs: 1.0,
+            ..config()
+        }]);
+        // A constant bias settles near bias × leak_secs instead of growing
+        let settled = run(&mut i, 2.0, &steps);
+        assert!((settled - 2.0).abs() < 0.1, "{}", settled);
+
+        let mut i = Integrator::new(vec![IntegralConfig {
+            reset_secs: 2.0,
+            ..config()
+        }]);
+        // Reset at 2 s and 4 s, then 1 s more
+        assert!((run(&mut i, 10.0, &steps) - 10.0).abs() < 1e-9);
+    }
+}
@@ -0,0 +1,233 @@
+//! Lap timing from GPS position and a start/finish line.
+//!
+//! The line is two `[latitude, longitude]` points from `[laps]` in config.
+//! Each GPS fix is joined to the previous one, and a lap is counted when
+//! that segment crosses the line.  The crossing time is interpolated along
+//! the segment, so lap times are not limited to the GPS update rate.
+
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::config::LapsConfig;
+use crate::sensors::{Quality, SensorData};
+
+/// Mean Earth radius in metres (equirectangular projection).
+const EARTH_RADIUS_M: f64 = 6_371_000.0;
+
+/// Fixes further apart than this are a gap in coverage, not a path.
+const MAX_GAP_SECS: f64 = 5.0;
+
+/// Lap counter shown in the TUI.  `started` is kept instead of the current
+/// lap time so attached clients can tick the clock between snapshots.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct LapStatus {
+    /// Lap in progress; 0 until the line is first crossed
+    pub lap: u32,
+    pub started: Option<DateTime<Utc>>,
+    pub last: Option<Duration>,
+    pub best: Option<Duration>,
+}
+
+impl LapStatus {
+    /// Time on the lap in progress.
+    pub fn current(&self, now: DateTime<Utc>) -> Option<Duration> {
+        self.started.and_then(|t| (now - t).to_std().ok())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum LapEvent {
+    /// First crossing — lap 1 starts
+    Started,
+    /// A lap finished and the next one started
+    Completed {
+        lap: u32,
+        time: Duration,
+        best: bool,
+    },
+}
+
+pub struct LapTimer {
+    cfg: LapsConfig,
+    status: LapStatus,
+    last_fix: Option<(DateTime<Utc>, [f64; 2])>,
+}
+
+impl LapTimer {
+    pub fn new(cfg: LapsConfig) -> Self {
+        Self {
+            cfg,
+            status: LapStatus::default(),
+            last_fix: None,
+        }
+    }
+
+    pub fn status(&self) -> &LapStatus {
+        &self.status
+    }
+
+    /// Feed one GPS reading.  Readings without a good position are ignored.
+    pub fn update(&mut self, data: &SensorData) -> Option<LapEvent> {
+        let fix = [
+            self.coordinate(data, &self.cfg.latitude_field)?,
+            self.coordinate(data, &self.cfg.longitude_field)?,
+        ];
+        let (prev_ts, prev) = self.last_fix.replace((data.timestamp, fix))?;
+
+        let span = (data.timestamp - prev_ts).num_milliseconds() as f64 / 1000.0;
+        if span <= 0.0 || span > MAX_GAP_SECS {
+            return None;
+        }
+        let t = crossing(prev, fix, self.cfg.start_finish)?;
+        let at = prev_ts + chrono::Duration::milliseconds((span * t * 1000.0) as i64);
+        self.cross(at)
+    }
+
+    fn coordinate(&self, data: &SensorData, key: &str) -> Option<f64> {
+        if data.quality(key) != Quality::Good {
+            return None;
+        }
+        data.fields.get(key).copied()
+    }
+
+    fn cross(&mut self, at: DateTime<Utc>) -> Option<LapEvent> {
+        let Some(time) = self.status.current(at) else {
+            self.status.lap = 1;
+            self.status.started = Some(at);
+            return Some(LapEvent::Started);
+        };
+        // GPS jitter around the line would otherwise count several laps
+        if time.as_secs_f64() < self.cfg.min_lap_secs {
+            return None;
+        }
+
+        let best = self.status.best.is_none_or(|b| time < b);
+        if best {
+            self.status.best = Some(time);
+        }
+        let lap = self.status.lap;
+        self.status.last = Some(time);
+        self.status.lap += 1;
+        self.status.started = Some(at);
+        Some(LapEvent::Completed { lap, time, best })
+    }
+}
+
+/// Where along `p → q` the path crosses the line `[a, b]`, as a fraction
+/// in `0..=1`.  Points are `[latitude, longitude]`, projected to metres
+/// around `a` — plenty accurate over the few metres between fixes.
+fn crossing(p: [f64; 2], q: [f64; 2], [a, b]: [[f64; 2]; 2]) -> Option<f64> {
+    let to_xy = |pt: [f64; 2]| {
+        let x = (pt[1] - a[1]).to_radians() * a[0].to_radians().cos() * EARTH_RADIUS_M;
+        let y = (pt[0] - a[0]).to_radians() * EARTH_RADIUS_M;
+        (x, y)
+    };
+    let (px, py) = to_xy(p);
+    let (qx, qy) = to_xy(q);
+    let (bx, by) = to_xy(b);
+
+    let (rx, ry) = (qx - px, qy - py);
+    let denom = rx * by - ry * bx;
+    if denom.abs() < f64::EPSILON {
+        return None; // parallel to the line
+    }
+    // p + t·r = u·b, with a at the origin
+    let t = (-px * by + py * bx) / denom;
+    let u = (-px * ry + py * rx) / denom;
+    ((0.0..=1.0).contains(&t) && (0.0..=1.0).contains(&u)).then_some(t)
+}
+
+/// `1:23.456`
+pub fn format_lap(d: Duration) -> String {
+    let ms = d.as_millis();
+    format!("{}:{:02}.{:03}", ms / 60_000, ms / 1000 % 60, ms % 1000)
+}
+
+// ---------------------------------------------------------------------------
+// Unit tests
+// ---------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A north–south line at longitude 24.0 between latitudes 57.0 and 57.001.
+    fn timer() -> LapTimer {
+        LapTimer::new(LapsConfig {
+            enabled: true,
+            start_finish: [[57.0, 24.0], [57.001, 24.0]],
+            ..Default::default()
+        })
+    }
+
+    fn fix(t: DateTime<Utc>, lat: f64, lon: f64) -> SensorData {
+        let mut d = SensorData::new(
+            [
+                ("latitude".into(), lat),
+                ("longitude".into(), lon),
+            ]
+            .into_iter()
+            .collect(),
+        );
+        d.timestamp = t;
+        d
+    }
+
+    #[test]
+    fn test_crossing_interpolates_between_fixes() {
+        // Halfway across, a quarter of the way along the line
+        let t = crossing(
+            [57.00025, 23.9999],
+            [57.00025, 24.0001],
+            [[57.0, 24.0], [57.001, 24.0]],
+        );
+        assert!((t.unwrap() - 0.5).abs() < 1e-6);
+        // Passes north of the line's end
+        assert!(
+            crossing(
+                [57.002, 23.9999],
+                [57.002, 24.0001],
+                [[57.0, 24.0], [57.001, 24.0]]
+            )
+            .is_none()
+        );
+    }
+
+    #[test]
+    fn test_laps_counted_on_crossings() {
+        let mut timer = timer();
+        let t0 = Utc::now();
+        let s = chrono::Duration::seconds;
+
+        assert_eq!(timer.update(&fix(t0, 57.0005, 23.9999)), None);
+        assert_eq!(
+            timer.update(&fix(t0 + s(1), 57.0005, 24.0001)),
+            Some(LapEvent::Started)
+        );
+        assert_eq!(timer.status().lap, 1);
+
+        // Jitter back over the line straight away is ignored
+        assert_eq!(timer.update(&fix(t0 + s(2), 57.0005, 23.9999)), None);
+
+        assert_eq!(timer.update(&fix(t0 + s(60), 57.0005, 23.9999)), None);
+        let ev = timer.update(&fix(t0 + s(61), 57.0005, 24.0001)).unwrap();
+        assert_eq!(
+            ev,
+            LapEvent::Completed {
+                lap: 1,
+                time: Duration::from_secs(60),
+                best: true
+            }
+        );
+        assert_eq!(timer.status().lap, 2);
+        assert_eq!(timer.status().best, Some(Duration::from_secs(60)));
+    }
+
+    #[test]
+    fn test_format_lap() {
+        assert_eq!(format_lap(Duration::from_millis(83_456)), "1:23.456");
+        assert_eq!(format_lap(Duration::from_millis(5_007)), "0:05.007");
+    }
+}
@@ -2,12 +2,46 @@
 //!
 //! Exposes all modules for integration testing and potential reuse.
 
+pub mod activity;
+pub mod aggregation;
+pub mod alerts;
+pub mod anomaly;
+pub mod clock;
+pub mod command_auth;
 pub mod config;
+pub mod dashboard;
+pub mod derivatives;
+pub mod diagnostics;
+pub mod differences;
+pub mod drift;
+pub mod dynamics;
 pub mod error;
+pub mod error_budget;
+pub mod export;
+pub mod extremes;
 pub mod filters;
+pub mod fusion;
+pub mod groups;
+pub mod ignition;
+pub mod integrals;
+pub mod laps;
 pub mod models;
+pub mod payload;
+pub mod processing;
+pub mod protobuf;
+pub mod publishing;
+pub mod read_order;
 pub mod mqtt_handler;
+pub mod recorder;
+pub mod remote_config;
+pub mod replay;
+pub mod retention;
+pub mod self_check;
 pub mod sensors;
 pub mod service;
+pub mod slip;
+pub mod subscriber;
+pub mod transforms;
 pub mod transport;
 pub mod tui;
+pub mod vibration;
@@ -1,8 +1,14 @@
 //! sensors-to-mqtt — main entry point.
 //!
-//! TTY detection:
+//! TTY detection (unless `--mode` or `service.run_mode` chooses):
 //!   - stdout is a terminal → TUI mode (interactive)
 //!   - stdout is piped / systemd → daemon mode (structured logs to stdout)
+//!
+//! The TUI needs a terminal on stdin and stdout; asked for without one it
+//! falls back to daemon mode (`sub`: plain mode) with a warning.
+//!
+//! `--mode plain` prints a refreshing plain-text summary instead of the TUI.
+//! In daemon mode the UI is served on `ui.attach_socket`; `attach` shows it.
 
 use anyhow::Result;
 use gumdrop::Options as _;
@@ -12,10 +18,26 @@ use tokio::sync::RwLock;
 use tokio_util::sync::CancellationToken;
 use tracing_subscriber::EnvFilter;
 
-use sensors_to_mqtt::config::load_configuration;
+use sensors_to_mqtt::command_auth::{self, CommandAuth};
+use sensors_to_mqtt::config::{AppConfig, RunMode, load_configuration, load_raw_configuration};
 use sensors_to_mqtt::models::{AppState, SharedState};
-use sensors_to_mqtt::service::{register_sensors, spawn_sensor_task};
-use sensors_to_mqtt::{mqtt_handler, tui};
+use sensors_to_mqtt::read_order::ReadOrder;
+use sensors_to_mqtt::sensors::registry::create_sensor;
+use sensors_to_mqtt::service::{
+    PayloadKeys, SensorProcessing, driver_fields, payload_json_schemas, publish_disabled,
+    register_sensors, spawn_sensor_task,
+};
+use sensors_to_mqtt::{
+    clock, dashboard, diagnostics, extremes, fusion, groups, mqtt_handler, protobuf, publishing,
+    remote_config, replay, self_check, subscriber, transport, tui,
+};
+
+/// Longest wait at shutdown for sensor tasks to stop and for their final
+/// `offline` messages to be published.
+const SHUTDOWN_GRACE: std::time::Duration = std::time::Duration::from_secs(2);
+/// Longest a bounded run (`--once`, `--samples`) waits at the end for a
+/// broker it hasn't reached yet.
+const BOUNDED_CONNECT_WAIT: std::time::Duration = std::time::Duration::from_secs(10);
 
 // ---------------------------------------------------------------------------
 // CLI  (gumdrop)
@@ -34,6 +56,179 @@ struct Opts {
 
     #[options(long = "log-level", help = "log level: trace|debug|info|warn|error")]
     log_level: Option<String>,
+
+    #[options(
+        short = "m",
+        help = "interface: tui|plain|daemon (default: service.run_mode)"
+    )]
+    mode: Option<Mode>,
+
+    #[options(no_short, help = "read and publish every sensor once, then exit")]
+    once: bool,
+
+    #[options(no_short, help = "read every sensor N times, then exit")]
+    samples: Option<u64>,
+
+    #[options(no_short, help = "exit after this many seconds")]
+    duration: Option<f64>,
+
+    #[options(command)]
+    command: Option<Command>,
+}
+
+#[derive(Debug, gumdrop::Options)]
+enum Command {
+    #[options(help = "display the live TUI of a running daemon")]
+    Attach(AttachOpts),
+    #[options(help = "run the sensors' built-in self-tests and exit")]
+    SelfTest(SelfTestOpts),
+    #[options(help = "show what the broker receives from a daemon")]
+    Sub(SubOpts),
+    #[options(help = "print JSON Schemas of the sensors' MQTT payloads and exit")]
+    Schema(SchemaOpts),
+    #[options(help = "print the .proto for payload_format = \"protobuf\" and exit")]
+    Proto(ProtoOpts),
+    #[options(help = "print a command signed with command_auth.secret and exit")]
+    Sign(SignOpts),
+    #[options(help = "publish recorded session files to the broker")]
+    Replay(ReplayOpts),
+}
+
+#[derive(Debug, gumdrop::Options)]
+struct AttachOpts {
+    #[options(help = "print help")]
+    help: bool,
+
+    #[options(help = "daemon socket (default: ui.attach_socket from the config)")]
+    socket: Option<String>,
+}
+
+#[derive(Debug, gumdrop::Options)]
+struct SelfTestOpts {
+    #[options(help = "print help")]
+    help: bool,
+
+    #[options(help = "only test this sensor (default: all enabled sensors)")]
+    sensor: Option<String>,
+}
+
+#[derive(Debug, gumdrop::Options)]
+struct SchemaOpts {
+    #[options(help = "print help")]
+    help: bool,
+
+    #[options(help = "only this sensor (default: all enabled sensors)")]
+    sensor: Option<String>,
+}
+
+#[derive(Debug, gumdrop::Options)]
+struct ProtoOpts {
+    #[options(help = "print help")]
+    help: bool,
+}
+
+#[derive(Debug, gumdrop::Options)]
+struct SignOpts {
+    #[options(help = "print help")]
+    help: bool,
+
+    #[options(help = "topic the command will be published to")]
+    topic: Option<String>,
+
+    #[options(free, help = "the command, e.g. `on`")]
+    command: Vec<String>,
+}
+
+#[derive(Debug, gumdrop::Options)]
+struct ReplayOpts {
+    #[options(help = "print help")]
+    help: bool,
+
+    #[options(help = "playback speed, 2 is twice as fast as recorded (default: 1)")]
+    speed: Option<f64>,
+
+    #[options(
+        long = "loop",
+        no_short,
+        help = "start over after the last file until stopped"
+    )]
+    repeat: bool,
+
+    #[options(
+        long = "original-timestamps",
+        no_short,
+        help = "publish the recorded timestamps instead of the current time"
+    )]
+    original_timestamps: bool,
+
+    #[options(help = "broker host (default: mqtt.host from the config)")]
+    host: Option<String>,
+
+    #[options(help = "broker port (default: mqtt.port from the config)")]
+    port: Option<u16>,
+
+    #[options(
+        long = "base-topic",
+        help = "base topic to publish under (default: mqtt.base_topic from the config)"
+    )]
+    base_topic: Option<String>,
+
+    #[options(free, help = "session files (.jsonl), played in the order given")]
+    files: Vec<String>,
+}
+
+#[derive(Debug, gumdrop::Options)]
+struct SubOpts {
+    #[options(help = "print help")]
+    help: bool,
+
+    #[options(help = "broker host (default: mqtt.host from the config)")]
+    host: Option<String>,
+
+    #[options(help = "broker port (default: mqtt.port from the config)")]
+    port: Option<u16>,
+
+    #[options(
+        long = "base-topic",
+        help = "daemon's base topic (default: mqtt.base_topic from the config)"
+    )]
+    base_topic: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Mode {
+    Tui,
+    Plain,
+    Daemon,
+}
+
+impl std::str::FromStr for Mode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "tui" => Ok(Mode::Tui),
+            "plain" => Ok(Mode::Plain),
+            "daemon" => Ok(Mode::Daemon),
+            other => Err(format!("unknown mode '{}' (tui|plain|daemon)", other)),
+        }
+    }
+}
+
+/// `service.run_mode`; `None` leaves it to TTY detection.
+fn configured_mode(cfg: &AppConfig) -> Option<Mode> {
+    match cfg.service.run_mode {
+        RunMode::Auto => None,
+        RunMode::Tui => Some(Mode::Tui),
+        RunMode::Plain => Some(Mode::Plain),
+        RunMode::Daemon => Some(Mode::Daemon),
+    }
+}
+
+/// Whether the TUI can start: it reads keys from stdin in raw mode and
+/// draws on stdout.
+fn has_terminal() -> bool {
+    atty::is(atty::Stream::Stdin) && atty::is(atty::Stream::Stdout)
 }
 
 // ---------------------------------------------------------------------------
@@ -44,9 +239,92 @@ struct Opts {
 async fn main() -> Result<()> {
     let opts = Opts::parse_args_default_or_exit();
 
-    let cfg = load_configuration(opts.config.as_deref())?;
+    let mut cfg = load_configuration(opts.config.as_deref())?;
+
+    match opts.command {
+        Some(Command::Attach(attach)) => {
+            let socket = attach
+                .socket
+                .unwrap_or_else(|| cfg.ui.attach_socket.clone());
+            return tui::run_attach(
+                std::path::Path::new(&socket),
+                CancellationToken::new(),
+                cfg.ui.clone(),
+                cfg.tui_refresh(),
+            )
+            .await;
+        }
+        Some(Command::SelfTest(st)) => return self_test(&cfg, st.sensor.as_deref()),
+        Some(Command::Schema(sc)) => return json_schema(&cfg, sc.sensor.as_deref()),
+        Some(Command::Proto(_)) => {
+            print!("{}", protobuf::PROTO);
+            return Ok(());
+        }
+        Some(Command::Sign(sign)) => {
+            if cfg.command_auth.secret.is_empty() {
+                anyhow::bail!("command_auth.secret is not set");
+            }
+            let topic = sign
+                .topic
+                .ok_or_else(|| anyhow::anyhow!("--topic is required"))?;
+            println!(
+                "{}",
+                command_auth::sign(
+                    &cfg.command_auth.secret,
+                    &topic,
+                    &sign.command.join(" "),
+                    chrono::Utc::now().timestamp_millis(),
+                )
+            );
+            return Ok(());
+        }
+        Some(Command::Sub(sub)) => {
+            let log_level = opts.log_level.unwrap_or_else(|| cfg.log_level.clone());
+            // A daemon's `run_mode = "daemon"` doesn't apply to `sub`
+            let mode = opts
+                .mode
+                .or(configured_mode(&cfg).filter(|&m| m != Mode::Daemon));
+            return subscribe(cfg, sub, mode, &log_level).await;
+        }
+        Some(Command::Replay(rp)) => {
+            let log_level = opts.log_level.unwrap_or_else(|| cfg.log_level.clone());
+            return replay_sessions(cfg, rp, &log_level).await;
+        }
+        None => {}
+    }
+
+    cfg.sampling.max_samples = match (opts.once, opts.samples) {
+        (true, Some(_)) => anyhow::bail!("--once and --samples can't be combined"),
+        (_, Some(0)) => anyhow::bail!("--samples must be at least 1"),
+        (true, None) => Some(1),
+        (false, n) => n,
+    };
+    let run_for = match opts.duration {
+        Some(secs) if !(secs > 0.0 && secs.is_finite()) => {
+            anyhow::bail!("--duration must be a positive number of seconds")
+        }
+        secs => secs.map(std::time::Duration::from_secs_f64),
+    };
 
-    let is_tty = atty::is(atty::Stream::Stdout);
+    // --mode, then service.run_mode, then whether stdout is a terminal
+    let (requested, chosen_by) = match (opts.mode, configured_mode(&cfg)) {
+        (Some(mode), _) => (Some(mode), "--mode"),
+        (None, Some(mode)) => (Some(mode), "service.run_mode"),
+        (None, None) => (None, "terminal detection"),
+    };
+    let (mode, no_terminal) = match requested {
+        Some(Mode::Tui) if !has_terminal() => (Mode::Daemon, true),
+        Some(mode) => (mode, false),
+        None if atty::is(atty::Stream::Stdout) => {
+            let mode = if cfg!(feature = "tui") {
+                Mode::Tui
+            } else {
+                Mode::Plain
+            };
+            (mode, false)
+        }
+        None => (Mode::Daemon, false),
+    };
 
     // Log buffer shared with TUI writer
     let log_buf: Arc<std::sync::Mutex<VecDeque<String>>> =
@@ -59,7 +337,7 @@ async fn main() -> Result<()> {
         .unwrap_or(cfg.log_level.as_str())
         .to_string();
 
-    if is_tty {
+    if mode != Mode::Daemon {
         let tui_writer = tui::TuiWriter::new(Arc::clone(&log_buf));
         let filter = EnvFilter::try_new(&log_level).unwrap_or_else(|_| EnvFilter::new("info"));
         tracing_subscriber::fmt()
@@ -68,64 +346,231 @@ async fn main() -> Result<()> {
             .without_time()
             .init();
     } else {
+        use tracing_subscriber::Layer as _;
+        use tracing_subscriber::layer::SubscriberExt as _;
+        use tracing_subscriber::util::SubscriberInitExt as _;
+
         let filter = EnvFilter::try_new(&log_level).unwrap_or_else(|_| EnvFilter::new("info"));
-        if cfg.log_json {
-            tracing_subscriber::fmt()
-                .with_env_filter(filter)
-                .json()
-                .init();
+        let stdout_layer = if cfg.log_json {
+            tracing_subscriber::fmt::layer().json().boxed()
         } else {
-            tracing_subscriber::fmt().with_env_filter(filter).init();
-        }
+            tracing_subscriber::fmt::layer().boxed()
+        };
+        // Also keep recent lines for the log panel of `attach` clients
+        let attach_layer = tracing_subscriber::fmt::layer()
+            .with_writer(tui::TuiWriter::new(Arc::clone(&log_buf)))
+            .with_ansi(false)
+            .without_time();
+        tracing_subscriber::registry()
+            .with(filter)
+            .with(stdout_layer)
+            .with(attach_layer)
+            .init();
     }
 
     tracing::info!("Starting sensors-to-mqtt v{}", env!("CARGO_PKG_VERSION"));
+    if no_terminal {
+        tracing::warn!(
+            "No terminal for the TUI ({}); running in daemon mode instead",
+            chosen_by
+        );
+    } else {
+        tracing::info!("{:?} mode, chosen by {}", mode, chosen_by);
+    }
+    transport::check_bus_speeds(&cfg.i2c_buses);
+    match load_raw_configuration(opts.config.as_deref()) {
+        Ok(settings) => diagnostics::record_config(settings),
+        Err(e) => tracing::warn!("Diagnostic bundles won't include the settings: {:#}", e),
+    }
 
     let cancel = CancellationToken::new();
 
     // MQTT
-    let mqtt_handle = if cfg.mqtt.enabled && !opts.no_mqtt {
-        Some(mqtt_handler::spawn_mqtt_task(&cfg.mqtt))
-    } else {
-        None
+    let mqtt_enabled = cfg.mqtt.enabled && !opts.no_mqtt;
+    #[cfg(feature = "mqtt")]
+    let mqtt_handle = mqtt_enabled.then(|| mqtt_handler::spawn_mqtt_task(&cfg.mqtt));
+    #[cfg(not(feature = "mqtt"))]
+    let (mqtt_enabled, mqtt_handle) = {
+        if mqtt_enabled {
+            tracing::warn!("Built without the `mqtt` feature — not publishing");
+        }
+        (false, None::<mqtt_handler::MqttHandle>)
     };
 
-    let mqtt_address = if cfg.mqtt.enabled && !opts.no_mqtt {
+    let mqtt_address = if mqtt_enabled {
         cfg.mqtt.address()
     } else {
         "disabled".to_string()
     };
 
     // Shared application state
-    let state: SharedState = Arc::new(RwLock::new(AppState::new(
-        mqtt_address,
-        cfg.mqtt.enabled && !opts.no_mqtt,
-        1000,
-    )));
+    let state: SharedState = Arc::new(RwLock::new(AppState::new(mqtt_address, mqtt_enabled, 1000)));
 
     // Register all configured sensors in the state map
+    let mut extremes_file = None;
     {
         let mut s = state.write().await;
-        register_sensors(&mut s, &cfg.sensors);
+        register_sensors(&mut s, &cfg.sensors, cfg.ui.history_max_mb);
+        groups::register_groups(&mut s.groups, &cfg.groups);
+        fusion::register(&mut s, &cfg.fused_sensors);
+        if cfg.extremes.enabled {
+            let (tracker, path) = extremes::load(&cfg.extremes);
+            s.extremes = Some(tracker);
+            extremes_file = path;
+        }
         if let Some(ref h) = mqtt_handle {
             s.messages_published = Arc::clone(&h.counter);
+            s.messages_dropped = Arc::clone(&h.dropped);
             // Share the exact same Arc so TUI always reflects live MQTT state
             s.mqtt_status = Arc::clone(&h.status);
         }
     }
 
+    // Check the system clock is synchronised before trusting timestamps
+    clock::spawn_sync_check(&cfg.clock, Arc::clone(&state), cancel.clone());
+
+    // Which MQTT commands are followed, and whether they must be signed
+    let command_auth = Arc::new(CommandAuth::new(&cfg.command_auth));
+
+    if !cfg.groups.is_empty()
+        && let Some(ref h) = mqtt_handle
+    {
+        groups::spawn_group_control(
+            Arc::clone(&state),
+            h.clone(),
+            &cfg.mqtt.base_topic,
+            Arc::clone(&command_auth),
+            cancel.clone(),
+        );
+    }
+
+    if let Some(ref h) = mqtt_handle {
+        publishing::spawn_publish_control(
+            Arc::clone(&state),
+            h.clone(),
+            &cfg.mqtt.base_topic,
+            Arc::clone(&command_auth),
+            cancel.clone(),
+        );
+    }
+
+    if cfg.extremes.enabled {
+        extremes::spawn_extremes_store(
+            Arc::clone(&state),
+            mqtt_handle.clone(),
+            &cfg.mqtt.base_topic,
+            extremes_file.clone(),
+            std::time::Duration::from_secs(cfg.extremes.save_interval_secs.max(1)),
+            Arc::clone(&command_auth),
+            cancel.clone(),
+        );
+    }
+
+    if cfg.dashboard.enabled
+        && let Some(ref h) = mqtt_handle
+    {
+        dashboard::spawn_dashboard(
+            &cfg.dashboard,
+            Arc::clone(&state),
+            h.clone(),
+            &cfg.mqtt.base_topic,
+            cancel.clone(),
+        );
+    }
+
+    if cfg.remote_config.enabled
+        && let Some(ref h) = mqtt_handle
+    {
+        remote_config::spawn_config_control(
+            Arc::clone(&state),
+            h.clone(),
+            &cfg.mqtt.base_topic,
+            Arc::clone(&command_auth),
+            cfg.remote_config.clone(),
+            cfg.sensors.clone(),
+            cancel.clone(),
+        );
+    }
+
     // Spawn a task per enabled sensor
+    let read_order = ReadOrder::new(cfg.sampling.read_strategy);
+    let mut sensor_tasks = Vec::new();
     for sensor_cfg in &cfg.sensors {
         if !sensor_cfg.enabled {
             tracing::info!("Sensor '{}' is disabled, skipping", sensor_cfg.name);
+            if let Some(ref h) = mqtt_handle {
+                publish_disabled(h, &cfg.mqtt.base_topic, sensor_cfg).await;
+            }
             continue;
         }
-        spawn_sensor_task(
+        sensor_tasks.push(spawn_sensor_task(
             sensor_cfg.clone(),
             Arc::clone(&state),
             mqtt_handle.clone(),
             cancel.clone(),
             cfg.mqtt.base_topic.clone(),
+            cfg.sampling.clone(),
+            SensorProcessing {
+                read_turn: read_order.turn_for(sensor_cfg),
+                ..SensorProcessing::for_sensor(&cfg, &sensor_cfg.name)
+            },
+        ));
+    }
+    for fused in &cfg.fused_sensors {
+        let interval = match fused.interval_ms {
+            0 => cfg.sampling.interval_ms,
+            ms => ms,
+        };
+        fusion::spawn_fused_sensor(
+            fused.clone(),
+            Arc::clone(&state),
+            mqtt_handle.clone(),
+            cfg.mqtt.base_topic.clone(),
+            std::time::Duration::from_millis(interval.max(1)),
+            cancel.clone(),
+        );
+    }
+
+    // Bounded runs end on their own once every sensor has made its reads
+    // or the time is up
+    let sensor_tasks = match cfg.sampling.max_samples {
+        Some(n) => {
+            let all = futures_util::future::join_all(sensor_tasks);
+            let mqtt = mqtt_handle.clone();
+            let c = cancel.clone();
+            vec![tokio::spawn(async move {
+                all.await;
+                tracing::info!("Every sensor made its {} reads, exiting", n);
+                // Give a broker that is still connecting the chance to
+                // receive them
+                if let Some(h) = mqtt {
+                    wait_for_broker(&h, BOUNDED_CONNECT_WAIT).await;
+                }
+                c.cancel();
+            })]
+        }
+        None => sensor_tasks,
+    };
+    if let Some(d) = run_for {
+        let c = cancel.clone();
+        tokio::spawn(async move {
+            tokio::select! {
+                _ = c.cancelled() => {}
+                _ = tokio::time::sleep(d) => {
+                    tracing::info!("Ran for {:?}, exiting", d);
+                    c.cancel();
+                }
+            }
+        });
+    }
+
+    if cfg.self_check.enabled {
+        self_check::spawn_self_check(
+            &cfg,
+            Arc::clone(&state),
+            mqtt_handle.clone(),
+            mode == Mode::Daemon && cfg.self_check.exit_on_failure,
+            cancel.clone(),
         );
     }
 
@@ -133,28 +578,337 @@ async fn main() -> Result<()> {
     {
         let c = cancel.clone();
         tokio::spawn(async move {
-            let mut signals = signal_hook_tokio::Signals::new(&[
-                signal_hook::consts::SIGTERM,
-                signal_hook::consts::SIGINT,
-            ])
-            .expect("signal handler");
-            use futures_util::StreamExt;
-            if signals.next().await.is_some() {
-                tracing::info!("Shutdown signal received");
-                c.cancel();
-            }
+            shutdown_signal().await;
+            tracing::info!("Shutdown signal received");
+            c.cancel();
         });
     }
 
-    // Run TUI or wait for cancel
-    if is_tty {
-        tui::run_tui(Arc::clone(&state), Arc::clone(&log_buf), cancel.clone()).await?;
-        cancel.cancel();
-    } else {
-        tracing::info!("Running in daemon mode (no TUI)");
-        cancel.cancelled().await;
+    // Run TUI / plain mode or wait for cancel
+    match mode {
+        Mode::Tui => {
+            tui::run_tui(
+                Arc::clone(&state),
+                Arc::clone(&log_buf),
+                cancel.clone(),
+                cfg.ui.clone(),
+                cfg.tui_refresh(),
+            )
+            .await?;
+            cancel.cancel();
+        }
+        Mode::Plain => {
+            tui::plain::run_plain(
+                Arc::clone(&state),
+                Arc::clone(&log_buf),
+                cancel.clone(),
+                std::time::Duration::from_secs(cfg.ui.plain_refresh_secs.max(1)),
+            )
+            .await?;
+            cancel.cancel();
+        }
+        Mode::Daemon => {
+            if !cfg.ui.attach_socket.is_empty() {
+                let serve = tui::attach::serve(
+                    cfg.ui.attach_socket.clone().into(),
+                    Arc::clone(&state),
+                    Arc::clone(&log_buf),
+                    cfg.ui.clone(),
+                    cfg.tui_refresh(),
+                    cancel.clone(),
+                );
+                tokio::spawn(async move {
+                    if let Err(e) = serve.await {
+                        tracing::warn!("Attach socket disabled: {:#}", e);
+                    }
+                });
+            }
+            cancel.cancelled().await;
+        }
     }
 
     tracing::info!("Shutting down");
+    // Let sensor tasks mark themselves offline, then give the broker a
+    // moment to receive it.
+    let stop = futures_util::future::join_all(sensor_tasks);
+    let _ = tokio::time::timeout(SHUTDOWN_GRACE, stop).await;
+    if let Some(rec) = state.write().await.recording.take() {
+        match rec.finish() {
+            Ok((path, n)) => tracing::info!("Recorded {} readings to {}", n, path.display()),
+            Err(e) => tracing::error!("Recording failed: {:#}", e),
+        }
+    }
+    if let Some(path) = &extremes_file {
+        extremes::save(&state, path).await;
+    }
+    if let Some(ref h) = mqtt_handle {
+        h.flush(SHUTDOWN_GRACE).await;
+    }
+    if let Some(e) = state.read().await.fatal_error.clone() {
+        anyhow::bail!(e);
+    }
+    Ok(())
+}
+
+/// Wait until `h` is connected, up to `timeout`.
+async fn wait_for_broker(h: &mqtt_handler::MqttHandle, timeout: std::time::Duration) {
+    let connected = async {
+        while !h.is_connected().await {
+            tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+        }
+    };
+    let _ = tokio::time::timeout(timeout, connected).await;
+}
+
+/// Resolves on SIGTERM or SIGINT.
+#[cfg(unix)]
+async fn shutdown_signal() {
+    use futures_util::StreamExt;
+    let mut signals = signal_hook_tokio::Signals::new(&[
+        signal_hook::consts::SIGTERM,
+        signal_hook::consts::SIGINT,
+    ])
+    .expect("signal handler");
+    signals.next().await;
+}
+
+/// Resolves on Ctrl+C.
+#[cfg(not(unix))]
+async fn shutdown_signal() {
+    tokio::signal::ctrl_c().await.expect("signal handler");
+}
+
+// ---------------------------------------------------------------------------
+// sub
+// ---------------------------------------------------------------------------
+
+/// Rebuild the UI from the daemon's MQTT topics instead of from sensors,
+/// to check what actually reaches the broker.
+async fn subscribe(
+    mut cfg: AppConfig,
+    opts: SubOpts,
+    mut mode: Option<Mode>,
+    log_level: &str,
+) -> Result<()> {
+    if !cfg!(feature = "mqtt") {
+        anyhow::bail!("built without the `mqtt` feature, so `sub` can't subscribe");
+    }
+    if let Some(host) = opts.host {
+        cfg.mqtt.host = host;
+    }
+    if let Some(port) = opts.port {
+        cfg.mqtt.port = port;
+    }
+    if let Some(base) = opts.base_topic {
+        cfg.mqtt.base_topic = base;
+    }
+
+    let log_buf: Arc<std::sync::Mutex<VecDeque<String>>> =
+        Arc::new(std::sync::Mutex::new(VecDeque::with_capacity(1000)));
+    let filter = EnvFilter::try_new(log_level).unwrap_or_else(|_| EnvFilter::new("info"));
+    tracing_subscriber::fmt()
+        .with_env_filter(filter)
+        .with_writer(tui::TuiWriter::new(Arc::clone(&log_buf)))
+        .without_time()
+        .init();
+
+    let state: SharedState = Arc::new(RwLock::new(AppState::new(cfg.mqtt.address(), true, 1000)));
+    let cancel = CancellationToken::new();
+    let feed = tokio::spawn({
+        let (mqtt, state, cancel) = (cfg.mqtt.clone(), Arc::clone(&state), cancel.clone());
+        async move { subscriber::run(&mqtt, state, cancel).await }
+    });
+    {
+        let c = cancel.clone();
+        tokio::spawn(async move {
+            shutdown_signal().await;
+            c.cancel();
+        });
+    }
+
+    if mode.is_none_or(|m| m == Mode::Tui) && !has_terminal() {
+        tracing::warn!("No terminal for the TUI; printing plain summaries instead");
+        mode = Some(Mode::Plain);
+    }
+    let ui = match mode {
+        Some(Mode::Plain) => {
+            tui::plain::run_plain(
+                Arc::clone(&state),
+                Arc::clone(&log_buf),
+                cancel.clone(),
+                std::time::Duration::from_secs(cfg.ui.plain_refresh_secs.max(1)),
+            )
+            .await
+        }
+        Some(Mode::Daemon) => Err(anyhow::anyhow!("`sub` needs --mode tui or plain")),
+        Some(Mode::Tui) | None => {
+            tui::run_tui(
+                Arc::clone(&state),
+                Arc::clone(&log_buf),
+                cancel.clone(),
+                cfg.ui.clone(),
+                cfg.tui_refresh(),
+            )
+            .await
+        }
+    };
+    cancel.cancel();
+    ui?;
+    feed.await?
+}
+
+// ---------------------------------------------------------------------------
+// replay
+// ---------------------------------------------------------------------------
+
+/// Publish recorded sessions as the daemon would have, for working on
+/// dashboards and alerts without the car.
+async fn replay_sessions(mut cfg: AppConfig, opts: ReplayOpts, log_level: &str) -> Result<()> {
+    if !cfg!(feature = "mqtt") {
+        anyhow::bail!("built without the `mqtt` feature, so `replay` can't publish");
+    }
+    if opts.files.is_empty() {
+        anyhow::bail!("no session files given");
+    }
+    let speed = opts.speed.unwrap_or(1.0);
+    if !(speed.is_finite() && speed > 0.0) {
+        anyhow::bail!("--speed must be above 0, got {}", speed);
+    }
+    if let Some(host) = opts.host {
+        cfg.mqtt.host = host;
+    }
+    if let Some(port) = opts.port {
+        cfg.mqtt.port = port;
+    }
+    if let Some(base) = opts.base_topic {
+        cfg.mqtt.base_topic = base;
+    }
+    // Alongside a running daemon: its client id would disconnect it
+    cfg.mqtt.client_id = format!("{}-replay-{}", cfg.mqtt.client_id, std::process::id());
+    cfg.mqtt.bridge.enabled = false;
+
+    let filter = EnvFilter::try_new(log_level).unwrap_or_else(|_| EnvFilter::new("info"));
+    tracing_subscriber::fmt().with_env_filter(filter).init();
+
+    #[cfg(feature = "mqtt")]
+    let mqtt = mqtt_handler::spawn_mqtt_task(&cfg.mqtt);
+    #[cfg(not(feature = "mqtt"))]
+    let mqtt = mqtt_handler::MqttHandle::offline(&cfg.mqtt);
+    let cancel = CancellationToken::new();
+    {
+        let c = cancel.clone();
+        tokio::spawn(async move {
+            shutdown_signal().await;
+            c.cancel();
+        });
+    }
+
+    let files: Vec<std::path::PathBuf> = opts.files.iter().map(Into::into).collect();
+    tracing::info!(
+        "Replaying {} file(s) to {} at {}x",
+        files.len(),
+        cfg.mqtt.address(),
+        speed
+    );
+    let options = replay::ReplayOptions {
+        speed,
+        original_timestamps: opts.original_timestamps,
+        repeat: opts.repeat,
+    };
+    let result = replay::replay(&files, &cfg, &mqtt, &options, &cancel).await;
+    mqtt.flush(SHUTDOWN_GRACE).await;
+    let published = result?;
+    println!("Published {} readings", published);
+    Ok(())
+}
+
+// ---------------------------------------------------------------------------
+// self-test
+// ---------------------------------------------------------------------------
+
+/// Open each sensor, run its self-test and print the result.  Talks to the
+/// hardware directly, so stop a running daemon first.
+fn self_test(cfg: &AppConfig, only: Option<&str>) -> Result<()> {
+    let sensors: Vec<_> = cfg
+        .sensors
+        .iter()
+        .filter(|s| only.map_or(s.enabled, |name| s.name == name))
+        .collect();
+    if sensors.is_empty() {
+        anyhow::bail!("no sensors to test");
+    }
+
+    let mut failed = 0;
+    for sensor_cfg in sensors {
+        match create_sensor(sensor_cfg).and_then(|mut s| s.self_test()) {
+            Ok(Some(report)) => {
+                let verdict = if report.passed() { "PASS" } else { "FAIL" };
+                println!("{} ({}): {}", sensor_cfg.name, sensor_cfg.driver, verdict);
+                for check in &report.checks {
+                    println!("  {}", check);
+                }
+                if !report.passed() {
+                    failed += 1;
+                }
+            }
+            Ok(None) => println!(
+                "{} ({}): no self-test available",
+                sensor_cfg.name, sensor_cfg.driver
+            ),
+            Err(e) => {
+                println!(
+                    "{} ({}): error: {:#}",
+                    sensor_cfg.name, sensor_cfg.driver, e
+                );
+                failed += 1;
+            }
+        }
+    }
+    if failed > 0 {
+        anyhow::bail!("{} sensor(s) failed self-test", failed);
+    }
+    Ok(())
+}
+
+// ---------------------------------------------------------------------------
+// schema
+// ---------------------------------------------------------------------------
+
+/// Print `{sensor: {INFO, FILTERED, DERIVED}}` JSON Schemas.  The channels
+/// depend on each driver's settings, so the drivers are opened as for
+/// `self-test`.
+fn json_schema(cfg: &AppConfig, only: Option<&str>) -> Result<()> {
+    let sensors: Vec<_> = cfg
+        .sensors
+        .iter()
+        .filter(|s| only.map_or(s.enabled, |name| s.name == name))
+        .collect();
+    if sensors.is_empty() {
+        anyhow::bail!("no sensors to describe");
+    }
+
+    let mut out = serde_json::Map::new();
+    for sensor_cfg in sensors {
+        let sensor = create_sensor(sensor_cfg)
+            .map_err(|e| anyhow::anyhow!("{} ({}): {:#}", sensor_cfg.name, sensor_cfg.driver, e))?;
+        let processing = SensorProcessing::for_sensor(cfg, &sensor_cfg.name);
+        let mut fields = driver_fields(sensor.as_ref(), &processing.transforms);
+        fields.extend(processing.extra_fields());
+        out.insert(
+            sensor_cfg.name.clone(),
+            payload_json_schemas(
+                &sensor_cfg.name,
+                &sensor_cfg.tags,
+                &fields,
+                &PayloadKeys::new(
+                    &processing.derived_keys(),
+                    &cfg.mqtt.filtered_channels,
+                    &cfg.mqtt.derived_channels,
+                )
+                .with_channel_map(&sensor_cfg.channel_map),
+            ),
+        );
+    }
+    println!("{}", serde_json::to_string_pretty(&out)?);
     Ok(())
 }
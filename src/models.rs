@@ -1,21 +1,35 @@
 //! Shared application state models.
 
-use crate::sensors::SensorData;
+use crate::alerts::{Alert, AlertChange};
+use crate::extremes::Extremes;
+use crate::laps::LapStatus;
+use crate::recorder::SessionRecorder;
+use crate::sensors::{CalibrationProgress, FieldMeta, SensorData};
+use chrono::{DateTime, Local, Utc};
+use indexmap::IndexMap;
+use serde::{Deserialize, Serialize};
 use std::collections::{HashMap, VecDeque};
 use std::sync::Arc;
 use std::sync::atomic::AtomicU64;
-use tokio::sync::RwLock;
+use std::time::{Duration, Instant};
+use tokio::sync::{RwLock, mpsc};
 
 // ---------------------------------------------------------------------------
 // MQTT status
 // ---------------------------------------------------------------------------
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub enum MqttStatus {
     Disabled,
     Connecting,
     Connected,
     Disconnected,
+    /// Lost the broker and waiting `retry_secs` before attempt `attempt`
+    Reconnecting {
+        attempt: u32,
+        retry_secs: u64,
+        error: String,
+    },
     Error(String),
 }
 
@@ -30,19 +44,96 @@ impl MqttStatus {
             MqttStatus::Connecting => "CONNECTING",
             MqttStatus::Connected => "CONNECTED",
             MqttStatus::Disconnected => "DISCONNECTED",
+            MqttStatus::Reconnecting { .. } => "RECONNECTING",
             MqttStatus::Error(_) => "ERROR",
         }
     }
+
+    /// What went wrong and, while reconnecting, when the next attempt is.
+    pub fn detail(&self) -> Option<String> {
+        match self {
+            MqttStatus::Reconnecting {
+                attempt,
+                retry_secs,
+                error,
+            } => Some(format!("attempt {} in {}s: {}", attempt, retry_secs, error)),
+            MqttStatus::Error(e) => Some(e.clone()),
+            _ => None,
+        }
+    }
+}
+
+/// Where sensor readings go, switchable at runtime (see
+/// [`crate::publishing`]).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PublishMode {
+    /// Published to the broker
+    #[default]
+    Mqtt,
+    /// Written to the log at debug level instead
+    Log,
+    /// Dropped
+    Off,
+}
+
+impl PublishMode {
+    pub fn label(self) -> &'static str {
+        match self {
+            PublishMode::Mqtt => "mqtt",
+            PublishMode::Log => "log",
+            PublishMode::Off => "off",
+        }
+    }
+
+    /// The mode after this one, for cycling through them from the TUI.
+    pub fn next(self) -> Self {
+        match self {
+            PublishMode::Mqtt => PublishMode::Log,
+            PublishMode::Log => PublishMode::Off,
+            PublishMode::Off => PublishMode::Mqtt,
+        }
+    }
+
+    /// `mqtt`, `log` or `off`, any case.
+    pub fn parse(payload: &[u8]) -> Option<Self> {
+        let text = std::str::from_utf8(payload).ok()?.trim();
+        match text.to_ascii_lowercase().as_str() {
+            "mqtt" => Some(PublishMode::Mqtt),
+            "log" => Some(PublishMode::Log),
+            "off" => Some(PublishMode::Off),
+            _ => None,
+        }
+    }
 }
 
 // ---------------------------------------------------------------------------
 // Per-sensor rolling history (for sparklines / charts)
 // ---------------------------------------------------------------------------
 
-#[derive(Debug, Clone)]
+/// Bytes one sample of one channel takes in a [`SensorHistory`], and the
+/// timestamp every sample has; used to hold a history to a memory budget.
+const CHANNEL_BYTES: usize = std::mem::size_of::<f64>();
+const TIMESTAMP_BYTES: usize = std::mem::size_of::<DateTime<Utc>>();
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SensorHistory {
+    /// Most samples kept
     pub capacity: usize,
+    /// Samples further than this many seconds before the newest are
+    /// dropped, however many there are; 0 keeps `capacity` samples
+    #[serde(default)]
+    pub window_secs: f64,
+    /// Approximate memory the samples may use; 0 is unlimited
+    #[serde(default)]
+    pub max_bytes: usize,
     pub fields: HashMap<String, VecDeque<f64>>,
+    /// Sample timestamps, newest at the back (same capacity as `fields`)
+    pub timestamps: VecDeque<DateTime<Utc>>,
+    /// Whether each sample was buffered while paused rather than published
+    /// (`[ignition] buffer_while_paused`); lines up with `timestamps`
+    #[serde(default)]
+    pub buffered: VecDeque<bool>,
     pub max_g_magnitude: f64,
 }
 
@@ -50,22 +141,53 @@ impl SensorHistory {
     pub fn new(capacity: usize) -> Self {
         Self {
             capacity,
+            window_secs: 0.0,
+            max_bytes: 0,
             fields: HashMap::new(),
+            // A time-limited history can have no fixed capacity
+            timestamps: VecDeque::with_capacity(capacity.min(1024)),
+            buffered: VecDeque::new(),
             max_g_magnitude: 0.0,
         }
     }
 
+    /// Keep `secs` of history rather than a number of samples, so the
+    /// length follows the actual sample rate.
+    pub fn with_window(mut self, secs: f64) -> Self {
+        self.window_secs = secs;
+        self
+    }
+
+    /// Drop the oldest samples once they take about `bytes`.
+    pub fn with_max_bytes(mut self, bytes: usize) -> Self {
+        self.max_bytes = bytes;
+        self
+    }
+
     pub fn push(&mut self, data: &SensorData) {
+        self.push_sample(data, false);
+    }
+
+    /// Keep a reading taken while paused, so the chart can set it apart.
+    pub fn push_buffered(&mut self, data: &SensorData) {
+        self.push_sample(data, true);
+    }
+
+    fn push_sample(&mut self, data: &SensorData, buffered: bool) {
+        self.timestamps.push_back(data.timestamp);
+        self.buffered.push_back(buffered);
         for (key, &val) in &data.fields {
-            let buf = self
-                .fields
-                .entry(key.clone())
-                .or_insert_with(|| VecDeque::with_capacity(self.capacity));
-            if buf.len() >= self.capacity {
-                buf.pop_front();
-            }
+            // Look up before `entry` so known channels don't clone their key
+            let buf = match self.fields.get_mut(&**key) {
+                Some(buf) => buf,
+                None => self
+                    .fields
+                    .entry(key.to_string())
+                    .or_insert_with(|| VecDeque::with_capacity(self.capacity.min(1024))),
+            };
             buf.push_back(val);
         }
+        self.trim();
         let gx = data.fields.get("g_force_x").copied().unwrap_or(0.0);
         let gy = data.fields.get("g_force_y").copied().unwrap_or(0.0);
         let gz = data.fields.get("g_force_z").copied().unwrap_or(0.0);
@@ -75,6 +197,40 @@ impl SensorHistory {
         }
     }
 
+    /// Drop samples from the front until the history is within its limits.
+    /// Channels that started later are shorter and only lose samples once
+    /// they reach back as far as the timestamps.
+    fn trim(&mut self) {
+        let Some(&newest) = self.timestamps.back() else {
+            return;
+        };
+        let sample_bytes = TIMESTAMP_BYTES + CHANNEL_BYTES * self.fields.len();
+        while let Some(&oldest) = self.timestamps.front() {
+            let n = self.timestamps.len();
+            let too_old = self.window_secs > 0.0
+                && (newest - oldest).num_milliseconds() as f64 / 1000.0 > self.window_secs;
+            let too_big = self.max_bytes > 0 && n * sample_bytes > self.max_bytes;
+            if n <= 1 || !(n > self.capacity || too_old || too_big) {
+                break;
+            }
+            self.timestamps.pop_front();
+            if self.buffered.len() >= n {
+                self.buffered.pop_front();
+            }
+            for buf in self.fields.values_mut() {
+                if buf.len() >= n {
+                    buf.pop_front();
+                }
+            }
+        }
+    }
+
+    /// Approximate memory the samples take.
+    pub fn bytes(&self) -> usize {
+        self.timestamps.len() * TIMESTAMP_BYTES
+            + self.fields.values().map(VecDeque::len).sum::<usize>() * CHANNEL_BYTES
+    }
+
     pub fn get(&self, field: &str) -> Option<&VecDeque<f64>> {
         self.fields.get(field)
     }
@@ -95,14 +251,167 @@ impl SensorHistory {
 // Per-sensor status
 // ---------------------------------------------------------------------------
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SensorStatus {
     pub name: String,
     pub driver: String,
     pub connection_display: String,
+    /// `[[groups]]` entry the sensor belongs to
+    pub group: Option<String>,
     pub enabled: bool,
     pub connected: bool,
     pub last_error: Option<String>,
+    /// Channels that have stopped changing (`[stuck_values]`)
+    #[serde(default)]
+    pub stuck: Vec<String>,
+    pub calibration: CalibrationState,
+    /// Display metadata from the driver, filled in once it is initialised
+    pub fields: Vec<FieldMeta>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub enum CalibrationState {
+    #[default]
+    Idle,
+    Running(CalibrationProgress),
+    Done(DateTime<Local>),
+    Failed(String),
+}
+
+impl CalibrationState {
+    pub fn is_running(&self) -> bool {
+        matches!(self, CalibrationState::Running(_))
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Commands sent from the UI to a sensor task
+// ---------------------------------------------------------------------------
+
+#[derive(Debug)]
+pub enum SensorCommand {
+    Recalibrate,
+    /// Restart the driver with these settings, keeping the old one if it
+    /// can't start with them; see [`crate::remote_config`].
+    Reconfigure {
+        settings: toml::Value,
+        reply: tokio::sync::oneshot::Sender<Result<(), String>>,
+    },
+}
+
+// ---------------------------------------------------------------------------
+// Status bar messages
+// ---------------------------------------------------------------------------
+
+/// How long the newest status message stays visible before it expires.
+pub const STATUS_TTL: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum StatusLevel {
+    Info,
+    Warn,
+    Error,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StatusMessage {
+    pub level: StatusLevel,
+    pub text: String,
+    /// Wall-clock time for display.
+    pub timestamp: DateTime<Local>,
+    /// Monotonic time for expiry (local to this process).
+    #[serde(skip, default = "Instant::now")]
+    pub posted: Instant,
+}
+
+/// Small ring of recent status messages.
+///
+/// The newest message is shown until it is older than `ttl`.  Scrolling
+/// back (`scroll > 0`) pins an older entry regardless of its age until the
+/// user scrolls forward again.
+#[derive(Debug, Clone)]
+pub struct StatusLog {
+    entries: VecDeque<StatusMessage>,
+    capacity: usize,
+    ttl: Duration,
+    /// 0 = newest; n = n entries back in history
+    scroll: usize,
+}
+
+impl StatusLog {
+    pub fn new(capacity: usize, ttl: Duration) -> Self {
+        Self {
+            entries: VecDeque::with_capacity(capacity),
+            capacity,
+            ttl,
+            scroll: 0,
+        }
+    }
+
+    pub fn push(&mut self, level: StatusLevel, text: impl Into<String>) {
+        if self.entries.len() >= self.capacity {
+            self.entries.pop_front();
+        }
+        self.entries.push_back(StatusMessage {
+            level,
+            text: text.into(),
+            timestamp: Local::now(),
+            posted: Instant::now(),
+        });
+        // New messages jump back to the live view.
+        self.scroll = 0;
+    }
+
+    pub fn info(&mut self, text: impl Into<String>) {
+        self.push(StatusLevel::Info, text);
+    }
+
+    pub fn warn(&mut self, text: impl Into<String>) {
+        self.push(StatusLevel::Warn, text);
+    }
+
+    pub fn error(&mut self, text: impl Into<String>) {
+        self.push(StatusLevel::Error, text);
+    }
+
+    /// The message to display at `now`, if any.
+    pub fn current(&self, now: Instant) -> Option<&StatusMessage> {
+        if self.scroll > 0 {
+            return self.entries.iter().rev().nth(self.scroll);
+        }
+        self.entries
+            .back()
+            .filter(|m| now.saturating_duration_since(m.posted) < self.ttl)
+    }
+
+    pub fn scroll_older(&mut self) {
+        if self.scroll + 1 < self.entries.len() {
+            self.scroll += 1;
+        }
+    }
+
+    pub fn scroll_newer(&mut self) {
+        self.scroll = self.scroll.saturating_sub(1);
+    }
+
+    /// `(position, total)` when browsing history, 1-based from newest.
+    pub fn position(&self) -> Option<(usize, usize)> {
+        (self.scroll > 0).then(|| (self.scroll + 1, self.entries.len()))
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+impl Default for StatusLog {
+    fn default() -> Self {
+        Self::new(20, STATUS_TTL)
+    }
 }
 
 // ---------------------------------------------------------------------------
@@ -115,11 +424,46 @@ pub struct AppState {
     pub sensor_history: HashMap<String, SensorHistory>,
     pub mqtt_status: Arc<std::sync::RwLock<MqttStatus>>,
     pub messages_published: Arc<AtomicU64>,
+    /// Messages dropped because the broker couldn't keep up
+    pub messages_dropped: Arc<AtomicU64>,
     pub mqtt_address: String,
     pub mqtt_enabled: bool,
     pub log_buffer: VecDeque<String>,
     pub log_capacity: usize,
     pub selected_tab: usize,
+    /// Time-series chart visibility for the X / Y / Z traces
+    pub chart_series: [bool; 3],
+    pub status: StatusLog,
+    /// Command channel per running sensor task
+    pub sensor_commands: HashMap<String, mpsc::Sender<SensorCommand>>,
+    /// Active session recording, fed by every sensor task
+    pub recording: Option<SessionRecorder>,
+    /// Lap timer state when `[laps]` is enabled
+    pub laps: Option<LapStatus>,
+    /// All-time extremes when `[extremes]` is enabled, updated with every
+    /// reading stored
+    pub extremes: Option<Extremes>,
+    /// Set while `[ignition]` reports the car off; sensor tasks stop
+    /// sampling and publishing until it is cleared.
+    pub paused: bool,
+    /// Where sensor tasks send their readings; switched from the TUI or
+    /// over MQTT without restarting them
+    pub publish_mode: PublishMode,
+    /// Set while `[adaptive_rate]` sees no motion; sensor tasks sample at
+    /// this interval instead of the full rate.
+    pub idle_interval: Option<std::time::Duration>,
+    /// Last answer from the clock sync check; `None` until it has run or
+    /// when it is off or failing
+    pub clock_synced: Option<bool>,
+    /// `[[groups]]` by name, with whether each is switched on; sensor tasks
+    /// in a switched-off group stop reading until it is switched back on.
+    pub groups: IndexMap<String, bool>,
+    /// Set when a required sensor can't be initialised; the process exits
+    /// with this error.
+    pub fatal_error: Option<String>,
+    /// `[[alerts]]` that have fired and are still outside their limits or
+    /// not yet acknowledged, oldest first
+    pub alerts: Vec<Alert>,
 }
 
 impl AppState {
@@ -134,11 +478,25 @@ impl AppState {
                 MqttStatus::Disabled
             })),
             messages_published: Arc::new(AtomicU64::new(0)),
+            messages_dropped: Arc::new(AtomicU64::new(0)),
             mqtt_address,
             mqtt_enabled,
             log_buffer: VecDeque::with_capacity(log_capacity),
             log_capacity,
             selected_tab: 0,
+            chart_series: [true; 3],
+            status: StatusLog::default(),
+            sensor_commands: HashMap::new(),
+            recording: None,
+            laps: None,
+            extremes: None,
+            paused: false,
+            publish_mode: PublishMode::default(),
+            idle_interval: None,
+            clock_synced: None,
+            groups: IndexMap::new(),
+            fatal_error: None,
+            alerts: Vec::new(),
         }
     }
 
@@ -149,6 +507,58 @@ impl AppState {
         self.log_buffer.push_back(line);
     }
 
+    /// Queue a command for sensor `name`.  Returns false if the sensor has
+    /// no running task or its queue is full.
+    pub fn send_command(&self, name: &str, cmd: SensorCommand) -> bool {
+        self.sensor_commands
+            .get(name)
+            .is_some_and(|tx| tx.try_send(cmd).is_ok())
+    }
+
+    /// Whether sensors in `group` should read; ungrouped sensors always do.
+    pub fn group_enabled(&self, group: Option<&str>) -> bool {
+        group.is_none_or(|g| self.groups.get(g).copied().unwrap_or(true))
+    }
+
+    /// Switch a group on or off.  Returns false if there is no such group.
+    pub fn set_group_enabled(&mut self, group: &str, enabled: bool) -> bool {
+        let Some(on) = self.groups.get_mut(group) else {
+            return false;
+        };
+        if *on != enabled {
+            *on = enabled;
+            let verb = if enabled { "enabled" } else { "disabled" };
+            self.status.info(format!("Group '{}' {}", group, verb));
+        }
+        true
+    }
+
+    /// Record an alert firing or clearing.  A cleared alert stays listed
+    /// until it has been acknowledged.
+    pub fn update_alert(&mut self, change: AlertChange) {
+        match change {
+            AlertChange::Fired(alert) => {
+                self.status.error(format!("Alert: {}", alert.text));
+                self.alerts.push(alert);
+            }
+            AlertChange::Cleared { id } => {
+                if let Some(a) = self.alerts.iter_mut().find(|a| a.id == id) {
+                    a.active = false;
+                    self.status.info(format!("Alert cleared: {}", a.text));
+                }
+                self.alerts.retain(|a| a.active || !a.acknowledged);
+            }
+        }
+    }
+
+    /// Acknowledge every alert; those no longer active are dropped.
+    pub fn acknowledge_alerts(&mut self) {
+        self.alerts.retain(|a| a.active);
+        for a in &mut self.alerts {
+            a.acknowledged = true;
+        }
+    }
+
     pub fn sensor_names(&self) -> Vec<String> {
         let mut names: Vec<String> = self.sensor_statuses.keys().cloned().collect();
         names.sort();
@@ -166,13 +576,9 @@ pub type SharedState = Arc<RwLock<AppState>>;
 mod tests {
     use super::*;
     use crate::sensors::SensorData;
-    use chrono::Utc;
 
     fn make_sensor_data(pairs: &[(&str, f64)]) -> SensorData {
-        SensorData {
-            timestamp: Utc::now(),
-            fields: pairs.iter().map(|(k, v)| (k.to_string(), *v)).collect(),
-        }
+        SensorData::new(pairs.iter().map(|(k, v)| (k.to_string().into(), *v)).collect())
     }
 
     // --- MqttStatus ---
@@ -193,6 +599,17 @@ mod tests {
         assert_eq!(MqttStatus::Connecting.label(), "CONNECTING");
         assert_eq!(MqttStatus::Disconnected.label(), "DISCONNECTED");
         assert_eq!(MqttStatus::Error("x".into()).label(), "ERROR");
+        let reconnecting = MqttStatus::Reconnecting {
+            attempt: 2,
+            retry_secs: 4,
+            error: "refused".into(),
+        };
+        assert_eq!(reconnecting.label(), "RECONNECTING");
+        assert_eq!(
+            reconnecting.detail().as_deref(),
+            Some("attempt 2 in 4s: refused")
+        );
+        assert_eq!(MqttStatus::Connected.detail(), None);
     }
 
     // --- SensorHistory ---
@@ -216,6 +633,58 @@ mod tests {
         assert_eq!(*buf.front().unwrap(), 5.0, "oldest should be 5");
     }
 
+    #[test]
+    fn test_history_marks_buffered_samples() {
+        let mut h = SensorHistory::new(3);
+        h.push(&make_sensor_data(&[("val", 1.0)]));
+        h.push_buffered(&make_sensor_data(&[("val", 2.0)]));
+        h.push_buffered(&make_sensor_data(&[("val", 3.0)]));
+        h.push(&make_sensor_data(&[("val", 4.0)]));
+        assert_eq!(h.buffered, [true, true, false]);
+        assert_eq!(h.get("val").unwrap(), &[2.0, 3.0, 4.0]);
+    }
+
+    #[test]
+    fn test_history_window_and_memory_budget() {
+        let t0 = Utc::now();
+        let at = |ms: i64, v: f64| {
+            let mut d = make_sensor_data(&[("val", v)]);
+            d.timestamp = t0 + chrono::Duration::milliseconds(ms);
+            d
+        };
+
+        // 2 s at 50 Hz, however many samples that is
+        let mut h = SensorHistory::new(usize::MAX).with_window(2.0);
+        for i in 0..500 {
+            h.push(&at(i * 20, i as f64));
+        }
+        assert_eq!(h.timestamps.len(), 101);
+        assert_eq!(h.get("val").unwrap().front(), Some(&399.0));
+
+        // A channel that started late keeps its alignment at the back
+        let mut h = SensorHistory::new(4);
+        h.push(&at(0, 0.0));
+        h.push(&at(10, 1.0));
+        let mut d = at(20, 2.0);
+        d.fields.insert("late".into(), 20.0);
+        h.push(&d);
+        for i in 3..6 {
+            let mut d = at(i * 10, i as f64);
+            d.fields.insert("late".into(), i as f64 * 10.0);
+            h.push(&d);
+        }
+        assert_eq!(h.get("val").unwrap().len(), 4);
+        assert_eq!(h.get("late").unwrap().len(), 4);
+        assert_eq!(h.get("late").unwrap().front(), Some(&20.0));
+
+        let mut h = SensorHistory::new(usize::MAX).with_max_bytes(10_000);
+        for i in 0..10_000 {
+            h.push(&at(i, 0.0));
+        }
+        assert!(h.bytes() <= 10_000, "{} bytes", h.bytes());
+        assert!(h.bytes() > 9_000, "{} bytes", h.bytes());
+    }
+
     #[test]
     fn test_history_stats() {
         let mut h = SensorHistory::new(100);
@@ -276,6 +745,54 @@ mod tests {
         assert_eq!(s.log_buffer.front().unwrap(), "b");
     }
 
+    // --- StatusLog ---
+
+    #[test]
+    fn test_status_shows_newest() {
+        let mut log = StatusLog::new(5, Duration::from_secs(5));
+        log.info("first");
+        log.warn("second");
+        let cur = log.current(Instant::now()).unwrap();
+        assert_eq!(cur.text, "second");
+        assert_eq!(cur.level, StatusLevel::Warn);
+    }
+
+    #[test]
+    fn test_status_expires() {
+        let mut log = StatusLog::new(5, Duration::from_secs(5));
+        log.info("stale");
+        let later = Instant::now() + Duration::from_secs(6);
+        assert!(log.current(later).is_none());
+        assert_eq!(log.len(), 1, "expired messages stay in history");
+    }
+
+    #[test]
+    fn test_status_capacity() {
+        let mut log = StatusLog::new(3, Duration::from_secs(5));
+        for i in 0..5 {
+            log.info(format!("msg{}", i));
+        }
+        assert_eq!(log.len(), 3);
+    }
+
+    #[test]
+    fn test_status_scroll_pins_old_entry() {
+        let mut log = StatusLog::new(5, Duration::from_secs(5));
+        log.info("a");
+        log.info("b");
+        log.info("c");
+        log.scroll_older();
+        log.scroll_older();
+        log.scroll_older(); // clamped at oldest
+        let later = Instant::now() + Duration::from_secs(60);
+        assert_eq!(log.current(later).unwrap().text, "a");
+        assert_eq!(log.position(), Some((3, 3)));
+        log.scroll_newer();
+        assert_eq!(log.current(later).unwrap().text, "b");
+        log.info("d");
+        assert_eq!(log.position(), None, "new message returns to live view");
+    }
+
     #[test]
     fn test_appstate_sensor_names_sorted() {
         let mut s = AppState::new("".into(), false, 10);
@@ -286,13 +803,47 @@ mod tests {
                     name: name.into(),
                     driver: "synthetic".into(),
                     connection_display: "".into(),
+                    group: None,
                     enabled: true,
                     connected: false,
                     last_error: None,
+                    stuck: Vec::new(),
+                    calibration: CalibrationState::Idle,
+                    fields: Vec::new(),
                 },
             );
         }
         let names = s.sensor_names();
         assert_eq!(names, vec!["alpha", "mango", "zebra"]);
     }
+
+    #[test]
+    fn test_alerts_stay_until_acknowledged() {
+        let alert = |id| Alert {
+            id,
+            sensor: "engine".into(),
+            text: format!("alert {}", id),
+            since: Local::now(),
+            active: true,
+            acknowledged: false,
+        };
+        let mut s = AppState::new("".into(), false, 10);
+        s.update_alert(AlertChange::Fired(alert(1)));
+        s.update_alert(AlertChange::Fired(alert(2)));
+        assert_eq!(
+            s.status.current(Instant::now()).unwrap().text,
+            "Alert: alert 2"
+        );
+
+        // Cleared before anyone saw it: still listed, no longer active
+        s.update_alert(AlertChange::Cleared { id: 1 });
+        assert_eq!(s.alerts.len(), 2);
+        assert!(!s.alerts[0].active);
+
+        s.acknowledge_alerts();
+        assert_eq!(s.alerts.len(), 1, "acknowledging drops cleared alerts");
+        assert!(s.alerts[0].acknowledged);
+        s.update_alert(AlertChange::Cleared { id: 2 });
+        assert!(s.alerts.is_empty());
+    }
 }
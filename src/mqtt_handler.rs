@@ -1,17 +1,43 @@
 //! Async MQTT handler using rumqttc.
 //!
 //! Runs the rumqttc event loop in a background Tokio task.
-//! Publishes are done via a bounded mpsc channel so callers never block.
+//! Publishes go through a bounded queue to a separate publish task, so a
+//! slow broker never stalls the sensor loops: when the queue is full the
+//! oldest batch is dropped and counted.  Each queue item is a batch, so a
+//! caller with several topics to send (one sensor reading) hands them over
+//! in a single call.
+//!
+//! With `[mqtt.bridge]` enabled, every queued batch is also offered to a
+//! [`Bridge`], which forwards a rate-limited subset to a second broker
+//! through its own queue and connection, so either broker can be down
+//! without holding up the other.
+//!
+//! [`MqttHandle::subscribe`] delivers messages on a topic filter, for
+//! commands sent to the daemon; subscriptions are renewed on every
+//! reconnect.
+//!
+//! Every connection — the publisher, the bridge and `subscribe` — retries a
+//! lost broker through the same [`Reconnect`] backoff, so the daemon and
+//! the TUI behave alike and show the wait in their MQTT status.
+//!
+//! The broker connection needs the `mqtt` feature; without it only the
+//! queue and [`MqttHandle::offline`] are built.
 
+#[cfg(feature = "mqtt")]
 use rumqttc::{AsyncClient, Event, EventLoop, Incoming, MqttOptions, QoS};
-use std::sync::Arc;
-use std::sync::RwLock;
+use std::collections::{HashMap, VecDeque};
 use std::sync::atomic::{AtomicU64, Ordering};
-use tokio::sync::mpsc;
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, Instant};
+use tokio::sync::{Notify, mpsc};
+#[cfg(feature = "mqtt")]
 use tracing::{error, info, warn};
 
-use crate::config::MqttConfig;
+#[cfg(any(feature = "mqtt", test))]
+use crate::config::MqttBridgeConfig;
+use crate::config::{AggregationConfig, ChannelFilter, MqttConfig};
 use crate::models::MqttStatus;
+use crate::payload::{self, PayloadSerializer};
 
 // ---------------------------------------------------------------------------
 // Publish message
@@ -19,8 +45,122 @@ use crate::models::MqttStatus;
 
 #[derive(Debug)]
 pub struct PublishMsg {
-    pub topic: String,
-    pub payload: String,
+    /// Shared so per-sensor topics are built once and reused
+    pub topic: Arc<str>,
+    /// JSON text, or binary with `payload_format = "protobuf"`
+    pub payload: Vec<u8>,
+    /// Ask the broker to keep this as the topic's last known value
+    pub retain: bool,
+}
+
+impl PublishMsg {
+    pub fn new(topic: impl Into<Arc<str>>, payload: impl Into<Vec<u8>>) -> Self {
+        Self {
+            topic: topic.into(),
+            payload: payload.into(),
+            retain: false,
+        }
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Subscriptions
+// ---------------------------------------------------------------------------
+
+/// Topic filters asked for through [`MqttHandle::subscribe`], each with
+/// where its messages go.
+#[derive(Default)]
+struct Subscriptions {
+    routes: Mutex<Vec<(String, mpsc::Sender<PublishMsg>)>>,
+    #[cfg(feature = "mqtt")]
+    client: std::sync::OnceLock<AsyncClient>,
+}
+
+#[cfg(any(feature = "mqtt", test))]
+impl Subscriptions {
+    fn filters(&self) -> Vec<String> {
+        let routes = self.routes.lock().unwrap();
+        routes.iter().map(|(f, _)| f.clone()).collect()
+    }
+
+    /// Hand a received message to every subscriber whose filter matches.
+    /// A subscriber that has fallen behind misses it.
+    fn deliver(&self, topic: &str, payload: &[u8]) {
+        let mut routes = self.routes.lock().unwrap();
+        routes.retain(|(_, tx)| !tx.is_closed());
+        for (filter, tx) in routes.iter() {
+            if topic_matches(filter, topic) {
+                let _ = tx.try_send(PublishMsg::new(topic, payload));
+            }
+        }
+    }
+}
+
+/// MQTT filter matching: `+` stands for one topic level, a final `#` for
+/// any number.
+#[cfg(any(feature = "mqtt", test))]
+fn topic_matches(filter: &str, topic: &str) -> bool {
+    let mut levels = topic.split('/');
+    for f in filter.split('/') {
+        match (f, levels.next()) {
+            ("#", _) => return true,
+            ("+", Some(_)) => {}
+            (f, Some(t)) if f == t => {}
+            _ => return false,
+        }
+    }
+    levels.next().is_none()
+}
+
+// ---------------------------------------------------------------------------
+// Publish queue
+// ---------------------------------------------------------------------------
+
+/// Batches waiting for the publish task.
+const QUEUE_CAPACITY: usize = 1000;
+
+/// Bounded queue between the sensor tasks and the publish task.  Full means
+/// the broker is falling behind; a fresh reading is worth more than a stale
+/// one, so the oldest batch makes room.
+struct PublishQueue {
+    batches: Mutex<VecDeque<Vec<PublishMsg>>>,
+    capacity: usize,
+    ready: Notify,
+    dropped: Arc<AtomicU64>,
+}
+
+impl PublishQueue {
+    fn new(capacity: usize, dropped: Arc<AtomicU64>) -> Self {
+        Self {
+            batches: Mutex::new(VecDeque::with_capacity(capacity)),
+            capacity,
+            ready: Notify::new(),
+            dropped,
+        }
+    }
+
+    fn push(&self, batch: Vec<PublishMsg>) {
+        {
+            let mut batches = self.batches.lock().unwrap();
+            if batches.len() >= self.capacity
+                && let Some(old) = batches.pop_front()
+            {
+                self.dropped.fetch_add(old.len() as u64, Ordering::Relaxed);
+            }
+            batches.push_back(batch);
+        }
+        self.ready.notify_one();
+    }
+
+    #[cfg(any(feature = "mqtt", test))]
+    async fn pop(&self) -> Vec<PublishMsg> {
+        loop {
+            if let Some(batch) = self.batches.lock().unwrap().pop_front() {
+                return batch;
+            }
+            self.ready.notified().await;
+        }
+    }
 }
 
 // ---------------------------------------------------------------------------
@@ -29,21 +169,132 @@ pub struct PublishMsg {
 
 #[derive(Clone)]
 pub struct MqttHandle {
-    tx: mpsc::Sender<PublishMsg>,
+    queue: Arc<PublishQueue>,
+    /// Messages handed to the broker client
     pub counter: Arc<AtomicU64>,
+    /// Messages dropped because the queue was full
+    pub dropped: Arc<AtomicU64>,
     pub status: Arc<RwLock<MqttStatus>>,
+    /// `mqtt.info_every_reading`
+    pub info_every_reading: bool,
+    /// `mqtt.payload_precision`
+    pub payload_precision: Option<u32>,
+    /// Encodes FILTERED / DERIVED / LATEST; `mqtt.payload_format`, unless
+    /// replaced by an embedding crate
+    pub serializer: Arc<dyn PayloadSerializer>,
+    /// `mqtt.filtered_channels`
+    pub filtered_channels: ChannelFilter,
+    /// `mqtt.derived_channels`
+    pub derived_channels: ChannelFilter,
+    /// `mqtt.snapshot_interval_secs`
+    pub snapshot_interval: Option<Duration>,
+    /// `mqtt.schema_version`; `None` in compatibility mode
+    pub schema_version: Option<u32>,
+    /// `mqtt.aggregation`, when enabled
+    pub aggregation: Option<AggregationConfig>,
+    /// Second broker fed from this handle's publishes
+    bridge: Option<Arc<Bridge>>,
+    subscriptions: Arc<Subscriptions>,
 }
 
 impl MqttHandle {
-    /// Queue a publish.  Returns immediately; drops message if channel is full.
-    pub async fn publish(&self, topic: impl Into<String>, payload: impl Into<String>) {
-        let msg = PublishMsg {
-            topic: topic.into(),
-            payload: payload.into(),
+    fn new(cfg: &MqttConfig, status: MqttStatus) -> Self {
+        let dropped = Arc::new(AtomicU64::new(0));
+        Self {
+            queue: Arc::new(PublishQueue::new(QUEUE_CAPACITY, Arc::clone(&dropped))),
+            counter: Arc::new(AtomicU64::new(0)),
+            dropped,
+            status: Arc::new(RwLock::new(status)),
+            info_every_reading: cfg.info_every_reading,
+            payload_precision: cfg.payload_precision,
+            serializer: payload::serializer(cfg.payload_format),
+            schema_version: (cfg.schema_version > 0).then_some(cfg.schema_version),
+            filtered_channels: cfg.filtered_channels.clone(),
+            derived_channels: cfg.derived_channels.clone(),
+            snapshot_interval: (cfg.snapshot_interval_secs > 0)
+                .then(|| Duration::from_secs(cfg.snapshot_interval_secs)),
+            aggregation: Some(cfg.aggregation.clone()).filter(|a| a.enabled),
+            bridge: None,
+            subscriptions: Arc::default(),
+        }
+    }
+
+    /// A handle with no broker behind it.  Publishes stay queued until
+    /// [`take_queued`](Self::take_queued), so tests can see exactly what
+    /// would have been sent.
+    pub fn offline(cfg: &MqttConfig) -> Self {
+        Self::new(cfg, MqttStatus::Disconnected)
+    }
+
+    /// Remove and return everything queued, oldest first.
+    pub fn take_queued(&self) -> Vec<PublishMsg> {
+        let mut batches = self.queue.batches.lock().unwrap();
+        batches.drain(..).flatten().collect()
+    }
+
+    /// Queue a publish.  Returns immediately; a full queue drops its oldest batch.
+    pub async fn publish(&self, topic: impl Into<Arc<str>>, payload: impl Into<Vec<u8>>) {
+        self.queue(vec![PublishMsg::new(topic, payload)]);
+    }
+
+    /// Queue a retained publish — for messages late subscribers must see,
+    /// such as the per-sensor schema.
+    pub async fn publish_retained(&self, topic: impl Into<Arc<str>>, payload: impl Into<Vec<u8>>) {
+        self.queue(vec![PublishMsg {
+            retain: true,
+            ..PublishMsg::new(topic, payload)
+        }]);
+    }
+
+    /// Queue several publishes in one hand-over.  Batches are dropped
+    /// whole, so subscribers never see half of a reading.
+    pub async fn publish_batch(&self, batch: Vec<PublishMsg>) {
+        self.queue(batch);
+    }
+
+    fn queue(&self, batch: Vec<PublishMsg>) {
+        if let Some(bridge) = &self.bridge {
+            let forwarded = bridge.forward(&batch, Instant::now());
+            if !forwarded.is_empty() {
+                bridge.remote.queue.push(forwarded);
+            }
+        }
+        if !batch.is_empty() {
+            self.queue.push(batch);
+        }
+    }
+
+    /// Wait until everything queued has been handed to the broker client,
+    /// up to `timeout`, then allow a moment for it to be sent.  Best effort:
+    /// used at shutdown so final retained messages aren't lost.
+    pub async fn flush(&self, timeout: std::time::Duration) {
+        let queues =
+            std::iter::once(&self.queue).chain(self.bridge.as_ref().map(|b| &b.remote.queue));
+        let drained = async {
+            for queue in queues {
+                while !queue.batches.lock().unwrap().is_empty() {
+                    tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+                }
+            }
         };
-        if self.tx.try_send(msg).is_ok() {
-            self.counter.fetch_add(1, Ordering::Relaxed);
+        if tokio::time::timeout(timeout, drained).await.is_ok() {
+            tokio::time::sleep(std::time::Duration::from_millis(200)).await;
+        }
+    }
+
+    /// Messages arriving on `filter` (`+` and `#` wildcards allowed), at
+    /// QoS 1.  Subscribing lasts until the receiver is dropped.  An offline
+    /// handle never receives anything.
+    pub fn subscribe(&self, filter: impl Into<String>) -> mpsc::Receiver<PublishMsg> {
+        let filter = filter.into();
+        let (tx, rx) = mpsc::channel(16);
+        #[cfg(feature = "mqtt")]
+        if let Some(client) = self.subscriptions.client.get() {
+            // Before the first connection this fails, and ConnAck subscribes
+            let _ = client.try_subscribe(filter.clone(), QoS::AtLeastOnce);
         }
+        self.subscriptions.routes.lock().unwrap().push((filter, tx));
+        rx
     }
 
     pub async fn is_connected(&self) -> bool {
@@ -51,67 +302,490 @@ impl MqttHandle {
     }
 }
 
+// ---------------------------------------------------------------------------
+// Bridge to a second broker
+// ---------------------------------------------------------------------------
+
+/// Forwards part of what is published to another broker: only the topics
+/// named in `[mqtt.bridge] topics`, only the listed channels of FILTERED /
+/// DERIVED payloads, and at most `max_rate_hz` messages per topic.  Topics
+/// keep their path below the base topic, which is swapped for the bridge's.
+pub struct Bridge {
+    /// Queue, counters and status of the remote connection
+    remote: MqttHandle,
+    local_base: String,
+    remote_base: String,
+    topics: Vec<String>,
+    channels: Vec<String>,
+    min_interval: Option<Duration>,
+    /// When each topic was last forwarded, for the rate limit
+    last_sent: Mutex<HashMap<Arc<str>, Instant>>,
+}
+
+impl Bridge {
+    #[cfg(any(feature = "mqtt", test))]
+    fn new(local_base: &str, cfg: &MqttBridgeConfig, remote: MqttHandle) -> Self {
+        Self {
+            remote,
+            local_base: local_base.to_string(),
+            remote_base: match cfg.base_topic.as_str() {
+                "" => local_base.to_string(),
+                base => base.to_string(),
+            },
+            topics: cfg.topics.clone(),
+            channels: cfg.channels.clone(),
+            min_interval: (cfg.max_rate_hz > 0.0)
+                .then(|| Duration::from_secs_f64(1.0 / cfg.max_rate_hz)),
+            last_sent: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// The messages of `batch` to send on, with their remote topics.
+    fn forward(&self, batch: &[PublishMsg], now: Instant) -> Vec<PublishMsg> {
+        let mut last_sent = self.last_sent.lock().unwrap();
+        batch
+            .iter()
+            .filter_map(|msg| {
+                let rest = msg
+                    .topic
+                    .strip_prefix(self.local_base.as_str())?
+                    .strip_prefix('/')?;
+                let sub = rest.rsplit('/').next().unwrap_or(rest);
+                if !self.topics.is_empty() && !self.topics.iter().any(|t| t == sub) {
+                    return None;
+                }
+                let payload = match sub {
+                    "FILTERED" | "DERIVED" if !self.channels.is_empty() => {
+                        self.select_channels(&msg.payload)?
+                    }
+                    _ => msg.payload.clone(),
+                };
+                if !msg.retain
+                    && let Some(min) = self.min_interval
+                {
+                    if last_sent
+                        .get(&msg.topic)
+                        .is_some_and(|t| now.duration_since(*t) < min)
+                    {
+                        return None;
+                    }
+                    last_sent.insert(Arc::clone(&msg.topic), now);
+                }
+                Some(PublishMsg {
+                    topic: format!("{}/{}", self.remote_base, rest).into(),
+                    payload,
+                    retain: msg.retain,
+                })
+            })
+            .collect()
+    }
+
+    /// `timestamp`, `schema_version` and the configured channels; `None`
+    /// when the payload has none of the channels.  The config only allows
+    /// `channels` with JSON payloads (see [`crate::config::AppConfig::check_bridge`]);
+    /// anything else that isn't a JSON object passes unchanged.
+    fn select_channels(&self, payload: &[u8]) -> Option<Vec<u8>> {
+        let Ok(serde_json::Value::Object(mut map)) = serde_json::from_slice(payload) else {
+            return Some(payload.to_vec());
+        };
+        if !map.keys().any(|k| self.channels.contains(k)) {
+            return None;
+        }
+        map.retain(|k, _| {
+            matches!(k.as_str(), "timestamp" | "schema_version") || self.channels.contains(k)
+        });
+        Some(serde_json::Value::Object(map).to_string().into_bytes())
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Reconnect backoff
+// ---------------------------------------------------------------------------
+
+/// When to retry a lost broker (`auto_reconnect`, `reconnect_min_secs`,
+/// `reconnect_max_secs`): the wait starts at the minimum, doubles after each
+/// failed attempt up to the maximum, and starts over once connected.
+#[derive(Debug, Clone)]
+pub struct Reconnect {
+    enabled: bool,
+    min: Duration,
+    max: Duration,
+    /// Failed attempts since the last connection
+    attempt: u32,
+}
+
+impl Reconnect {
+    pub fn new(cfg: &MqttConfig) -> Self {
+        let min = Duration::from_secs(cfg.reconnect_min_secs.max(1));
+        Self {
+            enabled: cfg.auto_reconnect,
+            min,
+            max: Duration::from_secs(cfg.reconnect_max_secs).max(min),
+            attempt: 0,
+        }
+    }
+
+    /// The broker accepted the connection.
+    pub fn connected(&mut self) {
+        self.attempt = 0;
+    }
+
+    /// Record a failed or dropped connection.  Returns the status to show
+    /// and how long to wait before the next attempt, or no wait when
+    /// `auto_reconnect` is off and the client should give up.
+    pub fn failed(&mut self, error: String) -> (MqttStatus, Option<Duration>) {
+        if !self.enabled {
+            return (MqttStatus::Error(error), None);
+        }
+        self.attempt += 1;
+        let wait = self
+            .min
+            .saturating_mul(1 << (self.attempt - 1).min(16))
+            .min(self.max);
+        let status = MqttStatus::Reconnecting {
+            attempt: self.attempt,
+            retry_secs: wait.as_secs(),
+            error,
+        };
+        (status, Some(wait))
+    }
+}
+
 // ---------------------------------------------------------------------------
 // Start the MQTT event-loop task.  Returns a handle usable from any task.
 // ---------------------------------------------------------------------------
 
+#[cfg(feature = "mqtt")]
 pub fn spawn_mqtt_task(cfg: &MqttConfig) -> MqttHandle {
-    let (tx, rx) = mpsc::channel::<PublishMsg>(1000);
-    let counter = Arc::new(AtomicU64::new(0));
-    let status = Arc::new(RwLock::new(MqttStatus::Connecting));
+    let mut handle = MqttHandle::new(cfg, MqttStatus::Connecting);
+    let credentials = cfg.username.clone().zip(cfg.password.clone());
+    start_client(
+        "MQTT",
+        &cfg.host,
+        cfg.port,
+        &cfg.client_id,
+        credentials,
+        Reconnect::new(cfg),
+        &handle,
+    );
 
-    let handle = MqttHandle {
-        tx,
-        counter: Arc::clone(&counter),
-        status: Arc::clone(&status),
-    };
+    if cfg.bridge.enabled {
+        let b = &cfg.bridge;
+        let remote = MqttHandle::new(cfg, MqttStatus::Connecting);
+        let credentials = b.username.clone().zip(b.password.clone());
+        start_client(
+            "MQTT bridge",
+            &b.host,
+            b.port,
+            &b.client_id,
+            credentials,
+            Reconnect::new(cfg),
+            &remote,
+        );
+        handle.bridge = Some(Arc::new(Bridge::new(&cfg.base_topic, b, remote)));
+    }
+    handle
+}
 
-    let mut opts = MqttOptions::new(cfg.client_id.clone(), cfg.host.clone(), cfg.port);
+/// Connect to one broker and publish whatever `handle` queues.
+#[cfg(feature = "mqtt")]
+fn start_client(
+    label: &'static str,
+    host: &str,
+    port: u16,
+    client_id: &str,
+    credentials: Option<(String, String)>,
+    reconnect: Reconnect,
+    handle: &MqttHandle,
+) {
+    let mut opts = MqttOptions::new(client_id, host, port);
     opts.set_keep_alive(std::time::Duration::from_secs(20));
     opts.set_clean_session(true);
 
-    if let (Some(u), Some(p)) = (cfg.username.clone(), cfg.password.clone()) {
+    if let Some((u, p)) = credentials {
         opts.set_credentials(u, p);
     }
 
     let qos = QoS::AtLeastOnce;
 
     let (client, event_loop) = AsyncClient::new(opts, 100);
+    let _ = handle.subscriptions.client.set(client.clone());
 
-    tokio::spawn(run_event_loop(event_loop, status.clone()));
-    tokio::spawn(run_publish_loop(client, rx, qos));
-
-    handle
+    tokio::spawn(run_event_loop(
+        event_loop,
+        Arc::clone(&handle.status),
+        Arc::clone(&handle.subscriptions),
+        reconnect,
+        label,
+    ));
+    tokio::spawn(run_publish_loop(
+        client,
+        Arc::clone(&handle.queue),
+        Arc::clone(&handle.counter),
+        qos,
+        label,
+    ));
 }
 
-async fn run_event_loop(mut evl: EventLoop, status: Arc<RwLock<MqttStatus>>) {
+#[cfg(feature = "mqtt")]
+async fn run_event_loop(
+    mut evl: EventLoop,
+    status: Arc<RwLock<MqttStatus>>,
+    subscriptions: Arc<Subscriptions>,
+    mut reconnect: Reconnect,
+    label: &str,
+) {
     loop {
         match evl.poll().await {
             Ok(Event::Incoming(Incoming::ConnAck(_))) => {
-                info!("MQTT connected");
+                info!("{} connected", label);
+                reconnect.connected();
                 *status.write().unwrap() = MqttStatus::Connected;
+                // Clean sessions forget subscriptions across reconnects
+                if let Some(client) = subscriptions.client.get() {
+                    for filter in subscriptions.filters() {
+                        if let Err(e) = client.try_subscribe(filter.clone(), QoS::AtLeastOnce) {
+                            warn!("{} subscribe to {} failed: {}", label, filter, e);
+                        }
+                    }
+                }
+            }
+            Ok(Event::Incoming(Incoming::Publish(p))) => {
+                subscriptions.deliver(&p.topic, &p.payload);
             }
             Ok(Event::Incoming(Incoming::Disconnect)) => {
-                warn!("MQTT disconnected");
+                warn!("{} disconnected", label);
                 *status.write().unwrap() = MqttStatus::Disconnected;
             }
             Err(e) => {
-                error!("MQTT error: {}", e);
-                *status.write().unwrap() = MqttStatus::Error(e.to_string());
-                tokio::time::sleep(std::time::Duration::from_secs(3)).await;
+                let (new_status, wait) = reconnect.failed(e.to_string());
+                *status.write().unwrap() = new_status;
+                let Some(wait) = wait else {
+                    error!("{} error: {}; auto_reconnect is off, giving up", label, e);
+                    return;
+                };
+                error!("{} error: {}; reconnecting in {:?}", label, e, wait);
+                tokio::time::sleep(wait).await;
             }
             _ => {}
         }
     }
 }
 
-async fn run_publish_loop(client: AsyncClient, mut rx: mpsc::Receiver<PublishMsg>, qos: QoS) {
-    while let Some(msg) = rx.recv().await {
-        if let Err(e) = client
-            .publish(&msg.topic, qos, false, msg.payload.as_bytes())
-            .await
-        {
-            warn!("MQTT publish error on {}: {}", msg.topic, e);
+#[cfg(feature = "mqtt")]
+async fn run_publish_loop(
+    client: AsyncClient,
+    queue: Arc<PublishQueue>,
+    counter: Arc<AtomicU64>,
+    qos: QoS,
+    label: &str,
+) {
+    loop {
+        for msg in queue.pop().await {
+            match client
+                .publish(&*msg.topic, qos, msg.retain, msg.payload)
+                .await
+            {
+                Ok(()) => {
+                    counter.fetch_add(1, Ordering::Relaxed);
+                }
+                Err(e) => warn!("{} publish error on {}: {}", label, msg.topic, e),
+            }
+        }
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Unit tests
+// ---------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn batch(n: usize) -> Vec<PublishMsg> {
+        (0..n)
+            .map(|i| PublishMsg::new(format!("t/{}", i), "{}"))
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_batches_queued_whole() {
+        let queue = PublishQueue::new(4, Arc::new(AtomicU64::new(0)));
+        queue.push(batch(3));
+        queue.push(batch(1));
+
+        let got = queue.pop().await;
+        assert_eq!(got.len(), 3);
+        assert_eq!(&*got[2].topic, "t/2");
+        assert_eq!(queue.pop().await.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_full_queue_drops_oldest() {
+        let dropped = Arc::new(AtomicU64::new(0));
+        let queue = PublishQueue::new(2, Arc::clone(&dropped));
+        queue.push(batch(3));
+        queue.push(batch(1));
+        queue.push(batch(2));
+
+        assert_eq!(dropped.load(Ordering::Relaxed), 3);
+        assert_eq!(queue.pop().await.len(), 1);
+        assert_eq!(queue.pop().await.len(), 2);
+    }
+
+    fn bridged(cfg: MqttBridgeConfig) -> MqttHandle {
+        let local = MqttConfig::default();
+        let remote = MqttHandle::offline(&local);
+        MqttHandle {
+            bridge: Some(Arc::new(Bridge::new(&local.base_topic, &cfg, remote))),
+            ..MqttHandle::offline(&local)
         }
     }
+
+    fn remote_queued(h: &MqttHandle) -> Vec<PublishMsg> {
+        h.bridge.as_ref().unwrap().remote.take_queued()
+    }
+
+    #[tokio::test]
+    async fn test_bridge_forwards_selected_topics_and_channels() {
+        let h = bridged(MqttBridgeConfig {
+            base_topic: "cars/van-2".into(),
+            topics: vec!["DERIVED".into(), "AVAILABILITY".into()],
+            channels: vec!["g_force_x".into()],
+            ..Default::default()
+        });
+        h.publish_batch(vec![
+            PublishMsg::new(
+                "/SENSORS/IMU/imu/FILTERED",
+                r#"{"timestamp":"t","accel_x":1.0}"#,
+            ),
+            PublishMsg::new(
+                "/SENSORS/IMU/imu/DERIVED",
+                r#"{"timestamp":"t","g_force_x":0.5,"g_force_y":0.1}"#,
+            ),
+            PublishMsg::new("/OTHER/IMU/imu/DERIVED", "{}"),
+        ])
+        .await;
+        h.publish_retained("/SENSORS/IMU/imu/AVAILABILITY", "online")
+            .await;
+
+        assert_eq!(h.take_queued().len(), 4, "local publishing is unchanged");
+        let remote = remote_queued(&h);
+        let sent: Vec<_> = remote
+            .iter()
+            .map(|m| {
+                (
+                    &*m.topic,
+                    std::str::from_utf8(&m.payload).unwrap(),
+                    m.retain,
+                )
+            })
+            .collect();
+        assert_eq!(
+            sent,
+            [
+                (
+                    "cars/van-2/IMU/imu/DERIVED",
+                    r#"{"timestamp":"t","g_force_x":0.5}"#,
+                    false
+                ),
+                ("cars/van-2/IMU/imu/AVAILABILITY", "online", true),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_reconnect_backs_off_and_resets() {
+        let cfg = MqttConfig {
+            reconnect_min_secs: 2,
+            reconnect_max_secs: 10,
+            ..MqttConfig::default()
+        };
+        let mut r = Reconnect::new(&cfg);
+        let waits: Vec<u64> = (0..4)
+            .map(|_| r.failed("refused".into()).1.unwrap().as_secs())
+            .collect();
+        assert_eq!(waits, [2, 4, 8, 10]);
+        let (status, _) = r.failed("refused".into());
+        assert_eq!(
+            status,
+            MqttStatus::Reconnecting {
+                attempt: 5,
+                retry_secs: 10,
+                error: "refused".into()
+            }
+        );
+
+        r.connected();
+        assert_eq!(r.failed("gone".into()).1, Some(Duration::from_secs(2)));
+
+        let mut r = Reconnect::new(&MqttConfig {
+            auto_reconnect: false,
+            ..MqttConfig::default()
+        });
+        assert_eq!(
+            r.failed("gone".into()),
+            (MqttStatus::Error("gone".into()), None)
+        );
+    }
+
+    #[test]
+    fn test_subscriptions_deliver_matching_topics() {
+        let h = MqttHandle::offline(&MqttConfig::default());
+        let mut set = h.subscribe("/S/GROUPS/+/SET");
+        let mut all = h.subscribe("/S/#");
+        h.subscriptions.deliver("/S/GROUPS/chassis/SET", b"off");
+        h.subscriptions.deliver("/S/GROUPS/chassis", b"on");
+
+        let got = set.try_recv().unwrap();
+        assert_eq!(
+            (&*got.topic, &got.payload[..]),
+            ("/S/GROUPS/chassis/SET", &b"off"[..])
+        );
+        assert!(set.try_recv().is_err());
+        assert_eq!(all.try_recv().unwrap().payload, b"off");
+        assert_eq!(all.try_recv().unwrap().payload, b"on");
+
+        drop(set);
+        h.subscriptions.deliver("/S/x", b"");
+        assert_eq!(h.subscriptions.filters(), ["/S/#"]);
+        assert!(!topic_matches("/S/GROUPS/+/SET", "/S/GROUPS/a/b/SET"));
+        assert!(
+            topic_matches("/S/#", "/S"),
+            "# also matches the parent level"
+        );
+    }
+
+    #[test]
+    fn test_bridge_rate_limits_each_topic() {
+        let remote = MqttHandle::offline(&MqttConfig::default());
+        let bridge = Bridge::new(
+            "/S",
+            &MqttBridgeConfig {
+                topics: Vec::new(),
+                max_rate_hz: 2.0,
+                ..Default::default()
+            },
+            remote,
+        );
+        let t0 = Instant::now();
+        let at = |ms| t0 + Duration::from_millis(ms);
+        let msg = |topic: &str| vec![PublishMsg::new(topic, "{}")];
+        assert_eq!(bridge.forward(&msg("/S/IMU/a/DERIVED"), at(0)).len(), 1);
+        assert_eq!(bridge.forward(&msg("/S/IMU/a/DERIVED"), at(100)).len(), 0);
+        // Other topics have their own budget
+        assert_eq!(bridge.forward(&msg("/S/IMU/b/DERIVED"), at(100)).len(), 1);
+        assert_eq!(bridge.forward(&msg("/S/IMU/a/DERIVED"), at(500)).len(), 1);
+        // Retained messages are never dropped
+        let retained = vec![PublishMsg {
+            retain: true,
+            ..PublishMsg::new("/S/IMU/a/DERIVED", "{}")
+        }];
+        assert_eq!(bridge.forward(&retained, at(510)).len(), 1);
+        assert_eq!(
+            &*bridge.forward(&retained, at(510))[0].topic,
+            "/S/IMU/a/DERIVED"
+        );
+    }
 }
@@ -0,0 +1,377 @@
+//! Encodings of `FILTERED`, `DERIVED` and `LATEST` (`mqtt.payload_format`).
+//!
+//! Every format implements [`PayloadSerializer`] over the same [`Reading`],
+//! so the publishers pick channels once and never match on the format.  A
+//! crate embedding the service can encode its own way by setting
+//! [`MqttHandle::serializer`](crate::mqtt_handler::MqttHandle::serializer).
+//!
+//! | Format        | Payload                                                            |
+//! |---------------|--------------------------------------------------------------------|
+//! | `json`        | `{"timestamp":…,"monotonic":…,<key>:<value>,…}`                    |
+//! | `protobuf`    | a `Reading` message, see [`crate::protobuf`]                       |
+//! | `cbor`        | the JSON object's map, in CBOR (RFC 8949)                          |
+//! | `influx_line` | one InfluxDB line: `<sensor>,<tag>=<value>,… <key>=<value>,… <ns>` |
+
+use std::sync::Arc;
+
+use indexmap::IndexMap;
+use serde::ser::{Serialize, SerializeMap, Serializer};
+
+use crate::config::{ChannelFilter, PayloadFormat};
+use crate::protobuf;
+use crate::sensors::{ChannelValue, SensorData};
+use crate::service::round_to;
+
+/// Layout version of the payloads on a sensor's topics, published as
+/// `schema_version` (`mqtt.schema_version`).  Raised whenever a payload
+/// changes in a way an existing consumer could misread; older layouts stay
+/// selectable.
+pub const SCHEMA_VERSION: u32 = 1;
+
+/// One payload's worth of a reading: the `keys` found in `data.fields`,
+/// then the `data.values` that `values` allows.
+pub struct Reading<'a> {
+    /// Only formats that don't go out on the sensor's own topic use it
+    pub sensor: &'a str,
+    /// The sensor's `tags`; only formats with a tag set of their own use it
+    pub tags: &'a IndexMap<String, String>,
+    pub timestamp: &'a str,
+    /// Left out in compatibility mode (`mqtt.schema_version = 0`)
+    pub schema_version: Option<u32>,
+    pub data: &'a SensorData,
+    pub keys: &'a [&'a str],
+    /// Append the non-numeric channels this allows
+    pub values: Option<&'a ChannelFilter>,
+    /// Decimal places floats are rounded to
+    pub precision: Option<u32>,
+}
+
+impl Reading<'_> {
+    fn round(&self, v: f64) -> f64 {
+        round_to(v, self.precision)
+    }
+
+    /// The numeric channels, rounded, in `keys` order.
+    fn fields(&self) -> impl Iterator<Item = (&str, f64)> {
+        self.keys
+            .iter()
+            .filter_map(|&k| Some((k, self.round(*self.data.fields.get(k)?))))
+    }
+
+    /// How many channels [`Reading::values`] yields.
+    fn value_count(&self) -> usize {
+        self.values.map_or(0, |filter| {
+            self.data.values.keys().filter(|k| filter.allows(k)).count()
+        })
+    }
+
+    /// The non-numeric channels `values` allows, floats rounded.
+    fn values(&self) -> impl Iterator<Item = (&str, ChannelValue)> {
+        self.values.into_iter().flat_map(|filter| {
+            self.data
+                .values
+                .iter()
+                .filter(|(k, _)| filter.allows(k))
+                .map(|(k, v)| match v {
+                    ChannelValue::Float(f) => (k.as_str(), ChannelValue::Float(self.round(*f))),
+                    other => (k.as_str(), other.clone()),
+                })
+        })
+    }
+}
+
+/// `{"timestamp": …, <key>: <value>, …}` serialised straight from a
+/// reading, without building an intermediate JSON map per message.
+impl Serialize for Reading<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(None)?;
+        map.serialize_entry("timestamp", self.timestamp)?;
+        if let Some(v) = self.schema_version {
+            map.serialize_entry("schema_version", &v)?;
+        }
+        if let Some(t) = self.data.monotonic {
+            map.serialize_entry("monotonic", &t)?;
+        }
+        if self.data.clock_unsynced {
+            map.serialize_entry("clock_unsynced", &true)?;
+        }
+        for (key, v) in self.fields() {
+            map.serialize_entry(key, &v)?;
+        }
+        for (key, v) in self.values() {
+            map.serialize_entry(key, &v)?;
+        }
+        map.end()
+    }
+}
+
+/// Turns a [`Reading`] into the bytes published for it.
+pub trait PayloadSerializer: Send + Sync {
+    /// Append the payload to `out`.  The publishers pass a buffer they
+    /// keep, so a steady stream of readings encodes without allocating.
+    fn encode_into(&self, reading: &Reading<'_>, out: &mut Vec<u8>);
+
+    fn encode(&self, reading: &Reading<'_>) -> Vec<u8> {
+        let mut out = Vec::new();
+        self.encode_into(reading, &mut out);
+        out
+    }
+}
+
+/// The serializer for a configured `payload_format`.
+pub fn serializer(format: PayloadFormat) -> Arc<dyn PayloadSerializer> {
+    match format {
+        PayloadFormat::Json => Arc::new(Json),
+        PayloadFormat::Protobuf => Arc::new(Protobuf),
+        PayloadFormat::Cbor => Arc::new(Cbor),
+        PayloadFormat::InfluxLine => Arc::new(InfluxLine),
+    }
+}
+
+pub struct Json;
+
+impl PayloadSerializer for Json {
+    fn encode_into(&self, reading: &Reading<'_>, out: &mut Vec<u8>) {
+        serde_json::to_writer(out, reading).expect("string keys and f64 values always serialise")
+    }
+}
+
+/// Full precision, whatever `payload_precision` says: doubles are 8 bytes
+/// on the wire either way.
+pub struct Protobuf;
+
+impl PayloadSerializer for Protobuf {
+    fn encode_into(&self, reading: &Reading<'_>, out: &mut Vec<u8>) {
+        protobuf::encode_reading_into(
+            out,
+            reading.timestamp,
+            reading.schema_version,
+            reading.data,
+            reading.keys,
+            reading.values,
+        )
+    }
+}
+
+pub struct Cbor;
+
+impl PayloadSerializer for Cbor {
+    fn encode_into(&self, r: &Reading<'_>, out: &mut Vec<u8>) {
+        // The map's head carries its length, so count the entries first
+        let entries = 1
+            + u64::from(r.schema_version.is_some())
+            + u64::from(r.data.monotonic.is_some())
+            + u64::from(r.data.clock_unsynced)
+            + r.fields().count() as u64
+            + r.value_count() as u64;
+        cbor_head(out, 5, entries);
+        cbor_text(out, "timestamp");
+        cbor_text(out, r.timestamp);
+        if let Some(v) = r.schema_version {
+            cbor_text(out, "schema_version");
+            cbor_head(out, 0, u64::from(v));
+        }
+        if let Some(t) = r.data.monotonic {
+            cbor_text(out, "monotonic");
+            cbor_float(out, t);
+        }
+        if r.data.clock_unsynced {
+            cbor_text(out, "clock_unsynced");
+            out.push(0xf5);
+        }
+        for (key, v) in r.fields() {
+            cbor_text(out, key);
+            cbor_float(out, v);
+        }
+        for (key, v) in r.values() {
+            cbor_text(out, key);
+            match v {
+                ChannelValue::Bool(b) => out.push(if b { 0xf5 } else { 0xf4 }),
+                ChannelValue::Int(i) if i >= 0 => cbor_head(out, 0, i as u64),
+                ChannelValue::Int(i) => cbor_head(out, 1, !i as u64),
+                ChannelValue::Float(f) => cbor_float(out, f),
+                ChannelValue::Text(t) => cbor_text(out, &t),
+            }
+        }
+    }
+}
+
+/// Major type and argument, in the shortest form.
+fn cbor_head(buf: &mut Vec<u8>, major: u8, n: u64) {
+    let major = major << 5;
+    match n {
+        0..24 => buf.push(major | n as u8),
+        24..0x100 => buf.extend_from_slice(&[major | 24, n as u8]),
+        0x100..0x1_0000 => {
+            buf.push(major | 25);
+            buf.extend_from_slice(&(n as u16).to_be_bytes());
+        }
+        0x1_0000..0x1_0000_0000 => {
+            buf.push(major | 26);
+            buf.extend_from_slice(&(n as u32).to_be_bytes());
+        }
+        _ => {
+            buf.push(major | 27);
+            buf.extend_from_slice(&n.to_be_bytes());
+        }
+    }
+}
+
+fn cbor_text(buf: &mut Vec<u8>, s: &str) {
+    cbor_head(buf, 3, s.len() as u64);
+    buf.extend_from_slice(s.as_bytes());
+}
+
+fn cbor_float(buf: &mut Vec<u8>, v: f64) {
+    buf.push(0xfb);
+    buf.extend_from_slice(&v.to_be_bytes());
+}
+
+/// The sensor is the measurement, its `tags` the tag set, and the reading's
+/// timestamp the point's time.  Line protocol has no empty tag values, nor
+/// NaN or infinity, so those tags and channels are left out; a reading left
+/// with no channels at all publishes an empty payload.
+pub struct InfluxLine;
+
+impl PayloadSerializer for InfluxLine {
+    fn encode_into(&self, r: &Reading<'_>, out: &mut Vec<u8>) {
+        use std::io::Write;
+
+        let start = out.len();
+        let _ = write!(out, "{}", Escaped(r.sensor, &[',', ' ']));
+        for (key, value) in r.tags.iter().filter(|(_, v)| !v.is_empty()) {
+            let escape = &[',', '=', ' '];
+            let _ = write!(out, ",{}={}", Escaped(key, escape), Escaped(value, escape));
+        }
+        let head = out.len();
+        let mut field = |key: &str, value: std::fmt::Arguments| {
+            let sep = if out.len() == head { ' ' } else { ',' };
+            let _ = write!(out, "{}{}={}", sep, Escaped(key, &[',', '=', ' ']), value);
+        };
+        if let Some(v) = r.schema_version {
+            field("schema_version", format_args!("{}i", v));
+        }
+        if let Some(t) = r.data.monotonic {
+            field("monotonic", format_args!("{}", t));
+        }
+        if r.data.clock_unsynced {
+            field("clock_unsynced", format_args!("true"));
+        }
+        for (key, v) in r.fields().filter(|(_, v)| v.is_finite()) {
+            field(key, format_args!("{}", v));
+        }
+        for (key, v) in r.values() {
+            match v {
+                ChannelValue::Bool(b) => field(key, format_args!("{}", b)),
+                ChannelValue::Int(i) => field(key, format_args!("{}i", i)),
+                ChannelValue::Float(f) if f.is_finite() => field(key, format_args!("{}", f)),
+                ChannelValue::Float(_) => {}
+                ChannelValue::Text(t) => {
+                    field(key, format_args!("\"{}\"", Escaped(&t, &['"', '\\'])))
+                }
+            }
+        }
+        if out.len() == head {
+            out.truncate(start);
+            return;
+        }
+        let time = r.data.timestamp.timestamp_nanos_opt().unwrap_or_default();
+        let _ = write!(out, " {}", time);
+    }
+}
+
+/// Displays a string with a backslash before each of the characters given.
+struct Escaped<'a>(&'a str, &'a [char]);
+
+impl std::fmt::Display for Escaped<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        use std::fmt::Write;
+
+        for c in self.0.chars() {
+            if self.1.contains(&c) {
+                f.write_char('\\')?;
+            }
+            f.write_char(c)?;
+        }
+        Ok(())
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Unit tests
+// ---------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn data() -> SensorData {
+        let mut data = SensorData::new(
+            [("g_force_x".into(), 0.5), ("bad".into(), f64::NAN)]
+                .into_iter()
+                .collect(),
+        );
+        data.timestamp = "2025-01-01T00:00:00Z".parse().unwrap();
+        data.set_value("fix", ChannelValue::Text("3d \"rtk\"".into()));
+        data.set_value("sats", ChannelValue::Int(-2));
+        data
+    }
+
+    fn encode(s: &dyn PayloadSerializer, data: &SensorData) -> Vec<u8> {
+        encode_tagged(s, data, &IndexMap::new())
+    }
+
+    fn encode_tagged(
+        s: &dyn PayloadSerializer,
+        data: &SensorData,
+        tags: &IndexMap<String, String>,
+    ) -> Vec<u8> {
+        s.encode(&Reading {
+            sensor: "Front IMU",
+            tags,
+            timestamp: "t",
+            schema_version: None,
+            data,
+            keys: &["g_force_x", "bad"],
+            values: Some(&ChannelFilter::default()),
+            precision: None,
+        })
+    }
+
+    #[test]
+    fn test_cbor_encoding() {
+        let mut data = data();
+        data.fields.shift_remove("bad");
+        data.values.shift_remove("fix");
+        let mut expected = vec![0xa3]; // map of 3
+        expected.extend_from_slice(b"\x69timestamp\x61t");
+        expected.extend_from_slice(b"\x69g_force_x\xfb\x3f\xe0\0\0\0\0\0\0");
+        expected.extend_from_slice(b"\x64sats\x21"); // -2 is major 1, argument 1
+        assert_eq!(encode(&Cbor, &data), expected);
+
+        let mut buf = Vec::new();
+        cbor_head(&mut buf, 3, 300);
+        assert_eq!(buf, [0x79, 0x01, 0x2c]);
+    }
+
+    #[test]
+    fn test_influx_line() {
+        let line = String::from_utf8(encode(&InfluxLine, &data())).unwrap();
+        assert_eq!(
+            line,
+            r#"Front\ IMU g_force_x=0.5,fix="3d \"rtk\"",sats=-2i 1735689600000000000"#
+        );
+        assert!(encode(&InfluxLine, &SensorData::default()).is_empty());
+    }
+
+    #[test]
+    fn test_influx_line_tags() {
+        let tags = IndexMap::from([
+            ("corner".to_string(), "front left".to_string()),
+            ("car".to_string(), "a=b,c".to_string()),
+            ("empty".to_string(), String::new()),
+        ]);
+        let line = String::from_utf8(encode_tagged(&InfluxLine, &data(), &tags)).unwrap();
+        assert!(line.starts_with(r#"Front\ IMU,corner=front\ left,car=a\=b\,c g_force_x=0.5,"#));
+    }
+}
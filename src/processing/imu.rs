@@ -0,0 +1,419 @@
+//! G-forces, rotation rates and orientation from any accelerometer and
+//! gyroscope.
+//!
+//! The MPU-6500 driver computes these channels itself from its filtered
+//! axes.  Other IMU drivers only need to report what the chip measures —
+//! `accel_raw_x/y/z` in G, gravity included, and `gyro_x/y/z` in °/s — and
+//! [`ImuChannels`] adds whichever of these groups the driver doesn't:
+//!
+//! | group       | channels                                                  |
+//! |-------------|-----------------------------------------------------------|
+//! | G-FORCES    | g_force_x/y/z (gravity removed), combined_g, peak_g       |
+//! | GYROSCOPE   | roll_rate, pitch_rate, yaw_rate, angular_velocity         |
+//! | ORIENTATION | lean_angle, bank_angle, tilt_angle                        |
+//!
+//! Drivers that also report their fused tilt as `quat_w/x/y/z`, such as the
+//! MPU-6500 with its gravity filter on, get `corrected_lateral_g` as well:
+//! lateral G measured in the level frame rather than the chip's, so body
+//! roll and road banking don't leak gravity into it.
+
+use crate::sensors::{FieldDescriptor, SensorData, VizType};
+
+const ACCEL_KEYS: [&str; 3] = ["accel_raw_x", "accel_raw_y", "accel_raw_z"];
+const GYRO_KEYS: [&str; 3] = ["gyro_x", "gyro_y", "gyro_z"];
+const QUAT_KEYS: [&str; 4] = ["quat_w", "quat_x", "quat_y", "quat_z"];
+
+pub const GFORCE_FIELDS: [FieldDescriptor; 5] = [
+    FieldDescriptor {
+        key: "g_force_x",
+        label: "G Lateral",
+        viz: VizType::GForce,
+        range: None,
+        group: Some("G-FORCES"),
+    },
+    FieldDescriptor {
+        key: "g_force_y",
+        label: "G Forward",
+        viz: VizType::GForce,
+        range: None,
+        group: None,
+    },
+    FieldDescriptor {
+        key: "g_force_z",
+        label: "G Vertical",
+        viz: VizType::GForce,
+        range: None,
+        group: None,
+    },
+    FieldDescriptor {
+        key: "combined_g",
+        label: "Combined G",
+        viz: VizType::GForce,
+        range: None,
+        group: None,
+    },
+    FieldDescriptor {
+        key: "peak_g",
+        label: "Peak G",
+        viz: VizType::GForce,
+        range: None,
+        group: None,
+    },
+];
+
+pub const RATE_FIELDS: [FieldDescriptor; 4] = [
+    FieldDescriptor {
+        key: "roll_rate",
+        label: "Roll Rate",
+        viz: VizType::AngularRate,
+        range: None,
+        group: Some("GYROSCOPE"),
+    },
+    FieldDescriptor {
+        key: "pitch_rate",
+        label: "Pitch Rate",
+        viz: VizType::AngularRate,
+        range: None,
+        group: None,
+    },
+    FieldDescriptor {
+        key: "yaw_rate",
+        label: "Yaw Rate",
+        viz: VizType::AngularRate,
+        range: None,
+        group: None,
+    },
+    FieldDescriptor {
+        key: "angular_velocity",
+        label: "Angular Vel",
+        viz: VizType::AngularRate,
+        range: None,
+        group: None,
+    },
+];
+
+pub const ANGLE_FIELDS: [FieldDescriptor; 3] = [
+    FieldDescriptor {
+        key: "lean_angle",
+        label: "Lean Angle",
+        viz: VizType::Angle,
+        range: None,
+        group: Some("ORIENTATION"),
+    },
+    FieldDescriptor {
+        key: "bank_angle",
+        label: "Bank Angle",
+        viz: VizType::Angle,
+        range: None,
+        group: None,
+    },
+    FieldDescriptor {
+        key: "tilt_angle",
+        label: "Tilt Angle",
+        viz: VizType::Angle,
+        range: None,
+        group: None,
+    },
+];
+
+pub const LATERAL_FIELDS: [FieldDescriptor; 1] = [FieldDescriptor {
+    key: "corrected_lateral_g",
+    label: "Lateral G (Level)",
+    viz: VizType::GForce,
+    range: None,
+    group: Some("ROLL COMPENSATED"),
+}];
+
+pub fn magnitude(v: [f64; 3]) -> f64 {
+    (v[0].powi(2) + v[1].powi(2) + v[2].powi(2)).sqrt()
+}
+
+/// Remove the static gravity component from accelerometer readings.
+/// X and Y get gravity subtracted proportionally; Z is returned raw
+/// (gravity is mostly along Z when the sensor is horizontal).
+pub fn remove_gravity(raw: [f64; 3]) -> [f64; 3] {
+    let mag = magnitude(raw);
+    if mag < 1e-9 {
+        return raw;
+    }
+    let scale = 1.0 / mag;
+    let gravity = [raw[0] * scale, raw[1] * scale, raw[2] * scale];
+    [raw[0] - gravity[0], raw[1] - gravity[1], raw[2]]
+}
+
+/// Orientation from the gravity vector, in degrees.  `atan2` keeps these
+/// finite and continuous when an axis reads 0: on its side (Z ≈ 0) tilt
+/// and bank are ±90° rather than the arctangent of a huge ratio.  Z is
+/// taken as `|Z|`, so upside down reads the same as level.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Angles {
+    /// Between Z and the gravity vector, 0..=90
+    pub tilt: f64,
+    /// Y against the XZ plane, -90..=90
+    pub lean: f64,
+    /// X against Z, -90..=90
+    pub bank: f64,
+}
+
+impl Angles {
+    pub fn from_accel(a: [f64; 3]) -> Self {
+        let [x, y, z] = a;
+        Self {
+            tilt: x.hypot(y).atan2(z.abs()).to_degrees(),
+            lean: y.atan2(x.hypot(z)).to_degrees(),
+            bank: x.atan2(z.abs()).to_degrees(),
+        }
+    }
+}
+
+/// Lateral (Y) acceleration in G with the chip's tilt taken out: `accel`,
+/// gravity included, rotated by the tilt quaternion `q` (`[w, x, y, z]`,
+/// chip frame onto a level frame with Z up).  Gravity then lies along Z
+/// only, so what is left on Y is the vehicle's own sideways acceleration,
+/// the same on a banked road as on a flat one.
+pub fn level_lateral_g(q: [f64; 4], accel: [f64; 3]) -> f64 {
+    let [w, x, y, z] = q;
+    let row = [
+        2.0 * (x * y + w * z),
+        1.0 - 2.0 * (x * x + z * z),
+        2.0 * (y * z - w * x),
+    ];
+    row.iter().zip(accel).map(|(r, a)| r * a).sum()
+}
+
+/// The groups of channels one sensor's driver leaves for the service to
+/// add; worked out from its descriptors when it starts.
+pub struct ImuChannels {
+    gforce: bool,
+    rates: bool,
+    angles: bool,
+    lateral: bool,
+    /// Highest `combined_g` so far
+    peak_g: f64,
+}
+
+impl ImuChannels {
+    /// `None` when the driver has no raw axes, or already reports every
+    /// group it could get from them.
+    pub fn for_fields(fields: &[FieldDescriptor]) -> Option<Self> {
+        let has = |key: &str| fields.iter().any(|d| d.key == key);
+        let accel = ACCEL_KEYS.iter().all(|k| has(k));
+        let gyro = GYRO_KEYS.iter().all(|k| has(k));
+        let quat = QUAT_KEYS.iter().all(|k| has(k));
+        let channels = Self {
+            gforce: accel && !has("g_force_x"),
+            rates: gyro && !has("angular_velocity"),
+            angles: accel && !has("tilt_angle"),
+            lateral: accel && quat && !has("corrected_lateral_g"),
+            peak_g: 0.0,
+        };
+        (channels.gforce || channels.rates || channels.angles || channels.lateral)
+            .then_some(channels)
+    }
+
+    /// Descriptors for the channels added, to list after the driver's.
+    pub fn field_descriptors(&self) -> Vec<FieldDescriptor> {
+        let mut fields = Vec::new();
+        if self.gforce {
+            fields.extend(GFORCE_FIELDS);
+        }
+        if self.rates {
+            fields.extend(RATE_FIELDS);
+        }
+        if self.angles {
+            fields.extend(ANGLE_FIELDS);
+        }
+        if self.lateral {
+            fields.extend(LATERAL_FIELDS);
+        }
+        fields
+    }
+
+    /// Add the channels to `data`.  A reading missing one of the raw axes,
+    /// or the quaternion, gets none of the groups computed from them.
+    pub fn process(&mut self, data: &mut SensorData) {
+        let accel = channels(data, ACCEL_KEYS);
+        let gyro = channels(data, GYRO_KEYS);
+        let quat = channels(data, QUAT_KEYS);
+
+        if let Some(a) = accel.filter(|_| self.gforce) {
+            let g = remove_gravity(a);
+            let combined = magnitude(g);
+            self.peak_g = self.peak_g.max(combined);
+            for (d, v) in GFORCE_FIELDS
+                .iter()
+                .zip([g[0], g[1], g[2], combined, self.peak_g])
+            {
+                data.fields.insert(d.key.into(), v);
+            }
+        }
+        if let Some(r) = gyro.filter(|_| self.rates) {
+            for (d, v) in RATE_FIELDS.iter().zip([r[0], r[1], r[2], magnitude(r)]) {
+                data.fields.insert(d.key.into(), v);
+            }
+        }
+        if let Some(a) = accel.filter(|_| self.angles) {
+            let Angles { tilt, lean, bank } = Angles::from_accel(a);
+            for (d, v) in ANGLE_FIELDS.iter().zip([lean, bank, tilt]) {
+                data.fields.insert(d.key.into(), v);
+            }
+        }
+        if let (Some(a), Some(q)) = (accel.filter(|_| self.lateral), quat) {
+            let d = &LATERAL_FIELDS[0];
+            data.fields.insert(d.key.into(), level_lateral_g(q, a));
+        }
+    }
+
+    /// Clear `peak_g`, as recalibrating does.
+    pub fn reset_peak(&mut self) {
+        self.peak_g = 0.0;
+    }
+}
+
+/// The values of `keys`, if the reading has all of them.
+fn channels<const N: usize>(data: &SensorData, keys: [&str; N]) -> Option<[f64; N]> {
+    let mut values = [0.0; N];
+    for (v, k) in values.iter_mut().zip(keys) {
+        *v = *data.fields.get(k)?;
+    }
+    Some(values)
+}
+
+// ---------------------------------------------------------------------------
+// Unit tests
+// ---------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(got: [f64; 3], want: [f64; 3]) {
+        let ok = got.iter().zip(want).all(|(g, w)| (g - w).abs() < 1e-9);
+        assert!(ok, "got {:?}, want {:?}", got, want);
+    }
+
+    #[test]
+    fn test_remove_gravity_golden_vectors() {
+        let s = 0.5f64.sqrt();
+        let cases: [([f64; 3], [f64; 3]); 4] = [
+            // Level and still: nothing left on X/Y, Z passes through
+            ([0.0, 0.0, 1.0], [0.0, 0.0, 1.0]),
+            // Tilted 45° about Y, still: gravity fully removed from X
+            ([s, 0.0, s], [0.0, 0.0, s]),
+            // 2 g along a 3-4-5 direction: 1 g of it is gravity
+            ([1.2, 0.0, 1.6], [0.6, 0.0, 1.6]),
+            // Free fall: returned as-is rather than divided by zero
+            ([0.0, 0.0, 0.0], [0.0, 0.0, 0.0]),
+        ];
+        for (raw, want) in cases {
+            assert_close(remove_gravity(raw), want);
+        }
+        assert!((magnitude([3.0, 4.0, 12.0]) - 13.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_angle_golden_vectors() {
+        let (s30, c30) = (0.5, 0.75f64.sqrt());
+        // (accel, tilt, lean, bank)
+        let cases: [([f64; 3], f64, f64, f64); 8] = [
+            ([0.0, 0.0, 1.0], 0.0, 0.0, 0.0),
+            ([s30, 0.0, c30], 30.0, 0.0, 30.0),
+            ([-s30, 0.0, c30], 30.0, 0.0, -30.0),
+            ([0.0, s30, c30], 30.0, 30.0, 0.0),
+            // Upside down reads as level
+            ([0.0, 0.0, -1.0], 0.0, 0.0, 0.0),
+            // On its side, Z = 0: ±90° instead of atan(x / 1e-9)
+            ([1.0, 0.0, 0.0], 90.0, 0.0, 90.0),
+            ([0.0, -1.0, 0.0], 90.0, -90.0, 0.0),
+            // Free fall: no direction, but still finite
+            ([0.0, 0.0, 0.0], 0.0, 0.0, 0.0),
+        ];
+        for (a, tilt, lean, bank) in cases {
+            let got = Angles::from_accel(a);
+            assert_close([got.tilt, got.lean, got.bank], [tilt, lean, bank]);
+        }
+
+        // Continuous through Z = 0 from either side
+        let near = Angles::from_accel([1.0, 0.0, 1e-12]);
+        assert!((near.bank - 90.0).abs() < 1e-6 && (near.tilt - 90.0).abs() < 1e-6);
+        let past = Angles::from_accel([1.0, 0.0, -1e-12]);
+        assert!((past.bank - 90.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_channels_added_only_where_the_driver_has_none() {
+        let raw = |key| FieldDescriptor {
+            key,
+            label: key,
+            viz: VizType::Value,
+            range: None,
+            group: None,
+        };
+        let accel_only: Vec<_> = ACCEL_KEYS.map(raw).into();
+        let mut imu = ImuChannels::for_fields(&accel_only).unwrap();
+        let keys: Vec<&str> = imu.field_descriptors().iter().map(|d| d.key).collect();
+        assert_eq!(keys[..2], ["g_force_x", "g_force_y"]);
+        assert!(keys.contains(&"tilt_angle") && !keys.contains(&"yaw_rate"));
+
+        let mut data = SensorData::new(
+            ACCEL_KEYS
+                .iter()
+                .zip([1.2, 0.0, 1.6])
+                .map(|(k, v)| ((*k).into(), v))
+                .collect(),
+        );
+        imu.process(&mut data);
+        assert!((data.fields["g_force_x"] - 0.6).abs() < 1e-9);
+        assert!((data.fields["combined_g"] - 0.6f64.hypot(1.6)).abs() < 1e-9);
+        assert_eq!(data.fields["peak_g"], data.fields["combined_g"]);
+        assert!(!data.fields.contains_key("angular_velocity"));
+
+        // A driver that computes everything itself, or has no raw axes
+        let mut full = accel_only.clone();
+        full.extend(GFORCE_FIELDS.into_iter().chain(ANGLE_FIELDS));
+        assert!(ImuChannels::for_fields(&full).is_none());
+        assert!(ImuChannels::for_fields(&[raw("temperature")]).is_none());
+    }
+
+    #[test]
+    fn test_lateral_g_is_level_on_a_banked_road() {
+        // 0.8 g cornering on a road banked 10°, chip rolled with the car
+        let bank = 10f64.to_radians();
+        let (s, c) = bank.sin_cos();
+        let accel = [0.0, 0.8 * c + s, c - 0.8 * s];
+        let q = [(bank / 2.0).cos(), (bank / 2.0).sin(), 0.0, 0.0];
+        assert!((level_lateral_g(q, accel) - 0.8).abs() < 1e-9);
+        // Parked on the bank: the chip's Y reads gravity, level lateral 0
+        assert!(level_lateral_g(q, [0.0, s, c]).abs() < 1e-9);
+
+        // A driver with every other group but a quaternion gets just this one
+        let raw = |key| FieldDescriptor {
+            key,
+            label: key,
+            viz: VizType::Value,
+            range: None,
+            group: None,
+        };
+        let mut fields: Vec<_> = ACCEL_KEYS.into_iter().chain(QUAT_KEYS).map(raw).collect();
+        fields.extend(
+            GFORCE_FIELDS
+                .into_iter()
+                .chain(RATE_FIELDS)
+                .chain(ANGLE_FIELDS),
+        );
+        let mut imu = ImuChannels::for_fields(&fields).unwrap();
+        let keys: Vec<&str> = imu.field_descriptors().iter().map(|d| d.key).collect();
+        assert_eq!(keys, ["corrected_lateral_g"]);
+
+        let mut data = SensorData::new(
+            ACCEL_KEYS
+                .into_iter()
+                .zip(accel)
+                .chain(QUAT_KEYS.into_iter().zip(q))
+                .map(|(k, v)| (k.into(), v))
+                .collect(),
+        );
+        imu.process(&mut data);
+        assert!((data.fields["corrected_lateral_g"] - 0.8).abs() < 1e-9);
+    }
+}
@@ -0,0 +1,5 @@
+//! Channel computations shared between drivers and applied by the service.
+
+pub mod imu;
+pub mod stuck;
+pub mod timing;
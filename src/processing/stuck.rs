@@ -0,0 +1,133 @@
+//! Stuck-value detection (`[stuck_values]`).
+//!
+//! A channel that reads exactly the same value `samples` times in a row is
+//! reported stuck: the classic symptom of a dead sensor or a hung bus, which
+//! otherwise keeps publishing plausible numbers.  A channel held at 0 by a
+//! driver's dead-zone filter looks the same, so `channels` can leave those
+//! out.  Only the driver's own channels are checked, before any processing
+//! adds to them.
+
+use indexmap::IndexMap;
+
+use crate::config::StuckValuesConfig;
+use crate::sensors::{ChannelKey, Quality, SensorData};
+
+/// A channel that became stuck or started changing again.
+#[derive(Debug, Clone, PartialEq)]
+pub enum StuckChange {
+    Stuck { key: String, value: f64 },
+    Changing { key: String },
+}
+
+pub struct StuckDetector {
+    cfg: StuckValuesConfig,
+    /// Per channel: the value it last read and how many times in a row
+    runs: IndexMap<ChannelKey, (f64, u32)>,
+}
+
+impl StuckDetector {
+    pub fn new(mut cfg: StuckValuesConfig) -> Self {
+        // One reading is always the same as itself
+        cfg.samples = cfg.samples.max(2);
+        Self {
+            cfg,
+            runs: IndexMap::new(),
+        }
+    }
+
+    /// Count `data`'s channels against the previous readings and return
+    /// the ones whose state changed.  Invalid readings neither count
+    /// towards a run nor end one.
+    pub fn process(&mut self, data: &SensorData) -> Vec<StuckChange> {
+        let mut changes = Vec::new();
+        for (key, &value) in &data.fields {
+            if !self.cfg.channels.allows(key) || data.quality(key) == Quality::Invalid {
+                continue;
+            }
+            let (last, count) = self.runs.entry(key.clone()).or_insert((value, 0));
+            if *last == value {
+                *count += 1;
+                if *count == self.cfg.samples {
+                    changes.push(StuckChange::Stuck {
+                        key: key.to_string(),
+                        value,
+                    });
+                }
+            } else {
+                if *count >= self.cfg.samples {
+                    changes.push(StuckChange::Changing { key: key.to_string() });
+                }
+                *last = value;
+                *count = 1;
+            }
+        }
+        changes
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Unit tests
+// ---------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::ChannelFilter;
+
+    fn reading(temp: f64, accel: f64) -> SensorData {
+        SensorData::new(
+            [("temp".into(), temp), ("accel_x".into(), accel)]
+                .into_iter()
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn test_reports_stuck_and_changing_channels() {
+        let mut d = StuckDetector::new(StuckValuesConfig {
+            enabled: true,
+            samples: 3,
+            channels: ChannelFilter::default(),
+        });
+        assert!(d.process(&reading(21.0, 0.1)).is_empty());
+        assert!(d.process(&reading(21.0, 0.2)).is_empty());
+        assert_eq!(
+            d.process(&reading(21.0, 0.3)),
+            [StuckChange::Stuck {
+                key: "temp".into(),
+                value: 21.0
+            }]
+        );
+        // Reported once, however long it stays stuck
+        assert!(d.process(&reading(21.0, 0.4)).is_empty());
+
+        // An invalid reading doesn't end the run
+        let mut invalid = reading(0.0, 0.5);
+        invalid.set_quality("temp", Quality::Invalid);
+        assert!(d.process(&invalid).is_empty());
+        assert_eq!(
+            d.process(&reading(21.5, 0.6)),
+            [StuckChange::Changing { key: "temp".into() }]
+        );
+    }
+
+    #[test]
+    fn test_excluded_channels_are_not_checked() {
+        let mut d = StuckDetector::new(StuckValuesConfig {
+            enabled: true,
+            samples: 2,
+            channels: ChannelFilter {
+                include: Vec::new(),
+                exclude: vec!["temp".into()],
+            },
+        });
+        d.process(&reading(21.0, 0.0));
+        assert_eq!(
+            d.process(&reading(21.0, 0.0)),
+            [StuckChange::Stuck {
+                key: "accel_x".into(),
+                value: 0.0
+            }]
+        );
+    }
+}
@@ -0,0 +1,128 @@
+//! Sampling regularity (`[sampling] timing_channels`).
+//!
+//! Every reading gets two channels:
+//!
+//! | Key            | Unit | Description                                       |
+//! |----------------|------|---------------------------------------------------|
+//! | dt_ms          | ms   | time since the sensor's previous reading          |
+//! | missed_samples |      | readings so far that came over 1.5 × the interval |
+//!
+//! Time is measured on the monotonic clock, so a clock step doesn't count
+//! as a late reading.  The gap across a pause, a group switched off or a
+//! recalibration is not counted either.
+
+use std::time::Duration;
+
+use crate::sensors::{FieldDescriptor, Quality, SensorData, VizType};
+
+/// A reading this much later than the interval counts as missed.
+const LATE_FACTOR: f64 = 1.5;
+
+const FIELDS: &[FieldDescriptor] = &[
+    FieldDescriptor {
+        key: "dt_ms",
+        label: "Sample Interval",
+        viz: VizType::Numeric { unit: "ms" },
+        range: None,
+        group: Some("TIMING"),
+    },
+    FieldDescriptor {
+        key: "missed_samples",
+        label: "Missed Samples",
+        viz: VizType::Value,
+        range: None,
+        group: None,
+    },
+];
+
+#[derive(Debug, Default)]
+pub struct SampleTiming {
+    /// Monotonic time of the previous reading and the interval it was
+    /// read at
+    last: Option<(f64, Duration)>,
+    missed: u64,
+}
+
+impl SampleTiming {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Descriptors for the channels [`process`](Self::process) adds.
+    pub fn field_descriptors() -> &'static [FieldDescriptor] {
+        FIELDS
+    }
+
+    /// Append the timing channels to `data`, read at `interval`.  Readings
+    /// without a monotonic time are left alone.
+    pub fn process(&mut self, data: &mut SensorData, interval: Duration) {
+        let Some(now) = data.monotonic else {
+            return;
+        };
+        let dt = self.last.map(|(last, last_interval)| {
+            // Judge by the slower rate across an `[adaptive_rate]` switch
+            let nominal = interval.max(last_interval).as_secs_f64();
+            let dt = now - last;
+            if dt > nominal * LATE_FACTOR {
+                self.missed += 1;
+            }
+            dt * 1000.0
+        });
+        self.last = Some((now, interval));
+
+        data.fields.insert("dt_ms".into(), dt.unwrap_or(0.0));
+        if dt.is_none() {
+            data.set_quality("dt_ms", Quality::Invalid);
+        }
+        data.fields
+            .insert("missed_samples".into(), self.missed as f64);
+    }
+
+    /// Forget the previous reading, so a deliberate gap isn't counted.
+    pub fn reset(&mut self) {
+        self.last = None;
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Unit tests
+// ---------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(t: &mut SampleTiming, secs: f64, interval_ms: u64) -> SensorData {
+        let mut data = SensorData {
+            monotonic: Some(secs),
+            ..SensorData::default()
+        };
+        t.process(&mut data, Duration::from_millis(interval_ms));
+        data
+    }
+
+    #[test]
+    fn test_dt_and_missed_samples() {
+        let mut t = SampleTiming::new();
+        let first = run(&mut t, 10.0, 100);
+        assert_eq!(first.quality("dt_ms"), Quality::Invalid);
+        assert_eq!(first.fields["missed_samples"], 0.0);
+
+        let d = run(&mut t, 10.1, 100);
+        assert!((d.fields["dt_ms"] - 100.0).abs() < 1e-6);
+        assert_eq!(d.quality("dt_ms"), Quality::Good);
+        // 140 ms is within 1.5 × 100 ms, 160 ms isn't
+        assert_eq!(run(&mut t, 10.24, 100).fields["missed_samples"], 0.0);
+        assert_eq!(run(&mut t, 10.4, 100).fields["missed_samples"], 1.0);
+
+        // Switching from a 1 s idle rate back to full rate isn't a miss
+        run(&mut t, 11.4, 1000);
+        assert_eq!(run(&mut t, 12.4, 100).fields["missed_samples"], 1.0);
+
+        // Nor is the gap across a pause
+        t.reset();
+        let d = run(&mut t, 100.0, 100);
+        assert_eq!(d.quality("dt_ms"), Quality::Invalid);
+        assert_eq!(d.fields["missed_samples"], 1.0);
+    }
+}
@@ -0,0 +1,294 @@
+//! Protobuf encoding of `FILTERED` / `DERIVED` (`mqtt.payload_format`).
+//!
+//! The message definition is `proto/sensors_to_mqtt.proto` ([`PROTO`]).  It
+//! is small and fixed, so the wire format is written by hand here rather
+//! than pulling in a code generator and a build script.
+
+use anyhow::{Context, Result, bail};
+use chrono::{DateTime, Utc};
+
+use crate::config::ChannelFilter;
+use crate::sensors::{ChannelValue, SensorData};
+
+/// The published message definition.
+pub const PROTO: &str = include_str!("../proto/sensors_to_mqtt.proto");
+
+// Wire types
+const VARINT: u8 = 0;
+const I64: u8 = 1;
+const LEN: u8 = 2;
+
+/// A `Reading` with the `keys` found in `data.fields`, plus the
+/// `data.values` that `values` allows — the same selection as the JSON
+/// payloads.
+pub fn encode_reading(
+    timestamp: &str,
+    schema_version: Option<u32>,
+    data: &SensorData,
+    keys: &[&str],
+    values: Option<&ChannelFilter>,
+) -> Vec<u8> {
+    let mut buf = Vec::with_capacity(32 + keys.len() * 24);
+    encode_reading_into(&mut buf, timestamp, schema_version, data, keys, values);
+    buf
+}
+
+/// [`encode_reading`], appended to `buf`.
+pub fn encode_reading_into(
+    buf: &mut Vec<u8>,
+    timestamp: &str,
+    schema_version: Option<u32>,
+    data: &SensorData,
+    keys: &[&str],
+    values: Option<&ChannelFilter>,
+) {
+    put_bytes(buf, 1, timestamp.as_bytes());
+    if let Some(t) = data.monotonic {
+        put_double(buf, 2, t);
+    }
+    if data.clock_unsynced {
+        put_varint_field(buf, 3, 1);
+    }
+    if let Some(v) = schema_version {
+        put_varint_field(buf, 6, u64::from(v));
+    }
+    for key in keys {
+        if let Some(&v) = data.fields.get(*key) {
+            // Key and double, written in place: the entry's length is known
+            let key_len = key.len() as u64;
+            put_tag(buf, 4, LEN);
+            put_varint(buf, 1 + varint_len(key_len) + key_len + 9);
+            put_bytes(buf, 1, key.as_bytes());
+            put_double(buf, 2, v);
+        }
+    }
+    if let Some(filter) = values {
+        let mut entry = Vec::new();
+        let mut value = Vec::new();
+        for (key, v) in data.values.iter().filter(|(k, _)| filter.allows(k)) {
+            value.clear();
+            match v {
+                ChannelValue::Bool(b) => put_varint_field(&mut value, 1, u64::from(*b)),
+                ChannelValue::Int(i) => put_varint_field(&mut value, 2, *i as u64),
+                ChannelValue::Float(f) => put_double(&mut value, 3, *f),
+                ChannelValue::Text(t) => put_bytes(&mut value, 4, t.as_bytes()),
+            }
+            entry.clear();
+            put_bytes(&mut entry, 1, key.as_bytes());
+            put_bytes(&mut entry, 2, &value);
+            put_bytes(buf, 5, &entry);
+        }
+    }
+}
+
+/// Decode a `Reading` back into the parts of a [`SensorData`] it carries.
+/// Unknown fields are skipped, so newer publishers stay readable.
+pub fn decode_reading(buf: &[u8]) -> Result<SensorData> {
+    let mut data = SensorData::default();
+    let mut timestamp = None;
+    let mut r = Reader(buf);
+    while let Some((field, wire)) = r.tag()? {
+        match (field, wire) {
+            (1, LEN) => timestamp = Some(std::str::from_utf8(r.bytes()?)?.to_string()),
+            (2, I64) => data.monotonic = Some(r.double()?),
+            (3, VARINT) => data.clock_unsynced = r.varint()? != 0,
+            (4, LEN) => {
+                let mut e = Reader(r.bytes()?);
+                let (mut key, mut value) = (String::new(), 0.0);
+                while let Some((f, w)) = e.tag()? {
+                    match (f, w) {
+                        (1, LEN) => key = std::str::from_utf8(e.bytes()?)?.to_string(),
+                        (2, I64) => value = e.double()?,
+                        _ => e.skip(w)?,
+                    }
+                }
+                data.fields.insert(key.into(), value);
+            }
+            (5, LEN) => {
+                let mut e = Reader(r.bytes()?);
+                let (mut key, mut value) = (String::new(), None);
+                while let Some((f, w)) = e.tag()? {
+                    match (f, w) {
+                        (1, LEN) => key = std::str::from_utf8(e.bytes()?)?.to_string(),
+                        (2, LEN) => value = decode_value(e.bytes()?)?,
+                        _ => e.skip(w)?,
+                    }
+                }
+                if let Some(v) = value {
+                    data.values.insert(key, v);
+                }
+            }
+            (_, w) => r.skip(w)?,
+        }
+    }
+    let timestamp = timestamp.context("reading without a timestamp")?;
+    data.timestamp = DateTime::parse_from_rfc3339(&timestamp)
+        .with_context(|| format!("bad timestamp {:?}", timestamp))?
+        .with_timezone(&Utc);
+    Ok(data)
+}
+
+fn decode_value(buf: &[u8]) -> Result<Option<ChannelValue>> {
+    let mut r = Reader(buf);
+    let mut value = None;
+    while let Some((field, wire)) = r.tag()? {
+        value = Some(match (field, wire) {
+            (1, VARINT) => ChannelValue::Bool(r.varint()? != 0),
+            (2, VARINT) => ChannelValue::Int(r.varint()? as i64),
+            (3, I64) => ChannelValue::Float(r.double()?),
+            (4, LEN) => ChannelValue::Text(std::str::from_utf8(r.bytes()?)?.to_string()),
+            (_, w) => {
+                r.skip(w)?;
+                continue;
+            }
+        });
+    }
+    Ok(value)
+}
+
+fn put_varint(buf: &mut Vec<u8>, mut v: u64) {
+    while v >= 0x80 {
+        buf.push(v as u8 | 0x80);
+        v >>= 7;
+    }
+    buf.push(v as u8);
+}
+
+/// Bytes [`put_varint`] writes for `v`.
+fn varint_len(v: u64) -> u64 {
+    u64::from((64 - v.leading_zeros()).max(1).div_ceil(7))
+}
+
+fn put_tag(buf: &mut Vec<u8>, field: u32, wire: u8) {
+    put_varint(buf, (u64::from(field) << 3) | u64::from(wire));
+}
+
+fn put_varint_field(buf: &mut Vec<u8>, field: u32, v: u64) {
+    put_tag(buf, field, VARINT);
+    put_varint(buf, v);
+}
+
+fn put_double(buf: &mut Vec<u8>, field: u32, v: f64) {
+    put_tag(buf, field, I64);
+    buf.extend_from_slice(&v.to_le_bytes());
+}
+
+fn put_bytes(buf: &mut Vec<u8>, field: u32, bytes: &[u8]) {
+    put_tag(buf, field, LEN);
+    put_varint(buf, bytes.len() as u64);
+    buf.extend_from_slice(bytes);
+}
+
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, n: usize) -> Result<&'a [u8]> {
+        if self.0.len() < n {
+            bail!("truncated protobuf message");
+        }
+        let (head, rest) = self.0.split_at(n);
+        self.0 = rest;
+        Ok(head)
+    }
+
+    fn varint(&mut self) -> Result<u64> {
+        let mut v = 0u64;
+        for shift in (0..64).step_by(7) {
+            let b = self.take(1)?[0];
+            v |= u64::from(b & 0x7f) << shift;
+            if b & 0x80 == 0 {
+                return Ok(v);
+            }
+        }
+        bail!("varint too long")
+    }
+
+    /// `(field number, wire type)`, or `None` at the end of the message.
+    fn tag(&mut self) -> Result<Option<(u64, u8)>> {
+        if self.0.is_empty() {
+            return Ok(None);
+        }
+        let tag = self.varint()?;
+        Ok(Some((tag >> 3, (tag & 7) as u8)))
+    }
+
+    fn double(&mut self) -> Result<f64> {
+        let bytes = self.take(8)?;
+        Ok(f64::from_le_bytes(bytes.try_into().expect("8 bytes")))
+    }
+
+    fn bytes(&mut self) -> Result<&'a [u8]> {
+        let len = self.varint()? as usize;
+        self.take(len)
+    }
+
+    fn skip(&mut self, wire: u8) -> Result<()> {
+        match wire {
+            VARINT => drop(self.varint()?),
+            I64 => drop(self.take(8)?),
+            LEN => drop(self.bytes()?),
+            5 => drop(self.take(4)?),
+            other => bail!("unsupported wire type {}", other),
+        }
+        Ok(())
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Unit tests
+// ---------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_known_encoding() {
+        // Tag bytes are (field << 3) | wire type; 1.0 is 0x3ff0… little-endian
+        let data = SensorData::new([("a".into(), 1.0)].into_iter().collect());
+        let buf = encode_reading("t", None, &data, &["a", "missing"], None);
+        assert_eq!(
+            buf,
+            [
+                0x0a, 1, b't', // timestamp
+                0x22, 12, 0x0a, 1, b'a', 0x11, 0, 0, 0, 0, 0, 0, 0xf0, 0x3f, // channels
+            ]
+        );
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let mut data = SensorData::new(
+            [
+                ("g_force_x".into(), -0.25),
+                ("speed".into(), 88.0),
+            ]
+            .into_iter()
+            .collect(),
+        );
+        data.timestamp = "2025-01-01T12:00:00.120Z".parse().unwrap();
+        data.monotonic = Some(12.5);
+        data.clock_unsynced = true;
+        data.set_value("fix", ChannelValue::Int(-3));
+        data.set_value("ok", ChannelValue::Bool(true));
+        data.set_value("status", ChannelValue::Text("armed".into()));
+        data.set_value("hdop", ChannelValue::Float(0.9));
+
+        let ts = data.timestamp.to_rfc3339();
+        let buf = encode_reading(
+            &ts,
+            Some(1),
+            &data,
+            &["g_force_x", "speed"],
+            Some(&ChannelFilter::default()),
+        );
+        let back = decode_reading(&buf).unwrap();
+        assert_eq!(back.timestamp, data.timestamp);
+        assert_eq!(back.monotonic, Some(12.5));
+        assert!(back.clock_unsynced);
+        assert_eq!(back.fields, data.fields);
+        assert_eq!(back.values, data.values);
+
+        assert!(decode_reading(&buf[..buf.len() - 1]).is_err());
+    }
+}
@@ -0,0 +1,99 @@
+//! Switching where readings go at runtime (`AppState::publish_mode`).
+//!
+//! `mqtt` publishes them as usual, `log` writes them to the log at debug
+//! level instead, and `off` drops them.  Switching away from `mqtt` quiets
+//! the broker, over a metered tether for instance, without restarting the
+//! sensor tasks and losing their calibration.  Only the readings are held
+//! back (`FILTERED`, `DERIVED`, `INFO`, `LATEST`); availability, errors and
+//! lap events still go out.
+//!
+//! The TUI cycles through the modes with `p`, and over MQTT a mode is set
+//! by publishing `mqtt`, `log` or `off` to `<base_topic>/PUBLISH/SET`.  The
+//! current mode is kept retained on `<base_topic>/PUBLISH`, whichever way
+//! it was changed.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use tokio_util::sync::CancellationToken;
+use tracing::{info, warn};
+
+use crate::command_auth::CommandAuth;
+use crate::models::{PublishMode, SharedState};
+use crate::mqtt_handler::MqttHandle;
+
+/// How often the retained mode is compared with `AppState`.
+const STATE_POLL: Duration = Duration::from_millis(500);
+
+/// Follow `SET` commands that `auth` accepts and publish the current mode
+/// until `cancel` fires.
+pub fn spawn_publish_control(
+    state: SharedState,
+    mqtt: MqttHandle,
+    base_topic: &str,
+    auth: Arc<CommandAuth>,
+    cancel: CancellationToken,
+) {
+    let topic: Arc<str> = format!("{}/PUBLISH", base_topic).into();
+    let mut commands = mqtt.subscribe(format!("{}/SET", topic));
+    tokio::spawn(async move {
+        let mut published = None;
+        let mut tick = tokio::time::interval(STATE_POLL);
+        loop {
+            tokio::select! {
+                _ = cancel.cancelled() => return,
+                Some(msg) = commands.recv() => {
+                    let payload = match auth.check("publish", &msg.topic, &msg.payload) {
+                        Ok(p) => p,
+                        Err(e) => {
+                            warn!("Rejected MQTT publish command: {}", e);
+                            continue;
+                        }
+                    };
+                    match PublishMode::parse(&payload) {
+                        Some(mode) => {
+                            info!("Publishing switched to {} over MQTT", mode.label());
+                            state.write().await.publish_mode = mode;
+                        }
+                        None => warn!(
+                            "Publish command: expected 'mqtt', 'log' or 'off', got {:?}",
+                            String::from_utf8_lossy(&payload)
+                        ),
+                    }
+                }
+                _ = tick.tick() => {}
+            }
+            let mode = state.read().await.publish_mode;
+            if published != Some(mode) {
+                mqtt.publish_retained(Arc::clone(&topic), mode.label())
+                    .await;
+                published = Some(mode);
+            }
+        }
+    });
+}
+
+// ---------------------------------------------------------------------------
+// Unit tests
+// ---------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_and_cycle_modes() {
+        assert_eq!(PublishMode::parse(b" OFF\n"), Some(PublishMode::Off));
+        assert_eq!(PublishMode::parse(b"log"), Some(PublishMode::Log));
+        assert_eq!(PublishMode::parse(b"on"), None);
+
+        let mut mode = PublishMode::default();
+        let cycle: Vec<_> = (0..3)
+            .map(|_| {
+                mode = mode.next();
+                mode.label()
+            })
+            .collect();
+        assert_eq!(cycle, ["log", "off", "mqtt"]);
+    }
+}
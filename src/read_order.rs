@@ -0,0 +1,245 @@
+//! Order of sensor reads that fall due together (`sampling.read_strategy`).
+//!
+//! Every sensor polls from its own task, so by default reads only wait for
+//! each other where they share a bus (the bus lock in
+//! [`crate::transport::i2c_bus`]), and then in whatever order the tasks
+//! happen to wake.  A slow environmental sensor can then hold the bus while
+//! the IMU waits.  Here each sensor takes a turn from a [`ReadQueue`]
+//! before reading; the queue serves one reader at a time, highest
+//! `priority` first, in arrival order otherwise.  Which sensors share a
+//! queue depends on the strategy.
+
+use std::cmp::Ordering;
+use std::collections::{BinaryHeap, HashMap};
+use std::sync::{Arc, Mutex, PoisonError};
+
+use tokio::sync::oneshot;
+
+use crate::config::{ConnectionConfig, ReadStrategy, SensorConfig};
+
+/// The queues for one run, one per group of sensors that take turns.
+pub struct ReadOrder {
+    strategy: ReadStrategy,
+    queues: Mutex<HashMap<String, Arc<ReadQueue>>>,
+}
+
+impl ReadOrder {
+    pub fn new(strategy: ReadStrategy) -> Self {
+        Self {
+            strategy,
+            queues: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Where `cfg`'s reads queue; `None` when it never waits for another
+    /// sensor.
+    pub fn turn_for(&self, cfg: &SensorConfig) -> Option<ReadTurn> {
+        let (group, priority) = match self.strategy {
+            ReadStrategy::Sequential => (String::new(), 0),
+            ReadStrategy::Priority => (String::new(), cfg.priority),
+            ReadStrategy::Parallel => (bus_key(&cfg.connection)?, cfg.priority),
+        };
+        let queue = self
+            .queues
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .entry(group)
+            .or_default()
+            .clone();
+        Some(ReadTurn { queue, priority })
+    }
+}
+
+/// The bus a connection's reads contend for; GPIO pins don't share one.
+fn bus_key(conn: &ConnectionConfig) -> Option<String> {
+    match conn {
+        ConnectionConfig::I2c(c) => Some(format!("i2c:{}", c.device)),
+        ConnectionConfig::Serial(c) => Some(format!("serial:{}", c.port)),
+        ConnectionConfig::Tcp(c) => Some(format!("tcp:{}:{}", c.host, c.port)),
+        ConnectionConfig::Gpio(_) => None,
+    }
+}
+
+/// Readers that go one at a time.
+#[derive(Default)]
+pub struct ReadQueue {
+    state: Mutex<QueueState>,
+}
+
+#[derive(Default)]
+struct QueueState {
+    busy: bool,
+    waiting: BinaryHeap<Waiter>,
+    arrivals: u64,
+}
+
+impl ReadQueue {
+    fn lock(&self) -> std::sync::MutexGuard<'_, QueueState> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Hand the turn to the next waiter, or free the queue.
+    fn release(&self) {
+        let mut q = self.lock();
+        while let Some(w) = q.waiting.pop() {
+            if w.wake.send(()).is_ok() {
+                return;
+            }
+        }
+        q.busy = false;
+    }
+}
+
+struct Waiter {
+    priority: i32,
+    arrival: u64,
+    wake: oneshot::Sender<()>,
+}
+
+impl Ord for Waiter {
+    /// Highest priority first, then earliest arrival.
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.priority
+            .cmp(&other.priority)
+            .then(other.arrival.cmp(&self.arrival))
+    }
+}
+
+impl PartialOrd for Waiter {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for Waiter {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Waiter {}
+
+/// One sensor's place in a [`ReadQueue`].
+#[derive(Clone)]
+pub struct ReadTurn {
+    queue: Arc<ReadQueue>,
+    priority: i32,
+}
+
+impl std::fmt::Debug for ReadTurn {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ReadTurn")
+            .field("priority", &self.priority)
+            .finish_non_exhaustive()
+    }
+}
+
+impl ReadTurn {
+    /// Wait until it is this sensor's turn to read; the turn passes on when
+    /// the guard drops.  Not cancel-safe: a turn handed to a dropped
+    /// future is lost, so always await it to completion.
+    pub async fn wait(&self) -> ReadGuard<'_> {
+        let wake = {
+            let mut q = self.queue.lock();
+            if !q.busy {
+                q.busy = true;
+                return ReadGuard(&self.queue);
+            }
+            let (tx, rx) = oneshot::channel();
+            q.arrivals += 1;
+            let arrival = q.arrivals;
+            q.waiting.push(Waiter {
+                priority: self.priority,
+                arrival,
+                wake: tx,
+            });
+            rx
+        };
+        // The sender only goes away with the queue, which `self` keeps alive
+        let _ = wake.await;
+        ReadGuard(&self.queue)
+    }
+}
+
+/// Held while a sensor reads.
+pub struct ReadGuard<'a>(&'a ReadQueue);
+
+impl Drop for ReadGuard<'_> {
+    fn drop(&mut self) {
+        self.0.release();
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Unit tests
+// ---------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sensor(name: &str, priority: i32, connection: &str) -> SensorConfig {
+        toml::from_str(&format!(
+            "name = {:?}\ndriver = \"synthetic\"\npriority = {}\n[connection]\n{}",
+            name, priority, connection
+        ))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_groups_follow_strategy() {
+        let imu = sensor("imu", 10, "type = \"i2c\"\naddress = 0x68");
+        let env = sensor("env", 0, "type = \"i2c\"\naddress = 0x76");
+        let other_bus = sensor("adc", 0, "type = \"tcp\"\nhost = \"pi\"");
+        let button = sensor("btn", 0, "type = \"gpio\"\npin = 17");
+
+        let order = ReadOrder::new(ReadStrategy::Parallel);
+        let same = |a: &SensorConfig, b: &SensorConfig| {
+            Arc::ptr_eq(
+                &order.turn_for(a).unwrap().queue,
+                &order.turn_for(b).unwrap().queue,
+            )
+        };
+        assert!(same(&imu, &env));
+        assert!(!same(&imu, &other_bus));
+        assert!(order.turn_for(&button).is_none());
+
+        let order = ReadOrder::new(ReadStrategy::Sequential);
+        let (a, b) = (
+            order.turn_for(&imu).unwrap(),
+            order.turn_for(&button).unwrap(),
+        );
+        assert!(Arc::ptr_eq(&a.queue, &b.queue));
+        assert_eq!(a.priority, 0, "sequential ignores priority");
+    }
+
+    #[tokio::test]
+    async fn test_highest_priority_reads_first() {
+        let queue = Arc::new(ReadQueue::default());
+        let turn = |priority| ReadTurn {
+            queue: Arc::clone(&queue),
+            priority,
+        };
+        let (slow, env, early, imu) = (turn(0), turn(0), turn(0), turn(10));
+        let order = Arc::new(Mutex::new(Vec::new()));
+
+        // `slow` is mid-read while the others fall due
+        let held = slow.wait().await;
+        let mut tasks = Vec::new();
+        for (name, t) in [("early", early), ("env", env), ("imu", imu)] {
+            let order = Arc::clone(&order);
+            tasks.push(tokio::spawn(async move {
+                let _guard = t.wait().await;
+                order.lock().unwrap().push(name);
+            }));
+            // Let it queue before the next one arrives
+            tokio::task::yield_now().await;
+        }
+        drop(held);
+        for t in tasks {
+            t.await.unwrap();
+        }
+        assert_eq!(*order.lock().unwrap(), ["imu", "early", "env"]);
+        assert!(!queue.lock().busy);
+    }
+}
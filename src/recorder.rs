@@ -0,0 +1,237 @@
+//! Session recording: every incoming reading from every sensor, appended to
+//! a JSON-lines file while recording is active (TUI `R` key).
+//!
+//! One line per reading:
+//! `{"sensor":"imu1","timestamp":"2025-…Z","fields":{"accel_x":0.01,…}}`
+//! plus `"values"` / `"quality"` objects when the reading has any.
+//!
+//! With a size limit in [`Retention`], a long session is split into
+//! `session_<time>_2.jsonl`, `_3`, … and old files are pruned as it goes.
+
+use anyhow::{Context, Result};
+use chrono::Local;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use tracing::{info, warn};
+
+use crate::retention::Retention;
+use crate::sensors::SensorData;
+
+pub struct SessionRecorder {
+    dir: PathBuf,
+    /// `session_<time>`; segments after the first get `_<n>` appended
+    stem: String,
+    segment: u32,
+    path: PathBuf,
+    out: BufWriter<File>,
+    started: Instant,
+    /// Written across all segments
+    bytes: u64,
+    segment_bytes: u64,
+    records: u64,
+    retention: Retention,
+}
+
+/// What the status bar needs to draw the REC indicator.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecordingInfo {
+    pub path: PathBuf,
+    pub elapsed: Duration,
+    pub bytes: u64,
+}
+
+impl SessionRecorder {
+    /// Start a new session file `<dir>/session_<YYYYmmdd_HHMMSS>.jsonl`,
+    /// creating `dir` if needed, and prune `dir` under `retention`.
+    pub fn start(dir: &Path, retention: Retention) -> Result<Self> {
+        std::fs::create_dir_all(dir)
+            .with_context(|| format!("Failed to create recording directory {}", dir.display()))?;
+        let stem = format!("session_{}", Local::now().format("%Y%m%d_%H%M%S"));
+        let path = dir.join(format!("{}.jsonl", stem));
+        let out = create(&path)?;
+        let rec = Self {
+            dir: dir.to_path_buf(),
+            stem,
+            segment: 1,
+            path,
+            out,
+            started: Instant::now(),
+            bytes: 0,
+            segment_bytes: 0,
+            records: 0,
+            retention,
+        };
+        rec.prune();
+        Ok(rec)
+    }
+
+    pub fn record(&mut self, sensor: &str, data: &SensorData) -> Result<()> {
+        let mut record = json!({
+            "sensor": sensor,
+            "timestamp": data.timestamp.to_rfc3339(),
+            "fields": data.fields,
+        });
+        if !data.values.is_empty() {
+            record["values"] = json!(data.values);
+        }
+        if !data.quality.is_empty() {
+            record["quality"] = json!(data.quality);
+        }
+        let line = record.to_string();
+        self.out.write_all(line.as_bytes())?;
+        self.out.write_all(b"\n")?;
+        self.bytes += line.len() as u64 + 1;
+        self.segment_bytes += line.len() as u64 + 1;
+        self.records += 1;
+        if self
+            .retention
+            .segment_bytes()
+            .is_some_and(|max| self.segment_bytes >= max)
+        {
+            self.next_segment()?;
+        }
+        Ok(())
+    }
+
+    /// Close the current file and continue in `<stem>_<n>.jsonl`.
+    fn next_segment(&mut self) -> Result<()> {
+        self.out
+            .flush()
+            .with_context(|| format!("Failed to flush {}", self.path.display()))?;
+        self.segment += 1;
+        self.path = self
+            .dir
+            .join(format!("{}_{}.jsonl", self.stem, self.segment));
+        self.out = create(&self.path)?;
+        self.segment_bytes = 0;
+        self.prune();
+        Ok(())
+    }
+
+    /// Make room for the segment now being written.  Failures are logged
+    /// rather than stopping the recording.
+    fn prune(&self) {
+        let reserve = self.retention.segment_bytes().unwrap_or(0);
+        match self.retention.prune(&self.dir, Some(&self.path), reserve) {
+            Ok(removed) => {
+                for path in removed {
+                    info!("Retention: deleted {}", path.display());
+                }
+            }
+            Err(e) => warn!("Retention pruning failed: {:#}", e),
+        }
+    }
+
+    /// Write out everything buffered so far and keep recording, so the
+    /// file is complete up to now if power is cut.
+    pub fn flush(&mut self) -> Result<()> {
+        self.out
+            .flush()
+            .with_context(|| format!("Failed to flush {}", self.path.display()))
+    }
+
+    /// Flush and close the file.  Returns the path and number of readings.
+    pub fn finish(mut self) -> Result<(PathBuf, u64)> {
+        self.flush()?;
+        Ok((self.path, self.records))
+    }
+
+    pub fn info(&self) -> RecordingInfo {
+        RecordingInfo {
+            path: self.path.clone(),
+            elapsed: self.started.elapsed(),
+            bytes: self.bytes,
+        }
+    }
+}
+
+fn create(path: &Path) -> Result<BufWriter<File>> {
+    let file =
+        File::create(path).with_context(|| format!("Failed to create {}", path.display()))?;
+    Ok(BufWriter::new(file))
+}
+
+/// `1.4 MB`, `820 kB`, `96 B`.
+pub fn human_bytes(bytes: u64) -> String {
+    const KB: f64 = 1000.0;
+    let b = bytes as f64;
+    if b >= KB * KB {
+        format!("{:.1} MB", b / (KB * KB))
+    } else if b >= KB {
+        format!("{:.0} kB", b / KB)
+    } else {
+        format!("{} B", bytes)
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Unit tests
+// ---------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use indexmap::IndexMap;
+
+    #[test]
+    fn test_record_writes_json_lines() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut rec = SessionRecorder::start(dir.path(), Retention::default()).unwrap();
+        let data = SensorData::new(IndexMap::from([("accel_x".into(), 0.5)]));
+        rec.record("imu1", &data).unwrap();
+        rec.record("imu2", &data).unwrap();
+        let bytes = rec.info().bytes;
+        rec.flush().unwrap();
+        let path = rec.info().path;
+        assert_eq!(std::fs::metadata(&path).unwrap().len(), bytes, "flushed");
+        let (path, n) = rec.finish().unwrap();
+        assert_eq!(n, 2);
+
+        let text = std::fs::read_to_string(&path).unwrap();
+        assert_eq!(text.len() as u64, bytes);
+        let lines: Vec<serde_json::Value> = text
+            .lines()
+            .map(|l| serde_json::from_str(l).unwrap())
+            .collect();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0]["sensor"], "imu1");
+        assert_eq!(lines[1]["fields"]["accel_x"], 0.5);
+    }
+
+    #[test]
+    fn test_long_session_splits_into_pruned_segments() {
+        let dir = tempfile::tempdir().unwrap();
+        let retention = Retention {
+            max_bytes: Some(4_000_000),
+            max_age: None,
+        };
+        let mut rec = SessionRecorder::start(dir.path(), retention).unwrap();
+        let first = rec.info().path;
+        let data = SensorData::new(IndexMap::from([("x".into(), 0.5)]));
+        while rec.info().bytes < 6_000_000 {
+            rec.record("imu", &data).unwrap();
+        }
+        let (last, _) = rec.finish().unwrap();
+
+        // 1 MB segments under a 4 MB limit: the start has been pruned
+        assert!(!first.exists());
+        assert_ne!(last, first);
+        let total: u64 = std::fs::read_dir(dir.path())
+            .unwrap()
+            .map(|e| e.unwrap().metadata().unwrap().len())
+            .sum();
+        assert!(total <= 4_000_000, "{}", total);
+    }
+
+    #[test]
+    fn test_human_bytes() {
+        assert_eq!(human_bytes(96), "96 B");
+        assert_eq!(human_bytes(820_000), "820 kB");
+        assert_eq!(human_bytes(1_400_000), "1.4 MB");
+    }
+}
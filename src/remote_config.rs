@@ -0,0 +1,384 @@
+//! Live settings changes over MQTT (`[remote_config]`).
+//!
+//! A partial settings document published to `<base_topic>/CONFIG/SET`
+//! changes the driver `settings` of running sensors, so a headless logger
+//! can be tuned without a visit:
+//!
+//! ```json
+//! {"sensors": {"IMU": {"settings": {"gyro_filter": {"q": 0.01}}}}}
+//! ```
+//!
+//! The document is JSON, or TOML if it doesn't start with `{`.  Each sensor
+//! named is restarted with the document merged into its settings; the
+//! driver checks them as it starts, and if it fails the sensor keeps
+//! running as before.  The outcome is published to
+//! `<base_topic>/CONFIG/RESULT`.  With `persist_file` set, changes that
+//! were applied are merged into that file, which is reapplied at startup.
+
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::{Context, Result, anyhow, bail};
+use tokio::sync::oneshot;
+use tokio_util::sync::CancellationToken;
+use tracing::{info, warn};
+
+use crate::command_auth::CommandAuth;
+use crate::config::{RemoteConfig, SensorConfig};
+use crate::models::{SensorCommand, SharedState};
+use crate::mqtt_handler::MqttHandle;
+
+/// Longest wait for a sensor to restart with new settings; the MPU-6500
+/// calibrates while it starts.
+const APPLY_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// A document: JSON if it starts with `{`, TOML otherwise.
+pub fn parse_document(payload: &[u8]) -> Result<toml::Table> {
+    let text = std::str::from_utf8(payload).context("not UTF-8")?.trim();
+    if text.starts_with('{') {
+        serde_json::from_str(text).context("invalid JSON")
+    } else {
+        toml::from_str(text).context("invalid TOML")
+    }
+}
+
+/// The settings `doc` changes, per sensor.  Only the `settings` of
+/// configured sensors can be changed.
+pub fn sensor_patches(
+    doc: &toml::Table,
+    sensors: &[SensorConfig],
+) -> Result<Vec<(String, toml::Table)>> {
+    if let Some(key) = doc.keys().find(|k| *k != "sensors") {
+        bail!("'{}' can't be changed remotely, only sensor settings", key);
+    }
+    let Some(entries) = doc.get("sensors") else {
+        bail!("no `sensors` in the document");
+    };
+    let entries = entries
+        .as_table()
+        .ok_or_else(|| anyhow!("`sensors` must be a table keyed by sensor name"))?;
+
+    let mut patches = Vec::new();
+    for (name, entry) in entries {
+        if !sensors.iter().any(|s| s.name == *name) {
+            bail!("no sensor named '{}'", name);
+        }
+        let entry = entry
+            .as_table()
+            .ok_or_else(|| anyhow!("sensor '{}' must be a table", name))?;
+        if let Some(key) = entry.keys().find(|k| *k != "settings") {
+            bail!("'{}' of sensor '{}' can't be changed remotely", key, name);
+        }
+        let settings = entry
+            .get("settings")
+            .and_then(toml::Value::as_table)
+            .ok_or_else(|| anyhow!("sensor '{}' needs a `settings` table", name))?;
+        patches.push((name.clone(), settings.clone()));
+    }
+    Ok(patches)
+}
+
+/// Merge `patch` into `base`: tables key by key, anything else replaced.
+pub fn merge(base: &mut toml::Table, patch: &toml::Table) {
+    for (key, value) in patch {
+        match (base.get_mut(key), value) {
+            (Some(toml::Value::Table(b)), toml::Value::Table(p)) => merge(b, p),
+            _ => {
+                base.insert(key.clone(), value.clone());
+            }
+        }
+    }
+}
+
+/// `settings` with `patch` merged in.
+fn merged(settings: Option<&toml::Value>, patch: &toml::Table) -> toml::Value {
+    let mut table = settings
+        .and_then(toml::Value::as_table)
+        .cloned()
+        .unwrap_or_default();
+    merge(&mut table, patch);
+    toml::Value::Table(table)
+}
+
+/// Apply `doc` to the settings in `sensors`, as at startup for the
+/// persisted changes.
+pub fn apply(sensors: &mut [SensorConfig], doc: &toml::Table) -> Result<()> {
+    for (name, patch) in sensor_patches(doc, sensors)? {
+        if let Some(s) = sensors.iter_mut().find(|s| s.name == name) {
+            s.settings = Some(merged(s.settings.as_ref(), &patch));
+        }
+    }
+    Ok(())
+}
+
+/// Follow `<base_topic>/CONFIG/SET` documents that `auth` accepts until
+/// `cancel` fires.
+pub fn spawn_config_control(
+    state: SharedState,
+    mqtt: MqttHandle,
+    base_topic: &str,
+    auth: Arc<CommandAuth>,
+    cfg: RemoteConfig,
+    mut sensors: Vec<SensorConfig>,
+    cancel: CancellationToken,
+) {
+    let mut commands = mqtt.subscribe(format!("{}/CONFIG/SET", base_topic));
+    let result_topic = format!("{}/CONFIG/RESULT", base_topic);
+    tokio::spawn(async move {
+        loop {
+            let msg = tokio::select! {
+                _ = cancel.cancelled() => return,
+                Some(msg) = commands.recv() => msg,
+            };
+            let payload = match auth.check("config", &msg.topic, &msg.payload) {
+                Ok(p) => p,
+                Err(e) => {
+                    warn!("Rejected settings document: {}", e);
+                    continue;
+                }
+            };
+            let outcome = handle(&payload, &mut sensors, &state, &cfg).await;
+            let mut s = state.write().await;
+            match &outcome.error {
+                None => {
+                    let text = format!(
+                        "Settings of {} changed over MQTT",
+                        outcome.applied.join(", ")
+                    );
+                    info!("{}", text);
+                    s.status.info(text);
+                }
+                Some(e) => {
+                    warn!("Settings change over MQTT failed: {}", e);
+                    s.status.error(format!("Settings change failed: {}", e));
+                }
+            }
+            drop(s);
+            mqtt.publish(result_topic.clone(), outcome.to_json().to_string())
+                .await;
+        }
+    });
+}
+
+/// What became of one document.
+#[derive(Debug, Default, PartialEq)]
+struct Outcome {
+    /// Sensors now running with their new settings
+    applied: Vec<String>,
+    persisted: bool,
+    error: Option<String>,
+}
+
+impl Outcome {
+    fn to_json(&self) -> serde_json::Value {
+        serde_json::json!({
+            "ok": self.error.is_none(),
+            "applied": self.applied,
+            "persisted": self.persisted,
+            "error": self.error,
+        })
+    }
+}
+
+/// Restart each sensor the document names with its new settings, stopping
+/// at the first that fails, and persist the ones applied.
+async fn handle(
+    payload: &[u8],
+    sensors: &mut [SensorConfig],
+    state: &SharedState,
+    cfg: &RemoteConfig,
+) -> Outcome {
+    let patches = match parse_document(payload).and_then(|doc| sensor_patches(&doc, sensors)) {
+        Ok(p) => p,
+        Err(e) => {
+            return Outcome {
+                error: Some(format!("{:#}", e)),
+                ..Outcome::default()
+            };
+        }
+    };
+
+    let mut outcome = Outcome::default();
+    let mut applied = toml::Table::new();
+    for (name, patch) in patches {
+        let Some(sensor) = sensors.iter_mut().find(|s| s.name == name) else {
+            continue;
+        };
+        let settings = merged(sensor.settings.as_ref(), &patch);
+        if let Err(e) = reconfigure(state, &name, settings.clone()).await {
+            outcome.error = Some(format!("{}: {}", name, e));
+            break;
+        }
+        sensor.settings = Some(settings);
+        let mut entry = toml::Table::new();
+        entry.insert("settings".into(), toml::Value::Table(patch));
+        applied.insert(name.clone(), toml::Value::Table(entry));
+        outcome.applied.push(name);
+    }
+
+    if !cfg.persist_file.is_empty() && !applied.is_empty() {
+        let mut doc = toml::Table::new();
+        doc.insert("sensors".into(), toml::Value::Table(applied));
+        match persist(Path::new(&cfg.persist_file), &doc) {
+            Ok(()) => outcome.persisted = true,
+            Err(e) => {
+                let e = format!("not persisted: {:#}", e);
+                outcome.error = Some(match outcome.error.take() {
+                    Some(prev) => format!("{}; {}", prev, e),
+                    None => e,
+                });
+            }
+        }
+    }
+    outcome
+}
+
+/// Ask the sensor's task to restart with `settings` and wait for it.
+async fn reconfigure(state: &SharedState, name: &str, settings: toml::Value) -> Result<(), String> {
+    let (reply, answer) = oneshot::channel();
+    let sent = state
+        .read()
+        .await
+        .send_command(name, SensorCommand::Reconfigure { settings, reply });
+    if !sent {
+        return Err("not running".into());
+    }
+    match tokio::time::timeout(APPLY_TIMEOUT, answer).await {
+        Ok(Ok(result)) => result,
+        Ok(Err(_)) => Err("sensor task stopped".into()),
+        Err(_) => Err(format!("no answer within {}s", APPLY_TIMEOUT.as_secs())),
+    }
+}
+
+/// Merge `doc` into the file at `path`, creating it if needed.
+fn persist(path: &Path, doc: &toml::Table) -> Result<()> {
+    let mut kept: toml::Table = match std::fs::read_to_string(path) {
+        Ok(text) => {
+            toml::from_str(&text).with_context(|| format!("Failed to parse {}", path.display()))?
+        }
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => toml::Table::new(),
+        Err(e) => return Err(e).with_context(|| format!("Failed to read {}", path.display())),
+    };
+    merge(&mut kept, doc);
+    if let Some(dir) = path.parent().filter(|d| !d.as_os_str().is_empty()) {
+        std::fs::create_dir_all(dir)
+            .with_context(|| format!("Failed to create {}", dir.display()))?;
+    }
+    std::fs::write(path, toml::to_string_pretty(&kept)?)
+        .with_context(|| format!("Failed to write {}", path.display()))
+}
+
+// ---------------------------------------------------------------------------
+// Unit tests
+// ---------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::AppState;
+
+    fn sensors() -> Vec<SensorConfig> {
+        let doc: toml::Table = toml::from_str(
+            r#"
+            [[sensors]]
+            name = "IMU"
+            driver = "synthetic"
+            [sensors.connection]
+            type = "gpio"
+            pin = 17
+            [sensors.settings]
+            rate_hz = 50
+            [sensors.settings.gyro_filter]
+            q = 0.1
+            r = 1.0
+            "#,
+        )
+        .unwrap();
+        doc["sensors"].clone().try_into().unwrap()
+    }
+
+    #[test]
+    fn test_documents_merge_into_sensor_settings() {
+        let mut s = sensors();
+        let json = br#"{"sensors": {"IMU": {"settings": {"gyro_filter": {"q": 0.01}}}}}"#;
+        apply(&mut s, &parse_document(json).unwrap()).unwrap();
+        let toml = b"[sensors.IMU.settings]\nnoise = 0.5";
+        apply(&mut s, &parse_document(toml).unwrap()).unwrap();
+
+        let settings = s[0].settings.as_ref().unwrap();
+        assert_eq!(settings["rate_hz"].as_integer(), Some(50));
+        assert_eq!(settings["gyro_filter"]["q"].as_float(), Some(0.01));
+        assert_eq!(settings["gyro_filter"]["r"].as_float(), Some(1.0));
+        assert_eq!(settings["noise"].as_float(), Some(0.5));
+
+        for (doc, expected) in [
+            (r#"{"mqtt": {"host": "x"}}"#, "'mqtt' can't be changed"),
+            (
+                r#"{"sensors": {"GPS": {"settings": {}}}}"#,
+                "no sensor named 'GPS'",
+            ),
+            (
+                r#"{"sensors": {"IMU": {"driver": "x"}}}"#,
+                "'driver' of sensor 'IMU'",
+            ),
+            (
+                r#"{"sensors": {"IMU": {"settings": 1}}}"#,
+                "needs a `settings` table",
+            ),
+        ] {
+            let err = apply(&mut s, &parse_document(doc.as_bytes()).unwrap()).unwrap_err();
+            assert!(err.to_string().contains(expected), "{}: {}", doc, err);
+        }
+        assert!(parse_document(b"{not json").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_applied_changes_are_persisted() {
+        let dir = tempfile::tempdir().unwrap();
+        let cfg = RemoteConfig {
+            enabled: true,
+            persist_file: dir.path().join("remote.toml").to_string_lossy().into(),
+        };
+        let mut app = AppState::new(String::new(), false, 10);
+        let (tx, mut rx) = tokio::sync::mpsc::channel(4);
+        app.sensor_commands.insert("IMU".into(), tx);
+        let state = Arc::new(tokio::sync::RwLock::new(app));
+        // Stands in for the sensor task: accepts q < 1, rejects the rest
+        tokio::spawn(async move {
+            while let Some(SensorCommand::Reconfigure { settings, reply }) = rx.recv().await {
+                let q = settings["gyro_filter"]["q"].as_float().unwrap();
+                let _ = reply.send(if q < 1.0 {
+                    Ok(())
+                } else {
+                    Err("q too big".into())
+                });
+            }
+        });
+
+        let mut s = sensors();
+        let doc = br#"{"sensors": {"IMU": {"settings": {"gyro_filter": {"q": 0.01}}}}}"#;
+        let outcome = handle(doc, &mut s, &state, &cfg).await;
+        assert_eq!(
+            outcome,
+            Outcome {
+                applied: vec!["IMU".into()],
+                persisted: true,
+                error: None,
+            }
+        );
+
+        let doc = br#"{"sensors": {"IMU": {"settings": {"gyro_filter": {"q": 5.0}}}}}"#;
+        let outcome = handle(doc, &mut s, &state, &cfg).await;
+        assert_eq!(outcome.error.as_deref(), Some("IMU: q too big"));
+        assert!(outcome.applied.is_empty() && !outcome.persisted);
+
+        // The rejected change is neither kept nor persisted
+        let settings = s[0].settings.as_ref().unwrap();
+        assert_eq!(settings["gyro_filter"]["q"].as_float(), Some(0.01));
+        let mut fresh = sensors();
+        let kept = std::fs::read_to_string(&cfg.persist_file).unwrap();
+        apply(&mut fresh, &toml::from_str(&kept).unwrap()).unwrap();
+        assert_eq!(fresh[0].settings, s[0].settings);
+    }
+}
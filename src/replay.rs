@@ -0,0 +1,262 @@
+//! Publishing a recorded session to the broker (`sensors-to-mqtt replay`).
+//!
+//! Reads session files written with the `R` key and publishes every
+//! reading on the topics the daemon would have used (`INFO`, `FILTERED`,
+//! `DERIVED`, `DIAGNOSTICS`, `AVAILABILITY`), keeping the recorded spacing
+//! between readings, divided by the playback speed.  Dashboards and alert
+//! rules can then be worked on with a real drive while the car is parked.
+//!
+//! Readings are stamped with the time they are published, unless the
+//! recorded timestamps are asked for.  A gap longer than [`MAX_GAP`], such
+//! as between two sessions replayed one after the other, is skipped.
+
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader};
+use std::path::PathBuf;
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use indexmap::IndexMap;
+use serde::Deserialize;
+use tokio::time::Instant;
+use tokio_util::sync::CancellationToken;
+
+use crate::config::AppConfig;
+use crate::mqtt_handler::MqttHandle;
+use crate::sensors::SensorData;
+use crate::service::{
+    PayloadKeys, PublishState, SensorTopics, derived_keys, publish_availability,
+    publish_sensor_data,
+};
+
+/// Longest pause between readings that is played back.
+pub const MAX_GAP: Duration = Duration::from_secs(10);
+
+pub struct ReplayOptions {
+    /// 2.0 plays back twice as fast as recorded
+    pub speed: f64,
+    /// Publish the recorded timestamps instead of the time of publishing
+    pub original_timestamps: bool,
+    /// Start again from the first file after the last
+    pub repeat: bool,
+}
+
+/// One line of a session file.
+#[derive(Deserialize)]
+struct Record {
+    sensor: String,
+    #[serde(flatten)]
+    data: SensorData,
+}
+
+/// A sensor seen in the session.
+struct Replayed<'a> {
+    topics: SensorTopics,
+    /// From the sensor's entry in the config, if it has one
+    tags: IndexMap<String, String>,
+    /// Channels published, under their `channel_map` names
+    keys: PayloadKeys<'a>,
+    publish_state: PublishState,
+}
+
+/// Publish the readings in `files`, in order, until they are done or
+/// `cancel` fires, then mark every sensor seen offline.  Returns the
+/// number of readings published.
+pub async fn replay(
+    files: &[PathBuf],
+    cfg: &AppConfig,
+    mqtt: &MqttHandle,
+    opts: &ReplayOptions,
+    cancel: &CancellationToken,
+) -> Result<u64> {
+    let derived = derived_keys(
+        &cfg.derivatives,
+        &cfg.integrals,
+        &cfg.differences,
+        &cfg.anomalies,
+    );
+    let mut sensors: HashMap<String, Replayed> = HashMap::new();
+    let mut published = 0;
+
+    let result: Result<()> = async {
+        loop {
+            let mut pacer = Pacer::new(opts.speed);
+            let start = Instant::now();
+            for path in files {
+                let file = std::fs::File::open(path)
+                    .with_context(|| format!("Failed to open {}", path.display()))?;
+                for (n, line) in BufReader::new(file).lines().enumerate() {
+                    let line =
+                        line.with_context(|| format!("Failed to read {}", path.display()))?;
+                    if line.trim().is_empty() {
+                        continue;
+                    }
+                    let Record { sensor, mut data } =
+                        serde_json::from_str(&line).with_context(|| {
+                            format!("{}:{}: not a recorded reading", path.display(), n + 1)
+                        })?;
+
+                    let due = start + pacer.due(data.timestamp);
+                    tokio::select! {
+                        _ = cancel.cancelled() => return Ok(()),
+                        _ = tokio::time::sleep_until(due) => {}
+                    }
+                    if !opts.original_timestamps {
+                        data.timestamp = Utc::now();
+                    }
+                    let replayed = match sensors.get_mut(&sensor) {
+                        Some(r) => r,
+                        None => {
+                            let sensor_cfg = cfg.sensors.iter().find(|s| s.name == sensor);
+                            let group = sensor_cfg.and_then(|s| s.topic_group.as_deref());
+                            let topics = SensorTopics::new(&cfg.mqtt.base_topic, group, &sensor);
+                            let keys = PayloadKeys::new(
+                                &derived,
+                                &mqtt.filtered_channels,
+                                &mqtt.derived_channels,
+                            );
+                            publish_availability(Some(mqtt), &topics, true).await;
+                            sensors.entry(sensor.clone()).or_insert(Replayed {
+                                topics,
+                                tags: sensor_cfg.map(|s| s.tags.clone()).unwrap_or_default(),
+                                keys: match sensor_cfg {
+                                    Some(s) => keys.with_channel_map(&s.channel_map),
+                                    None => keys,
+                                },
+                                publish_state: PublishState::default(),
+                            })
+                        }
+                    };
+                    publish_sensor_data(
+                        mqtt,
+                        &replayed.topics,
+                        &sensor,
+                        &replayed.tags,
+                        &data,
+                        &replayed.keys,
+                        &mut replayed.publish_state,
+                    )
+                    .await;
+                    published += 1;
+                }
+            }
+            if !opts.repeat || published == 0 {
+                return Ok(());
+            }
+        }
+    }
+    .await;
+
+    for r in sensors.values() {
+        publish_availability(Some(mqtt), &r.topics, false).await;
+    }
+    result.map(|()| published)
+}
+
+/// When to publish each reading, from its recorded timestamp.
+struct Pacer {
+    speed: f64,
+    prev: Option<DateTime<Utc>>,
+    /// Recorded time played back so far
+    elapsed: Duration,
+}
+
+impl Pacer {
+    fn new(speed: f64) -> Self {
+        Self {
+            speed,
+            prev: None,
+            elapsed: Duration::ZERO,
+        }
+    }
+
+    /// Time after the start of playback to publish a reading recorded at
+    /// `t`.  Steps back in time and gaps over [`MAX_GAP`] add nothing.
+    fn due(&mut self, t: DateTime<Utc>) -> Duration {
+        if let Some(prev) = self.prev {
+            let step = (t - prev).to_std().unwrap_or_default();
+            if step <= MAX_GAP {
+                self.elapsed += step;
+            }
+        }
+        self.prev = Some(t);
+        self.elapsed.div_f64(self.speed)
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Unit tests
+// ---------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::MqttConfig;
+    use crate::recorder::SessionRecorder;
+    use crate::retention::Retention;
+
+    #[test]
+    fn test_pacing_keeps_spacing_but_skips_gaps() {
+        let t0 = DateTime::from_timestamp(1_700_000_000, 0).unwrap();
+        let ms = |n: i64| t0 + chrono::Duration::milliseconds(n);
+        let mut pacer = Pacer::new(2.0);
+        let due: Vec<u128> = [0, 100, 300, 60_300, 60_500, 60_400]
+            .into_iter()
+            .map(|n| pacer.due(ms(n)).as_millis())
+            .collect();
+        // Half speed-up; the minute's gap and the step back add nothing
+        assert_eq!(due, [0, 50, 150, 150, 250, 250]);
+    }
+
+    #[tokio::test]
+    async fn test_recorded_session_is_published_on_sensor_topics() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut rec = SessionRecorder::start(dir.path(), Retention::default()).unwrap();
+        let t0 = Utc::now();
+        for (i, sensor) in ["imu", "engine", "imu"].into_iter().enumerate() {
+            let mut data = SensorData::new(IndexMap::from([
+                ("accel_x".into(), 0.5),
+                ("g_force_x".into(), 0.4),
+            ]));
+            data.timestamp = t0 + chrono::Duration::milliseconds(i as i64 * 20);
+            rec.record(sensor, &data).unwrap();
+        }
+        let (path, _) = rec.finish().unwrap();
+
+        let mut cfg = AppConfig::default();
+        cfg.mqtt.base_topic = "/R".into();
+        let mqtt = MqttHandle::offline(&MqttConfig::default());
+        let opts = ReplayOptions {
+            speed: 1.0,
+            original_timestamps: true,
+            repeat: false,
+        };
+        let started = std::time::Instant::now();
+        let n = replay(&[path], &cfg, &mqtt, &opts, &CancellationToken::new())
+            .await
+            .unwrap();
+        assert_eq!(n, 3);
+        assert!(started.elapsed() >= Duration::from_millis(40), "paced");
+
+        let sent: Vec<_> = mqtt.take_queued();
+        let topics: Vec<&str> = sent.iter().map(|m| &*m.topic).collect();
+        assert_eq!(
+            topics[..4],
+            [
+                "/R/IMU/imu/AVAILABILITY",
+                "/R/IMU/imu/INFO",
+                "/R/IMU/imu/FILTERED",
+                "/R/IMU/imu/DERIVED"
+            ]
+        );
+        let filtered: serde_json::Value = serde_json::from_slice(&sent[2].payload).unwrap();
+        assert_eq!(filtered["accel_x"], 0.5);
+        assert_eq!(filtered["timestamp"], t0.to_rfc3339());
+        let offline = sent
+            .iter()
+            .filter(|m| m.topic.ends_with("AVAILABILITY") && m.payload == b"offline")
+            .count();
+        assert_eq!(offline, 2, "both sensors offline at the end");
+    }
+}
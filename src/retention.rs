@@ -0,0 +1,189 @@
+//! Retention for the files written to `ui.export_dir`: session recordings
+//! and CSV history exports.
+//!
+//! Only files named the way this program names them are considered, so an
+//! export directory shared with other data is safe.  Files older than
+//! `export_max_age_days` are deleted first, then the oldest remaining ones
+//! until the total is under `export_max_mb`.  With a size limit, recordings
+//! are also split into segments (see [`Retention::segment_bytes`]), so a
+//! long session is trimmed from its start like a ring buffer instead of
+//! outgrowing the SD card.
+
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use anyhow::{Context, Result};
+
+use crate::config::UiConfig;
+
+/// Smallest recording segment, so a tiny limit doesn't split every few
+/// readings.
+const MIN_SEGMENT_BYTES: u64 = 1_000_000;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Retention {
+    pub max_bytes: Option<u64>,
+    pub max_age: Option<Duration>,
+}
+
+impl Retention {
+    pub fn from_config(ui: &UiConfig) -> Self {
+        Self {
+            max_bytes: (ui.export_max_mb > 0).then(|| ui.export_max_mb * 1_000_000),
+            max_age: (ui.export_max_age_days > 0)
+                .then(|| Duration::from_secs(ui.export_max_age_days * 86_400)),
+        }
+    }
+
+    /// Size at which a recording moves on to a new file: a quarter of the
+    /// limit, so the last three quarters of it survive pruning.
+    pub fn segment_bytes(&self) -> Option<u64> {
+        self.max_bytes.map(|m| (m / 4).max(MIN_SEGMENT_BYTES))
+    }
+
+    /// Delete expired files and then the oldest ones over the size limit,
+    /// leaving `reserve` bytes of room for a file still being written.
+    /// `keep` (that file) is counted but never deleted.  Returns the files
+    /// removed.
+    pub fn prune(&self, dir: &Path, keep: Option<&Path>, reserve: u64) -> Result<Vec<PathBuf>> {
+        if *self == Self::default() {
+            return Ok(Vec::new());
+        }
+        let mut files = Vec::new();
+        let entries =
+            std::fs::read_dir(dir).with_context(|| format!("Failed to list {}", dir.display()))?;
+        for entry in entries.flatten() {
+            let name = entry.file_name();
+            if !name.to_str().is_some_and(is_ours) {
+                continue;
+            }
+            let Ok(meta) = entry.metadata() else {
+                continue;
+            };
+            if meta.is_file() {
+                let modified = meta.modified().unwrap_or(SystemTime::UNIX_EPOCH);
+                files.push((modified, meta.len(), entry.path()));
+            }
+        }
+        files.sort();
+
+        let now = SystemTime::now();
+        let mut total: u64 = files.iter().map(|(_, len, _)| len).sum();
+        let mut removed = Vec::new();
+        for (modified, len, path) in files {
+            if keep == Some(path.as_path()) {
+                continue;
+            }
+            let expired = self
+                .max_age
+                .is_some_and(|age| now.duration_since(modified).unwrap_or_default() > age);
+            let over = self.max_bytes.is_some_and(|max| total + reserve > max);
+            if !expired && !over {
+                continue;
+            }
+            std::fs::remove_file(&path)
+                .with_context(|| format!("Failed to delete {}", path.display()))?;
+            total -= len;
+            removed.push(path);
+        }
+        Ok(removed)
+    }
+}
+
+/// `session_….jsonl` recordings and `<sensor>_YYYYmmdd_HHMMSS.csv` exports.
+fn is_ours(name: &str) -> bool {
+    if name.starts_with("session_") && name.ends_with(".jsonl") {
+        return true;
+    }
+    let Some(stem) = name.strip_suffix(".csv") else {
+        return false;
+    };
+    let b = stem.as_bytes();
+    // `_` + 8 digits + `_` + 6 digits, after a non-empty sensor name
+    b.len() > 16 && {
+        let stamp = &b[b.len() - 16..];
+        stamp[0] == b'_'
+            && stamp[9] == b'_'
+            && stamp[1..9]
+                .iter()
+                .chain(&stamp[10..])
+                .all(u8::is_ascii_digit)
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Unit tests
+// ---------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_only_own_files_match() {
+        assert!(is_ours("session_20250101_120000.jsonl"));
+        assert!(is_ours("session_20250101_120000_2.jsonl"));
+        assert!(is_ours("Front_IMU_20250101_120000.csv"));
+        assert!(!is_ours("_20250101_120000.csv"));
+        assert!(!is_ours("notes.csv"));
+        assert!(!is_ours("laps_2025.csv"));
+        assert!(!is_ours("session.txt"));
+    }
+
+    #[test]
+    fn test_prune_removes_oldest_over_limit() {
+        let dir = tempfile::tempdir().unwrap();
+        let write = |name: &str, age_secs: u64| {
+            let path = dir.path().join(name);
+            std::fs::write(&path, vec![b'x'; 400]).unwrap();
+            let t = SystemTime::now() - Duration::from_secs(age_secs);
+            std::fs::File::options()
+                .write(true)
+                .open(&path)
+                .unwrap()
+                .set_modified(t)
+                .unwrap();
+            path
+        };
+        let oldest = write("session_20250101_100000.jsonl", 300);
+        let keep = write("session_20250101_090000.jsonl", 200);
+        let export = write("IMU_20250101_110000.csv", 100);
+        let newest = write("session_20250101_120000.jsonl", 0);
+        let other = write("notes.csv", 400);
+
+        let retention = Retention {
+            max_bytes: Some(900),
+            max_age: None,
+        };
+        let removed = retention.prune(dir.path(), Some(&keep), 0).unwrap();
+        // 1600 bytes of ours: dropping the oldest deletable two leaves 800
+        assert_eq!(removed, vec![oldest, export]);
+        assert!(keep.exists() && newest.exists() && other.exists());
+
+        let by_age = Retention {
+            max_bytes: None,
+            max_age: Some(Duration::from_secs(150)),
+        };
+        assert_eq!(by_age.prune(dir.path(), None, 0).unwrap(), vec![keep]);
+        assert!(newest.exists() && other.exists());
+
+        // Room for a recording to grow
+        assert_eq!(
+            retention.prune(dir.path(), None, 600).unwrap(),
+            vec![newest.clone()]
+        );
+    }
+
+    #[test]
+    fn test_segment_bytes() {
+        let ui = UiConfig {
+            export_max_mb: 100,
+            ..Default::default()
+        };
+        assert_eq!(
+            Retention::from_config(&ui).segment_bytes(),
+            Some(25_000_000)
+        );
+        assert_eq!(Retention::default().segment_bytes(), None);
+    }
+}
@@ -0,0 +1,424 @@
+//! Startup self-check (`[self_check]`).
+//!
+//! Shortly after start, once every enabled sensor and the broker have come
+//! up or `timeout_secs` has passed, the daemon checks:
+//!
+//! | check  | passes when                                                  |
+//! |--------|--------------------------------------------------------------|
+//! | config | the configuration loaded and validated                       |
+//! | bus    | each local I2C device and serial port in use can be opened   |
+//! | sensor | the driver initialised (chip id / WHO_AM_I) and is reading   |
+//! | mqtt   | the broker accepted the connection                           |
+//! | clock  | the time daemon reports the wall clock synchronised          |
+//!
+//! The report is published, retained, to `<base_topic>/SELFTEST` and
+//! logged one line per check.  A failed check is critical when the daemon
+//! can't do its job without it: a `required` sensor, a bus one uses, or
+//! the broker.  In daemon mode a critical failure stops the process with an
+//! error, so the service manager notices at once rather than a dashboard
+//! staying empty.  The clock is never critical.
+
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use tokio_util::sync::CancellationToken;
+use tracing::{error, info, warn};
+
+use crate::config::{AppConfig, ConnectionConfig, SensorConfig, SyncCheck};
+use crate::models::{AppState, SharedState};
+use crate::mqtt_handler::MqttHandle;
+
+/// How often the sensors and broker are looked at while waiting.
+const POLL: Duration = Duration::from_millis(100);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CheckStatus {
+    Pass,
+    Warn,
+    Fail,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Check {
+    /// `config`, `bus`, `sensor`, `mqtt` or `clock`
+    pub check: &'static str,
+    /// What was checked: a sensor name, a device path, the broker address
+    pub name: String,
+    pub status: CheckStatus,
+    /// Whether failing it fails the self-check
+    pub critical: bool,
+    pub detail: String,
+}
+
+impl Check {
+    fn new(check: &'static str, name: impl Into<String>, status: CheckStatus) -> Self {
+        Self {
+            check,
+            name: name.into(),
+            status,
+            critical: false,
+            detail: String::new(),
+        }
+    }
+
+    fn critical(mut self, critical: bool) -> Self {
+        self.critical = critical;
+        self
+    }
+
+    fn detail(mut self, detail: impl Into<String>) -> Self {
+        self.detail = detail.into();
+        self
+    }
+
+    fn failed_critically(&self) -> bool {
+        self.critical && self.status == CheckStatus::Fail
+    }
+}
+
+impl std::fmt::Display for Check {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let status = match self.status {
+            CheckStatus::Pass => "ok",
+            CheckStatus::Warn => "warn",
+            CheckStatus::Fail if self.critical => "FAIL",
+            CheckStatus::Fail => "fail",
+        };
+        write!(f, "{:<4}  {:<6} {}", status, self.check, self.name)?;
+        if !self.detail.is_empty() {
+            write!(f, ": {}", self.detail)?;
+        }
+        Ok(())
+    }
+}
+
+/// The `SELFTEST` payload.
+#[derive(Debug, Clone, Serialize)]
+pub struct SelfCheckReport {
+    pub timestamp: DateTime<Utc>,
+    pub version: &'static str,
+    /// No critical check failed
+    pub passed: bool,
+    pub checks: Vec<Check>,
+}
+
+impl SelfCheckReport {
+    fn new(checks: Vec<Check>) -> Self {
+        Self {
+            timestamp: Utc::now(),
+            version: env!("CARGO_PKG_VERSION"),
+            passed: !checks.iter().any(Check::failed_critically),
+            checks,
+        }
+    }
+
+    /// E.g. `Self-check passed: 4 ok, 1 warnings, 0 failed`.
+    pub fn verdict(&self) -> String {
+        let count = |s| self.checks.iter().filter(|c| c.status == s).count();
+        format!(
+            "Self-check {}: {} ok, {} warnings, {} failed",
+            if self.passed { "passed" } else { "FAILED" },
+            count(CheckStatus::Pass),
+            count(CheckStatus::Warn),
+            count(CheckStatus::Fail),
+        )
+    }
+
+    /// The verdict, then one line per check.
+    pub fn summary(&self) -> String {
+        let mut out = self.verdict();
+        for c in &self.checks {
+            out.push_str(&format!("\n  {}", c));
+        }
+        out
+    }
+
+    /// The critical checks that failed, for the exit error.
+    fn failures(&self) -> String {
+        let failed: Vec<String> = self
+            .checks
+            .iter()
+            .filter(|c| c.failed_critically())
+            .map(|c| format!("{} {}", c.check, c.name))
+            .collect();
+        failed.join(", ")
+    }
+}
+
+/// Run the self-check in the background, publish and log its report, and
+/// with `exit_on_failure` stop the daemon if a critical check fails.
+pub fn spawn_self_check(
+    cfg: &AppConfig,
+    state: SharedState,
+    mqtt: Option<MqttHandle>,
+    exit_on_failure: bool,
+    cancel: CancellationToken,
+) {
+    let cfg = cfg.clone();
+    tokio::spawn(async move {
+        let Some(report) = run(&cfg, &state, mqtt.as_ref(), &cancel).await else {
+            return;
+        };
+        if report.passed {
+            info!("{}", report.verdict());
+        } else {
+            error!("{}", report.verdict());
+        }
+        for c in &report.checks {
+            match c.status {
+                CheckStatus::Pass => info!("  {}", c),
+                _ if c.failed_critically() => error!("  {}", c),
+                _ => warn!("  {}", c),
+            }
+        }
+        if let Some(h) = &mqtt {
+            match serde_json::to_string(&report) {
+                Ok(json) => {
+                    h.publish_retained(format!("{}/SELFTEST", cfg.mqtt.base_topic), json)
+                        .await
+                }
+                Err(e) => warn!("Failed to serialise the self-check report: {}", e),
+            }
+        }
+
+        let mut s = state.write().await;
+        if report.passed {
+            s.status.info("Self-check passed");
+        } else if exit_on_failure {
+            s.fatal_error = Some(format!("self-check failed: {}", report.failures()));
+            drop(s);
+            cancel.cancel();
+        } else {
+            s.status
+                .error(format!("Self-check failed: {}", report.failures()));
+        }
+    });
+}
+
+/// Wait for the sensors and broker to settle, up to `timeout_secs`, then
+/// check everything.  `None` if cancelled first.
+pub async fn run(
+    cfg: &AppConfig,
+    state: &SharedState,
+    mqtt: Option<&MqttHandle>,
+    cancel: &CancellationToken,
+) -> Option<SelfCheckReport> {
+    let mut checks = vec![check_config(cfg)];
+    checks.extend(check_buses(&cfg.sensors));
+
+    let deadline = tokio::time::Instant::now() + Duration::from_secs(cfg.self_check.timeout_secs);
+    loop {
+        let broker_up = match mqtt {
+            Some(h) => h.is_connected().await,
+            None => true,
+        };
+        if broker_up && sensors_settled(&*state.read().await)
+            || tokio::time::Instant::now() >= deadline
+        {
+            break;
+        }
+        tokio::select! {
+            _ = cancel.cancelled() => return None,
+            _ = tokio::time::sleep(POLL) => {}
+        }
+    }
+
+    let s = state.read().await;
+    checks.extend(check_sensors(&cfg.sensors, &s));
+    if let Some(h) = mqtt {
+        let status = h.status.read().unwrap().clone();
+        let check = if status.is_connected() {
+            Check::new("mqtt", cfg.mqtt.address(), CheckStatus::Pass)
+        } else {
+            Check::new("mqtt", cfg.mqtt.address(), CheckStatus::Fail).detail(format!(
+                "not connected after {} s ({})",
+                cfg.self_check.timeout_secs,
+                status.label()
+            ))
+        };
+        checks.push(check.critical(true));
+    }
+    if cfg.clock.sync_check != SyncCheck::Off {
+        checks.push(match s.clock_synced {
+            Some(true) => Check::new("clock", "wall clock", CheckStatus::Pass),
+            Some(false) => Check::new("clock", "wall clock", CheckStatus::Warn)
+                .detail("not synchronised; readings are flagged clock_unsynced"),
+            None => {
+                Check::new("clock", "wall clock", CheckStatus::Warn).detail("sync status unknown")
+            }
+        });
+    }
+    Some(SelfCheckReport::new(checks))
+}
+
+fn check_config(cfg: &AppConfig) -> Check {
+    let enabled = cfg.sensors.iter().filter(|s| s.enabled).count();
+    if enabled == 0 {
+        return Check::new("config", "settings", CheckStatus::Warn).detail("no sensors enabled");
+    }
+    Check::new("config", "settings", CheckStatus::Pass)
+        .critical(true)
+        .detail(format!(
+            "{} of {} sensors enabled",
+            enabled,
+            cfg.sensors.len()
+        ))
+}
+
+/// Open each local I2C device and serial port once.  TCP bridges and GPIO
+/// are left to the sensor check, which connects the same way the driver
+/// does.
+fn check_buses(sensors: &[SensorConfig]) -> Vec<Check> {
+    let mut checks: Vec<Check> = Vec::new();
+    for cfg in sensors.iter().filter(|s| s.enabled) {
+        let device = match &cfg.connection {
+            ConnectionConfig::I2c(c) => &c.device,
+            ConnectionConfig::Serial(c) => &c.port,
+            _ => continue,
+        };
+        if let Some(check) = checks.iter_mut().find(|c| c.name == *device) {
+            check.critical |= cfg.required;
+            continue;
+        }
+        let opened = std::fs::OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open(device);
+        checks.push(
+            match opened {
+                Ok(_) => Check::new("bus", device, CheckStatus::Pass),
+                Err(e) => Check::new("bus", device, CheckStatus::Fail).detail(e.to_string()),
+            }
+            .critical(cfg.required),
+        );
+    }
+    checks
+}
+
+/// Every enabled sensor is reading, apart from those switched off with
+/// their group.
+fn sensors_settled(s: &AppState) -> bool {
+    s.sensor_statuses
+        .values()
+        .filter(|st| st.enabled && !group_off(s, st.group.as_deref()))
+        .all(|st| st.connected)
+}
+
+fn group_off(s: &AppState, group: Option<&str>) -> bool {
+    group.is_some_and(|g| s.groups.get(g) == Some(&false))
+}
+
+fn check_sensors(sensors: &[SensorConfig], s: &AppState) -> Vec<Check> {
+    sensors
+        .iter()
+        .filter(|cfg| cfg.enabled)
+        .map(|cfg| {
+            let st = s.sensor_statuses.get(&cfg.name);
+            let check = |status| Check::new("sensor", &cfg.name, status);
+            if group_off(s, cfg.group.as_deref()) {
+                return check(CheckStatus::Warn).detail("group switched off");
+            }
+            match st {
+                Some(st) if st.connected => check(CheckStatus::Pass).detail(&cfg.driver),
+                Some(st) => check(CheckStatus::Fail).detail(
+                    st.last_error
+                        .clone()
+                        .unwrap_or_else(|| "not reading yet".to_string()),
+                ),
+                None => check(CheckStatus::Fail).detail("not started"),
+            }
+            .critical(cfg.required)
+        })
+        .collect()
+}
+
+// ---------------------------------------------------------------------------
+// Unit tests
+// ---------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::MqttConfig;
+    use crate::service::register_sensors;
+    use std::sync::Arc;
+    use tokio::sync::RwLock;
+
+    fn sensor(toml: &str) -> SensorConfig {
+        toml::from_str(toml).unwrap()
+    }
+
+    fn i2c(name: &str, device: &str, required: bool) -> SensorConfig {
+        sensor(&format!(
+            "name = \"{}\"\ndriver = \"bme280\"\nrequired = {}\n\
+             [connection]\ntype = \"i2c\"\ndevice = \"{}\"\naddress = 0x76",
+            name, required, device
+        ))
+    }
+
+    #[test]
+    fn test_bus_check_opens_each_device_once() {
+        let dir = tempfile::tempdir().unwrap();
+        let present = dir.path().join("i2c-1");
+        std::fs::write(&present, b"").unwrap();
+        let present = present.to_string_lossy().into_owned();
+        let missing = dir.path().join("i2c-9").to_string_lossy().into_owned();
+
+        let checks = check_buses(&[
+            i2c("a", &present, false),
+            i2c("b", &present, false),
+            i2c("c", &missing, false),
+            i2c("d", &missing, true),
+        ]);
+        assert_eq!(checks.len(), 2);
+        assert_eq!(checks[0].status, CheckStatus::Pass);
+        assert_eq!(checks[1].status, CheckStatus::Fail);
+        assert!(checks[1].critical, "a required sensor is on it");
+        assert!(!checks[0].critical);
+    }
+
+    #[tokio::test]
+    async fn test_report_fails_on_required_sensors_and_broker_only() {
+        let mut cfg = AppConfig::default();
+        cfg.self_check.timeout_secs = 0;
+        cfg.clock.sync_check = SyncCheck::Off;
+        cfg.sensors = vec![
+            i2c("imu", "/nonexistent/i2c-1", true),
+            i2c("cabin", "/nonexistent/i2c-1", false),
+        ];
+        let state: SharedState = Arc::new(RwLock::new(AppState::new("x".into(), false, 10)));
+        {
+            let mut s = state.write().await;
+            register_sensors(&mut s, &cfg.sensors, 1);
+            let imu = s.sensor_statuses.get_mut("imu").unwrap();
+            imu.connected = true;
+            let cabin = s.sensor_statuses.get_mut("cabin").unwrap();
+            cabin.last_error = Some("WHO_AM_I 0x00, expected 0x60".into());
+        }
+
+        let report = run(&cfg, &state, None, &CancellationToken::new())
+            .await
+            .unwrap();
+        // The bus fails critically, being the required sensor's
+        assert!(!report.passed);
+        assert_eq!(report.failures(), "bus /nonexistent/i2c-1");
+        let cabin = report.checks.iter().find(|c| c.name == "cabin").unwrap();
+        assert_eq!(cabin.status, CheckStatus::Fail);
+        assert!(!cabin.critical);
+        assert!(report.summary().contains("fail  sensor cabin: WHO_AM_I"));
+
+        // An unreachable broker is critical
+        cfg.sensors.truncate(0);
+        let mqtt = MqttHandle::offline(&MqttConfig::default());
+        let report = run(&cfg, &state, Some(&mqtt), &CancellationToken::new())
+            .await
+            .unwrap();
+        assert_eq!(report.failures(), format!("mqtt {}", cfg.mqtt.address()));
+        let json = serde_json::to_value(&report).unwrap();
+        assert_eq!(json["passed"], false);
+        assert_eq!(json["checks"][0]["check"], "config");
+        assert_eq!(json["checks"][1]["status"], "fail");
+    }
+}
@@ -19,8 +19,7 @@
 //! in software on the client side.
 
 use anyhow::{Context, Result};
-use chrono::Utc;
-use std::collections::HashMap;
+use indexmap::IndexMap;
 use std::time::{Duration, Instant};
 
 use crate::config::{ConnectionConfig, SensorConfig};
@@ -36,18 +35,21 @@ static FIELDS: &[FieldDescriptor] = &[
         key: "state",
         label: "State",
         viz: VizType::Value,
+        range: Some((0.0, 1.0)),
         group: Some("GPIO"),
     },
     FieldDescriptor {
         key: "press_count",
         label: "Presses",
         viz: VizType::Numeric { unit: "count" },
+        range: None,
         group: None,
     },
     FieldDescriptor {
         key: "press_duration_ms",
         label: "Duration",
         viz: VizType::Numeric { unit: "ms" },
+        range: None,
         group: None,
     },
 ];
@@ -257,14 +259,11 @@ impl Sensor for GpioButton {
 
         let duration_ms = self.state_entered.elapsed().as_millis() as f64;
 
-        let mut fields = HashMap::new();
+        let mut fields = IndexMap::new();
         fields.insert("state".into(), self.stable_state as u8 as f64);
         fields.insert("press_count".into(), self.press_count as f64);
         fields.insert("press_duration_ms".into(), duration_ms);
-        Ok(SensorData {
-            timestamp: Utc::now(),
-            fields,
-        })
+        Ok(SensorData::new(fields))
     }
 
     fn name(&self) -> &str {
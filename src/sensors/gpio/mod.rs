@@ -1,3 +1,4 @@
 //! GPIO sensor drivers (local sysfs on Linux, or TCP bridge on all platforms).
 
+#[cfg(feature = "gpio-button")]
 pub mod button;
@@ -29,9 +29,8 @@
 //! ```
 
 use anyhow::Result;
-use chrono::Utc;
+use indexmap::IndexMap;
 use serde::Deserialize;
-use std::collections::HashMap;
 
 use crate::config::SensorConfig;
 use crate::sensors::{FieldDescriptor, Sensor, SensorData, VizType};
@@ -151,7 +150,7 @@ impl Ads1115 {
         let dr_bits = dr_for(settings.sample_rate);
         let (device, address) = open_i2c(cfg, 0x48)?;
 
-        Ok(Self {
+        let mut sensor = Self {
             name: cfg.name.clone(),
             device,
             address,
@@ -161,7 +160,9 @@ impl Ads1115 {
             pga_bits,
             dr_bits,
             field_descs: Vec::new(),
-        })
+        };
+        sensor.init()?;
+        Ok(sensor)
     }
 
     /// Read one channel in single-shot mode; returns raw i16 code.
@@ -207,6 +208,7 @@ impl Sensor for Ads1115 {
                 key,
                 label: label_raw,
                 viz: VizType::Numeric { unit: "V" },
+                range: Some((0.0, self.fsr_v)),
                 group: if ch == 0 { Some("ADS1115") } else { None },
             });
 
@@ -215,10 +217,13 @@ impl Sensor for Ads1115 {
                 let key_m = leak(format!("ch{}_mapped", ch));
                 let label_m = leak(cc.label.clone());
                 let unit_m: &'static str = leak(cc.unit.clone());
+                let lo = (0.0 - cc.offset) * cc.scale;
+                let hi = (self.fsr_v - cc.offset) * cc.scale;
                 descs.push(FieldDescriptor {
                     key: key_m,
                     label: label_m,
                     viz: VizType::Numeric { unit: unit_m },
+                    range: Some((lo.min(hi), lo.max(hi))),
                     group: None,
                 });
             }
@@ -228,25 +233,22 @@ impl Sensor for Ads1115 {
     }
 
     fn read(&mut self) -> Result<SensorData> {
-        let mut fields = HashMap::new();
+        let mut fields = IndexMap::new();
         let fsr = self.fsr_v;
 
         for ch in 0..4u8 {
             let raw = self.read_channel(ch)?;
             // Full-scale ±FSR maps to ±32767
             let volts = raw as f64 * fsr / 32767.0;
-            fields.insert(format!("ch{}_v", ch), volts);
+            fields.insert(format!("ch{}_v", ch).into(), volts);
 
             if let Some(cc) = self.settings.channels.iter().find(|c| c.index == ch) {
                 let mapped = (volts - cc.offset) * cc.scale;
-                fields.insert(format!("ch{}_mapped", ch), mapped);
+                fields.insert(format!("ch{}_mapped", ch).into(), mapped);
             }
         }
 
-        Ok(SensorData {
-            timestamp: Utc::now(),
-            fields,
-        })
+        Ok(SensorData::new(fields))
     }
 
     fn name(&self) -> &str {
@@ -5,8 +5,7 @@
 //! Reports illuminance in lux and derived categories.
 
 use anyhow::Result;
-use chrono::Utc;
-use std::collections::HashMap;
+use indexmap::IndexMap;
 
 use crate::config::SensorConfig;
 use crate::sensors::{FieldDescriptor, Sensor, SensorData, VizType};
@@ -25,12 +24,14 @@ static FIELDS: &[FieldDescriptor] = &[
         key: "lux",
         label: "Illuminance",
         viz: VizType::Numeric { unit: "lux" },
+        range: Some((0.0, 65535.0)),
         group: Some("BH1750"),
     },
     FieldDescriptor {
         key: "lux_category",
         label: "Category",
         viz: VizType::Value,
+        range: None,
         group: None,
     },
 ];
@@ -84,13 +85,10 @@ impl Sensor for Bh1750 {
         // BH1750 datasheet: lux = raw / 1.2
         let lux = raw_val / 1.2;
 
-        let mut fields = HashMap::new();
+        let mut fields = IndexMap::new();
         fields.insert("lux".into(), lux);
         fields.insert("lux_category".into(), lux_to_category(lux));
-        Ok(SensorData {
-            timestamp: Utc::now(),
-            fields,
-        })
+        Ok(SensorData::new(fields))
     }
 
     fn name(&self) -> &str {
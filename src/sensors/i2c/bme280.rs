@@ -6,14 +6,15 @@
 //! Derived fields: altitude_m (barometric, std atmosphere).
 
 use anyhow::Result;
-use chrono::Utc;
+use indexmap::IndexMap;
 use serde::Deserialize;
-use std::collections::HashMap;
 
 use crate::config::SensorConfig;
 use crate::sensors::{FieldDescriptor, Sensor, SensorData, VizType};
 use crate::transport::{open_i2c, I2cBus};
 
+use super::verify_chip_id;
+
 // ---------------------------------------------------------------------------
 // Register map
 // ---------------------------------------------------------------------------
@@ -162,24 +163,28 @@ static FIELDS: &[FieldDescriptor] = &[
         key: "temperature",
         label: "Temperature",
         viz: VizType::Numeric { unit: "°C" },
+        range: None,
         group: Some("BME280"),
     },
     FieldDescriptor {
         key: "pressure_hpa",
         label: "Pressure",
         viz: VizType::Numeric { unit: "hPa" },
+        range: None,
         group: None,
     },
     FieldDescriptor {
         key: "humidity_pct",
         label: "Humidity",
         viz: VizType::Numeric { unit: "%" },
+        range: Some((0.0, 100.0)),
         group: None,
     },
     FieldDescriptor {
         key: "altitude_m",
         label: "Altitude",
         viz: VizType::Numeric { unit: "m" },
+        range: None,
         group: None,
     },
 ];
@@ -233,10 +238,13 @@ impl Bme280 {
 
 impl Sensor for Bme280 {
     fn init(&mut self) -> Result<()> {
-        let id = self.read_reg(REG_ID)?;
-        if id != CHIP_ID {
-            anyhow::bail!("BME280: unexpected chip id {:#04x}", id);
-        }
+        verify_chip_id(
+            self.device.as_mut(),
+            self.address,
+            REG_ID,
+            "BME280",
+            &[(CHIP_ID, "BME280")],
+        )?;
         self.write_reg(REG_RESET, 0xB6)?;
         std::thread::sleep(std::time::Duration::from_millis(10));
 
@@ -275,15 +283,12 @@ impl Sensor for Bme280 {
         let altitude =
             44330.0 * (1.0 - (pressure / self.settings.sea_level_pressure_hpa).powf(1.0 / 5.255));
 
-        let mut fields = HashMap::new();
+        let mut fields = IndexMap::new();
         fields.insert("temperature".into(), temperature);
         fields.insert("pressure_hpa".into(), pressure);
         fields.insert("humidity_pct".into(), humidity);
         fields.insert("altitude_m".into(), altitude);
-        Ok(SensorData {
-            timestamp: Utc::now(),
-            fields,
-        })
+        Ok(SensorData::new(fields))
     }
 
     fn name(&self) -> &str {
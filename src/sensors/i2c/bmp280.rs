@@ -5,14 +5,15 @@
 //! Derived fields: altitude (metres above sea level, std. atmosphere).
 
 use anyhow::Result;
-use chrono::Utc;
+use indexmap::IndexMap;
 use serde::Deserialize;
-use std::collections::HashMap;
 
 use crate::config::SensorConfig;
 use crate::sensors::{FieldDescriptor, Sensor, SensorData, VizType};
 use crate::transport::{open_i2c, I2cBus};
 
+use super::verify_chip_id;
+
 // ---------------------------------------------------------------------------
 // Register map (BMP280 datasheet §5.3)
 // ---------------------------------------------------------------------------
@@ -130,18 +131,21 @@ static FIELDS: &[FieldDescriptor] = &[
         key: "temperature",
         label: "Temperature",
         viz: VizType::Numeric { unit: "°C" },
+        range: None,
         group: Some("BMP280"),
     },
     FieldDescriptor {
         key: "pressure_hpa",
         label: "Pressure",
         viz: VizType::Numeric { unit: "hPa" },
+        range: None,
         group: None,
     },
     FieldDescriptor {
         key: "altitude_m",
         label: "Altitude",
         viz: VizType::Numeric { unit: "m" },
+        range: None,
         group: None,
     },
 ];
@@ -174,12 +178,6 @@ impl Bmp280 {
         })
     }
 
-    fn read_reg(&mut self, reg: u8) -> Result<u8> {
-        let mut buf = [0u8; 1];
-        self.device.write_read(self.address, &[reg], &mut buf)?;
-        Ok(buf[0])
-    }
-
     fn read_regs(&mut self, reg: u8, out: &mut [u8]) -> Result<()> {
         self.device.write_read(self.address, &[reg], out)?;
         Ok(())
@@ -193,14 +191,13 @@ impl Bmp280 {
 
 impl Sensor for Bmp280 {
     fn init(&mut self) -> Result<()> {
-        let id = self.read_reg(REG_ID)?;
-        if id != CHIP_ID {
-            anyhow::bail!(
-                "BMP280: unexpected chip id {:#04x} (expected {:#04x})",
-                id,
-                CHIP_ID
-            );
-        }
+        verify_chip_id(
+            self.device.as_mut(),
+            self.address,
+            REG_ID,
+            "BMP280",
+            &[(CHIP_ID, "BMP280")],
+        )?;
         // Soft-reset
         self.write_reg(REG_RESET, 0xB6)?;
         std::thread::sleep(std::time::Duration::from_millis(10));
@@ -235,14 +232,11 @@ impl Sensor for Bmp280 {
         let altitude =
             44330.0 * (1.0 - (pressure / self.settings.sea_level_pressure_hpa).powf(1.0 / 5.255));
 
-        let mut fields = HashMap::new();
+        let mut fields = IndexMap::new();
         fields.insert("temperature".into(), temperature);
         fields.insert("pressure_hpa".into(), pressure);
         fields.insert("altitude_m".into(), altitude);
-        Ok(SensorData {
-            timestamp: Utc::now(),
-            fields,
-        })
+        Ok(SensorData::new(fields))
     }
 
     fn name(&self) -> &str {
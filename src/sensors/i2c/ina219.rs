@@ -9,9 +9,8 @@
 //! are provided.
 
 use anyhow::Result;
-use chrono::Utc;
+use indexmap::IndexMap;
 use serde::Deserialize;
-use std::collections::HashMap;
 
 use crate::config::SensorConfig;
 use crate::sensors::{FieldDescriptor, Sensor, SensorData, VizType};
@@ -73,30 +72,35 @@ static FIELDS: &[FieldDescriptor] = &[
         key: "bus_voltage_v",
         label: "Bus Voltage",
         viz: VizType::Numeric { unit: "V" },
+        range: None,
         group: Some("INA219"),
     },
     FieldDescriptor {
         key: "shunt_mv",
         label: "Shunt Voltage",
         viz: VizType::Numeric { unit: "mV" },
+        range: None,
         group: None,
     },
     FieldDescriptor {
         key: "current_a",
         label: "Current",
         viz: VizType::Numeric { unit: "A" },
+        range: None,
         group: None,
     },
     FieldDescriptor {
         key: "power_w",
         label: "Power",
         viz: VizType::Numeric { unit: "W" },
+        range: None,
         group: None,
     },
     FieldDescriptor {
         key: "soc_pct",
         label: "State of Charge",
         viz: VizType::Numeric { unit: "%" },
+        range: Some((0.0, 100.0)),
         group: None,
     },
 ];
@@ -185,16 +189,13 @@ impl Sensor for Ina219 {
             _ => -1.0, // -1 = not configured
         };
 
-        let mut fields = HashMap::new();
+        let mut fields = IndexMap::new();
         fields.insert("bus_voltage_v".into(), bus_voltage_v);
         fields.insert("shunt_mv".into(), shunt_mv);
         fields.insert("current_a".into(), current_a);
         fields.insert("power_w".into(), power_w);
         fields.insert("soc_pct".into(), soc_pct);
-        Ok(SensorData {
-            timestamp: Utc::now(),
-            fields,
-        })
+        Ok(SensorData::new(fields))
     }
 
     fn name(&self) -> &str {
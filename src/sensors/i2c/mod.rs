@@ -1,9 +1,94 @@
 //! I2C sensor drivers (local hardware on Linux, or TCP bridge on all platforms).
 
+#[cfg(feature = "ads1115")]
 pub mod ads1115;
+#[cfg(feature = "bh1750")]
 pub mod bh1750;
+#[cfg(feature = "bme280")]
 pub mod bme280;
+#[cfg(feature = "bmp280")]
 pub mod bmp280;
+#[cfg(feature = "ina219")]
 pub mod ina219;
+#[cfg(feature = "mpu6500")]
 pub mod mpu6500;
+#[cfg(feature = "sht31")]
 pub mod sht31;
+
+use anyhow::Result;
+
+use crate::error::SensorError;
+use crate::transport::I2cBus;
+
+/// Read a chip's identity register and check it against the chips a driver
+/// supports, so a wrong address or driver fails at init instead of
+/// publishing garbage.  Returns the name of the chip found.
+pub fn verify_chip_id(
+    bus: &mut dyn I2cBus,
+    address: u8,
+    reg: u8,
+    driver: &str,
+    expected: &[(u8, &'static str)],
+) -> Result<&'static str> {
+    let mut id = [0u8; 1];
+    bus.write_read(address, &[reg], &mut id)?;
+    if let Some((_, chip)) = expected.iter().find(|(e, _)| *e == id[0]) {
+        return Ok(chip);
+    }
+    let wanted: Vec<String> = expected
+        .iter()
+        .map(|(e, chip)| format!("{:#04x} ({})", e, chip))
+        .collect();
+    Err(SensorError::InitializationError(format!(
+        "{}: identity register {:#04x} at address {:#04x} reads {:#04x}, expected {} \
+         — check the address and driver in the config",
+        driver,
+        reg,
+        address,
+        id[0],
+        wanted.join(" or ")
+    ))
+    .into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Answers every register read with `id`.
+    struct FixedId(u8);
+
+    impl I2cBus for FixedId {
+        fn write(&mut self, _addr: u8, _data: &[u8]) -> Result<()> {
+            Ok(())
+        }
+        fn read(&mut self, _addr: u8, buf: &mut [u8]) -> Result<()> {
+            buf.fill(self.0);
+            Ok(())
+        }
+        fn write_read(&mut self, _addr: u8, _write: &[u8], read: &mut [u8]) -> Result<()> {
+            read.fill(self.0);
+            Ok(())
+        }
+    }
+
+    const CHIPS: &[(u8, &str)] = &[(0x70, "MPU6500"), (0x71, "MPU9250")];
+
+    #[test]
+    fn test_verify_chip_id_accepts_known_chips() {
+        let chip = verify_chip_id(&mut FixedId(0x71), 0x68, 0x75, "mpu6500", CHIPS).unwrap();
+        assert_eq!(chip, "MPU9250");
+    }
+
+    #[test]
+    fn test_verify_chip_id_names_expected_and_found() {
+        let err = verify_chip_id(&mut FixedId(0x58), 0x76, 0x75, "mpu6500", CHIPS).unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<SensorError>(),
+            Some(SensorError::InitializationError(_))
+        ));
+        let msg = err.to_string();
+        assert!(msg.contains("reads 0x58"), "{}", msg);
+        assert!(msg.contains("0x70 (MPU6500) or 0x71 (MPU9250)"), "{}", msg);
+    }
+}
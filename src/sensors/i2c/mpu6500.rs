@@ -3,25 +3,91 @@
 //! Provides calibrated, Kalman-filtered accelerometer and gyroscope data.
 //! Extra derived quantities: combined_g, tilt_angle, angular_velocity_magnitude,
 //! and a rolling peak_g (cleared on recalibrate).
+//!
+//! The older MPU6050 shares the register layout and is handled by the same
+//! driver (`driver = "mpu6050"`); see [`Variant`] for what differs.
 
 use anyhow::Result;
-use chrono::Utc;
+use indexmap::IndexMap;
 use serde::Deserialize;
-use std::collections::HashMap;
 
-use crate::config::SensorConfig;
-use crate::filters::kalman_1d::KalmanFilter1D;
-use crate::sensors::{FieldDescriptor, Sensor, SensorData, VizType};
+use crate::config::{ConnectionConfig, SensorConfig};
+use crate::filters::Real;
+use crate::filters::gravity::GravityFilter;
+use crate::filters::high_pass::HighPassFilter;
+use crate::filters::kalman_1d::{AdaptiveGain, KalmanFilter1D};
+use crate::processing::imu::{self, magnitude, remove_gravity, Angles};
+use crate::sensors::{
+    AxisFrame, CalibrationProgress, FieldDescriptor, FRAME_CHIP_Z_UP, SelfTestCheck, SelfTestReport, Sensor, SensorData,
+    VizType,
+};
 use crate::transport::{open_i2c, I2cBus};
 
+use super::verify_chip_id;
+
 // ---------------------------------------------------------------------------
 // Register map
 // ---------------------------------------------------------------------------
+const SELF_TEST_X_GYRO: u8 = 0x00; // X, Y, Z
+const SELF_TEST_X_ACCEL: u8 = 0x0D; // X, Y, Z
 const PWR_MGMT_1: u8 = 0x6B;
 const SMPLRT_DIV: u8 = 0x19;
+const CONFIG: u8 = 0x1A;
 const ACCEL_CONFIG: u8 = 0x1C;
 const GYRO_CONFIG: u8 = 0x1B;
+const ACCEL_CONFIG2: u8 = 0x1D;
 const ACCEL_XOUT_H: u8 = 0x3B;
+const WHO_AM_I: u8 = 0x75;
+
+/// Longest gap between readings the gravity and vibration filters carry
+/// their state across; after a longer one (startup, calibration) they
+/// start over.
+const MAX_GAP_SECS: f64 = 0.5;
+
+// ---------------------------------------------------------------------------
+// Chip variants
+// ---------------------------------------------------------------------------
+
+/// Register-compatible chips the driver supports.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Variant {
+    /// MPU6500, and the MPU9250/9255 that embed it
+    Mpu6500,
+    /// MPU6050 — no separate accel DLPF register and no self-test support
+    Mpu6050,
+}
+
+impl Variant {
+    pub fn name(self) -> &'static str {
+        match self {
+            Variant::Mpu6500 => "MPU6500",
+            Variant::Mpu6050 => "MPU6050",
+        }
+    }
+
+    /// WHO_AM_I values accepted for this variant.
+    fn chip_ids(self) -> &'static [(u8, &'static str)] {
+        match self {
+            Variant::Mpu6500 => &[(0x70, "MPU6500"), (0x71, "MPU9250"), (0x73, "MPU9255")],
+            Variant::Mpu6050 => &[(0x68, "MPU6050")],
+        }
+    }
+
+    /// Die temperature in °C from the raw TEMP_OUT reading.
+    fn temperature(self, raw: i16) -> f64 {
+        match self {
+            Variant::Mpu6500 => raw as f64 / 333.87 + 21.0,
+            Variant::Mpu6050 => raw as f64 / 340.0 + 36.53,
+        }
+    }
+
+    fn gyro_dlpf(self) -> &'static [(u16, u8)] {
+        match self {
+            Variant::Mpu6500 => GYRO_DLPF,
+            Variant::Mpu6050 => GYRO_DLPF_6050,
+        }
+    }
+}
 
 // ---------------------------------------------------------------------------
 // Settings (deserialised from config.toml [sensors.settings])
@@ -32,9 +98,43 @@ pub struct FilterConfig {
     pub process_noise: f64,
     pub measurement_noise: f64,
     pub dead_zone: f64,
+    /// Once moving, keep following until the change per reading drops
+    /// below `dead_zone` times this; 1 disables the hysteresis
+    #[serde(default = "default_dead_zone_release")]
+    pub dead_zone_release: f64,
+    /// Fraction of the way a held output moves toward the estimate per
+    /// reading; 0 holds it until the dead zone is exceeded
+    #[serde(default)]
+    pub dead_zone_decay: f64,
     /// Clamp output to 0 when |value| < this (removes standing-still drift)
     #[serde(default)]
     pub absolute_zero: f64,
+    /// Scale the gain by innovation size; off gives a plain Kalman filter
+    #[serde(default = "default_adaptive")]
+    pub adaptive: bool,
+    /// Innovation, in the channel's units, above which `fast_gain` applies
+    #[serde(default = "default_adapt_threshold")]
+    pub adapt_threshold: f64,
+    #[serde(default = "default_fast_gain")]
+    pub fast_gain: f64,
+    #[serde(default = "default_slow_gain")]
+    pub slow_gain: f64,
+}
+
+fn default_dead_zone_release() -> f64 {
+    1.0
+}
+fn default_adaptive() -> bool {
+    true
+}
+fn default_adapt_threshold() -> f64 {
+    1.0
+}
+fn default_fast_gain() -> f64 {
+    1.5
+}
+fn default_slow_gain() -> f64 {
+    0.8
 }
 
 impl Default for FilterConfig {
@@ -43,7 +143,70 @@ impl Default for FilterConfig {
             process_noise: 0.00001,
             measurement_noise: 0.05,
             dead_zone: 0.005,
+            dead_zone_release: default_dead_zone_release(),
+            dead_zone_decay: 0.0,
             absolute_zero: 0.0,
+            adaptive: default_adaptive(),
+            adapt_threshold: default_adapt_threshold(),
+            fast_gain: default_fast_gain(),
+            slow_gain: default_slow_gain(),
+        }
+    }
+}
+
+impl FilterConfig {
+    /// A filter with these noise, dead-zone and gain settings.
+    fn build(&self) -> KalmanFilter1D {
+        let adaptive = self.adaptive.then_some(AdaptiveGain {
+            threshold: self.adapt_threshold as Real,
+            fast_gain: self.fast_gain as Real,
+            slow_gain: self.slow_gain as Real,
+        });
+        KalmanFilter1D::new(self.process_noise, self.measurement_noise)
+            .with_dead_zone(self.dead_zone)
+            .with_dead_zone_release(self.dead_zone_release)
+            .with_dead_zone_decay(self.dead_zone_decay)
+            .with_adaptive_gain(adaptive)
+    }
+}
+
+/// Joint 3-axis gravity tracking for the linear acceleration channels
+/// (see [`GravityFilter`]).  Off uses the per-axis estimate.
+#[derive(Debug, Deserialize, Clone)]
+#[serde(default)]
+pub struct GravityFilterConfig {
+    pub enabled: bool,
+    /// Fraction of the way toward the accelerometer's direction per reading
+    pub correction: f64,
+    /// Only trust the accelerometer's direction within this many g of 1 g
+    pub tolerance_g: f64,
+}
+
+impl Default for GravityFilterConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            correction: 0.02,
+            tolerance_g: 0.1,
+        }
+    }
+}
+
+/// High-pass filtered acceleration as `vibration_x/y/z`, for spotting
+/// wheel imbalance and other vibration without gravity or manoeuvres.
+#[derive(Debug, Deserialize, Clone)]
+#[serde(default)]
+pub struct VibrationConfig {
+    pub enabled: bool,
+    /// Slower changes than this are removed
+    pub cutoff_hz: f64,
+}
+
+impl Default for VibrationConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            cutoff_hz: 5.0,
         }
     }
 }
@@ -64,6 +227,32 @@ pub struct MPU6500Settings {
     pub accel_z_filter: FilterConfig,
     #[serde(default)]
     pub gyro_filter: FilterConfig,
+    /// On-chip accelerometer low-pass bandwidth in Hz, rounded up to the
+    /// nearest of 5, 10, 21, 45, 99, 218, 420 or 1046 (filter bypassed).
+    /// Unset leaves the chip default.
+    #[serde(default)]
+    pub accel_dlpf_hz: Option<u16>,
+    /// On-chip gyro low-pass bandwidth in Hz, rounded up to the nearest of
+    /// 5, 10, 20, 41, 92, 184, 250 or 3600.  `sample_rate` only takes effect
+    /// with 5–184 Hz.  Unset leaves the chip default.
+    #[serde(default)]
+    pub gyro_dlpf_hz: Option<u16>,
+    /// Check WHO_AM_I during init.  Defaults to on for local I2C and off
+    /// over TCP, where read-only bridges answer every read with the data frame.
+    #[serde(default)]
+    pub verify_id: Option<bool>,
+    /// Report each Kalman filter's variance, gain, innovation and dead-zone
+    /// hold with every reading, for tuning the filter settings.
+    #[serde(default)]
+    pub filter_diagnostics: bool,
+    #[serde(default)]
+    pub gravity_filter: GravityFilterConfig,
+    #[serde(default)]
+    pub vibration: VibrationConfig,
+    /// Also report linear acceleration as `accel_x/y/z`, duplicating
+    /// `g_force_x/y/z`, for consumers written against older versions.
+    #[serde(default)]
+    pub legacy_accel_fields: bool,
 }
 
 fn default_accel_range() -> u16 {
@@ -93,7 +282,15 @@ impl Default for MPU6500Settings {
                 measurement_noise: 0.1,
                 dead_zone: 1.0,
                 absolute_zero: 2.0,
+                ..Default::default()
             },
+            accel_dlpf_hz: None,
+            gyro_dlpf_hz: None,
+            verify_id: None,
+            filter_diagnostics: false,
+            gravity_filter: GravityFilterConfig::default(),
+            vibration: VibrationConfig::default(),
+            legacy_accel_fields: false,
         }
     }
 }
@@ -113,6 +310,7 @@ struct CalibrationData {
 // ---------------------------------------------------------------------------
 
 pub struct MPU6500 {
+    variant: Variant,
     device: Box<dyn I2cBus>,
     address: u8,
     sensor_name: String,
@@ -125,31 +323,67 @@ pub struct MPU6500 {
     linear_filters: [KalmanFilter1D; 3],
     /// Stateful Kalman filters for gyroscope
     gyro_filters: [KalmanFilter1D; 3],
+    /// Joint gravity estimate, when `gravity_filter` is enabled
+    gravity: Option<GravityFilter>,
+    /// High-pass filters on the unfiltered accel, when `vibration` is enabled
+    vibration: Option<[HighPassFilter; 3]>,
+    /// Time of the previous reading, for integrating the gyro
+    last_read: Option<std::time::Instant>,
     /// Rolling peak combined-G (reset on recalibrate)
     peak_g: f64,
     /// Field descriptors (built once)
     descriptors: Vec<FieldDescriptor>,
+    /// Channels in the previous reading, so the next one's map is
+    /// allocated at its full size rather than grown
+    field_count: usize,
 }
 
 impl MPU6500 {
     /// Construct from `SensorConfig` using the new config model.
     pub fn from_config(cfg: &SensorConfig) -> Result<Self> {
-        let settings: MPU6500Settings = cfg
+        Self::from_config_variant(cfg, Variant::Mpu6500)
+    }
+
+    /// Construct for a specific chip variant (`mpu6050` driver).
+    pub fn from_config_variant(cfg: &SensorConfig, variant: Variant) -> Result<Self> {
+        let (device, address) = open_i2c(cfg, 0x68)?;
+        Self::with_bus(cfg, variant, device, address)
+    }
+
+    /// Construct on an already-open bus, e.g. a scripted one in tests and
+    /// benchmarks.  Initialises and calibrates like [`from_config`](Self::from_config).
+    pub fn with_bus(
+        cfg: &SensorConfig,
+        variant: Variant,
+        device: Box<dyn I2cBus>,
+        address: u8,
+    ) -> Result<Self> {
+        let mut settings: MPU6500Settings = cfg
             .settings
             .as_ref()
             .map(|v| v.clone().try_into())
             .transpose()
-            .map_err(|e: toml::de::Error| anyhow::anyhow!("MPU6500 settings: {}", e))?
+            .map_err(|e: toml::de::Error| anyhow::anyhow!("{} settings: {}", variant.name(), e))?
             .unwrap_or_default();
-
-        let (device, address) = open_i2c(cfg, 0x68)?;
+        settings
+            .verify_id
+            .get_or_insert(!matches!(cfg.connection, ConnectionConfig::Tcp(_)));
 
         let accel_filters = Self::build_accel_filters(&settings);
         let linear_filters = Self::build_linear_filters(&settings);
         let gyro_filters = Self::build_gyro_filters(&settings);
-        let descriptors = Self::build_descriptors();
+        let g = &settings.gravity_filter;
+        let gravity = g
+            .enabled
+            .then(|| GravityFilter::new(g.correction, g.tolerance_g));
+        let vibration = settings
+            .vibration
+            .enabled
+            .then(|| [(); 3].map(|_| HighPassFilter::new(settings.vibration.cutoff_hz)));
+        let descriptors = Self::build_descriptors(&settings);
 
         let mut sensor = Self {
+            variant,
             device,
             address,
             sensor_name: cfg.name.clone(),
@@ -159,7 +393,11 @@ impl MPU6500 {
             accel_filters,
             linear_filters,
             gyro_filters,
+            gravity,
+            vibration,
+            last_read: None,
             peak_g: 0.0,
+            field_count: descriptors.len(),
             descriptors,
         };
 
@@ -169,137 +407,118 @@ impl MPU6500 {
     }
 
     fn build_accel_filters(s: &MPU6500Settings) -> [KalmanFilter1D; 3] {
-        let a = &s.accel_filter;
-        let z = &s.accel_z_filter;
-        [
-            KalmanFilter1D::new(a.process_noise, a.measurement_noise).with_dead_zone(a.dead_zone),
-            KalmanFilter1D::new(a.process_noise, a.measurement_noise).with_dead_zone(a.dead_zone),
-            KalmanFilter1D::new(z.process_noise, z.measurement_noise).with_dead_zone(z.dead_zone),
-        ]
+        let (a, z) = (&s.accel_filter, &s.accel_z_filter);
+        [a.build(), a.build(), z.build()]
     }
 
     fn build_linear_filters(s: &MPU6500Settings) -> [KalmanFilter1D; 3] {
-        let a = &s.accel_filter;
-        let z = &s.accel_z_filter;
-        [
-            KalmanFilter1D::new(a.process_noise, a.measurement_noise).with_dead_zone(a.dead_zone),
-            KalmanFilter1D::new(a.process_noise, a.measurement_noise).with_dead_zone(a.dead_zone),
-            KalmanFilter1D::new(z.process_noise, z.measurement_noise).with_dead_zone(z.dead_zone),
-        ]
+        let (a, z) = (&s.accel_filter, &s.accel_z_filter);
+        [a.build(), a.build(), z.build()]
     }
 
     fn build_gyro_filters(s: &MPU6500Settings) -> [KalmanFilter1D; 3] {
         let g = &s.gyro_filter;
-        [
-            KalmanFilter1D::new(g.process_noise, g.measurement_noise)
-                .with_dead_zone(g.dead_zone)
-                .with_absolute_zero(g.absolute_zero),
-            KalmanFilter1D::new(g.process_noise, g.measurement_noise)
-                .with_dead_zone(g.dead_zone)
-                .with_absolute_zero(g.absolute_zero),
-            KalmanFilter1D::new(g.process_noise, g.measurement_noise)
-                .with_dead_zone(g.dead_zone)
-                .with_absolute_zero(g.absolute_zero),
-        ]
-    }
-
-    fn build_descriptors() -> Vec<FieldDescriptor> {
-        vec![
-            // Accelerometer
-            FieldDescriptor {
-                key: "accel_x",
-                label: "Accel X",
-                viz: VizType::GForce,
-                group: Some("ACCELEROMETER"),
-            },
-            FieldDescriptor {
-                key: "accel_y",
-                label: "Accel Y",
-                viz: VizType::GForce,
-                group: None,
-            },
-            FieldDescriptor {
-                key: "accel_z",
-                label: "Accel Z",
-                viz: VizType::GForce,
-                group: None,
-            },
-            // G-forces
-            FieldDescriptor {
-                key: "g_force_x",
-                label: "G Lateral",
-                viz: VizType::GForce,
-                group: Some("G-FORCES"),
-            },
-            FieldDescriptor {
-                key: "g_force_y",
-                label: "G Forward",
-                viz: VizType::GForce,
-                group: None,
-            },
-            FieldDescriptor {
-                key: "g_force_z",
-                label: "G Vertical",
-                viz: VizType::GForce,
-                group: None,
-            },
-            FieldDescriptor {
-                key: "combined_g",
-                label: "Combined G",
-                viz: VizType::GForce,
-                group: None,
-            },
-            FieldDescriptor {
-                key: "peak_g",
-                label: "Peak G",
-                viz: VizType::GForce,
-                group: None,
-            },
-            // Gyroscope
-            FieldDescriptor {
-                key: "roll_rate",
-                label: "Roll Rate",
-                viz: VizType::AngularRate,
-                group: Some("GYROSCOPE"),
-            },
-            FieldDescriptor {
-                key: "pitch_rate",
-                label: "Pitch Rate",
-                viz: VizType::AngularRate,
-                group: None,
-            },
-            FieldDescriptor {
-                key: "yaw_rate",
-                label: "Yaw Rate",
-                viz: VizType::AngularRate,
-                group: None,
-            },
-            FieldDescriptor {
-                key: "angular_velocity",
-                label: "Angular Vel",
-                viz: VizType::AngularRate,
-                group: None,
-            },
-            // Orientation
-            FieldDescriptor {
-                key: "lean_angle",
-                label: "Lean Angle",
-                viz: VizType::Angle,
-                group: Some("ORIENTATION"),
-            },
-            FieldDescriptor {
-                key: "bank_angle",
-                label: "Bank Angle",
-                viz: VizType::Angle,
-                group: None,
-            },
+        [(); 3].map(|_| g.build().with_absolute_zero(g.absolute_zero))
+    }
+
+    fn build_descriptors(s: &MPU6500Settings) -> Vec<FieldDescriptor> {
+        let mut fields = Vec::new();
+        if s.legacy_accel_fields {
+            fields.extend([
+                FieldDescriptor {
+                    key: "accel_x",
+                    label: "Accel X",
+                    viz: VizType::GForce,
+                    range: None,
+                    group: Some("ACCELEROMETER"),
+                },
+                FieldDescriptor {
+                    key: "accel_y",
+                    label: "Accel Y",
+                    viz: VizType::GForce,
+                    range: None,
+                    group: None,
+                },
+                FieldDescriptor {
+                    key: "accel_z",
+                    label: "Accel Z",
+                    viz: VizType::GForce,
+                    range: None,
+                    group: None,
+                },
+            ]);
+        }
+        fields.extend(imu::GFORCE_FIELDS);
+        fields.extend(imu::RATE_FIELDS);
+        fields.extend(imu::ANGLE_FIELDS);
+        fields.extend([
+            // Chip
             FieldDescriptor {
-                key: "tilt_angle",
-                label: "Tilt Angle",
-                viz: VizType::Angle,
-                group: None,
+                key: "temperature",
+                label: "Die Temp",
+                viz: VizType::Numeric { unit: "°C" },
+                range: Some((-40.0, 85.0)),
+                group: Some("CHIP"),
             },
-        ]
+        ]);
+        if s.gravity_filter.enabled {
+            fields.extend([
+                FieldDescriptor {
+                    key: "quat_w",
+                    label: "Quat W",
+                    viz: VizType::Numeric { unit: "" },
+                    range: Some((-1.0, 1.0)),
+                    group: Some("QUATERNION"),
+                },
+                FieldDescriptor {
+                    key: "quat_x",
+                    label: "Quat X",
+                    viz: VizType::Numeric { unit: "" },
+                    range: Some((-1.0, 1.0)),
+                    group: None,
+                },
+                FieldDescriptor {
+                    key: "quat_y",
+                    label: "Quat Y",
+                    viz: VizType::Numeric { unit: "" },
+                    range: Some((-1.0, 1.0)),
+                    group: None,
+                },
+                FieldDescriptor {
+                    key: "quat_z",
+                    label: "Quat Z",
+                    viz: VizType::Numeric { unit: "" },
+                    range: Some((-1.0, 1.0)),
+                    group: None,
+                },
+            ]);
+        }
+        if s.vibration.enabled {
+            fields.extend([
+                FieldDescriptor {
+                    key: "vibration_x",
+                    label: "Vibration X",
+                    viz: VizType::GForce,
+                    range: None,
+                    group: Some("VIBRATION"),
+                },
+                FieldDescriptor {
+                    key: "vibration_y",
+                    label: "Vibration Y",
+                    viz: VizType::GForce,
+                    range: None,
+                    group: None,
+                },
+                FieldDescriptor {
+                    key: "vibration_z",
+                    label: "Vibration Z",
+                    viz: VizType::GForce,
+                    range: None,
+                    group: None,
+                },
+            ]);
+        }
+        fields
     }
 
     // -----------------------------------------------------------------------
@@ -312,64 +531,82 @@ impl MPU6500 {
     /// the MPU-6500 auto-increments the register pointer.
     /// For TCP (io-to-net) the write is discarded by the bridge (`read_only`)
     /// and the bridge returns its pre-read frame — `read_len` must be 14.
-    fn read_raw_6(&mut self) -> Result<[i16; 6]> {
+    fn read_frame(&mut self) -> Result<[u8; 14]> {
         let mut buf = [0u8; 14];
         self.device.write_read(self.address, &[ACCEL_XOUT_H], &mut buf)?;
-        Ok(parse_sensor_frame(&buf))
+        Ok(buf)
+    }
+
+    fn read_raw_6(&mut self) -> Result<[i16; 6]> {
+        Ok(parse_sensor_frame(&self.read_frame()?))
     }
 
     fn accel_scale(&self) -> f64 {
-        match self.settings.accel_range {
-            2 => 16384.0,
-            4 => 8192.0,
-            8 => 4096.0,
-            16 => 2048.0,
-            _ => 2048.0,
-        }
+        accel_lsb_per_g(self.settings.accel_range)
     }
 
     fn gyro_scale(&self) -> f64 {
-        match self.settings.gyro_range {
-            250 => 131.2,
-            500 => 65.6,
-            1000 => 32.8,
-            2000 => 16.4,
-            _ => 16.4,
-        }
+        gyro_lsb_per_dps(self.settings.gyro_range)
     }
 
     fn gravity_for_range(&self) -> i32 {
-        match self.settings.accel_range {
-            2 => 16384,
-            4 => 8192,
-            8 => 4096,
-            16 => 2048,
-            _ => 2048,
-        }
+        self.accel_scale() as i32
     }
 
-    /// Remove the static gravity component from accelerometer readings.
-    /// X and Y get gravity subtracted proportionally; Z is returned raw
-    /// (gravity is mostly along Z when the sensor is horizontal).
-    fn remove_gravity(raw: [f64; 3]) -> [f64; 3] {
-        let mag = (raw[0].powi(2) + raw[1].powi(2) + raw[2].powi(2)).sqrt();
-        if mag < 1e-9 {
-            return raw;
+    /// `<channel>_p`, `_k`, `_innovation` and `_held` (1 or 0) for every
+    /// filter, named after the channel it feeds.  With `legacy_accel_fields`
+    /// the linear acceleration filters keep their old `accel_*` names.
+    fn filter_diagnostics(&self) -> IndexMap<String, f64> {
+        let linear = if self.settings.legacy_accel_fields {
+            ["accel_x", "accel_y", "accel_z"]
+        } else {
+            ["g_force_x", "g_force_y", "g_force_z"]
+        };
+        let channels = ["accel_raw_x", "accel_raw_y", "accel_raw_z"]
+            .into_iter()
+            .chain(linear)
+            .chain(["gyro_x", "gyro_y", "gyro_z"]);
+        let filters = self
+            .accel_filters
+            .iter()
+            .chain(&self.linear_filters)
+            .chain(&self.gyro_filters);
+        let mut out = IndexMap::with_capacity(9 * 4);
+        for (channel, filter) in channels.zip(filters) {
+            let d = filter.diagnostics();
+            out.insert(format!("{}_p", channel), d.p);
+            out.insert(format!("{}_k", channel), d.k);
+            out.insert(format!("{}_innovation", channel), d.innovation);
+            out.insert(format!("{}_held", channel), f64::from(u8::from(d.held)));
         }
-        let scale = 1.0 / mag;
-        let gravity = [raw[0] * scale, raw[1] * scale, raw[2] * scale];
-        [raw[0] - gravity[0], raw[1] - gravity[1], raw[2]]
+        out
     }
 
     /// Perform calibration: 300 samples @ 10 ms, average, subtract 1G from Z.
     pub fn do_calibrate(&mut self) -> Result<()> {
+        self.calibrate_reporting(&mut |_| {})
+    }
+
+    /// Calibration loop shared by startup and TUI-triggered recalibration.
+    /// Reports every 10th sample plus the final one.
+    fn calibrate_reporting(&mut self, progress: &mut dyn FnMut(CalibrationProgress)) -> Result<()> {
         const N: i32 = 300;
+        let g_scale = self.gyro_scale();
         let mut sums = [0i64; 6];
-        for _ in 0..N {
+        let mut motion = GyroNoise::default();
+        for n in 1..=N {
             let raw = self.read_raw_6()?;
             for i in 0..6 {
                 sums[i] += raw[i] as i64;
             }
+            motion.push([raw[3], raw[4], raw[5]]);
+            if n % 10 == 0 || n == N {
+                progress(CalibrationProgress {
+                    collected: n as u32,
+                    total: N as u32,
+                    motion_dps: motion.std_dev() / g_scale,
+                });
+            }
             std::thread::sleep(std::time::Duration::from_millis(10));
         }
         for i in 0..3 {
@@ -383,12 +620,65 @@ impl MPU6500 {
     }
 }
 
+impl MPU6500 {
+    /// Factory self-test (InvenSense AN-MPU-6500A-02): average the outputs
+    /// with the self-test actuation off and on, and compare the difference
+    /// with the response trimmed into OTP at the factory.
+    fn run_self_test(&mut self) -> Result<SelfTestReport> {
+        // ±2 g / ±250 °/s at 1 kHz with ~92–99 Hz DLPF, as the procedure specifies
+        for (reg, val) in [
+            (SMPLRT_DIV, 0x00),
+            (CONFIG, 0x02),
+            (GYRO_CONFIG, 0x00),
+            (ACCEL_CONFIG2, 0x02),
+            (ACCEL_CONFIG, 0x00),
+        ] {
+            self.device.write(self.address, &[reg, val])?;
+        }
+        std::thread::sleep(SELF_TEST_SETTLE);
+        let normal = self.average_raw(SELF_TEST_SAMPLES)?;
+
+        self.device.write(self.address, &[ACCEL_CONFIG, 0xE0])?;
+        self.device.write(self.address, &[GYRO_CONFIG, 0xE0])?;
+        std::thread::sleep(SELF_TEST_SETTLE);
+        let active = self.average_raw(SELF_TEST_SAMPLES)?;
+
+        let mut codes = [0u8; 6];
+        self.device
+            .write_read(self.address, &[SELF_TEST_X_ACCEL], &mut codes[..3])?;
+        self.device
+            .write_read(self.address, &[SELF_TEST_X_GYRO], &mut codes[3..])?;
+        Ok(evaluate_self_test(normal, active, codes))
+    }
+
+    /// Mean of `n` raw frames taken 1 ms apart.
+    fn average_raw(&mut self, n: u32) -> Result<[f64; 6]> {
+        let mut sum = [0.0; 6];
+        for _ in 0..n {
+            for (s, v) in sum.iter_mut().zip(self.read_raw_6()?) {
+                *s += v as f64;
+            }
+            std::thread::sleep(std::time::Duration::from_millis(1));
+        }
+        Ok(sum.map(|s| s / n as f64))
+    }
+}
+
 // ---------------------------------------------------------------------------
 // Sensor trait implementation
 // ---------------------------------------------------------------------------
 
 impl Sensor for MPU6500 {
     fn init(&mut self) -> Result<()> {
+        if self.settings.verify_id.unwrap_or(true) {
+            verify_chip_id(
+                self.device.as_mut(),
+                self.address,
+                WHO_AM_I,
+                self.variant.name(),
+                self.variant.chip_ids(),
+            )?;
+        }
         // Wake up
         self.device.write(self.address, &[PWR_MGMT_1, 0x00])?;
         // Sample rate divider
@@ -412,22 +702,90 @@ impl Sensor for MPU6500 {
             _ => 0x18,
         };
         self.device.write(self.address, &[GYRO_CONFIG, gyro_cfg])?;
+        // Digital low-pass filters
+        let nyquist = self.settings.sample_rate / 2;
+        if let Some(hz) = self.settings.gyro_dlpf_hz {
+            let (bw, cfg) = dlpf_setting(self.variant.gyro_dlpf(), hz);
+            self.device.write(self.address, &[CONFIG, cfg])?;
+            if bw > nyquist {
+                tracing::warn!(
+                    "{}: gyro DLPF at {} Hz is above Nyquist ({} Hz) — expect aliasing",
+                    self.sensor_name,
+                    bw,
+                    nyquist
+                );
+            }
+        }
+        if let Some(hz) = self.settings.accel_dlpf_hz
+            && self.variant == Variant::Mpu6050
+        {
+            tracing::warn!(
+                "{}: MPU6050 has no separate accel DLPF, ignoring accel_dlpf_hz = {} \
+                 (gyro_dlpf_hz filters both)",
+                self.sensor_name,
+                hz
+            );
+        } else if let Some(hz) = self.settings.accel_dlpf_hz {
+            let (bw, cfg) = dlpf_setting(ACCEL_DLPF, hz);
+            self.device.write(self.address, &[ACCEL_CONFIG2, cfg])?;
+            if bw > nyquist {
+                tracing::warn!(
+                    "{}: accel DLPF at {} Hz is above Nyquist ({} Hz) — expect aliasing",
+                    self.sensor_name,
+                    bw,
+                    nyquist
+                );
+            }
+        }
         Ok(())
     }
 
     fn read(&mut self) -> Result<SensorData> {
-        let raw = self.read_raw_6()?;
+        let frame = self.read_frame()?;
+        let raw = parse_sensor_frame(&frame);
+        let temperature = self.variant.temperature(parse_temperature(&frame));
         let a_scale = self.accel_scale();
         let g_scale = self.gyro_scale();
 
         // Apply calibration and scale
-        let raw_accel: [f64; 3] = [
-            (raw[0] as i32 - self.calibration.accel_offsets[0]) as f64 / a_scale,
-            (raw[1] as i32 - self.calibration.accel_offsets[1]) as f64 / a_scale,
-            (raw[2] as i32 - self.calibration.accel_offsets[2]) as f64 / a_scale,
-        ];
+        let raw_accel = scale_axes(
+            [raw[0], raw[1], raw[2]],
+            self.calibration.accel_offsets,
+            a_scale,
+        );
+        let raw_gyro = scale_axes(
+            [raw[3], raw[4], raw[5]],
+            self.calibration.gyro_offsets,
+            g_scale,
+        );
 
-        let linear_accel = Self::remove_gravity(raw_accel);
+        // After a long gap the gravity and vibration state is stale
+        let now = std::time::Instant::now();
+        let dt = self
+            .last_read
+            .map(|t| (now - t).as_secs_f64())
+            .filter(|&dt| dt <= MAX_GAP_SECS);
+        self.last_read = Some(now);
+
+        let linear_accel = match self.gravity.as_mut() {
+            Some(g) => {
+                if dt.is_none() {
+                    g.reset();
+                }
+                g.update(raw_accel, raw_gyro, dt.unwrap_or(0.0))
+            }
+            None => remove_gravity(raw_accel),
+        };
+        let vibration = self.vibration.as_mut().map(|filters| {
+            let mut out = [0.0; 3];
+            for ((f, a), v) in filters.iter_mut().zip(raw_accel).zip(&mut out) {
+                if dt.is_none() {
+                    f.reset();
+                }
+                *v = f.update(a, dt.unwrap_or(0.0));
+            }
+            out
+        });
 
         // Filter raw accel (stateful)
         let filt_raw: [f64; 3] = [
@@ -443,30 +801,7 @@ impl Sensor for MPU6500 {
             self.linear_filters[2].update(linear_accel[2]),
         ];
 
-        // Gyro
-        let raw_gyro: [f64; 3] = [
-            (raw[3] as i32 - self.calibration.gyro_offsets[0]) as f64 / g_scale,
-            (raw[4] as i32 - self.calibration.gyro_offsets[1]) as f64 / g_scale,
-            (raw[5] as i32 - self.calibration.gyro_offsets[2]) as f64 / g_scale,
-        ];
-        let az = self.settings.gyro_filter.absolute_zero;
-        let raw_gyro: [f64; 3] = [
-            if raw_gyro[0].abs() < az {
-                0.0
-            } else {
-                raw_gyro[0]
-            },
-            if raw_gyro[1].abs() < az {
-                0.0
-            } else {
-                raw_gyro[1]
-            },
-            if raw_gyro[2].abs() < az {
-                0.0
-            } else {
-                raw_gyro[2]
-            },
-        ];
+        let raw_gyro = dead_band(raw_gyro, self.settings.gyro_filter.absolute_zero);
         let filt_gyro: [f64; 3] = [
             self.gyro_filters[0].update(raw_gyro[0]),
             self.gyro_filters[1].update(raw_gyro[1]),
@@ -474,67 +809,73 @@ impl Sensor for MPU6500 {
         ];
 
         // Derived quantities
-        let combined_g = (filt_lin[0].powi(2) + filt_lin[1].powi(2) + filt_lin[2].powi(2)).sqrt();
+        let combined_g = magnitude(filt_lin);
         if combined_g > self.peak_g {
             self.peak_g = combined_g;
         }
 
-        let tilt_angle = {
-            let ax2 = filt_raw[0].powi(2);
-            let ay2 = filt_raw[1].powi(2);
-            let az = filt_raw[2];
-            ((ax2 + ay2).sqrt() / az.abs().max(1e-9))
-                .atan()
-                .to_degrees()
-        };
-        let lean_angle = (filt_raw[1]
-            / (filt_raw[0].powi(2) + filt_raw[2].powi(2)).sqrt().max(1e-9))
-        .atan()
-        .to_degrees();
-        let bank_angle = (filt_raw[0] / filt_raw[2].abs().max(1e-9))
-            .atan()
-            .to_degrees();
-        let angular_velocity =
-            (filt_gyro[0].powi(2) + filt_gyro[1].powi(2) + filt_gyro[2].powi(2)).sqrt();
-
-        let mut fields = HashMap::new();
+        let Angles {
+            tilt: tilt_angle,
+            lean: lean_angle,
+            bank: bank_angle,
+        } = Angles::from_accel(filt_raw);
+        let angular_velocity = magnitude(filt_gyro);
+
+        let mut fields = IndexMap::with_capacity(self.field_count);
         // Raw accel
-        fields.insert("accel_raw_x".to_string(), filt_raw[0]);
-        fields.insert("accel_raw_y".to_string(), filt_raw[1]);
-        fields.insert("accel_raw_z".to_string(), filt_raw[2]);
+        fields.insert("accel_raw_x".into(), filt_raw[0]);
+        fields.insert("accel_raw_y".into(), filt_raw[1]);
+        fields.insert("accel_raw_z".into(), filt_raw[2]);
         // Linear accel / G-forces
-        fields.insert("accel_x".to_string(), filt_lin[0]);
-        fields.insert("accel_y".to_string(), filt_lin[1]);
-        fields.insert("accel_z".to_string(), filt_lin[2]);
-        fields.insert("g_force_x".to_string(), filt_lin[0]);
-        fields.insert("g_force_y".to_string(), filt_lin[1]);
-        fields.insert("g_force_z".to_string(), filt_lin[2]);
-        fields.insert("combined_g".to_string(), combined_g);
-        fields.insert("peak_g".to_string(), self.peak_g);
+        if self.settings.legacy_accel_fields {
+            fields.insert("accel_x".into(), filt_lin[0]);
+            fields.insert("accel_y".into(), filt_lin[1]);
+            fields.insert("accel_z".into(), filt_lin[2]);
+        }
+        fields.insert("g_force_x".into(), filt_lin[0]);
+        fields.insert("g_force_y".into(), filt_lin[1]);
+        fields.insert("g_force_z".into(), filt_lin[2]);
+        fields.insert("combined_g".into(), combined_g);
+        fields.insert("peak_g".into(), self.peak_g);
         // Gyro
-        fields.insert("gyro_x".to_string(), filt_gyro[0]);
-        fields.insert("gyro_y".to_string(), filt_gyro[1]);
-        fields.insert("gyro_z".to_string(), filt_gyro[2]);
-        fields.insert("roll_rate".to_string(), filt_gyro[0]);
-        fields.insert("pitch_rate".to_string(), filt_gyro[1]);
-        fields.insert("yaw_rate".to_string(), filt_gyro[2]);
-        fields.insert("angular_velocity".to_string(), angular_velocity);
+        fields.insert("gyro_x".into(), filt_gyro[0]);
+        fields.insert("gyro_y".into(), filt_gyro[1]);
+        fields.insert("gyro_z".into(), filt_gyro[2]);
+        fields.insert("roll_rate".into(), filt_gyro[0]);
+        fields.insert("pitch_rate".into(), filt_gyro[1]);
+        fields.insert("yaw_rate".into(), filt_gyro[2]);
+        fields.insert("angular_velocity".into(), angular_velocity);
         // Orientation
-        fields.insert("lean_angle".to_string(), lean_angle);
-        fields.insert("bank_angle".to_string(), bank_angle);
-        fields.insert("tilt_angle".to_string(), tilt_angle);
+        fields.insert("lean_angle".into(), lean_angle);
+        fields.insert("bank_angle".into(), bank_angle);
+        fields.insert("tilt_angle".into(), tilt_angle);
+        // Chip
+        fields.insert("temperature".into(), temperature);
+        if let Some([w, x, y, z]) = self.gravity.as_ref().and_then(|g| g.orientation()) {
+            fields.insert("quat_w".into(), w);
+            fields.insert("quat_x".into(), x);
+            fields.insert("quat_y".into(), y);
+            fields.insert("quat_z".into(), z);
+        }
+        if let Some(v) = vibration {
+            fields.insert("vibration_x".into(), v[0]);
+            fields.insert("vibration_y".into(), v[1]);
+            fields.insert("vibration_z".into(), v[2]);
+        }
 
-        Ok(SensorData {
-            timestamp: Utc::now(),
-            fields,
-        })
+        self.field_count = fields.len();
+        let mut data = SensorData::new(fields);
+        if self.settings.filter_diagnostics {
+            data.diagnostics = self.filter_diagnostics();
+        }
+        Ok(data)
     }
 
     fn name(&self) -> &str {
         &self.sensor_name
     }
     fn driver_name(&self) -> &str {
-        "MPU6500"
+        self.variant.name()
     }
     fn is_enabled(&self) -> bool {
         self.enabled
@@ -556,9 +897,180 @@ impl Sensor for MPU6500 {
         self.do_calibrate()
     }
 
+    fn recalibrate_with_progress(
+        &mut self,
+        progress: &mut dyn FnMut(CalibrationProgress),
+    ) -> Result<()> {
+        for f in self
+            .accel_filters
+            .iter_mut()
+            .chain(&mut self.linear_filters)
+            .chain(&mut self.gyro_filters)
+        {
+            f.reset();
+        }
+        self.calibrate_reporting(progress)
+    }
+
+    fn self_test(&mut self) -> Result<Option<SelfTestReport>> {
+        // The MPU6050 uses a different self-test register layout
+        if self.variant != Variant::Mpu6500 {
+            return Ok(None);
+        }
+        let report = self.run_self_test();
+        // Restore the configured ranges, rate and filters either way
+        self.init()?;
+        report.map(Some)
+    }
+
     fn field_descriptors(&self) -> &[FieldDescriptor] {
         &self.descriptors
     }
+
+    fn frame(&self) -> Option<&'static AxisFrame> {
+        Some(&FRAME_CHIP_Z_UP)
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Gyro noise accumulator (stillness during calibration)
+// ---------------------------------------------------------------------------
+
+/// Running per-axis variance (Welford) of raw gyro counts.
+#[derive(Debug, Default)]
+struct GyroNoise {
+    n: u32,
+    mean: [f64; 3],
+    m2: [f64; 3],
+}
+
+impl GyroNoise {
+    fn push(&mut self, sample: [i16; 3]) {
+        self.n += 1;
+        for (i, &v) in sample.iter().enumerate() {
+            let v = v as f64;
+            let delta = v - self.mean[i];
+            self.mean[i] += delta / self.n as f64;
+            self.m2[i] += delta * (v - self.mean[i]);
+        }
+    }
+
+    /// RMS of the per-axis standard deviations, in raw counts.
+    fn std_dev(&self) -> f64 {
+        if self.n < 2 {
+            return 0.0;
+        }
+        let var = self.m2.iter().sum::<f64>() / 3.0 / (self.n - 1) as f64;
+        var.sqrt()
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Digital low-pass filter
+// ---------------------------------------------------------------------------
+
+/// Gyro bandwidth (Hz) → CONFIG.DLPF_CFG, ascending.
+const GYRO_DLPF: &[(u16, u8)] = &[
+    (5, 6),
+    (10, 5),
+    (20, 4),
+    (41, 3),
+    (92, 2),
+    (184, 1),
+    (250, 0),
+    (3600, 7),
+];
+
+/// MPU6050 gyro bandwidth (Hz) → CONFIG.DLPF_CFG; the accel follows it.
+const GYRO_DLPF_6050: &[(u16, u8)] = &[
+    (5, 6),
+    (10, 5),
+    (20, 4),
+    (42, 3),
+    (98, 2),
+    (188, 1),
+    (256, 0),
+];
+
+/// Accel bandwidth (Hz) → ACCEL_CONFIG2, ascending.  1046 Hz sets
+/// ACCEL_FCHOICE_B, bypassing the filter.
+const ACCEL_DLPF: &[(u16, u8)] = &[
+    (5, 6),
+    (10, 5),
+    (21, 4),
+    (45, 3),
+    (99, 2),
+    (218, 1),
+    (420, 7),
+    (1046, 0x08),
+];
+
+/// The narrowest bandwidth that still passes `hz`, as `(bandwidth, register
+/// value)`; the widest when `hz` is above them all.
+fn dlpf_setting(table: &[(u16, u8)], hz: u16) -> (u16, u8) {
+    table
+        .iter()
+        .copied()
+        .find(|&(bw, _)| bw >= hz)
+        .unwrap_or(table[table.len() - 1])
+}
+
+// ---------------------------------------------------------------------------
+// Self-test evaluation
+// ---------------------------------------------------------------------------
+
+const SELF_TEST_SAMPLES: u32 = 200;
+const SELF_TEST_SETTLE: std::time::Duration = std::time::Duration::from_millis(20);
+
+/// LSB per g at ±2 g and per °/s at ±250 °/s.
+const SELF_TEST_ACCEL_LSB: f64 = 16384.0;
+const SELF_TEST_GYRO_LSB: f64 = 131.0;
+
+/// Expected self-test response in LSB for an OTP code; 0 means untrimmed.
+fn factory_trim(code: u8) -> f64 {
+    if code == 0 {
+        return 0.0;
+    }
+    2620.0 * 1.01f64.powi(code as i32 - 1)
+}
+
+/// Pass/fail per axis.  `normal` and `active` are averaged raw frames in
+/// [`parse_sensor_frame`] order; `codes` are the OTP codes in the same order.
+/// Limits are the application note's: accel 50–150 % of the trimmed response
+/// (225–675 mg untrimmed), gyro at least 50 % (60 °/s untrimmed).
+fn evaluate_self_test(normal: [f64; 6], active: [f64; 6], codes: [u8; 6]) -> SelfTestReport {
+    const NAMES: [&str; 6] = [
+        "accel_x", "accel_y", "accel_z", "gyro_x", "gyro_y", "gyro_z",
+    ];
+    let checks = NAMES
+        .iter()
+        .zip(normal.iter().zip(active))
+        .zip(codes)
+        .enumerate()
+        .map(|(i, ((&name, (off, on)), code))| {
+            let accel = i < 3;
+            let lsb = if accel {
+                SELF_TEST_ACCEL_LSB
+            } else {
+                SELF_TEST_GYRO_LSB
+            };
+            let trim = factory_trim(code) / lsb;
+            let (min, max) = match (accel, trim > 0.0) {
+                (true, true) => (0.5 * trim, 1.5 * trim),
+                (true, false) => (0.225, 0.675),
+                (false, true) => (0.5 * trim, f64::INFINITY),
+                (false, false) => (60.0, f64::INFINITY),
+            };
+            SelfTestCheck {
+                name,
+                unit: if accel { "g" } else { "°/s" },
+                response: (on - off) / lsb,
+                min,
+                max,
+            }
+        })
+        .collect();
+    SelfTestReport { checks }
 }
 
 // ---------------------------------------------------------------------------
@@ -584,6 +1096,48 @@ fn parse_sensor_frame(buf: &[u8; 14]) -> [i16; 6] {
     ]
 }
 
+/// Raw TEMP_OUT from bytes 6–7 of a sensor frame.
+fn parse_temperature(buf: &[u8; 14]) -> i16 {
+    i16::from_be_bytes([buf[6], buf[7]])
+}
+
+// ---------------------------------------------------------------------------
+// Scaling
+// ---------------------------------------------------------------------------
+
+/// Accelerometer sensitivity (LSB per g) for a full-scale range in g.
+/// Unknown ranges get the ±16 g figure, the widest.
+fn accel_lsb_per_g(range: u16) -> f64 {
+    match range {
+        2 => 16384.0,
+        4 => 8192.0,
+        8 => 4096.0,
+        _ => 2048.0,
+    }
+}
+
+/// Gyro sensitivity (LSB per °/s) for a full-scale range in °/s.
+/// Unknown ranges get the ±2000 °/s figure, the widest.
+fn gyro_lsb_per_dps(range: u16) -> f64 {
+    match range {
+        250 => 131.2,
+        500 => 65.6,
+        1000 => 32.8,
+        _ => 16.4,
+    }
+}
+
+/// Subtract the calibration offsets from three raw axes and convert them
+/// to physical units.
+fn scale_axes(raw: [i16; 3], offsets: [i32; 3], lsb_per_unit: f64) -> [f64; 3] {
+    std::array::from_fn(|i| (raw[i] as i32 - offsets[i]) as f64 / lsb_per_unit)
+}
+
+/// Zero any axis whose magnitude is below `threshold`.
+fn dead_band(v: [f64; 3], threshold: f64) -> [f64; 3] {
+    v.map(|x| if x.abs() < threshold { 0.0 } else { x })
+}
+
 // ---------------------------------------------------------------------------
 // Unit tests
 // ---------------------------------------------------------------------------
@@ -592,6 +1146,98 @@ fn parse_sensor_frame(buf: &[u8; 14]) -> [i16; 6] {
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_temperature_scaling_per_variant() {
+        let mut buf = [0u8; 14];
+        buf[6..8].copy_from_slice(&(-3400i16).to_be_bytes());
+        let raw = parse_temperature(&buf);
+        assert!((Variant::Mpu6050.temperature(raw) - 26.53).abs() < 1e-9);
+        assert!((Variant::Mpu6500.temperature(0) - 21.0).abs() < 1e-9);
+    }
+
+    fn assert_close(got: [f64; 3], want: [f64; 3]) {
+        let ok = got.iter().zip(want).all(|(g, w)| (g - w).abs() < 1e-9);
+        assert!(ok, "got {:?}, want {:?}", got, want);
+    }
+
+    #[test]
+    fn test_scaling_golden_vectors() {
+        assert_eq!(accel_lsb_per_g(2), 16384.0);
+        assert_eq!(accel_lsb_per_g(8), 4096.0);
+        assert_eq!(accel_lsb_per_g(3), 2048.0);
+        assert_eq!(gyro_lsb_per_dps(500), 65.6);
+        assert_eq!(gyro_lsb_per_dps(0), 16.4);
+
+        // (raw, offsets, LSB per unit, scaled)
+        type Case = ([i16; 3], [i32; 3], f64, [f64; 3]);
+        let (g2, g16, dps2000) = (16384.0, 2048.0, 16.4);
+        let cases: [Case; 4] = [
+            // Level at ±2 g after calibration: exactly 1 g on Z
+            ([120, -80, 16504], [120, -80, 120], g2, [0.0, 0.0, 1.0]),
+            // ±16 g, 2 g forward and -0.5 g sideways
+            ([4096, -1024, 2048], [0; 3], g16, [2.0, -0.5, 1.0]),
+            // ±2000 °/s, an offset larger than the reading goes negative
+            ([328, 0, 0], [0, 164, 0], dps2000, [20.0, -10.0, 0.0]),
+            // Full-scale raw values don't overflow with an offset applied
+            (
+                [i16::MIN, i16::MAX, 0],
+                [100, -100, 0],
+                1.0,
+                [-32868.0, 32867.0, 0.0],
+            ),
+        ];
+        for (raw, offsets, lsb, want) in cases {
+            assert_close(scale_axes(raw, offsets, lsb), want);
+        }
+
+        assert_eq!(dead_band([0.05, -0.2, -0.05], 0.1), [0.0, -0.2, 0.0]);
+    }
+
+    #[test]
+    fn test_dlpf_setting_rounds_up() {
+        assert_eq!(dlpf_setting(GYRO_DLPF, 41), (41, 3));
+        assert_eq!(dlpf_setting(GYRO_DLPF, 50), (92, 2));
+        assert_eq!(dlpf_setting(GYRO_DLPF, 0), (5, 6));
+        assert_eq!(dlpf_setting(ACCEL_DLPF, 44), (45, 3));
+        assert_eq!(dlpf_setting(ACCEL_DLPF, 5000), (1046, 0x08));
+    }
+
+    #[test]
+    fn test_accel_aliases_only_when_asked() {
+        let keys = |s: &MPU6500Settings| -> Vec<&str> {
+            MPU6500::build_descriptors(s).iter().map(|d| d.key).collect()
+        };
+        let canonical = keys(&MPU6500Settings::default());
+        assert!(canonical.contains(&"g_force_x"));
+        assert!(!canonical.contains(&"accel_x"));
+
+        let legacy = keys(&MPU6500Settings {
+            legacy_accel_fields: true,
+            ..Default::default()
+        });
+        assert!(legacy.starts_with(&["accel_x", "accel_y", "accel_z", "g_force_x"]));
+    }
+
+    #[test]
+    fn test_self_test_limits() {
+        let normal = [0.0, 0.0, 16384.0, 0.0, 0.0, 0.0];
+        // Code 100 trims accel to ~7001 LSB; gyro untrimmed needs 60 °/s
+        let codes = [100, 100, 100, 0, 0, 0];
+        let active = [
+            7000.0,
+            7100.0,
+            16384.0 + 1000.0,
+            80.0 * 131.0,
+            61.0 * 131.0,
+            10.0,
+        ];
+        let report = evaluate_self_test(normal, active, codes);
+        let passed: Vec<bool> = report.checks.iter().map(SelfTestCheck::passed).collect();
+        assert_eq!(passed, [true, true, false, true, true, false]);
+        assert!(!report.passed());
+        assert!((report.checks[0].response - 7000.0 / 16384.0).abs() < 1e-9);
+    }
+
     #[test]
     fn test_parse_sensor_frame_zeros() {
         let buf = [0u8; 14];
@@ -643,6 +1289,25 @@ mod tests {
         assert_eq!(out[5], -2);
     }
 
+    #[test]
+    fn test_gyro_noise_still_is_zero() {
+        let mut n = GyroNoise::default();
+        for _ in 0..50 {
+            n.push([12, -4, 7]);
+        }
+        assert_eq!(n.std_dev(), 0.0);
+    }
+
+    #[test]
+    fn test_gyro_noise_detects_motion() {
+        let mut n = GyroNoise::default();
+        for i in 0..50 {
+            let v = if i % 2 == 0 { 100 } else { -100 };
+            n.push([v, v, v]);
+        }
+        assert!(n.std_dev() > 90.0, "std_dev = {}", n.std_dev());
+    }
+
     #[test]
     fn test_parse_sensor_frame_temp_bytes_ignored() {
         let mut buf = [0u8; 14];
@@ -5,8 +5,7 @@
 //! Uses one-shot mode with high repeatability.
 
 use anyhow::Result;
-use chrono::Utc;
-use std::collections::HashMap;
+use indexmap::IndexMap;
 
 use crate::config::SensorConfig;
 use crate::sensors::{FieldDescriptor, Sensor, SensorData, VizType};
@@ -20,12 +19,14 @@ static FIELDS: &[FieldDescriptor] = &[
         key: "temperature",
         label: "Temperature",
         viz: VizType::Numeric { unit: "°C" },
+        range: None,
         group: Some("SHT31"),
     },
     FieldDescriptor {
         key: "humidity_pct",
         label: "Humidity",
         viz: VizType::Numeric { unit: "%" },
+        range: Some((0.0, 100.0)),
         group: None,
     },
 ];
@@ -97,13 +98,10 @@ impl Sensor for Sht31 {
         let temperature = -45.0 + 175.0 * t_raw / 65535.0;
         let humidity = 100.0 * h_raw / 65535.0;
 
-        let mut fields = HashMap::new();
+        let mut fields = IndexMap::new();
         fields.insert("temperature".into(), temperature);
         fields.insert("humidity_pct".into(), humidity);
-        Ok(SensorData {
-            timestamp: Utc::now(),
-            fields,
-        })
+        Ok(SensorData::new(fields))
     }
 
     fn name(&self) -> &str {
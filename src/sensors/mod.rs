@@ -6,13 +6,17 @@
 
 pub mod gpio;
 pub mod i2c;
+pub mod process;
 pub mod registry;
 pub mod serial;
 pub mod synthetic;
 
+use std::borrow::Cow;
+
 use anyhow::Result;
 use chrono::{DateTime, Utc};
-use std::collections::HashMap;
+use indexmap::IndexMap;
+use serde::{Deserialize, Serialize};
 
 // ---------------------------------------------------------------------------
 // Field descriptor — tells the TUI how to display a field
@@ -25,6 +29,8 @@ pub enum VizType {
     AngularRate,
     Angle,
     Numeric { unit: &'static str },
+    /// Non-numeric channel carried in [`SensorData::values`]
+    Text,
 }
 
 #[derive(Debug, Clone)]
@@ -32,18 +38,341 @@ pub struct FieldDescriptor {
     pub key: &'static str,
     pub label: &'static str,
     pub viz: VizType,
+    /// Expected `(min, max)` for gauges; `None` uses the viz type's default
+    pub range: Option<(f64, f64)>,
     /// Group header shown in the data panel (None = continuation)
     pub group: Option<&'static str>,
 }
 
+impl VizType {
+    pub fn unit(&self) -> &'static str {
+        match self {
+            VizType::Value => "",
+            VizType::GForce => "G",
+            VizType::AngularRate => "°/s",
+            VizType::Angle => "°",
+            VizType::Numeric { unit } => unit,
+            VizType::Text => "",
+        }
+    }
+
+    /// Channel type advertised in the MQTT schema message.
+    pub fn value_type(&self) -> &'static str {
+        match self {
+            VizType::Text => "text",
+            _ => "number",
+        }
+    }
+
+    fn default_range(&self) -> Option<(f64, f64)> {
+        match self {
+            VizType::GForce => Some((-4.0, 4.0)),
+            VizType::AngularRate => Some((-250.0, 250.0)),
+            VizType::Angle => Some((-90.0, 90.0)),
+            VizType::Value | VizType::Numeric { .. } | VizType::Text => None,
+        }
+    }
+}
+
+/// Owned, serialisable copy of a [`FieldDescriptor`] — what the TUI (and
+/// attached clients) use to label, scale and group a sensor's fields.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FieldMeta {
+    pub key: String,
+    pub label: String,
+    pub unit: String,
+    pub range: Option<(f64, f64)>,
+    pub group: Option<String>,
+    /// Acceleration in G — drives the G-meter / G-ball
+    pub is_g_force: bool,
+}
+
+impl From<&FieldDescriptor> for FieldMeta {
+    fn from(d: &FieldDescriptor) -> Self {
+        Self {
+            key: d.key.to_string(),
+            label: d.label.to_string(),
+            unit: d.viz.unit().to_string(),
+            range: d.range.or_else(|| d.viz.default_range()),
+            group: d.group.map(str::to_string),
+            is_g_force: matches!(d.viz, VizType::GForce),
+        }
+    }
+}
+
+impl FieldMeta {
+    /// `+0.012 G`, `21.40 °C`, `3 count` — precision chosen from the range.
+    pub fn format(&self, value: f64) -> String {
+        let span = self.range.map(|(lo, hi)| hi - lo).unwrap_or(value.abs());
+        let precision = if span > 10.0 && value.fract() == 0.0 {
+            0 // counts, AQI, …
+        } else if span <= 10.0 {
+            3
+        } else if span <= 1000.0 {
+            2
+        } else {
+            1
+        };
+        let signed = self.range.is_some_and(|(lo, _)| lo < 0.0);
+        let num = if signed {
+            format!("{:+.*}", precision, value)
+        } else {
+            format!("{:.*}", precision, value)
+        };
+        if self.unit.is_empty() {
+            num
+        } else {
+            format!("{} {}", num, self.unit)
+        }
+    }
+
+    /// This field's value in `data`, numeric or not, formatted for display.
+    pub fn display(&self, data: &SensorData) -> Option<String> {
+        match data.get(&self.key)? {
+            ChannelValue::Float(v) => Some(self.format(v)),
+            other if self.unit.is_empty() => Some(other.to_string()),
+            other => Some(format!("{} {}", other, self.unit)),
+        }
+    }
+
+    /// Position of `value` within the expected range, 0.0–1.0.
+    pub fn fraction(&self, value: f64) -> Option<f64> {
+        let (lo, hi) = self.range?;
+        if hi <= lo {
+            return None;
+        }
+        Some(((value - lo) / (hi - lo)).clamp(0.0, 1.0))
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Coordinate frame
+// ---------------------------------------------------------------------------
+
+/// Axis convention of a driver's vector channels, published in `SCHEMA`
+/// so 3-D visualisers don't have to guess it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct AxisFrame {
+    /// Short identifier
+    pub id: &'static str,
+    /// Where each positive axis points
+    pub x: &'static str,
+    pub y: &'static str,
+    pub z: &'static str,
+    pub handedness: &'static str,
+    /// Layout and meaning of the `quat_*` channels, when published
+    pub quaternion: &'static str,
+}
+
+/// The chip's own axes, as marked on the package: right-handed, reading
+/// +1 g on Z when lying face up.  How they map onto the vehicle depends on
+/// how the board is mounted.
+pub const FRAME_CHIP_Z_UP: AxisFrame = AxisFrame {
+    id: "chip_rh_z_up",
+    x: "chip +X",
+    y: "chip +Y",
+    z: "out of the top face",
+    handedness: "right",
+    quaternion: "w,x,y,z Hamilton, rotates the chip frame onto a level frame with Z up; no heading",
+};
+
 // ---------------------------------------------------------------------------
 // Sensor data
 // ---------------------------------------------------------------------------
 
-#[derive(Debug, Clone)]
+/// A non-numeric channel value (GPS fix type, fault codes, text status).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum ChannelValue {
+    Bool(bool),
+    Int(i64),
+    Float(f64),
+    Text(String),
+}
+
+impl std::fmt::Display for ChannelValue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ChannelValue::Bool(b) => write!(f, "{}", b),
+            ChannelValue::Int(i) => write!(f, "{}", i),
+            ChannelValue::Float(v) => write!(f, "{}", v),
+            ChannelValue::Text(t) => f.write_str(t),
+        }
+    }
+}
+
+/// How far a channel's value can be trusted.  Channels without an entry in
+/// [`SensorData::quality`] are `Good`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Quality {
+    #[default]
+    Good,
+    /// Last known value, not refreshed this cycle (e.g. GPS without fix)
+    Stale,
+    /// Derived or interpolated rather than measured
+    Estimated,
+    /// Out of range or failed a plausibility check
+    Invalid,
+    /// The device reported a fault for this channel
+    Fault,
+}
+
+impl Quality {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Quality::Good => "good",
+            Quality::Stale => "stale",
+            Quality::Estimated => "estimated",
+            Quality::Invalid => "invalid",
+            Quality::Fault => "fault",
+        }
+    }
+}
+
+/// Name of a numeric channel.  Drivers use their fixed channel names as
+/// `&'static str`, so filling a reading doesn't allocate for its keys;
+/// names built at runtime (config, derived channels) are owned.
+pub type ChannelKey = Cow<'static, str>;
+
+/// One reading.  Numeric channels live in `fields` (what history, charts
+/// and the FILTERED/DERIVED topics use); units come from the driver's
+/// [`FieldDescriptor`]s.  `values`, `quality` and `diagnostics` are
+/// optional extras and are omitted from JSON when empty, so existing
+/// consumers see no change.  All maps keep insertion order, so published
+/// JSON lists channels in the order the driver produced them.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct SensorData {
     pub timestamp: DateTime<Utc>,
-    pub fields: HashMap<String, f64>,
+    pub fields: IndexMap<ChannelKey, f64>,
+    /// Integer / text / boolean channels
+    #[serde(default, skip_serializing_if = "IndexMap::is_empty")]
+    pub values: IndexMap<String, ChannelValue>,
+    /// Per-channel quality flags; only non-`Good` channels are listed
+    #[serde(default, skip_serializing_if = "IndexMap::is_empty")]
+    pub quality: IndexMap<String, Quality>,
+    /// Filter internals for tuning, published on their own topic when a
+    /// driver is asked for them
+    #[serde(default, skip_serializing_if = "IndexMap::is_empty")]
+    pub diagnostics: IndexMap<String, f64>,
+    /// Seconds since the service started, from a clock that never steps;
+    /// set by the sensor loop (see [`crate::clock`])
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub monotonic: Option<f64>,
+    /// Taken while the system clock wasn't synchronised, so `timestamp`
+    /// may be off
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub clock_unsynced: bool,
+}
+
+impl SensorData {
+    /// A reading timestamped now with only numeric channels.
+    pub fn new(fields: IndexMap<ChannelKey, f64>) -> Self {
+        Self {
+            timestamp: Utc::now(),
+            fields,
+            ..Default::default()
+        }
+    }
+
+    pub fn set_value(&mut self, key: impl Into<String>, value: ChannelValue) {
+        self.values.insert(key.into(), value);
+    }
+
+    pub fn set_quality(&mut self, key: impl Into<String>, quality: Quality) {
+        let key = key.into();
+        if quality == Quality::Good {
+            self.quality.shift_remove(&key);
+        } else {
+            self.quality.insert(key, quality);
+        }
+    }
+
+    pub fn quality(&self, key: &str) -> Quality {
+        self.quality.get(key).copied().unwrap_or_default()
+    }
+
+    /// Value of any channel, numeric or not.
+    pub fn get(&self, key: &str) -> Option<ChannelValue> {
+        self.fields
+            .get(key)
+            .map(|&v| ChannelValue::Float(v))
+            .or_else(|| self.values.get(key).cloned())
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Calibration progress
+// ---------------------------------------------------------------------------
+
+/// Gyro noise (°/s std-dev) below which the sensor is considered still.
+pub const STILLNESS_THRESHOLD_DPS: f64 = 1.0;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct CalibrationProgress {
+    pub collected: u32,
+    pub total: u32,
+    /// Gyro noise over the samples so far (°/s std-dev); a moving sensor
+    /// produces bad offsets.
+    pub motion_dps: f64,
+}
+
+impl CalibrationProgress {
+    pub fn fraction(&self) -> f64 {
+        if self.total == 0 {
+            return 0.0;
+        }
+        (self.collected as f64 / self.total as f64).clamp(0.0, 1.0)
+    }
+
+    pub fn is_still(&self) -> bool {
+        self.motion_dps < STILLNESS_THRESHOLD_DPS
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Self-test
+// ---------------------------------------------------------------------------
+
+/// One axis of a factory self-test.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SelfTestCheck {
+    pub name: &'static str,
+    pub unit: &'static str,
+    /// Change in the reading with the self-test actuation on
+    pub response: f64,
+    /// Accepted response range
+    pub min: f64,
+    pub max: f64,
+}
+
+impl SelfTestCheck {
+    pub fn passed(&self) -> bool {
+        (self.min..=self.max).contains(&self.response)
+    }
+}
+
+impl std::fmt::Display for SelfTestCheck {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:<8} {:8.3} {}, expected ", self.name, self.response, self.unit)?;
+        if self.max.is_finite() {
+            write!(f, "{:.3}–{:.3}", self.min, self.max)?;
+        } else {
+            write!(f, "≥ {:.3}", self.min)?;
+        }
+        write!(f, " — {}", if self.passed() { "ok" } else { "FAIL" })
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SelfTestReport {
+    pub checks: Vec<SelfTestCheck>,
+}
+
+impl SelfTestReport {
+    pub fn passed(&self) -> bool {
+        self.checks.iter().all(SelfTestCheck::passed)
+    }
 }
 
 // ---------------------------------------------------------------------------
@@ -64,6 +393,24 @@ pub trait Sensor: Send + 'static {
     fn recalibrate(&mut self) -> Result<()> {
         Ok(())
     }
+    /// Recalibrate while reporting progress.  Drivers with a long sampling
+    /// phase override this; the default just calls [`Sensor::recalibrate`].
+    fn recalibrate_with_progress(
+        &mut self,
+        _progress: &mut dyn FnMut(CalibrationProgress),
+    ) -> Result<()> {
+        self.recalibrate()
+    }
+    /// Run the chip's built-in self-test, leaving the sensor configured as
+    /// after `init`.  `None` for drivers without one.
+    fn self_test(&mut self) -> Result<Option<SelfTestReport>> {
+        Ok(None)
+    }
     /// Ordered field descriptors for TUI rendering.
     fn field_descriptors(&self) -> &[FieldDescriptor];
+    /// Axis convention of the vector channels; `None` for drivers without
+    /// any.
+    fn frame(&self) -> Option<&'static AxisFrame> {
+        None
+    }
 }
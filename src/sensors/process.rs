@@ -0,0 +1,190 @@
+//! The daemon's own resource use as a sensor (`driver = "process"`).
+//!
+//! Reports this process's CPU load, resident memory and thread count from
+//! `/proc/self`, plus the worst sensor loop latency since the previous
+//! reading: how late any sensor task woke up for a scheduled read.  Dropped
+//! samples on a Pi usually line up with one of these climbing, so having
+//! them in the same stream makes the cause easy to spot.  Linux only; the
+//! connection block is required by the config format but ignored.
+//!
+//! | field           | unit | notes                                    |
+//! |-----------------|------|------------------------------------------|
+//! | cpu_pct         | %    | of one core, over at least `CPU_WINDOW`  |
+//! | rss_mb          | MB   | resident set size                        |
+//! | threads         |      |                                          |
+//! | loop_latency_ms | ms   | worst wake-up delay across sensor tasks  |
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+use anyhow::{Context, Result};
+use indexmap::IndexMap;
+
+use crate::config::SensorConfig;
+use crate::sensors::{FieldDescriptor, Sensor, SensorData, VizType};
+
+/// Kernel clock ticks per second in `/proc/<pid>/stat` (`USER_HZ`), fixed
+/// at 100 on every architecture Linux exposes it on.
+const USER_HZ: f64 = 100.0;
+
+/// Shortest span `cpu_pct` is averaged over; CPU time is counted in 10 ms
+/// ticks, so shorter windows at a fast sample rate would just flicker.
+const CPU_WINDOW: Duration = Duration::from_secs(1);
+
+/// Worst loop latency since the last reading, in microseconds.
+static LOOP_LATENCY_US: AtomicU64 = AtomicU64::new(0);
+
+/// Note how late a sensor task woke up for a read.
+pub fn record_loop_latency(late: Duration) {
+    let us = late.as_micros().min(u64::MAX as u128) as u64;
+    LOOP_LATENCY_US.fetch_max(us, Ordering::Relaxed);
+}
+
+static FIELDS: &[FieldDescriptor] = &[
+    FieldDescriptor {
+        key: "cpu_pct",
+        label: "CPU",
+        viz: VizType::Numeric { unit: "%" },
+        range: Some((0.0, 100.0)),
+        group: Some("Process"),
+    },
+    FieldDescriptor {
+        key: "rss_mb",
+        label: "Memory (RSS)",
+        viz: VizType::Numeric { unit: "MB" },
+        range: None,
+        group: None,
+    },
+    FieldDescriptor {
+        key: "threads",
+        label: "Threads",
+        viz: VizType::Value,
+        range: None,
+        group: None,
+    },
+    FieldDescriptor {
+        key: "loop_latency_ms",
+        label: "Loop latency",
+        viz: VizType::Numeric { unit: "ms" },
+        range: None,
+        group: Some("Scheduling"),
+    },
+];
+
+pub struct ProcessSensor {
+    name: String,
+    enabled: bool,
+    /// CPU seconds used and when, at the start of the current window
+    last_cpu: Option<(f64, Instant)>,
+    cpu_pct: f64,
+}
+
+impl ProcessSensor {
+    pub fn from_config(cfg: &SensorConfig) -> Result<Self> {
+        Ok(Self {
+            name: cfg.name.clone(),
+            enabled: cfg.enabled,
+            last_cpu: None,
+            cpu_pct: 0.0,
+        })
+    }
+}
+
+/// User plus system CPU seconds from `/proc/<pid>/stat`.
+fn parse_cpu_secs(stat: &str) -> Option<f64> {
+    // The command name can contain spaces, so count fields from after it
+    let rest = &stat[stat.rfind(')')? + 2..];
+    let mut fields = rest.split_whitespace();
+    // utime and stime are fields 14 and 15; `rest` starts at field 3
+    let utime: f64 = fields.nth(11)?.parse().ok()?;
+    let stime: f64 = fields.next()?.parse().ok()?;
+    Some((utime + stime) / USER_HZ)
+}
+
+/// `(VmRSS in MB, Threads)` from `/proc/<pid>/status`.
+fn parse_status(status: &str) -> Option<(f64, f64)> {
+    let field = |name: &str| {
+        status
+            .lines()
+            .find_map(|l| l.strip_prefix(name))
+            .and_then(|v| v.split_whitespace().next())
+            .and_then(|v| v.parse::<f64>().ok())
+    };
+    Some((field("VmRSS:")? / 1024.0, field("Threads:")?))
+}
+
+impl Sensor for ProcessSensor {
+    fn init(&mut self) -> Result<()> {
+        std::fs::read_to_string("/proc/self/stat")
+            .context("the process driver needs /proc (Linux only)")?;
+        Ok(())
+    }
+
+    fn read(&mut self) -> Result<SensorData> {
+        let now = Instant::now();
+        let stat = std::fs::read_to_string("/proc/self/stat")?;
+        let cpu = parse_cpu_secs(&stat).context("unexpected /proc/self/stat format")?;
+        let status = std::fs::read_to_string("/proc/self/status")?;
+        let (rss_mb, threads) =
+            parse_status(&status).context("unexpected /proc/self/status format")?;
+
+        match self.last_cpu {
+            Some((prev, at)) if now.duration_since(at) >= CPU_WINDOW => {
+                self.cpu_pct = (cpu - prev) / now.duration_since(at).as_secs_f64() * 100.0;
+                self.last_cpu = Some((cpu, now));
+            }
+            Some(_) => {}
+            None => self.last_cpu = Some((cpu, now)),
+        }
+        let latency_us = LOOP_LATENCY_US.swap(0, Ordering::Relaxed);
+
+        let mut fields = IndexMap::new();
+        fields.insert("cpu_pct".into(), self.cpu_pct);
+        fields.insert("rss_mb".into(), rss_mb);
+        fields.insert("threads".into(), threads);
+        fields.insert("loop_latency_ms".into(), latency_us as f64 / 1000.0);
+        Ok(SensorData::new(fields))
+    }
+
+    fn name(&self) -> &str {
+        &self.name
+    }
+    fn driver_name(&self) -> &str {
+        "process"
+    }
+    fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+    fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+    }
+    fn field_descriptors(&self) -> &[FieldDescriptor] {
+        FIELDS
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Unit tests
+// ---------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_proc_files() {
+        let stat = "4242 (sensors to mqtt) S 1 4242 4242 0 -1 4194560 1234 0 0 0 \
+                    250 50 0 0 20 0 9 0 1000 123456789 2048 18446744073709551615";
+        assert_eq!(parse_cpu_secs(stat), Some(3.0));
+        let status = "Name:\tsensors-to-mqtt\nVmRSS:\t   10240 kB\nThreads:\t9\n";
+        assert_eq!(parse_status(status), Some((10.0, 9.0)));
+        assert_eq!(parse_status("Name:\tx\n"), None);
+    }
+
+    #[test]
+    fn test_loop_latency_is_worst_since_last_read() {
+        record_loop_latency(Duration::from_micros(1500));
+        record_loop_latency(Duration::from_micros(300));
+        assert!(LOOP_LATENCY_US.swap(0, Ordering::Relaxed) >= 1500);
+    }
+}
@@ -5,7 +5,9 @@
 //! | driver        | connection           | notes                          |
 //! |---------------|----------------------|-------------------------------|
 //! | `synthetic`   | any                  | always available               |
+//! | `process`     | any                  | always available, Linux only   |
 //! | `mpu6500`     | i2c / tcp            | i2c: Linux only                |
+//! | `mpu6050`     | i2c / tcp            | i2c: Linux only                |
 //! | `bmp280`      | i2c / tcp            | i2c: Linux only                |
 //! | `bme280`      | i2c / tcp            | i2c: Linux only                |
 //! | `sht31`       | i2c / tcp            | i2c: Linux only                |
@@ -14,37 +16,85 @@
 //! | `ads1115`     | i2c / tcp            | i2c: Linux only                |
 //! | `gpio_button` | gpio / tcp           | gpio: Linux only               |
 //! | `sds011`      | serial / tcp         | serial: Linux / macOS          |
+//!
+//! Each driver but `synthetic` and `process` has a cargo feature of the same name
+//! (`mpu6500` also covers `mpu6050`, `gpio-button` is `gpio_button`); all
+//! are on by default.
 
 use super::Sensor;
+use super::process::ProcessSensor;
 use super::synthetic::SyntheticSensor;
 use crate::config::SensorConfig;
 use anyhow::{Result, bail};
 
+/// Drivers compiled into this build (see the cargo features).
+pub const DRIVERS: &[&str] = &[
+    "synthetic",
+    "process",
+    #[cfg(feature = "mpu6500")]
+    "mpu6500",
+    #[cfg(feature = "mpu6500")]
+    "mpu6050",
+    #[cfg(feature = "bmp280")]
+    "bmp280",
+    #[cfg(feature = "bme280")]
+    "bme280",
+    #[cfg(feature = "sht31")]
+    "sht31",
+    #[cfg(feature = "bh1750")]
+    "bh1750",
+    #[cfg(feature = "ina219")]
+    "ina219",
+    #[cfg(feature = "ads1115")]
+    "ads1115",
+    #[cfg(feature = "gpio-button")]
+    "gpio_button",
+    #[cfg(feature = "sds011")]
+    "sds011",
+];
+
 /// Create a boxed [`Sensor`] from configuration.
 pub fn create_sensor(config: &SensorConfig) -> Result<Box<dyn Sensor>> {
     match config.driver.as_str() {
         "synthetic" => Ok(Box::new(SyntheticSensor::from_config(config)?)),
+        "process" => Ok(Box::new(ProcessSensor::from_config(config)?)),
 
+        #[cfg(feature = "mpu6500")]
         "mpu6500" => Ok(Box::new(super::i2c::mpu6500::MPU6500::from_config(config)?)),
+        #[cfg(feature = "mpu6500")]
+        "mpu6050" => Ok(Box::new(
+            super::i2c::mpu6500::MPU6500::from_config_variant(
+                config,
+                super::i2c::mpu6500::Variant::Mpu6050,
+            )?,
+        )),
+        #[cfg(feature = "bmp280")]
         "bmp280" => Ok(Box::new(super::i2c::bmp280::Bmp280::from_config(config)?)),
+        #[cfg(feature = "bme280")]
         "bme280" => Ok(Box::new(super::i2c::bme280::Bme280::from_config(config)?)),
+        #[cfg(feature = "sht31")]
         "sht31" => Ok(Box::new(super::i2c::sht31::Sht31::from_config(config)?)),
+        #[cfg(feature = "bh1750")]
         "bh1750" => Ok(Box::new(super::i2c::bh1750::Bh1750::from_config(config)?)),
+        #[cfg(feature = "ina219")]
         "ina219" => Ok(Box::new(super::i2c::ina219::Ina219::from_config(config)?)),
+        #[cfg(feature = "ads1115")]
         "ads1115" => Ok(Box::new(super::i2c::ads1115::Ads1115::from_config(config)?)),
 
+        #[cfg(feature = "gpio-button")]
         "gpio_button" => Ok(Box::new(
             super::gpio::button::GpioButton::from_config(config)?,
         )),
 
+        #[cfg(feature = "sds011")]
         "sds011" => Ok(Box::new(
             super::serial::sds011::Sds011::from_config(config)?,
         )),
 
         other => bail!(
-            "Unknown sensor driver: '{}'. Available: synthetic, mpu6500, \
-            bmp280, bme280, sht31, bh1750, ina219, ads1115, gpio_button, sds011",
-            other
+            "Unknown sensor driver: '{}'. Available: {}",
+            other,
+            DRIVERS.join(", ")
         ),
     }
 }
@@ -1 +1,2 @@
+#[cfg(feature = "sds011")]
 pub mod sds011;
@@ -17,8 +17,7 @@
 //! Configure with `driver = "sds011"` in `settings.toml`.
 
 use anyhow::{Context, Result, bail};
-use chrono::Utc;
-use std::collections::HashMap;
+use indexmap::IndexMap;
 use std::io::Read;
 use std::net::TcpStream;
 use std::time::Duration;
@@ -45,24 +44,28 @@ static FIELDS: &[FieldDescriptor] = &[
         key: "pm2_5",
         label: "PM2.5",
         viz: VizType::Numeric { unit: "μg/m³" },
+        range: None,
         group: Some("PARTICULATE MATTER"),
     },
     FieldDescriptor {
         key: "pm10",
         label: "PM10",
         viz: VizType::Numeric { unit: "μg/m³" },
+        range: None,
         group: None,
     },
     FieldDescriptor {
         key: "aqi_pm2_5",
         label: "AQI (PM2.5)",
         viz: VizType::Numeric { unit: "" },
+        range: Some((0.0, 500.0)),
         group: Some("AIR QUALITY"),
     },
     FieldDescriptor {
         key: "aqi_pm10",
         label: "AQI (PM10)",
         viz: VizType::Numeric { unit: "" },
+        range: Some((0.0, 500.0)),
         group: None,
     },
 ];
@@ -207,16 +210,13 @@ impl Sensor for Sds011 {
         let pm2_5 = (frame[3] as f64 * 256.0 + frame[2] as f64) / 10.0;
         let pm10 = (frame[5] as f64 * 256.0 + frame[4] as f64) / 10.0;
 
-        let mut fields = HashMap::new();
+        let mut fields = IndexMap::new();
         fields.insert("pm2_5".into(), pm2_5);
         fields.insert("pm10".into(), pm10);
         fields.insert("aqi_pm2_5".into(), aqi_pm2_5(pm2_5));
         fields.insert("aqi_pm10".into(), aqi_pm10(pm10));
 
-        Ok(SensorData {
-            timestamp: Utc::now(),
-            fields,
-        })
+        Ok(SensorData::new(fields))
     }
 
     fn name(&self) -> &str {
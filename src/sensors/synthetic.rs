@@ -25,9 +25,8 @@
 //! | tilt_angle     | ±45 °       | coupled to g_force_z           |
 
 use anyhow::Result;
-use chrono::Utc;
+use indexmap::IndexMap;
 use serde::Deserialize;
-use std::collections::HashMap;
 use std::f64::consts::PI;
 use std::time::Instant;
 
@@ -89,90 +88,105 @@ static FIELDS: &[FieldDescriptor] = &[
         key: "g_force_x",
         label: "G-Force X",
         viz: VizType::GForce,
+        range: None,
         group: Some("Acceleration"),
     },
     FieldDescriptor {
         key: "g_force_y",
         label: "G-Force Y",
         viz: VizType::GForce,
+        range: None,
         group: None,
     },
     FieldDescriptor {
         key: "g_force_z",
         label: "G-Force Z",
         viz: VizType::GForce,
+        range: None,
         group: None,
     },
     FieldDescriptor {
         key: "combined_g",
         label: "Combined G",
         viz: VizType::GForce,
+        range: None,
         group: None,
     },
     FieldDescriptor {
         key: "tilt_angle",
         label: "Tilt Angle",
         viz: VizType::Angle,
+        range: None,
         group: Some("Orientation"),
     },
     FieldDescriptor {
         key: "gyro_x",
         label: "Gyro X",
         viz: VizType::AngularRate,
+        range: None,
         group: Some("Gyroscope"),
     },
     FieldDescriptor {
         key: "gyro_y",
         label: "Gyro Y",
         viz: VizType::AngularRate,
+        range: None,
         group: None,
     },
     FieldDescriptor {
         key: "gyro_z",
         label: "Gyro Z",
         viz: VizType::AngularRate,
+        range: None,
         group: None,
     },
     FieldDescriptor {
         key: "temperature",
         label: "Temperature",
         viz: VizType::Numeric { unit: "°C" },
+        range: None,
         group: Some("Environment"),
     },
     FieldDescriptor {
         key: "pressure",
         label: "Pressure",
         viz: VizType::Numeric { unit: "hPa" },
+        range: None,
         group: None,
     },
     FieldDescriptor {
         key: "humidity",
         label: "Humidity",
         viz: VizType::Numeric { unit: "%" },
+        range: Some((0.0, 100.0)),
         group: None,
     },
     FieldDescriptor {
         key: "battery_voltage",
         label: "Battery",
         viz: VizType::Numeric { unit: "V" },
+        range: None,
         group: Some("Electrical"),
     },
     FieldDescriptor {
         key: "rpm",
         label: "Engine RPM",
         viz: VizType::Numeric { unit: "rpm" },
+        range: None,
         group: Some("Engine"),
     },
     FieldDescriptor {
         key: "speed_kmh",
         label: "Speed",
         viz: VizType::Numeric { unit: "km/h" },
+        range: None,
         group: None,
     },
     FieldDescriptor {
         key: "throttle_pct",
         label: "Throttle",
         viz: VizType::Numeric { unit: "%" },
+        range: Some((0.0, 100.0)),
         group: None,
     },
 ];
@@ -251,7 +265,7 @@ impl Sensor for SyntheticSensor {
         let throttle_pct =
             (100.0 * (2.0 * PI * 0.15 * t).sin().abs() + n(13) * 3.0).clamp(0.0, 100.0);
 
-        let mut fields = HashMap::new();
+        let mut fields = IndexMap::new();
         fields.insert("g_force_x".into(), gx);
         fields.insert("g_force_y".into(), gy);
         fields.insert("g_force_z".into(), gz);
@@ -268,10 +282,7 @@ impl Sensor for SyntheticSensor {
         fields.insert("speed_kmh".into(), speed_kmh);
         fields.insert("throttle_pct".into(), throttle_pct);
 
-        Ok(SensorData {
-            timestamp: Utc::now(),
-            fields,
-        })
+        Ok(SensorData::new(fields))
     }
 
     fn name(&self) -> &str {
@@ -303,6 +314,7 @@ impl Sensor for SyntheticSensor {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::sensors::FieldMeta;
 
     fn make_sensor() -> SyntheticSensor {
         let mut s = SyntheticSensor::new("test");
@@ -323,6 +335,27 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_field_meta_units_and_ranges() {
+        let s = make_sensor();
+        let metas: Vec<FieldMeta> = s.field_descriptors().iter().map(FieldMeta::from).collect();
+        let gx = metas.iter().find(|m| m.key == "g_force_x").unwrap();
+        assert!(gx.is_g_force);
+        assert_eq!(gx.unit, "G");
+        assert_eq!(gx.range, Some((-4.0, 4.0)));
+        assert_eq!(gx.format(0.5), "+0.500 G");
+        assert_eq!(gx.fraction(0.0), Some(0.5));
+
+        let rpm = metas.iter().find(|m| m.key == "rpm").unwrap();
+        assert!(!rpm.is_g_force);
+        assert_eq!(rpm.format(1500.0), "1500 rpm");
+        assert_eq!(rpm.fraction(1500.0), None);
+
+        let thr = metas.iter().find(|m| m.key == "throttle_pct").unwrap();
+        assert_eq!(thr.format(42.5), "42.50 %");
+        assert_eq!(thr.fraction(150.0), Some(1.0));
+    }
+
     #[test]
     fn test_g_force_bounds() {
         let mut s = make_sensor();
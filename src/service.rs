@@ -1,13 +1,49 @@
 //! Per-sensor Tokio tasks and service lifecycle.
 
+use std::borrow::Cow;
+use std::sync::Arc;
+
+use indexmap::IndexMap;
 use tokio_util::sync::CancellationToken;
-use tracing::{error, info, warn};
+use tracing::{debug, error, info, warn};
 
-use crate::config::SensorConfig;
-use crate::models::{AppState, SensorHistory, SensorStatus, SharedState};
-use crate::mqtt_handler::MqttHandle;
+use crate::activity::ActivityMonitor;
+use crate::aggregation::Aggregator;
+use crate::alerts::{AlertChange, AlertMonitor};
+use crate::anomaly::{self, AnomalyMonitor};
+use crate::clock;
+use crate::config::{
+    AdaptiveRateConfig, AlertConfig, AnomalyConfig, AppConfig, ChannelFilter, DerivativeConfig,
+    DifferenceConfig, DriftConfig, DynamicsConfig, ErrorBudgetConfig, IgnitionConfig,
+    IntegralConfig, LapsConfig, SamplingConfig, SensorConfig, SlipConfig, StuckValuesConfig,
+    TransformConfig, VibrationMetricsConfig,
+};
+use crate::derivatives::{self, Differentiator};
+use crate::differences;
+use crate::drift::DriftMonitor;
+use crate::dynamics::{GpsFix, VehicleDynamics};
+use crate::error_budget::ErrorBudget;
+use crate::ignition::IgnitionMonitor;
+use crate::integrals::{self, Integrator};
+use crate::laps::{LapEvent, LapTimer, format_lap};
+use crate::models::{
+    AppState, CalibrationState, PublishMode, SensorCommand, SensorHistory, SensorStatus,
+    SharedState, StatusLevel,
+};
+use crate::mqtt_handler::{MqttHandle, PublishMsg};
+use crate::payload::Reading;
+use crate::processing::imu::ImuChannels;
+use crate::processing::stuck::{StuckChange, StuckDetector};
+use crate::processing::timing::SampleTiming;
+use crate::read_order::ReadTurn;
+use crate::sensors::process::record_loop_latency;
 use crate::sensors::registry::create_sensor;
-use crate::sensors::{Sensor, SensorData};
+use crate::sensors::{
+    CalibrationProgress, FieldDescriptor, FieldMeta, Quality, Sensor, SensorData,
+};
+use crate::slip::SlipMonitor;
+use crate::transforms::{self, Transformer};
+use crate::vibration::{AxisMetrics, VibrationMonitor};
 
 // ---------------------------------------------------------------------------
 // Sensor reading event
@@ -22,14 +58,24 @@ pub struct SensorEvent {
 // Initialise AppState with sensor stubs (before tasks start)
 // ---------------------------------------------------------------------------
 
-pub fn register_sensors(state: &mut AppState, sensors: &[SensorConfig]) {
+/// Chart history per sensor without `history_size` or `history_seconds`.
+const DEFAULT_HISTORY_SIZE: usize = 600;
+
+/// Add every sensor to `state`, offline, with an empty chart history.  The
+/// histories share `history_max_mb` evenly.
+pub fn register_sensors(state: &mut AppState, sensors: &[SensorConfig], history_max_mb: u64) {
+    let share = (history_max_mb as usize * 1024 * 1024) / sensors.len().max(1);
     for cfg in sensors {
-        let history_size = cfg
-            .settings
-            .as_ref()
-            .and_then(|v| v.get("history_size"))
-            .and_then(|v| v.as_integer())
-            .unwrap_or(600) as usize;
+        let setting = |key| cfg.settings.as_ref().and_then(|v| v.get(key));
+        let history_secs = setting("history_seconds")
+            .and_then(|v| v.as_float().or_else(|| v.as_integer().map(|n| n as f64)))
+            .unwrap_or(0.0);
+        let history_size = match setting("history_size").and_then(|v| v.as_integer()) {
+            Some(n) => n.max(1) as usize,
+            // Just as long as the time window needs at the actual rate
+            None if history_secs > 0.0 => usize::MAX,
+            None => DEFAULT_HISTORY_SIZE,
+        };
 
         state.sensor_statuses.insert(
             cfg.name.clone(),
@@ -37,15 +83,154 @@ pub fn register_sensors(state: &mut AppState, sensors: &[SensorConfig]) {
                 name: cfg.name.clone(),
                 driver: cfg.driver.clone(),
                 connection_display: cfg.connection.to_display(),
+                group: cfg.group.clone(),
                 enabled: cfg.enabled,
                 connected: false,
                 last_error: None,
+                stuck: Vec::new(),
+                calibration: CalibrationState::Idle,
+                fields: Vec::new(),
             },
         );
 
-        state
-            .sensor_history
-            .insert(cfg.name.clone(), SensorHistory::new(history_size));
+        state.sensor_history.insert(
+            cfg.name.clone(),
+            SensorHistory::new(history_size)
+                .with_window(history_secs)
+                .with_max_bytes(share),
+        );
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Optional processing stages
+// ---------------------------------------------------------------------------
+
+/// Processing run on one sensor's readings — the top-level config sections
+/// (`[dynamics]`, `[slip]`, `[laps]`, `[drift]`, `[vibration_metrics]`,
+/// `[[transforms]]`, `[[derivatives]]`, `[[integrals]]`, `[[differences]]`,
+/// `[[anomalies]]`, `[ignition]`, `[adaptive_rate]`) that are enabled and
+/// name it, plus `[error_budget]` and `[stuck_values]`, which cover every
+/// sensor.  Channels computed from a driver's raw IMU axes ([`ImuChannels`])
+/// depend on the driver, so are set up with it.
+#[derive(Debug, Clone, Default)]
+pub struct SensorProcessing {
+    pub dynamics: Option<DynamicsConfig>,
+    pub slip: Option<SlipConfig>,
+    pub laps: Option<LapsConfig>,
+    pub drift: Option<DriftConfig>,
+    pub vibration_metrics: Option<VibrationMetricsConfig>,
+    pub transforms: Vec<TransformConfig>,
+    pub derivatives: Vec<DerivativeConfig>,
+    pub integrals: Vec<IntegralConfig>,
+    pub differences: Vec<DifferenceConfig>,
+    pub alerts: Vec<AlertConfig>,
+    pub anomalies: Vec<AnomalyConfig>,
+    pub ignition: Option<IgnitionConfig>,
+    /// Keep reading into the history while the ignition has paused sampling
+    pub buffer_while_paused: bool,
+    /// `dt_ms` and `missed_samples` (`sampling.timing_channels`)
+    pub sample_timing: bool,
+    pub adaptive_rate: Option<AdaptiveRateConfig>,
+    pub error_budget: Option<ErrorBudgetConfig>,
+    pub stuck_values: Option<StuckValuesConfig>,
+    /// Where reads queue behind other sensors' (`sampling.read_strategy`);
+    /// `None` reads as soon as due.  Shared between sensors, so set by the
+    /// caller from one [`ReadOrder`](crate::read_order::ReadOrder).
+    pub read_turn: Option<ReadTurn>,
+}
+
+impl SensorProcessing {
+    pub fn for_sensor(cfg: &AppConfig, name: &str) -> Self {
+        Self {
+            dynamics: Some(cfg.dynamics.clone()).filter(|d| d.enabled && d.sensor == name),
+            slip: Some(cfg.slip.clone()).filter(|s| s.enabled && s.sensor == name),
+            laps: Some(cfg.laps.clone()).filter(|l| l.enabled && l.gps_sensor == name),
+            drift: Some(cfg.drift.clone()).filter(|d| d.enabled && d.sensor == name),
+            vibration_metrics: Some(cfg.vibration_metrics.clone())
+                .filter(|v| v.enabled && v.sensor == name),
+            transforms: cfg
+                .transforms
+                .iter()
+                .filter(|t| t.sensor == name)
+                .cloned()
+                .collect(),
+            derivatives: cfg
+                .derivatives
+                .iter()
+                .filter(|d| d.sensor == name)
+                .cloned()
+                .collect(),
+            integrals: cfg
+                .integrals
+                .iter()
+                .filter(|i| i.sensor == name)
+                .cloned()
+                .collect(),
+            differences: cfg
+                .differences
+                .iter()
+                .filter(|d| d.sensor() == name)
+                .cloned()
+                .collect(),
+            alerts: cfg
+                .alerts
+                .iter()
+                .filter(|a| a.sensor == name)
+                .cloned()
+                .collect(),
+            anomalies: cfg
+                .anomalies
+                .iter()
+                .filter(|a| a.sensor == name)
+                .cloned()
+                .collect(),
+            ignition: Some(cfg.ignition.clone()).filter(|i| i.enabled && i.sensor == name),
+            buffer_while_paused: cfg.ignition.enabled && cfg.ignition.buffer_while_paused,
+            sample_timing: cfg.sampling.timing_channels,
+            adaptive_rate: Some(cfg.adaptive_rate.clone())
+                .filter(|a| a.enabled && a.sensor == name),
+            error_budget: Some(cfg.error_budget.clone()).filter(|e| e.enabled),
+            stuck_values: Some(cfg.stuck_values.clone()).filter(|s| s.enabled),
+            read_turn: None,
+        }
+    }
+
+    /// Channels these stages append to the driver's own.
+    pub fn extra_fields(&self) -> Vec<FieldDescriptor> {
+        let mut fields = Vec::new();
+        if self.sample_timing {
+            fields.extend_from_slice(SampleTiming::field_descriptors());
+        }
+        if self.dynamics.is_some() {
+            fields.extend_from_slice(VehicleDynamics::field_descriptors());
+        }
+        if self.slip.is_some() {
+            fields.extend_from_slice(SlipMonitor::field_descriptors());
+        }
+        if self.drift.is_some() {
+            fields.extend_from_slice(DriftMonitor::field_descriptors());
+        }
+        if self.vibration_metrics.is_some() {
+            fields.extend_from_slice(VibrationMonitor::field_descriptors());
+        }
+        fields.extend(derivatives::field_descriptors(&self.derivatives));
+        fields.extend(integrals::field_descriptors(&self.integrals));
+        fields.extend(differences::field_descriptors(&self.differences));
+        fields.extend(anomaly::field_descriptors(&self.anomalies));
+        fields
+    }
+
+    /// Channels published on `DERIVED`: the built-in ones, then the
+    /// `[[derivatives]]`, `[[integrals]]`, `[[differences]]` and
+    /// `[[anomalies]]`.
+    pub fn derived_keys(&self) -> Vec<&str> {
+        derived_keys(
+            &self.derivatives,
+            &self.integrals,
+            &self.differences,
+            &self.anomalies,
+        )
     }
 }
 
@@ -54,54 +239,242 @@ pub fn register_sensors(state: &mut AppState, sensors: &[SensorConfig]) {
 // ---------------------------------------------------------------------------
 
 /// Spawn a task that continuously reads the sensor and pushes events.
-/// Uses `spawn_blocking` for the blocking I2C read.
+/// Uses `spawn_blocking` for the blocking I2C read.  The task ends when
+/// `cancel` fires, after marking the sensor offline.
 pub fn spawn_sensor_task(
-    cfg: SensorConfig,
+    mut cfg: SensorConfig,
     state: SharedState,
     mqtt: Option<MqttHandle>,
     cancel: CancellationToken,
     base_topic: String,
-) {
+    sampling: SamplingConfig,
+    processing: SensorProcessing,
+) -> tokio::task::JoinHandle<()> {
     tokio::spawn(async move {
         let name = cfg.name.clone();
         info!("Starting sensor task for '{}'", name);
 
+        let (cmd_tx, mut cmd_rx) = tokio::sync::mpsc::channel::<SensorCommand>(4);
+        state
+            .write()
+            .await
+            .sensor_commands
+            .insert(name.clone(), cmd_tx);
+
         // Build the driver
-        let sensor_result = tokio::task::spawn_blocking({
-            let cfg2 = cfg.clone();
-            move || create_sensor(&cfg2)
-        })
-        .await;
+        let events = ErrorEvents::new(mqtt.clone(), &base_topic);
+        let Some(sensor_result) = create_with_retry(&cfg, &state, &events, &cancel).await else {
+            return;
+        };
 
+        let extra_fields = processing.extra_fields();
+        let topics = SensorTopics::new(&base_topic, cfg.topic_group.as_deref(), &name);
+        let mut publish_state = PublishState::default();
+        let mut last_snapshot: Option<std::time::Instant> = None;
+        let mut aggregator = mqtt
+            .as_ref()
+            .and_then(|h| h.aggregation.as_ref())
+            .map(Aggregator::new);
         let mut sensor: Box<dyn Sensor> = match sensor_result {
-            Ok(Ok(s)) => {
-                update_status(&state, &cfg.name, true, None).await;
+            Ok(s) => {
+                let fields = describe_fields(
+                    s.as_ref(),
+                    &processing.transforms,
+                    &extra_fields,
+                    &cfg.channel_map,
+                );
+                publish_fields(&state, mqtt.as_ref(), &topics, &name, fields).await;
+                update_status(&state, &events, &cfg.name, true, None).await;
+                publish_availability(mqtt.as_ref(), &topics, true).await;
                 s
             }
-            Ok(Err(e)) => {
-                error!("Failed to initialise sensor '{}': {}", name, e);
-                update_status(&state, &cfg.name, false, Some(e.to_string())).await;
+            Err(e) if cfg.required => {
+                error!("Required sensor '{}' failed to initialise: {}", name, e);
+                update_status(&state, &events, &cfg.name, false, Some(e.clone())).await;
+                publish_availability(mqtt.as_ref(), &topics, false).await;
+                state.write().await.fatal_error = Some(format!(
+                    "required sensor '{}' failed to initialise: {}",
+                    name, e
+                ));
+                cancel.cancel();
                 return;
             }
             Err(e) => {
-                error!("Panic creating sensor '{}': {}", name, e);
+                warn!(
+                    "Optional sensor '{}' failed to initialise, continuing without it: {}",
+                    name, e
+                );
+                update_status(&state, &events, &cfg.name, false, Some(e)).await;
+                publish_availability(mqtt.as_ref(), &topics, false).await;
                 return;
             }
         };
 
-        let interval_ms = 20u64; // 50 Hz; driver sample_rate limits actual rate
+        let SensorProcessing {
+            dynamics,
+            slip,
+            laps,
+            drift,
+            vibration_metrics,
+            transforms,
+            derivatives,
+            integrals,
+            differences,
+            alerts,
+            anomalies,
+            ignition,
+            buffer_while_paused,
+            sample_timing,
+            adaptive_rate,
+            error_budget,
+            stuck_values,
+            read_turn,
+        } = processing;
+        let channel_map = cfg.channel_map.clone();
+        let payload_keys = match &mqtt {
+            Some(h) => PayloadKeys::new(
+                &derived_keys(&derivatives, &integrals, &differences, &anomalies),
+                &h.filtered_channels,
+                &h.derived_channels,
+            )
+            .with_channel_map(&channel_map),
+            None => PayloadKeys::default(),
+        };
+        let transformer = (!transforms.is_empty()).then(|| Transformer::new(transforms.clone()));
+        let mut imu = ImuChannels::for_fields(sensor.field_descriptors());
+        let mut differentiator =
+            (!derivatives.is_empty()).then(|| Differentiator::new(derivatives.clone()));
+        let mut integrator = (!integrals.is_empty()).then(|| Integrator::new(integrals.clone()));
+        let mut anomaly_monitor =
+            (!anomalies.is_empty()).then(|| AnomalyMonitor::new(anomalies.clone()));
+        let mut alert_monitor = (!alerts.is_empty()).then(|| AlertMonitor::new(alerts));
+        let mut vehicle = dynamics.clone().map(VehicleDynamics::new);
+        let mut slip_monitor = slip.clone().map(SlipMonitor::new);
+        let mut drift_monitor = drift.map(DriftMonitor::new);
+        let mut vibration = vibration_metrics.map(VibrationMonitor::new);
+        let mut lap_timer = laps.map(LapTimer::new);
+        let mut ignition = ignition.map(IgnitionMonitor::new);
+        let mut activity = adaptive_rate.map(ActivityMonitor::new);
+        let mut budget = error_budget.map(ErrorBudget::new);
+        let mut stuck = stuck_values.map(StuckDetector::new);
+        let mut timing = sample_timing.then(SampleTiming::new);
+        if let Some(t) = &lap_timer {
+            state.write().await.laps = Some(t.status().clone());
+        }
+        let full_rate = std::time::Duration::from_millis(sampling.interval_ms.max(1));
+        let mut group_on = true;
+        let mut reads = 0;
 
         loop {
+            if sampling.max_samples.is_some_and(|n| reads >= n) {
+                info!("Sensor '{}' made its {} reads", name, reads);
+                break;
+            }
+            let (paused, idle_interval, clock_synced, group_enabled, publish_mode) = {
+                let s = state.read().await;
+                let group_enabled = s.group_enabled(cfg.group.as_deref());
+                (
+                    s.paused,
+                    s.idle_interval,
+                    s.clock_synced,
+                    group_enabled,
+                    s.publish_mode,
+                )
+            };
+            if group_enabled != group_on {
+                group_on = group_enabled;
+                set_enabled(&state, &name, group_on).await;
+                if !group_on {
+                    publish_availability(mqtt.as_ref(), &topics, false).await;
+                }
+            }
+            let interval = if (paused && !buffer_while_paused) || !group_on {
+                PAUSED_POLL
+            } else {
+                idle_interval.unwrap_or(full_rate)
+            };
+            let slot = sampling
+                .align_to_clock
+                .then(|| next_slot(chrono::Utc::now(), interval));
+            let delay = match slot {
+                Some(t) => (t - chrono::Utc::now()).to_std().unwrap_or_default(),
+                None => interval,
+            };
+            let due = tokio::time::Instant::now() + delay;
             tokio::select! {
                 _ = cancel.cancelled() => {
                     info!("Sensor task '{}' cancelled", name);
                     break;
                 }
-                _ = tokio::time::sleep(std::time::Duration::from_millis(interval_ms)) => {}
+                Some(cmd) = cmd_rx.recv() => {
+                    match cmd {
+                        SensorCommand::Recalibrate => {
+                            match run_calibration(sensor, &state, &events, &name).await {
+                                Some(s) => sensor = s,
+                                None => break,
+                            }
+                            if let Some(i) = imu.as_mut() {
+                                i.reset_peak();
+                            }
+                        }
+                        SensorCommand::Reconfigure { settings, reply } => {
+                            let new_cfg = SensorConfig {
+                                settings: Some(settings),
+                                ..cfg.clone()
+                            };
+                            let created = tokio::task::spawn_blocking({
+                                let c = new_cfg.clone();
+                                move || create_sensor(&c)
+                            })
+                            .await;
+                            let result = match created {
+                                Ok(Ok(s)) => {
+                                    info!("Sensor '{}' restarted with new settings", name);
+                                    let fields = describe_fields(
+                                        s.as_ref(),
+                                        &transforms,
+                                        &extra_fields,
+                                        &new_cfg.channel_map,
+                                    );
+                                    publish_fields(&state, mqtt.as_ref(), &topics, &name, fields).await;
+                                    imu = ImuChannels::for_fields(s.field_descriptors());
+                                    sensor = s;
+                                    cfg = new_cfg;
+                                    Ok(())
+                                }
+                                Ok(Err(e)) => Err(format!("{:#}", e)),
+                                Err(e) => Err(format!("driver panicked: {}", e)),
+                            };
+                            if let Err(e) = &result {
+                                warn!("New settings for '{}' rejected: {}", name, e);
+                            }
+                            let _ = reply.send(result);
+                        }
+                    }
+                    if let Some(t) = timing.as_mut() {
+                        t.reset();
+                    }
+                    continue;
+                }
+                _ = tokio::time::sleep_until(due) => {
+                    record_loop_latency(due.elapsed());
+                }
+            }
+            // Only the ignition sensor keeps reading while paused, unless
+            // readings are buffered
+            if !group_on || (paused && ignition.is_none() && !buffer_while_paused) {
+                if let Some(t) = timing.as_mut() {
+                    t.reset();
+                }
+                continue;
             }
 
             // Read (blocking) in a thread pool
             let read_result = {
+                let _turn = match &read_turn {
+                    Some(t) => Some(t.wait().await),
+                    None => None,
+                };
                 // We need to move the sensor into spawn_blocking, but it's borrowed.
                 // Pattern: read synchronously here since I2C reads are ~1ms.
                 // For long-blocking drivers, restructure to Arc<Mutex<Box<dyn Sensor>>>.
@@ -110,105 +483,1799 @@ pub fn spawn_sensor_task(
                     Err(e) => Err(e.to_string()),
                 }
             };
+            reads += 1;
+
+            if let Some(b) = budget.as_mut()
+                && let Some(pct) = b.record(chrono::Utc::now(), read_result.is_ok())
+            {
+                disable_flapping(&state, &events, &name, pct).await;
+                break;
+            }
 
             match read_result {
-                Ok(data) => {
-                    update_status(&state, &name, true, None).await;
-                    push_data(&state, &name, data.clone()).await;
-                    if let Some(ref h) = mqtt {
-                        publish_sensor_data(h, &base_topic, &name, &data).await;
+                Ok(mut data) => {
+                    if let Some(t) = slot {
+                        data.timestamp = t;
+                    }
+                    data.monotonic = Some(clock::monotonic_secs());
+                    data.clock_unsynced = clock_synced == Some(false);
+                    if let Some(d) = stuck.as_mut() {
+                        for change in d.process(&data) {
+                            set_stuck(&state, &events, &name, change).await;
+                        }
                     }
+                    if let Some(t) = timing.as_mut() {
+                        t.process(&mut data, interval);
+                    }
+                    if let Some(t) = transformer.as_ref() {
+                        t.process(&mut data);
+                    }
+                    if let Some(i) = imu.as_mut() {
+                        i.process(&mut data);
+                    }
+                    if let Some(m) = ignition.as_mut() {
+                        if let Some(on) = m.update(&data) {
+                            on_ignition_change(&state, mqtt.as_ref(), &base_topic, on).await;
+                        }
+                        if !m.is_on() {
+                            if update_status(&state, &events, &name, true, None).await {
+                                publish_availability(mqtt.as_ref(), &topics, true).await;
+                            }
+                            if buffer_while_paused {
+                                buffer_data(&state, &name, &data).await;
+                            }
+                            continue;
+                        }
+                    } else if paused {
+                        if update_status(&state, &events, &name, true, None).await {
+                            publish_availability(mqtt.as_ref(), &topics, true).await;
+                        }
+                        buffer_data(&state, &name, &data).await;
+                        continue;
+                    }
+                    if let Some(m) = activity.as_mut()
+                        && let Some(idle) = m.update(&data)
+                    {
+                        set_idle(&state, idle.then(|| m.idle_interval())).await;
+                    }
+                    if let (Some(v), Some(dc)) = (vehicle.as_mut(), dynamics.as_ref()) {
+                        v.process(&mut data, gps_fix(&state, dc).await);
+                    }
+                    let mut alert_changes = Vec::new();
+                    if let (Some(m), Some(sc)) = (slip_monitor.as_mut(), slip.as_ref()) {
+                        alert_changes.extend(m.process(&mut data, wheel_speed(&state, sc).await));
+                    }
+                    if let Some(m) = drift_monitor.as_mut() {
+                        m.process(&mut data);
+                    }
+                    if let Some(m) = vibration.as_mut()
+                        && let Some(metrics) = m.process(&mut data)
+                        && let Some(ref h) = mqtt
+                    {
+                        publish_vibration(h, &topics, &data, m.window_secs(), metrics).await;
+                    }
+                    if let Some(d) = differentiator.as_mut() {
+                        d.process(&mut data);
+                    }
+                    if let Some(i) = integrator.as_mut() {
+                        i.process(&mut data);
+                    }
+                    if !differences.is_empty() {
+                        let s = state.read().await;
+                        differences::apply(&differences, &name, &mut data, &s.sensor_data);
+                    }
+                    if let Some(m) = anomaly_monitor.as_mut() {
+                        alert_changes.extend(m.process(&mut data));
+                    }
+                    if let Some(m) = alert_monitor.as_mut() {
+                        alert_changes.extend(m.process(&data));
+                    }
+                    if !alert_changes.is_empty() {
+                        let mut s = state.write().await;
+                        for change in alert_changes {
+                            if let AlertChange::Fired(a) = &change {
+                                warn!("Alert: {}", a.text);
+                            }
+                            s.update_alert(change);
+                        }
+                    }
+                    if update_status(&state, &events, &name, true, None).await {
+                        publish_availability(mqtt.as_ref(), &topics, true).await;
+                    }
+                    match (publish_mode, &mqtt) {
+                        (PublishMode::Mqtt, Some(h)) => {
+                            publish_sensor_data(
+                                h,
+                                &topics,
+                                &name,
+                                &cfg.tags,
+                                &data,
+                                &payload_keys,
+                                &mut publish_state,
+                            )
+                            .await;
+                            if let Some(every) = h.snapshot_interval
+                                && last_snapshot.is_none_or(|t| t.elapsed() >= every)
+                            {
+                                publish_snapshot(
+                                    h,
+                                    &topics,
+                                    &name,
+                                    &cfg.tags,
+                                    &data,
+                                    &payload_keys,
+                                )
+                                .await;
+                                last_snapshot = Some(std::time::Instant::now());
+                            }
+                            if let Some(a) = aggregator.as_mut() {
+                                publish_rollups(h, &topics, a, &data, &payload_keys).await;
+                            }
+                        }
+                        (PublishMode::Log, _) => debug!("'{}' reading: {:?}", name, data.fields),
+                        _ => {}
+                    }
+                    if let Some(t) = lap_timer.as_mut()
+                        && let Some(ev) = t.update(&data)
+                    {
+                        on_lap_event(&state, mqtt.as_ref(), &base_topic, t, ev).await;
+                    }
+                    push_data(&state, &name, data).await;
                 }
                 Err(e) => {
                     warn!("Read error on '{}': {}", name, e);
-                    update_status(&state, &name, false, Some(e)).await;
+                    if update_status(&state, &events, &name, false, Some(e)).await {
+                        publish_availability(mqtt.as_ref(), &topics, false).await;
+                    }
                 }
             }
         }
+        publish_availability(mqtt.as_ref(), &topics, false).await;
+    })
+}
+
+/// The first wall-clock multiple of `interval` (counted from the Unix
+/// epoch) after `now`, so tasks on different machines read at the same
+/// instants.
+pub fn next_slot(
+    now: chrono::DateTime<chrono::Utc>,
+    interval: std::time::Duration,
+) -> chrono::DateTime<chrono::Utc> {
+    let step = (interval.as_micros() as i64).max(1);
+    let next = (now.timestamp_micros().div_euclid(step) + 1) * step;
+    chrono::DateTime::from_timestamp_micros(next).unwrap_or(now)
+}
+
+/// [`driver_fields`] plus `extra_fields`, as listed in the sensor's status
+/// and as its retained schema.
+fn describe_fields(
+    sensor: &dyn Sensor,
+    transforms: &[TransformConfig],
+    extra_fields: &[FieldDescriptor],
+    channel_map: &IndexMap<String, String>,
+) -> (Vec<FieldMeta>, serde_json::Value) {
+    let mut fields = driver_fields(sensor, transforms);
+    fields.extend_from_slice(extra_fields);
+    (
+        fields.iter().map(FieldMeta::from).collect(),
+        schema_payload(sensor, &fields, channel_map),
+    )
+}
+
+/// The driver's channels after `transforms`, then those [`ImuChannels`]
+/// adds from its raw axes.
+pub fn driver_fields(sensor: &dyn Sensor, transforms: &[TransformConfig]) -> Vec<FieldDescriptor> {
+    let driver = sensor.field_descriptors();
+    let mut fields = transforms::field_descriptors(driver, transforms);
+    if let Some(imu) = ImuChannels::for_fields(driver) {
+        fields.extend(imu.field_descriptors());
+    }
+    fields
+}
+
+/// Put what [`describe_fields`] returned in the sensor's status and on its
+/// retained `SCHEMA` topic.
+async fn publish_fields(
+    state: &SharedState,
+    mqtt: Option<&MqttHandle>,
+    topics: &SensorTopics,
+    name: &str,
+    (fields, mut schema): (Vec<FieldMeta>, serde_json::Value),
+) {
+    if let Some(st) = state.write().await.sensor_statuses.get_mut(name) {
+        st.fields = fields;
+    }
+    if let Some(h) = mqtt {
+        if let Some(v) = h.schema_version {
+            schema["schema_version"] = serde_json::json!(v);
+        }
+        h.publish_retained(Arc::clone(&topics.schema), schema.to_string())
+            .await;
+    }
+}
+
+/// Build the driver on a blocking thread, retrying with backoff up to
+/// `init_retries` times.  `None` if cancelled while waiting to retry.
+async fn create_with_retry(
+    cfg: &SensorConfig,
+    state: &SharedState,
+    events: &ErrorEvents,
+    cancel: &CancellationToken,
+) -> Option<Result<Box<dyn Sensor>, String>> {
+    let attempts = cfg.init_retries + 1;
+    let mut attempt = 1;
+    loop {
+        let result = tokio::task::spawn_blocking({
+            let cfg = cfg.clone();
+            move || create_sensor(&cfg)
+        })
+        .await;
+        let err = match result {
+            Ok(Ok(s)) => return Some(Ok(s)),
+            Ok(Err(e)) => e.to_string(),
+            Err(e) => format!("driver panicked: {}", e),
+        };
+        if attempt >= attempts {
+            return Some(Err(err));
+        }
+
+        // 1 s, 2 s, 4 s, … capped at 10 s
+        let delay = std::time::Duration::from_secs((1u64 << (attempt - 1).min(4)).min(10));
+        warn!(
+            "Sensor '{}' init attempt {}/{} failed: {} — retrying in {}s",
+            cfg.name,
+            attempt,
+            attempts,
+            err,
+            delay.as_secs()
+        );
+        update_status(state, events, &cfg.name, false, Some(err)).await;
+        tokio::select! {
+            _ = cancel.cancelled() => return None,
+            _ = tokio::time::sleep(delay) => {}
+        }
+        attempt += 1;
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Calibration (off the async runtime, with progress in SensorStatus)
+// ---------------------------------------------------------------------------
+
+/// Recalibrate on a blocking thread and hand the sensor back.  Progress is
+/// written straight into the sensor's `SensorStatus` so the TUI can draw it.
+/// Returns `None` if the calibration thread panicked and took the driver
+/// with it.
+async fn run_calibration(
+    mut sensor: Box<dyn Sensor>,
+    state: &SharedState,
+    events: &ErrorEvents,
+    name: &str,
+) -> Option<Box<dyn Sensor>> {
+    info!("Recalibrating '{}'", name);
+    set_calibration(
+        state,
+        name,
+        CalibrationState::Running(CalibrationProgress::default()),
+    )
+    .await;
+
+    let task = tokio::task::spawn_blocking({
+        let state = state.clone();
+        let name = name.to_string();
+        move || {
+            let result = sensor.recalibrate_with_progress(&mut |p| {
+                let mut s = state.blocking_write();
+                if let Some(st) = s.sensor_statuses.get_mut(&name) {
+                    st.calibration = CalibrationState::Running(p);
+                }
+            });
+            (sensor, result)
+        }
     });
+
+    let (sensor, result) = match task.await {
+        Ok(pair) => pair,
+        Err(e) => {
+            error!("Calibration thread for '{}' panicked: {}", name, e);
+            set_calibration(state, name, CalibrationState::Failed(e.to_string())).await;
+            update_status(
+                state,
+                events,
+                name,
+                false,
+                Some(format!("calibration panicked: {}", e)),
+            )
+            .await;
+            return None;
+        }
+    };
+
+    match result {
+        Ok(()) => {
+            info!("Calibration of '{}' finished", name);
+            set_calibration(state, name, CalibrationState::Done(chrono::Local::now())).await;
+            state
+                .write()
+                .await
+                .status
+                .info(format!("Calibration of '{}' finished", name));
+            events
+                .publish(
+                    name,
+                    StatusLevel::Info,
+                    "calibrated",
+                    "calibration finished",
+                )
+                .await;
+        }
+        Err(e) => {
+            warn!("Calibration of '{}' failed: {}", name, e);
+            set_calibration(state, name, CalibrationState::Failed(e.to_string())).await;
+            state
+                .write()
+                .await
+                .status
+                .error(format!("Calibration of '{}' failed: {}", name, e));
+            events
+                .publish(
+                    name,
+                    StatusLevel::Warn,
+                    "calibration_failed",
+                    &format!("calibration failed: {}", e),
+                )
+                .await;
+        }
+    }
+    Some(sensor)
+}
+
+async fn set_calibration(state: &SharedState, name: &str, cal: CalibrationState) {
+    let mut s = state.write().await;
+    if let Some(st) = s.sensor_statuses.get_mut(name) {
+        st.calibration = cal;
+    }
 }
 
 // ---------------------------------------------------------------------------
 // State helpers
 // ---------------------------------------------------------------------------
 
-async fn update_status(state: &SharedState, name: &str, connected: bool, error: Option<String>) {
+/// Update a sensor's status, posting to the status bar only on changes so a
+/// sensor failing at 50 Hz doesn't flood the message history or the
+/// `ERRORS` topic.  Returns whether `connected` changed.
+async fn update_status(
+    state: &SharedState,
+    events: &ErrorEvents,
+    name: &str,
+    connected: bool,
+    error: Option<String>,
+) -> bool {
     let mut s = state.write().await;
-    if let Some(st) = s.sensor_statuses.get_mut(name) {
-        st.connected = connected;
-        st.last_error = error;
+    let Some(st) = s.sensor_statuses.get_mut(name) else {
+        return false;
+    };
+    let changed = st.connected != connected;
+    let recovered = connected && !st.connected && st.last_error.is_some();
+    let new_error = error
+        .as_ref()
+        .filter(|e| st.last_error.as_ref() != Some(*e))
+        .cloned();
+    st.connected = connected;
+    st.last_error = error;
+
+    if recovered {
+        s.status.info(format!("Sensor '{}' recovered", name));
+        drop(s);
+        events
+            .publish(name, StatusLevel::Info, "recovered", "sensor recovered")
+            .await;
+    } else if let Some(e) = new_error {
+        s.status.error(format!("Sensor '{}': {}", name, e));
+        drop(s);
+        events.publish(name, StatusLevel::Error, "fault", &e).await;
     }
+    changed
 }
 
-async fn push_data(state: &SharedState, name: &str, data: SensorData) {
+/// Flag or unflag a channel that stopped changing, on the sensor's status,
+/// the status bar and `ERRORS`.
+async fn set_stuck(state: &SharedState, events: &ErrorEvents, name: &str, change: StuckChange) {
+    let mut s = state.write().await;
+    let Some(st) = s.sensor_statuses.get_mut(name) else {
+        return;
+    };
+    match change {
+        StuckChange::Stuck { key, value } => {
+            let message = format!("{} stuck at {}", key, value);
+            warn!("Sensor '{}': {}", name, message);
+            st.stuck.push(key);
+            s.status.warn(format!("Sensor '{}': {}", name, message));
+            drop(s);
+            events
+                .publish(name, StatusLevel::Warn, "stuck", &message)
+                .await;
+        }
+        StuckChange::Changing { key } => {
+            let message = format!("{} changing again", key);
+            st.stuck.retain(|k| *k != key);
+            s.status.info(format!("Sensor '{}': {}", name, message));
+            drop(s);
+            events
+                .publish(name, StatusLevel::Info, "unstuck", &message)
+                .await;
+        }
+    }
+}
+
+/// Switch a sensor on or off with its group.  Off also means offline; the
+/// next reading after switching back on brings it online again.
+async fn set_enabled(state: &SharedState, name: &str, enabled: bool) {
+    info!(
+        "Sensor '{}' {} with its group",
+        name,
+        if enabled { "resumed" } else { "stopped" }
+    );
+    if let Some(st) = state.write().await.sensor_statuses.get_mut(name) {
+        st.enabled = enabled;
+        st.connected &= enabled;
+    }
+}
+
+/// Take a sensor that blew its error budget out of service; the task stops
+/// after this and publishes it offline.
+async fn disable_flapping(state: &SharedState, events: &ErrorEvents, name: &str, failure_pct: f64) {
+    let reason = format!("disabled after {:.0}% of reads failed", failure_pct);
+    error!("Sensor '{}' {}", name, reason);
+    {
+        let mut s = state.write().await;
+        if let Some(st) = s.sensor_statuses.get_mut(name) {
+            st.enabled = false;
+            st.connected = false;
+            st.last_error = Some(reason.clone());
+        }
+        s.status.error(format!("Sensor '{}' {}", name, reason));
+    }
+    events
+        .publish(name, StatusLevel::Error, "disabled", &reason)
+        .await;
+}
+
+/// GPS readings older than this are ignored by vehicle dynamics.
+const GPS_MAX_AGE_MS: i64 = 2000;
+
+/// Latest trustworthy GPS speed (km/h) and course for the dynamics fusion.
+async fn gps_fix(state: &SharedState, cfg: &DynamicsConfig) -> GpsFix {
+    if cfg.gps_sensor.is_empty() {
+        return GpsFix::default();
+    }
+    let s = state.read().await;
+    let Some(data) = s.sensor_data.get(&cfg.gps_sensor) else {
+        return GpsFix::default();
+    };
+    let age = chrono::Utc::now() - data.timestamp;
+    if age.num_milliseconds() > GPS_MAX_AGE_MS {
+        return GpsFix::default();
+    }
+    let good = |field: &str| {
+        data.fields
+            .get(field)
+            .copied()
+            .filter(|_| data.quality(field) == Quality::Good)
+    };
+    GpsFix {
+        speed_kmh: good(&cfg.gps_speed_field),
+        course: good(&cfg.gps_course_field),
+    }
+}
+
+/// Wheel speed older than this is ignored by the slip monitor.
+const WHEEL_MAX_AGE_MS: i64 = 500;
+
+/// Latest trustworthy wheel speed (km/h) for the slip monitor, if any.
+async fn wheel_speed(state: &SharedState, cfg: &SlipConfig) -> Option<f64> {
+    let s = state.read().await;
+    let data = s.sensor_data.get(&cfg.wheel_sensor)?;
+    let age = chrono::Utc::now() - data.timestamp;
+    if age.num_milliseconds() > WHEEL_MAX_AGE_MS
+        || data.quality(&cfg.wheel_speed_field) != Quality::Good
+    {
+        return None;
+    }
+    data.fields.get(cfg.wheel_speed_field.as_str()).copied()
+}
+
+pub(crate) async fn push_data(state: &SharedState, name: &str, data: SensorData) {
     let mut s = state.write().await;
     if let Some(hist) = s.sensor_history.get_mut(name) {
         hist.push(&data);
     }
-    s.sensor_data.insert(name.to_string(), data);
+    if let Some(Err(e)) = s.recording.as_mut().map(|r| r.record(name, &data)) {
+        error!("Session recording failed: {}", e);
+        s.recording = None;
+        s.status.error(format!("Recording stopped: {}", e));
+    }
+    if let Some(e) = s.extremes.as_mut() {
+        e.update(name, &data);
+    }
+    match s.sensor_data.get_mut(name) {
+        Some(slot) => *slot = data,
+        None => {
+            s.sensor_data.insert(name.to_string(), data);
+        }
+    }
+}
+
+/// Keep a reading taken while paused in the history alone: the live
+/// values, the recording and MQTT stay as they were when pausing.
+async fn buffer_data(state: &SharedState, name: &str, data: &SensorData) {
+    if let Some(hist) = state.write().await.sensor_history.get_mut(name) {
+        hist.push_buffered(data);
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Lap timing
+// ---------------------------------------------------------------------------
+
+async fn on_lap_event(
+    state: &SharedState,
+    mqtt: Option<&MqttHandle>,
+    base_topic: &str,
+    timer: &LapTimer,
+    event: LapEvent,
+) {
+    let laps = timer.status();
+    let mut s = state.write().await;
+    s.laps = Some(laps.clone());
+
+    let LapEvent::Completed { lap, time, best } = event else {
+        s.status.info("Lap timer started");
+        return;
+    };
+    s.status.info(format!(
+        "Lap {}: {}{}",
+        lap,
+        format_lap(time),
+        if best { " (best)" } else { "" }
+    ));
+    drop(s);
+
+    if let Some(h) = mqtt {
+        let payload = serde_json::json!({
+            "timestamp": laps.started.map(|t| t.to_rfc3339()),
+            "lap": lap,
+            "lap_time": time.as_secs_f64(),
+            "best_lap": laps.best.map(|b| b.as_secs_f64()),
+        });
+        h.publish(format!("{}/LAPS", base_topic), payload.to_string())
+            .await;
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Ignition
+// ---------------------------------------------------------------------------
+
+/// How often sensor tasks wake while paused; the ignition sensor is read at
+/// this rate so it can spot the engine starting.
+const PAUSED_POLL: std::time::Duration = std::time::Duration::from_millis(500);
+
+/// Longest wait on pausing for queued messages to reach the broker.
+const PAUSE_FLUSH: std::time::Duration = std::time::Duration::from_secs(1);
+
+/// Pause or resume every sensor task and publish the retained
+/// `<base_topic>/IGNITION` state.  Pausing also flushes the session
+/// recording and the MQTT queue, as power may be cut soon after.
+async fn on_ignition_change(
+    state: &SharedState,
+    mqtt: Option<&MqttHandle>,
+    base_topic: &str,
+    on: bool,
+) {
+    {
+        let mut s = state.write().await;
+        s.paused = !on;
+        if on {
+            s.status.info("Ignition on, sampling resumed");
+        } else {
+            s.status.info("Ignition off, sampling paused");
+            if let Some(Err(e)) = s.recording.as_mut().map(|r| r.flush()) {
+                error!("Session recording failed: {:#}", e);
+                s.recording = None;
+            }
+        }
+    }
+    info!("Ignition {}", if on { "on" } else { "off" });
+
+    if let Some(h) = mqtt {
+        let payload = serde_json::json!({
+            "timestamp": chrono::Utc::now().to_rfc3339(),
+            "ignition": if on { "on" } else { "off" },
+        });
+        h.publish_retained(format!("{}/IGNITION", base_topic), payload.to_string())
+            .await;
+        if !on {
+            h.flush(PAUSE_FLUSH).await;
+        }
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Adaptive sample rate
+// ---------------------------------------------------------------------------
+
+async fn set_idle(state: &SharedState, interval: Option<std::time::Duration>) {
+    match interval {
+        Some(i) => info!("No motion, sampling every {} ms", i.as_millis()),
+        None => info!("Motion detected, sampling at full rate"),
+    }
+    state.write().await.idle_interval = interval;
+}
+
+// ---------------------------------------------------------------------------
+// Error events
+// ---------------------------------------------------------------------------
+
+/// Publishes a sensor's faults, recoveries and calibration results to
+/// `<base_topic>/ERRORS`, one JSON object per event.  Callers only publish
+/// when something changes, so a sensor failing every read sends one event.
+#[derive(Clone)]
+struct ErrorEvents {
+    mqtt: Option<MqttHandle>,
+    topic: Arc<str>,
+}
+
+impl ErrorEvents {
+    fn new(mqtt: Option<MqttHandle>, base_topic: &str) -> Self {
+        Self {
+            mqtt,
+            topic: format!("{}/ERRORS", base_topic).into(),
+        }
+    }
+
+    async fn publish(&self, sensor: &str, severity: StatusLevel, event: &str, message: &str) {
+        let Some(h) = &self.mqtt else {
+            return;
+        };
+        let severity = match severity {
+            StatusLevel::Info => "info",
+            StatusLevel::Warn => "warning",
+            StatusLevel::Error => "error",
+        };
+        let payload = serde_json::json!({
+            "timestamp": chrono::Utc::now().to_rfc3339(),
+            "sensor": sensor,
+            "severity": severity,
+            "event": event,
+            "message": message,
+        });
+        h.publish(Arc::clone(&self.topic), payload.to_string())
+            .await;
+    }
 }
 
 // ---------------------------------------------------------------------------
 // MQTT publishing helper
 // ---------------------------------------------------------------------------
 
-async fn publish_sensor_data(mqtt: &MqttHandle, base_topic: &str, name: &str, data: &SensorData) {
+/// Retained `SCHEMA` payload: the sensor's channels in publish order, with
+/// label, unit, value type and expected range, so consumers can build
+/// columns before the first reading arrives.
+/// `fields` are all of them: the driver's, then those added after it, e.g.
+/// vehicle dynamics.  Keys are renamed by the sensor's `channel_map`.
+pub fn schema_payload(
+    sensor: &dyn Sensor,
+    fields: &[FieldDescriptor],
+    channel_map: &IndexMap<String, String>,
+) -> serde_json::Value {
     use serde_json::json;
 
+    let channels: Vec<serde_json::Value> = fields
+        .iter()
+        .filter_map(|d| Some((published_name(channel_map, d.key)?, d)))
+        .map(|(key, d)| {
+            json!({
+                "key": key,
+                "label": d.label,
+                "unit": d.viz.unit(),
+                "type": d.viz.value_type(),
+                "range": FieldMeta::from(d).range,
+            })
+        })
+        .collect();
+    let mut schema = json!({
+        "sensor": sensor.name(),
+        "driver": sensor.driver_name(),
+        "channels": channels,
+    });
+    if let Some(frame) = sensor.frame() {
+        schema["frame"] = json!(frame);
+    }
+    schema
+}
+
+/// JSON Schema (draft 2020-12) documents for a sensor's `INFO`, `FILTERED`
+/// and `DERIVED` payloads, keyed by subtopic.  Channels are optional so a
+/// reading that skips one still validates; `DERIVED` allows extra keys
+/// because drivers may add non-numeric channels that have no descriptor.
+pub fn payload_json_schemas(
+    name: &str,
+    tags: &IndexMap<String, String>,
+    descriptors: &[FieldDescriptor],
+    keys: &PayloadKeys,
+) -> serde_json::Value {
+    use serde_json::{Map, json};
+
+    let object = |title: String, properties: Map<String, serde_json::Value>, extra: bool| {
+        let required = properties
+            .keys()
+            .filter(|k| matches!(k.as_str(), "timestamp" | "sensor"))
+            .cloned()
+            .collect::<Vec<_>>();
+        json!({
+            "$schema": "https://json-schema.org/draft/2020-12/schema",
+            "title": title,
+            "type": "object",
+            "properties": properties,
+            "required": required,
+            "additionalProperties": extra,
+        })
+    };
+    let schema_version = json!({
+        "type": "integer",
+        "description": "Payload layout version; absent in compatibility mode",
+    });
+    let timing = || {
+        let mut p = Map::new();
+        p.insert(
+            "timestamp".into(),
+            json!({"type": "string", "format": "date-time"}),
+        );
+        p.insert("schema_version".into(), schema_version.clone());
+        p.insert(
+            "monotonic".into(),
+            json!({"type": "number", "description": "Seconds since the service started"}),
+        );
+        p.insert(
+            "clock_unsynced".into(),
+            json!({"const": true, "description": "Taken while the system clock was not synchronised"}),
+        );
+        p
+    };
+    let channel = |d: &FieldDescriptor| {
+        let mut c = json!({ "title": d.label });
+        c["type"] = json!(if d.viz.value_type() == "text" {
+            "string"
+        } else {
+            "number"
+        });
+        if !d.viz.unit().is_empty() {
+            c["description"] = json!(format!("Unit: {}", d.viz.unit()));
+        }
+        c
+    };
+
+    let mut filtered = timing();
+    let mut derived = timing();
+    for d in descriptors {
+        let Some(key) = keys.published(d.key) else {
+            continue;
+        };
+        if keys.filtered.contains(&key) {
+            filtered.insert(key.into(), channel(d));
+        } else if keys.derived.contains(&key)
+            || (d.viz.value_type() == "text" && keys.values.allows(key))
+        {
+            derived.insert(key.into(), channel(d));
+        }
+    }
+
+    let mut info = Map::new();
+    info.insert("sensor".into(), json!({"const": name}));
+    info.insert(
+        "timestamp".into(),
+        json!({"type": "string", "format": "date-time"}),
+    );
+    info.insert("schema_version".into(), schema_version.clone());
+    info.insert(
+        "quality".into(),
+        json!({
+            "type": "object",
+            "description": "Channels whose quality is not good",
+            "additionalProperties": {"enum": ["stale", "estimated", "invalid", "fault"]},
+        }),
+    );
+    if !tags.is_empty() {
+        let tags: Map<_, _> = tags
+            .iter()
+            .map(|(k, v)| (k.clone(), json!({"const": v})))
+            .collect();
+        info.insert(
+            "tags".into(),
+            json!({"type": "object", "properties": tags, "additionalProperties": false}),
+        );
+    }
+
+    json!({
+        "INFO": object(format!("{} INFO", name), info, false),
+        "FILTERED": object(format!("{} FILTERED", name), filtered, false),
+        "DERIVED": object(format!("{} DERIVED", name), derived, true),
+    })
+}
+
+/// Channels published on `FILTERED`.  The MPU-6500 reports its linear
+/// acceleration once, as `g_force_*` on `DERIVED`; `accel_*` are only in its
+/// readings with `legacy_accel_fields`, and are published here for the
+/// consumers that still read them.
+const FILTERED_KEYS: &[&str] = &[
+    "accel_x",
+    "accel_y",
+    "accel_z",
+    "gyro_x",
+    "gyro_y",
+    "gyro_z",
+    "roll_rate",
+    "pitch_rate",
+    "yaw_rate",
+];
+
+/// Channels published on `DERIVED`, followed by any `[[derivatives]]`,
+/// `[[integrals]]`, `[[differences]]`, `[[anomalies]]` and non-numeric
+/// channels.
+pub const DERIVED_KEYS: &[&str] = &[
+    "g_force_x",
+    "g_force_y",
+    "g_force_z",
+    "combined_g",
+    "peak_g",
+    "corrected_lateral_g",
+    "lean_angle",
+    "bank_angle",
+    "tilt_angle",
+    "angular_velocity",
+    "speed",
+    "corner_radius",
+    "braking_distance",
+    "heading",
+    "wheel_slip",
+    "lockup",
+    "wheelspin",
+    "roll_drift",
+    "pitch_drift",
+    "vibration_x",
+    "vibration_y",
+    "vibration_z",
+    "quat_w",
+    "quat_x",
+    "quat_y",
+    "quat_z",
+    "cpu_pct",
+    "rss_mb",
+    "threads",
+    "loop_latency_ms",
+    "dt_ms",
+    "missed_samples",
+];
+
+/// [`DERIVED_KEYS`] plus the `[[derivatives]]`, `[[integrals]]` and
+/// `[[anomalies]]` a sensor has.
+pub(crate) fn derived_keys<'a>(
+    derivatives: &'a [DerivativeConfig],
+    integrals: &'a [IntegralConfig],
+    differences: &'a [DifferenceConfig],
+    anomalies: &'a [AnomalyConfig],
+) -> Vec<&'a str> {
+    DERIVED_KEYS
+        .iter()
+        .copied()
+        .chain(derivatives.iter().map(|d| d.name.as_str()))
+        .chain(integrals.iter().map(|i| i.name.as_str()))
+        .chain(differences.iter().map(|d| d.name.as_str()))
+        .chain(anomalies.iter().map(|a| a.name.as_str()))
+        .collect()
+}
+
+/// The channels one sensor publishes on `FILTERED` and `DERIVED`, after
+/// `mqtt.filtered_channels` / `mqtt.derived_channels`; worked out once when
+/// its task starts.
+pub struct PayloadKeys<'a> {
+    filtered: Vec<&'a str>,
+    derived: Vec<&'a str>,
+    /// `filtered`, then the `derived` channels not among them, as `LATEST`
+    /// and the rollups carry them
+    all: Vec<&'a str>,
+    /// Non-numeric channels appended to `DERIVED`
+    values: ChannelFilter,
+    /// The sensor's `channel_map`, applied to readings before encoding
+    channel_map: Option<&'a IndexMap<String, String>>,
+}
+
+impl<'a> PayloadKeys<'a> {
+    /// `derived` is [`DERIVED_KEYS`] or [`SensorProcessing::derived_keys`].
+    pub fn new(
+        derived: &[&'a str],
+        filtered: &ChannelFilter,
+        derived_filter: &ChannelFilter,
+    ) -> Self {
+        let filtered: Vec<_> = FILTERED_KEYS
+            .iter()
+            .copied()
+            .filter(|k| filtered.allows(k))
+            .collect();
+        let derived: Vec<_> = derived
+            .iter()
+            .copied()
+            .filter(|k| derived_filter.allows(k))
+            .collect();
+        Self {
+            all: union(&filtered, &derived),
+            filtered,
+            derived,
+            values: derived_filter.clone(),
+            channel_map: None,
+        }
+    }
+
+    /// Publish channels under the names in a sensor's `channel_map`, and
+    /// leave out those mapped to `""`.  The channel filters keep matching
+    /// the driver's names.
+    pub fn with_channel_map(mut self, map: &'a IndexMap<String, String>) -> Self {
+        if map.is_empty() {
+            return self;
+        }
+        let rename = |keys: Vec<&'a str>| {
+            keys.into_iter()
+                .filter_map(|k| published_name(map, k))
+                .collect()
+        };
+        self.filtered = rename(self.filtered);
+        self.derived = rename(self.derived);
+        self.all = union(&self.filtered, &self.derived);
+        for key in self
+            .values
+            .include
+            .iter_mut()
+            .chain(&mut self.values.exclude)
+        {
+            if let Some(name) = published_name(map, key) {
+                *key = name.to_string();
+            }
+        }
+        self.channel_map = Some(map);
+        self
+    }
+
+    /// What `key` is published as; `None` if it isn't.
+    fn published<'k>(&'k self, key: &'k str) -> Option<&'k str> {
+        match self.channel_map {
+            Some(map) => published_name(map, key),
+            None => Some(key),
+        }
+    }
+}
+
+/// `a`, then the keys of `b` not in `a`.
+fn union<'a>(a: &[&'a str], b: &[&'a str]) -> Vec<&'a str> {
+    let mut all = a.to_vec();
+    all.extend(b.iter().filter(|k| !a.contains(k)));
+    all
+}
+
+/// `key` renamed by a sensor's `channel_map`; `None` when mapped to `""`.
+fn published_name<'k>(map: &'k IndexMap<String, String>, key: &'k str) -> Option<&'k str> {
+    match map.get(key) {
+        Some(name) if name.is_empty() => None,
+        Some(name) => Some(name),
+        None => Some(key),
+    }
+}
+
+/// `data` as it is published: with its channels under their `channel_map`
+/// names, if it has one.
+fn published<'d>(data: &'d SensorData, keys: &PayloadKeys<'_>) -> Cow<'d, SensorData> {
+    match keys.channel_map {
+        Some(map) => Cow::Owned(rename_channels(data, map)),
+        None => Cow::Borrowed(data),
+    }
+}
+
+/// `data` with its channels renamed by `map`, as it is published.
+fn rename_channels(data: &SensorData, map: &IndexMap<String, String>) -> SensorData {
+    fn renamed<K: AsRef<str> + From<String> + Eq + std::hash::Hash, V: Clone>(
+        channels: &IndexMap<K, V>,
+        map: &IndexMap<String, String>,
+    ) -> IndexMap<K, V> {
+        channels
+            .iter()
+            .filter_map(|(k, v)| {
+                Some((
+                    published_name(map, k.as_ref())?.to_string().into(),
+                    v.clone(),
+                ))
+            })
+            .collect()
+    }
+    SensorData {
+        timestamp: data.timestamp,
+        fields: renamed(&data.fields, map),
+        values: renamed(&data.values, map),
+        quality: renamed(&data.quality, map),
+        diagnostics: data.diagnostics.clone(),
+        monotonic: data.monotonic,
+        clock_unsynced: data.clock_unsynced,
+    }
+}
+
+impl Default for PayloadKeys<'_> {
+    /// Everything, unfiltered.
+    fn default() -> Self {
+        Self::new(
+            DERIVED_KEYS,
+            &ChannelFilter::default(),
+            &ChannelFilter::default(),
+        )
+    }
+}
+
+/// `v` rounded to `precision` decimal places (`mqtt.payload_precision`).
+pub(crate) fn round_to(v: f64, precision: Option<u32>) -> f64 {
+    match precision {
+        // Past ~15 digits an f64 has nothing left to round
+        Some(p) if p < 16 => {
+            let scale = 10f64.powi(p as i32);
+            (v * scale).round() / scale
+        }
+        _ => v,
+    }
+}
+
+/// A sensor's MQTT topics, built once when its task starts.
+pub(crate) struct SensorTopics {
+    info: Arc<str>,
+    filtered: Arc<str>,
+    derived: Arc<str>,
+    schema: Arc<str>,
+    availability: Arc<str>,
+    diagnostics: Arc<str>,
+    vibration: Arc<str>,
+    latest: Arc<str>,
+    /// `AGG`, followed by `/<n>S` for each window
+    aggregates: Arc<str>,
+}
+
+impl SensorTopics {
+    /// Under `<base_topic>/<group>/` when the group goes in the topic path.
+    pub(crate) fn new(base_topic: &str, group: Option<&str>, name: &str) -> Self {
+        let prefix = match group {
+            Some(group) => format!("{}/{}/IMU/{}", base_topic, group, name),
+            None => format!("{}/IMU/{}", base_topic, name),
+        };
+        let topic = |sub: &str| Arc::from(format!("{}/{}", prefix, sub));
+        Self {
+            info: topic("INFO"),
+            filtered: topic("FILTERED"),
+            derived: topic("DERIVED"),
+            schema: topic("SCHEMA"),
+            availability: topic("AVAILABILITY"),
+            diagnostics: topic("DIAGNOSTICS"),
+            vibration: topic("VIBRATION"),
+            latest: topic("LATEST"),
+            aggregates: topic("AGG"),
+        }
+    }
+}
+
+/// Retained `AVAILABILITY`: `online` while the sensor delivers readings,
+/// `offline` once it fails, stops or is disabled, so dashboards can grey it
+/// out instead of showing its last values.
+pub(crate) async fn publish_availability(
+    mqtt: Option<&MqttHandle>,
+    topics: &SensorTopics,
+    online: bool,
+) {
+    if let Some(h) = mqtt {
+        let payload = if online { "online" } else { "offline" };
+        h.publish_retained(Arc::clone(&topics.availability), payload)
+            .await;
+    }
+}
+
+/// Mark a sensor that is disabled in config as `offline`, replacing any
+/// `online` retained from an earlier run.
+pub async fn publish_disabled(mqtt: &MqttHandle, base_topic: &str, cfg: &SensorConfig) {
+    let topics = SensorTopics::new(base_topic, cfg.topic_group.as_deref(), &cfg.name);
+    publish_availability(Some(mqtt), &topics, false).await;
+}
+
+/// What a sensor's publisher keeps from one reading to the next.
+#[derive(Default)]
+pub(crate) struct PublishState {
+    /// Quality flags last sent on INFO
+    info_sent: Option<IndexMap<String, Quality>>,
+    /// Payloads are encoded here, then copied out at their exact size for
+    /// the MQTT client to own, so encoding doesn't grow a fresh buffer
+    /// for every message
+    buf: Vec<u8>,
+}
+
+impl PublishState {
+    fn encode(&mut self, encode: impl FnOnce(&mut Vec<u8>)) -> Vec<u8> {
+        self.buf.clear();
+        encode(&mut self.buf);
+        self.buf.as_slice().to_vec()
+    }
+}
+
+/// The `INFO` payload.  Quality flags appear only when some channel isn't
+/// `good`, so the payload is unchanged for sensors that never set them.
+#[derive(serde::Serialize)]
+struct Info<'a> {
+    sensor: &'a str,
+    timestamp: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    schema_version: Option<u32>,
+    #[serde(skip_serializing_if = "IndexMap::is_empty")]
+    tags: &'a IndexMap<String, String>,
+    #[serde(skip_serializing_if = "IndexMap::is_empty")]
+    quality: &'a IndexMap<String, Quality>,
+}
+
+/// The `DIAGNOSTICS` payload: `{"timestamp": …, <key>: <value>, …}`.
+struct Diagnostics<'a>(&'a str, &'a IndexMap<String, f64>);
+
+impl serde::Serialize for Diagnostics<'_> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        use serde::ser::SerializeMap;
+
+        let mut map = serializer.serialize_map(Some(self.1.len() + 1))?;
+        map.serialize_entry("timestamp", self.0)?;
+        for (key, v) in self.1 {
+            map.serialize_entry(key, v)?;
+        }
+        map.end()
+    }
+}
+
+pub(crate) async fn publish_sensor_data(
+    mqtt: &MqttHandle,
+    topics: &SensorTopics,
+    name: &str,
+    tags: &IndexMap<String, String>,
+    data: &SensorData,
+    keys: &PayloadKeys<'_>,
+    state: &mut PublishState,
+) {
+    const INFALLIBLE: &str = "string keys and f64 values always serialise";
+
+    let data = published(data, keys);
+    let data = data.as_ref();
     let ts = data.timestamp.to_rfc3339();
+    let mut batch = Vec::with_capacity(4);
+
+    // INFO — retained and only re-sent when the quality flags change, unless
+    // configured as a per-reading heartbeat
+    if mqtt.info_every_reading || state.info_sent.as_ref() != Some(&data.quality) {
+        let info = Info {
+            sensor: name,
+            timestamp: &ts,
+            schema_version: mqtt.schema_version,
+            tags,
+            quality: &data.quality,
+        };
+        let payload = state.encode(|buf| serde_json::to_writer(buf, &info).expect(INFALLIBLE));
+        batch.push(PublishMsg {
+            retain: !mqtt.info_every_reading,
+            ..PublishMsg::new(Arc::clone(&topics.info), payload)
+        });
+        state.info_sent = Some(data.quality.clone());
+    }
+
+    let mut encode = |keys, values| {
+        state.encode(|buf| {
+            mqtt.serializer.encode_into(
+                &Reading {
+                    sensor: name,
+                    tags,
+                    timestamp: &ts,
+                    schema_version: mqtt.schema_version,
+                    data,
+                    keys,
+                    values,
+                    precision: mqtt.payload_precision,
+                },
+                buf,
+            )
+        })
+    };
+    let filtered = encode(&keys.filtered, None);
+    let derived = encode(&keys.derived, Some(&keys.values));
+    batch.push(PublishMsg::new(Arc::clone(&topics.filtered), filtered));
+    batch.push(PublishMsg::new(Arc::clone(&topics.derived), derived));
+
+    // DIAGNOSTICS — only from drivers asked for filter internals
+    if !data.diagnostics.is_empty() {
+        let diag = Diagnostics(&ts, &data.diagnostics);
+        let payload = state.encode(|buf| serde_json::to_writer(buf, &diag).expect(INFALLIBLE));
+        batch.push(PublishMsg::new(Arc::clone(&topics.diagnostics), payload));
+    }
+    mqtt.publish_batch(batch).await;
+}
 
-    // INFO
-    let info_payload = json!({ "sensor": name, "timestamp": ts }).to_string();
-    mqtt.publish(format!("{}/IMU/{}/INFO", base_topic, name), info_payload)
+/// Retained `LATEST`: one reading with the channels of both `FILTERED`
+/// and `DERIVED`, sent every `mqtt.snapshot_interval_secs` so a subscriber
+/// that joins late has values before the next live message.
+pub(crate) async fn publish_snapshot(
+    mqtt: &MqttHandle,
+    topics: &SensorTopics,
+    name: &str,
+    tags: &IndexMap<String, String>,
+    data: &SensorData,
+    keys: &PayloadKeys<'_>,
+) {
+    let data = published(data, keys);
+    let data = data.as_ref();
+    let ts = data.timestamp.to_rfc3339();
+    let payload = mqtt.serializer.encode(&Reading {
+        sensor: name,
+        tags,
+        timestamp: &ts,
+        schema_version: mqtt.schema_version,
+        data,
+        keys: &keys.all,
+        values: Some(&keys.values),
+        precision: mqtt.payload_precision,
+    });
+    mqtt.publish_retained(Arc::clone(&topics.latest), payload)
         .await;
+}
 
-    // Build separate filtered and derived maps
-    let filtered_keys = [
-        "accel_x",
-        "accel_y",
-        "accel_z",
-        "gyro_x",
-        "gyro_y",
-        "gyro_z",
-        "roll_rate",
-        "pitch_rate",
-        "yaw_rate",
-    ];
-    let mut filtered = serde_json::Map::new();
-    filtered.insert("timestamp".into(), json!(ts));
-    for key in &filtered_keys {
-        if let Some(&v) = data.fields.get(*key) {
-            filtered.insert(key.to_string(), json!(v));
-        }
-    }
-    mqtt.publish(
-        format!("{}/IMU/{}/FILTERED", base_topic, name),
-        serde_json::Value::Object(filtered).to_string(),
-    )
-    .await;
+/// `AGG/<n>S`: one message per `[mqtt.aggregation]` window that `data`
+/// ended, with the statistics of the channels published during it.
+pub(crate) async fn publish_rollups(
+    mqtt: &MqttHandle,
+    topics: &SensorTopics,
+    aggregator: &mut Aggregator,
+    data: &SensorData,
+    keys: &PayloadKeys<'_>,
+) {
+    let data = published(data, keys);
+    for rollup in aggregator.process(&data, &keys.all) {
+        let round = |v: f64| round_to(v, mqtt.payload_precision);
+        let channels: serde_json::Map<_, _> = rollup
+            .channels
+            .iter()
+            .map(|(k, s)| {
+                let stats = serde_json::json!({
+                    "samples": s.samples,
+                    "min": round(s.min),
+                    "max": round(s.max),
+                    "mean": round(s.mean),
+                    "stddev": round(s.stddev),
+                });
+                (k.clone(), stats)
+            })
+            .collect();
+        let mut payload = serde_json::json!({
+            "timestamp": rollup.start.to_rfc3339(),
+            "window_secs": rollup.window_secs,
+            "channels": channels,
+        });
+        if let Some(v) = mqtt.schema_version {
+            payload["schema_version"] = serde_json::json!(v);
+        }
+        let topic = format!("{}/{}S", topics.aggregates, rollup.window_secs);
+        mqtt.publish(topic, payload.to_string()).await;
+    }
+}
 
-    // DERIVED — everything else
-    let derived_keys = [
-        "g_force_x",
-        "g_force_y",
-        "g_force_z",
-        "combined_g",
-        "peak_g",
-        "lean_angle",
-        "bank_angle",
-        "tilt_angle",
-        "angular_velocity",
-    ];
-    let mut derived = serde_json::Map::new();
-    derived.insert("timestamp".into(), json!(ts));
-    for key in &derived_keys {
-        if let Some(&v) = data.fields.get(*key) {
-            derived.insert(key.to_string(), json!(v));
-        }
-    }
-    mqtt.publish(
-        format!("{}/IMU/{}/DERIVED", base_topic, name),
-        serde_json::Value::Object(derived).to_string(),
-    )
-    .await;
+/// One `VIBRATION` report per completed `[vibration_metrics]` window.
+async fn publish_vibration(
+    mqtt: &MqttHandle,
+    topics: &SensorTopics,
+    data: &SensorData,
+    window_secs: f64,
+    metrics: [AxisMetrics; 3],
+) {
+    let [x, y, z] = metrics;
+    let payload = serde_json::json!({
+        "timestamp": data.timestamp.to_rfc3339(),
+        "window_secs": window_secs,
+        "x": x,
+        "y": y,
+        "z": z,
+    });
+    mqtt.publish(Arc::clone(&topics.vibration), payload.to_string())
+        .await;
+}
+
+/// FILTERED and DERIVED JSON for one reading.
+pub fn channel_payloads(
+    data: &SensorData,
+    timestamp: &str,
+    keys: &PayloadKeys<'_>,
+    precision: Option<u32>,
+) -> [String; 2] {
+    let payload = |keys, values| {
+        serde_json::to_string(&Reading {
+            // JSON payloads don't name the sensor; their topic does
+            sensor: "",
+            tags: &IndexMap::new(),
+            timestamp,
+            schema_version: None,
+            data,
+            keys,
+            values,
+            precision,
+        })
+        .expect("string keys and f64 values always serialise")
+    };
+    [
+        payload(&keys.filtered, None),
+        payload(&keys.derived, Some(&keys.values)),
+    ]
+}
+
+// ---------------------------------------------------------------------------
+// Unit tests
+// ---------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sensors::ChannelValue;
+
+    #[test]
+    fn test_channel_payload_keeps_listed_keys_in_order() {
+        let mut data = SensorData::new(
+            [("tilt_angle", 3.0), ("unlisted", 1.0), ("g_force_x", 0.5)]
+                .into_iter()
+                .map(|(k, v)| (k.into(), v))
+                .collect(),
+        );
+        data.set_value("fix", ChannelValue::Text("3d".into()));
+
+        let all = ChannelFilter::default();
+        let json = |values| {
+            serde_json::to_string(&Reading {
+                sensor: "imu",
+                tags: &IndexMap::new(),
+                timestamp: "t",
+                schema_version: None,
+                data: &data,
+                keys: DERIVED_KEYS,
+                values,
+                precision: None,
+            })
+            .unwrap()
+        };
+        assert_eq!(
+            json(Some(&all)),
+            r#"{"timestamp":"t","g_force_x":0.5,"tilt_angle":3.0,"fix":"3d"}"#
+        );
+        assert_eq!(
+            json(None),
+            r#"{"timestamp":"t","g_force_x":0.5,"tilt_angle":3.0}"#
+        );
+    }
+
+    #[test]
+    fn test_payload_channel_filters() {
+        let mut data = SensorData::new(
+            [
+                ("accel_x", 0.5),
+                ("gyro_x", 2.0),
+                ("g_force_x", 0.5),
+                ("tilt_angle", 3.0),
+            ]
+            .into_iter()
+            .map(|(k, v)| (k.into(), v))
+            .collect(),
+        );
+        data.set_value("fix", ChannelValue::Text("3d".into()));
+        data.set_value("sats", ChannelValue::Int(9));
+
+        let filter = |include: &[&str], exclude: &[&str]| ChannelFilter {
+            include: include.iter().map(|k| k.to_string()).collect(),
+            exclude: exclude.iter().map(|k| k.to_string()).collect(),
+        };
+        let keys = PayloadKeys::new(
+            DERIVED_KEYS,
+            &filter(&[], &["accel_x"]),
+            &filter(&["g_force_x", "sats"], &["sats"]),
+        );
+        let [filtered, derived] = channel_payloads(&data, "t", &keys, None);
+        assert_eq!(filtered, r#"{"timestamp":"t","gyro_x":2.0}"#);
+        assert_eq!(derived, r#"{"timestamp":"t","g_force_x":0.5}"#);
+    }
+
+    #[test]
+    fn test_next_slot_aligns_to_wall_clock() {
+        let at = |s: &str| chrono::DateTime::parse_from_rfc3339(s).unwrap().to_utc();
+        let ms = std::time::Duration::from_millis;
+        assert_eq!(
+            next_slot(at("2024-05-01T12:00:00.037Z"), ms(100)),
+            at("2024-05-01T12:00:00.100Z")
+        );
+        // Exactly on a boundary waits for the next one
+        assert_eq!(
+            next_slot(at("2024-05-01T12:00:00.100Z"), ms(100)),
+            at("2024-05-01T12:00:00.200Z")
+        );
+        assert_eq!(
+            next_slot(at("2024-05-01T12:00:59.990Z"), ms(20)),
+            at("2024-05-01T12:01:00Z")
+        );
+    }
+
+    #[test]
+    fn test_json_schemas_cover_published_keys() {
+        use crate::sensors::VizType;
+
+        let fields = [
+            FieldDescriptor {
+                key: "accel_x",
+                label: "Accel X",
+                viz: VizType::GForce,
+                range: None,
+                group: None,
+            },
+            FieldDescriptor {
+                key: "g_force_x",
+                label: "G X",
+                viz: VizType::GForce,
+                range: None,
+                group: None,
+            },
+            FieldDescriptor {
+                key: "status",
+                label: "Status",
+                viz: VizType::Text,
+                range: None,
+                group: None,
+            },
+        ];
+        let schemas =
+            payload_json_schemas("imu", &IndexMap::new(), &fields, &PayloadKeys::default());
+
+        let mut data = SensorData::new(
+            [("accel_x".into(), 0.1), ("g_force_x".into(), 0.2)]
+                .into_iter()
+                .collect(),
+        );
+        data.set_value("status", ChannelValue::Text("ok".into()));
+        data.monotonic = Some(1.0);
+        let payloads = channel_payloads(
+            &data,
+            "2025-01-01T00:00:00+00:00",
+            &PayloadKeys::default(),
+            None,
+        );
+        for (topic, payload) in ["FILTERED", "DERIVED"].iter().zip(payloads) {
+            let json: serde_json::Value = serde_json::from_str(&payload).unwrap();
+            let props = schemas[topic]["properties"].as_object().unwrap();
+            for key in json.as_object().unwrap().keys() {
+                assert!(props.contains_key(key), "{} missing from {}", key, topic);
+            }
+        }
+        let derived = &schemas["DERIVED"]["properties"];
+        assert_eq!(derived["g_force_x"]["description"], "Unit: G");
+        assert_eq!(derived["status"]["type"], "string");
+        assert!(schemas["FILTERED"]["properties"].get("g_force_x").is_none());
+        assert_eq!(
+            schemas["INFO"]["required"],
+            serde_json::json!(["sensor", "timestamp"])
+        );
+    }
+
+    #[test]
+    fn test_clock_fields_only_when_set() {
+        let mut data = SensorData::new([("unlisted".into(), 1.0)].into_iter().collect());
+        let [filtered, _] = channel_payloads(&data, "t", &PayloadKeys::default(), None);
+        assert_eq!(filtered, r#"{"timestamp":"t"}"#);
+
+        data.monotonic = Some(12.5);
+        data.clock_unsynced = true;
+        let [filtered, derived] = channel_payloads(&data, "t", &PayloadKeys::default(), None);
+        assert_eq!(
+            filtered,
+            r#"{"timestamp":"t","monotonic":12.5,"clock_unsynced":true}"#
+        );
+        assert_eq!(filtered, derived);
+    }
+
+    #[tokio::test]
+    async fn test_info_sent_once_then_on_quality_change() {
+        let mqtt = MqttHandle::offline(&Default::default());
+        let topics = SensorTopics::new("/T", None, "imu");
+        let mut state = PublishState::default();
+        let mut data = SensorData::new([("speed".into(), 1.0)].into_iter().collect());
+
+        let mut publish = async |data: &SensorData| {
+            publish_sensor_data(
+                &mqtt,
+                &topics,
+                "imu",
+                &IndexMap::new(),
+                data,
+                &PayloadKeys::default(),
+                &mut state,
+            )
+            .await;
+            let msgs = mqtt.take_queued();
+            msgs.iter()
+                .filter(|m| m.topic == topics.info)
+                .map(|m| m.retain)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(publish(&data).await, [true]);
+        assert!(publish(&data).await.is_empty());
+        data.set_quality("speed", Quality::Stale);
+        assert_eq!(publish(&data).await, [true]);
+        assert!(publish(&data).await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_info_carries_tags() {
+        let mqtt = MqttHandle::offline(&Default::default());
+        let topics = SensorTopics::new("/T", None, "wheel");
+        let tags = IndexMap::from([
+            ("location".into(), "front-left".to_string()),
+            ("axle".into(), "front".to_string()),
+        ]);
+        let data = SensorData::new([("temp".into(), 40.0)].into_iter().collect());
+        publish_sensor_data(
+            &mqtt,
+            &topics,
+            "wheel",
+            &tags,
+            &data,
+            &PayloadKeys::default(),
+            &mut PublishState::default(),
+        )
+        .await;
+        let msgs = mqtt.take_queued();
+        let info = msgs.iter().find(|m| m.topic == topics.info).unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&info.payload).unwrap();
+        assert_eq!(
+            json["tags"],
+            serde_json::json!({"location": "front-left", "axle": "front"})
+        );
+
+        let schemas = payload_json_schemas("wheel", &tags, &[], &PayloadKeys::default());
+        let tag_schema = &schemas["INFO"]["properties"]["tags"]["properties"];
+        assert_eq!(tag_schema["axle"]["const"], "front");
+    }
+
+    #[tokio::test]
+    async fn test_channel_map_renames_and_drops_published_channels() {
+        let mqtt = MqttHandle::offline(&Default::default());
+        let topics = SensorTopics::new("/T", None, "imu");
+        let map = IndexMap::from([
+            ("g_force_y".into(), "longitudinal_g".to_string()),
+            ("gyro_z".into(), String::new()),
+        ]);
+        let keys = PayloadKeys::default().with_channel_map(&map);
+        let mut data = SensorData::new(
+            [("gyro_x", 1.0), ("gyro_z", 2.0), ("g_force_y", 0.3)]
+                .into_iter()
+                .map(|(k, v)| (k.into(), v))
+                .collect(),
+        );
+        data.set_quality("g_force_y", Quality::Estimated);
+        publish_sensor_data(
+            &mqtt,
+            &topics,
+            "imu",
+            &IndexMap::new(),
+            &data,
+            &keys,
+            &mut PublishState::default(),
+        )
+        .await;
+
+        let payload = |topic: &Arc<str>| -> serde_json::Value {
+            let msgs = mqtt.take_queued();
+            let m = msgs.iter().find(|m| m.topic == *topic).unwrap();
+            serde_json::from_slice(&m.payload).unwrap()
+        };
+        let json = payload(&topics.filtered);
+        assert_eq!(json["gyro_x"], 1.0);
+        assert!(json.get("gyro_z").is_none());
+        publish_sensor_data(
+            &mqtt,
+            &topics,
+            "imu",
+            &IndexMap::new(),
+            &data,
+            &keys,
+            &mut PublishState::default(),
+        )
+        .await;
+        let json = payload(&topics.derived);
+        assert_eq!(json["longitudinal_g"], 0.3);
+        assert!(json.get("g_force_y").is_none());
+        publish_sensor_data(
+            &mqtt,
+            &topics,
+            "imu",
+            &IndexMap::new(),
+            &data,
+            &keys,
+            &mut PublishState::default(),
+        )
+        .await;
+        assert_eq!(
+            payload(&topics.info)["quality"]["longitudinal_g"],
+            "estimated"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_snapshot_is_retained_with_filtered_and_derived_channels() {
+        let mqtt = MqttHandle::offline(&Default::default());
+        let topics = SensorTopics::new("/T", None, "imu");
+        let map = IndexMap::from([("g_force_y".into(), "longitudinal_g".to_string())]);
+        let keys = PayloadKeys::default().with_channel_map(&map);
+        let tags = IndexMap::new();
+        let data = SensorData::new(
+            [("gyro_x", 1.0), ("g_force_y", 0.3)]
+                .into_iter()
+                .map(|(k, v)| (k.into(), v))
+                .collect(),
+        );
+        publish_snapshot(&mqtt, &topics, "imu", &tags, &data, &keys).await;
+
+        let msgs = mqtt.take_queued();
+        let [m] = msgs.as_slice() else {
+            panic!("expected one message, got {}", msgs.len());
+        };
+        assert_eq!(&*m.topic, "/T/IMU/imu/LATEST");
+        assert!(m.retain);
+        let json: serde_json::Value = serde_json::from_slice(&m.payload).unwrap();
+        assert_eq!(json["gyro_x"], 1.0);
+        assert_eq!(json["longitudinal_g"], 0.3);
+    }
+
+    #[tokio::test]
+    async fn test_rollups_published_per_window() {
+        let mqtt = MqttHandle::offline(&Default::default());
+        let topics = SensorTopics::new("/T", None, "imu");
+        let map = IndexMap::from([("g_force_y".into(), "longitudinal_g".to_string())]);
+        let keys = PayloadKeys::default().with_channel_map(&map);
+        let mut aggregator = Aggregator::new(&crate::config::AggregationConfig {
+            enabled: true,
+            windows_secs: vec![1, 10],
+            ..Default::default()
+        });
+        for (ms, g) in [(0, 0.2), (500, 0.4), (1000, 0.0)] {
+            let mut data = SensorData::new([("g_force_y".into(), g)].into_iter().collect());
+            data.timestamp = chrono::DateTime::from_timestamp_millis(ms).unwrap();
+            publish_rollups(&mqtt, &topics, &mut aggregator, &data, &keys).await;
+        }
+
+        let msgs = mqtt.take_queued();
+        let [m] = msgs.as_slice() else {
+            panic!("expected one message, got {}", msgs.len());
+        };
+        assert_eq!(&*m.topic, "/T/IMU/imu/AGG/1S");
+        let json: serde_json::Value = serde_json::from_slice(&m.payload).unwrap();
+        assert_eq!(json["window_secs"], 1);
+        assert_eq!(json["schema_version"], crate::payload::SCHEMA_VERSION);
+        let g = &json["channels"]["longitudinal_g"];
+        assert_eq!(g["samples"], 2);
+        assert_eq!(g["max"], 0.4);
+        assert!((g["mean"].as_f64().unwrap() - 0.3).abs() < 1e-9);
+    }
+
+    #[tokio::test]
+    async fn test_diagnostics_published_only_when_present() {
+        let mqtt = MqttHandle::offline(&Default::default());
+        let topics = SensorTopics::new("/T", None, "imu");
+        let mut data = SensorData::new([("gyro_x".into(), 1.0)].into_iter().collect());
+
+        publish_sensor_data(
+            &mqtt,
+            &topics,
+            "imu",
+            &IndexMap::new(),
+            &data,
+            &PayloadKeys::default(),
+            &mut PublishState::default(),
+        )
+        .await;
+        let msgs = mqtt.take_queued();
+        assert!(msgs.iter().all(|m| m.topic != topics.diagnostics));
+
+        data.diagnostics.insert("gyro_x_k".into(), 0.25);
+        data.diagnostics.insert("gyro_x_held".into(), 1.0);
+        publish_sensor_data(
+            &mqtt,
+            &topics,
+            "imu",
+            &IndexMap::new(),
+            &data,
+            &PayloadKeys::default(),
+            &mut PublishState::default(),
+        )
+        .await;
+        let msgs = mqtt.take_queued();
+        let diag = msgs.iter().find(|m| m.topic == topics.diagnostics).unwrap();
+        assert!(!diag.retain);
+        let json: serde_json::Value = serde_json::from_slice(&diag.payload).unwrap();
+        assert_eq!(json["gyro_x_k"], 0.25);
+        assert_eq!(json["gyro_x_held"], 1.0);
+        assert!(json["timestamp"].is_string());
+    }
+
+    #[tokio::test]
+    async fn test_error_events_only_on_changes() {
+        let mqtt = MqttHandle::offline(&Default::default());
+        let events = ErrorEvents::new(Some(mqtt.clone()), "/T");
+        let mut app = AppState::new(String::new(), false, 10);
+        app.sensor_statuses.insert(
+            "imu".into(),
+            SensorStatus {
+                name: "imu".into(),
+                driver: "mpu6500".into(),
+                connection_display: String::new(),
+                group: None,
+                enabled: true,
+                connected: true,
+                last_error: None,
+                stuck: Vec::new(),
+                calibration: CalibrationState::Idle,
+                fields: Vec::new(),
+            },
+        );
+        let state: SharedState = Arc::new(tokio::sync::RwLock::new(app));
+
+        let timeout = Some("I2C read timed out".to_string());
+        update_status(&state, &events, "imu", false, timeout.clone()).await;
+        update_status(&state, &events, "imu", false, timeout).await;
+        update_status(&state, &events, "imu", true, None).await;
+        update_status(&state, &events, "imu", true, None).await;
+
+        let msgs = mqtt.take_queued();
+        assert!(msgs.iter().all(|m| &*m.topic == "/T/ERRORS" && !m.retain));
+        let events: Vec<serde_json::Value> = msgs
+            .iter()
+            .map(|m| serde_json::from_slice(&m.payload).unwrap())
+            .collect();
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0]["sensor"], "imu");
+        assert_eq!(events[0]["severity"], "error");
+        assert_eq!(events[0]["event"], "fault");
+        assert_eq!(events[0]["message"], "I2C read timed out");
+        assert_eq!(events[1]["severity"], "info");
+        assert_eq!(events[1]["event"], "recovered");
+    }
+
+    #[test]
+    fn test_channel_payload_rounds_to_precision() {
+        let mut data = SensorData::new(
+            [("g_force_x", 0.123456789), ("tilt_angle", -12.5551)]
+                .into_iter()
+                .map(|(k, v)| (k.into(), v))
+                .collect(),
+        );
+        data.set_value("hdop", ChannelValue::Float(1.23456));
+        data.set_value("sats", ChannelValue::Int(9));
+
+        let json = serde_json::to_string(&Reading {
+            sensor: "imu",
+            tags: &IndexMap::new(),
+            timestamp: "t",
+            schema_version: None,
+            data: &data,
+            keys: DERIVED_KEYS,
+            values: Some(&ChannelFilter::default()),
+            precision: Some(2),
+        })
+        .unwrap();
+        assert_eq!(
+            json,
+            r#"{"timestamp":"t","g_force_x":0.12,"tilt_angle":-12.56,"hdop":1.23,"sats":9}"#
+        );
+    }
 }
@@ -0,0 +1,197 @@
+//! Wheel slip — lockup and wheelspin from a wheel speed sensor and the
+//! vehicle's speed.
+//!
+//! Enabled with a `[slip]` section naming the IMU that `[dynamics]` adds
+//! `speed` to, and the sensor reporting the speed of one wheel (a pulse
+//! counter, or an ADC channel scaled with `[[transforms]]`).  Slip is how
+//! far the wheel is off the vehicle's speed:
+//!
+//! ```text
+//! wheel_slip = (wheel − vehicle) / vehicle × 100 %
+//! ```
+//!
+//! Beyond `lockup_pct` below zero the wheel is locking; beyond
+//! `wheelspin_pct` above it is spinning.  Each event raises an alert, as
+//! `[[alerts]]` do, and clears once slip is back within half the limit.
+
+use crate::alerts::{Alert, AlertChange};
+use crate::config::SlipConfig;
+use crate::sensors::{FieldDescriptor, Quality, SensorData, VizType};
+
+const FIELDS: &[FieldDescriptor] = &[
+    FieldDescriptor {
+        key: "wheel_slip",
+        label: "Wheel Slip",
+        viz: VizType::Numeric { unit: "%" },
+        range: Some((-100.0, 100.0)),
+        group: Some("SLIP"),
+    },
+    FieldDescriptor {
+        key: "lockup",
+        label: "Lockup",
+        viz: VizType::Value,
+        range: Some((0.0, 1.0)),
+        group: None,
+    },
+    FieldDescriptor {
+        key: "wheelspin",
+        label: "Wheelspin",
+        viz: VizType::Value,
+        range: Some((0.0, 1.0)),
+        group: None,
+    },
+];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum SlipKind {
+    Lockup,
+    Wheelspin,
+}
+
+pub struct SlipMonitor {
+    cfg: SlipConfig,
+    /// The event in progress and the id of its alert
+    event: Option<(SlipKind, u64)>,
+}
+
+impl SlipMonitor {
+    pub fn new(cfg: SlipConfig) -> Self {
+        Self { cfg, event: None }
+    }
+
+    /// Descriptors for the channels [`process`](Self::process) adds.
+    pub fn field_descriptors() -> &'static [FieldDescriptor] {
+        FIELDS
+    }
+
+    /// Compare `wheel_kmh`, the latest wheel speed if recent enough to
+    /// trust, with the reading's vehicle speed and append the slip channels.
+    /// Returns the alerts raised or cleared.  Readings without the vehicle
+    /// speed channel are left alone.
+    pub fn process(&mut self, data: &mut SensorData, wheel_kmh: Option<f64>) -> Vec<AlertChange> {
+        let Some(&vehicle) = data.fields.get(self.cfg.vehicle_speed_field.as_str()) else {
+            return Vec::new();
+        };
+        // Slip means little when barely moving, and nothing without a wheel
+        let slip = wheel_kmh
+            .filter(|_| vehicle >= self.cfg.min_speed_kmh)
+            .map(|wheel| (wheel - vehicle) / vehicle * 100.0);
+
+        let mut changes = Vec::new();
+        let within = |limit: f64, slip: f64| slip.abs() < limit / 2.0;
+        if let Some((kind, id)) = self.event {
+            let ended = match (kind, slip) {
+                (SlipKind::Lockup, Some(s)) => s > 0.0 || within(self.cfg.lockup_pct, s),
+                (SlipKind::Wheelspin, Some(s)) => s < 0.0 || within(self.cfg.wheelspin_pct, s),
+                (_, None) => true,
+            };
+            if ended {
+                self.event = None;
+                changes.push(AlertChange::Cleared { id });
+            }
+        }
+        if let (None, Some(s)) = (self.event, slip) {
+            let started = if s <= -self.cfg.lockup_pct {
+                Some((SlipKind::Lockup, "Wheel lockup"))
+            } else if s >= self.cfg.wheelspin_pct {
+                Some((SlipKind::Wheelspin, "Wheelspin"))
+            } else {
+                None
+            };
+            if let Some((kind, what)) = started {
+                let alert = Alert::new(
+                    &self.cfg.sensor,
+                    format!("{}: {:.0}% slip at {:.0} km/h", what, s, vehicle),
+                );
+                self.event = Some((kind, alert.id));
+                changes.push(AlertChange::Fired(alert));
+            }
+        }
+
+        data.fields.insert("wheel_slip".into(), slip.unwrap_or(0.0));
+        if slip.is_none() {
+            data.set_quality("wheel_slip", Quality::Invalid);
+        }
+        let flag = |kind| f64::from(u8::from(matches!(self.event, Some((k, _)) if k == kind)));
+        data.fields.insert("lockup".into(), flag(SlipKind::Lockup));
+        data.fields
+            .insert("wheelspin".into(), flag(SlipKind::Wheelspin));
+        changes
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Unit tests
+// ---------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn reading(speed: f64) -> SensorData {
+        SensorData::new([("speed".into(), speed)].into_iter().collect())
+    }
+
+    fn monitor() -> SlipMonitor {
+        SlipMonitor::new(SlipConfig {
+            sensor: "imu".into(),
+            ..SlipConfig::default()
+        })
+    }
+
+    #[test]
+    fn test_lockup_raises_and_clears_an_alert() {
+        let mut m = monitor();
+        let mut d = reading(80.0);
+        assert!(m.process(&mut d, Some(82.0)).is_empty());
+        assert!((d.fields["wheel_slip"] - 2.5).abs() < 1e-9);
+        assert_eq!(d.fields["lockup"], 0.0);
+
+        // Wheel stops while the car is still doing 60
+        let mut d = reading(60.0);
+        let changes = m.process(&mut d, Some(0.0));
+        let [AlertChange::Fired(alert)] = changes.as_slice() else {
+            panic!("expected one alert, got {:?}", changes);
+        };
+        assert_eq!(alert.text, "Wheel lockup: -100% slip at 60 km/h");
+        assert_eq!(alert.sensor, "imu");
+        assert_eq!(d.fields["lockup"], 1.0);
+
+        // Recovering, but not yet within half the limit
+        let mut d = reading(50.0);
+        assert!(m.process(&mut d, Some(42.0)).is_empty());
+        assert_eq!(d.fields["lockup"], 1.0);
+        let mut d = reading(45.0);
+        assert_eq!(
+            m.process(&mut d, Some(43.0)),
+            [AlertChange::Cleared { id: alert.id }]
+        );
+        assert_eq!(d.fields["lockup"], 0.0);
+    }
+
+    #[test]
+    fn test_wheelspin_and_invalid_slip() {
+        let mut m = monitor();
+        let mut d = reading(20.0);
+        let changes = m.process(&mut d, Some(30.0));
+        assert!(matches!(changes.as_slice(), [AlertChange::Fired(_)]));
+        assert_eq!(d.fields["wheelspin"], 1.0);
+        assert_eq!(d.fields["lockup"], 0.0);
+
+        // Stale wheel speed ends the event and flags slip as invalid
+        let mut d = reading(20.0);
+        let changes = m.process(&mut d, None);
+        assert!(matches!(changes.as_slice(), [AlertChange::Cleared { .. }]));
+        assert_eq!(d.quality("wheel_slip"), Quality::Invalid);
+
+        // Too slow to judge
+        let mut d = reading(3.0);
+        assert!(m.process(&mut d, Some(0.0)).is_empty());
+        assert_eq!(d.quality("wheel_slip"), Quality::Invalid);
+
+        // Without the vehicle speed channel nothing is added
+        let mut d = SensorData::default();
+        assert!(m.process(&mut d, Some(50.0)).is_empty());
+        assert!(!d.fields.contains_key("wheel_slip"));
+    }
+}
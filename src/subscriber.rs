@@ -0,0 +1,448 @@
+//! Inspector for what a daemon publishes (`sensors-to-mqtt sub`).
+//!
+//! Subscribes to `<base_topic>/#` and rebuilds an [`AppState`] from the
+//! messages the broker delivers: `SCHEMA` gives each sensor its channels,
+//! `FILTERED` and `DERIVED` become readings, `AVAILABILITY` drives the
+//! status dot, and `ERRORS` / `IGNITION` reach the status bar.  The usual
+//! TUI then draws it, so a laptop shows exactly what the broker received
+//! from the car rather than what the sensors read.
+
+use std::collections::HashMap;
+
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use indexmap::IndexMap;
+use serde::Deserialize;
+use tokio_util::sync::CancellationToken;
+
+use crate::config::MqttConfig;
+use crate::models::{
+    AppState, CalibrationState, PublishMode, SensorHistory, SensorStatus, SharedState,
+};
+use crate::protobuf;
+use crate::sensors::{ChannelValue, FieldMeta, Quality, SensorData};
+
+/// Readings kept per sensor for the charts, as for a local sensor without
+/// `history_size` or `history_seconds`.
+const HISTORY_SIZE: usize = 600;
+
+#[derive(Deserialize)]
+struct Schema {
+    #[serde(default)]
+    driver: String,
+    #[serde(default)]
+    channels: Vec<SchemaChannel>,
+}
+
+#[derive(Deserialize)]
+struct SchemaChannel {
+    key: String,
+    #[serde(default)]
+    label: String,
+    #[serde(default)]
+    unit: String,
+    #[serde(default)]
+    range: Option<(f64, f64)>,
+}
+
+#[derive(Deserialize)]
+struct ErrorEvent {
+    #[serde(default)]
+    sensor: String,
+    #[serde(default)]
+    severity: String,
+    #[serde(default)]
+    message: String,
+}
+
+pub struct Inspector {
+    base_topic: String,
+    /// Reading being assembled per sensor: `FILTERED` and `DERIVED` arrive
+    /// separately with the same timestamp
+    pending: HashMap<String, SensorData>,
+    /// Quality flags from the latest `INFO`, applied to later readings
+    quality: HashMap<String, IndexMap<String, Quality>>,
+}
+
+impl Inspector {
+    pub fn new(base_topic: &str) -> Self {
+        Self {
+            base_topic: base_topic.to_string(),
+            pending: HashMap::new(),
+            quality: HashMap::new(),
+        }
+    }
+
+    /// Apply one received message.  Topics this program doesn't publish
+    /// and payloads it can't parse are ignored.
+    pub fn apply(&mut self, s: &mut AppState, topic: &str, payload: &[u8]) {
+        let Some(rest) = topic
+            .strip_prefix(self.base_topic.as_str())
+            .and_then(|t| t.strip_prefix('/'))
+        else {
+            return;
+        };
+        match rest {
+            "ERRORS" => {
+                if let Ok(ev) = serde_json::from_slice::<ErrorEvent>(payload) {
+                    let text = format!("{}: {}", ev.sensor, ev.message);
+                    match ev.severity.as_str() {
+                        "error" => s.status.error(text),
+                        "warning" => s.status.warn(text),
+                        _ => s.status.info(text),
+                    }
+                }
+            }
+            "IGNITION" => {
+                if let Ok(v) = serde_json::from_slice::<serde_json::Value>(payload) {
+                    s.paused = v["ignition"] == "off";
+                }
+            }
+            "PUBLISH" => {
+                if let Some(mode) = PublishMode::parse(payload) {
+                    s.publish_mode = mode;
+                }
+            }
+            _ => {
+                let Some((group, name, sub)) = sensor_topic(rest) else {
+                    return;
+                };
+                self.apply_sensor(s, group, name, sub, payload);
+            }
+        }
+    }
+
+    fn apply_sensor(
+        &mut self,
+        s: &mut AppState,
+        group: Option<&str>,
+        name: &str,
+        sub: &str,
+        payload: &[u8],
+    ) {
+        if !s.sensor_statuses.contains_key(name) {
+            let base = match group {
+                Some(g) => format!("{}/{}", self.base_topic, g),
+                None => self.base_topic.clone(),
+            };
+            s.sensor_statuses.insert(
+                name.to_string(),
+                SensorStatus {
+                    name: name.to_string(),
+                    driver: String::new(),
+                    connection_display: format!("{}/IMU/{}", base, name),
+                    group: group.map(str::to_string),
+                    enabled: true,
+                    connected: false,
+                    last_error: None,
+                    stuck: Vec::new(),
+                    calibration: CalibrationState::Idle,
+                    fields: Vec::new(),
+                },
+            );
+            s.sensor_history
+                .insert(name.to_string(), SensorHistory::new(HISTORY_SIZE));
+        }
+        match sub {
+            "SCHEMA" => {
+                let Ok(schema) = serde_json::from_slice::<Schema>(payload) else {
+                    return;
+                };
+                if let Some(st) = s.sensor_statuses.get_mut(name) {
+                    st.driver = schema.driver;
+                    st.fields = schema
+                        .channels
+                        .into_iter()
+                        .map(|c| FieldMeta {
+                            is_g_force: c.unit == "G",
+                            key: c.key,
+                            label: c.label,
+                            unit: c.unit,
+                            range: c.range,
+                            group: None,
+                        })
+                        .collect();
+                }
+            }
+            "AVAILABILITY" => {
+                if let Some(st) = s.sensor_statuses.get_mut(name) {
+                    st.connected = payload == b"online";
+                }
+            }
+            "INFO" => {
+                if let Ok(v) = serde_json::from_slice::<serde_json::Value>(payload) {
+                    let quality = serde_json::from_value(v["quality"].clone()).unwrap_or_default();
+                    self.quality.insert(name.to_string(), quality);
+                }
+            }
+            "FILTERED" | "DERIVED" | "LATEST" => {
+                // JSON by default, protobuf with `payload_format = "protobuf"`
+                let reading = match serde_json::from_slice(payload) {
+                    Ok(map) => reading_from_json(map),
+                    Err(_) => protobuf::decode_reading(payload).ok(),
+                };
+                if let Some(reading) = reading {
+                    self.merge(s, name, reading);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Add one payload's channels to the sensor's current reading, moving
+    /// the previous reading into history once a new timestamp shows up.
+    fn merge(&mut self, s: &mut AppState, name: &str, reading: SensorData) {
+        let data = self.pending.entry(name.to_string()).or_default();
+        if data.timestamp != reading.timestamp {
+            if !data.fields.is_empty()
+                && let Some(h) = s.sensor_history.get_mut(name)
+            {
+                h.push(data);
+            }
+            *data = SensorData {
+                timestamp: reading.timestamp,
+                quality: self.quality.get(name).cloned().unwrap_or_default(),
+                ..Default::default()
+            };
+        }
+        data.monotonic = reading.monotonic.or(data.monotonic);
+        data.clock_unsynced |= reading.clock_unsynced;
+        data.fields.extend(reading.fields);
+        data.values.extend(reading.values);
+        s.sensor_data.insert(name.to_string(), data.clone());
+        if let Some(st) = s.sensor_statuses.get_mut(name) {
+            st.connected = true;
+        }
+    }
+}
+
+/// `(group, sensor, last segment)` of a sensor topic below the base:
+/// `IMU/<sensor>/<sub>`, or `<group>/IMU/<sensor>/<sub>` for a group that
+/// goes in the topic path.
+fn sensor_topic(rest: &str) -> Option<(Option<&str>, &str, &str)> {
+    let (group, rest) = match rest.strip_prefix("IMU/") {
+        Some(r) => (None, r),
+        None => {
+            let (group, r) = rest.split_once('/')?;
+            (Some(group), r.strip_prefix("IMU/")?)
+        }
+    };
+    let (name, sub) = rest.rsplit_once('/')?;
+    Some((group, name, sub))
+}
+
+/// The reading in a JSON FILTERED / DERIVED payload, `None` without a
+/// valid timestamp.
+fn reading_from_json(map: serde_json::Map<String, serde_json::Value>) -> Option<SensorData> {
+    let timestamp = map
+        .get("timestamp")
+        .and_then(|t| t.as_str())
+        .and_then(|t| DateTime::parse_from_rfc3339(t).ok())?
+        .with_timezone(&Utc);
+    let mut data = SensorData {
+        timestamp,
+        ..Default::default()
+    };
+    for (key, v) in map {
+        match (key.as_str(), v) {
+            ("timestamp" | "schema_version", _) => {}
+            ("monotonic", v) => data.monotonic = v.as_f64(),
+            ("clock_unsynced", v) => data.clock_unsynced = v == true,
+            (_, serde_json::Value::Number(n)) if n.is_f64() => {
+                data.fields.insert(key.into(), n.as_f64().unwrap_or_default());
+            }
+            (_, v) => {
+                if let Ok(value) = serde_json::from_value::<ChannelValue>(v) {
+                    data.values.insert(key, value);
+                }
+            }
+        }
+    }
+    Some(data)
+}
+
+/// Subscribe to `cfg.base_topic` and feed `state` until `cancel` fires,
+/// reconnecting after errors unless `auto_reconnect` is off.
+#[cfg(feature = "mqtt")]
+pub async fn run(cfg: &MqttConfig, state: SharedState, cancel: CancellationToken) -> Result<()> {
+    use rumqttc::{AsyncClient, Event, Incoming, MqttOptions, QoS};
+    use tracing::{info, warn};
+
+    use crate::models::MqttStatus;
+    use crate::mqtt_handler::Reconnect;
+
+    // A client id of its own, so the daemon isn't disconnected by the broker
+    let client_id = format!("{}-sub-{}", cfg.client_id, std::process::id());
+    let mut opts = MqttOptions::new(client_id, cfg.host.clone(), cfg.port);
+    opts.set_keep_alive(std::time::Duration::from_secs(cfg.keep_alive_secs.max(5)));
+    opts.set_clean_session(true);
+    // SCHEMA lists every channel and can outgrow rumqttc's 10 KB default
+    opts.set_max_packet_size(256 * 1024, 10 * 1024);
+    if let (Some(u), Some(p)) = (cfg.username.clone(), cfg.password.clone()) {
+        opts.set_credentials(u, p);
+    }
+    let (client, mut evl) = AsyncClient::new(opts, 10);
+    let status = state.read().await.mqtt_status.clone();
+    let filter = format!("{}/#", cfg.base_topic);
+    let mut inspector = Inspector::new(&cfg.base_topic);
+    let mut reconnect = Reconnect::new(cfg);
+
+    loop {
+        let event = tokio::select! {
+            _ = cancel.cancelled() => break,
+            ev = evl.poll() => ev,
+        };
+        match event {
+            Ok(Event::Incoming(Incoming::ConnAck(_))) => {
+                info!("MQTT connected, subscribing to {}", filter);
+                reconnect.connected();
+                *status.write().unwrap() = MqttStatus::Connected;
+                client.try_subscribe(filter.as_str(), QoS::AtMostOnce)?;
+            }
+            Ok(Event::Incoming(Incoming::Publish(p))) => {
+                inspector.apply(&mut *state.write().await, &p.topic, &p.payload);
+            }
+            Ok(Event::Incoming(Incoming::Disconnect)) => {
+                warn!("MQTT disconnected");
+                *status.write().unwrap() = MqttStatus::Disconnected;
+            }
+            Err(e) => {
+                let (new_status, wait) = reconnect.failed(e.to_string());
+                *status.write().unwrap() = new_status;
+                let Some(wait) = wait else {
+                    anyhow::bail!("MQTT error: {} (auto_reconnect is off)", e);
+                };
+                warn!("MQTT error: {}; reconnecting in {:?}", e, wait);
+                tokio::select! {
+                    _ = cancel.cancelled() => break,
+                    _ = tokio::time::sleep(wait) => {}
+                }
+            }
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+#[cfg(not(feature = "mqtt"))]
+pub async fn run(_cfg: &MqttConfig, _state: SharedState, _cancel: CancellationToken) -> Result<()> {
+    anyhow::bail!("built without the `mqtt` feature, so there is nothing to subscribe with")
+}
+
+// ---------------------------------------------------------------------------
+// Unit tests
+// ---------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rebuilds_sensor_from_topics() {
+        let mut s = AppState::new(String::new(), true, 10);
+        let mut inspector = Inspector::new("/SENSORS");
+        let mut apply = |s: &mut AppState, topic: &str, payload: &str| {
+            inspector.apply(s, topic, payload.as_bytes());
+        };
+        apply(
+            &mut s,
+            "/SENSORS/IMU/Front IMU/SCHEMA",
+            r#"{"sensor":"Front IMU","driver":"mpu6500","channels":[
+                {"key":"g_force_x","label":"G X","unit":"G","type":"number","range":[-4.0,4.0]},
+                {"key":"temperature","label":"Temp","unit":"°C","type":"number","range":null}]}"#,
+        );
+        apply(&mut s, "/SENSORS/IMU/Front IMU/AVAILABILITY", "offline");
+        apply(
+            &mut s,
+            "/SENSORS/IMU/Front IMU/INFO",
+            r#"{"sensor":"Front IMU","timestamp":"t","quality":{"temperature":"stale"}}"#,
+        );
+        let t1 = "2025-01-01T12:00:00+00:00";
+        let t2 = "2025-01-01T12:00:00.100+00:00";
+        apply(
+            &mut s,
+            "/SENSORS/IMU/Front IMU/FILTERED",
+            &format!(r#"{{"timestamp":"{}","monotonic":3.5,"accel_x":0.5}}"#, t1),
+        );
+        apply(
+            &mut s,
+            "/SENSORS/IMU/Front IMU/DERIVED",
+            &format!(r#"{{"timestamp":"{}","g_force_x":0.25,"fix":3}}"#, t1),
+        );
+        apply(&mut s, "/OTHER/IMU/x/FILTERED", r#"{"timestamp":"bad"}"#);
+
+        let st = &s.sensor_statuses["Front IMU"];
+        assert_eq!(st.driver, "mpu6500");
+        assert!(st.fields[0].is_g_force && !st.fields[1].is_g_force);
+        assert_eq!(st.fields[0].range, Some((-4.0, 4.0)));
+        assert!(st.connected, "readings arriving means online");
+        let data = &s.sensor_data["Front IMU"];
+        assert_eq!(data.fields["accel_x"], 0.5);
+        assert_eq!(data.fields["g_force_x"], 0.25);
+        assert_eq!(data.values["fix"], ChannelValue::Int(3));
+        assert_eq!(data.monotonic, Some(3.5));
+        assert_eq!(data.quality["temperature"], Quality::Stale);
+        assert_eq!(s.sensor_statuses.len(), 1);
+
+        // The first reading reaches history once the next one starts
+        assert!(s.sensor_history["Front IMU"].timestamps.is_empty());
+        apply(
+            &mut s,
+            "/SENSORS/IMU/Front IMU/FILTERED",
+            &format!(r#"{{"timestamp":"{}","accel_x":0.6}}"#, t2),
+        );
+        let h = &s.sensor_history["Front IMU"];
+        assert_eq!(h.timestamps.len(), 1);
+        assert_eq!(h.fields["g_force_x"], [0.25]);
+
+        // protobuf payloads merge into the same reading
+        let mut derived = SensorData::new([("g_force_x".into(), 0.3)].into_iter().collect());
+        derived.timestamp = t2.parse().unwrap();
+        let payload = protobuf::encode_reading(
+            t2,
+            None,
+            &derived,
+            &["g_force_x"],
+            Some(&Default::default()),
+        );
+        inspector.apply(&mut s, "/SENSORS/IMU/Front IMU/DERIVED", &payload);
+        let data = &s.sensor_data["Front IMU"];
+        assert_eq!(data.fields["accel_x"], 0.6);
+        assert_eq!(data.fields["g_force_x"], 0.3);
+    }
+
+    #[test]
+    fn test_sensor_topics_with_and_without_group() {
+        assert_eq!(
+            sensor_topic("IMU/imu/DERIVED"),
+            Some((None, "imu", "DERIVED"))
+        );
+        assert_eq!(
+            sensor_topic("chassis/IMU/imu/DERIVED"),
+            Some((Some("chassis"), "imu", "DERIVED"))
+        );
+        assert_eq!(sensor_topic("GROUPS/chassis"), None);
+        assert_eq!(sensor_topic("LAPS"), None);
+    }
+
+    #[test]
+    fn test_errors_and_ignition_reach_status() {
+        let mut s = AppState::new(String::new(), true, 10);
+        let mut inspector = Inspector::new("/SENSORS");
+        inspector.apply(
+            &mut s,
+            "/SENSORS/ERRORS",
+            br#"{"timestamp":"t","sensor":"imu","severity":"error","event":"fault","message":"I2C timeout"}"#,
+        );
+        assert_eq!(
+            s.status.current(std::time::Instant::now()).unwrap().text,
+            "imu: I2C timeout"
+        );
+        inspector.apply(
+            &mut s,
+            "/SENSORS/IGNITION",
+            br#"{"timestamp":"t","ignition":"off"}"#,
+        );
+        assert!(s.paused);
+    }
+}
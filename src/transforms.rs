@@ -0,0 +1,134 @@
+//! Per-channel scaling and trimming (`[[transforms]]`).
+//!
+//! Each entry rewrites one of a sensor's channels straight after the driver
+//! has read it, before any other processing, history or publishing sees it:
+//!
+//! ```text
+//! value = value × gain + offset
+//! value = 0                      if |value| < deadband
+//! value = clamp(value, min, max)
+//! ```
+//!
+//! That turns an ADS1115 voltage into PSI, or trims the offset of a slightly
+//! misaligned IMU, without a driver change.  Several entries for the same
+//! channel apply in the order they are listed.
+
+use crate::config::TransformConfig;
+use crate::sensors::{FieldDescriptor, SensorData, VizType};
+
+/// The `[[transforms]]` of one sensor.
+pub struct Transformer {
+    cfgs: Vec<TransformConfig>,
+}
+
+impl Transformer {
+    pub fn new(cfgs: Vec<TransformConfig>) -> Self {
+        Self { cfgs }
+    }
+
+    /// Rewrite the configured channels of `data`; readings without one are
+    /// left alone.
+    pub fn process(&self, data: &mut SensorData) {
+        for t in &self.cfgs {
+            if let Some(v) = data.fields.get_mut(t.field.as_str()) {
+                *v = apply(t, *v);
+            }
+        }
+    }
+}
+
+fn apply(t: &TransformConfig, v: f64) -> f64 {
+    let mut v = v * t.gain + t.offset;
+    if v.abs() < t.deadband {
+        v = 0.0;
+    }
+    clamp(t, v)
+}
+
+fn clamp(t: &TransformConfig, v: f64) -> f64 {
+    let v = t.min.map_or(v, |min| v.max(min));
+    t.max.map_or(v, |max| v.min(max))
+}
+
+/// The driver's `fields` with the units and expected ranges of transformed
+/// channels brought in line with their new values.  Units come from the
+/// config, so they are leaked once per call (at sensor start).
+pub fn field_descriptors(
+    fields: &[FieldDescriptor],
+    cfgs: &[TransformConfig],
+) -> Vec<FieldDescriptor> {
+    let leak = |s: &str| -> &'static str { Box::leak(s.to_string().into_boxed_str()) };
+    let mut fields = fields.to_vec();
+    for t in cfgs {
+        let Some(d) = fields.iter_mut().find(|d| d.key == t.field) else {
+            continue;
+        };
+        if let Some(unit) = &t.unit {
+            d.viz = VizType::Numeric { unit: leak(unit) };
+        }
+        d.range = match (d.range, t.min, t.max) {
+            (Some((lo, hi)), ..) => {
+                let (a, b) = (t.gain * lo + t.offset, t.gain * hi + t.offset);
+                Some((clamp(t, a.min(b)), clamp(t, a.max(b))))
+            }
+            (None, Some(min), Some(max)) => Some((min, max)),
+            (None, ..) => None,
+        };
+    }
+    fields
+}
+
+// ---------------------------------------------------------------------------
+// Unit tests
+// ---------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn transform(toml: &str) -> TransformConfig {
+        toml::from_str(&format!("sensor = \"adc\"\nfield = \"ch0\"\n{}", toml)).unwrap()
+    }
+
+    #[test]
+    fn test_gain_offset_deadband_and_clamp() {
+        // 0.5–4.5 V sender for 0–100 PSI
+        let psi = transform("gain = 25.0\noffset = -12.5\ndeadband = 0.5\nmin = 0.0\nmax = 100.0");
+        let t = Transformer::new(vec![psi]);
+        let read = |v: f64| {
+            let mut data = SensorData::new(
+                [("ch0".into(), v), ("ch1".into(), v)]
+                    .into_iter()
+                    .collect(),
+            );
+            t.process(&mut data);
+            assert_eq!(data.fields["ch1"], v, "other channels untouched");
+            data.fields["ch0"]
+        };
+        assert_eq!(read(2.5), 50.0);
+        assert_eq!(read(0.51), 0.0, "inside the deadband");
+        assert_eq!(read(0.3), 0.0, "clamped at min");
+        assert_eq!(read(5.0), 100.0, "clamped at max");
+    }
+
+    #[test]
+    fn test_descriptors_follow_the_transform() {
+        let driver = [FieldDescriptor {
+            key: "ch0",
+            label: "Channel 0",
+            viz: VizType::Numeric { unit: "V" },
+            range: Some((0.0, 5.0)),
+            group: None,
+        }];
+        let fields = field_descriptors(
+            &driver,
+            &[transform("gain = -2.0\noffset = 1.0\nunit = \"bar\"")],
+        );
+        assert_eq!(fields[0].viz.unit(), "bar");
+        assert_eq!(fields[0].range, Some((-9.0, 1.0)));
+
+        let fields = field_descriptors(&driver, &[transform("offset = 0.1")]);
+        assert_eq!(fields[0].viz.unit(), "V", "unit kept without `unit`");
+        assert_eq!(fields[0].range, Some((0.1, 5.1)));
+    }
+}
@@ -26,11 +26,13 @@
 //! ```
 
 use anyhow::{bail, Context, Result};
+use std::collections::HashMap;
 use std::io::{Read, Write as IoWrite};
 use std::net::TcpStream;
+use std::sync::{Arc, LazyLock, Mutex, MutexGuard, PoisonError, Weak};
 use std::time::Duration;
 
-use crate::config::{ConnectionConfig, SensorConfig};
+use crate::config::{ConnectionConfig, I2cBusConfig, SensorConfig};
 use crate::transport::framing::tcp_read_framed;
 
 // ---------------------------------------------------------------------------
@@ -138,33 +140,134 @@ impl I2cBus for LocalI2c {
     }
 }
 
+// ---------------------------------------------------------------------------
+// Shared bus handle
+// ---------------------------------------------------------------------------
+
+type BusCell = Mutex<Box<dyn I2cBus>>;
+
+/// Open buses by key.  Held weakly, so a bus closes with its last sensor.
+static SHARED_BUSES: LazyLock<Mutex<HashMap<String, Weak<BusCell>>>> =
+    LazyLock::new(Default::default);
+
+/// One bus shared by every sensor on it.  Each transaction runs under the
+/// bus lock, so sensors polled from their own threads can't interleave a
+/// register-pointer write with another device's read.
+#[derive(Clone)]
+pub struct SharedI2c(Arc<BusCell>);
+
+impl SharedI2c {
+    /// The open bus for `key`, or a new one from `open`.
+    pub fn get_or_open(
+        key: &str,
+        open: impl FnOnce() -> Result<Box<dyn I2cBus>>,
+    ) -> Result<Self> {
+        let mut buses = SHARED_BUSES.lock().unwrap_or_else(PoisonError::into_inner);
+        if let Some(bus) = buses.get(key).and_then(Weak::upgrade) {
+            return Ok(Self(bus));
+        }
+        buses.retain(|_, b| b.strong_count() > 0);
+        let bus = Arc::new(Mutex::new(open()?));
+        buses.insert(key.to_string(), Arc::downgrade(&bus));
+        Ok(Self(bus))
+    }
+
+    /// Put an already-open bus, e.g. a scripted mock, under `key`.  Sensors
+    /// whose `device` is `key` use it for as long as the returned handle or
+    /// any of them holds it.
+    pub fn install(key: &str, bus: Box<dyn I2cBus>) -> Self {
+        let bus = Arc::new(Mutex::new(bus));
+        SHARED_BUSES
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .insert(key.to_string(), Arc::downgrade(&bus));
+        Self(bus)
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Box<dyn I2cBus>> {
+        // A panic mid-transaction leaves nothing to repair on our side
+        self.0.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl I2cBus for SharedI2c {
+    fn write(&mut self, addr: u8, data: &[u8]) -> Result<()> {
+        self.lock().write(addr, data)
+    }
+
+    fn read(&mut self, addr: u8, buf: &mut [u8]) -> Result<()> {
+        self.lock().read(addr, buf)
+    }
+
+    fn write_read(&mut self, addr: u8, write: &[u8], read: &mut [u8]) -> Result<()> {
+        self.lock().write_read(addr, write, read)
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Bus speed check
+// ---------------------------------------------------------------------------
+
+/// Warn about `[[i2c_buses]]` whose `speed_hz` differs from the clock the
+/// kernel set up.  Only device-tree systems (e.g. Raspberry Pi) expose the
+/// clock; elsewhere it goes unchecked.
+pub fn check_bus_speeds(buses: &[I2cBusConfig]) {
+    for bus in buses {
+        let Some(want) = bus.speed_hz else {
+            continue;
+        };
+        match kernel_bus_speed(&bus.device) {
+            Some(actual) if actual != want => tracing::warn!(
+                "I2C bus '{}' ({}) runs at {} Hz, not the configured {} Hz — set it in \
+                 the device tree, e.g. dtparam=i2c_arm_baudrate={} on a Raspberry Pi",
+                bus.name,
+                bus.device,
+                actual,
+                want,
+                want
+            ),
+            Some(_) => {}
+            None => tracing::debug!(
+                "I2C bus '{}': kernel clock unknown, speed_hz not checked",
+                bus.name
+            ),
+        }
+    }
+}
+
+/// Clock of the adapter behind `device`, from its device-tree node.
+fn kernel_bus_speed(device: &str) -> Option<u32> {
+    let device = std::fs::canonicalize(device).ok()?;
+    let adapter = device.file_name()?.to_str()?;
+    let raw = std::fs::read(format!(
+        "/sys/class/i2c-adapter/{}/of_node/clock-frequency",
+        adapter
+    ))
+    .ok()?;
+    Some(u32::from_be_bytes(raw.get(..4)?.try_into().ok()?))
+}
+
 // ---------------------------------------------------------------------------
 // Factory
 // ---------------------------------------------------------------------------
 
 /// Open an I2C bus from a sensor config. Returns `(bus, i2c_address)`.
 ///
-/// - `type = "i2c"`: uses the local I2C device (Linux only).
+/// - `type = "i2c"`: uses the local I2C device (Linux only), shared with
+///   every other sensor on the same device.  A bus put in place with
+///   [`SharedI2c::install`] is used on any platform.
 /// - `type = "tcp"`: connects to an io-to-net bridge (all platforms).
 ///   The I2C device address is taken from the optional `address` field in the
 ///   TCP connection config, or falls back to `default_address`.
 pub fn open_i2c(cfg: &SensorConfig, default_address: u8) -> Result<(Box<dyn I2cBus>, u8)> {
     match &cfg.connection {
         ConnectionConfig::I2c(c) => {
-            #[cfg(target_os = "linux")]
-            {
-                let dev = linux_embedded_hal::I2cdev::new(&c.device)
-                    .with_context(|| format!("Failed to open I2C device {}", c.device))?;
-                Ok((Box::new(LocalI2c(dev)), c.address as u8))
-            }
-            #[cfg(not(target_os = "linux"))]
-            {
-                let _ = c;
-                bail!(
-                    "Local I2C (type = \"i2c\") is only supported on Linux. \
-                     Use type = \"tcp\" to connect via an io-to-net bridge."
-                )
-            }
+            // Symlinked adapters (e.g. udev names) resolve to the same bus
+            let key = std::fs::canonicalize(&c.device)
+                .map(|p| p.to_string_lossy().into_owned())
+                .unwrap_or_else(|_| c.device.clone());
+            let bus = SharedI2c::get_or_open(&key, || open_local(&c.device))?;
+            Ok((Box::new(bus), c.address as u8))
         }
         ConnectionConfig::Tcp(c) => {
             let addr = c.address.map(|a| a as u8).unwrap_or(default_address);
@@ -181,3 +284,66 @@ pub fn open_i2c(cfg: &SensorConfig, default_address: u8) -> Result<(Box<dyn I2cB
         ),
     }
 }
+
+#[cfg(target_os = "linux")]
+fn open_local(device: &str) -> Result<Box<dyn I2cBus>> {
+    let dev = linux_embedded_hal::I2cdev::new(device)
+        .with_context(|| format!("Failed to open I2C device {}", device))?;
+    Ok(Box::new(LocalI2c(dev)))
+}
+
+#[cfg(not(target_os = "linux"))]
+fn open_local(_device: &str) -> Result<Box<dyn I2cBus>> {
+    bail!(
+        "Local I2C (type = \"i2c\") is only supported on Linux. \
+         Use type = \"tcp\" to connect via an io-to-net bridge."
+    )
+}
+
+// ---------------------------------------------------------------------------
+// Unit tests
+// ---------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    /// Records the address of every write.
+    struct Recorder(Arc<Mutex<Vec<u8>>>);
+
+    impl I2cBus for Recorder {
+        fn write(&mut self, addr: u8, _data: &[u8]) -> Result<()> {
+            self.0.lock().unwrap().push(addr);
+            Ok(())
+        }
+        fn read(&mut self, _addr: u8, _buf: &mut [u8]) -> Result<()> {
+            Ok(())
+        }
+        fn write_read(&mut self, _addr: u8, _write: &[u8], _read: &mut [u8]) -> Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_shared_bus_opened_once_per_key() {
+        let opens = AtomicUsize::new(0);
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let open = || {
+            opens.fetch_add(1, Ordering::SeqCst);
+            Ok(Box::new(Recorder(Arc::clone(&log))) as Box<dyn I2cBus>)
+        };
+
+        let mut a = SharedI2c::get_or_open("test-bus", open).unwrap();
+        let mut b = SharedI2c::get_or_open("test-bus", open).unwrap();
+        a.write(0x68, &[0]).unwrap();
+        b.write(0x76, &[0]).unwrap();
+        assert_eq!(opens.load(Ordering::SeqCst), 1);
+        assert_eq!(*log.lock().unwrap(), [0x68, 0x76]);
+
+        // Reopened once every handle is gone
+        drop((a, b));
+        let _c = SharedI2c::get_or_open("test-bus", open).unwrap();
+        assert_eq!(opens.load(Ordering::SeqCst), 2);
+    }
+}
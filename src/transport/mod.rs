@@ -6,4 +6,4 @@
 pub mod framing;
 pub mod i2c_bus;
 pub use framing::{tcp_read_framed, FramedTcpReader};
-pub use i2c_bus::{open_i2c, I2cBus};
+pub use i2c_bus::{check_bus_speeds, open_i2c, I2cBus, SharedI2c};
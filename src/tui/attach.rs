@@ -0,0 +1,295 @@
+//! Headless TUI: a daemon streams [`StateSnapshot`]s over a Unix socket and
+//! `sensors-to-mqtt attach` renders them.
+//!
+//! Protocol — newline-delimited JSON in both directions:
+//!   daemon → client  one `StateSnapshot` per refresh tick
+//!   client → daemon  one `UiCommand` per key press
+//!
+//! View state (selected tab, chart traces, status scroll) lives in the
+//! daemon's `AppState`, so every attached client sees the same view.
+
+// The socket is Unix-only; elsewhere `serve` and `connect` just fail
+#![cfg_attr(not(unix), allow(dead_code, unused_imports))]
+
+use std::collections::VecDeque;
+#[cfg(feature = "tui")]
+use std::path::Path;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader};
+#[cfg(feature = "tui")]
+use tokio::sync::watch;
+use tokio_util::sync::CancellationToken;
+use tracing::{info, warn};
+
+use crate::config::UiConfig;
+use crate::models::SharedState;
+use crate::tui::snapshot::StateSnapshot;
+#[cfg(feature = "tui")]
+use crate::tui::terminal::UiSource;
+use crate::tui::{UiCommand, apply_command};
+
+// ---------------------------------------------------------------------------
+// Daemon side
+// ---------------------------------------------------------------------------
+
+/// Accept attach clients on `path` until `cancel` fires.
+#[cfg(unix)]
+pub async fn serve(
+    path: PathBuf,
+    state: SharedState,
+    log_buf: Arc<std::sync::Mutex<VecDeque<String>>>,
+    ui: UiConfig,
+    refresh: Duration,
+    cancel: CancellationToken,
+) -> Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+    use tokio::net::{UnixListener, UnixStream};
+
+    if path.exists() {
+        if UnixStream::connect(&path).await.is_ok() {
+            anyhow::bail!("another instance is listening on {}", path.display());
+        }
+        // Left behind by a previous run that didn't shut down cleanly.
+        std::fs::remove_file(&path)
+            .with_context(|| format!("Failed to remove stale socket {}", path.display()))?;
+    }
+    let listener = UnixListener::bind(&path)
+        .with_context(|| format!("Failed to bind attach socket {}", path.display()))?;
+    // Attached clients can recalibrate sensors and write files — owner only.
+    std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o600))?;
+    info!("Attach socket listening on {}", path.display());
+
+    loop {
+        tokio::select! {
+            _ = cancel.cancelled() => break,
+            conn = listener.accept() => {
+                let (stream, _) = conn?;
+                let (rd, wr) = stream.into_split();
+                tokio::spawn({
+                    let state = state.clone();
+                    let log_buf = log_buf.clone();
+                    let ui = ui.clone();
+                    let cancel = cancel.clone();
+                    async move {
+                        info!("Attach client connected");
+                        if let Err(e) =
+                            serve_client(rd, wr, state, log_buf, ui, refresh, cancel).await
+                        {
+                            warn!("Attach client error: {:#}", e);
+                        }
+                        info!("Attach client disconnected");
+                    }
+                });
+            }
+        }
+    }
+
+    let _ = std::fs::remove_file(&path);
+    Ok(())
+}
+
+#[cfg(not(unix))]
+pub async fn serve(
+    _path: PathBuf,
+    _state: SharedState,
+    _log_buf: Arc<std::sync::Mutex<VecDeque<String>>>,
+    _ui: UiConfig,
+    _refresh: Duration,
+    _cancel: CancellationToken,
+) -> Result<()> {
+    anyhow::bail!("the attach socket is only supported on Unix")
+}
+
+async fn serve_client(
+    rd: impl AsyncRead + Unpin,
+    mut wr: impl AsyncWrite + Unpin,
+    state: SharedState,
+    log_buf: Arc<std::sync::Mutex<VecDeque<String>>>,
+    ui: UiConfig,
+    refresh: Duration,
+    cancel: CancellationToken,
+) -> Result<()> {
+    let mut lines = BufReader::new(rd).lines();
+    let mut tick = tokio::time::interval(refresh);
+
+    loop {
+        tokio::select! {
+            _ = cancel.cancelled() => break,
+            _ = tick.tick() => {
+                let snap = {
+                    let s = state.read().await;
+                    StateSnapshot::from(&s, &log_buf).for_attach()
+                };
+                let mut line = serde_json::to_string(&snap)?;
+                line.push('\n');
+                wr.write_all(line.as_bytes()).await?;
+            }
+            line = lines.next_line() => match line? {
+                Some(l) => match serde_json::from_str::<UiCommand>(&l) {
+                    Ok(cmd) => apply_command(&mut *state.write().await, &ui, &log_buf, cmd),
+                    Err(e) => warn!("Ignoring malformed attach command {:?}: {}", l, e),
+                },
+                None => break,
+            },
+        }
+    }
+    Ok(())
+}
+
+// ---------------------------------------------------------------------------
+// Client side
+// ---------------------------------------------------------------------------
+
+#[cfg(feature = "tui")]
+pub(super) struct RemoteSource {
+    /// Latest snapshot from the daemon; the sender is dropped when the
+    /// connection closes.
+    snapshots: watch::Receiver<Option<StateSnapshot>>,
+    writer: Box<dyn AsyncWrite + Unpin + Send>,
+}
+
+#[cfg(feature = "tui")]
+impl RemoteSource {
+    #[cfg(unix)]
+    pub(super) async fn connect(path: &Path) -> Result<Self> {
+        let stream = tokio::net::UnixStream::connect(path)
+            .await
+            .with_context(|| {
+                format!(
+                    "Failed to connect to {} — is the daemon running?",
+                    path.display()
+                )
+            })?;
+        let (rd, wr) = stream.into_split();
+        Ok(Self::spawn(rd, Box::new(wr)))
+    }
+
+    #[cfg(not(unix))]
+    pub(super) async fn connect(_path: &Path) -> Result<Self> {
+        anyhow::bail!("attach is only supported on Unix")
+    }
+
+    /// Read snapshots in the background so a slow render loop always draws
+    /// the newest one instead of working through a backlog.
+    fn spawn(
+        rd: impl AsyncRead + Unpin + Send + 'static,
+        writer: Box<dyn AsyncWrite + Unpin + Send>,
+    ) -> Self {
+        let (tx, rx) = watch::channel(None);
+        tokio::spawn(async move {
+            let mut lines = BufReader::new(rd).lines();
+            while let Ok(Some(line)) = lines.next_line().await {
+                match serde_json::from_str::<StateSnapshot>(&line) {
+                    Ok(snap) => {
+                        if tx.send(Some(snap)).is_err() {
+                            break;
+                        }
+                    }
+                    Err(_) => break,
+                }
+            }
+        });
+        Self {
+            snapshots: rx,
+            writer,
+        }
+    }
+}
+
+#[cfg(feature = "tui")]
+impl UiSource for RemoteSource {
+    async fn snapshot(&mut self) -> Result<StateSnapshot> {
+        let closed = || anyhow::anyhow!("daemon closed the connection");
+        if self.snapshots.borrow().is_none() {
+            self.snapshots.changed().await.map_err(|_| closed())?;
+        }
+        self.snapshots.has_changed().map_err(|_| closed())?;
+        Ok(self
+            .snapshots
+            .borrow_and_update()
+            .clone()
+            .expect("first snapshot received above"))
+    }
+
+    async fn send(&mut self, cmd: UiCommand) -> Result<()> {
+        let mut line = serde_json::to_string(&cmd)?;
+        line.push('\n');
+        self.writer.write_all(line.as_bytes()).await?;
+        Ok(())
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Unit tests
+// ---------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_command_wire_format() {
+        let json = serde_json::to_string(&UiCommand::SelectTab { index: 2 }).unwrap();
+        assert_eq!(json, r#"{"cmd":"select_tab","index":2}"#);
+        let cmd: UiCommand = serde_json::from_str(r#"{"cmd":"next_tab"}"#).unwrap();
+        assert_eq!(cmd, UiCommand::NextTab);
+    }
+
+    #[cfg(feature = "tui")]
+    #[tokio::test]
+    async fn test_client_receives_snapshots_and_sends_commands() {
+        use crate::models::AppState;
+        use tokio::sync::RwLock;
+
+        let mut app = AppState::new("disabled".into(), false, 10);
+        app.sensor_statuses.insert(
+            "imu1".into(),
+            crate::models::SensorStatus {
+                name: "imu1".into(),
+                driver: "synthetic".into(),
+                connection_display: String::new(),
+                group: None,
+                enabled: true,
+                connected: true,
+                last_error: None,
+                stuck: Vec::new(),
+                calibration: Default::default(),
+                fields: Vec::new(),
+            },
+        );
+        let state: SharedState = Arc::new(RwLock::new(app));
+        let log_buf = Arc::new(std::sync::Mutex::new(VecDeque::new()));
+        let cancel = CancellationToken::new();
+
+        let (client, server) = tokio::io::duplex(64 * 1024);
+        let (srv_rd, srv_wr) = tokio::io::split(server);
+        tokio::spawn(serve_client(
+            srv_rd,
+            srv_wr,
+            state.clone(),
+            log_buf,
+            UiConfig::default(),
+            Duration::from_millis(10),
+            cancel.clone(),
+        ));
+
+        let (cl_rd, cl_wr) = tokio::io::split(client);
+        let mut remote = RemoteSource::spawn(cl_rd, Box::new(cl_wr));
+        let snap = remote.snapshot().await.unwrap();
+        assert_eq!(snap.sensor_names, vec!["imu1".to_string()]);
+
+        remote.send(UiCommand::NextTab).await.unwrap();
+        for _ in 0..100 {
+            if state.read().await.selected_tab == 1 {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        }
+        assert_eq!(state.read().await.selected_tab, 1);
+        cancel.cancel();
+    }
+}
@@ -1,33 +1,39 @@
-//! TUI entry point.
+//! Terminal UI and the pieces every interface shares.
 //!
-//! Layout (always):
-//!   header  (3 lines)
-//!   tabs    (1 line)
-//!   main    (Min 8) — content switches per selected tab
-//!   log     (8 lines) — always visible, shows recent tracing log lines
+//! The interactive TUI ([`run_tui`], [`run_attach`]) needs the `tui`
+//! feature.  Plain mode, the attach socket and the snapshot they all draw
+//! from are built without it.
 
+pub mod attach;
+pub mod plain;
+pub mod snapshot;
+#[cfg(feature = "tui")]
 pub mod tabs;
+#[cfg(feature = "tui")]
+mod terminal;
+#[cfg(feature = "tui")]
+pub mod theme;
+#[cfg(feature = "tui")]
+mod view_state;
+#[cfg(feature = "tui")]
 pub mod widgets;
 
 use std::collections::VecDeque;
 use std::io::{self, Write};
+use std::path::Path;
 use std::sync::Arc;
 
-use crossterm::event::{self, Event, KeyCode, KeyModifiers};
-use crossterm::execute;
-use crossterm::terminal::{
-    EnterAlternateScreen, LeaveAlternateScreen, disable_raw_mode, enable_raw_mode,
-};
-use ratatui::Terminal;
-use ratatui::backend::CrosstermBackend;
-use ratatui::layout::{Constraint, Direction, Layout};
-use ratatui::style::{Color, Modifier, Style};
-use ratatui::text::Span;
-use ratatui::widgets::Tabs;
-use tokio_util::sync::CancellationToken;
+use serde::{Deserialize, Serialize};
 use tracing_subscriber::fmt::MakeWriter;
 
-use crate::models::SharedState;
+use crate::config::UiConfig;
+use crate::models::{AppState, PublishMode, SensorCommand};
+use crate::recorder::SessionRecorder;
+use crate::retention::Retention;
+use snapshot::StateSnapshot;
+
+#[cfg(feature = "tui")]
+pub use terminal::{run_attach, run_tui};
 
 // ---------------------------------------------------------------------------
 // TuiWriter — feeds tracing output into the log ring-buffer
@@ -71,135 +77,184 @@ impl<'a> MakeWriter<'a> for TuiWriter {
 }
 
 // ---------------------------------------------------------------------------
-// TUI loop
+// UI commands — produced by key presses, applied to AppState by the producer
 // ---------------------------------------------------------------------------
 
-pub async fn run_tui(
-    state: SharedState,
-    log_buf: Arc<std::sync::Mutex<VecDeque<String>>>,
-    cancel: CancellationToken,
-) -> anyhow::Result<()> {
-    enable_raw_mode()?;
-    let mut stdout = io::stdout();
-    execute!(stdout, EnterAlternateScreen)?;
-    let backend = CrosstermBackend::new(stdout);
-    let mut terminal = Terminal::new(backend)?;
-
-    let result = tui_loop(&mut terminal, state, log_buf, cancel).await;
-
-    disable_raw_mode()?;
-    execute!(terminal.backend_mut(), LeaveAlternateScreen)?;
-    terminal.show_cursor()?;
-
-    result
+/// A user action that changes application state.  The local TUI applies it
+/// directly; an attached client sends it to the daemon as a JSON line.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "cmd", rename_all = "snake_case")]
+pub enum UiCommand {
+    PrevTab,
+    NextTab,
+    SelectTab { index: usize },
+    Recalibrate,
+    ToggleGroup,
+    Export,
+    ToggleRecording,
+    ToggleSeries { axis: usize },
+    StatusOlder,
+    StatusNewer,
+    AcknowledgeAlerts,
+    DiagnosticBundle,
+    CyclePublishing,
+    ResetExtremes,
 }
 
-async fn tui_loop(
-    terminal: &mut Terminal<CrosstermBackend<io::Stdout>>,
-    state: SharedState,
-    log_buf: Arc<std::sync::Mutex<VecDeque<String>>>,
-    cancel: CancellationToken,
-) -> anyhow::Result<()> {
-    let refresh = std::time::Duration::from_millis(100);
-
-    loop {
-        // Take a snapshot to avoid holding the lock during rendering
-        let snap = {
-            let s = state.read().await;
-            tabs::StateSnapshot::from(&*s, &log_buf)
-        };
-
-        terminal.draw(|frame| {
-            let area = frame.area();
-            let chunks = Layout::default()
-                .direction(Direction::Vertical)
-                .constraints([
-                    Constraint::Length(3),
-                    Constraint::Length(1),
-                    Constraint::Min(8),
-                    Constraint::Length(8),
-                ])
-                .split(area);
-
-            widgets::render_header(frame, chunks[0], snap.version.as_str());
-
-            // Build tab titles
-            let mut tab_titles: Vec<String> = snap
-                .sensor_names
-                .iter()
-                .enumerate()
-                .map(|(i, n)| format!(" {} ({}) ", n, i + 1))
-                .collect();
-            tab_titles.push(format!(" Connections ({}) ", snap.sensor_names.len() + 1));
-            tab_titles.push(format!(" Logs ({}) ", snap.sensor_names.len() + 2));
-
-            let tabs_widget = Tabs::new(tab_titles.iter().map(|s| s.as_str()).collect::<Vec<_>>())
-                .select(snap.selected_tab)
-                .style(Style::default().fg(Color::White))
-                .highlight_style(
-                    Style::default()
-                        .fg(Color::Yellow)
-                        .add_modifier(Modifier::BOLD),
-                )
-                .divider(Span::raw("|"));
-            frame.render_widget(tabs_widget, chunks[1]);
-
-            // Main content
-            let n_sensors = snap.sensor_names.len();
-            if snap.selected_tab < n_sensors {
-                tabs::render_sensor_tab(frame, chunks[2], &snap, snap.selected_tab);
-            } else if snap.selected_tab == n_sensors {
-                tabs::render_connections_tab(frame, chunks[2], &snap);
-            } else {
-                tabs::render_logs_tab(frame, chunks[2], &snap);
+pub fn apply_command(
+    s: &mut AppState,
+    ui: &UiConfig,
+    log_buf: &Arc<std::sync::Mutex<VecDeque<String>>>,
+    cmd: UiCommand,
+) {
+    let tab_count = s.sensor_names().len() + 2;
+    match cmd {
+        UiCommand::PrevTab => {
+            if s.selected_tab > 0 {
+                s.selected_tab -= 1;
             }
-
-            // Log panel (always visible)
-            widgets::render_log_panel(frame, chunks[3], &snap.logs);
-        })?;
-
-        // Input with timeout
-        if event::poll(refresh)? {
-            if let Event::Key(key) = event::read()? {
-                let n_sensors = {
-                    let s = state.read().await;
-                    s.sensor_names().len()
-                };
-                let tab_count = n_sensors + 2;
-
-                let mut s = state.write().await;
-                match (key.code, key.modifiers) {
-                    (KeyCode::Char('q'), _) | (KeyCode::Esc, _) => {
-                        break;
-                    }
-                    (KeyCode::Char('c'), KeyModifiers::CONTROL) => {
-                        break;
+        }
+        UiCommand::NextTab => {
+            if s.selected_tab + 1 < tab_count {
+                s.selected_tab += 1;
+            }
+        }
+        UiCommand::SelectTab { index } => {
+            if index < tab_count {
+                s.selected_tab = index;
+            }
+        }
+        UiCommand::Recalibrate => {
+            if let Some(name) = s.sensor_names().get(s.selected_tab).cloned() {
+                let running = s
+                    .sensor_statuses
+                    .get(&name)
+                    .is_some_and(|st| st.calibration.is_running());
+                if running {
+                    s.status.warn(format!("'{}' is already calibrating", name));
+                } else if s.send_command(&name, SensorCommand::Recalibrate) {
+                    s.status
+                        .info(format!("Calibrating '{}' — keep the sensor still", name));
+                } else {
+                    s.status.warn(format!("'{}' is not running", name));
+                }
+            }
+        }
+        UiCommand::ToggleGroup => {
+            if let Some(name) = s.sensor_names().get(s.selected_tab).cloned() {
+                let group = s.sensor_statuses.get(&name).and_then(|st| st.group.clone());
+                match group {
+                    Some(g) => {
+                        let on = !s.group_enabled(Some(&g));
+                        s.set_group_enabled(&g, on);
                     }
-                    (KeyCode::Left, _) => {
-                        if s.selected_tab > 0 {
-                            s.selected_tab -= 1;
-                        }
+                    None => s.status.warn(format!("'{}' is not in a group", name)),
+                }
+            }
+        }
+        UiCommand::Export => {
+            if let Some(name) = s.sensor_names().get(s.selected_tab).cloned() {
+                let result = match s.sensor_history.get(&name) {
+                    Some(h) if !h.timestamps.is_empty() => {
+                        crate::export::export_history_csv(Path::new(&ui.export_dir), &name, h)
                     }
-                    (KeyCode::Right, _) => {
-                        if s.selected_tab + 1 < tab_count {
-                            s.selected_tab += 1;
+                    _ => Err(anyhow::anyhow!("no history for '{}' yet", name)),
+                };
+                match result {
+                    Ok(path) => {
+                        let retention = Retention::from_config(ui);
+                        if let Err(e) = retention.prune(Path::new(&ui.export_dir), Some(&path), 0) {
+                            tracing::warn!("Retention pruning failed: {:#}", e);
                         }
+                        s.status.info(format!("Exported {}", path.display()))
                     }
-                    (KeyCode::Char(c), _) if c.is_ascii_digit() => {
-                        let idx = (c as usize).wrapping_sub('1' as usize);
-                        if idx < tab_count {
-                            s.selected_tab = idx;
-                        }
+                    Err(e) => s.status.error(format!("Export failed: {:#}", e)),
+                }
+            }
+        }
+        UiCommand::ToggleRecording => match s.recording.take() {
+            Some(rec) => match rec.finish() {
+                Ok((path, n)) => {
+                    s.status
+                        .info(format!("Recorded {} readings to {}", n, path.display()))
+                }
+                Err(e) => s.status.error(format!("Recording failed: {:#}", e)),
+            },
+            None => {
+                match SessionRecorder::start(Path::new(&ui.export_dir), Retention::from_config(ui))
+                {
+                    Ok(rec) => {
+                        s.status
+                            .info(format!("Recording to {}", rec.info().path.display()));
+                        s.recording = Some(rec);
                     }
-                    _ => {}
+                    Err(e) => s.status.error(format!("Recording failed: {:#}", e)),
                 }
             }
+        },
+        UiCommand::ToggleSeries { axis } => {
+            if let Some(on) = s.chart_series.get_mut(axis) {
+                *on ^= true;
+            }
         }
-
-        if cancel.is_cancelled() {
-            break;
+        UiCommand::StatusOlder => s.status.scroll_older(),
+        UiCommand::StatusNewer => s.status.scroll_newer(),
+        UiCommand::AcknowledgeAlerts => s.acknowledge_alerts(),
+        UiCommand::CyclePublishing => {
+            s.publish_mode = s.publish_mode.next();
+            tracing::info!("Publishing switched to {}", s.publish_mode.label());
+            s.status
+                .info(format!("Readings: {}", publish_mode_text(s.publish_mode)));
+        }
+        UiCommand::ResetExtremes => {
+            if let Some(e) = s.extremes.as_mut() {
+                e.reset(None);
+                tracing::info!("Extremes reset");
+                s.status.info("All-time extremes reset");
+            }
+        }
+        UiCommand::DiagnosticBundle => {
+            let snap = StateSnapshot::from(s, log_buf);
+            match crate::diagnostics::write_bundle(Path::new(&ui.export_dir), snap) {
+                Ok(path) => s
+                    .status
+                    .info(format!("Diagnostic bundle written to {}", path.display())),
+                Err(e) => s.status.error(format!("Diagnostic bundle failed: {:#}", e)),
+            }
         }
     }
+}
+
+/// How readings are handled in `mode`, for the status bar and overview.
+pub fn publish_mode_text(mode: PublishMode) -> &'static str {
+    match mode {
+        PublishMode::Mqtt => "published",
+        PublishMode::Log => "logged, not published",
+        PublishMode::Off => "not published",
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Without the `tui` feature
+// ---------------------------------------------------------------------------
+
+#[cfg(not(feature = "tui"))]
+pub async fn run_tui(
+    _state: crate::models::SharedState,
+    _log_buf: Arc<std::sync::Mutex<VecDeque<String>>>,
+    _cancel: tokio_util::sync::CancellationToken,
+    _ui: UiConfig,
+    _refresh: std::time::Duration,
+) -> anyhow::Result<()> {
+    anyhow::bail!("built without the `tui` feature — use --mode plain")
+}
 
-    Ok(())
+#[cfg(not(feature = "tui"))]
+pub async fn run_attach(
+    _socket: &Path,
+    _cancel: tokio_util::sync::CancellationToken,
+    _ui: UiConfig,
+    _refresh: std::time::Duration,
+) -> anyhow::Result<()> {
+    anyhow::bail!("built without the `tui` feature, so `attach` can't draw the TUI")
 }
@@ -0,0 +1,199 @@
+//! Plain-text interactive mode (`--mode plain`).
+//!
+//! Prints a line-oriented summary every few seconds — no alternate screen,
+//! no cursor movement, no colour — so it stays readable over slow SSH links,
+//! serial consoles and screen readers.  Type `q` + Enter to quit; a bare
+//! Enter prints a fresh summary immediately.
+
+use std::collections::VecDeque;
+use std::fmt::Write as _;
+use std::sync::Arc;
+use std::time::Duration;
+
+use tokio::io::{AsyncBufReadExt, BufReader};
+use tokio_util::sync::CancellationToken;
+
+use crate::laps::format_lap;
+use crate::models::{CalibrationState, PublishMode, SharedState, StatusLevel};
+use crate::recorder::human_bytes;
+use crate::sensors::Quality;
+use crate::tui::snapshot::StateSnapshot;
+
+/// Fields printed per line in a sensor block.
+const FIELDS_PER_LINE: usize = 4;
+
+pub async fn run_plain(
+    state: SharedState,
+    log_buf: Arc<std::sync::Mutex<VecDeque<String>>>,
+    cancel: CancellationToken,
+    interval: Duration,
+) -> anyhow::Result<()> {
+    let mut lines = BufReader::new(tokio::io::stdin()).lines();
+    let mut ticker = tokio::time::interval(interval);
+    let mut stdin_open = true;
+    // Last status message printed, so each one appears exactly once.
+    let mut last_status = None;
+
+    println!("Plain mode — type q + Enter to quit, Enter to refresh.");
+
+    loop {
+        tokio::select! {
+            _ = cancel.cancelled() => break,
+            _ = ticker.tick() => {}
+            line = lines.next_line(), if stdin_open => match line? {
+                Some(l) if l.trim().eq_ignore_ascii_case("q") => break,
+                Some(_) => {}
+                // stdin closed (e.g. `< /dev/null`) — keep printing.
+                None => stdin_open = false,
+            },
+        }
+
+        let snap = {
+            let s = state.read().await;
+            StateSnapshot::from(&s, &log_buf)
+        };
+
+        if let Some(m) = &snap.status {
+            let key = (m.timestamp, m.text.clone());
+            if last_status.as_ref() != Some(&key) {
+                let level = match m.level {
+                    StatusLevel::Info => "info",
+                    StatusLevel::Warn => "warning",
+                    StatusLevel::Error => "error",
+                };
+                println!("{} {}: {}", m.timestamp.format("%H:%M:%S"), level, m.text);
+                last_status = Some(key);
+            }
+        }
+        print!("{}", summary(&snap));
+    }
+
+    Ok(())
+}
+
+/// Render one summary block.
+pub fn summary(snap: &StateSnapshot) -> String {
+    let mut out = String::new();
+    let _ = writeln!(
+        out,
+        "== sensors-to-mqtt v{} at {} ==",
+        snap.version,
+        chrono::Local::now().format("%H:%M:%S")
+    );
+    if snap.mqtt_enabled {
+        let _ = write!(
+            out,
+            "MQTT {}: {}{}, {} messages published",
+            snap.mqtt_address,
+            snap.mqtt_status.label().to_lowercase(),
+            snap.mqtt_status
+                .detail()
+                .map_or(String::new(), |d| format!(" ({})", d)),
+            snap.messages_published
+        );
+        if snap.messages_dropped > 0 {
+            let _ = write!(out, ", {} dropped (broker too slow)", snap.messages_dropped);
+        }
+        out.push('\n');
+    } else {
+        let _ = writeln!(out, "MQTT disabled");
+    }
+    if snap.publish_mode != PublishMode::Mqtt {
+        let _ = writeln!(
+            out,
+            "Readings {}",
+            crate::tui::publish_mode_text(snap.publish_mode)
+        );
+    }
+    if snap.paused {
+        let _ = writeln!(out, "Ignition off, sampling paused");
+    } else if let Some(interval) = snap.idle_interval {
+        let _ = writeln!(out, "No motion, sampling every {} ms", interval.as_millis());
+    }
+    if let Some(rec) = &snap.recording {
+        let _ = writeln!(
+            out,
+            "Recording to {}: {} s, {}",
+            rec.path.display(),
+            rec.elapsed.as_secs(),
+            human_bytes(rec.bytes)
+        );
+    }
+
+    if let Some(laps) = &snap.laps {
+        match laps.current(chrono::Utc::now()) {
+            Some(current) => {
+                let fmt = |d: Option<Duration>| d.map_or_else(|| "-".to_string(), format_lap);
+                let _ = writeln!(
+                    out,
+                    "Lap {}: {}, last {}, best {}",
+                    laps.lap,
+                    format_lap(current),
+                    fmt(laps.last),
+                    fmt(laps.best)
+                );
+            }
+            None => {
+                let _ = writeln!(out, "Lap timer waiting for the start/finish line");
+            }
+        }
+    }
+
+    for (i, st) in snap.sensor_statuses.iter().enumerate() {
+        let state = if !st.enabled {
+            "disabled".to_string()
+        } else if st.connected {
+            "ok".to_string()
+        } else {
+            match &st.last_error {
+                Some(e) => format!("error: {}", e),
+                None => "not connected".to_string(),
+            }
+        };
+        let _ = writeln!(out, "Sensor {} ({}): {}", st.name, st.driver, state);
+        if !st.stuck.is_empty() {
+            let _ = writeln!(out, "  stuck: {}", st.stuck.join(", "));
+        }
+        if let CalibrationState::Running(p) = &st.calibration {
+            let _ = writeln!(
+                out,
+                "  calibrating {} of {} samples{}",
+                p.collected,
+                p.total,
+                if p.is_still() { "" } else { ", keep still" }
+            );
+        }
+
+        let Some(data) = snap.sensor_data.get(i).and_then(|d| d.as_ref()) else {
+            continue;
+        };
+        let cells: Vec<String> = if st.fields.is_empty() {
+            let mut keys: Vec<&str> = data
+                .fields
+                .keys()
+                .map(|k| &**k)
+                .chain(data.values.keys().map(String::as_str))
+                .collect();
+            keys.sort();
+            keys.iter()
+                .filter_map(|k| Some(format!("{} {}", k, data.get(k)?)))
+                .collect()
+        } else {
+            st.fields
+                .iter()
+                .filter_map(|m| {
+                    let value = m.display(data)?;
+                    Some(match data.quality(&m.key) {
+                        Quality::Good => format!("{} {}", m.label, value),
+                        q => format!("{} {} ({})", m.label, value, q.as_str()),
+                    })
+                })
+                .collect()
+        };
+        for chunk in cells.chunks(FIELDS_PER_LINE) {
+            let _ = writeln!(out, "  {}", chunk.join(", "));
+        }
+    }
+    out.push('\n');
+    out
+}
@@ -0,0 +1,130 @@
+//! Immutable view of `AppState` shared by every UI: the TUI, plain mode and
+//! attach clients.
+
+use std::collections::VecDeque;
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize};
+
+use crate::alerts::Alert;
+use crate::extremes::Extremes;
+use crate::laps::LapStatus;
+use crate::models::{
+    AppState, CalibrationState, MqttStatus, PublishMode, SensorHistory, SensorStatus, StatusMessage,
+};
+use crate::recorder::RecordingInfo;
+use crate::sensors::SensorData;
+
+// ---------------------------------------------------------------------------
+// Immutable snapshot — built once per render tick, passed to all renderers
+// ---------------------------------------------------------------------------
+
+/// Everything the renderers need.  Serialisable so a daemon can stream it
+/// to `sensors-to-mqtt attach` clients.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StateSnapshot {
+    pub version: String,
+    pub sensor_names: Vec<String>,
+    pub sensor_statuses: Vec<SensorStatus>,
+    pub sensor_data: Vec<Option<SensorData>>,
+    pub sensor_history: Vec<Option<SensorHistory>>,
+    pub mqtt_status: MqttStatus,
+    pub messages_published: u64,
+    pub messages_dropped: u64,
+    pub mqtt_address: String,
+    pub mqtt_enabled: bool,
+    pub logs: Vec<String>,
+    pub selected_tab: usize,
+    pub chart_series: [bool; 3],
+    pub status: Option<StatusMessage>,
+    /// `(position, total)` while browsing status history
+    pub status_position: Option<(usize, usize)>,
+    pub recording: Option<RecordingInfo>,
+    pub laps: Option<LapStatus>,
+    #[serde(default)]
+    pub extremes: Option<Extremes>,
+    /// Sampling paused because the ignition is off
+    pub paused: bool,
+    #[serde(default)]
+    pub publish_mode: PublishMode,
+    /// Reduced sampling interval while no motion is seen
+    pub idle_interval: Option<std::time::Duration>,
+    #[serde(default)]
+    pub alerts: Vec<Alert>,
+}
+
+impl StateSnapshot {
+    pub fn from(s: &AppState, log_buf: &Arc<std::sync::Mutex<VecDeque<String>>>) -> Self {
+        let sensor_names = s.sensor_names();
+        let sensor_statuses: Vec<SensorStatus> = sensor_names
+            .iter()
+            .map(|n| {
+                s.sensor_statuses
+                    .get(n)
+                    .cloned()
+                    .unwrap_or_else(|| SensorStatus {
+                        name: n.clone(),
+                        driver: String::new(),
+                        connection_display: String::new(),
+                        group: None,
+                        enabled: false,
+                        connected: false,
+                        last_error: None,
+                        stuck: Vec::new(),
+                        calibration: CalibrationState::Idle,
+                        fields: Vec::new(),
+                    })
+            })
+            .collect();
+        let sensor_data: Vec<Option<SensorData>> = sensor_names
+            .iter()
+            .map(|n| s.sensor_data.get(n).cloned())
+            .collect();
+        let sensor_history: Vec<Option<SensorHistory>> = sensor_names
+            .iter()
+            .map(|n| s.sensor_history.get(n).cloned())
+            .collect();
+
+        let logs = {
+            let g = log_buf.lock().unwrap();
+            g.iter().cloned().collect()
+        };
+
+        use std::sync::atomic::Ordering;
+        Self {
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            sensor_names,
+            sensor_statuses,
+            sensor_data,
+            sensor_history,
+            mqtt_status: s.mqtt_status.read().unwrap().clone(),
+            messages_published: s.messages_published.load(Ordering::Relaxed),
+            messages_dropped: s.messages_dropped.load(Ordering::Relaxed),
+            mqtt_address: s.mqtt_address.clone(),
+            mqtt_enabled: s.mqtt_enabled,
+            logs,
+            selected_tab: s.selected_tab,
+            chart_series: s.chart_series,
+            status: s.status.current(std::time::Instant::now()).cloned(),
+            status_position: s.status.position(),
+            recording: s.recording.as_ref().map(|r| r.info()),
+            laps: s.laps.clone(),
+            extremes: s.extremes.clone(),
+            paused: s.paused,
+            publish_mode: s.publish_mode,
+            idle_interval: s.idle_interval,
+            alerts: s.alerts.clone(),
+        }
+    }
+
+    /// Drop the histories of tabs that aren't on screen — they are by far
+    /// the largest part of a snapshot sent to attach clients.
+    pub fn for_attach(mut self) -> Self {
+        for (i, h) in self.sensor_history.iter_mut().enumerate() {
+            if i != self.selected_tab {
+                *h = None;
+            }
+        }
+        self
+    }
+}
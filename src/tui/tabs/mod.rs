@@ -1,86 +1,38 @@
-//! Tab renderers and StateSnapshot.
+//! Tab renderers.
 
 use std::collections::VecDeque;
-use std::sync::Arc;
 
+use chrono::{DateTime, Utc};
 use ratatui::Frame;
 use ratatui::layout::{Constraint, Direction, Layout, Rect};
-use ratatui::style::{Color, Modifier, Style};
-use ratatui::symbols::Marker;
+use ratatui::style::Modifier;
 use ratatui::text::{Line, Span};
 use ratatui::widgets::canvas::{Canvas, Context};
 use ratatui::widgets::{
     Axis, Block, Borders, Chart, Dataset, GraphType, List, ListItem, Paragraph,
 };
 
-use crate::models::{AppState, MqttStatus, SensorHistory, SensorStatus};
-use crate::sensors::SensorData;
-use crate::tui::widgets::{data_row, section_line, status_dot};
-
-// ---------------------------------------------------------------------------
-// Immutable snapshot — built once per render tick, passed to all renderers
-// ---------------------------------------------------------------------------
-
-pub struct StateSnapshot {
-    pub version: String,
-    pub sensor_names: Vec<String>,
-    pub sensor_statuses: Vec<SensorStatus>,
-    pub sensor_data: Vec<Option<SensorData>>,
-    pub sensor_history: Vec<Option<SensorHistory>>,
-    pub mqtt_status: MqttStatus,
-    pub messages_published: u64,
-    pub mqtt_address: String,
-    pub mqtt_enabled: bool,
-    pub logs: Vec<String>,
-    pub selected_tab: usize,
+use crate::config::UiConfig;
+use crate::models::{CalibrationState, SensorHistory, SensorStatus};
+use crate::sensors::{FieldMeta, Quality, SensorData};
+use crate::tui::snapshot::StateSnapshot;
+use crate::tui::theme::Theme;
+use crate::tui::widgets::{data_row, progress_bar, section_line, status_dot};
+
+/// Per-installation display settings from `[ui]`.
+#[derive(Debug, Clone, Copy)]
+pub struct RenderOptions {
+    /// G at a full G-meter bar, overriding the channel's range
+    pub g_full_scale: Option<f64>,
+    /// Ring the terminal bell when an alert fires
+    pub alert_bell: bool,
 }
 
-impl StateSnapshot {
-    pub fn from(s: &AppState, log_buf: &Arc<std::sync::Mutex<VecDeque<String>>>) -> Self {
-        let sensor_names = s.sensor_names();
-        let sensor_statuses: Vec<SensorStatus> = sensor_names
-            .iter()
-            .map(|n| {
-                s.sensor_statuses
-                    .get(n)
-                    .cloned()
-                    .unwrap_or_else(|| SensorStatus {
-                        name: n.clone(),
-                        driver: String::new(),
-                        connection_display: String::new(),
-                        enabled: false,
-                        connected: false,
-                        last_error: None,
-                    })
-            })
-            .collect();
-        let sensor_data: Vec<Option<SensorData>> = sensor_names
-            .iter()
-            .map(|n| s.sensor_data.get(n).cloned())
-            .collect();
-        let sensor_history: Vec<Option<SensorHistory>> = sensor_names
-            .iter()
-            .map(|n| s.sensor_history.get(n).cloned())
-            .collect();
-
-        let logs = {
-            let g = log_buf.lock().unwrap();
-            g.iter().cloned().collect()
-        };
-
-        use std::sync::atomic::Ordering;
+impl RenderOptions {
+    pub fn new(ui: &UiConfig) -> Self {
         Self {
-            version: env!("CARGO_PKG_VERSION").to_string(),
-            sensor_names,
-            sensor_statuses,
-            sensor_data,
-            sensor_history,
-            mqtt_status: s.mqtt_status.read().unwrap().clone(),
-            messages_published: s.messages_published.load(Ordering::Relaxed),
-            mqtt_address: s.mqtt_address.clone(),
-            mqtt_enabled: s.mqtt_enabled,
-            logs,
-            selected_tab: s.selected_tab,
+            g_full_scale: (ui.g_meter_full_scale > 0.0).then_some(ui.g_meter_full_scale),
+            alert_bell: ui.alert_bell,
         }
     }
 }
@@ -89,10 +41,18 @@ impl StateSnapshot {
 // Per-sensor tab
 // ---------------------------------------------------------------------------
 
-pub fn render_sensor_tab(frame: &mut Frame, area: Rect, snap: &StateSnapshot, idx: usize) {
+pub fn render_sensor_tab(
+    frame: &mut Frame,
+    area: Rect,
+    snap: &StateSnapshot,
+    idx: usize,
+    opts: RenderOptions,
+    theme: &Theme,
+) {
     let status = snap.sensor_statuses.get(idx);
     let data = snap.sensor_data.get(idx).and_then(|d| d.as_ref());
     let history = snap.sensor_history.get(idx).and_then(|h| h.as_ref());
+    let metas = field_metas(status, data);
 
     let outer = Layout::default()
         .direction(Direction::Horizontal)
@@ -100,7 +60,7 @@ pub fn render_sensor_tab(frame: &mut Frame, area: Rect, snap: &StateSnapshot, id
         .split(area);
 
     // Left: sensor info + field list
-    render_sensor_info(frame, outer[0], status, data);
+    render_sensor_info(frame, outer[0], status, data, &metas, theme);
 
     // Right: visualisations
     let right = Layout::default()
@@ -108,14 +68,22 @@ pub fn render_sensor_tab(frame: &mut Frame, area: Rect, snap: &StateSnapshot, id
         .constraints([Constraint::Percentage(45), Constraint::Percentage(55)])
         .split(outer[1]);
 
+    // G-meter, G-ball and orientation only make sense for an IMU; other
+    // sensors get range gauges and a full-width chart.
+    if !metas.iter().any(|m| m.is_g_force) {
+        render_levels(frame, right[0], data, &metas, theme);
+        render_sparklines(frame, right[1], history, &metas, snap.chart_series, theme);
+        return;
+    }
+
     // Top-right: G-meter + G-ball
     let viz_top = Layout::default()
         .direction(Direction::Horizontal)
         .constraints([Constraint::Percentage(50), Constraint::Percentage(50)])
         .split(right[0]);
 
-    render_g_meter(frame, viz_top[0], data);
-    render_g_ball(frame, viz_top[1], data);
+    render_g_meter(frame, viz_top[0], data, &metas, opts.g_full_scale, theme);
+    render_g_ball(frame, viz_top[1], data, theme);
 
     // Bottom-right: sparklines + orientation compass
     let viz_bot = Layout::default()
@@ -123,8 +91,40 @@ pub fn render_sensor_tab(frame: &mut Frame, area: Rect, snap: &StateSnapshot, id
         .constraints([Constraint::Percentage(55), Constraint::Percentage(45)])
         .split(right[1]);
 
-    render_sparklines(frame, viz_bot[0], history);
-    render_orientation(frame, viz_bot[1], data);
+    render_sparklines(frame, viz_bot[0], history, &metas, snap.chart_series, theme);
+    render_orientation(frame, viz_bot[1], data, theme);
+}
+
+/// Driver metadata for the sensor, or bare keys from the latest reading
+/// when the driver hasn't reported any (yet).
+fn field_metas(status: Option<&SensorStatus>, data: Option<&SensorData>) -> Vec<FieldMeta> {
+    if let Some(st) = status.filter(|st| !st.fields.is_empty()) {
+        return st.fields.clone();
+    }
+    let mut keys: Vec<&str> = data
+        .map(|d| {
+            d.fields
+                .keys()
+                .map(|k| &**k)
+                .chain(d.values.keys().map(String::as_str))
+                .collect()
+        })
+        .unwrap_or_default();
+    keys.sort();
+    keys.into_iter()
+        .map(|k| FieldMeta {
+            key: k.to_string(),
+            label: k.to_string(),
+            unit: String::new(),
+            range: None,
+            group: None,
+            is_g_force: k.starts_with("g_force_"),
+        })
+        .collect()
+}
+
+fn meta<'a>(metas: &'a [FieldMeta], key: &str) -> Option<&'a FieldMeta> {
+    metas.iter().find(|m| m.key == key)
 }
 
 // ---------------------------------------------------------------------------
@@ -136,65 +136,104 @@ fn render_sensor_info(
     area: Rect,
     status: Option<&SensorStatus>,
     data: Option<&SensorData>,
+    metas: &[FieldMeta],
+    theme: &Theme,
 ) {
     let block = Block::default()
         .title(" SENSOR INFO ")
         .borders(Borders::ALL)
-        .border_style(Style::default().fg(Color::Cyan));
+        .border_style(theme.fg(theme.accent));
 
     let mut lines: Vec<Line> = Vec::new();
 
     if let Some(st) = status {
-        lines.push(data_row("Name", st.name.clone()));
-        lines.push(data_row("Driver", st.driver.clone()));
-        lines.push(data_row("Connection", st.connection_display.clone()));
+        lines.push(data_row("Name", st.name.clone(), theme));
+        lines.push(data_row("Driver", st.driver.clone(), theme));
+        lines.push(data_row("Connection", st.connection_display.clone(), theme));
+        if let Some(ref g) = st.group {
+            lines.push(data_row("Group", g.clone(), theme));
+        }
         lines.push(Line::from(vec![
-            Span::styled(
-                format!("{:<14}", "Status"),
-                Style::default()
-                    .fg(Color::Cyan)
-                    .add_modifier(Modifier::BOLD),
-            ),
+            Span::styled(format!("{:<14}", "Status"), theme.label()),
             Span::raw(" "),
-            status_dot(st.connected),
+            status_dot(st.connected, theme),
+            Span::raw(if st.enabled { "" } else { " disabled" }),
         ]));
         if let Some(ref e) = st.last_error {
-            lines.push(data_row("Error", e.clone()));
+            lines.push(data_row("Error", e.clone(), theme));
         }
-        lines.push(section_line("READINGS"));
+        if !st.stuck.is_empty() {
+            lines.push(Line::from(vec![
+                Span::styled(format!("{:<14}", "Stuck"), theme.label()),
+                Span::raw(" "),
+                Span::styled(st.stuck.join(", "), theme.fg(theme.warn)),
+            ]));
+        }
+        match &st.calibration {
+            CalibrationState::Idle => {}
+            CalibrationState::Running(p) => {
+                lines.push(data_row(
+                    "Calibrating",
+                    format!("{}/{} samples", p.collected, p.total),
+                    theme,
+                ));
+                let width = area.width.saturating_sub(4) as usize;
+                lines.push(Line::from(vec![
+                    Span::raw(" "),
+                    progress_bar(p.fraction(), width, theme),
+                ]));
+                lines.push(if p.is_still() {
+                    Line::from(Span::styled(" ● still", theme.fg(theme.ok)))
+                } else {
+                    Line::from(Span::styled(
+                        format!(
+                            " ○ moving ({:.1} °/s) — keep the sensor still",
+                            p.motion_dps
+                        ),
+                        theme.fg(theme.warn),
+                    ))
+                });
+            }
+            CalibrationState::Done(at) => {
+                lines.push(data_row(
+                    "Calibrated",
+                    at.format("%H:%M:%S").to_string(),
+                    theme,
+                ));
+            }
+            CalibrationState::Failed(e) => {
+                lines.push(Line::from(Span::styled(
+                    format!("Calibration failed: {}", e),
+                    theme.error_style(),
+                )));
+            }
+        }
+        lines.push(section_line("READINGS", theme));
     }
 
     if let Some(d) = data {
         let ts_str = d.timestamp.format("%H:%M:%S.%3f").to_string();
-        lines.push(data_row("Timestamp", ts_str));
-        lines.push(section_line("G-FORCES"));
-        for key in &[
-            "g_force_x",
-            "g_force_y",
-            "g_force_z",
-            "combined_g",
-            "peak_g",
-        ] {
-            if let Some(&v) = d.fields.get(*key) {
-                lines.push(data_row(key, format!("{:+.4} G", v)));
+        lines.push(data_row("Timestamp", ts_str, theme));
+        for m in metas {
+            if let Some(group) = &m.group {
+                lines.push(section_line(&group.to_uppercase(), theme));
             }
-        }
-        lines.push(section_line("GYROSCOPE"));
-        for key in &["roll_rate", "pitch_rate", "yaw_rate", "angular_velocity"] {
-            if let Some(&v) = d.fields.get(*key) {
-                lines.push(data_row(key, format!("{:+.2} °/s", v)));
-            }
-        }
-        lines.push(section_line("ORIENTATION"));
-        for key in &["lean_angle", "bank_angle", "tilt_angle"] {
-            if let Some(&v) = d.fields.get(*key) {
-                lines.push(data_row(key, format!("{:+.2}°", v)));
+            if let Some(value) = m.display(d) {
+                let mut row = data_row(&m.label, value, theme);
+                let quality = d.quality(&m.key);
+                if quality != Quality::Good {
+                    row.push_span(Span::styled(
+                        format!(" [{}]", quality.as_str()),
+                        theme.fg(theme.warn),
+                    ));
+                }
+                lines.push(row);
             }
         }
     } else {
         lines.push(Line::from(Span::styled(
             "  Waiting for data…",
-            Style::default().fg(Color::DarkGray),
+            theme.fg(theme.muted),
         )));
     }
 
@@ -206,52 +245,58 @@ fn render_sensor_info(
 // ASCII G-meter
 // ---------------------------------------------------------------------------
 
-fn render_g_meter(frame: &mut Frame, area: Rect, data: Option<&SensorData>) {
+/// Spacing of the tick marks on the G-meter bars.
+const G_TICK: f64 = 0.5;
+
+fn render_g_meter(
+    frame: &mut Frame,
+    area: Rect,
+    data: Option<&SensorData>,
+    metas: &[FieldMeta],
+    full_scale: Option<f64>,
+    theme: &Theme,
+) {
     let block = Block::default()
         .title(" G-METER ")
         .borders(Borders::ALL)
-        .border_style(Style::default().fg(Color::Yellow));
+        .border_style(theme.fg(theme.warn));
 
     let inner = block.inner(area);
     frame.render_widget(block, area);
 
     if let Some(d) = data {
-        let bar_width = inner.width.saturating_sub(20) as usize;
-        let max_g = 4.0_f64;
-        let axes = [
-            ("Lateral  X", "g_force_x"),
-            ("Forward  Y", "g_force_y"),
-            ("Vertical Z", "g_force_z"),
-        ];
+        let axes = ["g_force_x", "g_force_y", "g_force_z"];
 
         let mut lines: Vec<Line> = Vec::new();
-        for (label, key) in &axes {
+        for key in &axes {
+            let m = meta(metas, key);
+            let label = m.map_or(*key, |m| m.label.as_str());
+            let max_g = full_scale.unwrap_or_else(|| {
+                m.and_then(|m| m.range)
+                    .map_or(4.0, |(lo, hi)| lo.abs().max(hi.abs()))
+            });
             let val = d.fields.get(*key).copied().unwrap_or(0.0);
-            let fill = ((val.abs() / max_g) * bar_width as f64).min(bar_width as f64) as usize;
-            let empty = bar_width.saturating_sub(fill);
-            let bar_color = g_color(val.abs());
-            let bar_str = format!("█").repeat(fill) + &"░".repeat(empty);
+            let prefix = format!("{:<12} {:+.3}G ", label, val);
+            // The bar takes whatever the label and value leave of the line
+            let bar_width = (inner.width as usize).saturating_sub(prefix.chars().count());
+            let bar_color = theme.g_color(val.abs());
+            let bar_str = g_bar(val.abs(), max_g, bar_width);
             lines.push(Line::from(vec![
-                Span::styled(
-                    format!("{:<12} {:+.3}G ", label, val),
-                    Style::default().fg(Color::Cyan),
-                ),
-                Span::styled(bar_str, Style::default().fg(bar_color)),
+                Span::styled(prefix, theme.fg(theme.accent)),
+                Span::styled(bar_str, theme.fg(bar_color)),
             ]));
         }
 
         if let Some(&cg) = d.fields.get("combined_g") {
             lines.push(Line::from(Span::styled(
                 format!("Combined      {:.3} G", cg),
-                Style::default()
-                    .fg(g_color(cg))
-                    .add_modifier(Modifier::BOLD),
+                theme.fg(theme.g_color(cg)).add_modifier(Modifier::BOLD),
             )));
         }
         if let Some(&pg) = d.fields.get("peak_g") {
             lines.push(Line::from(Span::styled(
                 format!("Peak          {:.3} G", pg),
-                Style::default().fg(Color::Red),
+                theme.fg(theme.error),
             )));
         }
 
@@ -260,27 +305,99 @@ fn render_g_meter(frame: &mut Frame, area: Rect, data: Option<&SensorData>) {
     }
 }
 
-fn g_color(g: f64) -> Color {
-    if g >= 3.0 {
-        Color::Red
-    } else if g >= 2.0 {
-        Color::LightRed
-    } else if g >= 1.0 {
-        Color::Yellow
+/// `width` cells filled in proportion to `g` / `full_scale`, with a tick
+/// every [`G_TICK`] in the unfilled part.  Ticks are left out when they
+/// would be closer than every other cell.
+fn g_bar(g: f64, full_scale: f64, width: usize) -> String {
+    let frac = if full_scale > 0.0 {
+        g / full_scale
     } else {
-        Color::Green
+        0.0
+    };
+    let fill = (frac.clamp(0.0, 1.0) * width as f64) as usize;
+    let per_tick = G_TICK / full_scale * width as f64;
+    (0..width)
+        .map(|i| {
+            // A tick where a multiple of G_TICK falls within this cell
+            let tick = per_tick >= 2.0 && i > 0 && {
+                let n = (i as f64 / per_tick).ceil();
+                n * per_tick < (i + 1) as f64
+            };
+            if i < fill {
+                '█'
+            } else if tick {
+                '┊'
+            } else {
+                '░'
+            }
+        })
+        .collect()
+}
+
+// ---------------------------------------------------------------------------
+// Levels — one gauge per field with an expected range (non-IMU sensors)
+// ---------------------------------------------------------------------------
+
+fn render_levels(
+    frame: &mut Frame,
+    area: Rect,
+    data: Option<&SensorData>,
+    metas: &[FieldMeta],
+    theme: &Theme,
+) {
+    let block = Block::default()
+        .title(" LEVELS ")
+        .borders(Borders::ALL)
+        .border_style(theme.fg(theme.warn));
+
+    let inner = block.inner(area);
+    frame.render_widget(block, area);
+
+    let Some(d) = data else {
+        return;
+    };
+    let label_width = metas
+        .iter()
+        .map(|m| m.label.chars().count())
+        .max()
+        .unwrap_or(0);
+    let bar_width = (inner.width as usize).saturating_sub(label_width + 18);
+
+    let mut lines: Vec<Line> = Vec::new();
+    for m in metas {
+        let Some(&v) = d.fields.get(m.key.as_str()) else {
+            continue;
+        };
+        let mut spans = vec![Span::styled(
+            format!("{:<w$} {:>14} ", m.label, m.format(v), w = label_width),
+            theme.fg(theme.accent),
+        )];
+        if let Some(f) = m.fraction(v) {
+            // Pinned at either end of the range usually means saturation
+            // or a wiring fault.
+            let color = if !(0.02..=0.98).contains(&f) {
+                theme.warn
+            } else {
+                theme.ok
+            };
+            spans.push(progress_bar(f, bar_width, theme).style(theme.fg(color)));
+        }
+        lines.push(Line::from(spans));
     }
+    frame.render_widget(Paragraph::new(lines), inner);
 }
 
 // ---------------------------------------------------------------------------
 // G-ball — 2-D dot on a circular canvas
 // ---------------------------------------------------------------------------
 
-fn render_g_ball(frame: &mut Frame, area: Rect, data: Option<&SensorData>) {
+fn render_g_ball(frame: &mut Frame, area: Rect, data: Option<&SensorData>, theme: &Theme) {
     let block = Block::default()
         .title(" G-BALL ")
         .borders(Borders::ALL)
-        .border_style(Style::default().fg(Color::Magenta));
+        .border_style(theme.fg(theme.accent_alt));
+    let grid = theme.muted;
+    let dot_theme = *theme;
 
     let gx = data
         .and_then(|d| d.fields.get("g_force_x"))
@@ -300,14 +417,14 @@ fn render_g_ball(frame: &mut Frame, area: Rect, data: Option<&SensorData>) {
                 x: 0.0,
                 y: 0.0,
                 radius: 2.0,
-                color: Color::DarkGray,
+                color: grid,
             });
             // 1G ring
             ctx.draw(&ratatui::widgets::canvas::Circle {
                 x: 0.0,
                 y: 0.0,
                 radius: 1.0,
-                color: Color::DarkGray,
+                color: grid,
             });
             // Cross-hairs
             ctx.draw(&ratatui::widgets::canvas::Line {
@@ -315,17 +432,17 @@ fn render_g_ball(frame: &mut Frame, area: Rect, data: Option<&SensorData>) {
                 y1: 0.0,
                 x2: 2.0,
                 y2: 0.0,
-                color: Color::DarkGray,
+                color: grid,
             });
             ctx.draw(&ratatui::widgets::canvas::Line {
                 x1: 0.0,
                 y1: -2.0,
                 x2: 0.0,
                 y2: 2.0,
-                color: Color::DarkGray,
+                color: grid,
             });
             // Ball dot
-            let dot_color = g_color((gx * gx + gy * gy).sqrt());
+            let dot_color = dot_theme.g_color((gx * gx + gy * gy).sqrt());
             ctx.draw(&ratatui::widgets::canvas::Circle {
                 x: gx.clamp(-1.9, 1.9),
                 y: gy.clamp(-1.9, 1.9),
@@ -341,92 +458,258 @@ fn render_g_ball(frame: &mut Frame, area: Rect, data: Option<&SensorData>) {
 // Sparklines (time-series chart)
 // ---------------------------------------------------------------------------
 
-fn render_sparklines(frame: &mut Frame, area: Rect, history: Option<&SensorHistory>) {
+/// Up to three fields for the time-series chart: the G-force axes for an
+/// IMU, otherwise the first fields sharing the first field's unit (so one
+/// y-axis fits them all).
+fn chart_fields(metas: &[FieldMeta]) -> Vec<&FieldMeta> {
+    let g: Vec<&FieldMeta> = ["g_force_x", "g_force_y", "g_force_z"]
+        .iter()
+        .filter_map(|k| meta(metas, k))
+        .collect();
+    if !g.is_empty() {
+        return g;
+    }
+    let Some(first) = metas.first() else {
+        return Vec::new();
+    };
+    metas
+        .iter()
+        .filter(|m| m.unit == first.unit)
+        .take(3)
+        .collect()
+}
+
+fn render_sparklines(
+    frame: &mut Frame,
+    area: Rect,
+    history: Option<&SensorHistory>,
+    metas: &[FieldMeta],
+    visible: [bool; 3],
+    theme: &Theme,
+) {
+    let series = chart_fields(metas);
+    let is_imu = series.iter().any(|m| m.is_g_force);
+    let names = if is_imu {
+        ["X", "Y", "Z"]
+    } else {
+        ["1", "2", "3"]
+    };
+    let flags: String = names
+        .iter()
+        .zip(visible)
+        .take(series.len().max(1))
+        .map(|(axis, on)| if on { *axis } else { "-" })
+        .collect::<Vec<_>>()
+        .join(" ");
     let block = Block::default()
-        .title(" TIME SERIES ")
+        .title(format!(" TIME SERIES [{}] ", flags))
         .borders(Borders::ALL)
-        .border_style(Style::default().fg(Color::Green));
-
-    if let Some(hist) = history {
-        // Show G-force X/Y/Z as a Chart
-        let axes_info = [
-            ("g_force_x", Color::Red),
-            ("g_force_y", Color::Green),
-            ("g_force_z", Color::Blue),
-        ];
+        .border_style(theme.fg(theme.ok));
 
-        let owned: Vec<(Vec<(f64, f64)>, Color)> = axes_info
+    if let Some(hist) = history.filter(|_| !series.is_empty()) {
+        let points: Vec<Vec<(f64, f64)>> = series
             .iter()
-            .map(|(key, color)| {
-                let pts: Vec<(f64, f64)> = hist
-                    .get(key)
-                    .map(|buf| {
-                        buf.iter()
-                            .enumerate()
-                            .map(|(i, &v)| (i as f64, v))
-                            .collect()
-                    })
-                    .unwrap_or_default();
-                (pts, *color)
+            .zip(visible)
+            .map(|(m, on)| match hist.get(&m.key) {
+                Some(buf) if on => time_points(&hist.timestamps, buf),
+                _ => Vec::new(),
             })
             .collect();
-
-        let datasets: Vec<Dataset> = owned
+        let span = points
+            .iter()
+            .filter_map(|pts| pts.first())
+            .map(|&(x, _)| -x)
+            .fold(1.0, f64::max);
+
+        // One dataset per run of samples, so a gap from dropped samples
+        // shows as a break instead of a straight line across it, and
+        // samples buffered while paused can be greyed.  Each run is cut
+        // down to what the chart can show: two braille dots per cell.
+        let columns = area.width.saturating_sub(2) as usize * 2;
+        let runs: Vec<Vec<_>> = points
             .iter()
-            .zip(["Lat-X", "Fwd-Y", "Vrt-Z"])
-            .map(|((pts, color), label)| {
-                Dataset::default()
-                    .name(label)
-                    .marker(Marker::Braille)
-                    .graph_type(GraphType::Line)
-                    .style(Style::default().fg(*color))
-                    .data(pts)
+            .map(|pts| {
+                let buffered = buffered_flags(&hist.buffered, pts.len());
+                split_runs(pts, &buffered)
+                    .into_iter()
+                    .map(|(run, buffered)| (downsample(run, span, columns), buffered))
+                    .collect()
             })
             .collect();
 
-        let max_x = owned.iter().map(|(pts, _)| pts.len()).max().unwrap_or(1) as f64;
+        let datasets: Vec<Dataset> = runs
+            .iter()
+            .zip(&series)
+            .enumerate()
+            .filter(|(i, _)| visible[*i])
+            .flat_map(|(i, (runs, m))| {
+                let color = theme.series[i];
+                runs.iter().enumerate().map(move |(run, (pts, buffered))| {
+                    let dataset = Dataset::default()
+                        .marker(theme.series_marker(i))
+                        .graph_type(GraphType::Line)
+                        .style(theme.fg(if *buffered { theme.muted } else { color }))
+                        .data(pts);
+                    // Only the first run goes in the legend
+                    if run == 0 {
+                        dataset.name(m.label.clone())
+                    } else {
+                        dataset
+                    }
+                })
+            })
+            .collect();
 
-        let (y_min, y_max) = hist
-            .stats("g_force_x")
-            .map(|(min, max, _)| (min - 0.5, max + 0.5))
+        // Scale to the visible series only, so hiding the vertical trace
+        // lets the lateral one use the full height.
+        let (y_min, y_max) = series
+            .iter()
+            .zip(visible)
+            .filter(|(_, on)| *on)
+            .filter_map(|(m, _)| hist.stats(&m.key))
+            .fold(None, |acc: Option<(f64, f64)>, (min, max, _)| {
+                Some(acc.map_or((min, max), |(lo, hi)| (lo.min(min), hi.max(max))))
+            })
+            .map(|(min, max)| {
+                let pad = ((max - min) * 0.05).max(0.5);
+                (min - pad, max + pad)
+            })
             .unwrap_or((-2.0, 2.0));
 
         let chart = Chart::new(datasets)
             .block(block)
             .x_axis(
                 Axis::default()
-                    .bounds([0.0, max_x])
-                    .style(Style::default().fg(Color::DarkGray)),
+                    .bounds([-span, 0.0])
+                    .labels(vec![
+                        Span::raw(ago_label(span)),
+                        Span::raw(ago_label(span / 2.0)),
+                        Span::raw("now"),
+                    ])
+                    .style(theme.fg(theme.muted)),
             )
             .y_axis(
                 Axis::default()
+                    .title(series[0].unit.clone())
                     .bounds([y_min, y_max])
                     .labels(vec![
                         Span::raw(format!("{:.1}", y_min)),
-                        Span::raw("0.0"),
+                        Span::raw(format!("{:.1}", (y_min + y_max) / 2.0)),
                         Span::raw(format!("{:.1}", y_max)),
                     ])
-                    .style(Style::default().fg(Color::DarkGray)),
+                    .style(theme.fg(theme.muted)),
             );
 
         frame.render_widget(chart, area);
     } else {
         let para = Paragraph::new("No history yet")
             .block(block)
-            .style(Style::default().fg(Color::DarkGray));
+            .style(theme.fg(theme.muted));
         frame.render_widget(para, area);
     }
 }
 
+/// `(seconds before the newest sample, value)` for each value.  A channel
+/// that appeared after the first sample has fewer values than there are
+/// timestamps; both end at the newest sample.
+fn time_points(timestamps: &VecDeque<DateTime<Utc>>, values: &VecDeque<f64>) -> Vec<(f64, f64)> {
+    let Some(&newest) = timestamps.back() else {
+        return Vec::new();
+    };
+    let skip = timestamps.len().saturating_sub(values.len());
+    timestamps
+        .iter()
+        .skip(skip)
+        .zip(values)
+        .map(|(&t, &v)| {
+            let ago = (newest - t).num_microseconds().unwrap_or(i64::MAX) as f64 / 1e6;
+            (-ago, v)
+        })
+        .collect()
+}
+
+/// Whether each of the newest `n` samples was buffered while paused.
+/// Samples from before the history kept the flags count as published.
+fn buffered_flags(buffered: &VecDeque<bool>, n: usize) -> Vec<bool> {
+    let pad = n.saturating_sub(buffered.len());
+    std::iter::repeat_n(false, pad)
+        .chain(
+            buffered
+                .iter()
+                .skip(buffered.len().saturating_sub(n))
+                .copied(),
+        )
+        .collect()
+}
+
+/// Split where consecutive samples are more than three times the median
+/// interval apart, and where buffered samples start or end.  Each run
+/// comes with whether it was buffered.
+fn split_runs<'a>(pts: &'a [(f64, f64)], buffered: &[bool]) -> Vec<(&'a [(f64, f64)], bool)> {
+    let is_buffered = |i: usize| buffered.get(i).copied().unwrap_or(false);
+    let mut steps: Vec<f64> = pts.windows(2).map(|w| w[1].0 - w[0].0).collect();
+    if steps.is_empty() {
+        return vec![(pts, is_buffered(0))];
+    }
+    steps.sort_by(f64::total_cmp);
+    let gap = steps[steps.len() / 2] * 3.0;
+    let mut runs = Vec::new();
+    let mut start = 0;
+    for i in 1..pts.len() {
+        if pts[i].0 - pts[i - 1].0 > gap || is_buffered(i) != is_buffered(i - 1) {
+            runs.push((&pts[start..i], is_buffered(start)));
+            start = i;
+        }
+    }
+    runs.push((&pts[start..], is_buffered(start)));
+    runs
+}
+
+/// At most `columns` × 2 points for a chart `span` seconds wide: the
+/// lowest and highest value in each column, in the order they occurred, so
+/// short peaks survive.  Runs that already fit are returned as they are.
+fn downsample(pts: &[(f64, f64)], span: f64, columns: usize) -> Vec<(f64, f64)> {
+    if pts.len() <= columns * 2 || columns == 0 {
+        return pts.to_vec();
+    }
+    let column_of = |x: f64| (((x + span) / span * columns as f64) as usize).min(columns - 1);
+    let mut out = Vec::with_capacity(columns * 2);
+    for bucket in pts.chunk_by(|a, b| column_of(a.0) == column_of(b.0)) {
+        let lo = bucket.iter().min_by(|a, b| a.1.total_cmp(&b.1)).unwrap();
+        let hi = bucket.iter().max_by(|a, b| a.1.total_cmp(&b.1)).unwrap();
+        if lo.0 <= hi.0 {
+            out.extend([*lo, *hi]);
+        } else {
+            out.extend([*hi, *lo]);
+        }
+    }
+    out.dedup();
+    out
+}
+
+/// `45s ago`, `2m30s ago`
+fn ago_label(secs: f64) -> String {
+    let secs = secs.round() as u64;
+    if secs < 60 {
+        format!("{}s ago", secs)
+    } else if secs.is_multiple_of(60) {
+        format!("{}m ago", secs / 60)
+    } else {
+        format!("{}m{:02}s ago", secs / 60, secs % 60)
+    }
+}
+
 // ---------------------------------------------------------------------------
 // Orientation compass canvas
 // ---------------------------------------------------------------------------
 
-fn render_orientation(frame: &mut Frame, area: Rect, data: Option<&SensorData>) {
+fn render_orientation(frame: &mut Frame, area: Rect, data: Option<&SensorData>, theme: &Theme) {
     let block = Block::default()
         .title(" ORIENTATION ")
         .borders(Borders::ALL)
-        .border_style(Style::default().fg(Color::Cyan));
+        .border_style(theme.fg(theme.accent));
+    let grid = theme.muted;
+    let (lean_color, bank_color) = (theme.warn, theme.error);
 
     let lean = data
         .and_then(|d| d.fields.get("lean_angle"))
@@ -451,7 +734,7 @@ fn render_orientation(frame: &mut Frame, area: Rect, data: Option<&SensorData>)
                 x: 0.0,
                 y: 0.0,
                 radius: 80.0,
-                color: Color::DarkGray,
+                color: grid,
             });
             // Cross
             ctx.draw(&ratatui::widgets::canvas::Line {
@@ -459,14 +742,14 @@ fn render_orientation(frame: &mut Frame, area: Rect, data: Option<&SensorData>)
                 y1: 0.0,
                 x2: 80.0,
                 y2: 0.0,
-                color: Color::DarkGray,
+                color: grid,
             });
             ctx.draw(&ratatui::widgets::canvas::Line {
                 x1: 0.0,
                 y1: -80.0,
                 x2: 0.0,
                 y2: 80.0,
-                color: Color::DarkGray,
+                color: grid,
             });
 
             // Lean indicator (Y axis — side tilt)
@@ -478,7 +761,7 @@ fn render_orientation(frame: &mut Frame, area: Rect, data: Option<&SensorData>)
                 y1: 0.0,
                 x2: lx,
                 y2: ly,
-                color: Color::Yellow,
+                color: lean_color,
             });
 
             // Bank indicator (X axis)
@@ -490,7 +773,7 @@ fn render_orientation(frame: &mut Frame, area: Rect, data: Option<&SensorData>)
                 y1: 0.0,
                 x2: bx,
                 y2: by,
-                color: Color::Red,
+                color: bank_color,
             });
         });
 
@@ -501,45 +784,87 @@ fn render_orientation(frame: &mut Frame, area: Rect, data: Option<&SensorData>)
 // Connections tab
 // ---------------------------------------------------------------------------
 
-pub fn render_connections_tab(frame: &mut Frame, area: Rect, snap: &StateSnapshot) {
+pub fn render_connections_tab(frame: &mut Frame, area: Rect, snap: &StateSnapshot, theme: &Theme) {
     let block = Block::default()
         .title(" CONNECTIONS ")
         .borders(Borders::ALL)
-        .border_style(Style::default().fg(Color::Cyan));
+        .border_style(theme.fg(theme.accent));
     let inner = block.inner(area);
     frame.render_widget(block, area);
 
     let mut lines: Vec<Line> = Vec::new();
-    lines.push(section_line("SENSORS"));
+    lines.push(section_line("SENSORS", theme));
 
     for st in &snap.sensor_statuses {
         lines.push(Line::from(vec![
-            Span::styled(
-                format!("  {:<16}", st.name),
-                Style::default().fg(Color::Cyan),
-            ),
-            status_dot(st.connected),
+            Span::styled(format!("  {:<16}", st.name), theme.fg(theme.accent)),
+            status_dot(st.connected, theme),
             Span::raw(format!("  {}", st.connection_display)),
         ]));
         if let Some(ref e) = st.last_error {
             lines.push(Line::from(Span::styled(
                 format!("    Error: {}", e),
-                Style::default().fg(Color::Red),
+                theme.error_style(),
+            )));
+        }
+        if !st.stuck.is_empty() {
+            lines.push(Line::from(Span::styled(
+                format!("    Stuck: {}", st.stuck.join(", ")),
+                theme.fg(theme.warn),
             )));
         }
     }
 
-    lines.push(section_line("MQTT"));
+    lines.push(section_line("MQTT", theme));
     let mqtt_dot = snap.mqtt_status.is_connected();
     lines.push(Line::from(vec![
-        Span::styled("  Broker          ", Style::default().fg(Color::Cyan)),
-        status_dot(mqtt_dot),
+        Span::styled("  Broker          ", theme.fg(theme.accent)),
+        status_dot(mqtt_dot, theme),
         Span::raw(format!("  {}", snap.mqtt_address)),
     ]));
+    lines.push(data_row(
+        "  Readings",
+        crate::tui::publish_mode_text(snap.publish_mode).to_string(),
+        theme,
+    ));
+    if let Some(detail) = snap.mqtt_status.detail() {
+        lines.push(data_row(
+            "  Status",
+            format!("{} ({})", snap.mqtt_status.label(), detail),
+            theme,
+        ));
+    }
     lines.push(data_row(
         "  Published",
         format!("{}", snap.messages_published),
+        theme,
     ));
+    lines.push(data_row(
+        "  Dropped",
+        format!("{}", snap.messages_dropped),
+        theme,
+    ));
+
+    if let Some(extremes) = &snap.extremes {
+        lines.push(section_line("ALL-TIME EXTREMES", theme));
+        let fmt = |e: Option<crate::extremes::Extreme>| match e {
+            Some(e) => format!("{:>9.2} ({})", e.value, e.at.format("%Y-%m-%d")),
+            None => format!("{:>9} {:12}", "—", ""),
+        };
+        let width = extremes.channels.keys().map(|k| k.chars().count()).max();
+        for (key, e) in &extremes.channels {
+            lines.push(Line::from(vec![
+                Span::styled(
+                    format!("  {:<w$}  ", key, w = width.unwrap_or(0)),
+                    theme.label(),
+                ),
+                Span::styled(
+                    format!("min {}   max {}", fmt(e.min), fmt(e.max)),
+                    theme.fg(theme.text),
+                ),
+            ]));
+        }
+    }
 
     let para = Paragraph::new(lines);
     frame.render_widget(para, inner);
@@ -549,11 +874,11 @@ pub fn render_connections_tab(frame: &mut Frame, area: Rect, snap: &StateSnapsho
 // Logs tab
 // ---------------------------------------------------------------------------
 
-pub fn render_logs_tab(frame: &mut Frame, area: Rect, snap: &StateSnapshot) {
+pub fn render_logs_tab(frame: &mut Frame, area: Rect, snap: &StateSnapshot, theme: &Theme) {
     let block = Block::default()
         .title(" APPLICATION LOGS ")
         .borders(Borders::ALL)
-        .border_style(Style::default().fg(Color::DarkGray));
+        .border_style(theme.fg(theme.muted));
 
     let height = area.height.saturating_sub(2) as usize;
     let start = snap.logs.len().saturating_sub(height);
@@ -561,13 +886,13 @@ pub fn render_logs_tab(frame: &mut Frame, area: Rect, snap: &StateSnapshot) {
         .iter()
         .map(|line| {
             let style = if line.contains("ERROR") {
-                Style::default().fg(Color::Red)
+                theme.error_style()
             } else if line.contains("WARN") {
-                Style::default().fg(Color::Yellow)
+                theme.fg(theme.warn)
             } else if line.contains("INFO") {
-                Style::default().fg(Color::Gray)
+                theme.fg(theme.text)
             } else {
-                Style::default().fg(Color::DarkGray)
+                theme.fg(theme.muted)
             };
             ListItem::new(Line::from(Span::styled(line.clone(), style)))
         })
@@ -576,3 +901,81 @@ pub fn render_logs_tab(frame: &mut Frame, area: Rect, snap: &StateSnapshot) {
     let list = List::new(items).block(block);
     frame.render_widget(list, area);
 }
+
+// ---------------------------------------------------------------------------
+// Unit tests
+// ---------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_chart_x_axis_follows_timestamps() {
+        let t0 = DateTime::from_timestamp(1_700_000_000, 0).unwrap();
+        // 10 Hz with one 1.5 s stall
+        let ms = [0, 100, 200, 300, 1800, 1900, 2000];
+        let timestamps: VecDeque<_> = ms
+            .iter()
+            .map(|&m| t0 + chrono::Duration::milliseconds(m))
+            .collect();
+        let values: VecDeque<f64> = (0..ms.len()).map(|i| i as f64).collect();
+
+        let pts = time_points(&timestamps, &values);
+        assert_eq!(pts.first(), Some(&(-2.0, 0.0)));
+        assert_eq!(pts.last(), Some(&(0.0, 6.0)));
+        let runs = split_runs(&pts, &[]);
+        assert_eq!(runs.iter().map(|r| r.0.len()).collect::<Vec<_>>(), [4, 3]);
+
+        // The last two samples were buffered while paused
+        let buffered: VecDeque<bool> = [false, false, true, true].into_iter().collect();
+        let flags = buffered_flags(&buffered, pts.len());
+        assert_eq!(flags, [false, false, false, false, false, true, true]);
+        let runs: Vec<(usize, bool)> = split_runs(&pts, &flags)
+            .iter()
+            .map(|(run, b)| (run.len(), *b))
+            .collect();
+        assert_eq!(runs, [(4, false), (1, false), (2, true)]);
+
+        // A channel that appeared late lines up with the newest samples
+        let late: VecDeque<f64> = [5.0, 6.0].into_iter().collect();
+        assert_eq!(time_points(&timestamps, &late), [(-0.1, 5.0), (0.0, 6.0)]);
+
+        assert_eq!(ago_label(45.2), "45s ago");
+        assert_eq!(ago_label(120.0), "2m ago");
+        assert_eq!(ago_label(150.0), "2m30s ago");
+    }
+
+    #[test]
+    fn test_downsample_keeps_peaks() {
+        // 10 s at 1 kHz with one 1 ms spike, onto 50 columns
+        let mut pts: Vec<(f64, f64)> = (0..10_000)
+            .map(|i| (i as f64 / 1000.0 - 10.0, (i as f64 / 500.0).sin()))
+            .collect();
+        pts[6_543].1 = 9.0;
+        let out = downsample(&pts, 10.0, 50);
+        assert!(out.len() <= 100, "{} points", out.len());
+        assert!(out.contains(&pts[6_543]), "spike kept");
+        assert!(
+            out.windows(2).all(|w| w[0].0 <= w[1].0),
+            "still in time order"
+        );
+        let min = |p: &[(f64, f64)]| p.iter().map(|p| p.1).fold(f64::MAX, f64::min);
+        assert_eq!(min(&out), min(&pts));
+
+        assert_eq!(downsample(&pts[..80], 10.0, 50), &pts[..80]);
+    }
+
+    #[test]
+    fn test_g_bar_scales_to_width_with_ticks() {
+        // 2 G full scale over 16 cells: a tick every 4 cells
+        assert_eq!(g_bar(0.0, 2.0, 16), "░░░░┊░░░┊░░░┊░░░");
+        assert_eq!(g_bar(0.75, 2.0, 16), "██████░░┊░░░┊░░░");
+        assert_eq!(g_bar(5.0, 2.0, 8), "████████", "clamped at full scale");
+        assert_eq!(g_bar(1.0, 2.0, 32).chars().count(), 32);
+        // Too narrow for 0.5 G ticks at 16 G full scale
+        assert_eq!(g_bar(0.0, 16.0, 10), "░".repeat(10));
+        assert_eq!(g_bar(1.0, 0.0, 4), "░░░░");
+        assert_eq!(g_bar(1.0, 2.0, 0), "");
+    }
+}
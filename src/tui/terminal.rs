@@ -0,0 +1,497 @@
+//! Interactive terminal UI, local or attached to a daemon.
+//!
+//! Layout (always):
+//!   header  (3 lines)
+//!   tabs    (1 line)
+//!   main    (Min 8) — content switches per selected tab
+//!   log     (8 lines) — always visible, shows recent tracing log lines
+//!   status  (1 line)  — latest status message; `[` / `]` browse history
+//!
+//! Below [`MIN_WIDTH`] × [`MIN_HEIGHT`] only a "terminal too small"
+//! placeholder is drawn until the terminal is resized.
+//!
+//! The sensor tasks publish on their own whether or not a UI is drawn; the
+//! render loop only receives snapshots through a channel and reads the
+//! keyboard on a thread of its own, so neither a slow terminal nor
+//! crossterm's blocking reads hold up sampling or MQTT.
+
+use std::collections::VecDeque;
+use std::io::{self, Write};
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+
+use crossterm::event::{self, Event, KeyCode, KeyModifiers};
+use crossterm::execute;
+use crossterm::terminal::{
+    EnterAlternateScreen, LeaveAlternateScreen, disable_raw_mode, enable_raw_mode,
+};
+use ratatui::backend::CrosstermBackend;
+use ratatui::layout::{Constraint, Direction, Layout};
+use ratatui::text::{Line, Span};
+use ratatui::widgets::Tabs;
+use ratatui::{Frame, Terminal};
+use tokio::sync::{Notify, mpsc, watch};
+use tokio_util::sync::CancellationToken;
+
+use crate::config::{UiConfig, resolve_path};
+use crate::models::SharedState;
+use crate::tui::snapshot::StateSnapshot;
+use crate::tui::tabs::RenderOptions;
+use crate::tui::theme::Theme;
+use crate::tui::view_state::ViewState;
+use crate::tui::{UiCommand, apply_command, attach, tabs, widgets};
+
+/// Smallest terminal the full layout is drawn in: every panel keeps its
+/// borders and at least a line of content.
+const MIN_WIDTH: u16 = 60;
+const MIN_HEIGHT: u16 = 21;
+
+/// How often the input thread checks whether the UI has quit
+const INPUT_POLL: Duration = Duration::from_millis(250);
+
+// ---------------------------------------------------------------------------
+// Key bindings
+// ---------------------------------------------------------------------------
+
+enum KeyAction {
+    Quit,
+    Command(UiCommand),
+}
+
+fn key_action(code: KeyCode, modifiers: KeyModifiers) -> Option<KeyAction> {
+    let cmd = match (code, modifiers) {
+        (KeyCode::Char('q'), _) | (KeyCode::Esc, _) => return Some(KeyAction::Quit),
+        (KeyCode::Char('c'), KeyModifiers::CONTROL) => return Some(KeyAction::Quit),
+        (KeyCode::Left, _) => UiCommand::PrevTab,
+        (KeyCode::Right, _) => UiCommand::NextTab,
+        (KeyCode::Char('r'), _) => UiCommand::Recalibrate,
+        (KeyCode::Char('g'), _) => UiCommand::ToggleGroup,
+        (KeyCode::Char('e'), _) => UiCommand::Export,
+        (KeyCode::Char('a'), _) => UiCommand::AcknowledgeAlerts,
+        (KeyCode::Char('R'), _) => UiCommand::ToggleRecording,
+        (KeyCode::Char('D'), _) => UiCommand::DiagnosticBundle,
+        (KeyCode::Char('p'), _) => UiCommand::CyclePublishing,
+        (KeyCode::Char('X'), _) => UiCommand::ResetExtremes,
+        (KeyCode::Char(c @ '1'..='3'), KeyModifiers::ALT) => UiCommand::ToggleSeries {
+            axis: c as usize - '1' as usize,
+        },
+        (KeyCode::Char(c @ ('x' | 'y' | 'z')), _) => UiCommand::ToggleSeries {
+            axis: c as usize - 'x' as usize,
+        },
+        (KeyCode::Char('['), _) => UiCommand::StatusOlder,
+        (KeyCode::Char(']'), _) => UiCommand::StatusNewer,
+        (KeyCode::Char(c), _) if c.is_ascii_digit() => UiCommand::SelectTab {
+            index: (c as usize).wrapping_sub('1' as usize),
+        },
+        _ => return None,
+    };
+    Some(KeyAction::Command(cmd))
+}
+
+// ---------------------------------------------------------------------------
+// Snapshot sources — in-process state or a remote daemon
+// ---------------------------------------------------------------------------
+
+/// Where the render loop gets its snapshots and sends its commands.
+pub(super) trait UiSource {
+    async fn snapshot(&mut self) -> anyhow::Result<StateSnapshot>;
+    async fn send(&mut self, cmd: UiCommand) -> anyhow::Result<()>;
+}
+
+struct LocalSource {
+    state: SharedState,
+    log_buf: Arc<std::sync::Mutex<VecDeque<String>>>,
+    ui: UiConfig,
+    /// Built every refresh tick by a task of their own
+    snapshots: watch::Receiver<StateSnapshot>,
+    /// Asks that task for a snapshot now, after a command changed the view
+    wake: Arc<Notify>,
+    /// A command was applied since the last snapshot was taken
+    stale: bool,
+}
+
+impl LocalSource {
+    async fn spawn(
+        state: SharedState,
+        log_buf: Arc<std::sync::Mutex<VecDeque<String>>>,
+        ui: UiConfig,
+        refresh: Duration,
+    ) -> Self {
+        let wake = Arc::new(Notify::new());
+        let first = StateSnapshot::from(&*state.read().await, &log_buf);
+        let (tx, rx) = watch::channel(first);
+        tokio::spawn({
+            let (state, log_buf) = (state.clone(), log_buf.clone());
+            let wake = Arc::clone(&wake);
+            async move {
+                let mut tick = tokio::time::interval(refresh);
+                loop {
+                    tokio::select! {
+                        _ = tick.tick() => {}
+                        _ = wake.notified() => {}
+                    }
+                    let snap = StateSnapshot::from(&*state.read().await, &log_buf);
+                    // Ends with the render loop, which owns the receiver
+                    if tx.send(snap).is_err() {
+                        break;
+                    }
+                }
+            }
+        });
+        Self {
+            state,
+            log_buf,
+            ui,
+            snapshots: rx,
+            wake,
+            stale: false,
+        }
+    }
+}
+
+impl UiSource for LocalSource {
+    async fn snapshot(&mut self) -> anyhow::Result<StateSnapshot> {
+        if self.stale {
+            self.stale = false;
+            self.snapshots.changed().await?;
+        }
+        Ok(self.snapshots.borrow_and_update().clone())
+    }
+
+    async fn send(&mut self, cmd: UiCommand) -> anyhow::Result<()> {
+        apply_command(&mut *self.state.write().await, &self.ui, &self.log_buf, cmd);
+        // Show the command's effect now rather than at the next tick
+        self.snapshots.mark_unchanged();
+        self.stale = true;
+        self.wake.notify_one();
+        Ok(())
+    }
+}
+
+/// Read terminal events on a thread of their own: crossterm's reads block,
+/// and would otherwise hold up one of the runtime's few workers on a Pi.
+/// The thread stops once the receiver is dropped or reading fails.
+fn spawn_input_reader() -> mpsc::UnboundedReceiver<io::Result<Event>> {
+    let (tx, rx) = mpsc::unbounded_channel();
+    std::thread::spawn(move || {
+        while !tx.is_closed() {
+            let event = match event::poll(INPUT_POLL) {
+                Ok(false) => continue,
+                Ok(true) => event::read(),
+                Err(e) => Err(e),
+            };
+            let failed = event.is_err();
+            if tx.send(event).is_err() || failed {
+                break;
+            }
+        }
+    });
+    rx
+}
+
+// ---------------------------------------------------------------------------
+// TUI loop
+// ---------------------------------------------------------------------------
+
+pub async fn run_tui(
+    state: SharedState,
+    log_buf: Arc<std::sync::Mutex<VecDeque<String>>>,
+    cancel: CancellationToken,
+    ui: UiConfig,
+    refresh: Duration,
+) -> anyhow::Result<()> {
+    let theme = Theme::resolve(ui.theme);
+    let view_path = resolve_path(&ui.view_state_file);
+    if let Some(view) = view_path.as_deref().and_then(ViewState::load) {
+        view.apply(&mut *state.write().await);
+    }
+    let mut source = LocalSource::spawn(state.clone(), log_buf, ui, refresh).await;
+    let opts = RenderOptions::new(&source.ui);
+    run_terminal(&mut source, cancel, &theme, opts, refresh).await?;
+
+    if let Some(path) = &view_path {
+        let view = ViewState::capture(&*state.read().await);
+        if let Err(e) = view.save(path) {
+            tracing::warn!("Couldn't save view state: {:#}", e);
+        }
+    }
+
+    // Don't lose the tail of an active recording on quit.
+    if let Some(rec) = state.write().await.recording.take() {
+        rec.finish()?;
+    }
+    Ok(())
+}
+
+/// Display the TUI of a daemon listening on `socket` (`sensors-to-mqtt attach`).
+pub async fn run_attach(
+    socket: &Path,
+    cancel: CancellationToken,
+    ui: UiConfig,
+    refresh: Duration,
+) -> anyhow::Result<()> {
+    let mut source = attach::RemoteSource::connect(socket).await?;
+    run_terminal(
+        &mut source,
+        cancel,
+        &Theme::resolve(ui.theme),
+        RenderOptions::new(&ui),
+        refresh,
+    )
+    .await
+}
+
+async fn run_terminal(
+    source: &mut impl UiSource,
+    cancel: CancellationToken,
+    theme: &Theme,
+    opts: RenderOptions,
+    refresh: Duration,
+) -> anyhow::Result<()> {
+    enable_raw_mode()?;
+    let mut stdout = io::stdout();
+    execute!(stdout, EnterAlternateScreen)?;
+    let backend = CrosstermBackend::new(stdout);
+    let mut terminal = Terminal::new(backend)?;
+
+    let result = tui_loop(&mut terminal, source, cancel, theme, opts, refresh).await;
+
+    disable_raw_mode()?;
+    execute!(terminal.backend_mut(), LeaveAlternateScreen)?;
+    terminal.show_cursor()?;
+
+    result
+}
+
+async fn tui_loop(
+    terminal: &mut Terminal<CrosstermBackend<io::Stdout>>,
+    source: &mut impl UiSource,
+    cancel: CancellationToken,
+    theme: &Theme,
+    opts: RenderOptions,
+    refresh: Duration,
+) -> anyhow::Result<()> {
+    let mut input = spawn_input_reader();
+    // Newest alert the bell has rung for; ids only ever increase
+    let mut rung = 0;
+
+    loop {
+        let snap = source.snapshot().await?;
+
+        if let Some(newest) = snap
+            .alerts
+            .iter()
+            .filter(|a| !a.acknowledged)
+            .map(|a| a.id)
+            .max()
+            && newest > rung
+        {
+            rung = newest;
+            if opts.alert_bell {
+                terminal.backend_mut().write_all(b"\x07")?;
+                terminal.backend_mut().flush()?;
+            }
+        }
+
+        terminal.draw(|frame| draw(frame, &snap, theme, opts))?;
+
+        // Redraw at the refresh rate, or straight away on input
+        let event = tokio::select! {
+            _ = cancel.cancelled() => break,
+            _ = tokio::time::sleep(refresh) => continue,
+            event = input.recv() => event.ok_or_else(|| anyhow::anyhow!("input reader stopped"))??,
+        };
+        match event {
+            Event::Key(key) => match key_action(key.code, key.modifiers) {
+                Some(KeyAction::Quit) => break,
+                Some(KeyAction::Command(cmd)) => source.send(cmd).await?,
+                None => {}
+            },
+            // Repaint everything at the new size rather than diffing
+            // against a buffer of the old one
+            Event::Resize(..) => {
+                terminal.autoresize()?;
+                terminal.clear()?;
+            }
+            _ => {}
+        }
+    }
+
+    Ok(())
+}
+
+fn draw(frame: &mut Frame, snap: &StateSnapshot, theme: &Theme, opts: RenderOptions) {
+    let area = frame.area();
+    if area.width < MIN_WIDTH || area.height < MIN_HEIGHT {
+        widgets::render_too_small(frame, area, (MIN_WIDTH, MIN_HEIGHT), theme);
+        return;
+    }
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([
+            Constraint::Length(3),
+            Constraint::Length(1),
+            Constraint::Min(8),
+            Constraint::Length(8),
+            Constraint::Length(1),
+        ])
+        .split(area);
+
+    widgets::render_header(frame, chunks[0], snap.version.as_str(), theme);
+
+    // Build tab titles; sensors with an unacknowledged alert flash
+    let mut tab_titles: Vec<Line> = snap
+        .sensor_names
+        .iter()
+        .enumerate()
+        .map(|(i, n)| {
+            let title = format!(" {} ({}) ", n, i + 1);
+            if snap
+                .alerts
+                .iter()
+                .any(|a| !a.acknowledged && &a.sensor == n)
+            {
+                Line::styled(title, widgets::alert_style(theme))
+            } else {
+                Line::raw(title)
+            }
+        })
+        .collect();
+    tab_titles.push(Line::raw(format!(
+        " Connections ({}) ",
+        snap.sensor_names.len() + 1
+    )));
+    tab_titles.push(Line::raw(format!(
+        " Logs ({}) ",
+        snap.sensor_names.len() + 2
+    )));
+
+    let tabs_widget = Tabs::new(tab_titles)
+        .select(snap.selected_tab)
+        .style(theme.fg(theme.text))
+        .highlight_style(theme.selected())
+        .divider(Span::raw("|"));
+    frame.render_widget(tabs_widget, chunks[1]);
+
+    // Main content
+    let n_sensors = snap.sensor_names.len();
+    if snap.selected_tab < n_sensors {
+        tabs::render_sensor_tab(frame, chunks[2], snap, snap.selected_tab, opts, theme);
+    } else if snap.selected_tab == n_sensors {
+        tabs::render_connections_tab(frame, chunks[2], snap, theme);
+    } else {
+        tabs::render_logs_tab(frame, chunks[2], snap, theme);
+    }
+
+    // Log panel (always visible)
+    widgets::render_log_panel(frame, chunks[3], &snap.logs, theme);
+    widgets::render_status_bar(frame, chunks[4], snap, theme);
+}
+
+// ---------------------------------------------------------------------------
+// Unit tests
+// ---------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{AppState, SensorStatus};
+    use crate::sensors::SensorData;
+    use ratatui::backend::TestBackend;
+
+    fn render(snap: &StateSnapshot, width: u16, height: u16) -> String {
+        let mut terminal = Terminal::new(TestBackend::new(width, height)).unwrap();
+        let theme = Theme::resolve(Default::default());
+        let opts = RenderOptions::new(&Default::default());
+        terminal
+            .draw(|frame| draw(frame, snap, &theme, opts))
+            .unwrap();
+        let buf = terminal.backend().buffer();
+        buf.content().iter().map(|c| c.symbol()).collect()
+    }
+
+    #[test]
+    fn test_small_terminals_get_a_placeholder() {
+        let mut s = AppState::new(String::new(), false, 10);
+        s.sensor_statuses.insert(
+            "imu".into(),
+            SensorStatus {
+                name: "imu".into(),
+                driver: "synthetic".into(),
+                connection_display: String::new(),
+                group: None,
+                enabled: true,
+                connected: true,
+                last_error: None,
+                stuck: Vec::new(),
+                calibration: Default::default(),
+                fields: Vec::new(),
+            },
+        );
+        // Larger than the G-meter's range, so its bar is full
+        s.sensor_data.insert(
+            "imu".into(),
+            SensorData::new([("g_force_x".into(), 9.0)].into_iter().collect()),
+        );
+        let snap = StateSnapshot::from(&s, &Default::default());
+
+        for (w, h) in [(1, 1), (12, 3), (MIN_WIDTH - 1, 40), (200, MIN_HEIGHT - 1)] {
+            let text = render(&snap, w, h);
+            assert!(!text.contains("sensors-to-mqtt v"), "{}×{}", w, h);
+        }
+        assert!(render(&snap, 40, 10).contains("Terminal too small"));
+
+        for (w, h) in [(MIN_WIDTH, MIN_HEIGHT), (300, 80)] {
+            let text = render(&snap, w, h);
+            assert!(text.contains("sensors-to-mqtt v"), "{}×{}", w, h);
+        }
+
+        // The G-meter bar grows with the terminal instead of a fixed width
+        let bar = |w| render(&snap, w, 40).matches('█').count();
+        assert!(bar(200) > bar(100), "{} vs {}", bar(200), bar(100));
+    }
+
+    #[tokio::test]
+    async fn test_local_source_snapshots_follow_state_and_commands() {
+        let mut s = AppState::new(String::new(), false, 10);
+        s.sensor_statuses.insert(
+            "imu".into(),
+            SensorStatus {
+                name: "imu".into(),
+                driver: "synthetic".into(),
+                connection_display: String::new(),
+                group: None,
+                enabled: true,
+                connected: true,
+                last_error: None,
+                stuck: Vec::new(),
+                calibration: Default::default(),
+                fields: Vec::new(),
+            },
+        );
+        let state: SharedState = Arc::new(tokio::sync::RwLock::new(s));
+        let log_buf = Arc::new(std::sync::Mutex::new(VecDeque::new()));
+        let mut source = LocalSource::spawn(
+            state.clone(),
+            log_buf,
+            UiConfig::default(),
+            Duration::from_millis(10),
+        )
+        .await;
+        assert_eq!(source.snapshot().await.unwrap().sensor_names, ["imu"]);
+
+        // A command shows in the very next snapshot, not a tick later
+        source.send(UiCommand::NextTab).await.unwrap();
+        assert_eq!(source.snapshot().await.unwrap().selected_tab, 1);
+
+        // Changes made by the sensor tasks arrive on the refresh tick
+        state.write().await.sensor_data.insert(
+            "imu".into(),
+            SensorData::new([("g_force_x".into(), 1.0)].into_iter().collect()),
+        );
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        let snap = source.snapshot().await.unwrap();
+        assert!(snap.sensor_data[0].is_some());
+    }
+}
@@ -0,0 +1,161 @@
+//! TUI colour themes.
+//!
+//! Every renderer takes its colours from a [`Theme`] instead of hardcoding
+//! `Color::*` values, so switching `ui.theme` restyles the whole interface.
+
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::symbols::Marker;
+
+use crate::config::ThemeName;
+
+#[derive(Debug, Clone, Copy)]
+pub struct Theme {
+    /// No colours at all — emphasis via modifiers only.
+    pub mono: bool,
+    /// Labels, titles and the primary panel borders.
+    pub accent: Color,
+    /// Secondary panel accent (G-ball border).
+    pub accent_alt: Color,
+    /// Regular values.
+    pub text: Color,
+    /// Hints, placeholders, grid lines, inactive borders.
+    pub muted: Color,
+    pub ok: Color,
+    pub warn: Color,
+    /// Between warn and error (e.g. 2–3 G).
+    pub alert: Color,
+    pub error: Color,
+    /// Selected tab.
+    pub highlight: Color,
+    /// X / Y / Z chart series.
+    pub series: [Color; 3],
+}
+
+impl Theme {
+    /// Resolve the configured theme, falling back to monochrome when the
+    /// `NO_COLOR` convention (<https://no-color.org>) is in effect.
+    pub fn resolve(name: ThemeName) -> Self {
+        let no_color = std::env::var_os("NO_COLOR").is_some_and(|v| !v.is_empty());
+        if no_color {
+            Self::from_name(ThemeName::Mono)
+        } else {
+            Self::from_name(name)
+        }
+    }
+
+    pub fn from_name(name: ThemeName) -> Self {
+        match name {
+            ThemeName::Dark => Self {
+                mono: false,
+                accent: Color::Cyan,
+                accent_alt: Color::Magenta,
+                text: Color::White,
+                muted: Color::DarkGray,
+                ok: Color::Green,
+                warn: Color::Yellow,
+                alert: Color::LightRed,
+                error: Color::Red,
+                highlight: Color::Yellow,
+                series: [Color::Red, Color::Green, Color::Blue],
+            },
+            ThemeName::Light => Self {
+                mono: false,
+                accent: Color::Blue,
+                accent_alt: Color::Magenta,
+                text: Color::Black,
+                muted: Color::Gray,
+                ok: Color::Green,
+                warn: Color::Rgb(0xB5, 0x89, 0x00),
+                alert: Color::LightRed,
+                error: Color::Red,
+                highlight: Color::Blue,
+                series: [Color::Red, Color::Green, Color::Blue],
+            },
+            ThemeName::HighContrast => Self {
+                mono: false,
+                accent: Color::LightCyan,
+                accent_alt: Color::LightMagenta,
+                text: Color::White,
+                muted: Color::Gray,
+                ok: Color::LightGreen,
+                warn: Color::LightYellow,
+                alert: Color::LightRed,
+                error: Color::LightRed,
+                highlight: Color::LightYellow,
+                series: [Color::LightRed, Color::LightGreen, Color::LightCyan],
+            },
+            ThemeName::Mono => Self {
+                mono: true,
+                accent: Color::Reset,
+                accent_alt: Color::Reset,
+                text: Color::Reset,
+                muted: Color::Reset,
+                ok: Color::Reset,
+                warn: Color::Reset,
+                alert: Color::Reset,
+                error: Color::Reset,
+                highlight: Color::Reset,
+                series: [Color::Reset; 3],
+            },
+        }
+    }
+
+    pub fn fg(&self, color: Color) -> Style {
+        Style::default().fg(color)
+    }
+
+    /// Bold label style (data rows, headers).
+    pub fn label(&self) -> Style {
+        self.fg(self.accent).add_modifier(Modifier::BOLD)
+    }
+
+    /// Selected tab / focused element.
+    pub fn selected(&self) -> Style {
+        if self.mono {
+            Style::default().add_modifier(Modifier::REVERSED | Modifier::BOLD)
+        } else {
+            self.fg(self.highlight).add_modifier(Modifier::BOLD)
+        }
+    }
+
+    /// Error text — underlined in mono mode so it still stands out.
+    pub fn error_style(&self) -> Style {
+        if self.mono {
+            Style::default().add_modifier(Modifier::BOLD | Modifier::UNDERLINED)
+        } else {
+            self.fg(self.error)
+        }
+    }
+
+    /// Colour for a G magnitude (green → yellow → light red → red).
+    pub fn g_color(&self, g: f64) -> Color {
+        if g >= 3.0 {
+            self.error
+        } else if g >= 2.0 {
+            self.alert
+        } else if g >= 1.0 {
+            self.warn
+        } else {
+            self.ok
+        }
+    }
+
+    /// Chart marker for series `idx`; mono mode varies the glyph so the
+    /// X/Y/Z traces stay distinguishable without colour.
+    pub fn series_marker(&self, idx: usize) -> Marker {
+        if !self.mono {
+            return Marker::Braille;
+        }
+        match idx {
+            0 => Marker::Braille,
+            1 => Marker::Dot,
+            _ => Marker::Block,
+        }
+    }
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Self::from_name(ThemeName::default())
+    }
+}
@@ -0,0 +1,153 @@
+//! View preferences kept between TUI sessions (`ui.view_state_file`).
+//!
+//! A small TOML file holding the selected tab and the visible chart
+//! series, read when the TUI starts and written when it quits.  The tab is
+//! stored by name, so adding or removing sensors doesn't shift it, and the
+//! file can be copied to another device to carry the same view over.
+
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+
+use crate::models::AppState;
+
+/// Tabs after the sensor tabs, in order.
+const FIXED_TABS: [&str; 2] = ["Connections", "Logs"];
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ViewState {
+    /// Sensor name, or `Connections` / `Logs`
+    pub tab: Option<String>,
+    /// X / Y / Z chart traces shown
+    pub chart_series: Option<[bool; 3]>,
+}
+
+impl ViewState {
+    pub fn capture(s: &AppState) -> Self {
+        let names = s.sensor_names();
+        let tab = names
+            .get(s.selected_tab)
+            .map(String::as_str)
+            .or_else(|| FIXED_TABS.get(s.selected_tab - names.len()).copied())
+            .map(String::from);
+        Self {
+            tab,
+            chart_series: Some(s.chart_series),
+        }
+    }
+
+    /// Restore what still applies; a tab that no longer exists is skipped.
+    pub fn apply(&self, s: &mut AppState) {
+        let names = s.sensor_names();
+        if let Some(tab) = &self.tab {
+            let index = names.iter().position(|n| n == tab).or_else(|| {
+                FIXED_TABS
+                    .iter()
+                    .position(|t| t == tab)
+                    .map(|i| names.len() + i)
+            });
+            if let Some(i) = index {
+                s.selected_tab = i;
+            }
+        }
+        if let Some(series) = self.chart_series {
+            s.chart_series = series;
+        }
+    }
+
+    /// `None` when the file doesn't exist yet.  A file that can't be parsed
+    /// is reported and ignored, so a bad edit never keeps the TUI from
+    /// starting.
+    pub fn load(path: &Path) -> Option<Self> {
+        let text = std::fs::read_to_string(path).ok()?;
+        match toml::from_str(&text) {
+            Ok(v) => Some(v),
+            Err(e) => {
+                warn!("Ignoring view state {}: {}", path.display(), e);
+                None
+            }
+        }
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        if let Some(dir) = path.parent().filter(|d| !d.as_os_str().is_empty()) {
+            std::fs::create_dir_all(dir)
+                .with_context(|| format!("Failed to create {}", dir.display()))?;
+        }
+        let text = toml::to_string(self)?;
+        std::fs::write(path, text).with_context(|| format!("Failed to write {}", path.display()))
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Unit tests
+// ---------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{CalibrationState, SensorStatus};
+
+    fn state(sensors: &[&str]) -> AppState {
+        let mut s = AppState::new(String::new(), false, 10);
+        for name in sensors {
+            s.sensor_statuses.insert(
+                name.to_string(),
+                SensorStatus {
+                    name: name.to_string(),
+                    driver: "synthetic".into(),
+                    connection_display: String::new(),
+                    group: None,
+                    enabled: true,
+                    connected: false,
+                    last_error: None,
+                    stuck: Vec::new(),
+                    calibration: CalibrationState::Idle,
+                    fields: Vec::new(),
+                },
+            );
+        }
+        s
+    }
+
+    #[test]
+    fn test_round_trip_follows_tab_by_name() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("state/view.toml");
+
+        let mut s = state(&["Front IMU", "Rear IMU"]);
+        s.selected_tab = 1;
+        s.chart_series = [true, false, true];
+        ViewState::capture(&s).save(&path).unwrap();
+
+        // A sensor added before it shifts the index, not the selection
+        let mut s = state(&["Cabin", "Front IMU", "Rear IMU"]);
+        ViewState::load(&path).unwrap().apply(&mut s);
+        assert_eq!(s.selected_tab, 2);
+        assert_eq!(s.chart_series, [true, false, true]);
+
+        s.selected_tab = 4;
+        assert_eq!(ViewState::capture(&s).tab.as_deref(), Some("Logs"));
+    }
+
+    #[test]
+    fn test_missing_tab_and_bad_file_are_ignored() {
+        let mut s = state(&["IMU"]);
+        ViewState {
+            tab: Some("Gone".into()),
+            chart_series: None,
+        }
+        .apply(&mut s);
+        assert_eq!(s.selected_tab, 0);
+        assert_eq!(s.chart_series, [true; 3]);
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("view.toml");
+        assert_eq!(ViewState::load(&path), None);
+        std::fs::write(&path, "tab = [").unwrap();
+        assert_eq!(ViewState::load(&path), None);
+    }
+}
@@ -1,57 +1,76 @@
 //! Shared widget renderers.
 
 use ratatui::Frame;
-use ratatui::layout::Rect;
-use ratatui::style::{Color, Modifier, Style};
+use ratatui::layout::{Alignment, Constraint, Layout, Rect};
+use ratatui::style::{Modifier, Style};
 use ratatui::text::{Line, Span};
-use ratatui::widgets::{Block, Borders, List, ListItem, Paragraph};
+use ratatui::widgets::{Block, Borders, List, ListItem, Paragraph, Wrap};
+
+use crate::alerts::Alert;
+use crate::laps::{LapStatus, format_lap};
+use crate::models::StatusLevel;
+use crate::recorder::{RecordingInfo, human_bytes};
+use crate::tui::snapshot::StateSnapshot;
+use crate::tui::theme::Theme;
 
 // ---------------------------------------------------------------------------
 // Header
 // ---------------------------------------------------------------------------
 
-pub fn render_header(frame: &mut Frame, area: Rect, version: &str) {
+pub fn render_header(frame: &mut Frame, area: Rect, version: &str, theme: &Theme) {
     let block = Block::default()
         .borders(Borders::ALL)
-        .style(Style::default().fg(Color::Cyan));
+        .style(theme.fg(theme.accent));
     let title = Paragraph::new(Line::from(vec![
+        Span::styled(format!("  sensors-to-mqtt v{}  ", version), theme.label()),
         Span::styled(
-            format!("  sensors-to-mqtt v{}  ", version),
-            Style::default()
-                .fg(Color::Cyan)
-                .add_modifier(Modifier::BOLD),
-        ),
-        Span::styled(
-            " ← → or 1-9: switch tabs   q: quit",
-            Style::default().fg(Color::DarkGray),
+            " ← → or 1-9: switch tabs   r: recalibrate   g: group on/off   e: export csv   R: record   p: publishing   X: reset extremes   x/y/z: chart axes   [ ]: status history   a: acknowledge alerts   q: quit",
+            theme.fg(theme.muted),
         ),
     ]))
     .block(block);
     frame.render_widget(title, area);
 }
 
+// ---------------------------------------------------------------------------
+// Terminal too small
+// ---------------------------------------------------------------------------
+
+/// Drawn instead of the layout while the terminal is smaller than `min`
+/// (width, height).  Wraps, so it stays readable down to a few cells.
+pub fn render_too_small(frame: &mut Frame, area: Rect, min: (u16, u16), theme: &Theme) {
+    let lines = vec![
+        Line::from(Span::styled("Terminal too small", theme.fg(theme.warn))),
+        Line::from(Span::styled(
+            format!("{}×{}, need {}×{}", area.width, area.height, min.0, min.1),
+            theme.fg(theme.muted),
+        )),
+    ];
+    // Vertically centred when there is room for it
+    let top = area.height.saturating_sub(lines.len() as u16) / 2;
+    let [_, body] = Layout::vertical([Constraint::Length(top), Constraint::Min(0)]).areas(area);
+    let para = Paragraph::new(lines)
+        .alignment(Alignment::Center)
+        .wrap(Wrap { trim: true });
+    frame.render_widget(para, body);
+}
+
 // ---------------------------------------------------------------------------
 // Log panel (always visible at bottom)
 // ---------------------------------------------------------------------------
 
-pub fn render_log_panel(frame: &mut Frame, area: Rect, logs: &[String]) {
+pub fn render_log_panel(frame: &mut Frame, area: Rect, logs: &[String], theme: &Theme) {
     let block = Block::default()
         .title(" LOG (recent) ")
         .borders(Borders::ALL)
-        .border_style(Style::default().fg(Color::DarkGray));
+        .border_style(theme.fg(theme.muted));
 
     let height = area.height.saturating_sub(2) as usize;
     let start = logs.len().saturating_sub(height);
     let items: Vec<ListItem> = logs[start..]
         .iter()
         .map(|line| {
-            let style = if line.contains("ERROR") {
-                Style::default().fg(Color::Red)
-            } else if line.contains("WARN") {
-                Style::default().fg(Color::Yellow)
-            } else {
-                Style::default().fg(Color::DarkGray)
-            };
+            let style = log_line_style(line, theme);
             ListItem::new(Line::from(Span::styled(line.clone(), style)))
         })
         .collect();
@@ -60,34 +79,173 @@ pub fn render_log_panel(frame: &mut Frame, area: Rect, logs: &[String]) {
     frame.render_widget(list, area);
 }
 
+// ---------------------------------------------------------------------------
+// Status bar (single line, bottom)
+// ---------------------------------------------------------------------------
+
+pub fn render_status_bar(frame: &mut Frame, area: Rect, snap: &StateSnapshot, theme: &Theme) {
+    let area = match snap.recording.as_ref() {
+        Some(rec) => {
+            let [msg_area, rec_area] =
+                Layout::horizontal([Constraint::Min(0), Constraint::Length(24)]).areas(area);
+            render_rec_indicator(frame, rec_area, rec, theme);
+            msg_area
+        }
+        None => area,
+    };
+    let area = match snap.laps.as_ref() {
+        Some(laps) => {
+            let [msg_area, lap_area] =
+                Layout::horizontal([Constraint::Min(0), Constraint::Length(36)]).areas(area);
+            render_lap_indicator(frame, lap_area, laps, theme);
+            msg_area
+        }
+        None => area,
+    };
+
+    // An unacknowledged alert takes the place of the status message
+    let unacked: Vec<&Alert> = snap.alerts.iter().filter(|a| !a.acknowledged).collect();
+    if let Some(alert) = unacked.last() {
+        let mut spans = vec![
+            Span::styled(" ALERT ", alert_style(theme)),
+            Span::styled(
+                format!(" {} ", alert.since.format("%H:%M:%S")),
+                theme.fg(theme.muted),
+            ),
+            Span::styled(alert.text.clone(), theme.error_style()),
+        ];
+        if unacked.len() > 1 {
+            spans.push(Span::styled(
+                format!("  (+{} more)", unacked.len() - 1),
+                theme.fg(theme.warn),
+            ));
+        }
+        spans.push(Span::styled("  a: acknowledge", theme.fg(theme.muted)));
+        frame.render_widget(Paragraph::new(Line::from(spans)), area);
+        return;
+    }
+
+    let mut spans: Vec<Span> = Vec::new();
+    if let Some((pos, total)) = snap.status_position {
+        spans.push(Span::styled(
+            format!("[{}/{}] ", pos, total),
+            theme.fg(theme.muted),
+        ));
+    }
+    if let Some(m) = &snap.status {
+        let style = match m.level {
+            StatusLevel::Info => theme.fg(theme.text),
+            StatusLevel::Warn => theme.fg(theme.warn),
+            StatusLevel::Error => theme.error_style(),
+        };
+        spans.push(Span::styled(
+            format!("{} ", m.timestamp.format("%H:%M:%S")),
+            theme.fg(theme.muted),
+        ));
+        spans.push(Span::styled(m.text.clone(), style));
+    }
+    frame.render_widget(Paragraph::new(Line::from(spans)), area);
+}
+
+/// Flashes at 1 Hz between reversed and plain error text.
+pub fn alert_style(theme: &Theme) -> Style {
+    if chrono::Local::now().timestamp_subsec_millis() < 500 {
+        theme.error_style().add_modifier(Modifier::REVERSED)
+    } else {
+        theme.error_style()
+    }
+}
+
+/// `● REC 01:23  1.4 MB` — the dot blinks at 1 Hz.
+fn render_rec_indicator(frame: &mut Frame, area: Rect, rec: &RecordingInfo, theme: &Theme) {
+    let secs = rec.elapsed.as_secs();
+    let dot = if rec.elapsed.as_millis() % 1000 < 500 {
+        "●"
+    } else {
+        " "
+    };
+    let line = Line::from(vec![
+        Span::styled(format!("{} REC ", dot), theme.error_style()),
+        Span::styled(
+            format!(
+                "{:02}:{:02}  {}",
+                secs / 60,
+                secs % 60,
+                human_bytes(rec.bytes)
+            ),
+            theme.fg(theme.text),
+        ),
+    ]);
+    frame.render_widget(Paragraph::new(line).alignment(Alignment::Right), area);
+}
+
+/// `LAP 3  1:02.345  best 0:58.901` — or a hint until the line is crossed.
+fn render_lap_indicator(frame: &mut Frame, area: Rect, laps: &LapStatus, theme: &Theme) {
+    let line = match laps.current(chrono::Utc::now()) {
+        Some(current) => {
+            let mut spans = vec![
+                Span::styled(format!("LAP {} ", laps.lap), theme.label()),
+                Span::styled(format_lap(current), theme.fg(theme.text)),
+            ];
+            if let Some(best) = laps.best {
+                spans.push(Span::styled(
+                    format!("  best {}", format_lap(best)),
+                    theme.fg(theme.muted),
+                ));
+            }
+            Line::from(spans)
+        }
+        None => Line::from(Span::styled(
+            "LAP -  waiting for start line",
+            theme.fg(theme.muted),
+        )),
+    };
+    frame.render_widget(Paragraph::new(line).alignment(Alignment::Right), area);
+}
+
 // ---------------------------------------------------------------------------
 // Helpers re-exported to tab renderers
 // ---------------------------------------------------------------------------
 
-pub fn section_line(title: &str) -> Line<'static> {
+/// Colour a tracing line by its level.
+fn log_line_style(line: &str, theme: &Theme) -> Style {
+    if line.contains("ERROR") {
+        theme.error_style()
+    } else if line.contains("WARN") {
+        theme.fg(theme.warn)
+    } else {
+        theme.fg(theme.muted)
+    }
+}
+
+pub fn section_line(title: &str, theme: &Theme) -> Line<'static> {
     Line::from(Span::styled(
         format!("── {} ──", title),
-        Style::default().fg(Color::DarkGray),
+        theme.fg(theme.muted),
     ))
 }
 
-pub fn data_row(label: &str, value: String) -> Line<'static> {
+pub fn data_row(label: &str, value: String, theme: &Theme) -> Line<'static> {
     Line::from(vec![
-        Span::styled(
-            format!("{:<14}", label),
-            Style::default()
-                .fg(Color::Cyan)
-                .add_modifier(Modifier::BOLD),
-        ),
+        Span::styled(format!("{:<14}", label), theme.label()),
         Span::raw(" "),
-        Span::styled(value, Style::default().fg(Color::White)),
+        Span::styled(value, theme.fg(theme.text)),
     ])
 }
 
-pub fn status_dot(connected: bool) -> Span<'static> {
+/// Horizontal bar `width` cells wide, `fraction` of it filled.
+pub fn progress_bar(fraction: f64, width: usize, theme: &Theme) -> Span<'static> {
+    let fill = ((fraction.clamp(0.0, 1.0) * width as f64).round() as usize).min(width);
+    Span::styled(
+        "█".repeat(fill) + &"░".repeat(width - fill),
+        theme.fg(theme.ok),
+    )
+}
+
+pub fn status_dot(connected: bool, theme: &Theme) -> Span<'static> {
     if connected {
-        Span::styled("● ONLINE ", Style::default().fg(Color::Green))
+        Span::styled("● ONLINE ", theme.fg(theme.ok))
     } else {
-        Span::styled("○ OFFLINE", Style::default().fg(Color::Red))
+        Span::styled("○ OFFLINE", theme.error_style())
     }
 }
@@ -0,0 +1,313 @@
+//! Vibration severity: windowed RMS, peak-to-peak and crest factor.
+//!
+//! The standard condition-monitoring figures for one IMU's three axes.
+//! Readings are collected over `window_secs`; at the end of each window
+//! the metrics are computed, added to every following reading as
+//! `vibration_rms_x`, `vibration_p2p_x`, `vibration_crest_x` (and Y / Z),
+//! and returned once so the service can publish them on their own topic at
+//! the window rate rather than the sample rate.
+//!
+//! Feed it high-passed acceleration (the MPU6500's `vibration_*` channels):
+//! on raw acceleration gravity dominates the RMS.  Crest factor is peak
+//! over RMS; a rising value with a steady RMS points to impacts, such as a
+//! damaged bearing, rather than steady imbalance.
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+
+use crate::config::VibrationMetricsConfig;
+use crate::sensors::{FieldDescriptor, Quality, SensorData, VizType};
+
+/// Readings further apart than this restart the window.
+const MAX_STEP_SECS: f64 = 0.5;
+
+const AXES: [&str; 3] = ["x", "y", "z"];
+
+const FIELDS: &[FieldDescriptor] = &[
+    FieldDescriptor {
+        key: "vibration_rms_x",
+        label: "Vib RMS X",
+        viz: VizType::GForce,
+        range: None,
+        group: Some("VIBRATION SEVERITY"),
+    },
+    FieldDescriptor {
+        key: "vibration_rms_y",
+        label: "Vib RMS Y",
+        viz: VizType::GForce,
+        range: None,
+        group: None,
+    },
+    FieldDescriptor {
+        key: "vibration_rms_z",
+        label: "Vib RMS Z",
+        viz: VizType::GForce,
+        range: None,
+        group: None,
+    },
+    FieldDescriptor {
+        key: "vibration_p2p_x",
+        label: "Vib P-P X",
+        viz: VizType::GForce,
+        range: None,
+        group: None,
+    },
+    FieldDescriptor {
+        key: "vibration_p2p_y",
+        label: "Vib P-P Y",
+        viz: VizType::GForce,
+        range: None,
+        group: None,
+    },
+    FieldDescriptor {
+        key: "vibration_p2p_z",
+        label: "Vib P-P Z",
+        viz: VizType::GForce,
+        range: None,
+        group: None,
+    },
+    FieldDescriptor {
+        key: "vibration_crest_x",
+        label: "Crest X",
+        viz: VizType::Numeric { unit: "" },
+        range: None,
+        group: None,
+    },
+    FieldDescriptor {
+        key: "vibration_crest_y",
+        label: "Crest Y",
+        viz: VizType::Numeric { unit: "" },
+        range: None,
+        group: None,
+    },
+    FieldDescriptor {
+        key: "vibration_crest_z",
+        label: "Crest Z",
+        viz: VizType::Numeric { unit: "" },
+        range: None,
+        group: None,
+    },
+];
+
+/// One axis over one window.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize)]
+pub struct AxisMetrics {
+    pub rms: f64,
+    pub peak_to_peak: f64,
+    /// Peak over RMS; `None` when the axis was perfectly still
+    pub crest_factor: Option<f64>,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Window {
+    n: u64,
+    sum_sq: f64,
+    min: f64,
+    max: f64,
+}
+
+impl Default for Window {
+    fn default() -> Self {
+        Self {
+            n: 0,
+            sum_sq: 0.0,
+            min: f64::INFINITY,
+            max: f64::NEG_INFINITY,
+        }
+    }
+}
+
+impl Window {
+    fn push(&mut self, v: f64) {
+        self.n += 1;
+        self.sum_sq += v * v;
+        self.min = self.min.min(v);
+        self.max = self.max.max(v);
+    }
+
+    fn metrics(&self) -> AxisMetrics {
+        let rms = (self.sum_sq / self.n.max(1) as f64).sqrt();
+        let peak = self.max.abs().max(self.min.abs());
+        AxisMetrics {
+            rms,
+            peak_to_peak: self.max - self.min,
+            crest_factor: (rms > 0.0).then(|| peak / rms),
+        }
+    }
+}
+
+pub struct VibrationMonitor {
+    cfg: VibrationMetricsConfig,
+    window_start: Option<DateTime<Utc>>,
+    last: Option<DateTime<Utc>>,
+    axes: [Window; 3],
+    /// Metrics from the last complete window
+    latest: Option<[AxisMetrics; 3]>,
+}
+
+impl VibrationMonitor {
+    pub fn new(cfg: VibrationMetricsConfig) -> Self {
+        Self {
+            cfg,
+            window_start: None,
+            last: None,
+            axes: [Window::default(); 3],
+            latest: None,
+        }
+    }
+
+    /// Descriptors for the channels [`process`](Self::process) adds.
+    pub fn field_descriptors() -> &'static [FieldDescriptor] {
+        FIELDS
+    }
+
+    pub fn window_secs(&self) -> f64 {
+        self.cfg.window_secs
+    }
+
+    /// Update from one reading and append the metric channels to it.
+    /// Returns the metrics when this reading completes a window.  Readings
+    /// without the configured channels are left alone.
+    pub fn process(&mut self, data: &mut SensorData) -> Option<[AxisMetrics; 3]> {
+        let field = |k: &String| data.fields.get(k.as_str()).copied();
+        let (Some(x), Some(y), Some(z)) = (
+            field(&self.cfg.accel_fields[0]),
+            field(&self.cfg.accel_fields[1]),
+            field(&self.cfg.accel_fields[2]),
+        ) else {
+            return None;
+        };
+
+        let step = self
+            .last
+            .map(|t| (data.timestamp - t).num_microseconds().unwrap_or(0) as f64 / 1e6);
+        self.last = Some(data.timestamp);
+        if !step.is_some_and(|dt| dt > 0.0 && dt <= MAX_STEP_SECS) {
+            self.restart(data.timestamp);
+        }
+
+        for (window, v) in self.axes.iter_mut().zip([x, y, z]) {
+            window.push(v);
+        }
+        let start = *self.window_start.get_or_insert(data.timestamp);
+        let elapsed = (data.timestamp - start).num_milliseconds() as f64 / 1000.0;
+        let report = (elapsed >= self.cfg.window_secs).then(|| {
+            let metrics = self.axes.map(|w| w.metrics());
+            self.latest = Some(metrics);
+            self.restart(data.timestamp);
+            metrics
+        });
+
+        for (i, axis) in AXES.iter().enumerate() {
+            let m = self.latest.map(|l| l[i]).unwrap_or_default();
+            for (key, v) in [
+                ("rms", m.rms),
+                ("p2p", m.peak_to_peak),
+                ("crest", m.crest_factor.unwrap_or(0.0)),
+            ] {
+                let key = format!("vibration_{}_{}", key, axis);
+                data.fields.insert(key.clone().into(), v);
+                if self.latest.is_none() {
+                    data.set_quality(key, Quality::Invalid);
+                }
+            }
+        }
+        report
+    }
+
+    fn restart(&mut self, at: DateTime<Utc>) {
+        self.window_start = Some(at);
+        self.axes = [Window::default(); 3];
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Unit tests
+// ---------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+
+    fn monitor() -> VibrationMonitor {
+        VibrationMonitor::new(VibrationMetricsConfig {
+            enabled: true,
+            sensor: "IMU".into(),
+            ..Default::default()
+        })
+    }
+
+    /// `secs` of readings at 200 Hz from `t0`, with `signal(t)` on X and
+    /// nothing on Y / Z.  Returns the reports and the last reading.
+    fn run(
+        m: &mut VibrationMonitor,
+        t0: DateTime<Utc>,
+        secs: f64,
+        signal: impl Fn(f64) -> f64,
+    ) -> (Vec<[AxisMetrics; 3]>, SensorData) {
+        let mut reports = Vec::new();
+        let mut last = SensorData::default();
+        for i in 0..(secs * 200.0) as i64 {
+            let t = i as f64 / 200.0;
+            let mut d = SensorData::new(
+                [
+                    ("vibration_x", signal(t)),
+                    ("vibration_y", 0.0),
+                    ("vibration_z", 0.0),
+                ]
+                .into_iter()
+                .map(|(k, v)| (k.into(), v))
+                .collect(),
+            );
+            d.timestamp = t0 + Duration::microseconds(i * 5000);
+            reports.extend(m.process(&mut d));
+            last = d;
+        }
+        (reports, last)
+    }
+
+    #[test]
+    fn test_sine_metrics() {
+        let mut m = monitor();
+        // 0.2 g at 25 Hz: RMS 0.2/√2, peak-to-peak 0.4, crest √2
+        let (reports, last) = run(&mut m, Utc::now(), 2.5, |t| {
+            0.2 * (2.0 * std::f64::consts::PI * 25.0 * t).sin()
+        });
+        assert_eq!(reports.len(), 2);
+        let x = reports[1][0];
+        assert!((x.rms - 0.2 / 2f64.sqrt()).abs() < 1e-3, "{:?}", x);
+        assert!((x.peak_to_peak - 0.4).abs() < 1e-3, "{:?}", x);
+        assert!(
+            (x.crest_factor.unwrap() - 2f64.sqrt()).abs() < 1e-2,
+            "{:?}",
+            x
+        );
+        assert_eq!(reports[1][1].crest_factor, None);
+        assert_eq!(last.fields["vibration_rms_x"], x.rms);
+        assert_eq!(last.quality("vibration_rms_x"), Quality::Good);
+    }
+
+    #[test]
+    fn test_impacts_raise_crest_factor() {
+        let mut m = monitor();
+        let t0 = Utc::now();
+        let (_, first) = run(&mut m, t0, 0.5, |_| 0.0);
+        assert_eq!(first.quality("vibration_crest_x"), Quality::Invalid);
+
+        // One sharp spike per 100 ms on top of light vibration
+        let (reports, _) = run(&mut m, t0 + Duration::seconds(10), 1.1, |t| {
+            let spike = if (t * 200.0).round() as i64 % 20 == 0 {
+                1.0
+            } else {
+                0.0
+            };
+            spike + 0.05 * (2.0 * std::f64::consts::PI * 25.0 * t).sin()
+        });
+        assert!(
+            reports[0][0].crest_factor.unwrap() > 3.0,
+            "{:?}",
+            reports[0]
+        );
+    }
+}
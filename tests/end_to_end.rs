@@ -0,0 +1,356 @@
+//! End-to-end tests against a scripted I2C device.
+//!
+//! `MockI2c` stands in for the chip: a register map that the test sets up
+//! and changes while the driver runs.  Published messages are captured from
+//! an offline `MqttHandle` instead of a broker, so the whole path from
+//! register bytes to MQTT payloads runs without hardware.
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use anyhow::Result;
+use serde_json::Value;
+use tokio_util::sync::CancellationToken;
+
+use sensors_to_mqtt::config::{
+    ConnectionConfig, I2cConnectionConfig, MqttConfig, SamplingConfig, SensorConfig,
+};
+use sensors_to_mqtt::models::AppState;
+use sensors_to_mqtt::mqtt_handler::{MqttHandle, PublishMsg};
+use sensors_to_mqtt::payload::{Json, PayloadSerializer, Reading};
+use sensors_to_mqtt::sensors::{Sensor, SensorData};
+use sensors_to_mqtt::sensors::i2c::mpu6500::{MPU6500, Variant};
+use sensors_to_mqtt::service::{SensorProcessing, register_sensors, spawn_sensor_task};
+use sensors_to_mqtt::transport::{I2cBus, SharedI2c};
+
+// ---------------------------------------------------------------------------
+// Mock device
+// ---------------------------------------------------------------------------
+
+const WHO_AM_I: u8 = 0x75;
+const ACCEL_XOUT_H: u8 = 0x3B;
+const GYRO_XOUT_H: u8 = 0x43;
+
+/// Register-mapped I2C device.  A write's first byte sets the register
+/// pointer and any further bytes are stored from there; reads return
+/// consecutive registers.  Clones share the registers, so a test keeps one
+/// to script the device while the driver owns another.
+#[derive(Clone, Default)]
+struct MockI2c {
+    regs: Arc<Mutex<Vec<u8>>>,
+    writes: Arc<Mutex<Vec<Vec<u8>>>>,
+    pointer: u8,
+}
+
+impl MockI2c {
+    /// An MPU6500 lying level and still: 1 g on Z at ±16 g.
+    fn mpu6500() -> Self {
+        let mock = Self {
+            regs: Arc::new(Mutex::new(vec![0; 256])),
+            ..Default::default()
+        };
+        mock.set(WHO_AM_I, &[0x70]);
+        mock.set_words(ACCEL_XOUT_H, &[0, 0, 2048]);
+        mock
+    }
+
+    fn set(&self, reg: u8, bytes: &[u8]) {
+        let start = reg as usize;
+        self.regs.lock().unwrap()[start..start + bytes.len()].copy_from_slice(bytes);
+    }
+
+    /// Store big-endian 16-bit words from `reg` on.
+    fn set_words(&self, reg: u8, words: &[i16]) {
+        let bytes: Vec<u8> = words.iter().flat_map(|w| w.to_be_bytes()).collect();
+        self.set(reg, &bytes);
+    }
+
+    fn writes(&self) -> Vec<Vec<u8>> {
+        self.writes.lock().unwrap().clone()
+    }
+}
+
+impl I2cBus for MockI2c {
+    fn write(&mut self, _addr: u8, data: &[u8]) -> Result<()> {
+        self.writes.lock().unwrap().push(data.to_vec());
+        if let Some((&reg, value)) = data.split_first() {
+            self.pointer = reg;
+            self.set(reg, value);
+        }
+        Ok(())
+    }
+
+    fn read(&mut self, _addr: u8, buf: &mut [u8]) -> Result<()> {
+        let start = self.pointer as usize;
+        buf.copy_from_slice(&self.regs.lock().unwrap()[start..start + buf.len()]);
+        Ok(())
+    }
+
+    fn write_read(&mut self, addr: u8, write: &[u8], read: &mut [u8]) -> Result<()> {
+        self.pointer = write[0];
+        self.read(addr, read)
+    }
+}
+
+fn mpu6500_config(name: &str, device: &str) -> SensorConfig {
+    SensorConfig {
+        name: name.to_string(),
+        enabled: true,
+        required: true,
+        init_retries: 0,
+        priority: 0,
+        group: None,
+        topic_group: None,
+        tags: Default::default(),
+        channel_map: Default::default(),
+        driver: "mpu6500".to_string(),
+        connection: ConnectionConfig::I2c(I2cConnectionConfig {
+            device: device.to_string(),
+            address: 0x68,
+            bus: None,
+        }),
+        settings: None,
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Allocation counting
+// ---------------------------------------------------------------------------
+
+thread_local! {
+    static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+}
+
+/// The system allocator, counting allocations per thread so tests running
+/// alongside don't show up in each other's counts.
+struct CountingAlloc;
+
+unsafe impl GlobalAlloc for CountingAlloc {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let _ = ALLOCATIONS.try_with(|n| n.set(n.get() + 1));
+        unsafe { System.alloc(layout) }
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        unsafe { System.dealloc(ptr, layout) }
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        let _ = ALLOCATIONS.try_with(|n| n.set(n.get() + 1));
+        unsafe { System.realloc(ptr, layout, new_size) }
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAlloc = CountingAlloc;
+
+fn allocations() -> usize {
+    ALLOCATIONS.with(Cell::get)
+}
+
+// ---------------------------------------------------------------------------
+// Driver math
+// ---------------------------------------------------------------------------
+
+#[test]
+fn test_mpu6500_configures_calibrates_and_scales() {
+    let mock = MockI2c::mpu6500();
+    // Offsets the calibration has to remove
+    mock.set_words(ACCEL_XOUT_H, &[100, -50, 2048 + 30]);
+    mock.set_words(GYRO_XOUT_H, &[20, -10, 5]);
+
+    let cfg = mpu6500_config("IMU", "mock");
+    let mut imu = MPU6500::with_bus(&cfg, Variant::Mpu6500, Box::new(mock.clone()), 0x68).unwrap();
+    let writes = mock.writes();
+    assert!(writes.contains(&vec![0x6B, 0x00]), "woken up");
+    assert!(writes.contains(&vec![0x1C, 0x18]), "±16 g");
+    assert!(writes.contains(&vec![0x1B, 0x18]), "±2000 °/s");
+
+    // +0.5 g on X (2048 LSB/g) and +10 °/s on X (16.4 LSB/°/s)
+    mock.set_words(
+        ACCEL_XOUT_H,
+        &[100 + 1024, -50, 2048 + 30, 0, 20 + 164, -10, 5],
+    );
+    let d = imu.read().unwrap();
+    assert!((d.fields["accel_raw_x"] - 0.5).abs() < 1e-9);
+    assert!(d.fields["accel_raw_y"].abs() < 1e-9);
+    assert!((d.fields["accel_raw_z"] - 1.0).abs() < 1e-9);
+    assert!((d.fields["gyro_x"] - 10.0).abs() < 1e-9);
+    assert_eq!(d.fields["gyro_y"], 0.0);
+    // atan(0.5 / 1.0)
+    assert!((d.fields["tilt_angle"] - 26.565).abs() < 1e-3);
+    assert!((d.fields["temperature"] - 21.0).abs() < 1e-9);
+
+    // A step is smoothed by the Kalman filter, not passed straight through
+    mock.set_words(ACCEL_XOUT_H, &[100 + 2048]);
+    let x = imu.read().unwrap().fields["accel_raw_x"];
+    assert!(x > 0.5 && x < 1.0, "{}", x);
+}
+
+#[test]
+fn test_mpu6500_vibration_skips_gravity_and_smoothing() {
+    let mock = MockI2c::mpu6500();
+    let mut cfg = mpu6500_config("IMU", "mock");
+    cfg.settings = Some(toml::from_str("[vibration]\nenabled = true").unwrap());
+    let mut imu = MPU6500::with_bus(&cfg, Variant::Mpu6500, Box::new(mock.clone()), 0x68).unwrap();
+    assert!(
+        imu.field_descriptors()
+            .iter()
+            .any(|f| f.key == "vibration_z")
+    );
+
+    // Resting on 1 g: nothing to report
+    let d = imu.read().unwrap();
+    assert_eq!(d.fields["vibration_z"], 0.0);
+
+    // A +0.5 g jolt shows on the vibration channel before the Kalman
+    // filtered one catches up
+    mock.set_words(ACCEL_XOUT_H, &[0, 0, 2048 + 1024]);
+    let d = imu.read().unwrap();
+    let (jolt, filtered) = (d.fields["vibration_z"], d.fields["accel_raw_z"] - 1.0);
+    assert!(jolt > 0.4 && jolt <= 0.5, "{}", jolt);
+    assert!(filtered < jolt, "{} vs {}", filtered, jolt);
+}
+
+#[test]
+fn test_mpu6500_legacy_diagnostic_names() {
+    let mut cfg = mpu6500_config("IMU", "mock");
+    let settings = |legacy: bool| {
+        toml::from_str(&format!(
+            "filter_diagnostics = true\nlegacy_accel_fields = {}",
+            legacy
+        ))
+        .unwrap()
+    };
+    cfg.settings = Some(settings(false));
+    let mut imu =
+        MPU6500::with_bus(&cfg, Variant::Mpu6500, Box::new(MockI2c::mpu6500()), 0x68).unwrap();
+    let d = imu.read().unwrap();
+    assert!(d.diagnostics.contains_key("g_force_x_k"));
+    assert!(!d.diagnostics.contains_key("accel_x_k"));
+
+    cfg.settings = Some(settings(true));
+    let mut imu =
+        MPU6500::with_bus(&cfg, Variant::Mpu6500, Box::new(MockI2c::mpu6500()), 0x68).unwrap();
+    let d = imu.read().unwrap();
+    assert!(d.diagnostics.contains_key("accel_x_k"));
+    assert!(d.diagnostics.contains_key("accel_raw_x_k"));
+    assert!(!d.diagnostics.contains_key("g_force_x_k"));
+}
+
+#[test]
+fn test_mpu6500_sample_allocations() {
+    let cfg = mpu6500_config("IMU", "mock");
+    let mut imu =
+        MPU6500::with_bus(&cfg, Variant::Mpu6500, Box::new(MockI2c::mpu6500()), 0x68).unwrap();
+    let first = imu.read().unwrap();
+    let keys: Vec<&str> = first.fields.keys().map(|k| &**k).collect();
+    fn encode(data: &SensorData, keys: &[&str], buf: &mut Vec<u8>) {
+        buf.clear();
+        let reading = Reading {
+            sensor: "IMU",
+            tags: &Default::default(),
+            timestamp: "2025-01-01T00:00:00+00:00",
+            schema_version: Some(1),
+            data,
+            keys,
+            values: None,
+            precision: Some(4),
+        };
+        Json.encode_into(&reading, buf);
+    }
+    // The payload buffer grows to size once
+    let mut buf = Vec::new();
+    encode(&first, &keys, &mut buf);
+
+    const SAMPLES: usize = 100;
+    let before = allocations();
+    for _ in 0..SAMPLES {
+        let data = imu.read().unwrap();
+        encode(&data, &keys, &mut buf);
+    }
+    // A reading's channel map: its entries and its hash table.  Keys are
+    // static and the payload reuses `buf`.
+    let per_sample = (allocations() - before) as f64 / SAMPLES as f64;
+    assert!(per_sample <= 2.0, "{} allocations per sample", per_sample);
+}
+
+// ---------------------------------------------------------------------------
+// Service loop
+// ---------------------------------------------------------------------------
+
+/// Messages captured from `mqtt` until one satisfies `done`.
+async fn capture_until(mqtt: &MqttHandle, done: impl Fn(&PublishMsg) -> bool) -> Vec<PublishMsg> {
+    let mut seen = Vec::new();
+    for _ in 0..200 {
+        let new = mqtt.take_queued();
+        let finished = new.iter().any(&done);
+        seen.extend(new);
+        if finished {
+            return seen;
+        }
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+    panic!("expected message not published; got {:?}", seen);
+}
+
+fn json(msg: &PublishMsg) -> Value {
+    serde_json::from_slice(&msg.payload).unwrap()
+}
+
+#[tokio::test]
+async fn test_service_publishes_mock_imu_readings() {
+    let mock = MockI2c::mpu6500();
+    let _bus = SharedI2c::install("mock-i2c-service", Box::new(mock.clone()));
+    let cfg = mpu6500_config("Mock IMU", "mock-i2c-service");
+
+    let mut app = AppState::new("offline".into(), true, 10);
+    register_sensors(&mut app, std::slice::from_ref(&cfg), 64);
+    let state = Arc::new(tokio::sync::RwLock::new(app));
+    let mqtt = MqttHandle::offline(&MqttConfig::default());
+    let cancel = CancellationToken::new();
+    spawn_sensor_task(
+        cfg,
+        state.clone(),
+        Some(mqtt.clone()),
+        cancel.clone(),
+        "/T".into(),
+        SamplingConfig::default(),
+        SensorProcessing::default(),
+    );
+
+    let topic = |sub: &str| format!("/T/IMU/Mock IMU/{}", sub);
+    let msgs = capture_until(&mqtt, |m| *m.topic == topic("DERIVED")).await;
+    let find = |sub: &str| msgs.iter().find(|m| *m.topic == topic(sub)).unwrap();
+
+    let schema = find("SCHEMA");
+    assert!(schema.retain);
+    assert!(json(schema).to_string().contains("tilt_angle"));
+    assert_eq!(json(schema)["frame"]["id"], "chip_rh_z_up");
+    let info = find("INFO");
+    assert!(info.retain);
+    assert_eq!(json(info)["sensor"], "Mock IMU");
+    let availability = find("AVAILABILITY");
+    assert!(availability.retain);
+    assert_eq!(availability.payload, b"online");
+    // Level and still after calibration
+    assert_eq!(json(find("FILTERED"))["gyro_x"], 0.0);
+    assert_eq!(json(find("DERIVED"))["tilt_angle"], 0.0);
+
+    // Roll the device at 10 °/s; the filtered rate follows
+    mock.set_words(GYRO_XOUT_H, &[164]);
+    capture_until(&mqtt, |m| {
+        *m.topic == topic("FILTERED") && json(m)["gyro_x"].as_f64().unwrap() > 9.0
+    })
+    .await;
+    assert!(state.read().await.sensor_statuses["Mock IMU"].connected);
+
+    // Stopping the service marks the sensor offline
+    cancel.cancel();
+    capture_until(&mqtt, |m| {
+        *m.topic == topic("AVAILABILITY") && m.payload == b"offline"
+    })
+    .await;
+}
@@ -4,8 +4,8 @@
 //! All tests use the `synthetic` driver which has no OS dependencies.
 
 use sensors_to_mqtt::config::{
-    AppConfig, ConnectionConfig, GpioConnectionConfig, I2cConnectionConfig, SensorConfig,
-    SerialConnectionConfig, TcpConnectionConfig,
+    AppConfig, ConnectionConfig, GpioConnectionConfig, I2cConnectionConfig, SamplingConfig,
+    SensorConfig, SerialConnectionConfig, TcpConnectionConfig,
 };
 use sensors_to_mqtt::models::{AppState, SensorHistory};
 use sensors_to_mqtt::sensors::registry::create_sensor;
@@ -18,10 +18,18 @@ fn synthetic_sensor_config(name: &str) -> SensorConfig {
     SensorConfig {
         name: name.to_string(),
         enabled: true,
+        required: true,
+        init_retries: 0,
+        priority: 0,
+        group: None,
+        topic_group: None,
+        tags: Default::default(),
+        channel_map: Default::default(),
         driver: "synthetic".to_string(),
         connection: ConnectionConfig::I2c(I2cConnectionConfig {
             device: "/dev/i2c-1".to_string(),
             address: 0x68,
+            bus: None,
         }),
         settings: None,
     }
@@ -31,6 +39,13 @@ fn tcp_sensor_config(name: &str, driver: &str, host: &str, port: u16, address: u
     SensorConfig {
         name: name.to_string(),
         enabled: true,
+        required: true,
+        init_retries: 0,
+        priority: 0,
+        group: None,
+        topic_group: None,
+        tags: Default::default(),
+        channel_map: Default::default(),
         driver: driver.to_string(),
         connection: ConnectionConfig::Tcp(TcpConnectionConfig {
             host: host.to_string(),
@@ -104,6 +119,217 @@ address = 0x68
     }
 }
 
+#[test]
+fn test_i2c_bus_names_resolve_to_devices() {
+    use sensors_to_mqtt::config::load_configuration;
+    use std::io::Write;
+
+    let config = |bus: &str| {
+        let mut f = tempfile::Builder::new().suffix(".toml").tempfile().unwrap();
+        write!(
+            f,
+            r#"
+[[i2c_buses]]
+name   = "usb"
+device = "/dev/i2c-7"
+
+[[sensors]]
+name   = "imu"
+driver = "mpu6500"
+[sensors.connection]
+type    = "i2c"
+bus     = "{}"
+address = 0x68
+"#,
+            bus
+        )
+        .unwrap();
+        load_configuration(Some(f.path().to_str().unwrap()))
+    };
+
+    let cfg = config("usb").unwrap();
+    match &cfg.sensors[0].connection {
+        ConnectionConfig::I2c(c) => assert_eq!(c.device, "/dev/i2c-7"),
+        other => panic!("expected I2c connection, got {:?}", other),
+    }
+
+    let msg = config("i2c-9").unwrap_err().to_string();
+    assert!(msg.contains("'i2c-9'"), "{}", msg);
+}
+
+#[test]
+fn test_sensor_groups_resolve() {
+    use sensors_to_mqtt::config::load_configuration;
+    use std::io::Write;
+
+    let config = |group: &str| {
+        let mut f = tempfile::Builder::new().suffix(".toml").tempfile().unwrap();
+        write!(
+            f,
+            r#"
+[[groups]]
+name     = "chassis"
+in_topic = true
+
+[[groups]]
+name    = "environment"
+enabled = false
+
+[[sensors]]
+name   = "imu"
+driver = "synthetic"
+group  = "{}"
+[sensors.connection]
+type = "gpio"
+pin  = 17
+"#,
+            group
+        )
+        .unwrap();
+        load_configuration(Some(f.path().to_str().unwrap()))
+    };
+
+    let cfg = config("chassis").unwrap();
+    assert_eq!(cfg.sensors[0].topic_group.as_deref(), Some("chassis"));
+    assert!(!cfg.groups[1].enabled);
+    let cfg = config("environment").unwrap();
+    assert_eq!(cfg.sensors[0].topic_group, None, "not in the topic path");
+
+    let msg = config("engine").unwrap_err().to_string();
+    assert!(msg.contains("'engine'"), "{}", msg);
+}
+
+#[test]
+fn test_derivative_and_integral_config() {
+    use sensors_to_mqtt::config::load_configuration;
+    use sensors_to_mqtt::service::SensorProcessing;
+    use std::io::Write;
+
+    let config = |sensor: &str| {
+        let mut f = tempfile::Builder::new().suffix(".toml").tempfile().unwrap();
+        write!(
+            f,
+            r#"
+[[derivatives]]
+sensor = "{}"
+field  = "accel_x"
+name   = "jerk_x"
+unit   = "G/s"
+
+[[integrals]]
+sensor = "imu"
+field  = "yaw_rate"
+name   = "yaw_angle"
+wrap   = 360.0
+
+[[sensors]]
+name   = "imu"
+driver = "synthetic"
+[sensors.connection]
+type = "gpio"
+pin  = 17
+"#,
+            sensor
+        )
+        .unwrap();
+        load_configuration(Some(f.path().to_str().unwrap()))
+    };
+
+    let cfg = config("imu").unwrap();
+    assert_eq!(cfg.derivatives[0].window_secs, 0.1);
+    assert_eq!(cfg.derivatives[0].scale, 1.0);
+    assert_eq!(cfg.integrals[0].leak_secs, 0.0);
+    let processing = SensorProcessing::for_sensor(&cfg, "imu");
+    assert!(processing.derived_keys().ends_with(&["jerk_x", "yaw_angle"]));
+    assert!(processing.extra_fields().iter().any(|f| f.key == "jerk_x"));
+    assert!(processing.extra_fields().iter().any(|f| f.key == "yaw_angle"));
+
+    let msg = config("gps").unwrap_err().to_string();
+    assert!(msg.contains("'gps'"), "{}", msg);
+}
+
+#[test]
+fn test_alert_config() {
+    use sensors_to_mqtt::config::load_configuration;
+    use sensors_to_mqtt::service::SensorProcessing;
+    use std::io::Write;
+
+    let config = |limits: &str| {
+        let mut f = tempfile::Builder::new().suffix(".toml").tempfile().unwrap();
+        write!(
+            f,
+            r#"
+[[alerts]]
+sensor = "engine"
+field  = "coolant"
+{}
+
+[[sensors]]
+name   = "engine"
+driver = "synthetic"
+[sensors.connection]
+type = "gpio"
+pin  = 17
+"#,
+            limits
+        )
+        .unwrap();
+        load_configuration(Some(f.path().to_str().unwrap()))
+    };
+
+    let cfg = config("above = 110.0").unwrap();
+    assert_eq!(cfg.alerts[0].below, None);
+    assert_eq!(cfg.alerts[0].hysteresis, 0.0);
+    assert_eq!(SensorProcessing::for_sensor(&cfg, "engine").alerts.len(), 1);
+    assert!(SensorProcessing::for_sensor(&cfg, "imu").alerts.is_empty());
+
+    let msg = config("").unwrap_err().to_string();
+    assert!(msg.contains("`above`, `below`"), "{}", msg);
+    let msg = config("below = 40.0\nhysteresis = -1.0").unwrap_err().to_string();
+    assert!(msg.contains("hysteresis"), "{}", msg);
+}
+
+#[test]
+fn test_persisted_remote_settings_apply_at_load() {
+    use sensors_to_mqtt::config::load_configuration;
+
+    let dir = tempfile::tempdir().unwrap();
+    let kept = dir.path().join("remote.toml");
+    std::fs::write(&kept, "[sensors.imu.settings]\nnoise = 0.5\n").unwrap();
+    let path = dir.path().join("config.toml");
+    std::fs::write(
+        &path,
+        format!(
+            r#"
+[remote_config]
+enabled      = true
+persist_file = {:?}
+
+[[sensors]]
+name   = "imu"
+driver = "synthetic"
+[sensors.connection]
+type = "gpio"
+pin  = 17
+[sensors.settings]
+rate_hz = 50
+noise   = 0.02
+"#,
+            kept.to_str().unwrap()
+        ),
+    )
+    .unwrap();
+
+    let cfg = load_configuration(Some(path.to_str().unwrap())).unwrap();
+    let settings = cfg.sensors[0].settings.as_ref().unwrap();
+    assert_eq!(settings["noise"].as_float(), Some(0.5));
+    assert_eq!(settings["rate_hz"].as_integer(), Some(50));
+
+    std::fs::write(&kept, "[sensors.gps.settings]\nnoise = 0.5\n").unwrap();
+    let msg = format!("{:#}", load_configuration(Some(path.to_str().unwrap())).unwrap_err());
+    assert!(msg.contains("no sensor named 'gps'"), "{}", msg);
+}
+
 #[test]
 fn test_tcp_config_address_is_optional() {
     use sensors_to_mqtt::config::load_configuration;
@@ -145,6 +371,13 @@ fn test_open_i2c_rejects_serial_connection() {
     let cfg = SensorConfig {
         name: "bad".to_string(),
         enabled: true,
+        required: true,
+        init_retries: 0,
+        priority: 0,
+        group: None,
+        topic_group: None,
+        tags: Default::default(),
+        channel_map: Default::default(),
         driver: "bmp280".to_string(),
         connection: ConnectionConfig::Serial(SerialConnectionConfig {
             port: "/dev/ttyUSB0".to_string(),
@@ -165,6 +398,13 @@ fn test_open_i2c_rejects_gpio_connection() {
     let cfg = SensorConfig {
         name: "bad".to_string(),
         enabled: true,
+        required: true,
+        init_retries: 0,
+        priority: 0,
+        group: None,
+        topic_group: None,
+        tags: Default::default(),
+        channel_map: Default::default(),
         driver: "bmp280".to_string(),
         connection: ConnectionConfig::Gpio(GpioConnectionConfig {
             pin: 17,
@@ -190,21 +430,41 @@ fn test_open_i2c_tcp_fails_without_server() {
     assert!(result.is_err(), "expected connection failure to unreachable server");
 }
 
+#[test]
+#[cfg(feature = "mpu6500")]
+fn test_mpu6050_driver_is_registered() {
+    use sensors_to_mqtt::sensors::registry::create_sensor;
+
+    // Fails on the connection, not as an unknown driver
+    let cfg = tcp_sensor_config("imu", "mpu6050", "127.0.0.1", 19999, 0x68);
+    let msg = create_sensor(&cfg).err().unwrap().to_string();
+    assert!(!msg.contains("Unknown sensor driver"), "{}", msg);
+}
+
 // ---------------------------------------------------------------------------
 // GPIO button — connection type validation
 // ---------------------------------------------------------------------------
 
 #[test]
+#[cfg(feature = "gpio-button")]
 fn test_gpio_button_rejects_i2c_connection() {
     use sensors_to_mqtt::sensors::gpio::button::GpioButton;
 
     let cfg = SensorConfig {
         name: "bad".to_string(),
         enabled: true,
+        required: true,
+        init_retries: 0,
+        priority: 0,
+        group: None,
+        topic_group: None,
+        tags: Default::default(),
+        channel_map: Default::default(),
         driver: "gpio_button".to_string(),
         connection: ConnectionConfig::I2c(I2cConnectionConfig {
             device: "/dev/i2c-1".to_string(),
             address: 0x40,
+            bus: None,
         }),
         settings: None,
     };
@@ -215,12 +475,20 @@ fn test_gpio_button_rejects_i2c_connection() {
 }
 
 #[test]
+#[cfg(feature = "gpio-button")]
 fn test_gpio_button_rejects_serial_connection() {
     use sensors_to_mqtt::sensors::gpio::button::GpioButton;
 
     let cfg = SensorConfig {
         name: "bad".to_string(),
         enabled: true,
+        required: true,
+        init_retries: 0,
+        priority: 0,
+        group: None,
+        topic_group: None,
+        tags: Default::default(),
+        channel_map: Default::default(),
         driver: "gpio_button".to_string(),
         connection: ConnectionConfig::Serial(SerialConnectionConfig {
             port: "/dev/ttyUSB0".to_string(),
@@ -233,12 +501,20 @@ fn test_gpio_button_rejects_serial_connection() {
 }
 
 #[test]
+#[cfg(feature = "gpio-button")]
 fn test_gpio_button_tcp_fails_without_server() {
     use sensors_to_mqtt::sensors::gpio::button::GpioButton;
 
     let cfg = SensorConfig {
         name: "brake".to_string(),
         enabled: true,
+        required: true,
+        init_retries: 0,
+        priority: 0,
+        group: None,
+        topic_group: None,
+        tags: Default::default(),
+        channel_map: Default::default(),
         driver: "gpio_button".to_string(),
         connection: ConnectionConfig::Tcp(TcpConnectionConfig {
             host: "127.0.0.1".to_string(),
@@ -292,17 +568,115 @@ fn test_synthetic_full_pipeline() {
 fn test_register_sensors_populates_state() {
     use sensors_to_mqtt::service::register_sensors;
 
-    let sensors = vec![
-        synthetic_sensor_config("IMU"),
-        synthetic_sensor_config("ENV"),
-    ];
+    let mut env = synthetic_sensor_config("ENV");
+    env.settings = Some(toml::Value::Table(
+        toml::from_str("history_seconds = 30").unwrap(),
+    ));
+    let sensors = vec![synthetic_sensor_config("IMU"), env];
     let mut state = AppState::new("localhost:1883".into(), true, 100);
-    register_sensors(&mut state, &sensors);
+    register_sensors(&mut state, &sensors, 64);
 
     assert_eq!(state.sensor_statuses.len(), 2);
     assert!(state.sensor_statuses.contains_key("IMU"));
     assert!(state.sensor_statuses.contains_key("ENV"));
-    assert!(state.sensor_history.contains_key("IMU"));
+
+    let imu = &state.sensor_history["IMU"];
+    assert_eq!((imu.capacity, imu.window_secs), (600, 0.0));
+    let env = &state.sensor_history["ENV"];
+    assert_eq!((env.capacity, env.window_secs), (usize::MAX, 30.0));
+    assert_eq!(env.max_bytes, 32 * 1024 * 1024, "half the budget each");
+}
+
+/// Spawn a sensor task for an unknown driver and wait until it gives up.
+/// Returns the sensor's last error, the fatal error and whether the
+/// service was cancelled.
+async fn run_failing_sensor(
+    required: bool,
+    init_retries: u32,
+) -> (Option<String>, Option<String>, bool) {
+    use sensors_to_mqtt::service::{SensorProcessing, register_sensors, spawn_sensor_task};
+    use std::sync::Arc;
+    use tokio_util::sync::CancellationToken;
+
+    let mut cfg = synthetic_sensor_config("missing");
+    cfg.driver = "no_such_driver".to_string();
+    cfg.required = required;
+    cfg.init_retries = init_retries;
+
+    let mut app = AppState::new("disabled".into(), false, 10);
+    register_sensors(&mut app, std::slice::from_ref(&cfg), 64);
+    let state = Arc::new(tokio::sync::RwLock::new(app));
+    let cancel = CancellationToken::new();
+    spawn_sensor_task(
+        cfg,
+        state.clone(),
+        None,
+        cancel.clone(),
+        "/T".into(),
+        SamplingConfig::default(),
+        SensorProcessing::default(),
+    );
+
+    for _ in 0..100 {
+        let s = state.read().await;
+        let last_error = s.sensor_statuses["missing"].last_error.clone();
+        if s.fatal_error.is_some() || (!required && last_error.is_some()) {
+            return (last_error, s.fatal_error.clone(), cancel.is_cancelled());
+        }
+        drop(s);
+        tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+    }
+    panic!("sensor task did not give up");
+}
+
+#[tokio::test]
+async fn test_optional_sensor_failure_marks_offline() {
+    let (last_error, fatal, cancelled) = run_failing_sensor(false, 0).await;
+    assert!(last_error.unwrap().contains("Unknown sensor driver"));
+    assert!(fatal.is_none());
+    assert!(!cancelled);
+}
+
+#[tokio::test]
+async fn test_required_sensor_failure_stops_service_after_retries() {
+    let started = std::time::Instant::now();
+    let (_, fatal, cancelled) = run_failing_sensor(true, 1).await;
+    assert!(fatal.unwrap().contains("'missing'"));
+    assert!(cancelled);
+    // One retry after a 1 s backoff
+    assert!(started.elapsed() >= std::time::Duration::from_secs(1));
+}
+
+#[tokio::test]
+async fn test_sensor_task_ends_after_max_samples() {
+    use sensors_to_mqtt::service::{SensorProcessing, register_sensors, spawn_sensor_task};
+    use std::sync::Arc;
+    use tokio_util::sync::CancellationToken;
+
+    let cfg = synthetic_sensor_config("IMU");
+    let mut app = AppState::new("disabled".into(), false, 10);
+    register_sensors(&mut app, std::slice::from_ref(&cfg), 64);
+    let state = Arc::new(tokio::sync::RwLock::new(app));
+    let sampling = SamplingConfig {
+        interval_ms: 5,
+        max_samples: Some(3),
+        ..SamplingConfig::default()
+    };
+    let task = spawn_sensor_task(
+        cfg,
+        state.clone(),
+        None,
+        CancellationToken::new(),
+        "/T".into(),
+        sampling,
+        SensorProcessing::default(),
+    );
+
+    tokio::time::timeout(std::time::Duration::from_secs(10), task)
+        .await
+        .expect("sensor task should end on its own")
+        .unwrap();
+    assert_eq!(state.read().await.sensor_history["IMU"].timestamps.len(), 3);
 }
 
 // ---------------------------------------------------------------------------
@@ -375,6 +749,30 @@ debounce_ms = 30
     }
 }
 
+#[test]
+fn test_config_load_lap_timer_line() {
+    use sensors_to_mqtt::config::load_configuration;
+    use std::io::Write;
+
+    let mut f = tempfile::Builder::new().suffix(".toml").tempfile().unwrap();
+    write!(
+        f,
+        r#"
+[laps]
+enabled      = true
+gps_sensor   = "GPS"
+start_finish = [[56.9512, 24.1134], [56.9514, 24.1139]]
+"#
+    )
+    .unwrap();
+
+    let cfg = load_configuration(Some(f.path().to_str().unwrap())).unwrap();
+    assert!(cfg.laps.enabled);
+    assert_eq!(cfg.laps.gps_sensor, "GPS");
+    assert_eq!(cfg.laps.start_finish[1], [56.9514, 24.1139]);
+    assert_eq!(cfg.laps.latitude_field, "latitude");
+}
+
 #[test]
 fn test_config_load_tcp_sensors() {
     use sensors_to_mqtt::config::load_configuration;
@@ -567,15 +965,77 @@ fn test_history_empty_stats() {
 
 #[test]
 fn test_history_single_point_stats() {
-    use chrono::Utc;
     use sensors_to_mqtt::sensors::SensorData;
     let mut h = SensorHistory::new(10);
-    h.push(&SensorData {
-        timestamp: Utc::now(),
-        fields: [("x".to_string(), 42.0)].into_iter().collect(),
-    });
+    h.push(&SensorData::new([("x".into(), 42.0)].into_iter().collect()));
     let (min, max, avg) = h.stats("x").unwrap();
     assert!((min - 42.0).abs() < 1e-9);
     assert!((max - 42.0).abs() < 1e-9);
     assert!((avg - 42.0).abs() < 1e-9);
 }
+
+// ---------------------------------------------------------------------------
+// SensorData — optional values and quality flags
+// ---------------------------------------------------------------------------
+
+#[test]
+fn test_sensor_data_json_unchanged_without_extras() {
+    use sensors_to_mqtt::sensors::SensorData;
+    let d = SensorData::new([("x".into(), 1.5)].into_iter().collect());
+    let v = serde_json::to_value(&d).unwrap();
+    let keys: Vec<&String> = v.as_object().unwrap().keys().collect();
+    assert_eq!(keys.len(), 2, "only timestamp + fields: {:?}", keys);
+    assert_eq!(v["fields"]["x"], 1.5);
+}
+
+#[test]
+fn test_sensor_data_values_and_quality_round_trip() {
+    use sensors_to_mqtt::sensors::{ChannelValue, Quality, SensorData};
+    let mut d = SensorData::new([("speed".into(), 12.0)].into_iter().collect());
+    d.set_value("fix", ChannelValue::Text("3d".into()));
+    d.set_value("sats", ChannelValue::Int(9));
+    d.set_quality("speed", Quality::Stale);
+
+    let json = serde_json::to_string(&d).unwrap();
+    let back: SensorData = serde_json::from_str(&json).unwrap();
+    assert_eq!(back.get("fix"), Some(ChannelValue::Text("3d".into())));
+    assert_eq!(back.get("sats"), Some(ChannelValue::Int(9)));
+    assert_eq!(back.get("speed"), Some(ChannelValue::Float(12.0)));
+    assert_eq!(back.quality("speed"), Quality::Stale);
+    assert_eq!(back.quality("fix"), Quality::Good);
+
+    d.set_quality("speed", Quality::Good);
+    assert!(d.quality.is_empty(), "good is the implicit default");
+}
+
+#[test]
+fn test_sensor_data_json_keeps_insertion_order() {
+    use sensors_to_mqtt::sensors::SensorData;
+    let keys = ["zeta", "alpha", "mid"];
+    let d = SensorData::new(keys.iter().map(|k| (k.to_string().into(), 1.0)).collect());
+    let json = serde_json::to_string(&d.fields).unwrap();
+    assert_eq!(json, r#"{"zeta":1.0,"alpha":1.0,"mid":1.0}"#);
+}
+
+// ---------------------------------------------------------------------------
+// MQTT schema message
+// ---------------------------------------------------------------------------
+
+#[test]
+fn test_schema_payload_lists_channels() {
+    use sensors_to_mqtt::service::schema_payload;
+    let sensor = create_sensor(&synthetic_sensor_config("imu")).unwrap();
+    let schema = schema_payload(sensor.as_ref(), sensor.field_descriptors(), &Default::default());
+
+    assert_eq!(schema["sensor"], "imu");
+    assert_eq!(schema["driver"], "synthetic");
+    let channels = schema["channels"].as_array().unwrap();
+    assert_eq!(channels.len(), sensor.field_descriptors().len());
+
+    let gx = channels.iter().find(|c| c["key"] == "g_force_x").unwrap();
+    assert_eq!(gx["unit"], "G");
+    assert_eq!(gx["type"], "number");
+    assert_eq!(gx["range"], serde_json::json!([-4.0, 4.0]));
+    // No axis convention to document
+    assert!(schema.get("frame").is_none());
+}